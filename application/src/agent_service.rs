@@ -1,13 +1,258 @@
+//! Agent mode: turning a goal into a plan of shell steps, then running the
+//! steps with their `check`s for work already done, `when` conditions,
+//! `on_failure` fallbacks and `{{prev.stdout}}` output piping. How a command is confirmed and run is
+//! left to the caller's [`StepExecutor`].
+
+use crate::plan_parsing::parse_plan_steps;
+use crate::read_only::is_read_only;
+use anyhow::anyhow;
+use domain::command_plan::PlanStep;
+use infrastructure::ollama_client::OllamaClient;
 use shared::types::Result;
+use shared::utils::shell_quote;
+use std::future::Future;
+
+/// Stands for the previous step's standard output in a plan step; the
+/// executor substitutes it, so it is not a placeholder to fill in.
+pub const PREV_STDOUT: &str = "{{prev.stdout}}";
 
-pub struct AgentService;
+/// How a command run by the executor ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepOutcome {
+    /// Exited 0; `stdout` is what it printed, when that was captured.
+    Succeeded { stdout: Option<String> },
+    Failed,
+    /// Skipped, declined or blocked.
+    NotRun,
+}
+
+/// Progress of a step, reported to the executor as the plan runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepEvent<'a> {
+    Started,
+    /// The step's `check` passed, so it is skipped as already done.
+    AlreadyDone(&'a str),
+    /// The step's `check` is not on the read-only allowlist, so it is not
+    /// run and the step runs as usual.
+    CheckIgnored(&'a str),
+    ConditionMet(&'a str),
+    Skipped(&'a str),
+    /// The step failed and this fallback runs in its place.
+    FallingBack(&'a str),
+    Finished(&'a StepOutcome),
+}
+
+/// Confirms and runs commands on behalf of [`execute`].
+pub trait StepExecutor {
+    /// Evaluate a `check` or `when` condition: `Some(true)` if it exits 0,
+    /// `None` if it was not run.
+    fn check(&mut self, condition: &str) -> impl Future<Output = Result<Option<bool>>>;
+
+    /// Run a step's command, or its fallback.
+    fn run(&mut self, command: &str) -> impl Future<Output = Result<StepOutcome>>;
+
+    fn event(&mut self, step: usize, event: StepEvent<'_>);
+}
+
+/// Plans multi-step tasks with the model.
+pub struct AgentService {
+    client: OllamaClient,
+    /// Summaries of earlier agent runs in this project, oldest first.
+    history: Vec<String>,
+}
 
 impl AgentService {
-    pub fn new() -> Self {
-        Self
+    pub fn new(client: OllamaClient) -> Self {
+        Self {
+            client,
+            history: Vec::new(),
+        }
+    }
+
+    /// Tell the model what earlier runs already did, so a plan neither
+    /// repeats nor undoes it.
+    pub fn with_history(mut self, history: Vec<String>) -> Self {
+        self.history = history;
+        self
     }
 
-    pub async fn run_agent(&self, _input: &str) -> Result<String> {
-        Ok("Agent not implemented".to_string())
+    /// A plan for `task`; empty if no command list could be parsed.
+    pub async fn plan(&self, task: &str, environment: &str) -> Result<Vec<PlanStep>> {
+        let response = self
+            .client
+            .generate_response(&plan_prompt(task, environment, &self.history))
+            .await?;
+        Ok(parse_plan_steps(&response))
     }
+
+    /// A new plan after the user rejected `previous` with `feedback`.
+    pub async fn revise(
+        &self,
+        task: &str,
+        environment: &str,
+        previous: &[PlanStep],
+        feedback: &str,
+    ) -> Result<Vec<PlanStep>> {
+        let steps = previous
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!("{}. {}", i + 1, s))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = format!(
+            "{}\n\nThe user rejected this plan:\n{}\nTheir feedback: {}\n\
+Revise the plan to address the feedback, keeping the steps that are still right.",
+            plan_prompt(task, environment, &self.history),
+            steps,
+            feedback
+        );
+        let response = self.client.generate_response(&prompt).await?;
+        Ok(parse_plan_steps(&response))
+    }
+}
+
+pub fn plan_prompt(task: &str, environment: &str, history: &[String]) -> String {
+    let history = if history.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "Earlier agent runs in this project already did the following (oldest first); don't redo or contradict it unless the request asks to:\n{}\n\n",
+            history
+                .iter()
+                .map(|run| format!("- {}", run))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    };
+    format!(
+        "You are an assistant that turns a user's goal into a sequence of POSIX shell commands that can be run one-by-one with confirmation in between.\n\
+Environment: {}.\n\
+Constraints:\n\
+- Respond ONLY with a JSON array. Each element must be a complete shell command ready to run, as a string.\n\
+- For a step that should only run if a check succeeds, or that has a fallback if it fails, use an object instead: {{\"command\": \"...\", \"when\": \"check command\", \"on_failure\": \"fallback command\"}} (both keys optional). Prefer this to chaining with || or &&.\n\
+- Give each step that changes the system a \"check\": a read-only command that exits 0 only if the step's work is already done (e.g. {{\"command\": \"sudo apt-get install -y nginx\", \"check\": \"dpkg -s nginx\"}}), so the plan can be run again safely. Use only plain probes such as test, dpkg -s, command -v, id, systemctl is-active or grep -q, without sudo, redirects or $(...); any other check is not run.\n\
+- For a step that may take more than a minute (a large download, a backup, a build), add \"expected_seconds\": roughly how long it should take.\n\
+- No prose, no markdown, no comments. If you cannot produce a valid JSON array, respond with [].\n\
+- Prefer Debian/Ubuntu defaults (apt/apt-get, systemctl) unless otherwise implied.\n\
+- Use real paths; avoid placeholders like /path/to.\n\
+- Keep commands minimal and idempotent (check state before changing it).\n\
+- To use the previous command's output, write {} where it belongs (it is inserted as one quoted word) instead of repeating that command in $(...).\n\n\
+{}User request: {}",
+        environment, PREV_STDOUT, history, task
+    )
+}
+
+/// Run `steps` in order: a step whose `check` passes is already done and
+/// counts as succeeded, a step is skipped unless its `when` check passes,
+/// and its `on_failure` fallback runs if it fails, standing in for its
+/// outcome. Returns each step's outcome.
+pub async fn execute(
+    steps: &[PlanStep],
+    executor: &mut impl StepExecutor,
+) -> Result<Vec<StepOutcome>> {
+    let mut outcomes = Vec::with_capacity(steps.len());
+    // Standard output of the last step, for a `{{prev.stdout}}` in the next
+    let mut previous: Option<String> = None;
+    for (i, step) in steps.iter().enumerate() {
+        executor.event(i, StepEvent::Started);
+        let outcome = run_step(i, step, previous.as_deref(), executor).await?;
+        executor.event(i, StepEvent::Finished(&outcome));
+        previous = match &outcome {
+            StepOutcome::Succeeded { stdout } => stdout.clone(),
+            _ => None,
+        };
+        outcomes.push(outcome);
+    }
+    Ok(outcomes)
+}
+
+async fn run_step(
+    i: usize,
+    step: &PlanStep,
+    previous: Option<&str>,
+    executor: &mut impl StepExecutor,
+) -> Result<StepOutcome> {
+    let step = match substitute_step(step, previous) {
+        Ok(step) => step,
+        Err(e) => {
+            executor.event(i, StepEvent::Skipped(&e.to_string()));
+            return Ok(StepOutcome::NotRun);
+        }
+    };
+    if let Some(check) = &step.check {
+        if !is_read_only(check) {
+            executor.event(i, StepEvent::CheckIgnored(check));
+        } else if executor.check(check).await? == Some(true) {
+            executor.event(i, StepEvent::AlreadyDone(check));
+            return Ok(StepOutcome::Succeeded { stdout: None });
+        }
+    }
+    if let Some(condition) = &step.when {
+        match executor.check(condition).await? {
+            Some(true) => executor.event(i, StepEvent::ConditionMet(condition)),
+            Some(false) => {
+                let reason = format!("condition not met: {}", condition);
+                executor.event(i, StepEvent::Skipped(&reason));
+                return Ok(StepOutcome::NotRun);
+            }
+            None => {
+                executor.event(i, StepEvent::Skipped("the condition was not checked."));
+                return Ok(StepOutcome::NotRun);
+            }
+        }
+    }
+    let outcome = executor.run(&step.command).await?;
+    match (&outcome, &step.on_failure) {
+        (StepOutcome::Failed, Some(fallback)) => {
+            executor.event(i, StepEvent::FallingBack(fallback));
+            executor.run(fallback).await
+        }
+        _ => Ok(outcome),
+    }
+}
+
+pub fn uses_previous(step: &str) -> bool {
+    step.contains(PREV_STDOUT)
+}
+
+/// `step` with [`PREV_STDOUT`] replaced by `previous`, trimmed and quoted as
+/// a single shell word (quotes the model put around the token are dropped).
+/// `previous` is `None` when the last step was skipped, failed or ran
+/// interactively; a step that needs its output then cannot run.
+pub fn substitute(step: &str, previous: Option<&str>) -> Result<String> {
+    if !uses_previous(step) {
+        return Ok(step.to_string());
+    }
+    let previous = previous.ok_or_else(|| {
+        anyhow!(
+            "This step uses {} but the previous step did not succeed with captured output.",
+            PREV_STDOUT
+        )
+    })?;
+    let value = previous.trim();
+    if value.is_empty() {
+        return Err(anyhow!(
+            "This step uses {} but the previous step printed nothing.",
+            PREV_STDOUT
+        ));
+    }
+    let quoted = shell_quote(value);
+    let single = format!("'{}'", PREV_STDOUT);
+    let double = format!("\"{}\"", PREV_STDOUT);
+    Ok(step
+        .replace(&single, &quoted)
+        .replace(&double, &quoted)
+        .replace(PREV_STDOUT, &quoted))
+}
+
+/// [`substitute`] applied to a step's command and its branches.
+pub fn substitute_step(step: &PlanStep, previous: Option<&str>) -> Result<PlanStep> {
+    let branch = |b: &Option<String>| b.as_deref().map(|b| substitute(b, previous)).transpose();
+    Ok(PlanStep {
+        command: substitute(&step.command, previous)?,
+        check: branch(&step.check)?,
+        when: branch(&step.when)?,
+        on_failure: branch(&step.on_failure)?,
+        expected_secs: step.expected_secs,
+    })
 }
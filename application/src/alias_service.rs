@@ -0,0 +1,218 @@
+//! `vibe_cli aliasify`: shell aliases and functions for the commands the
+//! user accepts again and again, proposed by the model from the audit logs
+//! and appended to `aliases.sh` in the config directory.
+
+use infrastructure::audit_log::AuditEntry;
+use infrastructure::ollama_client::OllamaClient;
+use serde::Deserialize;
+use shared::types::Result;
+use shared::utils::shell_quote;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// File in the config directory the aliases are written to.
+pub const ALIASES_FILE: &str = "aliases.sh";
+/// Times a command must have succeeded to be worth an alias.
+pub const DEFAULT_MIN_COUNT: usize = 3;
+/// Commands shown to the model, most frequent first.
+const MAX_COMMANDS: usize = 25;
+/// Comment at the top of a new aliases file.
+const HEADER: &str = "# Aliases and functions written by `vibe_cli aliasify`.\n# Sourced from your shell's startup file.\n";
+
+/// A command that succeeded `count` times.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrequentCommand {
+    pub command: String,
+    pub count: usize,
+}
+
+/// Commands that succeeded at least `min_count` times, most frequent
+/// first. Single words are left out: there is nothing to shorten.
+pub fn frequent_commands(entries: &[AuditEntry], min_count: usize) -> Vec<FrequentCommand> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for entry in entries.iter().filter(|entry| entry.succeeded()) {
+        let command = entry.command.trim();
+        if command.split_whitespace().nth(1).is_some() {
+            *counts.entry(command).or_default() += 1;
+        }
+    }
+    let mut frequent: Vec<FrequentCommand> = counts
+        .into_iter()
+        .filter(|(_, count)| *count >= min_count.max(1))
+        .map(|(command, count)| FrequentCommand {
+            command: command.to_string(),
+            count,
+        })
+        .collect();
+    frequent.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.command.cmp(&b.command)));
+    frequent.truncate(MAX_COMMANDS);
+    frequent
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AliasKind {
+    #[default]
+    Alias,
+    /// A shell function, for commands that take arguments.
+    Function,
+}
+
+/// An alias or function the model proposes.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AliasProposal {
+    pub name: String,
+    #[serde(default)]
+    pub kind: AliasKind,
+    /// One line on what it does.
+    #[serde(default)]
+    pub doc: String,
+    /// The aliased command, or the function body.
+    #[serde(alias = "command")]
+    pub body: String,
+}
+
+impl AliasProposal {
+    /// The definition as written to the aliases file, with its doc as a
+    /// comment.
+    pub fn render(&self) -> String {
+        let doc = match self.doc.is_empty() {
+            true => String::new(),
+            false => format!("# {}\n", self.doc),
+        };
+        match self.kind {
+            AliasKind::Alias => format!("{}alias {}={}\n", doc, self.name, shell_quote(&self.body)),
+            AliasKind::Function => {
+                let body: String = self.body.lines().map(|line| format!("    {}\n", line)).collect();
+                format!("{}{}() {{\n{}}}\n", doc, self.name, body)
+            }
+        }
+    }
+}
+
+/// A name a shell accepts for both aliases and functions.
+fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Names `script` defines with `alias name=`, `name() {` or `function name`.
+pub fn defined_names(script: &str) -> Vec<String> {
+    script
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let name = if let Some(rest) = line.strip_prefix("alias ") {
+                rest.split('=').next()?
+            } else if let Some(rest) = line.strip_prefix("function ") {
+                rest.split(|c: char| c == '(' || c.is_whitespace()).next()?
+            } else {
+                line.split_once("()")?.0
+            };
+            Some(name.trim().to_string()).filter(|name| valid_name(name))
+        })
+        .collect()
+}
+
+/// Whether `name` is an executable on `PATH`, which an alias would hide.
+pub fn shadows_command(name: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| dir.join(name).is_file())
+    })
+}
+
+/// Proposals from the model's JSON reply. Invalid names, empty bodies,
+/// repeated names and names in `taken` are dropped.
+pub fn parse_proposals(response: &str, taken: &[String]) -> Vec<AliasProposal> {
+    let (Some(start), Some(end)) = (response.find('['), response.rfind(']')) else {
+        return Vec::new();
+    };
+    if start >= end {
+        return Vec::new();
+    }
+    let Ok(found) = serde_json::from_str::<Vec<AliasProposal>>(&response[start..=end]) else {
+        return Vec::new();
+    };
+    let mut proposals: Vec<AliasProposal> = Vec::new();
+    for mut proposal in found {
+        proposal.name = proposal.name.trim().to_string();
+        proposal.body = proposal.body.trim().to_string();
+        proposal.doc = proposal.doc.lines().next().unwrap_or("").trim().to_string();
+        let repeated = proposals.iter().any(|p| p.name == proposal.name);
+        if valid_name(&proposal.name)
+            && !proposal.body.is_empty()
+            && !repeated
+            && !taken.contains(&proposal.name)
+        {
+            proposals.push(proposal);
+        }
+    }
+    proposals
+}
+
+/// Append `proposals` to the aliases file at `path`, creating it with a
+/// header.
+pub fn append_aliases(path: &Path, proposals: &[AliasProposal]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut script = std::fs::read_to_string(path).unwrap_or_else(|_| HEADER.to_string());
+    for proposal in proposals {
+        script.push('\n');
+        script.push_str(&proposal.render());
+    }
+    std::fs::write(path, script)?;
+    Ok(())
+}
+
+/// The startup file of `shell` (a path such as `/bin/zsh`) under `home`;
+/// `None` for shells other than bash and zsh.
+pub fn rc_file(shell: &str, home: &Path) -> Option<PathBuf> {
+    match Path::new(shell).file_name()?.to_str()? {
+        "bash" => Some(home.join(".bashrc")),
+        "zsh" => Some(home.join(".zshrc")),
+        _ => None,
+    }
+}
+
+/// The line that sources the aliases file at `path` when it exists.
+pub fn source_line(path: &Path) -> String {
+    let path = shell_quote(&path.to_string_lossy());
+    format!("[ -f {path} ] && . {path}")
+}
+
+pub struct AliasService {
+    client: OllamaClient,
+}
+
+impl AliasService {
+    pub fn new(client: OllamaClient) -> Self {
+        Self { client }
+    }
+
+    /// Aliases or functions for `commands`, avoiding the names in `taken`.
+    pub async fn propose(&self, commands: &[FrequentCommand], taken: &[String]) -> Result<Vec<AliasProposal>> {
+        if commands.is_empty() {
+            return Ok(Vec::new());
+        }
+        let listed = commands
+            .iter()
+            .map(|c| format!("{}x  {}", c.count, c.command))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = format!(
+            "These shell commands were run successfully many times (count, then command):\n{}\n\n\
+Propose short, memorable shell aliases or functions for the ones worth shortening. \
+Use an alias for a fixed command. Use a function when commands differ only in an argument, with \"$1\" or \"$@\" in its place. \
+Do not use these names: {}.\n\
+Respond with only a JSON array of objects with the fields \"name\", \"kind\" (\"alias\" or \"function\"), \"doc\" (one short line on what it does) and \"body\" (the command, or the function body).",
+            listed,
+            if taken.is_empty() { "none".to_string() } else { taken.join(", ") }
+        );
+        let response = self.client.generate_response(&prompt).await?;
+        Ok(parse_proposals(&response, taken))
+    }
+}
@@ -0,0 +1,50 @@
+use infrastructure::ollama_client::OllamaClient;
+use shared::types::Result;
+
+/// Openings of requests for an action, even when phrased as a question
+/// ("how do I free up disk space?").
+const ACTION_OPENINGS: &[&str] = &[
+    "how do i", "how can i", "how to", "how would i", "how should i", "can you", "could you",
+    "please", "show me", "find", "list", "give me",
+];
+/// Openings of questions that want an answer rather than a command.
+const QUESTION_OPENINGS: &[&str] = &[
+    "what", "why", "who", "when", "which", "how does", "how do ", "how is", "how are", "is ",
+    "are ", "does ", "should ", "explain", "define", "describe", "difference between",
+    "meaning of", "tell me about",
+];
+
+/// Whether `input` asks for an answer rather than a shell command: it opens
+/// like a question ("what does errno 111 mean") or ends with `?`, and isn't
+/// a request for an action ("how do I ...", "list ..."). A quick heuristic,
+/// so `vibe ask` remains the way to be sure.
+pub fn looks_like_question(input: &str) -> bool {
+    let input = input.trim().to_lowercase().replace('\'', "");
+    if input.is_empty() || ACTION_OPENINGS.iter().any(|o| input.starts_with(o)) {
+        return false;
+    }
+    input.ends_with('?') || QUESTION_OPENINGS.iter().any(|o| input.starts_with(o))
+}
+
+/// Plain questions answered in prose, with no command to run.
+pub struct AskService {
+    client: OllamaClient,
+}
+
+impl AskService {
+    pub fn new(client: OllamaClient) -> Self {
+        Self { client }
+    }
+
+    /// `environment` describes the user's system, for questions whose answer
+    /// depends on it.
+    pub async fn answer(&self, question: &str, environment: &str) -> Result<String> {
+        let prompt = format!(
+            "Answer this question from a user on a system with: {}.\n\n{}\n\n\
+Answer in a few short paragraphs of plain prose. Use a fenced code block only for code or commands that illustrate the answer; do not reply with just a command.",
+            environment, question
+        );
+        let response = self.client.generate_response(&prompt).await?;
+        Ok(response.trim().to_string())
+    }
+}
@@ -0,0 +1,150 @@
+//! A cached command checked against where it is about to be reused: the
+//! programs it runs are installed, the paths it reads exist, and it was
+//! generated in the same project. A command that no longer fits (an
+//! uninstalled tool, a moved directory, another project) is offered with a
+//! warning or generated afresh instead of being reused silently.
+
+use crate::env_review::{program_words, split_segments};
+use std::path::{Path, PathBuf};
+
+/// Commands whose every argument is something they create.
+const CREATORS: &[&str] = &["mkdir", "touch", "tee"];
+/// Commands whose last argument is where they write.
+const COPIERS: &[&str] = &["cp", "mv", "ln", "rsync", "scp", "install"];
+/// Options followed by a file the command writes, e.g. `curl -o out.html`.
+const OUTPUT_OPTIONS: &[&str] = &["-o", "-O", "--output", "--output-document"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// A program it runs isn't installed here.
+    MissingProgram(String),
+    /// A path it reads doesn't exist.
+    MissingPath(String),
+    /// It was generated in another project, `cached` being that project's
+    /// root.
+    OtherProject { cached: String },
+}
+
+impl Problem {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::MissingProgram(program) => format!("`{}` is not installed", program),
+            Self::MissingPath(path) => format!("{} does not exist", path),
+            Self::OtherProject { cached } => format!("it was generated in {}", cached),
+        }
+    }
+}
+
+/// What to do with a cached command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reuse {
+    /// Nothing against it: offer it as usual.
+    Offer,
+    /// Offer it, but with the problems shown and "no" as the default.
+    Warn,
+    /// It can't work here: generate a new one.
+    Regenerate,
+}
+
+/// How to treat a cached command with `problems`: one that runs a missing
+/// program is regenerated, any other problem is shown as a warning.
+pub fn reuse(problems: &[Problem]) -> Reuse {
+    if problems.iter().any(|p| matches!(p, Problem::MissingProgram(_))) {
+        Reuse::Regenerate
+    } else if problems.is_empty() {
+        Reuse::Offer
+    } else {
+        Reuse::Warn
+    }
+}
+
+/// The scope a command generated in `dir` is cached under: the root of
+/// its project, or `dir` itself outside any project.
+pub fn scope(dir: &Path) -> String {
+    shared::paths::project_root_of(dir).unwrap_or_else(|| dir.display().to_string())
+}
+
+/// The problems with reusing `cmd`, cached under `cached_scope`, in `dir`.
+/// `missing_programs` are the programs it runs that aren't installed.
+/// Entries cached before scopes were recorded aren't held to a project.
+pub fn problems(cmd: &str, cached_scope: Option<&str>, dir: &Path, missing_programs: Vec<String>) -> Vec<Problem> {
+    let mut problems: Vec<Problem> = missing_programs.into_iter().map(Problem::MissingProgram).collect();
+    problems.extend(missing_paths(cmd, dir).into_iter().map(Problem::MissingPath));
+    if let Some(cached) = cached_scope.filter(|cached| *cached != scope(dir)) {
+        problems.push(Problem::OtherProject {
+            cached: cached.to_string(),
+        });
+    }
+    problems
+}
+
+/// The paths `cmd` reads that don't exist when it runs in `dir`. Only
+/// words that are clearly paths are checked: those starting with `/`,
+/// `~/`, `./` or `../`, and relative ones whose first directory exists.
+/// Paths a command creates or writes (`mkdir`, a copy's destination, a
+/// redirection, `-o FILE`) are left out, and so is everything after a
+/// `cd`.
+pub fn missing_paths(cmd: &str, dir: &Path) -> Vec<String> {
+    let mut missing: Vec<String> = Vec::new();
+    for segment in split_segments(cmd) {
+        let words: Vec<&str> = segment.iter().map(String::as_str).collect();
+        let Some((program, args)) = program_words(&words).split_first() else {
+            continue;
+        };
+        let program = program.rsplit('/').next().unwrap_or(program);
+        if matches!(program, "cd" | "pushd") {
+            break;
+        }
+        if CREATORS.contains(&program) {
+            continue;
+        }
+        let mut written = false;
+        for (i, word) in args.iter().enumerate() {
+            if std::mem::take(&mut written) {
+                continue;
+            }
+            if word.starts_with('>') || word.ends_with('>') {
+                // `> out.txt` or `2>` followed by the file
+                written = word.ends_with('>');
+                continue;
+            }
+            if OUTPUT_OPTIONS.contains(word) || (program == "tar" && is_tar_create(word)) {
+                written = true;
+                continue;
+            }
+            if i + 1 == args.len() && COPIERS.contains(&program) {
+                continue;
+            }
+            let word = word.trim_matches(['"', '\'']);
+            if let Some(path) = path_in(word, dir) {
+                if !path.exists() && !missing.iter().any(|m| m == word) {
+                    missing.push(word.to_string());
+                }
+            }
+        }
+    }
+    missing
+}
+
+/// `-czf` and the like: tar writing the archive named next.
+fn is_tar_create(word: &str) -> bool {
+    word.starts_with('-') && !word.starts_with("--") && word.contains('c') && word.ends_with('f')
+}
+
+/// Where `word` points from `dir`, if it is clearly a path.
+fn path_in(word: &str, dir: &Path) -> Option<PathBuf> {
+    if word.starts_with('-') || word.contains("://") || word.contains(['$', '*', '?', '[', '{', '`', '=']) {
+        return None;
+    }
+    if let Some(rest) = word.strip_prefix("~/") {
+        return std::env::var_os("HOME").map(|home| Path::new(&home).join(rest));
+    }
+    if word.starts_with('/') {
+        return Some(PathBuf::from(word));
+    }
+    if word.starts_with("./") || word.starts_with("../") {
+        return Some(dir.join(word));
+    }
+    let (first, _) = word.split_once('/')?;
+    (!first.is_empty() && dir.join(first).is_dir()).then(|| dir.join(word))
+}
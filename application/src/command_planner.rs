@@ -0,0 +1,78 @@
+use crate::agent_service::AgentService;
+use crate::safety_service::SafetyService;
+use domain::command_plan::{CommandPlan, CommandPlanner, PlanStep};
+use infrastructure::ollama_client::OllamaClient;
+use shared::types::Result;
+
+/// Produces [`CommandPlan`]s with the model: the steps come from
+/// [`AgentService`], and every plan carries the safety checks for its
+/// steps.
+pub struct LlmCommandPlanner {
+    agent: AgentService,
+    safety: SafetyService,
+    /// Describes the system the plan will run on, for the prompt.
+    environment: String,
+}
+
+impl LlmCommandPlanner {
+    pub fn new(client: OllamaClient) -> Self {
+        Self {
+            agent: AgentService::new(client),
+            safety: SafetyService::new(),
+            environment: String::new(),
+        }
+    }
+
+    pub fn with_environment(mut self, environment: impl Into<String>) -> Self {
+        self.environment = environment.into();
+        self
+    }
+
+    /// Summaries of earlier agent runs in this project; see
+    /// [`AgentService::with_history`].
+    pub fn with_history(mut self, history: Vec<String>) -> Self {
+        self.agent = self.agent.with_history(history);
+        self
+    }
+
+    pub fn with_safety(mut self, safety: SafetyService) -> Self {
+        self.safety = safety;
+        self
+    }
+
+    /// A new plan for the same task after the user rejected `plan` with
+    /// `feedback`.
+    pub async fn revise(&self, plan: &CommandPlan, feedback: &str) -> Result<CommandPlan> {
+        let steps = self
+            .agent
+            .revise(&plan.description, &self.environment, &plan.steps, feedback)
+            .await?;
+        Ok(self.build(&plan.description, steps))
+    }
+
+    /// `plan` with its steps replaced, e.g. by reviewed ones, and its safety
+    /// checks redone.
+    pub fn with_steps(&self, plan: CommandPlan, steps: Vec<PlanStep>) -> CommandPlan {
+        self.build(&plan.description, steps)
+    }
+
+    fn build(&self, description: &str, steps: Vec<PlanStep>) -> CommandPlan {
+        CommandPlan {
+            // Revisions of a plan for the same task keep its id
+            id: format!("{:x}", md5::compute(description.as_bytes()))[..12].to_string(),
+            description: description.to_string(),
+            safety_checks: self.safety.checks(&steps),
+            steps,
+        }
+    }
+}
+
+impl CommandPlanner for LlmCommandPlanner {
+    /// A plan for the task `input`; without steps if the model's reply had
+    /// no command list.
+    async fn plan_command(&self, input: &str) -> Result<CommandPlan> {
+        let description = input.trim();
+        let steps = self.agent.plan(description, &self.environment).await?;
+        Ok(self.build(description, steps))
+    }
+}
@@ -0,0 +1,222 @@
+//! Common requests answered from a table of curated commands instead of the
+//! model: disk space, memory, the busiest processes, large files, recent
+//! logs. A rule only answers when it accounts for the whole request, so
+//! anything more specific ("large files in /var older than a week") still
+//! goes to the model. Rules of the user's own, kept in `rules.toml` in the
+//! config directory, come first; a command accepted again and again for the
+//! same kind of request is offered as one.
+
+use infrastructure::audit_log::AuditEntry;
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::path::Path;
+
+/// File in the config directory the user's rules are kept in.
+pub const RULES_FILE: &str = "rules.toml";
+/// Times a command must have been accepted for similar requests before a
+/// rule is offered, and again every as many times after a refusal.
+pub const PROMOTE_AFTER: usize = 5;
+/// Comment at the top of a new rules file.
+const HEADER: &str = "# Requests answered without the model; see `vibe_cli rules`.\n\
+# [[rule]]\n\
+# name = \"disk-space\"\n\
+# phrases = [\"disk space\", \"free space\"]\n\
+# command = \"df -h\"\n";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandRule {
+    /// Shown when the rule answers, e.g. `disk-space`.
+    pub name: String,
+    /// Ways of asking for the command, e.g. "disk space"; a phrase matches
+    /// when the request has each of its words.
+    pub phrases: Vec<String>,
+    pub command: String,
+}
+
+impl CommandRule {
+    fn new(name: &str, phrases: &[&str], command: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            phrases: phrases.iter().map(|p| p.to_string()).collect(),
+            command: command.to_string(),
+        }
+    }
+}
+
+/// Words a request may have besides a rule's phrase and still be the same
+/// request.
+const FILLER_WORDS: &[&str] = &[
+    "a", "all", "am", "an", "are", "can", "check", "current", "currently", "display", "do",
+    "does", "find", "for", "get", "give", "here", "how", "i", "in", "is", "left", "list",
+    "machine", "me", "much", "my", "now", "of", "on", "please", "see", "show", "system", "the",
+    "this", "used", "using", "view", "what", "whats", "which", "you",
+];
+
+/// The curated rules, with commands for the platform vibe_cli runs on.
+pub fn builtin_rules() -> Vec<CommandRule> {
+    let macos = cfg!(target_os = "macos");
+    vec![
+        CommandRule::new(
+            "disk-space",
+            &["disk space", "disk usage", "free space", "disk free", "free disk", "space disk"],
+            "df -h",
+        ),
+        CommandRule::new(
+            "memory",
+            &["memory usage", "free memory", "ram usage", "memory", "ram", "free ram"],
+            if macos { "vm_stat" } else { "free -h" },
+        ),
+        CommandRule::new(
+            "top-processes",
+            &[
+                "top processes", "running processes", "processes cpu", "processes memory",
+                "biggest processes", "busiest processes", "heaviest processes",
+            ],
+            if macos {
+                "ps aux -r | head -n 15"
+            } else {
+                "ps aux --sort=-%cpu | head -n 15"
+            },
+        ),
+        CommandRule::new(
+            "large-files",
+            &["large files", "big files", "largest files", "biggest files", "huge files"],
+            "find . -xdev -type f -size +100M -exec ls -lh {} + 2>/dev/null | sort -k5 -rh | head -n 20",
+        ),
+        CommandRule::new(
+            "tail-logs",
+            &["tail logs", "system logs", "recent logs", "latest logs", "tail syslog", "syslog"],
+            if macos {
+                "log show --last 5m | tail -n 50"
+            } else {
+                "journalctl -n 50 --no-pager"
+            },
+        ),
+    ]
+}
+
+/// The request's words, lowercased, without punctuation or a plural `s`.
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '/' && c != '.')
+        .map(|word| word.trim_matches(['\'', '.']).replace('\'', ""))
+        .filter(|word| !word.is_empty())
+        .map(|word| match word.strip_suffix('s') {
+            Some(stem) if stem.len() > 2 && !stem.ends_with('s') => stem.to_string(),
+            _ => word,
+        })
+        .collect()
+}
+
+/// The rule answering `request`: one of its phrases is in the request and
+/// every other word of the request is filler. The longest phrase wins.
+pub fn match_rule<'a>(rules: &'a [CommandRule], request: &str) -> Option<&'a CommandRule> {
+    let request = words(request);
+    let filler = words(&FILLER_WORDS.join(" "));
+    let mut best: Option<(&CommandRule, usize)> = None;
+    for rule in rules {
+        for phrase in &rule.phrases {
+            let phrase = words(phrase);
+            if phrase.is_empty() || !phrase.iter().all(|word| request.contains(word)) {
+                continue;
+            }
+            let covered = request.iter().all(|word| phrase.contains(word) || filler.contains(word));
+            if covered && best.is_none_or(|(_, len)| phrase.len() > len) {
+                best = Some((rule, phrase.len()));
+            }
+        }
+    }
+    best.map(|(rule, _)| rule)
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<CommandRule>,
+}
+
+/// The user's rules in `path`; none if it doesn't exist.
+pub fn load_rules(path: &Path) -> Result<Vec<CommandRule>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let file: RulesFile = toml::from_str(&text)
+        .map_err(|e| anyhow::anyhow!("invalid rules file {}: {}", path.display(), e))?;
+    Ok(file.rule)
+}
+
+/// Write `rules` to `path`, replacing what it held.
+pub fn save_rules(path: &Path, rules: &[CommandRule]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let body = toml::to_string(&RulesFile { rule: rules.to_vec() })?;
+    std::fs::write(path, format!("{}\n{}", HEADER, body))?;
+    Ok(())
+}
+
+/// Create `path` with an explanatory comment if it doesn't exist yet, for
+/// editing by hand.
+pub fn ensure_rules_file(path: &Path) -> Result<()> {
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, HEADER)?;
+    }
+    Ok(())
+}
+
+/// A rule for `command` once it has succeeded for requests `similar` to
+/// `request` a multiple of `min_count` times; those requests become its
+/// phrases.
+pub fn promotion(
+    entries: &[AuditEntry],
+    request: &str,
+    command: &str,
+    min_count: usize,
+    similar: impl Fn(&str, &str) -> bool,
+) -> Option<CommandRule> {
+    let mut phrases: Vec<String> = Vec::new();
+    let mut count = 0;
+    for entry in entries {
+        if entry.mode != "command" || !entry.succeeded() || entry.command.trim() != command.trim() {
+            continue;
+        }
+        if !similar(&entry.prompt, request) {
+            continue;
+        }
+        count += 1;
+        let phrase = entry.prompt.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        if !phrase.is_empty() && !phrases.contains(&phrase) {
+            phrases.push(phrase);
+        }
+    }
+    let min_count = min_count.max(1);
+    if count == 0 || count % min_count != 0 || phrases.is_empty() {
+        return None;
+    }
+    Some(CommandRule {
+        name: rule_name(request),
+        phrases,
+        command: command.trim().to_string(),
+    })
+}
+
+/// A short name from the request's first few words, e.g. `check-disk-space`.
+fn rule_name(request: &str) -> String {
+    let name = request
+        .split_whitespace()
+        .map(|word| word.to_lowercase().replace(|c: char| !c.is_ascii_alphanumeric(), ""))
+        .filter(|word| !word.is_empty())
+        .take(4)
+        .collect::<Vec<_>>()
+        .join("-");
+    if name.is_empty() {
+        "rule".to_string()
+    } else {
+        name
+    }
+}
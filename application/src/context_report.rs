@@ -0,0 +1,161 @@
+//! Which chunks made it into a RAG prompt and why the rest didn't, printed
+//! with `--verbose`: similarity scores, chunks under the minimum score,
+//! chunks left out for diversity or the per-file cap, and chunks dropped to
+//! fit the context budget.
+
+use std::collections::HashMap;
+
+/// Chunks listed per group of left-out chunks.
+const SHOWN_MISSES: usize = 5;
+
+/// A chunk, by [`chunk_label`], and its similarity to the query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkScore {
+    pub label: String,
+    pub score: f32,
+}
+
+/// How retrieval picked its chunks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetrievalStats {
+    /// Chunks compared with the query.
+    pub candidates: usize,
+    pub min_score: f32,
+    /// The picked chunks.
+    pub picked: Vec<ChunkScore>,
+    /// Best first, the chunks under `min_score`.
+    pub below_min_score: Vec<ChunkScore>,
+    /// Best first, chunks good enough but passed over as near-copies of
+    /// picked ones or past the per-file cap.
+    pub not_picked: Vec<ChunkScore>,
+}
+
+impl RetrievalStats {
+    /// Both retrievals' chunks, e.g. of two collections.
+    pub fn merge(&mut self, other: RetrievalStats) {
+        self.candidates += other.candidates;
+        self.picked.extend(other.picked);
+        self.below_min_score.extend(other.below_min_score);
+        self.not_picked.extend(other.not_picked);
+        for list in [&mut self.below_min_score, &mut self.not_picked] {
+            list.sort_by(|a, b| b.score.total_cmp(&a.score));
+        }
+    }
+}
+
+/// A short name for a context chunk: `path @offset` for file chunks, and
+/// what it is for the rest (crate docs, lockfile versions, README, tree).
+pub fn chunk_label(chunk: &str) -> String {
+    let mut lines = chunk.lines();
+    let first = lines.next().unwrap_or("");
+    if let Some(path) = first.strip_prefix("FILE: ") {
+        return match lines.next().and_then(|l| l.strip_prefix("OFFSET: ")) {
+            Some(offset) => format!("{} @{}", path.trim(), offset.trim()),
+            None => path.trim().to_string(),
+        };
+    }
+    if let Some(name) = first.strip_prefix("CRATE DOCS: ") {
+        return format!("crate docs: {}", name.trim());
+    }
+    if first.starts_with("DEPENDENCY VERSIONS") {
+        return "dependency versions (lockfiles)".to_string();
+    }
+    if first.starts_with("DIRECTORY TREE") {
+        return "directory tree".to_string();
+    }
+    let first: String = first.chars().take(60).collect();
+    first.trim().to_string()
+}
+
+/// What went into one prompt's context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextReport {
+    pub stats: RetrievalStats,
+    /// Labels and scores of the chunks in the prompt, in order; no score
+    /// for chunks that weren't ranked (neighbors, docs, earlier context).
+    pub included: Vec<(String, Option<f32>)>,
+    /// Chunks dropped from the end to fit the budget.
+    pub over_budget: Vec<(String, Option<f32>)>,
+    pub used_chars: usize,
+    /// The budget; 0 for none.
+    pub max_chars: usize,
+}
+
+impl ContextReport {
+    /// The report for `before`, the assembled context, cut down to `after`
+    /// to fit `max_chars`.
+    pub fn new(stats: RetrievalStats, before: &[String], after: &[String], max_chars: usize) -> Self {
+        let scores: HashMap<&str, f32> = stats
+            .picked
+            .iter()
+            .map(|chunk| (chunk.label.as_str(), chunk.score))
+            .collect();
+        let scored = |chunk: &String| {
+            let label = chunk_label(chunk);
+            let score = scores.get(label.as_str()).copied();
+            (label, score)
+        };
+        let included = after.iter().map(scored).collect();
+        let over_budget = before.iter().skip(after.len()).map(scored).collect();
+        Self {
+            included,
+            over_budget,
+            used_chars: after.iter().map(|c| c.chars().count()).sum(),
+            max_chars,
+            stats,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let score = |score: &Option<f32>| match score {
+            Some(score) => format!("{:.3}", score),
+            None => "  -  ".to_string(),
+        };
+        let stats = &self.stats;
+        let mut out = String::from("===== context =====\n");
+        out.push_str(&format!(
+            "retrieval: {} candidate chunk(s) compared; {} picked, {} under the minimum score {:.2}, {} passed over as near-copies or past the per-file cap\n",
+            stats.candidates,
+            stats.picked.len(),
+            stats.below_min_score.len(),
+            stats.min_score,
+            stats.not_picked.len()
+        ));
+        let budget = match self.max_chars {
+            0 => "no limit".to_string(),
+            max => format!("of {}", max),
+        };
+        out.push_str(&format!(
+            "in the prompt: {} chunk(s), {} chars ({})\n",
+            self.included.len(),
+            self.used_chars,
+            budget
+        ));
+        for (label, s) in &self.included {
+            out.push_str(&format!("  {}  {}\n", score(s), label));
+        }
+        if !self.over_budget.is_empty() {
+            out.push_str(&format!("dropped for the context budget: {}\n", self.over_budget.len()));
+            for (label, s) in &self.over_budget {
+                out.push_str(&format!("  {}  {}\n", score(s), label));
+            }
+        }
+        for (title, chunks) in [
+            ("passed over", &stats.not_picked),
+            ("closest under the minimum score", &stats.below_min_score),
+        ] {
+            if chunks.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("{}:\n", title));
+            for chunk in chunks.iter().take(SHOWN_MISSES) {
+                out.push_str(&format!("  {:.3}  {}\n", chunk.score, chunk.label));
+            }
+            if chunks.len() > SHOWN_MISSES {
+                out.push_str(&format!("  ... and {} more\n", chunks.len() - SHOWN_MISSES));
+            }
+        }
+        out.push_str("===================\n");
+        out
+    }
+}
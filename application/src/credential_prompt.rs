@@ -0,0 +1,260 @@
+//! Credentials a generated command asks for with a placeholder
+//! (`PGPASSWORD=<password>`, `mysql -p`, `docker login -p TOKEN`). The
+//! placeholder is replaced by a variable reference, and the secret, typed
+//! at run time, reaches the command through that variable or on its stdin,
+//! so it is never part of the command string that is shown, cached or
+//! audited.
+
+use crate::env_review::{looks_like_secret, program_words, split_assignment, split_segments};
+
+/// Variables that hold a secret although their names don't say so.
+const SECRET_VARS: &[&str] = &["MYSQL_PWD", "SSHPASS", "REDISCLI_AUTH"];
+/// MySQL clients, which read the password from `MYSQL_PWD`.
+const MYSQL_PROGRAMS: &[&str] = &[
+    "mysql", "mariadb", "mysqldump", "mysqladmin", "mysqlimport", "mysqlcheck", "mysqlshow",
+];
+/// Programs whose `login` subcommand takes `--password-stdin`.
+const REGISTRY_PROGRAMS: &[&str] = &["docker", "podman", "nerdctl", "helm", "buildah", "skopeo"];
+/// Long flags whose value is a credential, for any program.
+const SECRET_FLAGS: &[&str] = &[
+    "--password", "--passwd", "--pass", "--token", "--auth-token", "--api-key", "--secret",
+];
+/// Placeholder values, once lowercased and stripped of `your`/`my`.
+const PLACEHOLDER_WORDS: &[&str] = &[
+    "password", "passwd", "pass", "pw", "pwd", "secret", "token", "apikey", "api_key", "api-key",
+    "changeme", "redacted",
+];
+
+/// A secret to ask for before the command runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretSlot {
+    /// Variable the secret is passed in.
+    pub var: String,
+    /// What to ask for, e.g. `PGPASSWORD` or `password for docker login`.
+    pub label: String,
+}
+
+/// A command with its credential placeholders replaced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretRewrite {
+    pub command: String,
+    pub slots: Vec<SecretSlot>,
+}
+
+/// Whether `value` stands in for a secret rather than being one:
+/// `<password>`, `YOUR_TOKEN`, `***`, `changeme`, or nothing at all.
+pub fn is_placeholder(value: &str) -> bool {
+    let value = value.trim_matches(['"', '\'']);
+    let bracketed = |open: &str, close: &str| value.starts_with(open) && value.ends_with(close);
+    if value.is_empty() || bracketed("<", ">") || bracketed("[", "]") || bracketed("{{", "}}") {
+        return true;
+    }
+    if value.len() >= 3 && value.chars().all(|c| matches!(c, '*' | 'x' | 'X')) {
+        return true;
+    }
+    let lower = value.to_lowercase();
+    let bare = lower
+        .trim_start_matches("your")
+        .trim_start_matches("my")
+        .trim_start_matches(['_', '-']);
+    PLACEHOLDER_WORDS.contains(&bare)
+        || (value.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+            && ["PASS", "TOKEN", "SECRET", "KEY"].iter().any(|w| value.contains(w)))
+}
+
+fn secret_var(name: &str) -> bool {
+    SECRET_VARS.contains(&name) || looks_like_secret(name, "")
+}
+
+/// A word of `cmd` and where it is.
+struct Word {
+    start: usize,
+    text: String,
+}
+
+impl Word {
+    fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+}
+
+/// [`split_segments`] with the offset of each word in `cmd`; words keep
+/// their quotes, so each is found verbatim after the one before.
+fn located_segments(cmd: &str) -> Vec<Vec<Word>> {
+    let mut cursor = 0;
+    split_segments(cmd)
+        .into_iter()
+        .map(|segment| {
+            segment
+                .into_iter()
+                .map(|text| {
+                    let start = cursor + cmd[cursor..].find(&text).unwrap_or(0);
+                    cursor = start + text.len();
+                    Word { start, text }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+struct Rewriter<'a> {
+    cmd: &'a str,
+    /// Replacements of byte ranges of `cmd`.
+    edits: Vec<(usize, usize, String)>,
+    slots: Vec<SecretSlot>,
+}
+
+impl Rewriter<'_> {
+    fn slot(&mut self, var: &str, label: &str) {
+        if !self.slots.iter().any(|slot| slot.var == var) {
+            self.slots.push(SecretSlot {
+                var: var.to_string(),
+                label: label.to_string(),
+            });
+        }
+    }
+
+    /// A new `VIBE_SECRET_<n>` variable asked for as `label`.
+    fn generic_slot(&mut self, label: &str) -> String {
+        let var = format!("VIBE_SECRET_{}", self.slots.len() + 1);
+        self.slot(&var, label);
+        var
+    }
+
+    fn replace(&mut self, word: &Word, text: String) {
+        self.edits.push((word.start, word.end(), text));
+    }
+
+    /// Drop `word` along with the blanks after it.
+    fn remove(&mut self, word: &Word) {
+        let rest = &self.cmd[word.end()..];
+        let blanks = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+        self.edits.push((word.start, word.end() + blanks, String::new()));
+    }
+
+    fn finish(mut self) -> SecretRewrite {
+        self.edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+        let mut command = self.cmd.to_string();
+        for (start, end, text) in self.edits {
+            command.replace_range(start..end, &text);
+        }
+        SecretRewrite {
+            command,
+            slots: self.slots,
+        }
+    }
+}
+
+/// `cmd` with each credential placeholder turned into a variable reference
+/// (or dropped for clients that read a variable themselves), and the
+/// secrets to ask for. Real values already in the command are left alone.
+pub fn secret_slots(cmd: &str) -> SecretRewrite {
+    let mut rewriter = Rewriter {
+        cmd,
+        edits: Vec::new(),
+        slots: Vec::new(),
+    };
+    for words in located_segments(cmd) {
+        // `NAME=<placeholder>`, before a command or after `export`/`env`.
+        for word in &words {
+            if let Some((name, value)) = split_assignment(&word.text) {
+                if secret_var(name) && is_placeholder(value) {
+                    rewriter.replace(word, format!("{}=\"${}\"", name, name));
+                    rewriter.slot(name, name);
+                }
+            }
+        }
+        let texts: Vec<&str> = words.iter().map(|w| w.text.as_str()).collect();
+        let program_at = words.len() - program_words(&texts).len();
+        if program_at == words.len() {
+            continue;
+        }
+        let program = words[program_at].text.rsplit('/').next().unwrap_or("");
+        let args = &words[program_at + 1..];
+        let login = REGISTRY_PROGRAMS.contains(&program)
+            && args.iter().any(|w| w.text == "login");
+        let mut i = 0;
+        while i < args.len() {
+            let word = &args[i];
+            let next = args.get(i + 1).filter(|next| is_placeholder(&next.text));
+            let (flag, attached) = match word.text.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+                _ => (word.text.as_str(), None),
+            };
+            if MYSQL_PROGRAMS.contains(&program) {
+                let short = flag.strip_prefix("-p").filter(|_| !flag.starts_with("--"));
+                let placeholder = match (flag, short, attached) {
+                    ("--password", _, None) => true,
+                    ("--password", _, Some(value)) => is_placeholder(value),
+                    (_, Some(value), _) => is_placeholder(value),
+                    _ => false,
+                };
+                if placeholder {
+                    rewriter.remove(word);
+                    rewriter.slot("MYSQL_PWD", &format!("password for {}", program));
+                }
+            } else if program == "redis-cli" && flag == "-a" && next.is_some() {
+                rewriter.remove(word);
+                rewriter.remove(&args[i + 1]);
+                rewriter.slot("REDISCLI_AUTH", "password for redis-cli");
+                i += 1;
+            } else if program == "sshpass" && flag == "-p" && next.is_some() {
+                rewriter.replace(word, "-e".to_string());
+                rewriter.remove(&args[i + 1]);
+                rewriter.slot("SSHPASS", "password for sshpass");
+                i += 1;
+            } else if login && matches!(flag, "-p" | "--password") {
+                // `Some(None)` for `--password=<placeholder>`, `Some(word)`
+                // when the placeholder is the next word.
+                let placeholder = match attached {
+                    Some(value) => is_placeholder(value).then_some(None),
+                    None => next.map(Some),
+                };
+                if let Some(value_word) = placeholder {
+                    let var = rewriter.generic_slot(&format!("password for {} login", program));
+                    rewriter.replace(word, "--password-stdin".to_string());
+                    if let Some(value_word) = value_word {
+                        rewriter.remove(value_word);
+                        i += 1;
+                    }
+                    // The secret goes in on stdin; printf is a builtin, so
+                    // it doesn't show up in the process list.
+                    let first = &words[0];
+                    rewriter.edits.push((
+                        first.start,
+                        first.start,
+                        format!("printf '%s\\n' \"${}\" | ", var),
+                    ));
+                }
+            } else if program == "curl" && matches!(flag, "-u" | "--user") {
+                let user = args.get(i + 1).and_then(|next| {
+                    let (user, password) = next.text.trim_matches(['"', '\'']).split_once(':')?;
+                    is_placeholder(password).then(|| (user.to_string(), next))
+                });
+                if let Some((user, value_word)) = user {
+                    let var = rewriter.generic_slot(&format!("password of {} for curl", user));
+                    rewriter.replace(value_word, format!("\"{}:${}\"", user, var));
+                    i += 1;
+                }
+            } else if SECRET_FLAGS.contains(&flag) {
+                let label = format!("{} for {}", flag.trim_start_matches('-'), program);
+                match attached {
+                    Some(value) if is_placeholder(value) => {
+                        let var = rewriter.generic_slot(&label);
+                        rewriter.replace(word, format!("{}=\"${}\"", flag, var));
+                    }
+                    Some(_) => {}
+                    None => {
+                        if let Some(next) = next {
+                            let var = rewriter.generic_slot(&label);
+                            rewriter.replace(next, format!("\"${}\"", var));
+                            i += 1;
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
+    rewriter.finish()
+}
@@ -0,0 +1,130 @@
+//! `--explain --diff`: what a change does, from a unified diff of two files
+//! or of a git revision range, computed locally.
+
+use anyhow::anyhow;
+use infrastructure::ollama_client::OllamaClient;
+use shared::types::Result;
+use similar::TextDiff;
+use std::path::Path;
+use std::process::Command;
+
+/// Characters of diff sent to the model; the rest is cut.
+const MAX_DIFF_CHARS: usize = 24_000;
+/// Identifiers taken from changed lines to look up related definitions.
+const MAX_IDENTIFIERS: usize = 20;
+
+/// Unified diff of `old` and `new` with three lines of context.
+pub fn unified_diff(old_name: &str, old: &str, new_name: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(old_name, new_name)
+        .to_string()
+}
+
+/// Unified diff of two files.
+pub fn diff_files(old: &Path, new: &Path) -> Result<String> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))
+    };
+    Ok(unified_diff(
+        &old.display().to_string(),
+        &read(old)?,
+        &new.display().to_string(),
+        &read(new)?,
+    ))
+}
+
+/// `git diff` of `range` (`A..B`, or `A` for A against the working tree),
+/// limited to `paths` if any are given.
+pub fn diff_revisions(range: &str, paths: &[String]) -> Result<String> {
+    if range.trim().is_empty() || range.starts_with('-') {
+        return Err(anyhow!("invalid revision range '{}'", range));
+    }
+    git_diff(&[range.trim()], paths)
+}
+
+/// `git diff` with `args` before the `--` and `paths` after it.
+pub fn git_diff(args: &[&str], paths: &[String]) -> Result<String> {
+    let output = Command::new("git")
+        .args(["diff", "--no-color", "--no-ext-diff"])
+        .args(args)
+        .arg("--")
+        .args(paths)
+        .output()
+        .map_err(|e| anyhow!("cannot run git: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git diff failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Names of functions, types and variables on the changed lines of `diff`,
+/// most frequent first, for finding their definitions in the index.
+pub fn changed_identifiers(diff: &str) -> Vec<String> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    let changed = diff.lines().filter(|line| {
+        (line.starts_with('+') || line.starts_with('-'))
+            && !line.starts_with("+++")
+            && !line.starts_with("---")
+    });
+    for line in changed {
+        let words = line[1..]
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|w| w.len() > 3 && !w.chars().next().unwrap_or('0').is_ascii_digit());
+        for word in words {
+            match counts.iter_mut().find(|(w, _)| w == word) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((word.to_string(), 1)),
+            }
+        }
+    }
+    counts.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+    counts
+        .into_iter()
+        .take(MAX_IDENTIFIERS)
+        .map(|(w, _)| w)
+        .collect()
+}
+
+/// Explains the behavioral impact of a diff.
+pub struct DiffExplainService {
+    client: OllamaClient,
+}
+
+impl DiffExplainService {
+    pub fn new(client: OllamaClient) -> Self {
+        Self { client }
+    }
+
+    /// `context` holds chunks of the surrounding code, e.g. from the RAG
+    /// index; it may be empty.
+    pub async fn explain(&self, diff: &str, context: &[String]) -> Result<String> {
+        self.client.generate_response(&diff_prompt(diff, context)).await
+    }
+}
+
+pub fn diff_prompt(diff: &str, context: &[String]) -> String {
+    let (diff, cut) = match diff.char_indices().nth(MAX_DIFF_CHARS) {
+        Some((end, _)) => (&diff[..end], true),
+        None => (diff, false),
+    };
+    let mut prompt = String::from(
+        "You are an expert software engineer reviewing a change. Explain its behavioral impact: what behaves differently after it, which callers or users are affected, and any risks or edge cases it introduces. Skip restating the diff line by line.\n\n",
+    );
+    if !context.is_empty() {
+        prompt.push_str("Definitions from the project that the change touches:\n");
+        prompt.push_str(&context.join("\n\n"));
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("Diff:\n");
+    prompt.push_str(diff);
+    if cut {
+        prompt.push_str("\n[diff truncated]");
+    }
+    prompt
+}
@@ -0,0 +1,493 @@
+//! Room a space-heavy command needs (`cp -r`, `rsync`, `tar`, `dd`,
+//! `docker pull`, package installs), estimated mechanically from the sizes
+//! of its sources or a read-only probe (`tar -tv`, `docker manifest
+//! inspect`, the package manager's dry run), and checked against the free
+//! space and inodes of the file system it writes to before it is
+//! confirmed, so it doesn't fail halfway with the disk full.
+
+use crate::env_review::{program_words, split_segments};
+use crate::package_preview::{PackageManager, PackageTransaction};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Entries measured under a source before giving up; the estimate is then
+/// a lower bound.
+const MAX_COUNTED: u64 = 100_000;
+/// How long a probe (`tar -tv`, `docker manifest inspect`) may take.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Image layers unpack to roughly twice their compressed size.
+const UNPACK_FACTOR: u64 = 2;
+
+/// Room one command needs on one file system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpaceNeed {
+    /// What writes, e.g. `cp`, `tar -x`, `docker pull nginx`.
+    pub what: String,
+    /// Where it writes; the file system holding it is checked.
+    pub target: PathBuf,
+    pub bytes: u64,
+    /// Files and directories created; 0 when unknown.
+    pub files: u64,
+}
+
+/// Free room on a file system, as `df` reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FreeSpace {
+    pub mount: String,
+    pub bytes: u64,
+    /// `None` when the file system has no fixed inode count (btrfs, ...).
+    pub inodes: Option<u64>,
+}
+
+/// A [`SpaceNeed`] the file system can't meet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortfall {
+    pub need: SpaceNeed,
+    pub free: FreeSpace,
+}
+
+impl Shortfall {
+    pub fn out_of_space(&self) -> bool {
+        self.need.bytes > self.free.bytes
+    }
+
+    pub fn out_of_inodes(&self) -> bool {
+        self.need.files > 0 && self.free.inodes.is_some_and(|inodes| self.need.files > inodes)
+    }
+}
+
+/// The room the `cp`, `rsync`, `tar`, `dd`, `docker pull` and `podman pull`
+/// invocations in `cmd` need when it runs in `dir`. Segments after a `cd`
+/// are skipped: their directory isn't known.
+pub fn space_needs(cmd: &str, dir: &Path) -> Vec<SpaceNeed> {
+    let mut needs = Vec::new();
+    for segment in split_segments(cmd) {
+        let words: Vec<&str> = segment
+            .iter()
+            .map(|w| w.trim_matches(['"', '\'']))
+            .collect();
+        let Some((program, args)) = program_words(&words).split_first() else {
+            continue;
+        };
+        let program = program.rsplit('/').next().unwrap_or(program);
+        let need = match program {
+            "cd" | "pushd" => break,
+            "cp" => copy_need("cp", args, dir),
+            "rsync" => copy_need("rsync", args, dir),
+            "tar" => tar_need(args, dir),
+            "dd" => dd_need(args, dir),
+            "docker" | "podman" => image_need(program, args),
+            _ => None,
+        };
+        needs.extend(need);
+    }
+    needs
+}
+
+/// The room a package transaction from `manager`'s dry run needs: its
+/// download in the package cache and its installed size under `/usr`.
+pub fn package_needs(manager: PackageManager, transaction: &PackageTransaction) -> Vec<SpaceNeed> {
+    let cache = match manager {
+        PackageManager::Apt => "/var/cache/apt/archives",
+        PackageManager::Dnf => "/var/cache/dnf",
+        PackageManager::Pacman => "/var/cache/pacman/pkg",
+    };
+    let mut needs = Vec::new();
+    if let Some(bytes) = transaction.download.as_deref().and_then(parse_size) {
+        needs.push(SpaceNeed {
+            what: format!("{} download", manager.name()),
+            target: PathBuf::from(cache),
+            bytes,
+            files: 0,
+        });
+    }
+    // A transaction that frees space ("-3 MB") needs none.
+    if let Some(bytes) = transaction.disk.as_deref().and_then(|d| d.strip_prefix('+')).and_then(parse_size) {
+        needs.push(SpaceNeed {
+            what: format!("{} install", manager.name()),
+            target: PathBuf::from("/usr"),
+            bytes,
+            files: 0,
+        });
+    }
+    needs
+}
+
+/// The `needs` whose file system lacks the room or the inodes. Needs on
+/// the same file system are added up, since one command makes them all.
+pub fn shortfalls(needs: &[SpaceNeed]) -> Vec<Shortfall> {
+    let mut checked: Vec<(FreeSpace, SpaceNeed)> = Vec::new();
+    for need in needs {
+        let Some(free) = free_space(&need.target) else {
+            continue;
+        };
+        match checked.iter_mut().find(|(f, _)| f.mount == free.mount) {
+            Some((_, total)) => {
+                total.what = format!("{}, {}", total.what, need.what);
+                total.bytes += need.bytes;
+                total.files += need.files;
+            }
+            None => checked.push((free, need.clone())),
+        }
+    }
+    checked
+        .into_iter()
+        .map(|(free, need)| Shortfall { need, free })
+        .filter(|s| s.out_of_space() || s.out_of_inodes())
+        .collect()
+}
+
+/// Free bytes and inodes of the file system holding `path`, or the nearest
+/// existing directory above it.
+pub fn free_space(path: &Path) -> Option<FreeSpace> {
+    let existing = path.ancestors().find(|p| p.exists() && !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let df = |flag: &str| -> Option<Vec<String>> {
+        let output = Command::new("df").arg(flag).arg(existing).stdin(Stdio::null()).output().ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        // -P keeps each file system on one line, after the heading.
+        Some(stdout.lines().nth(1)?.split_whitespace().map(str::to_string).collect())
+    };
+    // Filesystem 1024-blocks Used Available Capacity Mounted-on
+    let blocks = df("-Pk")?;
+    let available: u64 = blocks.get(3)?.parse().ok()?;
+    let mount = blocks.get(5..)?.join(" ");
+    // Filesystem Inodes IUsed IFree IUse% Mounted-on
+    let inodes = df("-Pi").and_then(|fields| {
+        let total: u64 = fields.get(1)?.parse().ok()?;
+        let free: u64 = fields.get(3)?.parse().ok()?;
+        (total > 0).then_some(free)
+    });
+    Some(FreeSpace {
+        mount,
+        bytes: available * 1024,
+        inodes,
+    })
+}
+
+/// `cp`/`rsync` sources and destination: the last operand, or cp's
+/// `-t DIR`. Remote rsync paths (`host:path`) aren't measured.
+fn copy_need(program: &str, args: &[&str], dir: &Path) -> Option<SpaceNeed> {
+    let mut recursive = false;
+    let mut target: Option<&str> = None;
+    let mut operands = Vec::new();
+    let mut options_done = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if options_done || !arg.starts_with('-') || *arg == "-" {
+            operands.push(*arg);
+        } else if *arg == "--" {
+            options_done = true;
+        } else if program == "cp" && (*arg == "-t" || *arg == "--target-directory") {
+            target = args.next().copied();
+        } else if let Some(dir) = arg.strip_prefix("--target-directory=") {
+            target = Some(dir);
+        } else if arg.starts_with("--") {
+            recursive |= *arg == "--recursive" || *arg == "--archive";
+        } else {
+            recursive |= arg.contains(['r', 'R', 'a']);
+        }
+    }
+    let target = match target {
+        Some(target) => target,
+        None => operands.pop()?,
+    };
+    if operands.is_empty() || target.contains(':') || operands.iter().any(|s| s.contains(':')) {
+        return None;
+    }
+    let (mut bytes, mut files) = (0, 0);
+    for source in operands {
+        let (b, f) = measure(&dir.join(source), recursive);
+        bytes += b;
+        files += f;
+    }
+    (bytes > 0).then(|| SpaceNeed {
+        what: program.to_string(),
+        target: dir.join(target),
+        bytes,
+        files,
+    })
+}
+
+/// `tar -c` needs at most the size of what it archives (compression only
+/// helps); `tar -x` needs what `tar -tv` lists in the archive.
+fn tar_need(args: &[&str], dir: &Path) -> Option<SpaceNeed> {
+    let mut mode: Option<char> = None;
+    let mut archive: Option<&str> = None;
+    let mut directory: Option<&str> = None;
+    let mut operands = Vec::new();
+    let mut args = args.iter().enumerate();
+    while let Some((i, arg)) = args.next() {
+        if let Some(long) = arg.strip_prefix("--") {
+            match long.split_once('=') {
+                Some(("file", file)) => archive = Some(file),
+                Some(("directory", dir)) => directory = Some(dir),
+                None if long == "create" => mode = Some('c'),
+                None if long == "extract" || long == "get" => mode = Some('x'),
+                None if long == "file" => archive = args.next().map(|(_, a)| *a),
+                None if long == "directory" => directory = args.next().map(|(_, a)| *a),
+                _ => {}
+            }
+            continue;
+        }
+        // The first word may bundle options without a dash: `tar czf out.tgz`.
+        let bundle = match arg.strip_prefix('-') {
+            Some(bundle) => bundle,
+            None if i == 0 => arg,
+            None => {
+                operands.push(*arg);
+                continue;
+            }
+        };
+        if bundle == "C" {
+            directory = args.next().map(|(_, a)| *a);
+            continue;
+        }
+        for (at, flag) in bundle.char_indices() {
+            match flag {
+                'c' | 'x' => mode = Some(flag),
+                'f' => {
+                    // The rest of the bundle, or the next word, names it.
+                    let rest = &bundle[at + 1..];
+                    archive = if rest.is_empty() { args.next().map(|(_, a)| *a) } else { Some(rest) };
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+    let archive = dir.join(archive.filter(|a| *a != "-")?);
+    let directory = directory.map(|d| dir.join(d));
+    match mode? {
+        'c' => {
+            let bytes: u64 = operands
+                .iter()
+                .map(|source| {
+                    let base = directory.as_deref().unwrap_or(dir).join(source);
+                    measure(&base, true).0
+                })
+                .sum();
+            (bytes > 0).then(|| SpaceNeed {
+                what: "tar -c".to_string(),
+                target: archive,
+                bytes,
+                files: 1,
+            })
+        }
+        _ => {
+            let listing = probe("tar", &["-tvf", &archive.to_string_lossy()])?;
+            let (bytes, files) = listed_sizes(&listing);
+            Some(SpaceNeed {
+                what: "tar -x".to_string(),
+                target: directory.unwrap_or_else(|| dir.to_path_buf()),
+                bytes,
+                files,
+            })
+        }
+    }
+}
+
+/// Total size and entry count of a `tar -tv` listing. GNU tar prints
+/// `mode owner/group size date time name`, bsdtar `mode links owner group
+/// size month day time name`.
+pub fn listed_sizes(listing: &str) -> (u64, u64) {
+    let mut bytes = 0;
+    let mut files = 0;
+    for line in listing.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let size = match fields.get(1) {
+            Some(owner) if owner.contains('/') => fields.get(2),
+            Some(_) => fields.get(4),
+            None => continue,
+        };
+        if let Some(size) = size.and_then(|s| s.parse::<u64>().ok()) {
+            bytes += size;
+            files += 1;
+        }
+    }
+    (bytes, files)
+}
+
+/// `dd` writes `bs` × `count` bytes when both are given, else the size of
+/// `if`. Writing to a device (`of=/dev/sdb`) needs no file system room.
+fn dd_need(args: &[&str], dir: &Path) -> Option<SpaceNeed> {
+    let operand = |name: &str| {
+        args.iter()
+            .find_map(|arg| arg.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')))
+    };
+    let output = operand("of")?;
+    if output.starts_with("/dev/") {
+        return None;
+    }
+    let block = operand("bs").or(operand("obs")).and_then(parse_dd_size);
+    let bytes = match (block, operand("count").and_then(parse_dd_size)) {
+        (Some(block), Some(count)) => block.saturating_mul(count),
+        _ => std::fs::metadata(dir.join(operand("if")?)).ok().filter(|m| m.is_file())?.len(),
+    };
+    Some(SpaceNeed {
+        what: "dd".to_string(),
+        target: dir.join(output),
+        bytes,
+        files: 1,
+    })
+}
+
+/// A `dd` number: digits with an optional suffix (`c`, `w`, `b`, `K`/`kB`,
+/// `M`/`MB`, `G`/`GB`, `T`/`TB`; the `B` forms are powers of 1000).
+pub fn parse_dd_size(value: &str) -> Option<u64> {
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (digits, suffix) = value.split_at(split);
+    let number: u64 = digits.parse().ok()?;
+    let factor: u64 = match suffix {
+        "" | "c" => 1,
+        "w" => 2,
+        "b" => 512,
+        "kB" => 1000,
+        "K" | "k" | "KiB" => 1 << 10,
+        "MB" => 1000_u64.pow(2),
+        "M" | "MiB" => 1 << 20,
+        "GB" => 1000_u64.pow(3),
+        "G" | "GiB" => 1 << 30,
+        "TB" => 1000_u64.pow(4),
+        "T" | "TiB" => 1 << 40,
+        _ => return None,
+    };
+    number.checked_mul(factor)
+}
+
+/// A size as package managers print it: `1,234 kB`, `40.2 MB`, `12 M`.
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.replace(',', "");
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let factor = match unit.trim() {
+        "" | "B" => 1.0,
+        "kB" => 1e3,
+        "k" | "K" | "KB" | "KiB" => 1024.0,
+        "MB" => 1e6,
+        "M" | "MiB" => 1024.0 * 1024.0,
+        "GB" => 1e9,
+        "G" | "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number * factor) as u64)
+}
+
+/// `docker pull IMAGE`: the compressed layers `docker manifest inspect`
+/// lists for this machine's architecture, unpacked, under the engine's
+/// storage directory.
+fn image_need(engine: &str, args: &[&str]) -> Option<SpaceNeed> {
+    let (verb, rest) = args.split_first()?;
+    if *verb != "pull" {
+        return None;
+    }
+    let image = rest.iter().find(|a| !a.starts_with('-'))?;
+    let manifest = probe(engine, &["manifest", "inspect", "-v", image])?;
+    let compressed = layer_bytes(&serde_json::from_str(&manifest).ok()?)?;
+    let root_format = match engine {
+        "podman" => "{{.Store.GraphRoot}}",
+        _ => "{{.DockerRootDir}}",
+    };
+    let root = probe(engine, &["info", "--format", root_format])
+        .map(|root| root.trim().to_string())
+        .filter(|root| !root.is_empty())
+        .unwrap_or_else(|| "/var/lib/docker".to_string());
+    Some(SpaceNeed {
+        what: format!("{} pull {}", engine, image),
+        target: PathBuf::from(root),
+        bytes: compressed * UNPACK_FACTOR,
+        files: 0,
+    })
+}
+
+/// Compressed size of the layers in `docker manifest inspect -v` output: a
+/// single manifest, or a list with one per platform, of which this
+/// machine's is taken (else the first).
+pub fn layer_bytes(manifest: &serde_json::Value) -> Option<u64> {
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch => arch,
+    };
+    let entry = match manifest.as_array() {
+        Some(entries) => entries
+            .iter()
+            .find(|e| e["Descriptor"]["platform"]["architecture"] == arch)
+            .or(entries.first())?,
+        None => manifest,
+    };
+    let layers = ["SchemaV2Manifest", "OCIManifest"]
+        .iter()
+        .find_map(|key| entry[key]["layers"].as_array())?;
+    Some(layers.iter().filter_map(|layer| layer["size"].as_u64()).sum())
+}
+
+/// Bytes and entries at `path`, descending into directories when
+/// `recursive` (symlinks aren't followed), up to [`MAX_COUNTED`] entries.
+fn measure(path: &Path, recursive: bool) -> (u64, u64) {
+    let Ok(meta) = std::fs::symlink_metadata(path) else {
+        return (0, 0);
+    };
+    if !meta.is_dir() {
+        return (meta.len(), 1);
+    }
+    if !recursive {
+        return (0, 0);
+    }
+    let mut totals = (0, 1);
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if totals.1 >= MAX_COUNTED {
+                return totals;
+            }
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            totals.1 += 1;
+            if meta.is_dir() {
+                pending.push(entry.path());
+            } else {
+                totals.0 += meta.len();
+            }
+        }
+    }
+    totals
+}
+
+/// Stdout of a read-only probe, or `None` if it fails or takes longer
+/// than [`PROBE_TIMEOUT`].
+fn probe(program: &str, args: &[&str]) -> Option<String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut stdout = child.stdout.take()?;
+    // Read on a thread so a chatty probe can't fill the pipe and stall.
+    let reader = std::thread::spawn(move || {
+        let mut text = String::new();
+        stdout.read_to_string(&mut text).map(|_| text)
+    });
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return reader.join().ok()?.ok(),
+            Ok(Some(_)) | Err(_) => return None,
+            Ok(None) if started.elapsed() > PROBE_TIMEOUT => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! `vibe gen-docs`: doc comments for the public items of a Rust or Python
+//! file that have none, found with tree-sitter and written from each item's
+//! body and, optionally, its call sites in the RAG index.
+
+use crate::rag_service::RagService;
+use crate::test_gen_service::mentions;
+use anyhow::anyhow;
+use infrastructure::ollama_client::OllamaClient;
+use shared::types::Result;
+use std::path::Path;
+use tree_sitter::{Node, Parser};
+
+/// Characters of an item's source sent to the model.
+const MAX_ITEM_CHARS: usize = 6_000;
+/// Chunks searched for call sites of one item.
+const CALL_SITE_CHUNKS: usize = 8;
+/// Call sites sent per item.
+const MAX_CALL_SITES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocLanguage {
+    Rust,
+    Python,
+}
+
+impl DocLanguage {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(DocLanguage::Rust),
+            "py" | "pyi" => Some(DocLanguage::Python),
+            _ => None,
+        }
+    }
+}
+
+/// A public item without a doc comment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndocumentedItem {
+    /// e.g. `function`, `method`, `struct`, `class`.
+    pub kind: String,
+    pub name: String,
+    /// 0-based line the comment is inserted before: above the item's
+    /// attributes in Rust, the first line of the body in Python.
+    pub line: usize,
+    /// Indentation of the comment.
+    pub indent: String,
+    /// The item's source.
+    pub text: String,
+}
+
+/// Public items of `source` without a doc comment, in file order. In Rust
+/// that is `pub` items, including those of `pub mod` blocks and `pub`
+/// methods of inherent impls; in Python, functions, classes and methods
+/// whose name doesn't start with `_`.
+pub fn undocumented_items(source: &str, language: DocLanguage) -> Result<Vec<UndocumentedItem>> {
+    let mut parser = Parser::new();
+    let grammar = match language {
+        DocLanguage::Rust => tree_sitter_rust::LANGUAGE,
+        DocLanguage::Python => tree_sitter_python::LANGUAGE,
+    };
+    parser
+        .set_language(&grammar.into())
+        .map_err(|e| anyhow!("cannot load the parser: {}", e))?;
+    let tree = parser
+        .parse(source, None)
+        .ok_or_else(|| anyhow!("the file could not be parsed"))?;
+    let mut items = Vec::new();
+    match language {
+        DocLanguage::Rust => rust_items(tree.root_node(), source, false, &mut items),
+        DocLanguage::Python => python_items(tree.root_node(), source, false, &mut items),
+    }
+    Ok(items)
+}
+
+fn text<'a>(node: Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+fn indent_of(source: &str, line: usize) -> String {
+    source
+        .lines()
+        .nth(line)
+        .map(|l| l.chars().take_while(|c| c.is_whitespace()).collect())
+        .unwrap_or_default()
+}
+
+fn item(kind: &str, name: &str, node: Node, line: usize, source: &str) -> UndocumentedItem {
+    UndocumentedItem {
+        kind: kind.to_string(),
+        name: name.to_string(),
+        line,
+        indent: indent_of(source, line),
+        text: text(node, source).to_string(),
+    }
+}
+
+fn rust_items(parent: Node, source: &str, in_impl: bool, items: &mut Vec<UndocumentedItem>) {
+    let mut cursor = parent.walk();
+    for node in parent.named_children(&mut cursor) {
+        let public = node
+            .named_children(&mut node.walk())
+            .any(|child| child.kind() == "visibility_modifier" && text(child, source) == "pub");
+        let kind = match node.kind() {
+            "function_item" if in_impl => "method",
+            "function_item" => "function",
+            "struct_item" => "struct",
+            "enum_item" => "enum",
+            "union_item" => "union",
+            "trait_item" => "trait",
+            "type_item" => "type alias",
+            "const_item" => "constant",
+            "static_item" => "static",
+            "mod_item" => "module",
+            "impl_item" => {
+                // Trait impls take the trait's docs.
+                if node.child_by_field_name("trait").is_none() {
+                    if let Some(body) = node.child_by_field_name("body") {
+                        rust_items(body, source, true, items);
+                    }
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if !public {
+            continue;
+        }
+        let (documented, line) = rust_docs(node, source);
+        if !documented {
+            let name = node
+                .child_by_field_name("name")
+                .map(|n| text(n, source))
+                .unwrap_or("");
+            items.push(item(kind, name, node, line, source));
+        }
+        if node.kind() == "mod_item" {
+            if let Some(body) = node.child_by_field_name("body") {
+                rust_items(body, source, false, items);
+            }
+        }
+    }
+}
+
+/// Whether the item has a doc comment or `#[doc]` attribute above it, and
+/// the line above its attributes.
+fn rust_docs(node: Node, source: &str) -> (bool, usize) {
+    let mut line = node.start_position().row;
+    let mut previous = node.prev_sibling();
+    while let Some(sibling) = previous {
+        let sibling_text = text(sibling, source);
+        match sibling.kind() {
+            "attribute_item" if sibling_text.starts_with("#[doc") => return (true, line),
+            "attribute_item" => line = sibling.start_position().row,
+            "line_comment" => {
+                return (
+                    sibling_text.starts_with("///") && !sibling_text.starts_with("////"),
+                    line,
+                )
+            }
+            "block_comment" => return (sibling_text.starts_with("/**"), line),
+            _ => break,
+        }
+        previous = sibling.prev_sibling();
+    }
+    (false, line)
+}
+
+fn python_items(parent: Node, source: &str, in_class: bool, items: &mut Vec<UndocumentedItem>) {
+    let mut cursor = parent.walk();
+    for node in parent.named_children(&mut cursor) {
+        let definition = match node.kind() {
+            "decorated_definition" => match node.child_by_field_name("definition") {
+                Some(definition) => definition,
+                None => continue,
+            },
+            _ => node,
+        };
+        let kind = match definition.kind() {
+            "function_definition" if in_class => "method",
+            "function_definition" => "function",
+            "class_definition" => "class",
+            _ => continue,
+        };
+        let name = definition
+            .child_by_field_name("name")
+            .map(|n| text(n, source))
+            .unwrap_or("");
+        let Some(body) = definition.child_by_field_name("body") else {
+            continue;
+        };
+        if name.starts_with('_') {
+            continue;
+        }
+        let first = body
+            .named_children(&mut body.walk())
+            .find(|statement| statement.kind() != "comment");
+        let documented = first.is_some_and(|statement| {
+            statement.kind() == "expression_statement"
+                && statement
+                    .named_child(0)
+                    .is_some_and(|value| value.kind() == "string")
+        });
+        // One-line definitions like `def f(): pass` are left alone.
+        let line = body.start_position().row;
+        if !documented && line > definition.start_position().row {
+            items.push(item(kind, name, node, line, source));
+        }
+        if kind == "class" {
+            python_items(body, source, true, items);
+        }
+    }
+}
+
+/// The text of a doc comment as the model wrote it, without code fences or
+/// comment markers.
+pub fn clean_doc(reply: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in reply.trim().lines() {
+        let line = line.trim();
+        if line.starts_with("```") {
+            continue;
+        }
+        let line = ["///", "//!", "/**", "*/"]
+            .iter()
+            .find_map(|marker| line.strip_prefix(marker))
+            .unwrap_or(line);
+        let line = line.trim_start_matches("\"\"\"").trim_end_matches("\"\"\"");
+        lines.push(line.trim().to_string());
+    }
+    let mut doc = Vec::new();
+    for line in lines.iter().skip_while(|l| l.is_empty()) {
+        if !(line.is_empty() && doc.last().is_some_and(|l: &&String| l.is_empty())) {
+            doc.push(line);
+        }
+    }
+    while doc.last().is_some_and(|l| l.is_empty()) {
+        doc.pop();
+    }
+    doc.into_iter().cloned().collect::<Vec<_>>().join("\n")
+}
+
+/// `doc` as a comment for `item`: `///` lines in Rust, a docstring in
+/// Python.
+pub fn format_doc(doc: &str, item: &UndocumentedItem, language: DocLanguage) -> String {
+    let indent = &item.indent;
+    match language {
+        DocLanguage::Rust => doc
+            .lines()
+            .map(|line| match line.is_empty() {
+                true => format!("{}///\n", indent),
+                false => format!("{}/// {}\n", indent, line),
+            })
+            .collect(),
+        DocLanguage::Python => {
+            let doc = doc.replace("\"\"\"", "\\\"\\\"\\\"");
+            let mut lines = doc.lines();
+            let first = lines.next().unwrap_or("");
+            let rest: Vec<&str> = lines.collect();
+            if rest.is_empty() {
+                return format!("{}\"\"\"{}\"\"\"\n", indent, first);
+            }
+            let mut out = format!("{}\"\"\"{}\n", indent, first);
+            for line in rest {
+                match line.is_empty() {
+                    true => out.push('\n'),
+                    false => out.push_str(&format!("{}{}\n", indent, line)),
+                }
+            }
+            out.push_str(&format!("{}\"\"\"\n", indent));
+            out
+        }
+    }
+}
+
+/// `source` with each comment inserted at its item's line.
+pub fn apply_docs(source: &str, docs: &[(UndocumentedItem, String)]) -> String {
+    let mut lines: Vec<String> = source.split_inclusive('\n').map(str::to_string).collect();
+    let mut sorted: Vec<&(UndocumentedItem, String)> = docs.iter().collect();
+    sorted.sort_by_key(|(item, _)| std::cmp::Reverse(item.line));
+    for (item, comment) in sorted {
+        let at = item.line.min(lines.len());
+        if at == lines.len() {
+            if let Some(last) = lines.last_mut().filter(|l| !l.ends_with('\n')) {
+                last.push('\n');
+            }
+        }
+        lines.insert(at, comment.clone());
+    }
+    lines.concat()
+}
+
+/// Chunks of the index that call `name`, outside its own definition.
+pub async fn call_sites(index: &RagService, name: &str) -> Result<Vec<String>> {
+    let definition = ["fn ", "def "].map(|keyword| format!("{}{}", keyword, name));
+    Ok(index
+        .related_context(name, CALL_SITE_CHUNKS)
+        .await?
+        .into_iter()
+        .filter(|chunk| {
+            mentions(chunk, &format!("{}(", name))
+                && !definition.iter().any(|d| mentions(chunk, d))
+        })
+        .take(MAX_CALL_SITES)
+        .collect())
+}
+
+pub struct DocGenService {
+    client: OllamaClient,
+}
+
+impl DocGenService {
+    pub fn new(client: OllamaClient) -> Self {
+        Self { client }
+    }
+
+    /// The doc comment's text, without comment markers.
+    pub async fn document(
+        &self,
+        item: &UndocumentedItem,
+        language: DocLanguage,
+        call_sites: &[String],
+    ) -> Result<String> {
+        let reply = self
+            .client
+            .generate_response(&doc_prompt(item, language, call_sites))
+            .await?;
+        Ok(clean_doc(&reply))
+    }
+}
+
+pub fn doc_prompt(item: &UndocumentedItem, language: DocLanguage, call_sites: &[String]) -> String {
+    let source = match item.text.char_indices().nth(MAX_ITEM_CHARS) {
+        Some((end, _)) => format!("{}\n[truncated]", &item.text[..end]),
+        None => item.text.clone(),
+    };
+    let style = match language {
+        DocLanguage::Rust => "a Rust doc comment in Markdown: a one-sentence summary, then only the details a caller needs, such as the meaning of arguments, errors returned and panics",
+        DocLanguage::Python => "a PEP 257 docstring: a one-line summary, then only the details a caller needs, such as arguments, return value and exceptions raised",
+    };
+    let mut prompt = format!(
+        "Write {} for the {} `{}`. Describe what it does from the code below, not what it is named; do not invent behavior.\n\n{}\n\n",
+        style, item.kind, item.name, source
+    );
+    if !call_sites.is_empty() {
+        prompt.push_str("It is used like this in the project:\n");
+        prompt.push_str(&call_sites.join("\n\n"));
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("Respond with only the comment's text, without comment markers or quotes.");
+    prompt
+}
@@ -0,0 +1,122 @@
+//! `docs add|refresh|remove|list`: external documentation (a site such as
+//! the Leptos book, an internal wiki, a local doc tree) chunked into the
+//! `docs` collection, searched with `--rag --collections code,docs`.
+
+use crate::rag_service::RagService;
+use domain::models::DocSource;
+use infrastructure::config::Config;
+use infrastructure::doc_source::{self, DocPage};
+use infrastructure::embedding_storage::EmbeddingStorage;
+use infrastructure::ollama_client::OllamaClient;
+use shared::types::Result;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Collection that documentation is added to.
+pub const DOCS_COLLECTION: &str = "docs";
+/// Pages fetched from one site unless told otherwise.
+pub const DEFAULT_MAX_PAGES: usize = 50;
+
+pub struct DocsService {
+    rag: RagService,
+    storage: EmbeddingStorage,
+}
+
+impl DocsService {
+    pub async fn new(client: OllamaClient, config: &Config) -> Result<Self> {
+        let db_path = config.for_collection(DOCS_COLLECTION)?.db_path;
+        Ok(Self {
+            rag: RagService::collection(".", DOCS_COLLECTION, client, config).await?,
+            storage: EmbeddingStorage::new(db_path).await?,
+        })
+    }
+
+    /// Fetch or read `source` (a URL, or a local file or directory) and
+    /// embed its pages. Adding a source again refreshes it: changed pages
+    /// are re-embedded and pages it no longer has are dropped.
+    pub async fn add(&self, source: &str, max_pages: usize) -> Result<DocSource> {
+        let source = normalize_source(source);
+        let pages = fetch(&source, max_pages).await?;
+        if pages.is_empty() {
+            return Err(anyhow::anyhow!("no documentation found at {}", source));
+        }
+        let locations: Vec<String> = pages.iter().map(|page| page.location.clone()).collect();
+        if let Some(previous) = self.find(&source).await? {
+            for page in previous.pages.into_iter().filter(|p| !locations.contains(p)) {
+                self.storage.delete_file(page).await?;
+            }
+        }
+        self.rag.index_documents(&pages).await?;
+        let record = DocSource {
+            source,
+            pages: locations,
+            fetched_at: now(),
+        };
+        self.storage.upsert_doc_source(record.clone()).await?;
+        Ok(record)
+    }
+
+    /// Add each recorded source again, or only `source`.
+    pub async fn refresh(&self, source: Option<&str>, max_pages: usize) -> Result<Vec<DocSource>> {
+        let sources = match source {
+            Some(source) => vec![self.require(source).await?.source],
+            None => self.sources().await?.into_iter().map(|s| s.source).collect(),
+        };
+        let mut refreshed = Vec::new();
+        for source in sources {
+            refreshed.push(self.add(&source, max_pages).await?);
+        }
+        Ok(refreshed)
+    }
+
+    /// Drop `source` and all of its pages from the collection.
+    pub async fn remove(&self, source: &str) -> Result<DocSource> {
+        let record = self.require(source).await?;
+        for page in &record.pages {
+            self.storage.delete_file(page.clone()).await?;
+        }
+        self.storage.delete_doc_source(record.source.clone()).await?;
+        Ok(record)
+    }
+
+    pub async fn sources(&self) -> Result<Vec<DocSource>> {
+        self.storage.get_doc_sources().await
+    }
+
+    async fn find(&self, source: &str) -> Result<Option<DocSource>> {
+        let source = normalize_source(source);
+        Ok(self.sources().await?.into_iter().find(|s| s.source == source))
+    }
+
+    async fn require(&self, source: &str) -> Result<DocSource> {
+        self.find(source).await?.ok_or_else(|| {
+            anyhow::anyhow!("{} has not been added; see `vibe_cli docs list`", source)
+        })
+    }
+}
+
+async fn fetch(source: &str, max_pages: usize) -> Result<Vec<DocPage>> {
+    if doc_source::is_url(source) {
+        doc_source::fetch_site(source, max_pages).await
+    } else {
+        doc_source::read_tree(Path::new(source))
+    }
+}
+
+/// URLs as given; local paths made absolute, so the same tree is the same
+/// source from any directory.
+pub fn normalize_source(source: &str) -> String {
+    if doc_source::is_url(source) {
+        return source.to_string();
+    }
+    std::fs::canonicalize(source)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|_| source.to_string())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,363 @@
+//! Environment variables a command sets (`FOO=1 cmd`, `export FOO=1`,
+//! `env FOO=1 cmd`), resolved and shown before it runs so a secret isn't
+//! handed to a command unnoticed, and the scrubbed environment paranoid mode
+//! runs commands with.
+
+/// Variables kept in a scrubbed environment in addition to the configured
+/// ones; a trailing `*` matches any suffix.
+pub const DEFAULT_KEPT_VARS: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "SHELL", "TERM", "LANG", "LC_*", "TZ", "TMPDIR", "PWD",
+];
+
+/// Parts of a variable name that mark its value as a secret.
+const SECRET_NAME_PARTS: &[&str] = &[
+    "TOKEN", "SECRET", "PASSW", "CREDENTIAL", "AUTH", "API_KEY", "APIKEY", "PRIVATE_KEY",
+    "ACCESS_KEY",
+];
+/// Prefixes of well-known token formats.
+const SECRET_VALUE_PREFIXES: &[&str] = &[
+    "ghp_", "gho_", "ghs_", "github_pat_", "glpat-", "sk-", "xoxb-", "xoxp-", "AKIA", "AIza",
+    "eyJ",
+];
+/// Longest non-secret value shown in full.
+const MAX_SHOWN_CHARS: usize = 120;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvAssignment {
+    pub name: String,
+    /// The value with quotes removed and `$VAR` references expanded;
+    /// command substitutions are left as written.
+    pub value: String,
+    pub secret: bool,
+}
+
+impl EnvAssignment {
+    /// The value as shown to the user; a secret only hints at what it is.
+    pub fn display_value(&self) -> String {
+        if self.value.is_empty() {
+            return "(empty)".to_string();
+        }
+        let chars = self.value.chars().count();
+        if self.secret {
+            if chars < 12 {
+                return format!("**** ({} chars)", chars);
+            }
+            let start: String = self.value.chars().take(4).collect();
+            return format!("{}… ({} chars)", start, chars);
+        }
+        let line = self.value.replace(['\n', '\r'], " ");
+        if chars > MAX_SHOWN_CHARS {
+            let cut: String = line.chars().take(MAX_SHOWN_CHARS).collect();
+            format!("{}…", cut)
+        } else {
+            line
+        }
+    }
+}
+
+/// The variables `cmd` assigns, in order. `lookup` resolves variables from
+/// the current environment; ones assigned earlier in `cmd` take precedence.
+pub fn env_assignments(cmd: &str, lookup: impl Fn(&str) -> Option<String>) -> Vec<EnvAssignment> {
+    let mut found: Vec<EnvAssignment> = Vec::new();
+    for segment in split_segments(cmd) {
+        let mut words = segment.iter().map(String::as_str).peekable();
+        if words.peek() == Some(&"sudo") {
+            words.next();
+            while words.peek().is_some_and(|w| w.starts_with('-')) {
+                words.next();
+            }
+        }
+        // `export` and `declare` take assignments among bare names and
+        // flags; elsewhere assignments only lead the command.
+        let builtin = matches!(words.peek(), Some(&"export") | Some(&"declare") | Some(&"typeset"));
+        let env = words.peek() == Some(&"env");
+        if builtin || env {
+            words.next();
+        }
+        for word in words {
+            if (builtin || env) && word.starts_with('-') {
+                continue;
+            }
+            let Some((name, raw)) = split_assignment(word) else {
+                if builtin {
+                    continue;
+                }
+                break;
+            };
+            let value = expand(raw, |var| {
+                found
+                    .iter()
+                    .rev()
+                    .find(|a| a.name == var)
+                    .map(|a| a.value.clone())
+                    .or_else(|| lookup(var))
+            });
+            found.push(EnvAssignment {
+                secret: looks_like_secret(name, &value),
+                name: name.to_string(),
+                value,
+            });
+        }
+    }
+    found
+}
+
+/// Whether a variable called `name` holding `value` is likely a credential.
+pub fn looks_like_secret(name: &str, value: &str) -> bool {
+    let upper = name.to_uppercase();
+    if SECRET_NAME_PARTS.iter().any(|part| upper.contains(part))
+        || upper.ends_with("_KEY")
+        || upper.ends_with("_PASS")
+        || upper == "KEY"
+    {
+        return true;
+    }
+    let value = value.trim();
+    if SECRET_VALUE_PREFIXES.iter().any(|p| value.starts_with(p)) && value.len() >= 16 {
+        return true;
+    }
+    if let Some((_, rest)) = value.split_once("://") {
+        let authority = rest.split('/').next().unwrap_or_default();
+        if authority.split_once('@').is_some_and(|(user, _)| user.contains(':')) {
+            return true;
+        }
+    }
+    value.len() >= 24
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || "+/=_-".contains(c))
+        && value.chars().any(|c| c.is_ascii_uppercase())
+        && value.chars().any(|c| c.is_ascii_lowercase())
+        && value.chars().any(|c| c.is_ascii_digit())
+}
+
+/// The variables of `vars` kept by name: [`DEFAULT_KEPT_VARS`] and `keep`.
+pub fn scrubbed_environment(
+    vars: impl IntoIterator<Item = (String, String)>,
+    keep: &[String],
+) -> Vec<(String, String)> {
+    let patterns: Vec<&str> = DEFAULT_KEPT_VARS
+        .iter()
+        .copied()
+        .chain(keep.iter().map(|k| k.trim()))
+        .filter(|k| !k.is_empty())
+        .collect();
+    vars.into_iter()
+        .filter(|(name, _)| {
+            patterns.iter().any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern,
+            })
+        })
+        .collect()
+}
+
+/// `NAME` and the raw value of a `NAME=value` word.
+pub(crate) fn split_assignment(word: &str) -> Option<(&str, &str)> {
+    let (name, raw) = word.split_once('=')?;
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then_some((name, raw))
+}
+
+/// Words of each simple command in `cmd`, split at `;`, `|`, `&` and
+/// newlines outside quotes and command substitutions. Words keep their
+/// quotes.
+pub(crate) fn split_segments(cmd: &str) -> Vec<Vec<String>> {
+    split_pipeline(cmd).into_iter().map(|(words, _)| words).collect()
+}
+
+/// Commands that run the words after them as the real command: each with
+/// its options that take a value as the next word, and the arguments it
+/// takes before that command (`timeout`'s duration).
+const WRAPPERS: &[(&str, &[&str], usize)] = &[
+    (
+        "sudo",
+        &[
+            "-u", "-g", "-p", "-C", "-D", "-R", "-T", "-U", "-r", "-t", "--user", "--group", "--prompt",
+            "--chdir", "--chroot", "--close-from", "--role", "--type", "--other-user", "--host",
+            "--command-timeout",
+        ],
+        0,
+    ),
+    ("doas", &["-u", "-C"], 0),
+    ("env", &["-u", "-C", "-S", "--unset", "--chdir", "--split-string"], 0),
+    ("command", &[], 0),
+    ("nohup", &[], 0),
+    ("nice", &["-n", "--adjustment"], 0),
+    ("ionice", &["-c", "-n", "-p", "-P", "-u", "--class", "--classdata"], 0),
+    ("time", &["-f", "-o", "--format", "--output"], 0),
+    ("exec", &["-a"], 0),
+    ("timeout", &["-s", "-k", "--signal", "--kill-after"], 1),
+    (
+        "xargs",
+        &[
+            "-a", "-d", "-E", "-I", "-L", "-n", "-P", "-s", "--arg-file", "--delimiter", "--eof",
+            "--max-lines", "--max-args", "--max-procs", "--max-chars", "--process-slot-var",
+        ],
+        0,
+    ),
+];
+
+/// The words of `segment` from the command it runs on: leading
+/// `NAME=value` assignments and wrappers such as `sudo -u www-data` or
+/// `nice -n 10` are skipped, with their options and option values. Empty
+/// if there is no command.
+pub fn program_words<S: AsRef<str>>(segment: &[S]) -> &[S] {
+    let mut i = 0;
+    while let Some(word) = segment.get(i).map(AsRef::as_ref) {
+        if split_assignment(word).is_some() {
+            i += 1;
+            continue;
+        }
+        let Some((_, valued, positional)) = WRAPPERS.iter().find(|(name, _, _)| *name == word) else {
+            break;
+        };
+        i += 1;
+        while let Some(option) = segment.get(i).map(AsRef::as_ref).filter(|w| w.starts_with('-')) {
+            i += 1;
+            if option == "--" {
+                break;
+            }
+            if valued.contains(&option) {
+                i += 1;
+            }
+        }
+        i += positional;
+    }
+    segment.get(i..).unwrap_or_default()
+}
+
+/// [`split_segments`], each with whether it reads the output of the one
+/// before it (a lone `|` separates them).
+pub(crate) fn split_pipeline(cmd: &str) -> Vec<(Vec<String>, bool)> {
+    let mut segments = Vec::new();
+    let mut current: (Vec<String>, bool) = (Vec::new(), false);
+    let mut word = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = cmd.chars().peekable();
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => {
+                word.push(c);
+                if c == '\\' && q == '"' {
+                    if let Some(next) = chars.next() {
+                        word.push(next);
+                    }
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '\'' | '"' => {
+                    quote = Some(c);
+                    word.push(c);
+                }
+                '\\' => {
+                    word.push(c);
+                    if let Some(next) = chars.next() {
+                        word.push(next);
+                    }
+                }
+                '$' if chars.peek() == Some(&'(') => {
+                    word.push(c);
+                    let mut depth = 0;
+                    for c in chars.by_ref() {
+                        word.push(c);
+                        match c {
+                            '(' => depth += 1,
+                            ')' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                c if c.is_whitespace() && c != '\n' => {
+                    if !word.is_empty() {
+                        current.0.push(std::mem::take(&mut word));
+                    }
+                }
+                ';' | '|' | '&' | '\n' => {
+                    if !word.is_empty() {
+                        current.0.push(std::mem::take(&mut word));
+                    }
+                    if current.0.is_empty() {
+                        // A second separator in a row (`||`, `|&`) makes it
+                        // something other than a pipe.
+                        current.1 = false;
+                    } else {
+                        segments.push(std::mem::replace(&mut current, (Vec::new(), c == '|')));
+                    }
+                }
+                _ => word.push(c),
+            },
+        }
+    }
+    if !word.is_empty() {
+        current.0.push(word);
+    }
+    segments.push(current);
+    segments.retain(|(words, _)| !words.is_empty());
+    segments
+}
+
+/// `raw` with quotes removed and `$NAME` / `${NAME}` expanded outside
+/// single quotes; `$(...)` and backticks are kept as written.
+fn expand(raw: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => out.push(c),
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (_, '\\') => {
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            }
+            (_, '$') => match chars.peek() {
+                Some('{') => {
+                    chars.next();
+                    let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    out.push_str(&lookup(&name).unwrap_or_default());
+                }
+                Some('(') => {
+                    let mut depth = 0;
+                    out.push('$');
+                    for c in chars.by_ref() {
+                        out.push(c);
+                        match c {
+                            '(' => depth += 1,
+                            ')' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Some(&next) if next.is_ascii_alphabetic() || next == '_' => {
+                    let mut name = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if !(c.is_ascii_alphanumeric() || c == '_') {
+                            break;
+                        }
+                        name.push(c);
+                        chars.next();
+                    }
+                    out.push_str(&lookup(&name).unwrap_or_default());
+                }
+                _ => out.push('$'),
+            },
+            _ => out.push(c),
+        }
+    }
+    out
+}
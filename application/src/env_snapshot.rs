@@ -0,0 +1,213 @@
+//! A quick snapshot of the system taken before an agent plan runs and
+//! again after it: installed packages, enabled services and hashes of the
+//! files the plan mentions. The difference is the run's "what changed"
+//! report.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Files hashed per directory a plan mentions.
+const FILES_PER_DIR: usize = 100;
+/// Files hashed at most, in total.
+const MAX_FILES: usize = 500;
+/// Files larger than this are tracked by size and mtime only.
+const MAX_HASHED_BYTES: u64 = 16 * 1024 * 1024;
+/// Trees whose contents aren't configuration.
+const UNTRACKED_ROOTS: &[&str] = &["/dev/", "/proc/", "/sys/", "/run/", "/tmp/", "/var/tmp/"];
+
+/// Installed packages, enabled services and tracked file hashes; `None`
+/// where the probe isn't available on this system.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvSnapshot {
+    /// Package name to version.
+    pub packages: Option<BTreeMap<String, String>>,
+    /// Enabled systemd units.
+    pub services: Option<BTreeSet<String>>,
+    /// File to content hash; `None` for a file that doesn't exist.
+    pub files: BTreeMap<String, Option<String>>,
+}
+
+fn read_only(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// `name version` lines into a map.
+pub fn parse_packages(listing: &str) -> BTreeMap<String, String> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            Some((words.next()?.to_string(), words.next().unwrap_or("").to_string()))
+        })
+        .collect()
+}
+
+/// Installed packages from the first package database found: dpkg, rpm,
+/// pacman or Homebrew.
+fn packages() -> Option<BTreeMap<String, String>> {
+    let probes: [(&str, &[&str]); 4] = [
+        ("dpkg-query", &["-W", "-f", "${Package} ${Version}\\n"]),
+        ("rpm", &["-qa", "--qf", "%{NAME} %{VERSION}-%{RELEASE}\\n"]),
+        ("pacman", &["-Q"]),
+        ("brew", &["list", "--versions"]),
+    ];
+    probes
+        .iter()
+        .find_map(|(program, args)| read_only(program, args))
+        .map(|listing| parse_packages(&listing))
+}
+
+/// Enabled units from `systemctl list-unit-files --state=enabled`.
+pub fn parse_enabled_units(listing: &str) -> BTreeSet<String> {
+    listing
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|unit| unit.contains('.'))
+        .map(str::to_string)
+        .collect()
+}
+
+fn services() -> Option<BTreeSet<String>> {
+    read_only(
+        "systemctl",
+        &["list-unit-files", "--state=enabled", "--no-legend", "--no-pager"],
+    )
+    .map(|listing| parse_enabled_units(&listing))
+}
+
+/// Absolute or home paths named in `commands`, with a leading `~/`
+/// expanded; system trees such as `/proc` and `/tmp` are left out.
+pub fn mentioned_paths(commands: &[&str]) -> Vec<String> {
+    let home = std::env::var("HOME").ok();
+    let mut paths: Vec<String> = Vec::new();
+    for command in commands {
+        for word in command.split(|c: char| c.is_whitespace() || ";|&()".contains(c)) {
+            let word = word
+                .trim_start_matches(['>', '<', '2', '1'])
+                .trim_matches(['"', '\'', ','])
+                .trim_end_matches('/');
+            let word = match word.split_once('=') {
+                // `--config=/etc/x.conf`, `of=/etc/x`
+                Some((_, value)) if value.starts_with(['/', '~']) => value,
+                _ => word,
+            };
+            let path = match (word.strip_prefix("~/"), &home) {
+                (Some(rest), Some(home)) => format!("{}/{}", home.trim_end_matches('/'), rest),
+                _ => word.to_string(),
+            };
+            let plain = !path.contains(['*', '?', '[', '$', '`', '{']);
+            let tracked = !UNTRACKED_ROOTS.iter().any(|root| format!("{}/", path).starts_with(root));
+            if path.len() > 1 && path.starts_with('/') && plain && tracked && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+/// The content hash of `path`, or its size and mtime when it is large;
+/// `None` when it doesn't exist or can't be read.
+fn file_hash(path: &Path) -> Option<String> {
+    let meta = std::fs::metadata(path).ok()?;
+    if meta.len() > MAX_HASHED_BYTES {
+        let mtime = meta
+            .modified()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?;
+        return Some(format!("size {} mtime {}", meta.len(), mtime.as_secs()));
+    }
+    let content = std::fs::read(path).ok()?;
+    Some(format!("{:x}", md5::compute(content)))
+}
+
+/// The files to hash for `paths`: files themselves, and the files
+/// directly inside directories, up to [`MAX_FILES`].
+fn tracked_files(paths: &[String]) -> Vec<String> {
+    let mut files = Vec::new();
+    for path in paths {
+        let p = Path::new(path);
+        if p.is_dir() {
+            let mut inside: Vec<String> = std::fs::read_dir(p)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+                .map(|entry| entry.path().to_string_lossy().to_string())
+                .collect();
+            inside.sort();
+            files.extend(inside.into_iter().take(FILES_PER_DIR));
+        } else if !p.exists() || p.is_file() {
+            files.push(path.clone());
+        }
+    }
+    files.dedup();
+    files.truncate(MAX_FILES);
+    files
+}
+
+impl EnvSnapshot {
+    /// Snapshot the system, hashing the files `paths` name (see
+    /// [`mentioned_paths`]); files that appear only afterwards are found by
+    /// taking the second snapshot with the same paths.
+    pub fn capture(paths: &[String]) -> Self {
+        Self {
+            packages: packages(),
+            services: services(),
+            files: tracked_files(paths)
+                .into_iter()
+                .map(|file| {
+                    let hash = file_hash(Path::new(&file));
+                    (file, hash)
+                })
+                .collect(),
+        }
+    }
+
+    /// What changed from `self` to `after`, one line per change.
+    pub fn changes(&self, after: &EnvSnapshot) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let (Some(before), Some(now)) = (&self.packages, &after.packages) {
+            for (name, version) in now {
+                match before.get(name) {
+                    None => lines.push(format!("installed {} {}", name, version)),
+                    Some(old) if old != version => {
+                        lines.push(format!("upgraded {} {} -> {}", name, old, version))
+                    }
+                    _ => {}
+                }
+            }
+            for (name, version) in before {
+                if !now.contains_key(name) {
+                    lines.push(format!("removed {} {}", name, version));
+                }
+            }
+        }
+        if let (Some(before), Some(now)) = (&self.services, &after.services) {
+            lines.extend(now.difference(before).map(|unit| format!("enabled {}", unit)));
+            lines.extend(before.difference(now).map(|unit| format!("disabled {}", unit)));
+        }
+        let files: BTreeSet<&String> = self.files.keys().chain(after.files.keys()).collect();
+        for file in files {
+            let old = self.files.get(file).cloned().flatten();
+            let new = after.files.get(file).cloned().flatten();
+            match (old, new) {
+                (None, Some(_)) => lines.push(format!("created {}", file)),
+                (Some(_), None) => lines.push(format!("deleted {}", file)),
+                (Some(old), Some(new)) if old != new => lines.push(format!("changed {}", file)),
+                _ => {}
+            }
+        }
+        lines
+    }
+}
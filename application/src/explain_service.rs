@@ -1,13 +1,193 @@
+use crate::summary_service::split_chunks;
+use anyhow::anyhow;
+use docx_rs::{read_docx, DocumentChild};
+use infrastructure::ollama_client::OllamaClient;
 use shared::types::Result;
+use std::future::Future;
+use std::path::Path;
 
-pub struct ExplainService;
+/// Characters of document text sent to the model per request.
+const CHUNK_CHARS: usize = 12_000;
+
+/// The result of explaining a document; `complete` is false when it was
+/// cancelled and `text` holds only the notes on the parts read so far.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub text: String,
+    pub complete: bool,
+    pub parts_done: usize,
+    pub parts_total: usize,
+}
+
+/// Explains documents too large for one request map-reduce style: each part
+/// is summarized on its own, then the notes are merged into one explanation.
+pub struct ExplainService {
+    client: OllamaClient,
+}
 
 impl ExplainService {
-    pub fn new() -> Self {
-        Self
+    pub fn new(client: OllamaClient) -> Self {
+        Self { client }
+    }
+
+    /// Explain `content`, calling `progress(done, total)` as parts finish.
+    /// If `cancel` resolves first, the notes gathered so far are returned
+    /// instead of an error.
+    pub async fn explain(
+        &self,
+        name: &str,
+        content: &str,
+        mut progress: impl FnMut(usize, usize),
+        cancel: impl Future<Output = ()>,
+    ) -> Result<Explanation> {
+        tokio::pin!(cancel);
+        let chunks = split_chunks(content, CHUNK_CHARS);
+        let total = chunks.len();
+        if total <= 1 {
+            let prompt = format!("Explain this content in detail:\n\n{}", content);
+            return tokio::select! {
+                text = self.client.generate_response(&prompt) => Ok(Explanation {
+                    text: text?,
+                    complete: true,
+                    parts_done: total,
+                    parts_total: total,
+                }),
+                _ = &mut cancel => Ok(partial(Vec::new(), total)),
+            };
+        }
+
+        let mut notes = Vec::with_capacity(total);
+        progress(0, total);
+        for (i, chunk) in chunks.iter().enumerate() {
+            let prompt = format!(
+                "This is part {} of {} of the document `{}`. Summarize what this part covers as short bullet points, keeping names, figures and definitions a reader would need.\n\nDocument part:\n{}",
+                i + 1,
+                total,
+                name,
+                chunk
+            );
+            tokio::select! {
+                note = self.client.generate_response(&prompt) => notes.push(note?),
+                _ = &mut cancel => return Ok(partial(notes, total)),
+            }
+            progress(i + 1, total);
+        }
+
+        let prompt = format!(
+            "Below are notes taken from consecutive parts of the document `{}`. Using them, explain the document in detail: what it is about, its structure, and its key points.\n\n{}",
+            name,
+            numbered(&notes)
+        );
+        tokio::select! {
+            text = self.client.generate_response(&prompt) => Ok(Explanation {
+                text: text?,
+                complete: true,
+                parts_done: total,
+                parts_total: total,
+            }),
+            _ = &mut cancel => Ok(partial(notes, total)),
+        }
+    }
+}
+
+fn partial(notes: Vec<String>, total: usize) -> Explanation {
+    Explanation {
+        text: numbered(&notes),
+        complete: false,
+        parts_done: notes.len(),
+        parts_total: total,
+    }
+}
+
+fn numbered(notes: &[String]) -> String {
+    notes
+        .iter()
+        .enumerate()
+        .map(|(i, n)| format!("Part {}:\n{}", i + 1, n))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Text of the document at `path`: a PDF (only the `pages` selected, if
+/// given), the paragraphs of a DOCX file, or anything else read as text.
+pub fn load_document(path: &Path, pages: Option<&str>) -> Result<String> {
+    let file = path.display();
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+    if pages.is_some() && ext.as_deref() != Some("pdf") {
+        return Err(anyhow!("A page selection only applies to PDF files."));
+    }
+    match ext.as_deref() {
+        Some("pdf") => read_pdf(path, pages)
+            .map_err(|e| anyhow!("Cannot extract text from PDF '{}': {}", file, e)),
+        Some("docx") => {
+            let bytes = std::fs::read(path)
+                .map_err(|e| anyhow!("Cannot read DOCX file '{}': {}", file, e))?;
+            let docx = read_docx(&bytes).map_err(|e| anyhow!("Cannot parse DOCX '{}': {}", file, e))?;
+            let mut text = String::new();
+            for child in &docx.document.children {
+                match child {
+                    DocumentChild::Paragraph(p) => {
+                        text.push_str(&p.raw_text());
+                        text.push('\n');
+                    }
+                    // Table cells are not extracted yet
+                    DocumentChild::Table(_) => text.push_str("[Table content not extracted]\n"),
+                    _ => {}
+                }
+            }
+            Ok(text)
+        }
+        _ => std::fs::read_to_string(path).map_err(|_| {
+            anyhow!(
+                "Cannot read file '{}' as text. Supported formats: text files, PDF, DOCX.",
+                file
+            )
+        }),
     }
+}
+
+fn read_pdf(path: &Path, pages: Option<&str>) -> Result<String> {
+    let Some(selection) = pages else {
+        return Ok(pdf_extract::extract_text(path)?);
+    };
+    let texts = pdf_extract::extract_text_by_pages(path)?;
+    let selected = select_pages(selection, texts.len()).ok_or_else(|| {
+        anyhow!(
+            "invalid page selection '{}' for a {}-page document",
+            selection,
+            texts.len()
+        )
+    })?;
+    Ok(selected
+        .into_iter()
+        .map(|i| texts[i].as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n"))
+}
 
-    pub async fn explain_file(&self, _file_path: &str) -> Result<String> {
-        Ok("Explanation not implemented".to_string())
+/// Resolve a page selection like `3`, `2-5`, `7-` or `1,4-6` against a
+/// document of `page_count` pages into sorted 0-based page indexes. `None`
+/// if the selection is malformed or names a page past the end.
+pub fn select_pages(selection: &str, page_count: usize) -> Option<Vec<usize>> {
+    let mut pages = Vec::new();
+    for part in selection.split(',').map(str::trim) {
+        let (start, end) = match part.split_once('-') {
+            Some((start, "")) => (start.trim().parse().ok()?, page_count),
+            Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+            None => {
+                let page = part.parse().ok()?;
+                (page, page)
+            }
+        };
+        if start == 0 || start > end || end > page_count {
+            return None;
+        }
+        pages.extend(start - 1..end);
     }
+    pages.sort_unstable();
+    pages.dedup();
+    Some(pages)
 }
@@ -0,0 +1,167 @@
+//! The paths a destructive command's globs match (`rm -rf build/*`,
+//! `chmod -R 644 *.conf`), expanded mechanically before it is confirmed so
+//! the user sees what will be hit. Patterns are expanded by bash as data,
+//! never as code, and never by the model.
+
+use crate::env_review::{program_words, split_segments};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Commands whose glob arguments are previewed.
+const DESTRUCTIVE_PROGRAMS: &[&str] = &["rm", "mv", "chmod", "chown", "chgrp"];
+/// Programs taking a mode or owner before the paths.
+const TAKES_SPEC: &[&str] = &["chmod", "chown", "chgrp"];
+/// Entries counted under the directories of a recursive operation.
+const MAX_COUNTED: usize = 100_000;
+/// Paths listed per glob.
+pub const SHOWN_PATHS: usize = 10;
+
+/// A glob argument of a destructive command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobTarget {
+    pub program: String,
+    pub pattern: String,
+    /// The command descends into directories (`rm -r`, `chmod -R`).
+    pub recursive: bool,
+}
+
+/// What a [`GlobTarget`] matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobPreview {
+    pub target: GlobTarget,
+    /// Matched paths, sorted as bash sorts them.
+    pub matches: Vec<String>,
+    /// Paths affected: the matches plus, when recursive, everything below
+    /// them.
+    pub total: usize,
+    /// Counting stopped at [`MAX_COUNTED`].
+    pub capped: bool,
+}
+
+/// A word bash would glob: it has `*`, `?` or `[` and no quoting,
+/// escaping or expansion of its own, which leaves it for the real run.
+fn is_plain_glob(word: &str) -> bool {
+    word.contains(['*', '?', '['])
+        && !word.contains(['\'', '"', '\\', '$', '`', '{', '(', '<', '>'])
+}
+
+fn is_recursive(program: &str, option: &str) -> bool {
+    match option {
+        "--recursive" => true,
+        option if option.starts_with("--") => false,
+        option if program == "rm" => option.contains(['r', 'R']),
+        option => option.contains('R'),
+    }
+}
+
+/// Glob arguments of the rm, mv, chmod, chown and chgrp invocations in
+/// `cmd`. Segments after a `cd` are skipped: their directory isn't known.
+pub fn destructive_globs(cmd: &str) -> Vec<GlobTarget> {
+    let mut targets = Vec::new();
+    for segment in split_segments(cmd) {
+        let mut words = program_words(&segment).iter().map(String::as_str);
+        let Some(program) = words.next().map(|p| p.rsplit('/').next().unwrap_or(p)) else {
+            continue;
+        };
+        if program == "cd" || program == "pushd" {
+            break;
+        }
+        if !DESTRUCTIVE_PROGRAMS.contains(&program) {
+            continue;
+        }
+        let mut recursive = false;
+        let mut options_done = false;
+        let mut spec_pending = TAKES_SPEC.contains(&program);
+        let mut patterns = Vec::new();
+        for word in words {
+            if !options_done && word == "--" {
+                options_done = true;
+            } else if !options_done && word.starts_with('-') && word.len() > 1 {
+                recursive |= is_recursive(program, word);
+                // `--reference=file` stands in for the mode or owner.
+                spec_pending &= !word.starts_with("--reference");
+            } else if spec_pending {
+                spec_pending = false;
+            } else if is_plain_glob(word) {
+                patterns.push(word.to_string());
+            }
+        }
+        targets.extend(patterns.into_iter().map(|pattern| GlobTarget {
+            program: program.to_string(),
+            pattern,
+            recursive,
+        }));
+    }
+    targets
+}
+
+/// The paths `pattern` matches in `dir`, as bash would expand it there.
+/// The pattern is passed to bash as `$1`, so it is globbed but never run;
+/// a leading `~/` is expanded first.
+pub fn expand_glob(pattern: &str, dir: &Path) -> Vec<String> {
+    let pattern = match (pattern.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home.trim_end_matches('/'), rest),
+        _ => pattern.to_string(),
+    };
+    let output = Command::new("bash")
+        .args(["--noprofile", "--norc", "-c"])
+        .arg(r#"shopt -s nullglob; IFS=; for p in $1; do printf '%s\0' "$p"; done"#)
+        .arg("bash")
+        .arg(&pattern)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Entries below `path` when it is a directory (symlinks aren't
+/// followed), counting on from `count` up to [`MAX_COUNTED`].
+fn count_below(path: &Path, count: &mut usize) {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if *count >= MAX_COUNTED {
+            return;
+        }
+        *count += 1;
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            count_below(&entry.path(), count);
+        }
+    }
+}
+
+/// A preview of every glob argument of the destructive commands in `cmd`,
+/// run in `dir`.
+pub fn preview(cmd: &str, dir: &Path) -> Vec<GlobPreview> {
+    destructive_globs(cmd)
+        .into_iter()
+        .map(|target| {
+            let matches = expand_glob(&target.pattern, dir);
+            let mut total = matches.len();
+            if target.recursive {
+                for path in &matches {
+                    let path = dir.join(path);
+                    let is_dir = std::fs::symlink_metadata(&path).is_ok_and(|m| m.is_dir());
+                    if is_dir {
+                        count_below(&path, &mut total);
+                    }
+                }
+            }
+            GlobPreview {
+                capped: total >= MAX_COUNTED,
+                target,
+                matches,
+                total,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,108 @@
+use crate::ask_service::looks_like_question;
+use infrastructure::ollama_client::OllamaClient;
+use shared::types::Result;
+use shared::utils::is_supported_file;
+use std::path::Path;
+
+/// What the user wants done with their input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intent {
+    /// A single shell command.
+    Command,
+    /// A prose answer to a general question.
+    Question,
+    /// An answer about, or change to, the code in this project (RAG).
+    Code,
+    /// A multi-step task for agent mode.
+    Plan,
+}
+
+impl Intent {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().trim_matches(|c: char| !c.is_alphabetic()).to_lowercase().as_str() {
+            "command" => Some(Self::Command),
+            "question" => Some(Self::Question),
+            "code" => Some(Self::Code),
+            "plan" => Some(Self::Plan),
+            _ => None,
+        }
+    }
+}
+
+/// Phrases that point at the code of the current project.
+const CODE_MARKERS: &[&str] = &[
+    "this codebase", "the codebase", "this project", "this repo", "the repo", "in the code",
+    "in our code", "this crate", "this module", "this function", "refactor", "which file",
+    "implemented", "defined",
+];
+/// Phrases joining several steps of a larger task.
+const PLAN_MARKERS: &[&str] = &[
+    " and then ", ", then ", " then ", "step by step", "set up ", "setup ", "install and ",
+    " and configure ", " and enable ", " and start ", " afterwards ", " finally ",
+];
+
+/// Decides which mode handles free-form input, so users don't have to pick
+/// `--agent`, `--rag` or `ask` themselves. Clear cases are settled by
+/// heuristics; the rest go to the model when one is set with
+/// [`Self::with_model`], and are treated as a command otherwise.
+#[derive(Default)]
+pub struct IntentRouter {
+    client: Option<OllamaClient>,
+}
+
+impl IntentRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_model(mut self, client: OllamaClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub async fn route(&self, input: &str) -> Result<Intent> {
+        if let Some(intent) = classify(input) {
+            return Ok(intent);
+        }
+        let Some(client) = &self.client else {
+            return Ok(Intent::Command);
+        };
+        let prompt = format!(
+            "Classify this request to a terminal assistant as one word:\n\
+command - a single shell command would do it\n\
+question - a general question wanting an explanation\n\
+code - a question about or change to the code of the current project\n\
+plan - a task needing several commands in order\n\n\
+Request: {}\n\nReply with only the word.",
+            input
+        );
+        let reply = client.generate_response(&prompt).await?;
+        Ok(reply
+            .split_whitespace()
+            .find_map(Intent::parse)
+            .unwrap_or(Intent::Command))
+    }
+}
+
+/// The intent `input` clearly shows, if any.
+pub fn classify(input: &str) -> Option<Intent> {
+    let lower = format!(" {} ", input.trim().to_lowercase());
+    if lower.trim().is_empty() {
+        return None;
+    }
+    let names_source_file = input
+        .split(|c: char| c.is_whitespace() || "`'\"(),".contains(c))
+        .map(|word| word.trim_end_matches(['.', ':', '?']))
+        .any(|word| word.contains('/') && is_supported_file(Path::new(word)));
+    let names_code_path = input.contains("::");
+    if names_source_file || names_code_path || CODE_MARKERS.iter().any(|m| lower.contains(m)) {
+        return Some(Intent::Code);
+    }
+    if PLAN_MARKERS.iter().any(|m| lower.contains(m)) {
+        return Some(Intent::Plan);
+    }
+    if looks_like_question(input) {
+        return Some(Intent::Question);
+    }
+    None
+}
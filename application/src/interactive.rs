@@ -0,0 +1,133 @@
+//! Whether a command will want the user at the keyboard: a full-screen
+//! program, an editor, an interactive session or a prompt for
+//! confirmation. With its output captured such a command seems to hang,
+//! since a prompt without a newline is never shown, so it is run attached
+//! to the terminal instead.
+
+use crate::env_review::{program_words, split_pipeline};
+
+/// Programs that take over the terminal.
+const FULL_SCREEN: &[&str] = &[
+    "vim", "vi", "nvim", "vimdiff", "nano", "emacs", "less", "more", "most", "man", "top", "htop",
+    "btop", "atop", "watch", "tmux", "screen", "mc", "ranger", "nnn", "ncdu", "tig", "lazygit",
+    "k9s", "fzf",
+];
+/// Programs that always ask for something: a password, a passphrase or a
+/// remote shell.
+const PROMPTING: &[&str] = &[
+    "ssh", "mosh", "telnet", "sftp", "ftp", "su", "passwd", "login", "ssh-copy-id", "visudo",
+    "sudoedit", "read",
+];
+/// Programs that start a session when given nothing to run, with the
+/// options that give them something (a script or a command).
+const SESSIONS: &[(&str, &[&str])] = &[
+    ("python", &["-c", "-m"]),
+    ("python3", &["-c", "-m"]),
+    ("ipython", &["-c", "-m"]),
+    ("node", &["-e", "--eval", "-p", "--print"]),
+    ("irb", &[]),
+    ("psql", &["-c", "--command", "-f", "--file", "-l", "--list"]),
+    ("mysql", &["-e", "--execute"]),
+    ("sqlite3", &[]),
+    ("mongosh", &["--eval", "-f", "--file"]),
+    ("bash", &["-c"]),
+    ("sh", &["-c"]),
+    ("zsh", &["-c"]),
+];
+
+/// Why `cmd` needs the user at the keyboard, e.g. "apt asks for
+/// confirmation"; `None` if it runs on its own. Input given by a
+/// redirection (`psql db < dump.sql`) or a pipe counts as an answer.
+pub fn interaction(cmd: &str) -> Option<String> {
+    for (segment, piped) in split_pipeline(cmd) {
+        let words: Vec<&str> = segment
+            .iter()
+            .map(|w| w.trim_matches(['"', '\'']))
+            .collect();
+        let Some((program, args)) = program_words(&words).split_first() else {
+            continue;
+        };
+        let program = program.rsplit('/').next().unwrap_or(program);
+        let fed = piped || segment.iter().any(|w| w.starts_with('<'));
+        if let Some(reason) = needs_user(program, args, fed) {
+            return Some(reason);
+        }
+    }
+    None
+}
+
+fn needs_user(program: &str, args: &[&str], fed: bool) -> Option<String> {
+    // A flag, as its own word or as `--flag=value`.
+    let has = |flags: &[&str]| {
+        args.iter().any(|a| {
+            flags.contains(a) || a.split_once('=').is_some_and(|(flag, _)| flags.contains(&flag))
+        })
+    };
+    // A letter in a bundle of short options (`-it`).
+    let short = |flag: char| {
+        args.iter()
+            .any(|a| a.starts_with('-') && !a.starts_with("--") && a[1..].contains(flag))
+    };
+    if FULL_SCREEN.contains(&program) {
+        return Some(format!("{} takes over the terminal", program));
+    }
+    if PROMPTING.contains(&program) {
+        return Some(format!("{} asks for input", program));
+    }
+    if let Some((_, runs)) = SESSIONS.iter().find(|(name, _)| *name == program) {
+        let operands = args.iter().filter(|a| !a.starts_with('-')).count();
+        // sqlite3 takes the statement after the database; psql and mysql
+        // take a database, not a script, as their operand.
+        let given = match program {
+            "sqlite3" => operands > 1,
+            "psql" | "mysql" | "irb" => has(runs),
+            _ => has(runs) || operands > 0,
+        };
+        return (!given && !fed).then(|| format!("{} starts an interactive session", program));
+    }
+    let confirms = match program {
+        "apt" | "apt-get" | "aptitude" => {
+            args.iter().any(|a| {
+                ["install", "remove", "purge", "upgrade", "dist-upgrade", "full-upgrade", "autoremove"].contains(a)
+            }) && !has(&["-y", "--yes", "--assume-yes"])
+                && !short('y')
+        }
+        "dnf" | "yum" | "zypper" => {
+            args.iter().any(|a| ["install", "remove", "erase", "upgrade", "update", "in", "rm", "up"].contains(a))
+                && !has(&["-y", "--assumeyes", "-n", "--non-interactive", "--assumeno"])
+        }
+        "pacman" => {
+            args.iter().any(|a| a.starts_with("-S") || a.starts_with("-R") || a.starts_with("-U"))
+                && !has(&["--noconfirm"])
+        }
+        "rm" | "cp" | "mv" | "ln" => short('i') || has(&["--interactive"]),
+        _ => false,
+    };
+    if confirms && !fed {
+        return Some(format!("{} asks for confirmation", program));
+    }
+    let edits = match (program, args) {
+        ("git", ["commit", rest @ ..]) => !rest.iter().any(|a| {
+            ["--no-edit", "--fixup", "-F", "--file", "-C", "--reuse-message"].contains(a)
+                || a.starts_with("--message")
+                || a.starts_with("--fixup=")
+                || (a.starts_with('-') && !a.starts_with("--") && a.contains('m'))
+        }),
+        ("git", ["rebase", rest @ ..]) => rest.iter().any(|a| *a == "-i" || *a == "--interactive"),
+        ("git", ["add", rest @ ..]) => rest.iter().any(|a| ["-p", "-i", "--patch", "--interactive"].contains(a)),
+        ("crontab", _) => short('e'),
+        ("systemctl", [verb, ..]) | ("kubectl", [verb, ..]) => *verb == "edit",
+        _ => false,
+    };
+    if edits {
+        return Some(format!("{} opens an editor", program));
+    }
+    let attaches = match (program, args) {
+        ("docker" | "podman", [verb, ..]) if ["run", "exec"].contains(verb) => {
+            short('i') || short('t') || has(&["--interactive", "--tty"])
+        }
+        ("kubectl", ["exec" | "attach" | "run", ..]) => short('i') || short('t') || has(&["--stdin", "--tty"]),
+        _ => false,
+    };
+    attaches.then(|| format!("{} attaches to a container's terminal", program))
+}
@@ -1,4 +1,35 @@
 pub mod agent_service;
+pub mod alias_service;
+pub mod ask_service;
+pub mod cache_check;
+pub mod command_planner;
+pub mod command_rules;
+pub mod context_report;
+pub mod credential_prompt;
+pub mod diff_explain_service;
+pub mod disk_guard;
+pub mod doc_gen_service;
+pub mod docs_service;
+pub mod env_review;
+pub mod env_snapshot;
 pub mod explain_service;
+pub mod glob_preview;
+pub mod intent_router;
+pub mod interactive;
+pub mod multi_host;
+pub mod network_guard;
+pub mod package_preview;
+pub mod plan_parsing;
+pub mod plan_review_service;
+pub mod prompt_guard;
 pub mod rag_service;
+pub mod read_only;
+pub mod recall_service;
+pub mod redaction;
+pub mod review_service;
 pub mod safety_service;
+pub mod service_impact;
+pub mod summary_service;
+pub mod test_gen_service;
+pub mod watch_service;
+pub mod working_dir;
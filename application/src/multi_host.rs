@@ -0,0 +1,157 @@
+//! Agent plans run on several hosts from the SSH inventory ("update nginx on
+//! web1 and web2"): which hosts a task targets, how each step reaches a host
+//! over `ssh`, how each host's steps are confirmed, and the step × host
+//! status matrix shown as the plan runs.
+
+use shared::utils::shell_quote;
+use infrastructure::ssh_inventory::SshHost;
+
+/// How the steps of a plan are confirmed on one host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostConfirm {
+    /// Every step is confirmed, as when running locally. These hosts run
+    /// one at a time.
+    Step,
+    /// The approved plan runs without further questions; steps the safety
+    /// check blocks or rates above mutating are left out. These hosts may
+    /// run in parallel.
+    Plan,
+}
+
+/// The inventory hosts `task` names, in inventory order.
+pub fn mentioned_hosts(task: &str, inventory: &[SshHost]) -> Vec<String> {
+    let words: Vec<&str> = task
+        .split(|c: char| c.is_whitespace() || ",;()'\"".contains(c))
+        .map(|word| word.trim_end_matches(['.', ':', '?', '!']))
+        .filter(|word| !word.is_empty())
+        .collect();
+    inventory
+        .iter()
+        .filter(|host| words.iter().any(|word| word.eq_ignore_ascii_case(&host.alias)))
+        .map(|host| host.alias.clone())
+        .collect()
+}
+
+/// Comma-separated host names, e.g. `web1,web2`, without blanks or repeats.
+pub fn parse_hosts(list: &str) -> Vec<String> {
+    let mut hosts: Vec<String> = Vec::new();
+    for host in list.split(',').map(str::trim).filter(|h| !h.is_empty()) {
+        if !hosts.iter().any(|h| h == host) {
+            hosts.push(host.to_string());
+        }
+    }
+    hosts
+}
+
+/// `cmd` run on `host` through `ssh`. With `batch`, ssh fails instead of
+/// asking for a password or passphrase, for hosts nobody is watching.
+pub fn remote_command(host: &str, cmd: &str, batch: bool) -> String {
+    let options = if batch { "-o BatchMode=yes " } else { "" };
+    format!("ssh {}{} -- {}", options, shell_quote(host), shell_quote(cmd))
+}
+
+/// The policy for `host`: [`HostConfirm::Plan`] if it matches one of
+/// `unattended` (a trailing `*` matches any suffix).
+pub fn confirm_policy(host: &str, unattended: &[String]) -> HostConfirm {
+    let matches = unattended.iter().map(|p| p.trim()).any(|pattern| match pattern.strip_suffix('*') {
+        Some(prefix) => host.starts_with(prefix),
+        None => host == pattern,
+    });
+    if matches {
+        HostConfirm::Plan
+    } else {
+        HostConfirm::Step
+    }
+}
+
+/// A note for the planning prompt: the steps run on `hosts`, not here.
+pub fn planning_note(hosts: &[String]) -> String {
+    format!(
+        "the plan runs on each of the remote hosts {} over SSH, every command in a fresh login shell in the home directory (so use absolute paths and don't rely on cd); write the commands as run on one host, without ssh",
+        hosts.join(", ")
+    )
+}
+
+/// Where a step stands on one host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    /// Skipped, declined, blocked or left for confirmation.
+    NotRun,
+}
+
+impl CellStatus {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Pending => "-",
+            Self::Running => "running",
+            Self::Succeeded => "ok",
+            Self::Failed => "failed",
+            Self::NotRun => "skipped",
+        }
+    }
+}
+
+/// The status of every step on every host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepMatrix {
+    hosts: Vec<String>,
+    /// `cells[step][host]`
+    cells: Vec<Vec<CellStatus>>,
+}
+
+impl StepMatrix {
+    pub fn new(steps: usize, hosts: &[String]) -> Self {
+        Self {
+            hosts: hosts.to_vec(),
+            cells: vec![vec![CellStatus::Pending; hosts.len()]; steps],
+        }
+    }
+
+    pub fn set(&mut self, step: usize, host: &str, status: CellStatus) {
+        let column = self.hosts.iter().position(|h| h == host);
+        if let Some(cell) = column.and_then(|c| self.cells.get_mut(step).and_then(|row| row.get_mut(c))) {
+            *cell = status;
+        }
+    }
+
+    pub fn get(&self, step: usize, host: &str) -> Option<CellStatus> {
+        let column = self.hosts.iter().position(|h| h == host)?;
+        self.cells.get(step)?.get(column).copied()
+    }
+
+    /// Hosts on which some step failed.
+    pub fn failed_hosts(&self) -> Vec<&str> {
+        self.hosts
+            .iter()
+            .enumerate()
+            .filter(|(c, _)| self.cells.iter().any(|row| row[*c] == CellStatus::Failed))
+            .map(|(_, host)| host.as_str())
+            .collect()
+    }
+
+    /// A table with a row per step and a column per host.
+    pub fn render(&self) -> Vec<String> {
+        let widths: Vec<usize> = self
+            .hosts
+            .iter()
+            .map(|host| host.chars().count().max("running".len()))
+            .collect();
+        let row = |label: String, cells: Vec<&str>| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            format!("{:<8}{}", label, cells.join("  ")).trim_end().to_string()
+        };
+        let mut lines = vec![row(String::new(), self.hosts.iter().map(String::as_str).collect())];
+        for (i, cells) in self.cells.iter().enumerate() {
+            lines.push(row(format!("step {}", i + 1), cells.iter().map(|c| c.label()).collect()));
+        }
+        lines
+    }
+}
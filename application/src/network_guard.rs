@@ -0,0 +1,335 @@
+//! Firewall and network changes (`ufw`, `iptables`, `nft`, `firewall-cmd`,
+//! interfaces taken down) that can cut off the session running them, and
+//! the revert window that guards them: the current state is saved, a
+//! rollback is scheduled with `at` or a systemd timer, and the user cancels
+//! it once they have checked they can still connect.
+
+use crate::env_review::{program_words, split_segments};
+
+/// ufw subcommands that change rules or whether the firewall is on.
+const UFW_CHANGES: &[&str] = &[
+    "enable", "disable", "reset", "default", "allow", "deny", "reject", "limit", "delete",
+    "insert", "prepend", "route", "reload",
+];
+/// iptables options that change a table.
+const IPTABLES_CHANGES: &[&str] = &[
+    "-A", "-I", "-D", "-R", "-F", "-P", "-X", "-N", "-Z", "-E", "--append", "--insert",
+    "--delete", "--replace", "--flush", "--policy", "--delete-chain", "--new-chain",
+    "--rename-chain",
+];
+/// nft subcommands that change the ruleset.
+const NFT_CHANGES: &[&str] = &[
+    "add", "insert", "delete", "flush", "replace", "create", "destroy", "rename",
+];
+/// Prefixes of firewall-cmd options that change zones or rules.
+const FIREWALLD_CHANGES: &[&str] = &[
+    "--add-", "--remove-", "--set-", "--change-", "--new-", "--delete-", "--reload",
+    "--complete-reload", "--panic-on", "--runtime-to-permanent",
+];
+/// Services whose restart drops and re-creates the network configuration.
+const NETWORK_SERVICES: &[&str] = &[
+    "networking", "network", "networkmanager", "systemd-networkd",
+];
+/// The here-document delimiter of the rollback script.
+const SCRIPT_END: &str = "VIBE_REVERT_END";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Firewall {
+    Ufw,
+    Iptables,
+    Nftables,
+    Firewalld,
+}
+
+impl Firewall {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Firewall::Ufw => "ufw",
+            Firewall::Iptables => "iptables",
+            Firewall::Nftables => "nftables",
+            Firewall::Firewalld => "firewalld",
+        }
+    }
+
+    /// Saves the rules into `$D`.
+    fn snapshot(&self) -> &'static str {
+        match self {
+            Firewall::Ufw => r#"cp -a /etc/ufw "$D/ufw""#,
+            Firewall::Iptables => {
+                r#"iptables-save > "$D/iptables.rules"; ip6tables-save > "$D/ip6tables.rules" 2>/dev/null || true"#
+            }
+            Firewall::Nftables => r#"nft list ruleset > "$D/nftables.rules""#,
+            Firewall::Firewalld => r#"cp -a /etc/firewalld "$D/firewalld""#,
+        }
+    }
+
+    /// Puts the rules saved by [`Firewall::snapshot`] back in force.
+    fn restore(&self) -> &'static str {
+        match self {
+            Firewall::Ufw => {
+                r#"cp -a "$D/ufw/." /etc/ufw/ && if grep -q '^ENABLED=yes' /etc/ufw/ufw.conf; then ufw --force enable; else ufw --force disable; fi"#
+            }
+            Firewall::Iptables => {
+                r#"iptables-restore < "$D/iptables.rules"; if [ -s "$D/ip6tables.rules" ]; then ip6tables-restore < "$D/ip6tables.rules"; fi"#
+            }
+            Firewall::Nftables => r#"nft flush ruleset && nft -f "$D/nftables.rules""#,
+            Firewall::Firewalld => r#"cp -a "$D/firewalld/." /etc/firewalld/ && firewall-cmd --reload"#,
+        }
+    }
+}
+
+/// A change to the firewall or network that can cut off the session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkChange {
+    Firewall(Firewall),
+    /// An interface or connection taken down, with the command that brings
+    /// it back up.
+    Down { undo: String },
+    /// A change with no automatic undo: addresses, routes, connection
+    /// profiles, network service restarts.
+    Other(&'static str),
+}
+
+impl NetworkChange {
+    /// Why the change is risky, for the confirmation.
+    pub fn risk(&self) -> String {
+        match self {
+            NetworkChange::Firewall(firewall) => format!(
+                "Changes {} rules: a rule or default policy that blocks your own connection locks you out of a remote machine.",
+                firewall.name()
+            ),
+            NetworkChange::Down { undo } => format!(
+                "Takes a network interface or connection down: if it carries your session, the machine becomes unreachable (undo: {}).",
+                undo
+            ),
+            NetworkChange::Other(risk) => risk.to_string(),
+        }
+    }
+}
+
+fn unquote(word: &str) -> &str {
+    word.trim_matches(['"', '\''])
+}
+
+/// `name` as one shell word.
+fn quoted(name: &str) -> String {
+    let plain = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_.:@/".contains(c));
+    match plain {
+        true => name.to_string(),
+        false => format!("'{}'", name.replace('\'', r"'\''")),
+    }
+}
+
+/// The firewall and network changes `cmd` makes, in order.
+pub fn network_changes(cmd: &str) -> Vec<NetworkChange> {
+    let mut changes = Vec::new();
+    for segment in split_segments(cmd) {
+        let words: Vec<&str> = segment
+            .iter()
+            .map(|w| unquote(w))
+            .collect();
+        let Some((program, args)) = program_words(&words).split_first() else {
+            continue;
+        };
+        let program = program.rsplit('/').next().unwrap_or(program);
+        let subcommand = args.iter().find(|a| !a.starts_with('-')).copied();
+        let change = match program {
+            "ufw" if subcommand.is_some_and(|s| UFW_CHANGES.contains(&s)) => {
+                Some(NetworkChange::Firewall(Firewall::Ufw))
+            }
+            p if p.starts_with("iptables") || p.starts_with("ip6tables") => {
+                let changes = p.ends_with("-restore")
+                    || (!p.ends_with("-save") && args.iter().any(|a| IPTABLES_CHANGES.contains(a)));
+                changes.then_some(NetworkChange::Firewall(Firewall::Iptables))
+            }
+            "nft" => {
+                let changes = subcommand.is_some_and(|s| NFT_CHANGES.contains(&s))
+                    || args.iter().any(|a| matches!(*a, "-f" | "--file"));
+                changes.then_some(NetworkChange::Firewall(Firewall::Nftables))
+            }
+            "firewall-cmd" => args
+                .iter()
+                .any(|a| FIREWALLD_CHANGES.iter().any(|p| a.starts_with(p)))
+                .then_some(NetworkChange::Firewall(Firewall::Firewalld)),
+            "ip" => ip_change(args),
+            "ifdown" => subcommand.map(|dev| NetworkChange::Down {
+                undo: format!("ifup {}", quoted(dev)),
+            }),
+            "nmcli" => nmcli_change(args),
+            "systemctl" => {
+                let restarts = matches!(subcommand, Some("stop" | "restart"));
+                let service = args.iter().any(|a| {
+                    NETWORK_SERVICES.contains(&a.trim_end_matches(".service").to_lowercase().as_str())
+                });
+                (restarts && service).then_some(NetworkChange::Other(
+                    "Stops or restarts the network service: every connection drops, and a broken configuration keeps it down.",
+                ))
+            }
+            _ => None,
+        };
+        if let Some(change) = change {
+            if !changes.contains(&change) {
+                changes.push(change);
+            }
+        }
+    }
+    changes
+}
+
+/// `ip link set [dev] X down`, and address or route removals.
+fn ip_change(args: &[&str]) -> Option<NetworkChange> {
+    let args: Vec<&str> = args.iter().copied().filter(|a| !a.starts_with('-')).collect();
+    match args.as_slice() {
+        ["link" | "l", "set", rest @ ..] if rest.contains(&"down") => {
+            let dev = rest.iter().find(|a| !matches!(**a, "dev" | "down"))?;
+            Some(NetworkChange::Down {
+                undo: format!("ip link set dev {} up", quoted(dev)),
+            })
+        }
+        ["addr" | "address" | "a", "del" | "delete" | "flush", ..] => Some(NetworkChange::Other(
+            "Removes IP addresses: the one your session uses makes the machine unreachable.",
+        )),
+        ["route" | "r", "del" | "delete" | "flush" | "replace" | "change", ..] => {
+            Some(NetworkChange::Other(
+                "Changes routes: losing the route back to you cuts off the session.",
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// `nmcli connection down X`, `nmcli device disconnect X`, and changes to
+/// connection profiles.
+fn nmcli_change(args: &[&str]) -> Option<NetworkChange> {
+    let args: Vec<&str> = args.iter().copied().filter(|a| !a.starts_with('-')).collect();
+    match args.as_slice() {
+        ["connection" | "con" | "c", "down", name, ..] => Some(NetworkChange::Down {
+            undo: format!("nmcli connection up {}", quoted(name)),
+        }),
+        ["device" | "dev" | "d", "disconnect" | "down", name, ..] => Some(NetworkChange::Down {
+            undo: format!("nmcli device connect {}", quoted(name)),
+        }),
+        ["connection" | "con" | "c", "modify" | "delete" | "reload", ..] => {
+            Some(NetworkChange::Other(
+                "Changes NetworkManager connection profiles, which can drop or re-address the connection you use.",
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// How a rollback is scheduled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheduler {
+    /// `at now + N minutes`; the job number cancels it with `atrm`.
+    At,
+    /// `systemd-run --on-active`; the unit name cancels it.
+    SystemdRun,
+}
+
+/// How to save the current state and put it back, for the changes of a
+/// command that can be undone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevertPlan {
+    /// Directory (root-owned) holding the saved state and the rollback.
+    pub dir: String,
+    /// What the rollback restores, e.g. `iptables rules`.
+    pub covers: Vec<String>,
+    /// Changes the rollback can't undo.
+    pub uncovered: Vec<String>,
+    snapshots: Vec<String>,
+    restores: Vec<String>,
+}
+
+/// The revert plan for `changes`, saving state into `dir`; `None` when
+/// none of them can be undone automatically.
+pub fn revert_plan(changes: &[NetworkChange], dir: &str) -> Option<RevertPlan> {
+    let mut plan = RevertPlan {
+        dir: dir.to_string(),
+        covers: Vec::new(),
+        uncovered: Vec::new(),
+        snapshots: Vec::new(),
+        restores: Vec::new(),
+    };
+    for change in changes {
+        match change {
+            NetworkChange::Firewall(firewall) => {
+                plan.covers.push(format!("{} rules", firewall.name()));
+                plan.snapshots.push(firewall.snapshot().to_string());
+                plan.restores.push(firewall.restore().to_string());
+            }
+            NetworkChange::Down { undo } => {
+                plan.covers.push(format!("`{}`", undo));
+                plan.restores.push(undo.clone());
+            }
+            NetworkChange::Other(risk) => plan.uncovered.push(risk.to_string()),
+        }
+    }
+    (!plan.restores.is_empty()).then_some(plan)
+}
+
+impl RevertPlan {
+    /// The rollback script.
+    pub fn rollback_path(&self) -> String {
+        format!("{}/rollback.sh", self.dir)
+    }
+
+    /// A script (run as root) that saves the current state into
+    /// [`RevertPlan::dir`] and writes the rollback next to it.
+    pub fn prepare_script(&self) -> String {
+        let mut script = format!("set -e\nD='{}'\nmkdir -m 700 \"$D\"\n", self.dir);
+        for snapshot in &self.snapshots {
+            script.push_str(snapshot);
+            script.push('\n');
+        }
+        script.push_str(&format!("cat > \"$D/rollback.sh\" <<'{}'\n", SCRIPT_END));
+        script.push_str(&format!("D='{}'\n", self.dir));
+        // Restores run in reverse: the last change made is undone first.
+        for restore in self.restores.iter().rev() {
+            script.push_str(restore);
+            script.push('\n');
+        }
+        script.push_str(SCRIPT_END);
+        script.push('\n');
+        script
+    }
+
+    /// A script that schedules the rollback in `minutes`; `unit` names the
+    /// systemd timer. For `at` the job number is in its output, see
+    /// [`parse_at_job`].
+    pub fn schedule_script(&self, scheduler: Scheduler, minutes: u64, unit: &str) -> String {
+        match scheduler {
+            Scheduler::At => format!(
+                "echo 'sh {}' | at now + {} minutes 2>&1",
+                self.rollback_path(),
+                minutes
+            ),
+            Scheduler::SystemdRun => format!(
+                "systemd-run --quiet --unit={} --on-active={}min sh {}",
+                unit,
+                minutes,
+                self.rollback_path()
+            ),
+        }
+    }
+}
+
+/// The script that cancels a scheduled rollback, given the `at` job
+/// number or the systemd unit.
+pub fn cancel_script(scheduler: Scheduler, job: &str) -> String {
+    match scheduler {
+        Scheduler::At => format!("atrm {}", job),
+        Scheduler::SystemdRun => format!("systemctl stop {}.timer", job),
+    }
+}
+
+/// The job number `at` reports, as in `job 7 at Fri Oct 16 12:05:00 2026`.
+pub fn parse_at_job(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let rest = line.trim().strip_prefix("job ")?;
+        let number = rest.split_whitespace().next()?;
+        number.chars().all(|c| c.is_ascii_digit()).then(|| number.to_string())
+    })
+}
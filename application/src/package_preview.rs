@@ -0,0 +1,327 @@
+//! The transaction a package-manager step would carry out, previewed with
+//! the manager's own dry run (`apt-get -s`, `dnf --assumeno`, `pacman -p`)
+//! before the real step is confirmed. The dry-run command is derived from
+//! the step mechanically, never by the model, and runs without a shell.
+
+use shared::utils::shell_quote;
+use crate::env_review::split_segments;
+use std::fmt;
+
+/// Packages listed per kind of change before the rest is counted.
+const SHOWN_PACKAGES: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManager {
+    Apt,
+    Dnf,
+    Pacman,
+}
+
+impl PackageManager {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PackageManager::Apt => "apt-get",
+            PackageManager::Dnf => "dnf",
+            PackageManager::Pacman => "pacman",
+        }
+    }
+}
+
+/// What a transaction installs, upgrades and removes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageTransaction {
+    pub install: Vec<String>,
+    pub upgrade: Vec<String>,
+    pub remove: Vec<String>,
+    /// e.g. `12.3 MB`, when the manager reports it.
+    pub download: Option<String>,
+    /// Disk space used (`+40 MB`) or freed (`-3 MB`), when reported.
+    pub disk: Option<String>,
+}
+
+impl PackageTransaction {
+    pub fn is_empty(&self) -> bool {
+        self.install.is_empty() && self.upgrade.is_empty() && self.remove.is_empty()
+    }
+
+    /// One line per kind of change, then the sizes.
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (label, packages) in [
+            ("install", &self.install),
+            ("upgrade", &self.upgrade),
+            ("remove", &self.remove),
+        ] {
+            if packages.is_empty() {
+                continue;
+            }
+            let mut shown = packages
+                .iter()
+                .take(SHOWN_PACKAGES)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ");
+            if packages.len() > SHOWN_PACKAGES {
+                shown.push_str(&format!(" and {} more", packages.len() - SHOWN_PACKAGES));
+            }
+            lines.push(format!("{} ({}): {}", label, packages.len(), shown));
+        }
+        let sizes: Vec<String> = [("download", &self.download), ("disk", &self.disk)]
+            .into_iter()
+            .filter_map(|(label, size)| size.as_ref().map(|size| format!("{} {}", label, size)))
+            .collect();
+        if !sizes.is_empty() {
+            lines.push(sizes.join("; "));
+        }
+        lines
+    }
+}
+
+/// A package manager's dry run: a program and its arguments, run directly
+/// rather than through a shell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DryRun {
+    pub manager: PackageManager,
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl DryRun {
+    fn new(manager: PackageManager, program: &str, args: Vec<String>) -> Self {
+        Self {
+            manager,
+            program: program.to_string(),
+            args,
+        }
+    }
+
+    /// Whether this is a pacman listing of packages to remove.
+    pub fn removes(&self) -> bool {
+        self.manager == PackageManager::Pacman && self.args.iter().any(|a| a.starts_with("-R"))
+    }
+}
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            match arg.contains(char::is_whitespace) {
+                true => write!(f, " {}", shell_quote(arg))?,
+                false => write!(f, " {}", arg)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Shell syntax that would make a word more than a plain argument. A step
+/// using any of it gets no preview.
+const SHELL_SYNTAX: &[&str] = &["$", "`", "<(", ">", "|", "\\"];
+
+/// `word` without its surrounding quotes; `None` if it holds shell syntax
+/// or quotes anywhere else.
+fn plain_word(word: &str) -> Option<&str> {
+    let unquoted = match word.as_bytes() {
+        [b'\'', .., b'\''] | [b'"', .., b'"'] if word.len() >= 2 => &word[1..word.len() - 1],
+        _ => word,
+    };
+    let plain = !SHELL_SYNTAX.iter().any(|syntax| word.contains(syntax)) && !unquoted.contains(['\'', '"']);
+    plain.then_some(unquoted)
+}
+
+/// apt and apt-get subcommands that change packages.
+const APT_CHANGES: &[&str] = &[
+    "install", "remove", "purge", "autoremove", "upgrade", "dist-upgrade", "full-upgrade",
+    "reinstall",
+];
+/// dnf and yum subcommands that change packages.
+const DNF_CHANGES: &[&str] = &[
+    "install", "remove", "erase", "upgrade", "update", "autoremove", "reinstall", "downgrade",
+    "distro-sync",
+];
+/// Flags that answer the manager's own prompt; pointless in a dry run.
+const ASSUME_YES: &[&str] = &["-y", "--yes", "--assume-yes", "--assumeyes", "--noconfirm", "-q", "-qq"];
+
+/// The dry-run form of the first segment of `cmd` that changes packages.
+/// `sudo` is dropped where the dry run needs no root (apt, pacman) and made
+/// non-interactive for dnf, so the preview never waits for a password.
+/// Commands with substitutions, redirections or other shell syntax in
+/// their words get none.
+pub fn dry_run_command(cmd: &str) -> Option<DryRun> {
+    for segment in split_segments(cmd) {
+        let sudo = segment.first().map(String::as_str) == Some("sudo");
+        let words: Vec<&str> = segment
+            .iter()
+            .map(|w| plain_word(w))
+            .collect::<Option<Vec<&str>>>()?
+            .into_iter()
+            .skip_while(|w| *w == "sudo" || w.starts_with('-') || w.contains('='))
+            .collect();
+        let Some((program, args)) = words.split_first() else {
+            continue;
+        };
+        let program = program.rsplit('/').next().unwrap_or(program);
+        let args: Vec<String> = args
+            .iter()
+            .filter(|a| !ASSUME_YES.contains(a))
+            .map(|a| a.to_string())
+            .collect();
+        let subcommand = args.iter().find(|a| !a.starts_with('-')).map(String::as_str);
+        match program {
+            "apt" | "apt-get" if subcommand.is_some_and(|s| APT_CHANGES.contains(&s)) => {
+                let args = ["-s".to_string()].into_iter().chain(args).collect();
+                return Some(DryRun::new(PackageManager::Apt, "apt-get", args));
+            }
+            "dnf" | "yum" if subcommand.is_some_and(|s| DNF_CHANGES.contains(&s)) => {
+                let dry_run: Vec<String> = [program, "--assumeno"]
+                    .into_iter()
+                    .map(str::to_string)
+                    .chain(args)
+                    .collect();
+                return Some(match sudo {
+                    true => DryRun::new(
+                        PackageManager::Dnf,
+                        "sudo",
+                        ["-n".to_string()].into_iter().chain(dry_run).collect(),
+                    ),
+                    false => DryRun::new(PackageManager::Dnf, program, dry_run[1..].to_vec()),
+                });
+            }
+            "pacman" => {
+                // The operation is the first flag: -S, -Syu, -R, -Rns, ...
+                let Some(op) = args.iter().position(|a| {
+                    a.starts_with('-') && !a.starts_with("--") && a[1..].starts_with(['S', 'R', 'U'])
+                }) else {
+                    continue;
+                };
+                let mut args = args;
+                // Refreshing the databases (`y`) needs root and changes
+                // them; the preview uses the local copies.
+                let flags: String = args[op][1..].chars().filter(|c| *c != 'y').collect();
+                if flags.contains(['s', 'i', 'l', 'q', 'c']) && flags.starts_with('S') {
+                    // -Ss, -Si, ... only query; -Sc cleans the cache.
+                    continue;
+                }
+                args[op] = format!("-{}p", flags);
+                args.extend(["--print-format".to_string(), "%n %v".to_string()]);
+                return Some(DryRun::new(PackageManager::Pacman, "pacman", args));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The transaction in the dry-run output of `manager`; `pacman_removes`
+/// says whether a pacman listing is of packages to remove (see
+/// [`DryRun::removes`]).
+pub fn parse_transaction(manager: PackageManager, output: &str, pacman_removes: bool) -> PackageTransaction {
+    match manager {
+        PackageManager::Apt => parse_apt(output),
+        PackageManager::Dnf => parse_dnf(output),
+        PackageManager::Pacman => {
+            let packages = output
+                .lines()
+                .filter_map(|line| {
+                    let (name, version) = line.trim().split_once(' ')?;
+                    let valid = !name.is_empty() && !name.contains(':') && !version.contains(' ');
+                    valid.then(|| format!("{} {}", name, version))
+                })
+                .collect();
+            match pacman_removes {
+                true => PackageTransaction {
+                    remove: packages,
+                    ..Default::default()
+                },
+                false => PackageTransaction {
+                    install: packages,
+                    ..Default::default()
+                },
+            }
+        }
+    }
+}
+
+/// `apt-get -s` prints `Inst name [old] (new ...)`, `Remv name [old]` and,
+/// when it knows them, the archive and disk sizes.
+fn parse_apt(output: &str) -> PackageTransaction {
+    let mut transaction = PackageTransaction::default();
+    for line in output.lines() {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("Inst"), Some(name)) => match words.next() {
+                Some(old) if old.starts_with('[') => transaction.upgrade.push(name.to_string()),
+                _ => transaction.install.push(name.to_string()),
+            },
+            (Some("Remv"), Some(name)) => transaction.remove.push(name.to_string()),
+            _ => {}
+        }
+        if let Some(rest) = line.strip_prefix("Need to get ") {
+            let size = rest.split(" of archives").next().unwrap_or(rest);
+            // "Need to get 0 B/1,234 kB of archives": already downloaded / total.
+            let size = size.rsplit('/').next().unwrap_or(size);
+            transaction.download = Some(size.trim().to_string());
+        }
+        if let Some(rest) = line.strip_prefix("After this operation, ") {
+            if let Some(size) = rest.split(" of additional disk space").next().filter(|s| *s != rest) {
+                transaction.disk = Some(format!("+{}", size.trim()));
+            } else if let Some(size) = rest.split(" disk space will be freed").next().filter(|s| *s != rest) {
+                transaction.disk = Some(format!("-{}", size.trim()));
+            }
+        }
+    }
+    transaction
+}
+
+/// `dnf --assumeno` prints a table under headings such as `Installing:`,
+/// `Installing dependencies:`, `Upgrading:` and `Removing:`, then the sizes.
+fn parse_dnf(output: &str) -> PackageTransaction {
+    let mut transaction = PackageTransaction::default();
+    let mut section: Option<&str> = None;
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if let Some(size) = trimmed.strip_prefix("Total download size:") {
+            transaction.download = Some(size.trim().to_string());
+            continue;
+        }
+        if let Some(size) = trimmed.strip_prefix("Installed size:") {
+            transaction.disk = Some(format!("+{}", size.trim()));
+            continue;
+        }
+        if let Some(size) = trimmed.strip_prefix("Freed space:") {
+            transaction.disk = Some(format!("-{}", size.trim()));
+            continue;
+        }
+        if !line.starts_with(' ') && trimmed.ends_with(':') {
+            let heading = trimmed.to_lowercase();
+            section = if heading.starts_with("install") || heading.starts_with("reinstall") {
+                Some("install")
+            } else if heading.starts_with("upgrad") || heading.starts_with("downgrad") {
+                Some("upgrade")
+            } else if heading.starts_with("remov") || heading.starts_with("eras") {
+                Some("remove")
+            } else {
+                None
+            };
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with("Transaction Summary") {
+            if trimmed.starts_with("Transaction Summary") {
+                section = None;
+            }
+            continue;
+        }
+        // Rows: name, arch, version, repository, size.
+        let Some(name) = line.strip_prefix(' ').and_then(|l| l.split_whitespace().next()) else {
+            continue;
+        };
+        let list = match section {
+            Some("install") => &mut transaction.install,
+            Some("upgrade") => &mut transaction.upgrade,
+            Some("remove") => &mut transaction.remove,
+            _ => continue,
+        };
+        list.push(name.to_string());
+    }
+    transaction
+}
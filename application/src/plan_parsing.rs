@@ -0,0 +1,154 @@
+//! Reading the model's agent plans: a JSON array of steps, often wrapped in
+//! fences or prose, with numbered list lines as the last resort.
+
+use domain::command_plan::PlanStep;
+use serde::de::DeserializeOwned;
+
+/// Remove markdown code fences/backticks and surrounding quotes
+pub fn clean_command_output(raw: &str) -> String {
+    let trimmed = raw.trim();
+    if trimmed.starts_with("```") && trimmed.ends_with("```") {
+        let lines: Vec<&str> = trimmed.lines().collect();
+        if lines.len() >= 3 && lines.last().is_some_and(|line| line.trim() == "```") {
+            return lines[1..lines.len() - 1].join("\n").trim().to_string();
+        }
+    }
+    trimmed
+        .trim_matches('`')
+        .trim_matches('"')
+        .trim_matches('\'')
+        .trim()
+        .to_string()
+}
+
+/// Extract last JSON object/array from text
+pub fn extract_last_json(raw: &str) -> Option<&str> {
+    let trimmed = raw.trim();
+    if trimmed.starts_with('{') && trimmed.ends_with('}')
+        || trimmed.starts_with('[') && trimmed.ends_with(']')
+    {
+        return Some(trimmed);
+    }
+    let bytes = trimmed.as_bytes();
+    let mut depth = 0;
+    let mut start = None;
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'{' || b == b'[' {
+            if depth == 0 {
+                start = Some(i);
+            }
+            depth += 1;
+        } else if b == b'}' || b == b']' {
+            depth -= 1;
+            if depth == 0 {
+                if let Some(s) = start {
+                    return Some(&trimmed[s..=i]);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Extract JSON array from possibly noisy text
+pub fn extract_json_array(text: &str) -> Option<&str> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut start = None;
+    let mut in_string = false;
+    let mut escape_next = false;
+
+    for (i, &b) in bytes.iter().enumerate() {
+        if escape_next {
+            escape_next = false;
+            continue;
+        }
+
+        match b {
+            b'"' => in_string = !in_string,
+            b'\\'
+                if in_string => {
+                    escape_next = true;
+                }
+            b'[' => {
+                if !in_string && depth == 0 {
+                    start = Some(i);
+                }
+                if !in_string {
+                    depth += 1;
+                }
+            }
+            b']'
+                if !in_string => {
+                    depth -= 1;
+                    if depth == 0 {
+                        if let Some(s) = start {
+                            return Some(&text[s..=i]);
+                        }
+                    }
+                }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse agent response into a list of commands
+pub fn parse_agent_plan(raw: &str) -> Vec<String> {
+    parse_json_list(raw).unwrap_or_else(|| list_lines(raw))
+}
+
+/// Parse agent response into plan steps: a JSON array whose elements are
+/// commands or `{"command", "check", "when", "on_failure"}` objects, or else list
+/// lines taken as bare commands.
+pub fn parse_plan_steps(raw: &str) -> Vec<PlanStep> {
+    parse_json_list(raw)
+        .unwrap_or_else(|| list_lines(raw).into_iter().map(PlanStep::from).collect())
+}
+
+fn parse_json_list<T: DeserializeOwned>(raw: &str) -> Option<Vec<T>> {
+    // Try plain parse
+    if let Ok(items) = serde_json::from_str(raw) {
+        return Some(items);
+    }
+    // Clean and try again
+    let cleaned = clean_command_output(raw);
+    if let Ok(items) = serde_json::from_str(&cleaned) {
+        return Some(items);
+    }
+    // Try to pull array from noisy text
+    if let Some(arr) = extract_json_array(raw) {
+        if let Ok(items) = serde_json::from_str(arr) {
+            return Some(items);
+        }
+    }
+    if let Some(json) = extract_last_json(raw) {
+        if let Ok(items) = serde_json::from_str(json) {
+            return Some(items);
+        }
+    }
+    None
+}
+
+/// Fallback: split non-empty lines, stripping common list markers and code fences
+fn list_lines(raw: &str) -> Vec<String> {
+    raw.lines()
+        .map(|l| l.trim())
+        .filter(|l| {
+            !l.is_empty() && !l.starts_with("```") && !l.ends_with("```") && *l != "[" && *l != "]"
+        })
+        .map(|l| {
+            let mut line = l
+                .trim_start_matches(['-', '*', '•'])
+                .trim();
+            if let Some(pos) = line.find([')', '.', ':']) {
+                // Only strip early numbering markers
+                if pos < 4 {
+                    line = line[pos + 1..].trim();
+                }
+            }
+            line.trim_matches(',').trim().trim_matches('"').to_string()
+        })
+        .filter(|l| !l.is_empty())
+        .collect()
+}
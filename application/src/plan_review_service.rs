@@ -0,0 +1,295 @@
+use crate::agent_service::PREV_STDOUT;
+use domain::command_plan::PlanStep;
+use infrastructure::ollama_client::OllamaClient;
+use serde::Deserialize;
+use shared::types::Result;
+
+/// A problem found in one step of an agent plan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanIssue {
+    /// Index into the plan's steps.
+    pub step: usize,
+    pub problem: String,
+    /// The corrected step command, when the issue could be fixed automatically.
+    pub revised: Option<String>,
+}
+
+/// A plan after review: `steps` have revisions applied, and `original`
+/// keeps what was proposed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReviewedPlan {
+    pub steps: Vec<PlanStep>,
+    pub original: Vec<PlanStep>,
+    pub issues: Vec<PlanIssue>,
+}
+
+impl ReviewedPlan {
+    /// Apply the revisions in order; each was made against the step as
+    /// revised by those before it.
+    pub fn new(original: Vec<PlanStep>, issues: Vec<PlanIssue>) -> Self {
+        let mut steps = original.clone();
+        for issue in &issues {
+            if let (Some(step), Some(command)) = (steps.get_mut(issue.step), &issue.revised) {
+                step.command = command.clone();
+            }
+        }
+        Self {
+            steps,
+            original,
+            issues,
+        }
+    }
+
+    pub fn issues_for(&self, step: usize) -> impl Iterator<Item = &PlanIssue> {
+        self.issues.iter().filter(move |i| i.step == step)
+    }
+}
+
+/// Checks an agent plan before it is shown: a second, cheap model pass for
+/// anything that contradicts the system the plan will run on, plus local
+/// rules for placeholders, missing `sudo` and services started before they
+/// are installed.
+pub struct PlanReviewService {
+    client: OllamaClient,
+}
+
+impl PlanReviewService {
+    pub fn new(client: OllamaClient) -> Self {
+        Self { client }
+    }
+
+    pub async fn review(
+        &self,
+        task: &str,
+        system_info: &str,
+        steps: &[PlanStep],
+        as_root: bool,
+    ) -> Result<ReviewedPlan> {
+        let numbered = steps
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!("{}. {}", i + 1, s))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = format!(
+            "Review this shell plan for the goal \"{}\" on a system with: {}.\n\n{}\n\n\
+Checks for work already done (\"done if\"), conditions (\"only if\") and fallbacks (\"on failure\") are run by the executor.\n\
+Check each step for: placeholder paths or values, commands that need root but lack sudo, steps in the wrong order (e.g. starting a service before installing it), and tools or package managers that do not match the system.\n\
+{} is not a placeholder: it is replaced with the previous step's output when the plan runs.\n\
+Respond ONLY with a JSON array with one object per problem: {{\"step\": <step number>, \"problem\": \"one short sentence\", \"fix\": \"the corrected command\" or null}}. Respond [] if the plan is fine.",
+            task, system_info, numbered, PREV_STDOUT
+        );
+        let response = self.client.generate_response(&prompt).await?;
+        let mut issues = parse_issues(&response, steps);
+        // The local rules run on the model's fixes, so e.g. a rewritten
+        // install step still gets its sudo.
+        let revised = ReviewedPlan::new(steps.to_vec(), issues.clone()).steps;
+        issues.extend(local_issues(&revised, as_root));
+        Ok(ReviewedPlan::new(steps.to_vec(), issues))
+    }
+}
+
+#[derive(Deserialize)]
+struct ModelIssue {
+    step: usize,
+    #[serde(default)]
+    problem: String,
+    #[serde(default)]
+    fix: Option<String>,
+}
+
+/// Issues from the model's JSON reply; anything unparseable, out of range
+/// or without a problem is dropped, as is a "fix" identical to the step.
+fn parse_issues(response: &str, steps: &[PlanStep]) -> Vec<PlanIssue> {
+    let (Some(start), Some(end)) = (response.find('['), response.rfind(']')) else {
+        return Vec::new();
+    };
+    if start >= end {
+        return Vec::new();
+    }
+    let Ok(found) = serde_json::from_str::<Vec<ModelIssue>>(&response[start..=end]) else {
+        return Vec::new();
+    };
+    found
+        .into_iter()
+        .filter(|i| i.step >= 1 && i.step <= steps.len() && !i.problem.trim().is_empty())
+        .map(|i| {
+            let step = i.step - 1;
+            let revised = i
+                .fix
+                .map(|f| f.trim().trim_matches('`').trim().to_string())
+                .filter(|f| !f.is_empty() && f != steps[step].command.trim());
+            PlanIssue {
+                step,
+                problem: i.problem.trim().to_string(),
+                revised,
+            }
+        })
+        .collect()
+}
+
+const PACKAGE_TOOLS: &[&str] = &["apt", "apt-get", "dnf", "yum", "zypper", "pacman", "apk"];
+const PACKAGE_VERBS: &[&str] = &[
+    "install", "remove", "purge", "upgrade", "update", "autoremove", "add", "del", "-S", "-Syu",
+    "-R", "-Rs",
+];
+const SERVICE_VERBS: &[&str] = &[
+    "start", "stop", "restart", "reload", "enable", "disable", "mask", "daemon-reload",
+];
+const ROOT_COMMANDS: &[&str] = &[
+    "useradd", "usermod", "userdel", "groupadd", "ufw", "iptables", "mount", "umount",
+    "modprobe", "update-grub",
+];
+
+/// Rule-based checks that need no model. Placeholders are looked for in a
+/// step's branches too; the other rules look at its command.
+pub fn local_issues(steps: &[PlanStep], as_root: bool) -> Vec<PlanIssue> {
+    let mut issues = Vec::new();
+    for (i, plan_step) in steps.iter().enumerate() {
+        let step = &plan_step.command;
+        let parts = [
+            Some(step),
+            plan_step.check.as_ref(),
+            plan_step.when.as_ref(),
+            plan_step.on_failure.as_ref(),
+        ];
+        if let Some(placeholder) = parts.into_iter().flatten().find_map(|p| placeholder(p)) {
+            issues.push(PlanIssue {
+                step: i,
+                problem: format!("Contains the placeholder `{}`; fill in a real value.", placeholder),
+                revised: None,
+            });
+        }
+        if i == 0 && plan_step.to_string().contains(PREV_STDOUT) {
+            issues.push(PlanIssue {
+                step: i,
+                problem: format!("Uses {} but there is no previous step.", PREV_STDOUT),
+                revised: None,
+            });
+        }
+        if !as_root {
+            if let Some(program) = segments(step).find_map(|words| needs_sudo(&words)) {
+                let single = segments(step).count() == 1;
+                issues.push(PlanIssue {
+                    step: i,
+                    problem: format!("`{}` needs root but is not run with sudo.", program),
+                    revised: single.then(|| format!("sudo {}", step.trim())),
+                });
+            }
+        }
+        for unit in started_services(step) {
+            let installed_later = steps
+                .iter()
+                .enumerate()
+                .skip(i + 1)
+                .find(|(_, later)| installed_packages(&later.command).iter().any(|p| p == &unit));
+            if let Some((j, _)) = installed_later {
+                issues.push(PlanIssue {
+                    step: i,
+                    problem: format!(
+                        "Uses the {} service before step {} installs it.",
+                        unit,
+                        j + 1
+                    ),
+                    revised: None,
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// Words of each simple command in `step`, split on `&&`, `||`, `;` and `|`.
+fn segments(step: &str) -> impl Iterator<Item = Vec<&str>> {
+    step.split(['&', '|', ';'])
+        .map(|segment| segment.split_whitespace().collect::<Vec<_>>())
+        .filter(|words| !words.is_empty())
+}
+
+/// The program in `words` that needs root, unless it already runs under sudo.
+fn needs_sudo<'a>(words: &[&'a str]) -> Option<&'a str> {
+    let program = *words.first()?;
+    let verb = words.get(1).copied().unwrap_or_default();
+    let privileged = (PACKAGE_TOOLS.contains(&program) && PACKAGE_VERBS.contains(&verb))
+        || (program == "systemctl"
+            && !words.contains(&"--user")
+            && words.iter().any(|w| SERVICE_VERBS.contains(w)))
+        || ROOT_COMMANDS.contains(&program);
+    privileged.then_some(program)
+}
+
+fn placeholder(step: &str) -> Option<String> {
+    let step = step.replace(PREV_STDOUT, "");
+    for marker in ["/path/to", "your_", "your-", "YOUR_", "example.com", "<", "{{"] {
+        let Some(start) = step.find(marker) else {
+            continue;
+        };
+        let rest = &step[start..];
+        let found = match marker {
+            "<" => {
+                // `<name>`, not a redirection like `< input.txt` or `<<EOF`
+                let Some(end) = rest.find('>') else {
+                    continue;
+                };
+                let name = &rest[1..end];
+                if name.is_empty()
+                    || !name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "_- ".contains(c))
+                {
+                    continue;
+                }
+                rest[..=end].to_string()
+            }
+            _ => rest
+                .split(|c: char| c.is_whitespace() || c == '"' || c == '\'')
+                .next()
+                .unwrap_or(marker)
+                .to_string(),
+        };
+        return Some(found);
+    }
+    None
+}
+
+/// Units `step` starts, enables or restarts, without the `.service` suffix.
+fn started_services(step: &str) -> Vec<String> {
+    segments(step)
+        .filter_map(|words| {
+            let at = words.iter().position(|w| *w == "systemctl")?;
+            let verb = *words.get(at + 1)?;
+            if !matches!(verb, "start" | "restart" | "enable" | "reload") {
+                return None;
+            }
+            Some(
+                words[at + 2..]
+                    .iter()
+                    .filter(|w| !w.starts_with('-'))
+                    .map(|w| w.trim_end_matches(".service").to_string())
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .flatten()
+        .collect()
+}
+
+/// Packages `step` installs.
+fn installed_packages(step: &str) -> Vec<String> {
+    segments(step)
+        .filter_map(|words| {
+            let at = words.iter().position(|w| PACKAGE_TOOLS.contains(w))?;
+            let verb = *words.get(at + 1)?;
+            if !matches!(verb, "install" | "add" | "-S") {
+                return None;
+            }
+            Some(
+                words[at + 2..]
+                    .iter()
+                    .filter(|w| !w.starts_with('-'))
+                    .map(|w| w.to_string())
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .flatten()
+        .collect()
+}
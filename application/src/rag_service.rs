@@ -1,34 +1,510 @@
+use crate::context_report::{chunk_label, ChunkScore, ContextReport, RetrievalStats};
+use crate::prompt_guard::{self, DATA_RULES};
 use infrastructure::{
-    config::Config,
-    embedder::{Embedder, EmbeddingInput},
-    embedding_storage::EmbeddingStorage,
-    file_scanner::FileScanner,
+    config::{project_index_settings, Config},
+    crate_docs::{mentioned_crates, rust_dependencies, CrateDocs, Dependency},
+    embedder::Embedder,
+    embedding_storage::{EmbeddingStorage, DEFAULT_COLLECTION},
+    doc_source::DocPage,
+    file_scanner::{FileChunk, FileScanResult, FileScanner},
+    lockfiles::{self, locked_dependencies, versions_summary},
     ollama_client::OllamaClient,
+    prompt_report,
+    quantization::Quantization,
+    scheduler::Priority,
     search::SearchEngine,
+    vector_cache::VectorMatrixCache,
 };
+use domain::models::Embedding;
 use md5;
+use serde::{Deserialize, Serialize};
+use shared::accessibility;
+use shared::keywords::keywords;
 use shared::types::Result;
-use std::path::PathBuf;
+use shared::utils::is_supported_file;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
+
+/// Progress of the current index build, observable while it runs in the
+/// background.
+#[derive(Debug, Clone, Default)]
+pub struct IndexStatus {
+    /// Files have been scanned and `total_chunks` is known.
+    pub scanned: bool,
+    pub total_chunks: usize,
+    pub embedded_chunks: usize,
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+impl IndexStatus {
+    pub fn percent(&self) -> Option<usize> {
+        if self.done {
+            Some(100)
+        } else if self.scanned && self.total_chunks > 0 {
+            Some(self.embedded_chunks * 100 / self.total_chunks)
+        } else {
+            None
+        }
+    }
+}
+
+/// Below this top similarity the best match is too weak to answer from.
+const MIN_TOP_SCORE: f32 = 0.35;
+/// When the best match is only middling, it must stand out from the rest of
+/// the retrieved set by at least this much; a flat spread means nothing in the
+/// index is really about the question.
+const MIN_SCORE_SPREAD: f32 = 0.05;
+const STRONG_TOP_SCORE: f32 = 0.6;
+
+/// How well the retrieved chunks match the question.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RetrievalConfidence {
+    /// Cosine similarity of the best chunk.
+    pub top_score: f32,
+    /// Best score minus the median of the retrieved scores.
+    pub spread: f32,
+}
+
+impl RetrievalConfidence {
+    /// `scores` sorted highest first.
+    pub fn from_scores(scores: &[f32]) -> Self {
+        match scores.first() {
+            Some(&top_score) => Self {
+                top_score,
+                spread: top_score - scores[scores.len() / 2],
+            },
+            None => Self::default(),
+        }
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.top_score < MIN_TOP_SCORE
+            || (self.top_score < STRONG_TOP_SCORE && self.spread < MIN_SCORE_SPREAD)
+    }
+
+    /// Prefix `answer` with a warning and suggestions when confidence is low.
+    fn annotate(&self, answer: String) -> String {
+        if self.is_low() {
+            format!("{}\n{}", self.warning(), answer)
+        } else {
+            answer
+        }
+    }
+
+    fn warning(&self) -> String {
+        format!(
+            "{} Low retrieval confidence (best match {:.2}, spread {:.2}): the indexed code may not cover this question, so the answer below may be unreliable.\n\
+Try indexing the relevant directory (e.g. `vibe_cli --context docs/`), rephrasing with names used in the code, or widening RAG_INCLUDE_PATTERNS.\n",
+            accessibility::glyph("⚠", "Warning:"),
+            self.top_score, self.spread
+        )
+    }
+}
+
+/// Characters of a file shown to the model when summarizing it.
+const SUMMARY_INPUT_CHARS: usize = 6_000;
+/// Pseudo-file holding the directory tree; it is never summarized.
+const DIR_OVERVIEW_PATH: &str = "__dir_overview__";
+/// Pseudo-file holding the dependency versions pinned by the lockfiles.
+const DEPENDENCY_VERSIONS_PATH: &str = "__dependency_versions__";
+
+/// Hits, best first, whose neighboring chunks are pulled in on expansion.
+const NEIGHBOR_EXPANSION_HITS: usize = 10;
+/// Chunks retrieved for the new terms of a follow-up question.
+const FOLLOW_UP_TOP_K: usize = 15;
+/// Cap on the context carried through a conversation.
+const MAX_CONVERSATION_CHUNKS: usize = 60;
+/// Turns of history sent with a follow-up question.
+const MAX_HISTORY_TURNS: usize = 4;
+/// Previous answers are cut to this length in the history.
+const MAX_HISTORY_ANSWER_CHARS: usize = 1_500;
+
+/// Files, best match first, checked for changes since indexing before a
+/// question is answered from them.
+const STALE_CHECK_FILES: usize = 10;
+
+/// Suggestions listed under a low-context answer.
+const MAX_LOW_CONTEXT_SUGGESTIONS: usize = 4;
+
+/// What retrieval passed over, for the suggestions of a low-context answer.
+#[derive(Debug, Clone)]
+struct MissedContext {
+    /// The best chunk under the minimum score.
+    closest: Option<ChunkScore>,
+    min_score: f32,
+}
+
+/// An answer together with the context it was built from.
+#[derive(Debug, Clone)]
+pub struct RagAnswer {
+    pub text: String,
+    pub confidence: RetrievalConfidence,
+    context: Vec<String>,
+}
+
+/// A short RAG conversation: recent turns plus the retrieved context that
+/// follow-up questions build on. Serializable so a session can keep it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RagConversation {
+    turns: Vec<(String, String)>,
+    context: Vec<String>,
+    confidence: RetrievalConfidence,
+}
+
+impl RagConversation {
+    pub fn is_empty(&self) -> bool {
+        self.turns.is_empty()
+    }
+
+    /// Keep an accepted answer so the next question can follow up on it.
+    pub fn record(&mut self, question: &str, answer: RagAnswer) {
+        self.turns.push((question.to_string(), answer.text));
+        if self.turns.len() > MAX_HISTORY_TURNS {
+            self.turns.remove(0);
+        }
+        self.context = answer.context;
+        self.confidence = answer.confidence;
+    }
+
+    /// Paths in the `FILE:` headers of the context.
+    fn context_files(&self) -> HashSet<String> {
+        self.context
+            .iter()
+            .flat_map(|chunk| chunk.lines().take(2))
+            .filter_map(|line| line.strip_prefix("FILE: "))
+            .map(|path| path.trim().trim_start_matches("./").to_string())
+            .collect()
+    }
+
+    /// `terms` not mentioned in any earlier question or answer.
+    fn new_terms(&self, terms: &[String]) -> Vec<String> {
+        let seen: HashSet<String> = self
+            .turns
+            .iter()
+            .flat_map(|(q, a)| keywords(q).into_iter().chain(keywords(a)))
+            .collect();
+        terms
+            .iter()
+            .filter(|t| !seen.contains(t.as_str()))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Drop chunks from the end until the rest fit in `max_chars` (0 for no
+/// cap); a first chunk too long on its own is cut instead.
+fn fit_context(chunks: &mut Vec<String>, max_chars: usize) {
+    if max_chars == 0 {
+        return;
+    }
+    let mut total = 0;
+    let fitting = chunks
+        .iter()
+        .take_while(|chunk| {
+            total += chunk.chars().count();
+            total <= max_chars
+        })
+        .count();
+    if fitting == 0 {
+        if let Some(first) = chunks.first_mut() {
+            *first = truncate_chars(first, max_chars).to_string();
+        }
+        chunks.truncate(1);
+    } else {
+        chunks.truncate(fitting);
+    }
+}
+
+/// The first chunk of each list, then the second of each, and so on,
+/// skipping repeats.
+fn interleave(lists: Vec<Vec<String>>) -> Vec<String> {
+    let longest = lists.iter().map(Vec::len).max().unwrap_or(0);
+    let mut merged: Vec<String> = Vec::new();
+    for rank in 0..longest {
+        for list in &lists {
+            if let Some(chunk) = list.get(rank) {
+                if !merged.contains(chunk) {
+                    merged.push(chunk.clone());
+                }
+            }
+        }
+    }
+    merged
+}
+
+/// Words of `text` that look like relative paths to source files, e.g.
+/// `src/session.rs` or `./lib.rs:42`, without any line number.
+fn mentioned_paths(text: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    for word in text.split(|c: char| c.is_whitespace() || "`'\"()[]{},<>".contains(c)) {
+        let word = word.trim_end_matches(['.', ':', ';', '!', '?']);
+        let word = word.split(':').next().unwrap_or_default();
+        let word = word.trim_start_matches("./");
+        let path = Path::new(word);
+        let relative = path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if !word.is_empty() && relative && is_supported_file(path) && !paths.iter().any(|p| p == word) {
+            paths.push(word.to_string());
+        }
+    }
+    paths
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((i, _)) => &text[..i],
+        None => text,
+    }
+}
+
+fn feedback_part(feedback: &str) -> String {
+    if feedback.is_empty() {
+        String::new()
+    } else {
+        format!("\n\nUser feedback for improvement: {}", feedback)
+    }
+}
+
+/// A chunk as it goes into the context: a `FILE:`/`OFFSET:` header (and
+/// `CELL:` for notebooks), then code fenced with its language so the model
+/// reads and writes it as that language. Prose and text of unknown
+/// language go unfenced.
+fn chunk_context(chunk: &FileChunk) -> String {
+    let mut context = format!("FILE: {}\nOFFSET: {}\n", chunk.path, chunk.start_offset);
+    if let Some(cell) = chunk.cell {
+        context.push_str(&format!("CELL: {}\n", cell));
+    }
+    if matches!(chunk.language.as_str(), "" | "markdown" | "org" | "rst") {
+        context.push_str(&chunk.text);
+        return context;
+    }
+    // Longer than any run of backticks in the code, so it can't close early.
+    let longest_run = chunk.text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    context.push_str(&format!(
+        "{fence}{}\n{}\n{fence}",
+        chunk.language,
+        chunk.text.trim_end_matches('\n')
+    ));
+    context
+}
+
+/// A warning naming the `stale` files an answer was built from; empty when
+/// there are none.
+fn stale_warning(stale: &[String]) -> String {
+    if stale.is_empty() {
+        return String::new();
+    }
+    format!(
+        "{} Changed since indexing and not re-indexed yet: {}. The answer may be based on old code; pass --fresh to re-index before answering.\n",
+        accessibility::glyph("⚠", "Warning:"),
+        stale.join(", ")
+    )
+}
+
+/// Whether `path` is a generated chunk (directory tree, dependency
+/// versions), which is never summarized.
+fn is_pseudo_file(path: &str) -> bool {
+    path == DIR_OVERVIEW_PATH || path == DEPENDENCY_VERSIONS_PATH
+}
+
+/// One changed file waiting to be (re-)embedded.
+struct PendingFile {
+    path: String,
+    hash: String,
+    /// Chunks; those without vectors still need embedding.
+    chunks: Vec<Embedding>,
+}
+
+/// Files embedded together, with their chunks' vectors, waiting to be
+/// stored.
+type EmbeddedBatch = (Vec<PendingFile>, Vec<Embedding>);
+
+/// Dependencies whose docs are looked up for one question.
+const MAX_CRATES: usize = 3;
+/// Characters of one crate's docs that are embedded; the front page of a
+/// large crate runs long, and its start says the most.
+const MAX_CRATE_DOC_CHARS: usize = 40_000;
+/// Chunks of one crate's docs added to the context.
+const CRATE_DOC_CHUNKS: usize = 4;
+/// Scanned files waiting to be indexed; scanning pauses while the queue is
+/// full.
+const SCAN_QUEUE: usize = 64;
+/// Embedded batches waiting to be stored; embedding pauses while the queue
+/// is full.
+const STORE_QUEUE: usize = 2;
+/// Chunks embedded per storage commit; each commit becomes searchable.
+const COMMIT_CHUNKS: usize = 32;
 
 pub struct RagService {
     scanner: FileScanner,
     storage: EmbeddingStorage,
     embedder: Embedder,
     client: OllamaClient,
+    /// For indexing-time requests (file summaries) that yield to queries.
+    background_client: OllamaClient,
     config: Config,
+    vector_cache: Mutex<Option<Arc<VectorMatrixCache>>>,
+    index_status: watch::Sender<IndexStatus>,
+    /// Further collections searched alongside this one.
+    collections: Vec<RagService>,
+    /// Looks up the docs of dependencies a question names.
+    crate_docs: Option<CrateDocs>,
+    /// The index holds project files read by the scanner, which are checked
+    /// for changes before answering; false for collections of documents.
+    project_files: bool,
 }
 
 impl RagService {
     pub async fn new(root_path: &str, db_path: &str, client: OllamaClient, config: Config) -> Result<Self> {
+        if config.encrypt_at_rest {
+            shared::at_rest::enable()?;
+        }
         Ok(Self {
-            scanner: FileScanner::new(root_path),
-            storage: EmbeddingStorage::new(db_path).await?,
+            scanner: FileScanner::new(root_path)
+                .with_walk(project_index_settings(Path::new(root_path), &config.index)),
+            storage: EmbeddingStorage::new(db_path)
+                .await?
+                .with_quantization(config.vector_quantization),
             embedder: Embedder::new(client.clone()),
-            client: client,
+            background_client: client.with_priority(Priority::Background),
+            client,
             config,
+            vector_cache: Mutex::new(None),
+            index_status: watch::Sender::new(IndexStatus {
+                done: true,
+                ..IndexStatus::default()
+            }),
+            collections: Vec::new(),
+            crate_docs: None,
+            project_files: true,
+        })
+    }
+
+    /// Service for the collection `name` of the store at `config.db_path`,
+    /// embedded and searched with that collection's settings. Indexing
+    /// reads files under `root_path`.
+    pub async fn collection(
+        root_path: &str,
+        name: &str,
+        client: OllamaClient,
+        config: &Config,
+    ) -> Result<Self> {
+        let client = client.with_embedding_model(config.embedding_model(name));
+        let config = config.for_collection(name)?;
+        let mut service = Self::new(root_path, &config.db_path.clone(), client, config).await?;
+        service.project_files = name == DEFAULT_COLLECTION;
+        Ok(service)
+    }
+
+    /// For questions naming a Rust crate the project depends on, add the
+    /// parts of its docs.rs page (or README), at the version Cargo.lock
+    /// resolves, that best match the question.
+    pub fn with_crate_docs(mut self, crate_docs: CrateDocs) -> Self {
+        self.crate_docs = Some(crate_docs);
+        self
+    }
+
+    /// Also search `collections` for each question. Their chunks are taken
+    /// in turns with this one's, so no collection crowds out the others.
+    pub fn with_collections(mut self, collections: Vec<RagService>) -> Self {
+        self.collections = collections;
+        self
+    }
+
+    /// Files named in the last accepted answer that exist in the project
+    /// but are not part of the conversation's context yet.
+    pub fn referenced_files(&self, conversation: &RagConversation) -> Vec<String> {
+        let Some((_, answer)) = conversation.turns.last() else {
+            return Vec::new();
+        };
+        let root = self.scanner.root();
+        let root_prefix = format!("{}/", root.to_string_lossy().trim_end_matches('/'));
+        let known: HashSet<String> = conversation
+            .context_files()
+            .into_iter()
+            .map(|path| path.strip_prefix(&root_prefix).map(str::to_string).unwrap_or(path))
+            .collect();
+        mentioned_paths(answer)
+            .into_iter()
+            .filter(|path| !known.contains(path) && root.join(path).is_file())
+            .collect()
+    }
+
+    /// Chunk `paths` (relative to the project root) into the conversation's
+    /// context, ahead of what was retrieved, so follow-up questions can see
+    /// them. Returns the number of chunks added.
+    pub fn include_files(&self, conversation: &mut RagConversation, paths: &[String]) -> Result<usize> {
+        let files: Vec<PathBuf> = paths.iter().map(|path| self.scanner.root().join(path)).collect();
+        let chunks: Vec<String> = self
+            .scanner
+            .scan_paths(&files)?
+            .into_iter()
+            .flat_map(|scan| scan.chunks)
+            .map(|chunk| chunk_context(&chunk))
+            .filter(|chunk| !conversation.context.contains(chunk))
+            .collect();
+        let added = chunks.len();
+        conversation.context.splice(0..0, chunks);
+        conversation.context.truncate(MAX_CONVERSATION_CHUNKS);
+        Ok(added)
+    }
+
+    pub fn index_status(&self) -> IndexStatus {
+        self.index_status.borrow().clone()
+    }
+
+    pub fn subscribe_index_status(&self) -> watch::Receiver<IndexStatus> {
+        self.index_status.subscribe()
+    }
+
+    /// Build the index for `keywords` on a background task; queries can run
+    /// meanwhile against whatever has been stored so far.
+    pub fn spawn_index_for_keywords(self: &Arc<Self>, keywords: Vec<String>) -> JoinHandle<()> {
+        // Mark the build as started before returning so callers never see a
+        // stale "done" status.
+        self.index_status.send_replace(IndexStatus::default());
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            // Failures are recorded in the index status.
+            let _ = service.build_index_for_keywords(&keywords).await;
         })
     }
 
+    /// Background counterpart of [`build_index`](Self::build_index).
+    pub fn spawn_index(self: &Arc<Self>) -> JoinHandle<()> {
+        self.index_status.send_replace(IndexStatus::default());
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let _ = service.build_index().await;
+        })
+    }
+
+    /// Wait until there is something to search: either the index already
+    /// holds chunks or the first batch of a running build has been stored.
+    pub async fn wait_for_searchable_index(&self) -> Result<()> {
+        let mut status = self.index_status.subscribe();
+        loop {
+            {
+                let current = status.borrow_and_update();
+                if current.done || current.embedded_chunks > 0 {
+                    return Ok(());
+                }
+            }
+            if self.storage.stats().await?.chunks > 0 {
+                return Ok(());
+            }
+            if status.changed().await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+
     pub async fn build_index(&self) -> Result<()> {
         self.build_index_with_files(&self.scanner.collect_files()?)
             .await
@@ -40,22 +516,18 @@ impl RagService {
         // Apply include/exclude patterns first
         files = self.filter_files_by_patterns(&files);
 
-        // Filter by keywords if provided
+        // Keywords are split into identifier words and stemmed, so they
+        // match file names however the name is cased or inflected.
+        let keywords = self.filter_relevant_keywords(keywords);
+        let matches = |path: &PathBuf| {
+            let path_str = path.to_string_lossy().to_lowercase();
+            keywords.iter().filter(|k| path_str.contains(k.as_str())).count()
+        };
+
         if !keywords.is_empty() {
-            let filtered_keywords = self.filter_relevant_keywords(keywords);
-            if !filtered_keywords.is_empty() {
-                let keyword_lower: Vec<String> = filtered_keywords.iter().map(|k| k.to_lowercase()).collect();
-                let filtered: Vec<PathBuf> = files
-                    .iter()
-                    .filter(|p| {
-                        let path_str = p.to_string_lossy().to_lowercase();
-                        keyword_lower.iter().any(|k| path_str.contains(k))
-                    })
-                    .cloned()
-                    .collect();
-                if !filtered.is_empty() {
-                    files = filtered;
-                }
+            let filtered: Vec<PathBuf> = files.iter().filter(|p| matches(p) > 0).cloned().collect();
+            if !filtered.is_empty() {
+                files = filtered;
             }
         }
 
@@ -66,34 +538,73 @@ impl RagService {
             let mut files_with_scores: Vec<(PathBuf, usize)> = files
                 .into_iter()
                 .map(|p| {
-                    let score = if keywords.is_empty() {
-                        1
-                    } else {
-                        let path_str = p.to_string_lossy().to_lowercase();
-                        keywords.iter()
-                            .filter(|k| path_str.contains(&k.to_lowercase()))
-                            .count()
-                    };
+                    let score = matches(&p);
                     (p, score)
                 })
                 .collect();
 
-            files_with_scores.sort_by(|a, b| b.1.cmp(&a.1));
+            files_with_scores.sort_by_key(|f| std::cmp::Reverse(f.1));
             files = files_with_scores.into_iter().take(MAX_FILES).map(|(p, _)| p).collect();
         }
 
         self.build_index_with_files(&files).await
     }
 
+    /// The stored chunks that best match `question` by keyword, without any
+    /// model call; what `--offline` shows instead of an answer.
+    pub async fn search_offline(&self, question: &str, top_k: usize) -> Result<Vec<String>> {
+        let mut texts = self.storage.get_all_texts().await?;
+        let ranked = SearchEngine::rank_by_keywords(&keywords(question), &texts, top_k);
+        Ok(ranked
+            .into_iter()
+            .map(|(_, i)| std::mem::take(&mut texts[i]))
+            .collect())
+    }
+
+    /// Up to `top_k` stored chunks related to `text`, within the context
+    /// size limit, without asking the model anything.
+    pub async fn related_context(&self, text: &str, top_k: usize) -> Result<Vec<String>> {
+        let (_, mut chunks, _) = self.retrieve(text, top_k).await?;
+        fit_context(&mut chunks, self.config.rag_max_context_chars);
+        Ok(chunks)
+    }
+
     pub async fn query(&self, question: &str) -> Result<String> {
         self.query_with_feedback(question, "").await
     }
 
     pub async fn query_with_feedback(&self, question: &str, feedback: &str) -> Result<String> {
-        let query_embedding = self.client.generate_embedding(question).await?;
-        let all_embeddings = self.storage.get_all_embeddings().await?;
-        let mut relevant_chunks =
-            SearchEngine::find_relevant_chunks(&query_embedding, &all_embeddings, 50);
+        let answer = self
+            .answer(&RagConversation::default(), question, feedback)
+            .await?;
+        Ok(answer.text)
+    }
+
+    /// Answer `question` within `conversation`. The first question retrieves
+    /// from scratch; follow-ups reuse the earlier context and only retrieve
+    /// for terms that haven't come up yet. Pass the result to
+    /// [`RagConversation::record`] once the user accepts it.
+    pub async fn answer(
+        &self,
+        conversation: &RagConversation,
+        question: &str,
+        feedback: &str,
+    ) -> Result<RagAnswer> {
+        if conversation.turns.is_empty() {
+            self.answer_first(question, feedback).await
+        } else {
+            self.answer_follow_up(conversation, question, feedback).await
+        }
+    }
+
+    async fn answer_first(&self, question: &str, feedback: &str) -> Result<RagAnswer> {
+        let (confidence, mut relevant_chunks, stats, stale) =
+            self.retrieve_fresh(question, self.config.rag_top_k).await?;
+        relevant_chunks.splice(0..0, self.crate_context(question).await);
+        if let Some(versions) = self.versions_context(question) {
+            relevant_chunks.retain(|chunk| *chunk != versions);
+            relevant_chunks.insert(0, versions);
+        }
 
         // For project-level questions, include README and directory tree if available
         if question.to_lowercase().contains("project") || question.to_lowercase().contains("what is") {
@@ -105,18 +616,526 @@ impl RagService {
                 relevant_chunks.insert(0, format!("DIRECTORY TREE:\n{}", dir_overview));
             }
         }
+        let stats_summary = MissedContext {
+            closest: stats.below_min_score.first().cloned(),
+            min_score: stats.min_score,
+        };
+        let assembled = prompt_report::verbose().then(|| relevant_chunks.clone());
+        fit_context(&mut relevant_chunks, self.config.rag_max_context_chars);
+        if let Some(assembled) = assembled {
+            self.report_context(stats, &assembled, &relevant_chunks);
+        }
+
+        if relevant_chunks.join("\n\n").is_empty() {
+            return self.answer_low_context(question, feedback, confidence, stats_summary).await;
+        }
+        let (context, removed) = prompt_guard::data_blocks(&relevant_chunks);
+        let prompt = format!("You are an expert software engineer. Based on the provided code context and directory structure, {}{} \n\n{}\n\nContext:\n{}\n\nProvide a concise summary that includes:\n- Project purpose\n- Main features\n- Technologies used\n- Architecture\n- Complete directory structure (copy exactly from the DIRECTORY TREE section in the context)\n\nBe accurate and base your answer only on the provided context; if it does not contain the answer, say so instead of guessing. Do not invent or modify the directory structure.", question, feedback_part(feedback), DATA_RULES, context);
+        let text = self.client.generate_response(&prompt).await?;
+        Ok(RagAnswer {
+            text: stale_warning(&stale) + &prompt_guard::removed_note(removed) + &confidence.annotate(text),
+            confidence,
+            context: relevant_chunks,
+        })
+    }
+
+    /// The answer when no chunk matched `question`: built from the
+    /// directory tree and the file summaries, labeled as a low-context
+    /// answer, and followed by changes that would get code into the context.
+    async fn answer_low_context(
+        &self,
+        question: &str,
+        feedback: &str,
+        confidence: RetrievalConfidence,
+        missed: MissedContext,
+    ) -> Result<RagAnswer> {
+        let mut context = Vec::new();
+        let overview = self.scanner.directory_overview(8, 2000);
+        if !overview.is_empty() {
+            context.push(format!("DIRECTORY TREE:\n{}", overview));
+        }
+        context.extend(
+            self.storage
+                .get_file_summaries()
+                .await?
+                .into_iter()
+                .filter(|(path, _)| path != DIR_OVERVIEW_PATH && path != DEPENDENCY_VERSIONS_PATH)
+                .map(|(path, summary)| format!("FILE: {}\nSUMMARY: {}", path, summary.trim())),
+        );
+        fit_context(&mut context, self.config.rag_max_context_chars);
+
+        let mut text = if context.is_empty() {
+            "No relevant code context found for this query.".to_string()
+        } else {
+            let (blocks, _) = prompt_guard::data_blocks(&context);
+            let prompt = format!(
+                "You are an expert software engineer. No code in the index matched the question, so all you have is the project's directory tree and short summaries of some of its files. Answer from them as far as they allow: {}{}\n\n{}\n\nContext:\n{}\n\nSay which parts of the answer are inferred from file names or summaries, and say so plainly if they don't answer the question.",
+                question,
+                feedback_part(feedback),
+                DATA_RULES,
+                blocks
+            );
+            let answer = self.client.generate_response(&prompt).await?;
+            format!(
+                "{} Low-context answer: no indexed code matched this question, so this is based only on the directory tree and file summaries.\n{}",
+                accessibility::glyph("⚠", "Warning:"),
+                answer
+            )
+        };
+        let suggestions = self.low_context_suggestions(question, &missed).await?;
+        text.push_str("\n\nTo get code for this question into the context:");
+        for suggestion in suggestions {
+            text.push_str(&format!("\n- {}", suggestion));
+        }
+        Ok(RagAnswer {
+            text,
+            confidence,
+            context,
+        })
+    }
+
+    /// Concrete changes that would let retrieval find code for `question`:
+    /// a lower minimum score when chunks only just missed it, and the
+    /// project files named like the question's terms that the include or
+    /// exclude patterns leave out or that aren't indexed yet.
+    async fn low_context_suggestions(&self, question: &str, missed: &MissedContext) -> Result<Vec<String>> {
+        let mut suggestions = Vec::new();
+        if self.storage.stats().await?.chunks == 0 {
+            suggestions.push(format!(
+                "Nothing is indexed yet: build the index with `vibe_cli rag index {}`.",
+                self.scanner.root().display()
+            ));
+        }
+        if let Some(closest) = &missed.closest {
+            // Rounded down, so the closest chunk makes it in.
+            let lower = (closest.score * 100.0).floor() / 100.0;
+            suggestions.push(format!(
+                "The closest chunk, {} ({:.2}), is under the minimum score {:.2}: retry with `--min-score {:.2}` or lower RAG_MIN_SCORE.",
+                closest.label, closest.score, missed.min_score, lower
+            ));
+        }
+        let terms: Vec<String> = keywords(question).into_iter().filter(|t| t.len() >= 3).collect();
+        let files = self.scanner.collect_files().unwrap_or_default();
+        for file in files {
+            if suggestions.len() >= MAX_LOW_CONTEXT_SUGGESTIONS {
+                break;
+            }
+            let name = file
+                .file_name()
+                .map(|n| n.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            if !terms.iter().any(|term| name.contains(term.as_str())) {
+                continue;
+            }
+            let path = file.to_string_lossy().to_string();
+            let excluded_by = self
+                .config
+                .rag_exclude_patterns
+                .iter()
+                .find(|pattern| self.matches_pattern(&path, pattern));
+            let included = self.config.rag_include_patterns.is_empty()
+                || self
+                    .config
+                    .rag_include_patterns
+                    .iter()
+                    .any(|pattern| self.matches_pattern(&path, pattern));
+            let suggestion = if let Some(pattern) = excluded_by {
+                format!(
+                    "`{}` looks relevant but RAG_EXCLUDE_PATTERNS leaves it out (`{}`); drop that pattern to index it.",
+                    path, pattern
+                )
+            } else if !included {
+                let pattern = match file.extension() {
+                    Some(ext) => format!("*.{}", ext.to_string_lossy()),
+                    None => path.clone(),
+                };
+                format!(
+                    "`{}` looks relevant but isn't covered by RAG_INCLUDE_PATTERNS; add `{}` to index it.",
+                    path, pattern
+                )
+            } else if self.storage.get_file_hash(path.clone()).await?.is_none() {
+                format!(
+                    "`{}` looks relevant but isn't indexed yet; ask again once indexing finishes (see `vibe_cli rag status`).",
+                    path
+                )
+            } else {
+                continue;
+            };
+            suggestions.push(suggestion);
+        }
+        if suggestions.is_empty() {
+            suggestions.push(
+                "Rephrase the question with names used in the code, or index the directory that holds it (e.g. `vibe_cli --context docs/`).".to_string(),
+            );
+        }
+        Ok(suggestions)
+    }
+
+    async fn answer_follow_up(
+        &self,
+        conversation: &RagConversation,
+        question: &str,
+        feedback: &str,
+    ) -> Result<RagAnswer> {
+        let mut context = conversation.context.clone();
+        let mut confidence = conversation.confidence;
+        let mut stats = RetrievalStats::default();
+        let mut stale = Vec::new();
 
-        let context = relevant_chunks.join("\n\n");
-        if context.is_empty() {
-            return Ok("No relevant code context found for this query.".to_string());
+        let new_terms = conversation.new_terms(&keywords(question));
+        if !new_terms.is_empty() {
+            let top_k = FOLLOW_UP_TOP_K.min(self.config.rag_top_k);
+            let (retrieved_confidence, chunks, retrieved, changed) =
+                self.retrieve_fresh(&new_terms.join(" "), top_k).await?;
+            confidence = retrieved_confidence;
+            stats = retrieved;
+            stale = changed;
+            let fresh: Vec<String> = chunks
+                .into_iter()
+                .filter(|text| !context.contains(text))
+                .collect();
+            // New material first; the oldest context falls off the end.
+            context.splice(0..0, fresh);
+            context.truncate(MAX_CONVERSATION_CHUNKS);
         }
-        let feedback_part = if feedback.is_empty() {
-            String::new()
+        let docs: Vec<String> = self
+            .crate_context(question)
+            .await
+            .into_iter()
+            .filter(|text| !context.contains(text))
+            .collect();
+        context.splice(0..0, docs);
+        if let Some(versions) = self.versions_context(question) {
+            context.retain(|chunk| *chunk != versions);
+            context.insert(0, versions);
+        }
+        let assembled = prompt_report::verbose().then(|| context.clone());
+        fit_context(&mut context, self.config.rag_max_context_chars);
+        if let Some(assembled) = assembled {
+            self.report_context(stats, &assembled, &context);
+        }
+
+        let history = conversation
+            .turns
+            .iter()
+            .map(|(q, a)| format!("Q: {}\nA: {}", q, truncate_chars(a, MAX_HISTORY_ANSWER_CHARS)))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let (blocks, removed) = prompt_guard::data_blocks(&context);
+        let prompt = format!(
+            "You are an expert software engineer answering follow-up questions about a codebase.\n\nConversation so far:\n{}\n\n{}\n\nContext:\n{}\n\nFollow-up question: {}{}\n\nAnswer concisely, based only on the provided context and conversation; if they do not contain the answer, say so instead of guessing.",
+            history,
+            DATA_RULES,
+            blocks,
+            question,
+            feedback_part(feedback)
+        );
+        let text = self.client.generate_response(&prompt).await?;
+        Ok(RagAnswer {
+            text: stale_warning(&stale) + &prompt_guard::removed_note(removed) + &confidence.annotate(text),
+            confidence,
+            context,
+        })
+    }
+
+    /// [`retrieve`](Self::retrieve), after re-indexing the retrieved files
+    /// that changed since they were indexed and retrieving again. While a
+    /// build is still running those files are left to it and returned
+    /// last, so the answer can warn that it may rest on old code; so are
+    /// files whose re-indexing failed.
+    async fn retrieve_fresh(
+        &self,
+        query: &str,
+        top_k: usize,
+    ) -> Result<(RetrievalConfidence, Vec<String>, RetrievalStats, Vec<String>)> {
+        let (confidence, chunks, stats) = self.retrieve(query, top_k).await?;
+        let stale = self.stale_files(&chunks).await?;
+        if stale.is_empty() || !self.index_status().done {
+            return Ok((confidence, chunks, stats, stale));
+        }
+        eprintln!(
+            "Re-indexing {} file(s) changed since indexing: {}",
+            stale.len(),
+            stale.join(", ")
+        );
+        if self.reindex_files(&stale).await.is_err() {
+            return Ok((confidence, chunks, stats, stale));
+        }
+        let (confidence, chunks, stats) = self.retrieve(query, top_k).await?;
+        Ok((confidence, chunks, stats, Vec::new()))
+    }
+
+    /// The indexed files behind `chunks`, up to [`STALE_CHECK_FILES`] of
+    /// them, that changed on disk or were deleted since they were indexed.
+    /// Chunks from other collections aren't in this index and are skipped,
+    /// and so are collections of documents, whose text isn't the file's.
+    async fn stale_files(&self, chunks: &[String]) -> Result<Vec<String>> {
+        if !self.project_files {
+            return Ok(Vec::new());
+        }
+        let mut paths: Vec<&str> = Vec::new();
+        for path in chunks.iter().filter_map(|chunk| chunk.lines().next()?.strip_prefix("FILE: ")) {
+            let path = path.trim();
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+            if paths.len() == STALE_CHECK_FILES {
+                break;
+            }
+        }
+        let mut stale = Vec::new();
+        for path in paths {
+            if path.contains("://") || !is_supported_file(Path::new(path)) {
+                continue;
+            }
+            let Some(indexed) = self.storage.get_file_hash(path.to_string()).await? else {
+                continue;
+            };
+            if !indexed.is_empty() && self.scanner.file_hash(Path::new(path)).as_deref() != Some(indexed.as_str()) {
+                stale.push(path.to_string());
+            }
+        }
+        Ok(stale)
+    }
+
+    /// Re-embed `paths`, and drop the ones that no longer exist from the
+    /// index.
+    async fn reindex_files(&self, paths: &[String]) -> Result<()> {
+        let (present, gone): (Vec<PathBuf>, Vec<PathBuf>) =
+            paths.iter().map(PathBuf::from).partition(|path| path.is_file());
+        for path in gone {
+            self.storage.delete_file(path.to_string_lossy().to_string()).await?;
+        }
+        let scans = self.scanner.scan_paths(&present)?;
+        self.index_scans(scans, false).await
+    }
+
+    /// Print (with `--verbose`) what made it into the prompt's context:
+    /// `assembled` before the budget cut it down to `context`.
+    fn report_context(&self, stats: RetrievalStats, assembled: &[String], context: &[String]) {
+        let report = ContextReport::new(stats, assembled, context, self.config.rag_max_context_chars);
+        eprint!("{}", report.render());
+    }
+
+    /// The versions the lockfiles pin, for questions about dependencies, so
+    /// the answer doesn't fall back on versions the model remembers.
+    fn versions_context(&self, question: &str) -> Option<String> {
+        let dependencies = locked_dependencies(self.scanner.root());
+        if !lockfiles::is_about_dependencies(question, &dependencies) {
+            return None;
+        }
+        Some(versions_summary(&dependencies)).filter(|summary| !summary.is_empty())
+    }
+
+    /// The best-matching chunks of the docs of each dependency `question`
+    /// names, embedded on the fly. Docs that can't be fetched are reported
+    /// and left out; the answer doesn't depend on them.
+    async fn crate_context(&self, question: &str) -> Vec<String> {
+        let Some(crate_docs) = &self.crate_docs else {
+            return Vec::new();
+        };
+        let dependencies = rust_dependencies(self.scanner.root());
+        let mut chunks = Vec::new();
+        for dependency in mentioned_crates(question, &dependencies).into_iter().take(MAX_CRATES) {
+            match self.crate_chunks(crate_docs, dependency, question).await {
+                Ok(found) => chunks.extend(found),
+                Err(e) => eprintln!("Leaving out the {} docs: {}", dependency.name, e),
+            }
+        }
+        chunks
+    }
+
+    async fn crate_chunks(
+        &self,
+        crate_docs: &CrateDocs,
+        dependency: &Dependency,
+        question: &str,
+    ) -> Result<Vec<String>> {
+        let text = crate_docs.fetch(dependency).await?;
+        let label = format!("{} {}", dependency.name, dependency.version);
+        let chunks: Vec<Embedding> = self
+            .scanner
+            .chunk_document(truncate_chars(&text, MAX_CRATE_DOC_CHARS), &label)
+            .into_iter()
+            .map(|chunk| Embedding {
+                id: format!("{}:{}", label, chunk.start_offset),
+                vector: Vec::new(),
+                text: format!("CRATE DOCS: {}\n{}", label, chunk.text),
+                path: label.clone(),
+                start_offset: chunk.start_offset,
+                language: String::new(),
+                ordinal: chunk.ordinal,
+                chunk_hash: String::new(),
+                cell: None,
+            })
+            .collect();
+        let embedded = self.embedder.generate_embeddings(&chunks).await?;
+        let query = self.client.generate_embedding(question).await?;
+        Ok(SearchEngine::find_scored_chunks(&query, &embedded, CRATE_DOC_CHUNKS)
+            .into_iter()
+            .map(|(_, chunk)| chunk.text.clone())
+            .collect())
+    }
+
+    /// [`retrieve_chunks`](Self::retrieve_chunks) for `text` in this
+    /// collection and each further one, each embedding `text` with its own
+    /// model and keeping at most its own `top_k`. Confidence is the best
+    /// collection's.
+    async fn retrieve(
+        &self,
+        text: &str,
+        top_k: usize,
+    ) -> Result<(RetrievalConfidence, Vec<String>, RetrievalStats)> {
+        let embedding = self.client.generate_embedding(text).await?;
+        let (mut confidence, chunks, mut stats) = self.retrieve_chunks(&embedding, top_k).await?;
+        if self.collections.is_empty() {
+            return Ok((confidence, chunks, stats));
+        }
+        let mut lists = vec![chunks];
+        for collection in &self.collections {
+            let embedding = collection.client.generate_embedding(text).await?;
+            let top_k = top_k.min(collection.config.rag_top_k);
+            let (found, chunks, found_stats) = collection.retrieve_chunks(&embedding, top_k).await?;
+            if found.top_score > confidence.top_score {
+                confidence = found;
+            }
+            lists.push(chunks);
+            stats.merge(found_stats);
+        }
+        Ok((confidence, interleave(lists), stats))
+    }
+
+    /// Best `top_k` chunks for the query that reach the minimum score, how
+    /// confident the match is, and, when enabled, the chunks around the
+    /// strongest hits. Confidence is judged before the cutoff, so a question
+    /// nothing matches well is still flagged. The chunks are picked from a
+    /// wider pool by maximal marginal relevance, so near-copies and one
+    /// dominant file don't crowd out the rest. The stats say how each
+    /// candidate fared, for `--verbose`.
+    async fn retrieve_chunks(
+        &self,
+        query_embedding: &[f32],
+        top_k: usize,
+    ) -> Result<(RetrievalConfidence, Vec<String>, RetrievalStats)> {
+        // Candidates fetched per requested chunk in the coarse quantized stage.
+        const RESCORE_FACTOR: usize = 4;
+        // Best-scoring chunks per requested chunk that diversification picks from.
+        const DIVERSITY_POOL_FACTOR: usize = 3;
+        let pool = top_k * DIVERSITY_POOL_FACTOR;
+
+        let file_summaries = if self.config.rag_summary_files > 0 {
+            self.storage.get_file_summary_vectors().await?
+        } else {
+            Vec::new()
+        };
+
+        let candidates = if !file_summaries.is_empty() {
+            // Two-stage: pick the files whose summaries match, then rank
+            // only their chunks.
+            let mut files: Vec<(f32, String)> = file_summaries
+                .into_iter()
+                .map(|(path, vector)| (SearchEngine::cosine_similarity(query_embedding, &vector), path))
+                .collect();
+            files.sort_by(|a, b| b.0.total_cmp(&a.0));
+            files.truncate(self.config.rag_summary_files);
+            let paths = files.into_iter().map(|(_, path)| path).collect();
+            self.storage.get_embeddings_in_files(paths).await?
+        } else if self.storage.quantization() == Quantization::None {
+            if self.config.vector_cache {
+                let cache = self.load_vector_cache().await?;
+                let ids = cache
+                    .top_ids(query_embedding, pool)
+                    .into_iter()
+                    .map(|(_, id)| id)
+                    .collect();
+                self.storage.get_embeddings_by_ids(ids).await?
+            } else {
+                self.storage.get_all_embeddings().await?
+            }
+        } else {
+            let entries = self.storage.get_quantized_embeddings().await?;
+            let ids = SearchEngine::find_candidate_ids(
+                query_embedding,
+                &entries,
+                top_k * RESCORE_FACTOR,
+            );
+            self.storage.get_embeddings_by_ids(ids).await?
+        };
+        let scored = SearchEngine::find_scored_chunks(query_embedding, &candidates, pool);
+        let scores: Vec<f32> = scored.iter().take(top_k).map(|(score, _)| *score).collect();
+        let confidence = RetrievalConfidence::from_scores(&scores);
+        let (relevant, below): (Vec<_>, Vec<_>) = scored
+            .into_iter()
+            .partition(|(score, _)| *score >= self.config.rag_min_score);
+        let picked = SearchEngine::diversify(
+            relevant.clone(),
+            top_k,
+            self.config.rag_mmr_lambda,
+            self.config.rag_max_chunks_per_file,
+        );
+        let chunk_score = |(score, embedding): &(f32, &Embedding)| ChunkScore {
+            label: chunk_label(&embedding.text),
+            score: *score,
+        };
+        let picked_ids: HashSet<&str> = picked.iter().map(|(_, e)| e.id.as_str()).collect();
+        let stats = RetrievalStats {
+            candidates: candidates.len(),
+            min_score: self.config.rag_min_score,
+            picked: picked.iter().map(chunk_score).collect(),
+            below_min_score: below.iter().map(chunk_score).collect(),
+            not_picked: relevant
+                .iter()
+                .filter(|(_, e)| !picked_ids.contains(e.id.as_str()))
+                .map(chunk_score)
+                .collect(),
+        };
+        let hits: Vec<&Embedding> = picked.into_iter().map(|(_, embedding)| embedding).collect();
+        let chunks = if self.config.rag_expand_neighbors {
+            self.expand_neighbors(&hits).await?
         } else {
-            format!("\n\nUser feedback for improvement: {}", feedback)
+            hits.iter().map(|hit| hit.text.clone()).collect()
         };
-        let prompt = format!("You are an expert software engineer. Based on the provided code context and directory structure, {}{} \n\nContext:\n{}\n\nProvide a concise summary that includes:\n- Project purpose\n- Main features\n- Technologies used\n- Architecture\n- Complete directory structure (copy exactly from the DIRECTORY TREE section in the context)\n\nBe accurate and base your answer only on the provided context. Do not invent or modify the directory structure.", question, feedback_part, context);
-        self.client.generate_response(&prompt).await
+        Ok((confidence, chunks, stats))
+    }
+
+    /// Surround each of the strongest hits with the previous and next chunk
+    /// of its file, so code split across a chunk boundary arrives whole.
+    async fn expand_neighbors(&self, hits: &[&Embedding]) -> Result<Vec<String>> {
+        let expanded = hits.len().min(NEIGHBOR_EXPANSION_HITS);
+        let anchors = hits[..expanded]
+            .iter()
+            .map(|hit| (hit.path.clone(), hit.start_offset))
+            .collect();
+        let neighbors = self.storage.get_neighbor_chunks(anchors).await?;
+
+        // Neighbors that are hits themselves keep their own place.
+        let mut seen: HashSet<String> = hits.iter().map(|hit| hit.text.clone()).collect();
+        let mut chunks = Vec::with_capacity(hits.len() + 2 * expanded);
+        for (i, hit) in hits.iter().enumerate() {
+            let (before, after) = neighbors.get(i).cloned().unwrap_or_default();
+            if let Some(before) = before.filter(|text| seen.insert(text.clone())) {
+                chunks.push(before);
+            }
+            chunks.push(hit.text.clone());
+            if let Some(after) = after.filter(|text| seen.insert(text.clone())) {
+                chunks.push(after);
+            }
+        }
+        Ok(chunks)
+    }
+
+    /// Reuse the mapped matrix across queries until the index changes.
+    async fn load_vector_cache(&self) -> Result<Arc<VectorMatrixCache>> {
+        let generation = self.storage.generation().await?;
+        let mut slot = self.vector_cache.lock().await;
+        if let Some(cache) = slot.as_ref() {
+            if cache.generation() == generation {
+                return Ok(Arc::clone(cache));
+            }
+        }
+        let cache = Arc::new(VectorMatrixCache::load(&self.storage).await?);
+        *slot = Some(Arc::clone(&cache));
+        Ok(cache)
     }
 
     fn filter_files_by_patterns(&self, files: &[PathBuf]) -> Vec<PathBuf> {
@@ -157,9 +1176,8 @@ impl RagService {
                 return true; // ** matches everything
             }
             path.contains(&format!("/{}", prefix)) || path.starts_with(prefix)
-        } else if pattern.starts_with("*.") {
+        } else if let Some(ext) = pattern.strip_prefix("*.") {
             // File extension pattern like "*.rs"
-            let ext = &pattern[2..];
             path.ends_with(&format!(".{}", ext))
         } else {
             // Exact match or contains
@@ -167,89 +1185,262 @@ impl RagService {
         }
     }
 
-    fn filter_relevant_keywords(&self, keywords: &[String]) -> Vec<String> {
-        // Filter out common stop words and very short words
-        let stop_words = [
-            "the", "a", "an", "and", "or", "but", "in", "on", "at", "to", "for", "of", "with", "by",
-            "is", "are", "was", "were", "be", "been", "being", "have", "has", "had", "do", "does",
-            "did", "will", "would", "could", "should", "may", "might", "must", "can", "shall",
-            "this", "that", "these", "those", "i", "you", "he", "she", "it", "we", "they", "me",
-            "him", "her", "us", "them", "my", "your", "his", "its", "our", "their", "what", "which",
-            "who", "when", "where", "why", "how", "all", "any", "both", "each", "few", "more",
-            "most", "other", "some", "such", "no", "nor", "not", "only", "own", "same", "so",
-            "than", "too", "very", "just", "now", "here", "there", "then", "once", "also",
-            "explain", "available", "list", "show", "get", "find", "search", "query", "select"
-        ];
-
-        keywords.iter()
-            .filter(|k| {
-                let k_lower = k.to_lowercase();
-                k.len() >= 3 && !stop_words.contains(&k_lower.as_str())
-            })
-            .cloned()
-            .collect()
+    fn filter_relevant_keywords(&self, words: &[String]) -> Vec<String> {
+        keywords(&words.join(" "))
     }
 
     async fn build_index_with_files(&self, files: &[PathBuf]) -> Result<()> {
-        eprintln!("Scanning {} files...", files.len());
-        let mut inputs: Vec<EmbeddingInput> = Vec::new();
-
-        // Add a small directory overview chunk to help the model understand layout.
-        let dir_overview = self.scanner.directory_overview(4, 400);
-        if !dir_overview.is_empty() {
-            let dir_hash = format!("{:x}", md5::compute(dir_overview.as_bytes()));
-            let meta = self.storage.get_file_hash("__dir_overview__".to_string()).await?;
-            if meta.as_deref() != Some(dir_hash.as_str()) {
-                self.storage
-                    .delete_embeddings_for_path("__dir_overview__".to_string()).await?;
-                inputs.push(EmbeddingInput {
-                    id: format!("__dir_overview__:{dir_hash}"),
-                    path: "__dir_overview__".to_string(),
-                    text: format!("DIRECTORY TREE:\n{}", dir_overview),
-                });
-                self.storage
-                    .upsert_file_hash("__dir_overview__".to_string(), dir_hash).await?;
-            }
-        }
-
-        let scans = self.scanner.scan_paths(files)?;
+        self.index_status.send_replace(IndexStatus::default());
+        // Scanning runs on its own thread ahead of indexing, and waits
+        // whenever the queue is full.
+        let (sender, scans) = mpsc::channel(SCAN_QUEUE);
+        let scanner = self.scanner.clone();
+        let files = files.to_vec();
+        let scan = tokio::task::spawn_blocking(move || {
+            scanner.scan_paths_with(&files, |scan| sender.blocking_send(scan).is_ok())
+        });
+        // Indexing drops the queue when it fails, which stops the scan.
+        let result = self.index_stream(scans, true).await;
+        let result = result.and(scan.await.map_err(Into::into));
+        self.finish_index(&result);
+        result
+    }
+
+    /// Embed `documents` that aren't stored with the same text yet, as if
+    /// they were files at their locations; for pages that aren't on disk,
+    /// such as fetched documentation.
+    pub async fn index_documents(&self, documents: &[DocPage]) -> Result<()> {
+        self.index_status.send_replace(IndexStatus::default());
+        let scans = documents
+            .iter()
+            .map(|document| FileScanResult {
+                path: document.location.clone(),
+                hash: format!("{:x}", md5::compute(document.text.as_bytes())),
+                chunks: self.scanner.chunk_document(&document.text, &document.location),
+            })
+            .collect();
+        let result = self.index_scans(scans, false).await;
+        self.finish_index(&result);
+        result
+    }
+
+    /// A single-chunk file generated from the project rather than read
+    /// from it; `None` when `text` is already embedded.
+    async fn pseudo_file(&self, path: &str, text: String) -> Result<Option<PendingFile>> {
+        let hash = format!("{:x}", md5::compute(text.as_bytes()));
+        let meta = self.storage.get_file_hash(path.to_string()).await?;
+        if meta.as_deref() == Some(hash.as_str()) {
+            return Ok(None);
+        }
+        Ok(Some(PendingFile {
+            path: path.to_string(),
+            chunks: vec![Embedding {
+                id: format!("{path}:{hash}"),
+                vector: Vec::new(),
+                text,
+                path: path.to_string(),
+                start_offset: 0,
+                language: String::new(),
+                ordinal: 0,
+                chunk_hash: String::new(),
+                cell: None,
+            }],
+            hash,
+        }))
+    }
+
+    fn finish_index(&self, result: &Result<()>) {
+        self.index_status.send_modify(|status| {
+            status.done = true;
+            status.error = result.as_ref().err().map(|e| e.to_string());
+        });
+    }
+
+    /// Embed the chunks that changed in the scanned files whose hash
+    /// changed, plus the directory overview and dependency versions when
+    /// `overview` is set.
+    async fn index_scans(&self, scans: Vec<FileScanResult>, overview: bool) -> Result<()> {
+        let (sender, receiver) = mpsc::channel(scans.len().max(1));
         for scan in scans {
-            if scan.hash.is_empty() || scan.chunks.is_empty() {
-                continue;
+            let _ = sender.try_send(Ok(scan));
+        }
+        drop(sender);
+        self.index_stream(receiver, overview).await
+    }
+
+    /// Index files as their scans arrive: the changed chunks of changed
+    /// files are gathered into batches, and each batch is embedded while the
+    /// one before it is stored. The queues between the stages are bounded,
+    /// so memory stays flat however large the project, and the first files
+    /// are searchable before the scan is over. Adds the directory overview
+    /// and dependency versions first when `overview` is set.
+    async fn index_stream(
+        &self,
+        mut scans: mpsc::Receiver<Result<FileScanResult>>,
+        overview: bool,
+    ) -> Result<()> {
+        let (embedded_sender, mut embedded) = mpsc::channel::<EmbeddedBatch>(STORE_QUEUE);
+
+        let embed = async move {
+            let mut batch: Vec<PendingFile> = Vec::new();
+            if overview {
+                // A small directory overview chunk helps the model understand layout.
+                let dir_overview = self.scanner.directory_overview(4, 400);
+                if !dir_overview.is_empty() {
+                    let text = format!("DIRECTORY TREE:\n{}", dir_overview);
+                    batch.extend(self.pseudo_file(DIR_OVERVIEW_PATH, text).await?);
+                }
+                let versions = versions_summary(&locked_dependencies(self.scanner.root()));
+                if versions.is_empty() {
+                    // The lockfiles are gone; so are the versions they pinned.
+                    self.storage.delete_file(DEPENDENCY_VERSIONS_PATH.to_string()).await?;
+                } else {
+                    batch.extend(self.pseudo_file(DEPENDENCY_VERSIONS_PATH, versions).await?);
+                }
             }
+            let mut batch_chunks: usize = batch.iter().map(|f| f.chunks.len()).sum();
+            self.index_status
+                .send_modify(|status| status.total_chunks += batch_chunks);
 
-            eprintln!("Processing {}...", scan.path);
-            let previous_hash = self.storage.get_file_hash(scan.path.clone()).await?;
-            if previous_hash.as_deref() == Some(scan.hash.as_str()) {
-                continue;
+            // Unchanged files still missing a summary (e.g. summaries were
+            // just turned on): (path, text) to summarize without re-embedding.
+            let mut unsummarized: Vec<(String, String)> = Vec::new();
+            loop {
+                let next = scans.recv().await;
+                let scanned = next.is_none();
+                if let Some(scan) = next {
+                    if let Some(file) = self.pending_file(scan?, &mut unsummarized).await? {
+                        let chunks = file.chunks.len();
+                        batch_chunks += chunks;
+                        batch.push(file);
+                        self.index_status
+                            .send_modify(|status| status.total_chunks += chunks);
+                    }
+                } else {
+                    self.index_status.send_modify(|status| status.scanned = true);
+                }
+                if !batch.is_empty() && (batch_chunks >= COMMIT_CHUNKS || scanned) {
+                    let (mut embeddings, changed): (Vec<Embedding>, Vec<Embedding>) = batch
+                        .iter()
+                        .flat_map(|f| f.chunks.iter().cloned())
+                        .partition(|chunk| !chunk.vector.is_empty());
+                    embeddings.extend(self.embedder.generate_embeddings(&changed).await?);
+                    let files = std::mem::take(&mut batch);
+                    if embedded_sender.send((files, embeddings)).await.is_err() {
+                        // Storing failed; its error is reported below.
+                        break;
+                    }
+                    batch_chunks = 0;
+                }
+                if scanned {
+                    break;
+                }
             }
+            Ok::<_, anyhow::Error>(unsummarized)
+        };
 
-            // File changed; drop old embeddings for this path.
-            self.storage.delete_embeddings_for_path(scan.path.clone()).await?;
-
-            for chunk in scan.chunks {
-                let id = format!("{}:{}", chunk.path, chunk.start_offset);
-                let text = format!(
-                    "FILE: {}\nOFFSET: {}\n{}",
-                    chunk.path, chunk.start_offset, chunk.text
-                );
-                inputs.push(EmbeddingInput {
-                    id,
-                    path: chunk.path,
-                    text,
-                });
+        // Each batch is committed together, so a partial index is
+        // searchable and an interrupted build only redoes the uncommitted
+        // files (their hashes are recorded last).
+        let store = async move {
+            while let Some((files, embeddings)) = embedded.recv().await {
+                self.store_batch(files, embeddings).await?;
             }
+            Ok::<_, anyhow::Error>(())
+        };
 
-            self.storage.upsert_file_hash(scan.path, scan.hash).await?;
+        let (unsummarized, stored) = tokio::join!(embed, store);
+        let unsummarized = unsummarized?;
+        stored?;
+        for (path, text) in unsummarized {
+            self.summarize_file(&path, &text).await?;
         }
+        Ok(())
+    }
 
-        if !inputs.is_empty() {
-            eprintln!("Generating embeddings for {} chunks...", inputs.len());
-            let embeddings = self.embedder.generate_embeddings(&inputs).await?;
-            eprintln!("Storing embeddings...");
-            self.storage.insert_embeddings(embeddings).await?;
-            eprintln!("Indexing complete - {} chunks processed", inputs.len());
+    /// The chunks of `scan` to store, those that changed without vectors;
+    /// `None` when there is nothing to index or the file is unchanged (an
+    /// unchanged file still missing its summary is noted in
+    /// `unsummarized`).
+    async fn pending_file(
+        &self,
+        scan: FileScanResult,
+        unsummarized: &mut Vec<(String, String)>,
+    ) -> Result<Option<PendingFile>> {
+        if scan.hash.is_empty() || scan.chunks.is_empty() {
+            return Ok(None);
         }
+
+        let previous_hash = self.storage.get_file_hash(scan.path.clone()).await?;
+        if previous_hash.as_deref() == Some(scan.hash.as_str()) {
+            if self.config.rag_summary_files > 0 && !self.storage.has_file_summary(scan.path.clone()).await? {
+                let text = scan.chunks.into_iter().map(|c| c.text).collect::<Vec<_>>().join("\n");
+                unsummarized.push((scan.path, text));
+            }
+            return Ok(None);
+        }
+
+        // Chunks whose content is unchanged keep their vectors, even
+        // when edits above them moved their offsets.
+        let known = self.storage.get_chunk_vectors(scan.path.clone()).await?;
+        let chunks = scan
+            .chunks
+            .into_iter()
+            .map(|chunk| {
+                let chunk_hash = format!("{:x}", md5::compute(chunk.text.as_bytes()));
+                Embedding {
+                    id: format!("{}:{}", chunk.path, chunk.start_offset),
+                    vector: known.get(&chunk_hash).cloned().unwrap_or_default(),
+                    text: chunk_context(&chunk),
+                    path: chunk.path,
+                    start_offset: chunk.start_offset,
+                    language: chunk.language,
+                    ordinal: chunk.ordinal,
+                    chunk_hash,
+                    cell: chunk.cell,
+                }
+            })
+            .collect();
+        Ok(Some(PendingFile {
+            path: scan.path,
+            hash: scan.hash,
+            chunks,
+        }))
+    }
+
+    /// Replace the stored chunks of `files` with `embeddings`, summarize
+    /// them if summaries are on, and record their hashes last.
+    async fn store_batch(&self, files: Vec<PendingFile>, embeddings: Vec<Embedding>) -> Result<()> {
+        let batch_chunks: usize = files.iter().map(|f| f.chunks.len()).sum();
+        for file in &files {
+            // File changed; drop old embeddings for this path.
+            self.storage.delete_embeddings_for_path(file.path.clone()).await?;
+        }
+        self.storage.insert_embeddings(embeddings).await?;
+        for file in files {
+            if self.config.rag_summary_files > 0 && !is_pseudo_file(&file.path) {
+                let text = file.chunks.iter().map(|c| c.text.as_str()).collect::<Vec<_>>().join("\n");
+                self.summarize_file(&file.path, &text).await?;
+            }
+            self.storage.upsert_file_hash(file.path, file.hash).await?;
+        }
+        self.index_status
+            .send_modify(|status| status.embedded_chunks += batch_chunks);
         Ok(())
     }
+
+    /// Have the model describe the file in a few sentences and store the
+    /// summary's embedding for the file-level retrieval stage.
+    async fn summarize_file(&self, path: &str, text: &str) -> Result<()> {
+        let prompt = format!(
+            "Summarize what the file `{}` does in two or three sentences, naming its main types, functions and responsibilities. Reply with the summary only.\n\n{}",
+            path,
+            truncate_chars(text, SUMMARY_INPUT_CHARS)
+        );
+        let summary = self.background_client.generate_response(&prompt).await?;
+        let vector = self.background_client.generate_embedding(&summary).await?;
+        self.storage
+            .upsert_file_summary(path.to_string(), summary, vector)
+            .await
+    }
 }
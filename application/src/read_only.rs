@@ -0,0 +1,285 @@
+//! The commands known to only read. Anything not on this list is treated as
+//! changing the system: only listed commands are rated
+//! [`Safe`](crate::safety_service::RiskTier::Safe), run as `when` conditions
+//! or agent `check`s without asking, or repeated by watch mode.
+
+use crate::env_review::{program_words, split_segments};
+use crate::safety_service::SSH_KEYGEN_READ_OPTIONS;
+
+/// Shell keywords that may start a segment (`if test -d x; then ls; fi`).
+const KEYWORDS: &[&str] = &["if", "then", "else", "elif", "fi", "while", "until", "do", "done", "!", "{", "}"];
+
+/// Programs that only read, whatever their arguments.
+const READERS: &[&str] = &[
+    "ls", "cat", "head", "tail", "less", "more", "wc", "grep", "egrep", "fgrep", "zgrep", "rg", "cut",
+    "tr", "column", "nl", "fold", "fmt", "tac", "rev", "od", "hexdump", "strings", "base64", "zcat",
+    "bzcat", "xzcat", "jq", "diff", "cmp", "comm", "md5sum", "sha1sum", "sha256sum", "sha512sum",
+    "b2sum", "cksum", "stat", "file", "readlink", "realpath", "basename", "dirname", "tree", "du",
+    "df", "free", "uptime", "uname", "arch", "nproc", "whoami", "id", "groups", "who", "w", "last",
+    "ps", "pgrep", "pidof", "lsblk", "blkid", "lscpu", "lsusb", "lspci", "lsmod", "lsof", "lsattr",
+    "findmnt", "getconf", "getent", "printenv", "locale", "lsb_release", "which", "whereis", "type",
+    "test", "[", "[[", "true", "false", ":", "echo", "printf", "pwd", "cd", "sleep", "seq", "dig",
+    "nslookup", "host", "ping", "traceroute", "tracepath", "ss", "netstat", "dpkg-query", "apt-cache",
+];
+
+/// `find` actions that delete, run commands or write files.
+const FIND_WRITES: &[&str] = &[
+    "-delete", "-exec", "-execdir", "-ok", "-okdir", "-fprint", "-fprint0", "-fprintf", "-fls",
+];
+
+/// `ip` verbs that change an address, route, link or rule.
+const IP_WRITES: &[&str] = &[
+    "add", "del", "delete", "change", "replace", "set", "flush", "append", "prepend", "exec", "restore",
+];
+
+/// `curl` options that write files or send data.
+const CURL_WRITES: &[&str] = &[
+    "output", "remote-name", "remote-name-all", "upload-file", "data", "data-binary", "data-raw",
+    "data-urlencode", "json", "form", "request", "config", "cookie-jar", "dump-header",
+];
+
+/// `journalctl` options that delete or rewrite the journal.
+const JOURNALCTL_WRITES: &[&str] = &[
+    "vacuum-size", "vacuum-time", "vacuum-files", "rotate", "flush", "sync", "relinquish-var",
+    "setup-keys", "update-catalog",
+];
+
+/// `iptables` options that change a chain.
+const IPTABLES_WRITES: &[&str] = &[
+    "append", "insert", "delete", "replace", "flush", "new-chain", "delete-chain", "policy", "zero",
+    "rename-chain",
+];
+
+/// `dpkg` actions that only query the package database.
+const DPKG_QUERIES: &[&str] = &[
+    "-l", "-L", "-s", "-S", "-p", "--list", "--listfiles", "--status", "--search",
+    "--print-architecture", "--get-selections",
+];
+
+/// Why `cmd` isn't known to only read, if it isn't.
+pub fn not_read_only(cmd: &str) -> Option<String> {
+    if ["$(", "`", "<(", ">("].iter().any(|s| cmd.contains(s)) {
+        return Some("Runs a command substitution, which isn't checked.".to_string());
+    }
+    let lower = cmd.to_lowercase();
+    if lower.replace("2>&1", "").replace(">/dev/null", "").contains('>') {
+        return Some("Redirects output to a file.".to_string());
+    }
+    for segment in split_segments(cmd) {
+        let start = segment.iter().take_while(|w| KEYWORDS.contains(&w.as_str())).count();
+        let segment = segment.get(start..).unwrap_or_default();
+        // `command -v` only looks a program up.
+        if segment.first().is_some_and(|w| w == "command")
+            && matches!(segment.get(1).map(String::as_str), Some("-v" | "-V"))
+        {
+            continue;
+        }
+        let words = program_words(segment);
+        let wrappers = segment.get(..segment.len() - words.len()).unwrap_or_default();
+        if wrappers.iter().any(|w| w == "sudo" || w == "doas") {
+            return Some("Runs as another user.".to_string());
+        }
+        let words: Vec<&str> = words.iter().map(|w| w.trim_matches(['"', '\''])).collect();
+        let Some((program, args)) = words.split_first() else {
+            continue;
+        };
+        let name = program.rsplit('/').next().unwrap_or(program);
+        if !reads_only(name, args) {
+            return Some(format!("'{}' is not on the list of read-only commands.", words.join(" ")));
+        }
+    }
+    None
+}
+
+pub fn is_read_only(cmd: &str) -> bool {
+    not_read_only(cmd).is_none()
+}
+
+/// Whether `program` run with `args` only reads.
+fn reads_only(program: &str, args: &[&str]) -> bool {
+    if READERS.contains(&program) || args == ["--version"] {
+        return true;
+    }
+    match program {
+        "find" => !args.iter().any(|a| FIND_WRITES.contains(a)),
+        "sort" => !has_option(args, "o", &["output"]),
+        "sed" => !has_option(args, "i", &["in-place"]),
+        // A second operand is a file `uniq` writes to.
+        "uniq" => args.iter().filter(|a| !a.starts_with('-')).count() <= 1,
+        "awk" | "gawk" | "mawk" => {
+            !args.iter().any(|a| a.contains("system") || a.contains('|') || a.contains("getline"))
+        }
+        "date" => !has_option(args, "s", &["set"]) && args.iter().all(|a| a.starts_with(['-', '+'])),
+        "hostname" => args.iter().all(|a| a.starts_with('-')) && !has_option(args, "Fb", &["file", "boot"]),
+        "dmesg" => !has_option(args, "cCDEn", &["clear", "read-clear", "console-off", "console-on", "console-level"]),
+        "journalctl" => !has_option(args, "", JOURNALCTL_WRITES),
+        "mount" => args.iter().all(|a| matches!(*a, "-l" | "--show-labels")),
+        "ifconfig" => args.len() <= 1,
+        "ip" => !args.iter().any(|a| IP_WRITES.contains(a)),
+        "curl" => !has_option(args, "oOTdFXKcD", CURL_WRITES),
+        "ssh-keygen" => args.iter().any(|a| SSH_KEYGEN_READ_OPTIONS.contains(a)) && !args.contains(&"-R"),
+        "iptables" | "ip6tables" => {
+            has_option(args, "LS", &["list", "list-rules"]) && !has_option(args, "AIDRFXNPZE", IPTABLES_WRITES)
+        }
+        "firewall-cmd" => args.iter().all(|a| {
+            ["--list", "--get", "--query", "--state", "--zone", "--permanent"]
+                .iter()
+                .any(|p| a.starts_with(p))
+        }),
+        "ufw" => args.first() == Some(&"status"),
+        "nft" => args.first() == Some(&"list"),
+        "dpkg" => args.first().is_some_and(|a| DPKG_QUERIES.contains(a)),
+        "rpm" => args.first().is_some_and(|a| a.starts_with("-q") || a.starts_with("--query")),
+        // `-Q*` queries installed packages; `-S` only reads with `-s`/`-i`.
+        "pacman" => args.first().is_some_and(|a| {
+            a.starts_with("-Q")
+                || a.strip_prefix("-S")
+                    .is_some_and(|f| !f.is_empty() && f.chars().all(|c| "silgq".contains(c)))
+        }),
+        "openssl" => {
+            matches!(args.first(), Some(&("x509" | "verify" | "s_client" | "version" | "ciphers" | "crl")))
+                && !args.contains(&"-out")
+        }
+        "service" => args == ["--status-all"] || matches!(args, [_, "status"]),
+        _ => match subcommand(program, args) {
+            Some((sub, rest)) => reads_only_subcommand(program, sub, rest),
+            None => false,
+        },
+    }
+}
+
+/// Programs whose first operand is a subcommand, with their options that
+/// take a value before it.
+const SUBCOMMAND_PROGRAMS: &[(&str, &[&str])] = &[
+    ("git", &["-C", "-c", "--git-dir", "--work-tree", "--namespace"]),
+    ("docker", &["-H", "--host", "--context", "-c", "--config", "-l", "--log-level"]),
+    ("podman", &["--connection", "--url", "--log-level"]),
+    (
+        "kubectl",
+        &[
+            "-n", "--namespace", "--context", "--kubeconfig", "--cluster", "--user", "-l", "--selector", "-o",
+            "--output",
+        ],
+    ),
+    (
+        "systemctl",
+        &[
+            "-H", "--host", "-M", "--machine", "-p", "--property", "-t", "--type", "--state", "-n", "--lines",
+            "-o", "--output",
+        ],
+    ),
+    ("timedatectl", &["-H", "--host", "-M", "--machine"]),
+    ("hostnamectl", &["-H", "--host", "-M", "--machine"]),
+    ("localectl", &["-H", "--host", "-M", "--machine"]),
+    ("loginctl", &["-H", "--host", "-M", "--machine"]),
+    ("networkctl", &[]),
+    ("resolvectl", &[]),
+    ("apt", &["-o", "--option"]),
+    ("dnf", &["--setopt", "--repo", "--enablerepo", "--disablerepo"]),
+    ("yum", &["--setopt", "--enablerepo", "--disablerepo"]),
+    ("brew", &[]),
+    ("snap", &[]),
+    ("flatpak", &[]),
+    ("npm", &["--prefix"]),
+    ("pnpm", &["--dir", "-C"]),
+    ("yarn", &["--cwd"]),
+    ("pip", &[]),
+    ("pip3", &[]),
+    ("cargo", &["--manifest-path", "-Z", "--color"]),
+    ("go", &[]),
+];
+
+/// `program`'s subcommand and the arguments after it, if `program` takes
+/// one; the subcommand is empty when none is given (`systemctl` alone lists
+/// units).
+fn subcommand<'a>(program: &str, args: &'a [&'a str]) -> Option<(&'a str, &'a [&'a str])> {
+    let (_, valued) = SUBCOMMAND_PROGRAMS.iter().find(|(name, _)| *name == program)?;
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        if !arg.starts_with('-') {
+            return Some((arg, args.get(i + 1..).unwrap_or_default()));
+        }
+        i += if valued.contains(arg) { 2 } else { 1 };
+    }
+    Some(("", &[]))
+}
+
+fn reads_only_subcommand(program: &str, sub: &str, rest: &[&str]) -> bool {
+    match program {
+        "git" => match sub {
+            "" | "log" | "show" | "status" | "diff" | "blame" | "rev-parse" | "ls-files" | "ls-tree"
+            | "ls-remote" | "describe" | "shortlog" | "grep" | "cat-file" | "rev-list" | "show-ref"
+            | "whatchanged" | "help" => true,
+            // Listing only: a name would create, and `-d` delete.
+            "branch" | "tag" => rest.iter().all(|a| {
+                matches!(
+                    *a,
+                    "-a" | "-r" | "-v" | "-vv" | "-l" | "--list" | "--all" | "--remotes" | "--show-current"
+                        | "--merged" | "--no-merged"
+                )
+            }),
+            "remote" => {
+                rest.iter().all(|a| matches!(*a, "-v" | "--verbose"))
+                    || matches!(rest.first(), Some(&("show" | "get-url")))
+            }
+            "stash" => matches!(rest.first(), Some(&("list" | "show"))),
+            "config" => rest
+                .iter()
+                .any(|a| matches!(*a, "--get" | "--get-all" | "--get-regexp" | "-l" | "--list")),
+            _ => false,
+        },
+        "docker" | "podman" => match sub {
+            "ps" | "images" | "logs" | "inspect" | "version" | "info" | "top" | "port" | "history" | "stats" => true,
+            "container" | "image" | "volume" | "network" | "compose" => matches!(
+                rest.iter().find(|a| !a.starts_with('-')),
+                Some(&("ls" | "list" | "ps" | "inspect" | "logs" | "images" | "config"))
+            ),
+            _ => false,
+        },
+        "kubectl" => match sub {
+            "get" | "describe" | "logs" | "top" | "version" | "explain" | "api-resources" | "api-versions"
+            | "cluster-info" => true,
+            "config" => matches!(
+                rest.first(),
+                Some(&("view" | "get-contexts" | "current-context" | "get-clusters"))
+            ),
+            "rollout" => matches!(rest.first(), Some(&("status" | "history"))),
+            "auth" => rest.first() == Some(&"can-i"),
+            _ => false,
+        },
+        "systemctl" => {
+            matches!(sub, "" | "status" | "show" | "cat" | "get-default")
+                || sub.starts_with("is-")
+                || sub.starts_with("list-")
+        }
+        "timedatectl" | "hostnamectl" | "localectl" | "loginctl" | "networkctl" | "resolvectl" => {
+            matches!(sub, "" | "status" | "show" | "query") || sub.starts_with("list")
+        }
+        "apt" => matches!(sub, "list" | "show" | "search" | "policy" | "depends" | "rdepends"),
+        "dnf" | "yum" => {
+            matches!(sub, "list" | "info" | "search" | "provides" | "repolist" | "repoquery" | "check-update")
+        }
+        "brew" => matches!(sub, "list" | "info" | "search" | "outdated" | "deps" | "leaves" | "config"),
+        "snap" => matches!(sub, "list" | "info" | "find" | "version" | "changes"),
+        "flatpak" => matches!(sub, "list" | "info" | "search"),
+        "npm" | "pnpm" | "yarn" => matches!(sub, "ls" | "list" | "view" | "info" | "outdated" | "why" | "explain"),
+        "pip" | "pip3" => matches!(sub, "list" | "show" | "freeze" | "check"),
+        "cargo" => matches!(sub, "tree" | "metadata" | "search" | "version"),
+        "go" => match sub {
+            "version" | "list" | "doc" => true,
+            "env" => !has_option(rest, "wu", &[]),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Whether `args` has one of the single-letter options in `short` (alone
+/// or bundled, as in `-fsSLo`) or one of the `long` ones, with or without
+/// `=value`.
+fn has_option(args: &[&str], short: &str, long: &[&str]) -> bool {
+    args.iter().any(|a| match a.strip_prefix("--") {
+        Some(name) => long.iter().any(|l| name.split('=').next() == Some(l)),
+        None => a.strip_prefix('-').is_some_and(|bundle| bundle.chars().any(|c| short.contains(c))),
+    })
+}
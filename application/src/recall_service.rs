@@ -0,0 +1,143 @@
+//! `vibe recall`: questions answered from what vibe_cli said in earlier
+//! sessions (RAG answers, explanations, chat exchanges), embedded into the
+//! index database's `memories` table.
+
+use domain::models::Memory;
+use infrastructure::embedding_storage::EmbeddingStorage;
+use infrastructure::ollama_client::OllamaClient;
+use infrastructure::search::SearchEngine;
+use shared::types::Result;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Characters of one memory that are stored; the rest is cut.
+const MAX_MEMORY_CHARS: usize = 4_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryKind {
+    Rag,
+    Explain,
+    Chat,
+}
+
+impl MemoryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryKind::Rag => "rag",
+            MemoryKind::Explain => "explain",
+            MemoryKind::Chat => "chat",
+        }
+    }
+}
+
+/// An answer from memory and the memories it was drawn from, most similar
+/// first.
+#[derive(Debug, Clone)]
+pub struct Recollection {
+    pub answer: String,
+    pub sources: Vec<Memory>,
+}
+
+pub struct RecallService {
+    storage: EmbeddingStorage,
+    client: OllamaClient,
+}
+
+impl RecallService {
+    pub async fn new(db_path: &str, client: OllamaClient) -> Result<Self> {
+        Ok(Self {
+            storage: EmbeddingStorage::new(db_path).await?,
+            client,
+        })
+    }
+
+    /// Store `body` under `title` (the question, file or request it
+    /// answered). Remembering the same thing twice keeps one copy.
+    pub async fn remember(&self, kind: MemoryKind, title: &str, body: &str) -> Result<()> {
+        let text = format!("{}\n\n{}", title.trim(), body.trim());
+        let text = match text.char_indices().nth(MAX_MEMORY_CHARS) {
+            Some((end, _)) => text[..end].to_string(),
+            None => text,
+        };
+        let vector = self.client.generate_embedding(&text).await?;
+        self.storage
+            .insert_memory(Memory {
+                id: format!("{:x}", md5::compute(format!("{}\n{}", kind.as_str(), text))),
+                kind: kind.as_str().to_string(),
+                text,
+                vector,
+                created_at: now(),
+            })
+            .await
+    }
+
+    /// Up to `limit` memories at least `min_score` similar to `question`,
+    /// most similar first.
+    pub async fn search(&self, question: &str, limit: usize, min_score: f32) -> Result<Vec<Memory>> {
+        let memories = self.storage.get_memories().await?;
+        if memories.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = self.client.generate_embedding(question).await?;
+        let mut scored: Vec<(f32, Memory)> = memories
+            .into_iter()
+            .map(|m| (SearchEngine::cosine_similarity(&query, &m.vector), m))
+            .filter(|(score, _)| *score >= min_score)
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(scored.into_iter().take(limit).map(|(_, m)| m).collect())
+    }
+
+    /// Answer `question` from the memories [`search`](Self::search) finds.
+    /// Nothing is asked of the model when there are none.
+    pub async fn recall(&self, question: &str, limit: usize, min_score: f32) -> Result<Recollection> {
+        let sources = self.search(question, limit, min_score).await?;
+        if sources.is_empty() {
+            return Ok(Recollection {
+                answer: "Nothing from earlier sessions matches this question.".to_string(),
+                sources,
+            });
+        }
+        let answer = self
+            .client
+            .generate_response(&recall_prompt(question, &sources, now()))
+            .await?;
+        Ok(Recollection { answer, sources })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// `today`, `yesterday` or `N days ago`.
+pub fn age(created_at: u64, now: u64) -> String {
+    match now.saturating_sub(created_at) / 86_400 {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        days => format!("{} days ago", days),
+    }
+}
+
+pub fn recall_prompt(question: &str, memories: &[Memory], now: u64) -> String {
+    let notes = memories
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            format!(
+                "[{}] {} ({}):\n{}",
+                i + 1,
+                m.kind,
+                age(m.created_at, now),
+                m.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    format!(
+        "The user is asking about something from their earlier sessions with this tool. These are the most relevant things it told them then:\n\n{}\n\nQuestion: {}\n\nAnswer from these notes only, citing them by number like [1]. If they don't answer the question, say so.",
+        notes, question
+    )
+}
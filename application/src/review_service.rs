@@ -0,0 +1,309 @@
+//! `vibe review`: review comments on a change, one model call per changed
+//! file with its hunks (numbered as in the new version) and, optionally,
+//! related code from the RAG index.
+
+use crate::diff_explain_service::git_diff;
+use anyhow::anyhow;
+use infrastructure::ollama_client::OllamaClient;
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::fmt;
+
+/// Characters of one file's hunks sent to the model; the rest is cut.
+const MAX_FILE_DIFF_CHARS: usize = 16_000;
+
+/// Uncommitted changes, staged or not, against `HEAD`.
+pub fn diff_working_tree() -> Result<String> {
+    git_diff(&["HEAD"], &[])
+}
+
+/// Changes staged for the next commit.
+pub fn diff_staged() -> Result<String> {
+    git_diff(&["--cached"], &[])
+}
+
+/// What `HEAD` adds on top of `branch`, as a pull request from it would
+/// show (`git diff branch...HEAD`).
+pub fn diff_branch(branch: &str) -> Result<String> {
+    if branch.trim().is_empty() || branch.starts_with('-') {
+        return Err(anyhow!("invalid branch '{}'", branch));
+    }
+    git_diff(&[&format!("{}...HEAD", branch.trim())], &[])
+}
+
+/// One `@@` hunk. `new_start` and `new_len` give the lines it covers in the
+/// new version of the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub new_start: u32,
+    pub new_len: u32,
+    /// The `@@` line and the body.
+    pub text: String,
+}
+
+impl Hunk {
+    pub fn covers(&self, line: u32) -> bool {
+        line >= self.new_start && line < self.new_start + self.new_len.max(1)
+    }
+
+    /// The body with new-version line numbers in front of context and
+    /// added lines, so comments can point at them.
+    pub fn numbered(&self) -> String {
+        let mut line = self.new_start;
+        let mut out = String::new();
+        for text in self.text.lines() {
+            if text.starts_with("@@") || text.starts_with('-') || text.starts_with('\\') {
+                out.push_str(&format!("{:>6} {}\n", "", text));
+            } else {
+                out.push_str(&format!("{:>6} {}\n", line, text));
+                line += 1;
+            }
+        }
+        out
+    }
+}
+
+/// The hunks of one changed file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDiff {
+    /// Path in the new version, or the old one for a deleted file.
+    pub path: String,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FileDiff {
+    pub fn text(&self) -> String {
+        self.hunks.iter().map(|h| h.text.as_str()).collect()
+    }
+
+    pub fn covers(&self, line: u32) -> bool {
+        self.hunks.iter().any(|h| h.covers(line))
+    }
+}
+
+/// Split a unified diff (from git, or with plain `---`/`+++` headers) into
+/// per-file hunks. Hunk bodies are read by their line counts, so a removed
+/// line that starts with `--` is not taken for a header. Files without
+/// hunks, e.g. binary ones, are left out.
+pub fn split_diff(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    let mut old_path: Option<String> = None;
+    let mut remaining = (0u32, 0u32);
+    for line in diff.lines() {
+        if remaining.0 > 0 || remaining.1 > 0 {
+            if let Some(hunk) = files.last_mut().and_then(|f| f.hunks.last_mut()) {
+                hunk.text.push_str(line);
+                hunk.text.push('\n');
+            }
+            match line.chars().next() {
+                Some('-') => remaining.0 = remaining.0.saturating_sub(1),
+                Some('+') => remaining.1 = remaining.1.saturating_sub(1),
+                Some('\\') => {}
+                _ => {
+                    remaining.0 = remaining.0.saturating_sub(1);
+                    remaining.1 = remaining.1.saturating_sub(1);
+                }
+            }
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            old_path = Some(header_path(path, "a/"));
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            let new_path = header_path(path, "b/");
+            let path = match (new_path.as_str(), old_path.take()) {
+                ("/dev/null", Some(old)) => old,
+                _ => new_path,
+            };
+            files.push(FileDiff {
+                path,
+                hunks: Vec::new(),
+            });
+        } else if let Some((old_len, new_start, new_len)) = hunk_header(line) {
+            if let Some(file) = files.last_mut() {
+                file.hunks.push(Hunk {
+                    new_start,
+                    new_len,
+                    text: format!("{}\n", line),
+                });
+                remaining = (old_len, new_len);
+            }
+        }
+    }
+    files.retain(|f| !f.hunks.is_empty());
+    files
+}
+
+/// The path in a `---`/`+++` header, without git's `a/`/`b/` prefix or a
+/// trailing timestamp.
+fn header_path(header: &str, prefix: &str) -> String {
+    let path = header.split('\t').next().unwrap_or(header).trim();
+    path.strip_prefix(prefix).unwrap_or(path).to_string()
+}
+
+/// `(old_len, new_start, new_len)` of an `@@ -a,b +c,d @@` line.
+fn hunk_header(line: &str) -> Option<(u32, u32, u32)> {
+    let mut ranges = line.strip_prefix("@@ ")?.split_whitespace();
+    let range = |r: Option<&str>, sign: char| -> Option<(u32, u32)> {
+        let r = r?.strip_prefix(sign)?;
+        Some(match r.split_once(',') {
+            Some((start, len)) => (start.parse().ok()?, len.parse().ok()?),
+            None => (r.parse().ok()?, 1),
+        })
+    };
+    let (_, old_len) = range(ranges.next(), '-')?;
+    let (new_start, new_len) = range(ranges.next(), '+')?;
+    Some((old_len, new_start, new_len))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Nit,
+    Minor,
+    Major,
+    Critical,
+}
+
+impl Severity {
+    /// Unknown labels count as `minor`.
+    fn parse(label: &str) -> Self {
+        match label.trim().to_lowercase().as_str() {
+            "critical" | "blocker" | "error" => Severity::Critical,
+            "major" | "high" | "warning" => Severity::Major,
+            "nit" | "nitpick" | "style" | "info" => Severity::Nit,
+            _ => Severity::Minor,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Severity::Nit => "nit",
+            Severity::Minor => "minor",
+            Severity::Major => "major",
+            Severity::Critical => "critical",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// One review comment. `line` is in the new version of `file` and always
+/// inside one of its hunks; comments on other lines keep only the file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewComment {
+    pub severity: Severity,
+    pub file: String,
+    pub line: Option<u32>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+impl ReviewComment {
+    /// `file:line`, or just the file.
+    pub fn location(&self) -> String {
+        match self.line {
+            Some(line) => format!("{}:{}", self.file, line),
+            None => self.file.clone(),
+        }
+    }
+}
+
+/// Most severe first, then by file and line.
+pub fn sort_comments(comments: &mut [ReviewComment]) {
+    comments.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| a.file.cmp(&b.file))
+            .then_with(|| a.line.cmp(&b.line))
+    });
+}
+
+pub struct ReviewService {
+    client: OllamaClient,
+}
+
+impl ReviewService {
+    pub fn new(client: OllamaClient) -> Self {
+        Self { client }
+    }
+
+    /// Comments on one file's hunks. `context` holds related code, e.g.
+    /// from the RAG index; it may be empty.
+    pub async fn review_file(
+        &self,
+        file: &FileDiff,
+        context: &[String],
+    ) -> Result<Vec<ReviewComment>> {
+        let response = self
+            .client
+            .generate_response(&review_prompt(file, context))
+            .await?;
+        Ok(parse_comments(&response, file))
+    }
+}
+
+pub fn review_prompt(file: &FileDiff, context: &[String]) -> String {
+    let numbered: String = file.hunks.iter().map(Hunk::numbered).collect();
+    let (hunks, cut) = match numbered.char_indices().nth(MAX_FILE_DIFF_CHARS) {
+        Some((end, _)) => (&numbered[..end], true),
+        None => (numbered.as_str(), false),
+    };
+    let mut prompt = format!(
+        "You are an expert software engineer reviewing a change to {}. Point out bugs, unhandled errors and edge cases, security problems, and code that is hard to maintain. Comment only on the changed lines; do not praise or restate the change.\n\n",
+        file.path
+    );
+    if !context.is_empty() {
+        prompt.push_str("Related code from the project:\n");
+        prompt.push_str(&context.join("\n\n"));
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("Hunks, with line numbers of the new version:\n");
+    prompt.push_str(hunks);
+    if cut {
+        prompt.push_str("\n[diff truncated]\n");
+    }
+    prompt.push_str(
+        "\nRespond ONLY with a JSON array with one object per comment: {\"severity\": \"critical\", \"major\", \"minor\" or \"nit\", \"line\": <line number>, \"comment\": \"what is wrong and why\", \"suggestion\": \"the replacement code\" or null}. Respond [] if there is nothing to point out.",
+    );
+    prompt
+}
+
+#[derive(Deserialize)]
+struct ModelComment {
+    #[serde(default)]
+    severity: String,
+    #[serde(default)]
+    line: Option<u32>,
+    #[serde(default, alias = "message")]
+    comment: String,
+    #[serde(default)]
+    suggestion: Option<String>,
+}
+
+/// Comments from the model's JSON reply; anything unparseable or without a
+/// comment is dropped, and lines outside the file's hunks are cleared.
+pub fn parse_comments(response: &str, file: &FileDiff) -> Vec<ReviewComment> {
+    let (Some(start), Some(end)) = (response.find('['), response.rfind(']')) else {
+        return Vec::new();
+    };
+    if start >= end {
+        return Vec::new();
+    }
+    let Ok(found) = serde_json::from_str::<Vec<ModelComment>>(&response[start..=end]) else {
+        return Vec::new();
+    };
+    found
+        .into_iter()
+        .filter(|c| !c.comment.trim().is_empty())
+        .map(|c| ReviewComment {
+            severity: Severity::parse(&c.severity),
+            file: file.path.clone(),
+            line: c.line.filter(|&line| file.covers(line)),
+            message: c.comment.trim().to_string(),
+            suggestion: c
+                .suggestion
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+        })
+        .collect()
+}
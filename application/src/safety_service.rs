@@ -1,18 +1,393 @@
+use crate::env_review::{program_words, split_pipeline, split_segments};
+use crate::network_guard::{network_changes, NetworkChange};
+use crate::read_only::{is_read_only, not_read_only};
+use domain::command_plan::{CommandPlan, PlanStep, SafetyCheck};
 use domain::safety_policy::SafetyPolicy;
 use shared::types::Result;
 
+/// Assesses commands before they run: hard blocks, warnings, and whether
+/// they change the system, which decides how they are confirmed.
 pub struct SafetyService {
     policy: SafetyPolicy,
+    /// Also block `sudo` (safe mode).
+    ultra_safe: bool,
+}
+
+impl Default for SafetyService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SafetyService {
     pub fn new() -> Self {
         Self {
             policy: SafetyPolicy::new(),
+            ultra_safe: false,
         }
     }
 
-    pub fn validate(&self, plan: &domain::command_plan::CommandPlan) -> Result<()> {
+    pub fn with_ultra_safe(mut self, ultra_safe: bool) -> Self {
+        self.ultra_safe = ultra_safe;
+        self
+    }
+
+    pub fn assess(&self, cmd: &str) -> SafetyAssessment {
+        assess_command(cmd, self.ultra_safe)
+    }
+
+    pub fn tier(&self, cmd: &str) -> RiskTier {
+        risk_tier(cmd, &self.assess(cmd))
+    }
+
+    /// One check per step, failed if the step's command or any branch is
+    /// blocked.
+    pub fn checks(&self, steps: &[PlanStep]) -> Vec<SafetyCheck> {
+        steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| {
+                let blocked = [
+                    Some(&step.command),
+                    step.check.as_ref(),
+                    step.when.as_ref(),
+                    step.on_failure.as_ref(),
+                ]
+                .into_iter()
+                .flatten()
+                .find_map(|cmd| self.assess(cmd).reasons.into_iter().next());
+                SafetyCheck {
+                    check_type: match &blocked {
+                        Some(reason) => format!("step {}: {}", i + 1, reason),
+                        None => format!("step {}: not blocked", i + 1),
+                    },
+                    passed: blocked.is_none(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn validate(&self, plan: &CommandPlan) -> Result<()> {
         self.policy.validate(plan)
     }
 }
+
+pub struct SafetyAssessment {
+    pub blocked: bool,
+    pub reasons: Vec<String>,
+    pub warnings: Vec<String>,
+    /// How the command changes SSH access (keys, trusted hosts, the
+    /// server's configuration), which puts it in [`RiskTier::Ssh`].
+    pub ssh: Vec<String>,
+    /// How the command changes the firewall or the network, which can cut
+    /// off a remote session; puts it in [`RiskTier::Network`].
+    pub network: Vec<String>,
+}
+
+impl Default for SafetyAssessment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SafetyAssessment {
+    pub fn new() -> Self {
+        Self {
+            blocked: false,
+            reasons: Vec::new(),
+            warnings: Vec::new(),
+            ssh: Vec::new(),
+            network: Vec::new(),
+        }
+    }
+}
+
+pub fn assess_command(cmd: &str, ultra_safe: bool) -> SafetyAssessment {
+    let mut assessment = SafetyAssessment::new();
+    let lower = cmd.to_lowercase();
+
+    let removals = removals(cmd);
+
+    // Absolute hard blocks
+    if lower.contains("rm -rf /") || lower.contains("rm -rf /*") || removals.iter().any(|r| r.recursive && r.root) {
+        assessment.blocked = true;
+        assessment.reasons.push("Contains 'rm -rf /' which is catastrophic.".to_string());
+    }
+
+    if lower.contains("mkfs") {
+        assessment.blocked = true;
+        assessment.reasons.push("Contains 'mkfs' which can format disks.".to_string());
+    }
+
+    if lower.contains("dd if=") && (lower.contains("/dev/sd") || lower.contains("/dev/nvme")) {
+        assessment.blocked = true;
+        assessment.reasons.push("Contains 'dd' with a block device, potentially destructive.".to_string());
+    }
+
+    if lower.contains(">: /dev/sd") || lower.contains(">/dev/sd") || lower.contains(">/dev/nvme") {
+        assessment.blocked = true;
+        assessment
+            .reasons
+            .push("Redirecting output to a block device is destructive.".to_string());
+    }
+
+    if lower.contains("cryptsetup") {
+        assessment.blocked = true;
+        assessment
+            .reasons
+            .push("Contains 'cryptsetup', which can modify encrypted volumes.".to_string());
+    }
+
+    if ultra_safe && lower.contains("sudo") {
+        assessment.blocked = true;
+        assessment
+            .reasons
+            .push("Contains 'sudo' which is disallowed in ultra-safe mode.".to_string());
+    }
+
+    // Warnings
+    if (lower.contains("rm -rf") || removals.iter().any(|r| r.recursive && r.force)) && !assessment.blocked {
+        assessment
+            .warnings
+            .push("Uses 'rm -rf' which can be dangerous if misused.".to_string());
+    }
+
+    if lower.contains("chmod 777") {
+        assessment
+            .warnings
+            .push("Uses 'chmod 777' which is usually unsafe on shared systems.".to_string());
+    }
+
+    if lower.contains("chown -r") {
+        assessment
+            .warnings
+            .push("Uses 'chown -R' which can change many file owners recursively.".to_string());
+    }
+
+    assessment.ssh = ssh_risks(cmd);
+    assessment.network = network_changes(cmd).iter().map(NetworkChange::risk).collect();
+    assessment
+}
+
+/// The flags and target of an `rm` in a command.
+struct Removal {
+    recursive: bool,
+    force: bool,
+    /// Removes `/` or everything under it.
+    root: bool,
+}
+
+/// Each `rm` in `cmd`, with its flags in any order (`-rf`, `-fr`, `-r -f`,
+/// `--recursive --force`).
+fn removals(cmd: &str) -> Vec<Removal> {
+    split_segments(cmd)
+        .iter()
+        .filter_map(|segment| {
+            let (program, args) = program_words(segment).split_first()?;
+            if program.rsplit('/').next() != Some("rm") {
+                return None;
+            }
+            let mut removal = Removal {
+                recursive: false,
+                force: false,
+                root: false,
+            };
+            for arg in args.iter().map(|a| a.trim_matches(['"', '\''])) {
+                match arg {
+                    "--recursive" => removal.recursive = true,
+                    "--force" => removal.force = true,
+                    "/" | "/*" => removal.root = true,
+                    a if a.starts_with("--") => {}
+                    a if a.starts_with('-') => {
+                        removal.recursive |= a.contains(['r', 'R']);
+                        removal.force |= a.contains('f');
+                    }
+                    _ => {}
+                }
+            }
+            Some(removal)
+        })
+        .collect()
+}
+
+/// `ssh-keygen` options that read or convert keys rather than create one.
+pub(crate) const SSH_KEYGEN_READ_OPTIONS: &[&str] = &["-l", "-lf", "-F", "-y", "-e", "-i", "-B", "-L", "-Q", "-Y"];
+
+/// The home directory for a leading `~/`, so existing key files are found.
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home.trim_end_matches('/'), rest),
+        _ => path.to_string(),
+    }
+}
+
+/// Explanations of how `cmd` changes who can log in over SSH or which
+/// hosts are trusted: edits to `authorized_keys`, `ssh-keygen` overwriting
+/// an existing key or forgetting a host key, disabled host key checking,
+/// and other changes under `~/.ssh` or to the SSH server's configuration.
+pub fn ssh_risks(cmd: &str) -> Vec<String> {
+    let lower = cmd.to_lowercase();
+    let words: Vec<&str> = cmd
+        .split(|c: char| c.is_whitespace() || "|;&".contains(c))
+        .map(|w| w.trim_matches(['"', '\'']))
+        .filter(|w| !w.is_empty())
+        .collect();
+    let mutating = mutation_reason(cmd).is_some() || lower.contains("sed -i");
+    let mut risks = Vec::new();
+
+    if words.contains(&"ssh-copy-id") || (lower.contains("authorized_keys") && mutating) {
+        risks.push(
+            "Changes authorized_keys: whoever holds an added key can log in as that user without a password."
+                .to_string(),
+        );
+    }
+    if let Some(at) = words.iter().position(|w| w.rsplit('/').next() == Some("ssh-keygen")) {
+        let args = &words[at + 1..];
+        let value_of = |flag: &str| {
+            args.iter()
+                .position(|a| *a == flag)
+                .and_then(|i| args.get(i + 1))
+                .copied()
+        };
+        if let Some(host) = value_of("-R") {
+            risks.push(format!(
+                "Forgets the recorded host key of {}: the next connection accepts whatever key it presents, so check the new fingerprint out of band.",
+                host
+            ));
+        } else if !args.iter().any(|a| SSH_KEYGEN_READ_OPTIONS.contains(a)) {
+            let target = match value_of("-f") {
+                Some(path) => expand_home(path),
+                None => {
+                    let kind = value_of("-t").unwrap_or("ed25519").to_lowercase();
+                    expand_home(&format!("~/.ssh/id_{}", kind))
+                }
+            };
+            if std::path::Path::new(&target).exists() {
+                risks.push(format!(
+                    "Overwrites the existing key {}: the old private key is lost, and servers that trust it stop accepting you.",
+                    target
+                ));
+            }
+        }
+    }
+    // `-o Option=value` and `-o 'Option value'` are both accepted.
+    let compact = lower.replace([' ', '\t', '"', '\'', '='], "");
+    if compact.contains("stricthostkeycheckingno")
+        || compact.contains("stricthostkeycheckingoff")
+        || compact.contains("userknownhostsfile/dev/null")
+    {
+        risks.push(
+            "Turns off host key checking: a machine impersonating the server is accepted silently, exposing passwords and sessions to it."
+                .to_string(),
+        );
+    }
+    if mutating && lower.contains("sshd_config") {
+        risks.push(
+            "Changes the SSH server's configuration, which decides who may log in and how (root logins, passwords, keys)."
+                .to_string(),
+        );
+    }
+    if risks.is_empty() && mutating && (lower.contains(".ssh/") || lower.ends_with(".ssh")) {
+        risks.push(
+            "Changes files under ~/.ssh, which hold your private keys and the hosts you trust."
+                .to_string(),
+        );
+    }
+    risks
+}
+
+/// Programs/subcommands that change system state; used to keep repeatedly
+/// executed commands (watch mode) to observation only.
+const MUTATING_WORDS: &[&str] = &[
+    "rm", "mv", "cp", "dd", "mkfs", "chmod", "chown", "kill", "pkill", "killall", "reboot",
+    "shutdown", "install", "remove", "purge", "upgrade", "restart", "stop", "start", "enable",
+    "disable", "apply", "delete", "push", "commit", "tee", "truncate", "sed -i", "-delete", "mkdir",
+    "touch", "ln", "git reset", "git clean",
+];
+
+/// Programs that run code handed to them on standard input or with `-c` /
+/// `-e`, which no word list can vet.
+const INTERPRETERS: &[&str] = &[
+    "sh", "bash", "zsh", "dash", "ksh", "fish", "python", "python3", "perl", "ruby", "node", "php",
+];
+
+/// Why `cmd` is not safe to run unattended and repeatedly, if it isn't.
+pub fn read_only_violation(cmd: &str) -> Option<String> {
+    let assessment = assess_command(cmd, true);
+    let reason = assessment
+        .reasons
+        .first()
+        .or(assessment.ssh.first())
+        .or(assessment.network.first())
+        .or(assessment.warnings.first());
+    if let Some(reason) = reason {
+        return Some(reason.clone());
+    }
+    mutation_reason(cmd).or_else(|| not_read_only(cmd))
+}
+
+/// Why `cmd` changes files or system state, if it does.
+pub fn mutation_reason(cmd: &str) -> Option<String> {
+    let lower = cmd.to_lowercase();
+    if lower.replace("2>&1", "").replace(">/dev/null", "").contains('>') {
+        return Some("Redirects output to a file.".to_string());
+    }
+    let words: Vec<&str> = lower.split(|c: char| c.is_whitespace() || "|;&".contains(c)).collect();
+    for word in MUTATING_WORDS {
+        let hit = if word.contains(' ') {
+            lower.contains(word)
+        } else {
+            words.contains(word)
+        };
+        if hit {
+            return Some(format!("Uses '{}', which can change system state.", word));
+        }
+    }
+    for (segment, piped) in split_pipeline(cmd) {
+        let Some((program, args)) = program_words(&segment).split_first() else {
+            continue;
+        };
+        let program = program.rsplit('/').next().unwrap_or(program);
+        if !INTERPRETERS.contains(&program) {
+            continue;
+        }
+        if piped {
+            return Some(format!("Pipes into '{}', which runs whatever it is given.", program));
+        }
+        if let Some(flag) = args.iter().find(|a| matches!(a.as_str(), "-c" | "-e")) {
+            return Some(format!("Runs code passed to '{} {}', which can change system state.", program, flag));
+        }
+    }
+    None
+}
+
+/// How much harm a command can do, which decides how it is confirmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RiskTier {
+    Safe,
+    Mutating,
+    Dangerous,
+    /// Changes SSH keys or host trust; see [`ssh_risks`].
+    Ssh,
+    /// Changes the firewall or network; see [`network_changes`].
+    Network,
+    Blocked,
+}
+
+/// Only commands on the read-only allowlist ([`is_read_only`]) are
+/// [`RiskTier::Safe`]; anything else that isn't rated higher is mutating.
+pub fn risk_tier(cmd: &str, assessment: &SafetyAssessment) -> RiskTier {
+    if assessment.blocked {
+        RiskTier::Blocked
+    } else if !assessment.network.is_empty() {
+        RiskTier::Network
+    } else if !assessment.ssh.is_empty() {
+        RiskTier::Ssh
+    } else if !assessment.warnings.is_empty() {
+        RiskTier::Dangerous
+    } else if mutation_reason(cmd).is_some() || cmd.to_lowercase().contains("sudo") || !is_read_only(cmd) {
+        RiskTier::Mutating
+    } else {
+        RiskTier::Safe
+    }
+}
@@ -0,0 +1,242 @@
+//! What restarting or stopping a service interrupts: its processes, the
+//! ports it listens on and the connections it holds, probed read-only
+//! (`systemctl show`, the service's cgroup, `ss`) before the step is
+//! confirmed.
+
+use crate::env_review::{program_words, split_segments};
+use std::collections::BTreeSet;
+use std::process::{Command, Stdio};
+
+/// systemctl verbs that interrupt a running service.
+const INTERRUPTING: &[&str] = &["restart", "try-restart", "reload-or-restart", "stop"];
+
+/// A service a command restarts or stops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceAction {
+    /// The unit, e.g. `nginx.service`.
+    pub unit: String,
+    /// `restart`, `stop`, ...
+    pub verb: String,
+}
+
+/// The services `cmd` restarts or stops with `systemctl` or `service`.
+pub fn interrupted_services(cmd: &str) -> Vec<ServiceAction> {
+    let mut actions: Vec<ServiceAction> = Vec::new();
+    for segment in split_segments(cmd) {
+        let words: Vec<&str> = segment
+            .iter()
+            .map(|w| w.trim_matches(['"', '\'']))
+            .collect();
+        let Some((program, args)) = program_words(&words).split_first() else {
+            continue;
+        };
+        let args: Vec<&str> = args.iter().copied().filter(|a| !a.starts_with('-')).collect();
+        let found: Vec<(&str, &str)> = match (program.rsplit('/').next().unwrap_or(program), args.as_slice()) {
+            ("systemctl", [verb, units @ ..]) if INTERRUPTING.contains(verb) => {
+                units.iter().map(|unit| (*unit, *verb)).collect()
+            }
+            ("service", [unit, verb, ..]) if INTERRUPTING.contains(verb) => vec![(*unit, *verb)],
+            _ => Vec::new(),
+        };
+        for (unit, verb) in found {
+            // Globs and templates name no single unit to probe.
+            if unit.contains(['*', '?', '[']) || unit.ends_with('@') {
+                continue;
+            }
+            let unit = match unit.contains('.') {
+                true => unit.to_string(),
+                false => format!("{}.service", unit),
+            };
+            if !actions.iter().any(|a| a.unit == unit) {
+                actions.push(ServiceAction {
+                    unit,
+                    verb: verb.to_string(),
+                });
+            }
+        }
+    }
+    actions
+}
+
+/// What a service is doing now.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceImpact {
+    pub unit: String,
+    /// `active (running)`, `inactive (dead)`, ...
+    pub state: String,
+    pub running: bool,
+    pub processes: usize,
+    /// Listening sockets, e.g. `:80`, `:53/udp`.
+    pub ports: Vec<String>,
+    /// Established TCP connections held by its processes.
+    pub connections: usize,
+    /// The service's sockets weren't visible (no root).
+    pub sockets_hidden: bool,
+}
+
+impl ServiceImpact {
+    /// One line, e.g. `nginx currently serving on :80/:443, 3 processes,
+    /// 12 open connections`.
+    pub fn summary(&self) -> String {
+        let name = self.unit.trim_end_matches(".service");
+        if !self.running {
+            return format!("{} is not running ({})", name, self.state);
+        }
+        let mut parts = Vec::new();
+        parts.push(match self.ports.is_empty() {
+            true => format!("{} currently running", name),
+            false => format!("{} currently serving on {}", name, self.ports.join("/")),
+        });
+        parts.push(match self.processes {
+            1 => "1 process".to_string(),
+            n => format!("{} processes", n),
+        });
+        if self.connections > 0 {
+            parts.push(format!("{} open connection(s)", self.connections));
+        }
+        if self.sockets_hidden {
+            parts.push("ports and connections unknown without root".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+/// Properties of `systemctl show -p ...` output.
+fn property<'a>(show: &'a str, name: &str) -> Option<&'a str> {
+    show.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+        .map(str::trim)
+}
+
+/// The pids in the `users:((...))` column of an `ss -p` line.
+fn socket_pids(line: &str) -> Vec<u32> {
+    line.split("pid=")
+        .skip(1)
+        .filter_map(|rest| {
+            let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().ok()
+        })
+        .collect()
+}
+
+/// The local port of an `ss` line: the last `:`-separated part of the
+/// first address.
+fn local_port(line: &str) -> Option<&str> {
+    line.split_whitespace()
+        .filter(|w| !w.starts_with("users:"))
+        .find(|w| w.contains(':'))
+        .and_then(|address| address.rsplit(':').next())
+        .filter(|port| !port.is_empty() && *port != "*")
+}
+
+/// The listening ports (`ss -Htlnup` output) owned by `pids`, sorted, with
+/// UDP ports marked.
+pub fn listening_ports(ss: &str, pids: &BTreeSet<u32>) -> Vec<String> {
+    let mut ports: Vec<(u32, String)> = Vec::new();
+    for line in ss.lines() {
+        if !socket_pids(line).iter().any(|pid| pids.contains(pid)) {
+            continue;
+        }
+        let Some(port) = local_port(line) else {
+            continue;
+        };
+        let label = match line.trim_start().starts_with("udp") {
+            true => format!(":{}/udp", port),
+            false => format!(":{}", port),
+        };
+        let key = port.parse().unwrap_or(u32::MAX);
+        if !ports.iter().any(|(_, l)| *l == label) {
+            ports.push((key, label));
+        }
+    }
+    ports.sort();
+    ports.into_iter().map(|(_, label)| label).collect()
+}
+
+/// Connections (`ss -Htnp state established` output) held by `pids`.
+pub fn connection_count(ss: &str, pids: &BTreeSet<u32>) -> usize {
+    ss.lines()
+        .filter(|line| socket_pids(line).iter().any(|pid| pids.contains(pid)))
+        .count()
+}
+
+fn read_only(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// `ss` with `args`; when no socket shows its process (they belong to
+/// another user), again through `sudo -n`, which never asks for a password.
+fn sockets(args: &[&str]) -> (String, bool) {
+    let plain = read_only("ss", args).unwrap_or_default();
+    if plain.contains("users:") {
+        return (plain, false);
+    }
+    let mut sudo_args = vec!["-n", "ss"];
+    sudo_args.extend_from_slice(args);
+    match read_only("sudo", &sudo_args) {
+        Some(output) => (output, false),
+        None => (plain, true),
+    }
+}
+
+/// The pids in the cgroup at `control_group`, or just `main_pid`.
+fn cgroup_pids(control_group: &str, main_pid: u32) -> BTreeSet<u32> {
+    let mut pids = BTreeSet::new();
+    if !control_group.is_empty() {
+        for root in ["/sys/fs/cgroup", "/sys/fs/cgroup/systemd", "/sys/fs/cgroup/unified"] {
+            let path = format!("{}{}/cgroup.procs", root, control_group);
+            if let Ok(procs) = std::fs::read_to_string(path) {
+                pids.extend(procs.lines().filter_map(|pid| pid.trim().parse::<u32>().ok()));
+                break;
+            }
+        }
+    }
+    if pids.is_empty() && main_pid != 0 {
+        pids.insert(main_pid);
+    }
+    pids
+}
+
+/// Probe `unit` read-only; `None` when systemd doesn't know it.
+pub fn probe(unit: &str) -> Option<ServiceImpact> {
+    let show = read_only(
+        "systemctl",
+        &[
+            "show",
+            unit,
+            "--property=LoadState,ActiveState,SubState,MainPID,ControlGroup",
+        ],
+    )?;
+    if property(&show, "LoadState") == Some("not-found") {
+        return None;
+    }
+    let active = property(&show, "ActiveState").unwrap_or("unknown");
+    let sub = property(&show, "SubState").unwrap_or("unknown");
+    let mut impact = ServiceImpact {
+        unit: unit.to_string(),
+        state: format!("{} ({})", active, sub),
+        running: active == "active" || active == "reloading",
+        ..Default::default()
+    };
+    if !impact.running {
+        return Some(impact);
+    }
+    let main_pid = property(&show, "MainPID").and_then(|p| p.parse().ok()).unwrap_or(0);
+    let pids = cgroup_pids(property(&show, "ControlGroup").unwrap_or(""), main_pid);
+    impact.processes = pids.len();
+    let (listening, hidden) = sockets(&["-Htlnup"]);
+    impact.ports = listening_ports(&listening, &pids);
+    let (established, _) = sockets(&["-Htnp", "state", "established"]);
+    impact.connections = connection_count(&established, &pids);
+    impact.sockets_hidden = hidden && impact.ports.is_empty();
+    Some(impact)
+}
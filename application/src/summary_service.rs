@@ -0,0 +1,73 @@
+use infrastructure::ollama_client::OllamaClient;
+use shared::types::Result;
+
+/// Characters of command output sent to the model per request.
+const CHUNK_CHARS: usize = 12_000;
+
+/// Summarizes long command output, map-reduce style when it doesn't fit in
+/// one request: each chunk is condensed to notes, then the notes are merged.
+pub struct SummaryService {
+    client: OllamaClient,
+}
+
+impl SummaryService {
+    pub fn new(client: OllamaClient) -> Self {
+        Self { client }
+    }
+
+    pub async fn summarize_output(&self, command: &str, output: &str) -> Result<String> {
+        let chunks = split_chunks(output, CHUNK_CHARS);
+        if chunks.len() <= 1 {
+            let prompt = format!(
+                "The shell command `{}` produced the output below. Summarize it in a few lines for the user. Call out errors, warnings, failures and anything anomalous; skip routine noise.\n\nOutput:\n{}",
+                command, output
+            );
+            return self.client.generate_response(&prompt).await;
+        }
+
+        let mut notes = Vec::with_capacity(chunks.len());
+        for (i, chunk) in chunks.iter().enumerate() {
+            eprintln!("Summarizing part {}/{}...", i + 1, chunks.len());
+            let prompt = format!(
+                "This is part {} of {} of the output of the shell command `{}`. List, as short bullet points, the errors, warnings, failures, anomalies and notable facts in this part. Reply with 'nothing notable' if there are none.\n\nOutput part:\n{}",
+                i + 1,
+                chunks.len(),
+                command,
+                chunk
+            );
+            notes.push(self.client.generate_response(&prompt).await?);
+        }
+
+        let prompt = format!(
+            "Below are notes taken from consecutive parts of the output of the shell command `{}`. Merge them into one short summary for the user, leading with errors and anomalies.\n\n{}",
+            command,
+            notes
+                .iter()
+                .enumerate()
+                .map(|(i, n)| format!("Part {}:\n{}", i + 1, n))
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        );
+        self.client.generate_response(&prompt).await
+    }
+}
+
+/// Split on line boundaries into pieces of at most `max_chars` (a single
+/// longer line becomes its own piece).
+pub(crate) fn split_chunks(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        if !current.is_empty() && current.len() + line.len() + 1 > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
@@ -0,0 +1,390 @@
+//! `vibe gen-tests`: unit tests for one function, written from its
+//! definition and callers in the RAG index and in the style of tests
+//! already in the project.
+
+use crate::rag_service::RagService;
+use anyhow::anyhow;
+use infrastructure::ollama_client::OllamaClient;
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+
+/// Chunks retrieved while looking for the function and its callers.
+const TARGET_CHUNKS: usize = 12;
+/// Callers sent with the definition.
+const MAX_CALLERS: usize = 4;
+/// Existing tests sent as style examples.
+const MAX_EXAMPLES: usize = 2;
+/// Characters of an existing test file sent so new tests fit in.
+const MAX_EXISTING_CHARS: usize = 4_000;
+
+/// A function named like `checksum::archive_checksum` or
+/// `src/checksum.rs::archive_checksum`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestTarget {
+    /// What comes before the function name, split on `::`, `/` and `.`;
+    /// `crate`, `self`, `super` and file extensions are left out.
+    pub module: Vec<String>,
+    pub function: String,
+}
+
+impl TestTarget {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (module, function) = match spec.rsplit_once("::") {
+            Some((module, function)) => (module, function),
+            None => ("", spec),
+        };
+        let function = function.trim().trim_end_matches("()");
+        if function.is_empty() || !function.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(anyhow!(
+                "'{}' does not name a function (use e.g. path::to::function)",
+                spec
+            ));
+        }
+        let module = module
+            .split("::")
+            .flat_map(|segment| segment.split('/'))
+            .map(|segment| segment.split('.').next().unwrap_or(segment))
+            .filter(|s| !s.is_empty() && !matches!(*s, "crate" | "self" | "super" | "src"))
+            .map(str::to_string)
+            .collect();
+        Ok(Self {
+            module,
+            function: function.to_string(),
+        })
+    }
+
+    /// Words to build the index for.
+    pub fn keywords(&self) -> Vec<String> {
+        let mut keywords = self.module.clone();
+        keywords.push(self.function.clone());
+        keywords
+    }
+
+    /// Whether `chunk` defines the function, in a file whose path contains
+    /// every module segment.
+    pub fn defined_in(&self, chunk: &str) -> bool {
+        let path = chunk_path(chunk).unwrap_or("").to_lowercase();
+        self.module
+            .iter()
+            .all(|segment| path.contains(&segment.to_lowercase()))
+            && ["fn ", "def ", "function ", "func "]
+                .iter()
+                .any(|keyword| mentions(chunk, &format!("{}{}", keyword, self.function)))
+    }
+
+    /// Whether `chunk` calls the function.
+    pub fn called_in(&self, chunk: &str) -> bool {
+        mentions(chunk, &format!("{}(", self.function))
+            || mentions(chunk, &format!("{}::<", self.function))
+    }
+}
+
+/// `text` contains `needle` not preceded by part of an identifier, and, if
+/// `needle` ends in one, not followed by more of it.
+pub(crate) fn mentions(text: &str, needle: &str) -> bool {
+    let ident = |c: char| c.is_alphanumeric() || c == '_';
+    let open_ended = needle.chars().last().is_some_and(ident);
+    text.match_indices(needle).any(|(at, _)| {
+        let before = text[..at].chars().next_back();
+        let after = text[at + needle.len()..].chars().next();
+        let joined_after = open_ended && after.is_some_and(ident);
+        !(before.is_some_and(ident) || joined_after)
+    })
+}
+
+/// The `FILE:` header of an indexed chunk.
+fn chunk_path(chunk: &str) -> Option<&str> {
+    chunk.lines().next()?.strip_prefix("FILE: ")
+}
+
+/// The chunk without its `FILE:`/`OFFSET:` headers.
+fn chunk_body(chunk: &str) -> &str {
+    let mut body = chunk;
+    for header in ["FILE: ", "OFFSET: "] {
+        if body.starts_with(header) {
+            body = body.split_once('\n').map(|(_, rest)| rest).unwrap_or("");
+        }
+    }
+    body
+}
+
+/// What the model is shown: the function, some of its callers and some of
+/// the project's existing tests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestContext {
+    /// File that defines the function, as indexed.
+    pub source: PathBuf,
+    pub definition: String,
+    pub callers: Vec<String>,
+    pub examples: Vec<String>,
+}
+
+/// Find `target` and its callers in `index`, plus existing tests in the
+/// language of its file.
+pub async fn gather(index: &RagService, target: &TestTarget) -> Result<TestContext> {
+    let query = format!("{} {}", target.module.join(" "), target.function);
+    let chunks = index.related_context(query.trim(), TARGET_CHUNKS).await?;
+    let definition = chunks
+        .iter()
+        .find(|chunk| target.defined_in(chunk))
+        .cloned()
+        .ok_or_else(|| anyhow!("no definition of `{}` found in the index", target.function))?;
+    let source = PathBuf::from(chunk_path(&definition).unwrap_or_default());
+    let callers = chunks
+        .iter()
+        .filter(|chunk| **chunk != definition && target.called_in(chunk))
+        .take(MAX_CALLERS)
+        .cloned()
+        .collect();
+
+    let markers = test_markers(&source);
+    let examples = match markers.first() {
+        Some(marker) => index
+            .related_context(marker, TARGET_CHUNKS)
+            .await?
+            .into_iter()
+            .filter(|chunk| *chunk != definition && markers.iter().any(|m| chunk.contains(m)))
+            .take(MAX_EXAMPLES)
+            .collect(),
+        None => Vec::new(),
+    };
+    Ok(TestContext {
+        source,
+        definition,
+        callers,
+        examples,
+    })
+}
+
+/// Text that marks a test in files of `source`'s language.
+fn test_markers(source: &Path) -> &'static [&'static str] {
+    match extension(source) {
+        "rs" => &["#[test]", "#[tokio::test]"],
+        "py" => &["def test_"],
+        "js" | "jsx" | "ts" | "tsx" | "mjs" | "cjs" => &["describe(", "it(", "test("],
+        "go" => &["func Test"],
+        _ => &[],
+    }
+}
+
+fn extension(path: &Path) -> &str {
+    path.extension().and_then(|e| e.to_str()).unwrap_or("")
+}
+
+/// Where tests for a source file conventionally go.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestFile {
+    pub path: PathBuf,
+    /// Rust tests go in a `#[cfg(test)]` module at the end of the source
+    /// file when the crate has no `tests/` directory.
+    pub inline: bool,
+    /// Set when `path` is in another crate than the current directory.
+    manifest: Option<PathBuf>,
+}
+
+/// The test file for `source`:
+/// - Rust: `<crate>/tests/<name>.rs` if the crate has a `tests/` directory,
+///   or else the source file itself;
+/// - Python: `tests/test_<name>.py` in the nearest directory that has
+///   `tests/`, or else `test_<name>.py` beside it;
+/// - JavaScript/TypeScript: `<name>.test.<ext>`, in `__tests__/` if there
+///   is one;
+/// - Go: `<name>_test.go` beside it;
+/// - anything else: `tests/<name>_test.<ext>`.
+pub fn test_file(source: &Path) -> TestFile {
+    let source = source.strip_prefix(".").unwrap_or(source);
+    let dir = source.parent().unwrap_or(Path::new(""));
+    let ext = extension(source);
+    let stem = match source.file_stem().and_then(|s| s.to_str()) {
+        Some("mod") | Some("index") | Some("__init__") | None => dir
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("module"),
+        Some(stem) => stem,
+    };
+    let file = |path: PathBuf| TestFile {
+        path,
+        inline: false,
+        manifest: None,
+    };
+    match ext {
+        "rs" => {
+            let root = source
+                .ancestors()
+                .skip(1)
+                .find(|dir| dir.join("Cargo.toml").is_file())
+                .unwrap_or(Path::new(""));
+            let manifest = (!root.as_os_str().is_empty()).then(|| root.join("Cargo.toml"));
+            if root.join("tests").is_dir() {
+                TestFile {
+                    path: root.join("tests").join(format!("{}.rs", stem)),
+                    inline: false,
+                    manifest,
+                }
+            } else {
+                TestFile {
+                    path: source.to_path_buf(),
+                    inline: true,
+                    manifest,
+                }
+            }
+        }
+        "py" => {
+            let name = format!("test_{}.py", stem);
+            match source.ancestors().skip(1).find(|d| d.join("tests").is_dir()) {
+                Some(root) => file(root.join("tests").join(name)),
+                None => file(dir.join(name)),
+            }
+        }
+        "js" | "jsx" | "ts" | "tsx" | "mjs" | "cjs" => {
+            let name = format!("{}.test.{}", stem, ext);
+            if dir.join("__tests__").is_dir() {
+                file(dir.join("__tests__").join(name))
+            } else {
+                file(dir.join(name))
+            }
+        }
+        "go" => file(dir.join(format!("{}_test.go", stem))),
+        _ => file(
+            PathBuf::from("tests").join(format!("{}_test.{}", stem, if ext.is_empty() { "txt" } else { ext })),
+        ),
+    }
+}
+
+impl TestFile {
+    /// Module that holds tests for `function` in an inline test file.
+    pub fn inline_module(function: &str) -> String {
+        format!("{}_tests", function)
+    }
+
+    /// The command that runs the new tests, if the language has a usual one.
+    pub fn command(&self, function: &str) -> Option<String> {
+        let path = self.path.display();
+        match extension(&self.path) {
+            "rs" => {
+                let manifest = match &self.manifest {
+                    Some(manifest) => format!(" --manifest-path {}", manifest.display()),
+                    None => String::new(),
+                };
+                Some(if self.inline {
+                    format!("cargo test{} {}", manifest, Self::inline_module(function))
+                } else {
+                    let stem = self.path.file_stem()?.to_str()?;
+                    format!("cargo test{} --test {}", manifest, stem)
+                })
+            }
+            "py" => Some(format!("python -m pytest {}", path)),
+            "js" | "jsx" | "ts" | "tsx" | "mjs" | "cjs" => Some(format!("npm test -- {}", path)),
+            "go" => {
+                let dir = self.path.parent().map(|d| d.display().to_string());
+                Some(match dir.filter(|d| !d.is_empty()) {
+                    Some(dir) => format!("go test ./{}", dir),
+                    None => "go test .".to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// `existing` followed by the generated `tests`, or just the tests for a new
+/// file.
+pub fn with_tests(existing: Option<&str>, tests: &str) -> String {
+    let mut contents = match existing {
+        Some(existing) if !existing.trim().is_empty() => format!("{}\n\n", existing.trim_end()),
+        _ => String::new(),
+    };
+    contents.push_str(tests.trim_matches('\n'));
+    contents.push('\n');
+    contents
+}
+
+/// The code in the first fenced block of `reply`, or the whole reply when
+/// it has none.
+pub fn extract_code(reply: &str) -> String {
+    let mut lines = reply.lines().skip_while(|line| !line.trim_start().starts_with("```"));
+    if lines.next().is_none() {
+        return reply.trim().to_string();
+    }
+    lines
+        .take_while(|line| !line.trim_start().starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub struct TestGenService {
+    client: OllamaClient,
+}
+
+impl TestGenService {
+    pub fn new(client: OllamaClient) -> Self {
+        Self { client }
+    }
+
+    /// The tests' code, to be added to `file`; `existing` is what the file
+    /// holds now.
+    pub async fn generate(
+        &self,
+        target: &TestTarget,
+        context: &TestContext,
+        file: &TestFile,
+        existing: Option<&str>,
+    ) -> Result<String> {
+        let prompt = test_prompt(target, context, file, existing);
+        Ok(extract_code(&self.client.generate_response(&prompt).await?))
+    }
+}
+
+pub fn test_prompt(
+    target: &TestTarget,
+    context: &TestContext,
+    file: &TestFile,
+    existing: Option<&str>,
+) -> String {
+    let mut prompt = format!(
+        "You are an expert software engineer. Write unit tests for `{}`, defined in {}:\n\n{}\n\n",
+        target.function,
+        context.source.display(),
+        chunk_body(&context.definition).trim()
+    );
+    if !context.callers.is_empty() {
+        prompt.push_str("It is called like this in the project:\n");
+        prompt.push_str(&context.callers.join("\n\n"));
+        prompt.push_str("\n\n");
+    }
+    if !context.examples.is_empty() {
+        prompt.push_str("Existing tests in the project; match their style, helpers and naming:\n");
+        prompt.push_str(&context.examples.join("\n\n"));
+        prompt.push_str("\n\n");
+    }
+    if file.inline {
+        prompt.push_str(&format!(
+            "The tests go at the end of {} itself, inside `#[cfg(test)] mod {} {{ use super::*; ... }}`.\n",
+            file.path.display(),
+            TestFile::inline_module(&target.function)
+        ));
+    } else {
+        prompt.push_str(&format!("The tests go in {}.\n", file.path.display()));
+    }
+    match existing {
+        Some(existing) if !file.inline && !existing.trim().is_empty() => {
+            let tail_start = existing
+                .char_indices()
+                .rev()
+                .nth(MAX_EXISTING_CHARS)
+                .map(|(i, _)| i)
+                .unwrap_or(0);
+            prompt.push_str(&format!(
+                "It already contains the code below; write only the tests to append, reusing its imports and helpers:\n{}\n",
+                &existing[tail_start..]
+            ));
+        }
+        _ if !file.inline => prompt.push_str("It is a new file; include the imports the tests need.\n"),
+        _ => {}
+    }
+    prompt.push_str(
+        "Cover normal inputs, edge cases and error cases the code handles. Respond with the test code only, in one fenced code block.",
+    );
+    prompt
+}
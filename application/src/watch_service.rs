@@ -0,0 +1,75 @@
+use infrastructure::ollama_client::OllamaClient;
+use serde::Deserialize;
+use shared::types::Result;
+
+/// Characters from the end of the output shown to the model each check;
+/// the latest lines are what a "has it finished yet" question depends on.
+const OUTPUT_TAIL_CHARS: usize = 8_000;
+
+/// The model's judgement of one run of a watched command.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WatchVerdict {
+    #[serde(default)]
+    pub satisfied: bool,
+    #[serde(default)]
+    pub reason: String,
+    /// Something unexpected worth alerting on even if the condition isn't met.
+    #[serde(default)]
+    pub anomaly: Option<String>,
+}
+
+/// Asks the model whether a watched command's output meets a condition
+/// described in plain English.
+pub struct WatchService {
+    client: OllamaClient,
+}
+
+impl WatchService {
+    pub fn new(client: OllamaClient) -> Self {
+        Self { client }
+    }
+
+    pub async fn evaluate(
+        &self,
+        command: &str,
+        condition: &str,
+        output: &str,
+        exit_code: Option<i32>,
+    ) -> Result<WatchVerdict> {
+        let start = output
+            .char_indices()
+            .rev()
+            .nth(OUTPUT_TAIL_CHARS)
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        let prompt = format!(
+            "A user is polling the shell command `{}` and wants to stop when this condition holds: \"{}\".\n\
+Exit code of the latest run: {}.\n\
+Latest output:\n{}\n\n\
+Respond ONLY with a JSON object: {{\"satisfied\": true|false, \"reason\": \"one short sentence\", \"anomaly\": null or \"short description of anything alarming (errors, crashes, stuck progress)\"}}",
+            command,
+            condition,
+            exit_code.map_or_else(|| "unknown".to_string(), |c| c.to_string()),
+            &output[start..]
+        );
+        let response = self.client.generate_response(&prompt).await?;
+        Ok(parse_verdict(&response))
+    }
+}
+
+fn parse_verdict(response: &str) -> WatchVerdict {
+    if let (Some(start), Some(end)) = (response.find('{'), response.rfind('}')) {
+        if start < end {
+            if let Ok(verdict) = serde_json::from_str::<WatchVerdict>(&response[start..=end]) {
+                return verdict;
+            }
+        }
+    }
+    // Small models sometimes ignore the format; accept a plain yes/no.
+    let trimmed = response.trim();
+    WatchVerdict {
+        satisfied: trimmed.to_lowercase().starts_with("yes"),
+        reason: trimmed.lines().next().unwrap_or_default().to_string(),
+        anomaly: None,
+    }
+}
@@ -0,0 +1,137 @@
+//! The directory commands run in, carried from one command to the next as
+//! an interactive shell would carry it. Plans and chat sessions often take
+//! that state for granted (`cd app`, then `npm test` as the next step), but
+//! each command runs in a fresh `bash -c`; so a `cd` leading a command is
+//! taken out of it and applied as the working directory of the rest of the
+//! command and of every command after it.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Where a command runs once its leading `cd`s are taken out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Located {
+    /// The directory the rest of the command runs in.
+    pub dir: PathBuf,
+    /// The command without its leading `cd`s; empty when it was only `cd`s.
+    pub command: String,
+}
+
+/// The working directory of a session or plan.
+#[derive(Debug, Clone)]
+pub struct WorkingDir {
+    start: PathBuf,
+    current: PathBuf,
+    previous: Option<PathBuf>,
+}
+
+impl WorkingDir {
+    /// A session starting in `start`, which should be absolute.
+    pub fn new(start: PathBuf) -> Self {
+        Self {
+            current: start.clone(),
+            start,
+            previous: None,
+        }
+    }
+
+    pub fn current(&self) -> &Path {
+        &self.current
+    }
+
+    /// Where `cmd` runs, and what is left to run there. Leading `cd DIR`s
+    /// joined by `&&` or `;` are resolved against the current directory,
+    /// or the starting one when the model repeats a `cd` it has already
+    /// made; `cd`, `cd ~` and `cd -` work as in bash. A `cd` whose
+    /// directory doesn't exist or depends on expansion is left in the
+    /// command, for bash to run or fail on, as is everything after it.
+    pub fn locate(&self, cmd: &str) -> Located {
+        let mut dir = self.current.clone();
+        let mut previous = self.previous.clone();
+        let mut rest = cmd.trim();
+        while let Some((target, after)) = leading_cd(rest) {
+            let next = match target.as_deref() {
+                None | Some("~") => home(),
+                Some("-") => previous.clone(),
+                Some(target) if target.contains(['$', '`', '*', '?', '[', '\\']) => None,
+                Some(target) => {
+                    let target = match target.strip_prefix("~/") {
+                        Some(below) => home().map(|home| home.join(below)),
+                        None => Some(PathBuf::from(target)),
+                    };
+                    target.and_then(|target| {
+                        [&dir, &self.start]
+                            .into_iter()
+                            .map(|base| normalize(&base.join(&target)))
+                            .find(|candidate| candidate.is_dir())
+                    })
+                }
+            };
+            let Some(next) = next.filter(|next| next.is_dir()) else {
+                break;
+            };
+            previous = Some(std::mem::replace(&mut dir, next));
+            rest = after;
+        }
+        Located {
+            dir,
+            command: rest.to_string(),
+        }
+    }
+
+    /// Move to `dir`, once the command located there has run.
+    pub fn enter(&mut self, dir: PathBuf) {
+        if dir != self.current {
+            self.previous = Some(std::mem::replace(&mut self.current, dir));
+        }
+    }
+}
+
+/// The target of a `cd` leading `cmd` (`None` for a bare `cd`) and what
+/// follows the `&&` or `;` after it. `None` unless `cmd` starts with a
+/// `cd` of one plain or quoted word followed by one of those or nothing.
+fn leading_cd(cmd: &str) -> Option<(Option<String>, &str)> {
+    let after = cmd.strip_prefix("cd")?;
+    if !(after.is_empty() || after.starts_with([' ', '\t', ';', '&'])) {
+        return None;
+    }
+    let after = after.trim_start_matches([' ', '\t']);
+    let (target, after) = match after.chars().next() {
+        None | Some(';') | Some('&') => (None, after),
+        Some(quote @ ('\'' | '"')) => {
+            let end = after[1..].find(quote)? + 1;
+            (Some(after[1..end].to_string()), &after[end + 1..])
+        }
+        Some(_) => {
+            let end = after.find([' ', '\t', ';', '&', '|', '<', '>', '(', ')', '\'', '"']).unwrap_or(after.len());
+            (Some(after[..end].to_string()), &after[end..])
+        }
+    };
+    let after = after.trim_start_matches([' ', '\t']);
+    let rest = if after.is_empty() {
+        after
+    } else if let Some(rest) = after.strip_prefix("&&") {
+        rest
+    } else {
+        after.strip_prefix(';').filter(|rest| !rest.starts_with(';'))?
+    };
+    Some((target, rest.trim_start()))
+}
+
+fn home() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+/// `path` with `.` and `..` folded away lexically, as bash's `cd` does.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
+            }
+            other => normal.push(other),
+        }
+    }
+    normal
+}
@@ -4,6 +4,10 @@ use presentation::cli::{Cli, CliApp};
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    // Paths are resolved while the app is built, so apply the override first.
+    if let Some(dir) = &cli.data_dir {
+        shared::paths::set_data_dir(dir.clone());
+    }
     let mut app = CliApp::new();
     app.run(cli).await?;
     Ok(())
@@ -1,14 +1,144 @@
 use serde::{Deserialize, Serialize};
 use shared::types::Result;
+use std::fmt;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommandPlan {
     pub id: String,
     pub description: String,
-    pub steps: Vec<String>,
+    pub steps: Vec<PlanStep>,
     pub safety_checks: Vec<SafetyCheck>,
 }
 
+/// One step of a plan. `check`, `when` and `on_failure` are branches the
+/// executor evaluates, so "install it if it is missing" needs no `||` chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "RawPlanStep")]
+pub struct PlanStep {
+    pub command: String,
+    /// Detects that the step is already done: run before anything else, and
+    /// the step is skipped as satisfied if it exits 0. This makes a plan
+    /// safe to run again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check: Option<String>,
+    /// A check run first; the step is skipped unless it exits 0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    /// Run if `command` fails; the step succeeds if this does.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+    /// How long the step should take, in seconds; the user is asked what to
+    /// do if it runs well past this.
+    #[serde(rename = "expected_seconds", skip_serializing_if = "Option::is_none")]
+    pub expected_secs: Option<u64>,
+}
+
+impl PlanStep {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            check: None,
+            when: None,
+            on_failure: None,
+            expected_secs: None,
+        }
+    }
+
+    pub fn with_check(mut self, check: impl Into<String>) -> Self {
+        self.check = Some(check.into());
+        self
+    }
+
+    pub fn with_when(mut self, condition: impl Into<String>) -> Self {
+        self.when = Some(condition.into());
+        self
+    }
+
+    pub fn with_on_failure(mut self, fallback: impl Into<String>) -> Self {
+        self.on_failure = Some(fallback.into());
+        self
+    }
+
+    pub fn with_expected_secs(mut self, secs: u64) -> Self {
+        self.expected_secs = Some(secs);
+        self
+    }
+}
+
+impl From<&str> for PlanStep {
+    fn from(command: &str) -> Self {
+        Self::new(command)
+    }
+}
+
+impl From<String> for PlanStep {
+    fn from(command: String) -> Self {
+        Self::new(command)
+    }
+}
+
+/// The command, then its branches and expected duration:
+/// `cmd (done if: check) (only if: condition) (on failure: fix) (expected: 60s)`.
+impl fmt::Display for PlanStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.command)?;
+        if let Some(check) = &self.check {
+            write!(f, " (done if: {})", check)?;
+        }
+        if let Some(condition) = &self.when {
+            write!(f, " (only if: {})", condition)?;
+        }
+        if let Some(fallback) = &self.on_failure {
+            write!(f, " (on failure: {})", fallback)?;
+        }
+        if let Some(secs) = self.expected_secs {
+            write!(f, " (expected: {}s)", secs)?;
+        }
+        Ok(())
+    }
+}
+
+/// A step is either a bare command or an object with branches.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawPlanStep {
+    Command(String),
+    Step {
+        #[serde(alias = "cmd")]
+        command: String,
+        #[serde(default, alias = "done_if", alias = "creates")]
+        check: Option<String>,
+        #[serde(default, alias = "if")]
+        when: Option<String>,
+        #[serde(default, alias = "fallback")]
+        on_failure: Option<String>,
+        #[serde(default, alias = "expected_secs", alias = "duration")]
+        expected_seconds: Option<u64>,
+    },
+}
+
+impl From<RawPlanStep> for PlanStep {
+    fn from(raw: RawPlanStep) -> Self {
+        let branch = |s: Option<String>| s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        match raw {
+            RawPlanStep::Command(command) => Self::new(command),
+            RawPlanStep::Step {
+                command,
+                check,
+                when,
+                on_failure,
+                expected_seconds,
+            } => Self {
+                command,
+                check: branch(check),
+                when: branch(when),
+                on_failure: branch(on_failure),
+                expected_secs: expected_seconds.filter(|&secs| secs > 0),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SafetyCheck {
     pub check_type: String,
@@ -12,4 +12,47 @@ pub struct Embedding {
     pub vector: Vec<f32>,
     pub text: String,
     pub path: String,
+    /// Byte offset of the chunk in its file; orders chunks within a file.
+    #[serde(default)]
+    pub start_offset: usize,
+    /// Language of the chunk's file, e.g. `rust`; empty when unknown.
+    #[serde(default)]
+    pub language: String,
+    /// Position of the chunk among its file's chunks, from 0.
+    #[serde(default)]
+    pub ordinal: usize,
+    /// Hash of the chunk's content without its `FILE:`/`OFFSET:` header, so
+    /// a chunk that didn't change when its file did keeps its vector; empty
+    /// when unknown.
+    #[serde(default)]
+    pub chunk_hash: String,
+    /// Index of the notebook cell the chunk comes from; `None` outside
+    /// notebooks.
+    #[serde(default)]
+    pub cell: Option<usize>,
+}
+
+/// Something said in an earlier session (a RAG answer, an explanation, a
+/// chat exchange), embedded so `recall` can search it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Memory {
+    pub id: String,
+    /// `rag`, `explain` or `chat`.
+    pub kind: String,
+    pub text: String,
+    pub vector: Vec<f32>,
+    /// Seconds since the Unix epoch.
+    pub created_at: u64,
+}
+
+/// A documentation site or local doc tree added with `docs add`, and the
+/// pages it was last fetched as.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocSource {
+    /// The URL or path it was added as.
+    pub source: String,
+    /// URLs or paths of its indexed pages.
+    pub pages: Vec<String>,
+    /// Seconds since the Unix epoch.
+    pub fetched_at: u64,
 }
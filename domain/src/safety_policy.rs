@@ -5,6 +5,12 @@ pub struct SafetyPolicy {
     pub rules: Vec<String>,
 }
 
+impl Default for SafetyPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SafetyPolicy {
     pub fn new() -> Self {
         Self {
@@ -1,25 +1,39 @@
 use serde::{Deserialize, Serialize};
+use shared::types::Result;
 use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     pub id: String,
     pub context: HashMap<String, String>,
     pub history: Vec<Message>,
+    /// Unix seconds.
+    #[serde(default)]
+    pub created_at: u64,
+    /// Unix seconds of the last change, for listing recent sessions first.
+    #[serde(default)]
+    pub updated_at: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
     pub content: String,
+    /// The mode that produced the message (`chat`, `agent`, `rag`).
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub mode: String,
 }
 
 impl Session {
     pub fn new(id: String) -> Self {
+        let now = now_secs();
         Self {
             id,
             context: HashMap::new(),
             history: Vec::new(),
+            created_at: now,
+            updated_at: now,
         }
     }
 
@@ -27,6 +41,63 @@ impl Session {
         self.history.push(Message {
             role: role.to_string(),
             content: content.to_string(),
+            mode: String::new(),
         });
+        self.updated_at = now_secs();
     }
+
+    /// Record a question and its answer from `mode`.
+    pub fn add_exchange(&mut self, mode: &str, question: &str, answer: &str) {
+        for (role, content) in [("user", question), ("assistant", answer)] {
+            self.history.push(Message {
+                role: role.to_string(),
+                content: content.to_string(),
+                mode: mode.to_string(),
+            });
+        }
+        self.updated_at = now_secs();
+    }
+
+    /// Question and answer pairs recorded from `mode`, oldest first.
+    pub fn exchanges(&self, mode: &str) -> Vec<(String, String)> {
+        let messages: Vec<&Message> = self.history.iter().filter(|m| m.mode == mode).collect();
+        messages
+            .windows(2)
+            .filter(|pair| pair[0].role == "user" && pair[1].role == "assistant")
+            .map(|pair| (pair[0].content.clone(), pair[1].content.clone()))
+            .collect()
+    }
+
+    pub fn set_context(&mut self, key: &str, value: String) {
+        self.context.insert(key.to_string(), value);
+        self.updated_at = now_secs();
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Where sessions are kept, and which one is in use.
+pub trait SessionRepository {
+    /// Store a new, empty session; fails if `id` is taken or not a valid id.
+    fn create(&self, id: &str) -> Result<Session>;
+
+    /// All sessions, most recently updated first.
+    fn list(&self) -> Result<Vec<Session>>;
+
+    fn load(&self, id: &str) -> Result<Option<Session>>;
+
+    fn save(&self, session: &Session) -> Result<()>;
+
+    /// `false` if there was no such session.
+    fn delete(&self, id: &str) -> Result<bool>;
+
+    /// The id of the session modes record into, if one is selected.
+    fn active(&self) -> Result<Option<String>>;
+
+    fn set_active(&self, id: Option<&str>) -> Result<()>;
 }
@@ -0,0 +1,218 @@
+//! Exact commands the user chose to run without being asked again, for one
+//! session or for the whole project. Each project keeps its own
+//! `<data_dir>/<project>_allowlist.json`, and every entry is signed with
+//! HMAC-SHA256 under a per-user key (`VIBE_ALLOWLIST_KEY`, or
+//! `<config_dir>/allowlist.key` made on first use), so an entry written or
+//! edited by anything but vibe_cli is ignored rather than trusted.
+
+use anyhow::anyhow;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use shared::at_rest::{decode_hex, encode_hex};
+use shared::cache::now_secs;
+use shared::paths;
+use shared::types::Result;
+use std::path::PathBuf;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const KEY_FILE: &str = "allowlist.key";
+/// Hex characters of the signature shown as an entry's id.
+const ID_LEN: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllowScope {
+    /// Only while this vibe session is active.
+    Session(String),
+    Project,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllowEntry {
+    pub command: String,
+    /// The session the entry is limited to; `None` for the whole project.
+    #[serde(default)]
+    pub session: Option<String>,
+    pub added_at: u64,
+    signature: String,
+}
+
+impl AllowEntry {
+    /// Short id for `vibe allowlist revoke`.
+    pub fn id(&self) -> &str {
+        &self.signature[..ID_LEN.min(self.signature.len())]
+    }
+
+    pub fn scope(&self) -> AllowScope {
+        match &self.session {
+            Some(id) => AllowScope::Session(id.clone()),
+            None => AllowScope::Project,
+        }
+    }
+}
+
+pub struct Allowlist {
+    path: PathBuf,
+    key: Vec<u8>,
+    /// Signed into every entry so one can't be copied to another project.
+    project: String,
+}
+
+impl Allowlist {
+    pub fn new(path: impl Into<PathBuf>, key: Vec<u8>, project: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            key,
+            project: project.into(),
+        }
+    }
+
+    /// The current project's allowlist under the user's key.
+    pub fn for_project() -> Result<Self> {
+        Ok(Self::new(
+            paths::project_data_file("allowlist.json"),
+            load_key()?,
+            paths::project_suffix(),
+        ))
+    }
+
+    /// Entries with a valid signature; the rest are skipped.
+    pub fn entries(&self) -> Result<Vec<AllowEntry>> {
+        let entries: Vec<AllowEntry> = match std::fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(entries
+            .into_iter()
+            .filter(|entry| self.verify(entry))
+            .collect())
+    }
+
+    /// The entry allowing exactly `command`, for the whole project or for
+    /// `session`.
+    pub fn allowing(&self, command: &str, session: Option<&str>) -> Result<Option<AllowEntry>> {
+        Ok(self.entries()?.into_iter().find(|entry| {
+            entry.command == command
+                && match entry.session.as_deref() {
+                    Some(scoped) => Some(scoped) == session,
+                    None => true,
+                }
+        }))
+    }
+
+    pub fn add(&self, command: &str, scope: AllowScope) -> Result<AllowEntry> {
+        let session = match scope {
+            AllowScope::Session(id) => Some(id),
+            AllowScope::Project => None,
+        };
+        let added_at = now_secs();
+        let mut entry = AllowEntry {
+            command: command.to_string(),
+            session,
+            added_at,
+            signature: String::new(),
+        };
+        entry.signature = encode_hex(&self.mac(&entry)?.finalize().into_bytes());
+        let mut entries = self.entries()?;
+        entries.retain(|e| !(e.command == entry.command && e.session == entry.session));
+        entries.push(entry.clone());
+        self.write(&entries)?;
+        Ok(entry)
+    }
+
+    /// Remove the entries whose id starts with `id`; how many were removed.
+    pub fn revoke(&self, id: &str) -> Result<usize> {
+        let id = id.trim().to_lowercase();
+        if id.is_empty() {
+            return Err(anyhow!("give the id of the entry to revoke"));
+        }
+        let mut entries = self.entries()?;
+        let before = entries.len();
+        entries.retain(|entry| !entry.signature.starts_with(&id));
+        let removed = before - entries.len();
+        if removed > 0 {
+            self.write(&entries)?;
+        }
+        Ok(removed)
+    }
+
+    pub fn clear(&self) -> Result<usize> {
+        let count = self.entries()?.len();
+        self.write(&[])?;
+        Ok(count)
+    }
+
+    fn write(&self, entries: &[AllowEntry]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_vec_pretty(entries)?)?;
+        Ok(())
+    }
+
+    fn mac(&self, entry: &AllowEntry) -> Result<HmacSha256> {
+        let mut mac = HmacSha256::new_from_slice(&self.key)
+            .map_err(|_| anyhow!("invalid allowlist key"))?;
+        let message = format!(
+            "vibe-allowlist-v1\0{}\0{}\0{}\0{}",
+            self.project,
+            entry.session.as_deref().unwrap_or_default(),
+            entry.added_at,
+            entry.command
+        );
+        mac.update(message.as_bytes());
+        Ok(mac)
+    }
+
+    fn verify(&self, entry: &AllowEntry) -> bool {
+        let (Ok(mac), Some(signature)) = (self.mac(entry), decode_hex(&entry.signature)) else {
+            return false;
+        };
+        mac.verify_slice(&signature).is_ok()
+    }
+}
+
+/// `VIBE_ALLOWLIST_KEY` (hex), or the key file, created with a random key
+/// readable only by the user if missing.
+fn load_key() -> Result<Vec<u8>> {
+    if let Ok(hex) = std::env::var("VIBE_ALLOWLIST_KEY") {
+        return decode_hex(hex.trim())
+            .filter(|key| key.len() >= 16)
+            .ok_or_else(|| anyhow!("VIBE_ALLOWLIST_KEY must be at least 32 hex characters"));
+    }
+    let path = paths::config_dir().join(KEY_FILE);
+    if let Ok(hex) = std::fs::read_to_string(&path) {
+        if let Some(key) = decode_hex(hex.trim()).filter(|key| key.len() >= 16) {
+            return Ok(key);
+        }
+    }
+    let mut key = vec![0u8; 32];
+    getrandom::getrandom(&mut key).map_err(|e| anyhow!("cannot generate allowlist key: {}", e))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    write_private(&path, encode_hex(&key).as_bytes())?;
+    Ok(key)
+}
+
+#[cfg(unix)]
+fn write_private(path: &std::path::Path, data: &[u8]) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(data)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &std::path::Path, data: &[u8]) -> Result<()> {
+    std::fs::write(path, data)?;
+    Ok(())
+}
@@ -0,0 +1,336 @@
+//! What vibe_cli proposed and ran in a project: one JSON line per command
+//! in `<data_dir>/<project>_audit.jsonl`, appended as commands finish.
+//! Lines are sealed (and hex-encoded) like the caches when encryption at
+//! rest is on; lines that can't be read are skipped.
+
+use serde::{Deserialize, Serialize};
+use shared::at_rest::{self, decode_hex, encode_hex};
+use shared::cache::now_secs;
+use shared::paths;
+use shared::types::Result;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Commands listed per earlier agent run in a planning prompt.
+const MAX_COMMANDS_PER_RUN: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The mode that proposed the command, e.g. `command`, `chat`, `agent`.
+    pub mode: String,
+    /// What the user asked for.
+    pub prompt: String,
+    pub command: String,
+    /// Risk tier the safety check gave the command, e.g. `mutating`.
+    pub verdict: String,
+    /// How it ended: `succeeded`, `failed`, `cancelled`, `blocked`, ...
+    pub outcome: String,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// Shared by the steps of one agent run.
+    #[serde(default)]
+    pub run: Option<String>,
+    /// What an agent run changed (packages, services, files), on the
+    /// run's closing `report` entry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<String>,
+    /// Where the command came from, a [`Provenance::label`]; empty on
+    /// entries logged before it was recorded.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub provenance: String,
+}
+
+impl AuditEntry {
+    pub fn new(mode: &str, prompt: &str, command: &str) -> Self {
+        Self {
+            timestamp: now_secs(),
+            mode: mode.to_string(),
+            prompt: prompt.to_string(),
+            command: command.to_string(),
+            verdict: String::new(),
+            outcome: String::new(),
+            exit_code: None,
+            run: None,
+            changes: Vec::new(),
+            provenance: String::new(),
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.outcome == "succeeded"
+    }
+
+    /// Whether the command was let through: `approved`, `declined` or
+    /// `blocked`; empty for entries that weren't proposed commands.
+    pub fn approval(&self) -> &'static str {
+        match self.outcome.as_str() {
+            "succeeded" | "failed" | "sent" | "backgrounded" | "detached" => "approved",
+            "cancelled" | "refine" => "declined",
+            "blocked" | "invalid" => "blocked",
+            _ => "",
+        }
+    }
+}
+
+/// Where a proposed command came from, shown when it is confirmed and
+/// recorded with it, so a fresh generation can be told from an old cache
+/// entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Provenance {
+    /// Generated by the model for this request.
+    #[default]
+    Model,
+    /// Served from the command cache, stored at `saved_at` (seconds since
+    /// the Unix epoch).
+    Cache { saved_at: u64 },
+    /// From the local rule table, by rule name.
+    Rule(String),
+    /// Typed or changed by the user.
+    UserEdited,
+}
+
+impl Provenance {
+    /// `model`, `cache`, `rule` or `user-edited`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Model => "model",
+            Self::Cache { .. } => "cache",
+            Self::Rule(_) => "rule",
+            Self::UserEdited => "user-edited",
+        }
+    }
+}
+
+/// How `audit export` writes entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON object per line.
+    Jsonl,
+    /// A header row, then one row per entry.
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "jsonl" | "json" => Some(Self::Jsonl),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// An exported entry, with the project it was logged in.
+#[derive(Serialize)]
+struct ExportRecord<'a> {
+    project: &'a str,
+    timestamp: u64,
+    mode: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt: Option<&'a str>,
+    command: &'a str,
+    verdict: &'a str,
+    approval: &'a str,
+    outcome: &'a str,
+    exit_code: Option<i32>,
+    run: Option<&'a str>,
+    provenance: &'a str,
+}
+
+const CSV_HEADER: &str = "project,timestamp,mode,prompt,command,verdict,approval,outcome,exit_code,run,provenance";
+
+/// `entries`, each with its project, written as `format`; prompts are left
+/// out (an empty CSV column) unless `include_prompts`.
+pub fn export(entries: &[(String, AuditEntry)], format: ExportFormat, include_prompts: bool) -> Result<String> {
+    let mut out = String::new();
+    if format == ExportFormat::Csv {
+        out.push_str(CSV_HEADER);
+        out.push('\n');
+    }
+    for (project, entry) in entries {
+        let record = ExportRecord {
+            project,
+            timestamp: entry.timestamp,
+            mode: &entry.mode,
+            prompt: include_prompts.then_some(entry.prompt.as_str()),
+            command: &entry.command,
+            verdict: &entry.verdict,
+            approval: entry.approval(),
+            outcome: &entry.outcome,
+            exit_code: entry.exit_code,
+            run: entry.run.as_deref(),
+            provenance: &entry.provenance,
+        };
+        match format {
+            ExportFormat::Jsonl => out.push_str(&serde_json::to_string(&record)?),
+            ExportFormat::Csv => {
+                let fields = [
+                    record.project.to_string(),
+                    record.timestamp.to_string(),
+                    record.mode.to_string(),
+                    record.prompt.unwrap_or_default().to_string(),
+                    record.command.to_string(),
+                    record.verdict.to_string(),
+                    record.approval.to_string(),
+                    record.outcome.to_string(),
+                    record.exit_code.map(|code| code.to_string()).unwrap_or_default(),
+                    record.run.unwrap_or_default().to_string(),
+                    record.provenance.to_string(),
+                ];
+                let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                out.push_str(&row.join(","));
+            }
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// `field` quoted when it holds a comma, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The commands that succeeded in one earlier agent run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentRun {
+    pub task: String,
+    pub finished_at: u64,
+    pub succeeded: Vec<String>,
+}
+
+impl AgentRun {
+    /// One line for a planning prompt, e.g.
+    /// `"set up nginx": apt-get install -y nginx; ufw enable`.
+    pub fn summary(&self) -> String {
+        let mut commands: Vec<&str> = self
+            .succeeded
+            .iter()
+            .take(MAX_COMMANDS_PER_RUN)
+            .map(String::as_str)
+            .collect();
+        if self.succeeded.len() > MAX_COMMANDS_PER_RUN {
+            commands.push("...");
+        }
+        format!("\"{}\": {}", self.task, commands.join("; "))
+    }
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The current project's log.
+    pub fn for_project() -> Self {
+        Self::new(paths::project_data_file("audit.jsonl"))
+    }
+
+    /// The logs of every project, in no particular order.
+    pub fn all_projects() -> Vec<Self> {
+        let Ok(entries) = std::fs::read_dir(paths::data_dir()) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.ends_with("_audit.jsonl"))
+            })
+            .map(Self::new)
+            .collect()
+    }
+
+    /// The project this log belongs to, from its file name.
+    pub fn project(&self) -> String {
+        let name = self.path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        name.strip_suffix("_audit.jsonl").unwrap_or(name).to_string()
+    }
+
+    pub fn append(&self, entry: &AuditEntry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec(entry)?;
+        let mut line = match at_rest::active() {
+            Some(_) => encode_hex(&at_rest::seal(json)?).into_bytes(),
+            None => json,
+        };
+        line.push(b'\n');
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)?;
+        Ok(())
+    }
+
+    /// Every readable entry, oldest first.
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        let text = match std::fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(text.lines().filter_map(parse_line).collect())
+    }
+
+    /// The last `limit` agent runs in which at least one step succeeded,
+    /// oldest first.
+    pub fn recent_agent_runs(&self, limit: usize) -> Result<Vec<AgentRun>> {
+        let mut runs: Vec<(String, AgentRun)> = Vec::new();
+        for entry in self.entries()? {
+            let Some(id) = entry.run.clone() else {
+                continue;
+            };
+            let index = match runs.iter().position(|(run, _)| *run == id) {
+                Some(index) => index,
+                None => {
+                    runs.push((
+                        id,
+                        AgentRun {
+                            task: entry.prompt.clone(),
+                            finished_at: entry.timestamp,
+                            succeeded: Vec::new(),
+                        },
+                    ));
+                    runs.len() - 1
+                }
+            };
+            let run = &mut runs[index].1;
+            run.finished_at = run.finished_at.max(entry.timestamp);
+            if entry.succeeded() {
+                run.succeeded.push(entry.command);
+            }
+        }
+        let mut runs: Vec<AgentRun> = runs
+            .into_iter()
+            .map(|(_, run)| run)
+            .filter(|run| !run.succeeded.is_empty())
+            .collect();
+        runs.sort_by_key(|run| run.finished_at);
+        let skip = runs.len().saturating_sub(limit);
+        Ok(runs.split_off(skip))
+    }
+}
+
+fn parse_line(line: &str) -> Option<AuditEntry> {
+    let line = line.trim();
+    if line.starts_with('{') {
+        return serde_json::from_str(line).ok();
+    }
+    let sealed = decode_hex(line)?;
+    serde_json::from_slice(&at_rest::open(sealed).ok()?).ok()
+}
@@ -0,0 +1,79 @@
+//! Waiting out a busy model server: requests answered with 429 or 503 are
+//! retried with exponential backoff and jitter, up to a maximum total wait
+//! (`OLLAMA_MAX_WAIT_SECS`), instead of failing mid-session.
+
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Response, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+const DEFAULT_MAX_WAIT_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// Delay before the first retry; doubled for each one after it.
+    pub initial: Duration,
+    /// Cap on a single delay.
+    pub max_delay: Duration,
+    /// Total time spent waiting before giving up; zero disables retries.
+    pub max_wait: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max_delay: Duration::from_secs(15),
+            max_wait: Duration::from_secs(DEFAULT_MAX_WAIT_SECS),
+        }
+    }
+}
+
+impl Backoff {
+    pub fn from_env() -> Self {
+        let max_wait = std::env::var("OLLAMA_MAX_WAIT_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_MAX_WAIT_SECS);
+        Self {
+            max_wait: Duration::from_secs(max_wait),
+            ..Self::default()
+        }
+    }
+
+    /// Delay before retry number `attempt` (from 0): the doubled base,
+    /// capped, scaled by `jitter` in `[0, 1)` to between half and all of it
+    /// so clients that were turned away together don't return together.
+    pub fn delay(&self, attempt: u32, jitter: f64) -> Duration {
+        let base = self
+            .initial
+            .saturating_mul(2u32.saturating_pow(attempt.min(16)))
+            .min(self.max_delay);
+        base.mul_f64(0.5 + 0.5 * jitter.clamp(0.0, 1.0))
+    }
+}
+
+/// The server is overloaded or rate limiting, and asking again later helps.
+pub fn is_busy(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// A `Retry-After` given in seconds.
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// A number in `[0, 1)`, different on every call.
+pub fn jitter() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
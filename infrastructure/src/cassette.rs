@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// One captured model call. Stored one per line (JSONL) so cassettes are easy
+/// to read, diff, and trim by hand when attached to bug reports.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Interaction {
+    Chat {
+        model: String,
+        system: String,
+        prompt: String,
+        response: String,
+    },
+    Embedding {
+        model: String,
+        prompt: String,
+        embedding: Vec<f32>,
+    },
+}
+
+enum Mode {
+    Record(File),
+    Replay(Vec<(Interaction, bool)>),
+}
+
+/// Records every model interaction to a file, or replays them from one
+/// instead of contacting the server.
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mutex<Mode>,
+}
+
+static ACTIVE: OnceLock<Arc<Cassette>> = OnceLock::new();
+
+/// Make `cassette` the process-wide cassette picked up by every client.
+pub fn install(cassette: Cassette) -> Result<()> {
+    ACTIVE
+        .set(Arc::new(cassette))
+        .map_err(|_| anyhow::anyhow!("a record/replay cassette is already active"))
+}
+
+pub fn active() -> Option<Arc<Cassette>> {
+    ACTIVE.get().cloned()
+}
+
+impl Cassette {
+    pub fn record(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)?;
+        Ok(Self {
+            path,
+            mode: Mutex::new(Mode::Record(file)),
+        })
+    }
+
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let reader = BufReader::new(File::open(&path)?);
+        let mut interactions = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let interaction: Interaction = serde_json::from_str(&line).map_err(|e| {
+                anyhow::anyhow!("{}:{}: invalid cassette entry: {}", path.display(), i + 1, e)
+            })?;
+            interactions.push((interaction, false));
+        }
+        Ok(Self {
+            path,
+            mode: Mutex::new(Mode::Replay(interactions)),
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(*self.lock(), Mode::Replay(_))
+    }
+
+    pub fn save(&self, interaction: Interaction) -> Result<()> {
+        if let Mode::Record(file) = &mut *self.lock() {
+            writeln!(file, "{}", serde_json::to_string(&interaction)?)?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Next unused recorded chat reply for this exact system + prompt.
+    pub fn replay_chat(&self, system: &str, prompt: &str) -> Result<String> {
+        let mut mode = self.lock();
+        if let Mode::Replay(interactions) = &mut *mode {
+            for (interaction, used) in interactions.iter_mut() {
+                if let Interaction::Chat {
+                    system: s,
+                    prompt: p,
+                    response,
+                    ..
+                } = interaction
+                {
+                    if !*used && s == system && p == prompt {
+                        *used = true;
+                        return Ok(response.clone());
+                    }
+                }
+            }
+        }
+        Err(self.miss("chat", prompt))
+    }
+
+    /// Recorded embedding for this exact text (embeddings are deterministic,
+    /// so entries may be reused).
+    pub fn replay_embedding(&self, prompt: &str) -> Result<Vec<f32>> {
+        let mode = self.lock();
+        if let Mode::Replay(interactions) = &*mode {
+            for (interaction, _) in interactions {
+                if let Interaction::Embedding {
+                    prompt: p,
+                    embedding,
+                    ..
+                } = interaction
+                {
+                    if p == prompt {
+                        return Ok(embedding.clone());
+                    }
+                }
+            }
+        }
+        Err(self.miss("embedding", prompt))
+    }
+
+    fn miss(&self, kind: &str, prompt: &str) -> anyhow::Error {
+        let preview: String = prompt.chars().take(80).collect();
+        anyhow::anyhow!(
+            "no recorded {} interaction in {} for: {}",
+            kind,
+            self.path.display(),
+            preview
+        )
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Mode> {
+        self.mode.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -1,73 +1,522 @@
+use crate::audit_log::Provenance;
+use crate::policy::{self, Policy};
+use crate::ollama_client::ApiStyle;
+use crate::embedding_storage::{collection_db_path, DEFAULT_COLLECTION};
+use crate::postprocess::RewriteRule;
+use crate::quantization::Quantization;
 use dotenvy::dotenv;
-use std::collections::hash_map::DefaultHasher;
+use serde::{Deserialize, Serialize};
+use shared::cache::CacheLimits;
+use shared::paths;
+use shared::types::Result;
+use std::collections::BTreeMap;
 use std::env;
-use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
-
-fn find_project_root() -> Option<String> {
-    let mut current = std::env::current_dir().ok()?;
-    loop {
-        // Check for various project indicators
-        let project_files = [
-            "Cargo.toml",      // Rust
-            "package.json",    // Node.js
-            "requirements.txt", // Python
-            "Pipfile",         // Python
-            "pyproject.toml",  // Python
-            "setup.py",        // Python
-            "Makefile",        // C/C++
-            "CMakeLists.txt",  // C/C++
-            "configure.ac",    // C/C++
-            "go.mod",          // Go
-            "Gemfile",         // Ruby
-            "composer.json",   // PHP
-            ".git",            // Git repo as fallback
-        ];
-
-        for file in &project_files {
-            if current.join(file).exists() {
-                return Some(current.display().to_string());
-            }
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Settings read from the user config file, for values that don't fit in
+/// an environment variable.
+#[derive(Deserialize, Default)]
+struct FileConfig {
+    #[serde(default)]
+    postprocess: Vec<RewriteRule>,
+    #[serde(default)]
+    cache: CacheSettings,
+    #[serde(default)]
+    notify: NotifySettings,
+    #[serde(default)]
+    accessible: bool,
+    #[serde(default)]
+    show_stats: bool,
+    remember: Option<bool>,
+    crate_docs: Option<bool>,
+    #[serde(default)]
+    confirm: ConfirmSettings,
+    api_style: Option<ApiStyle>,
+    api_key: Option<String>,
+    #[serde(default)]
+    env_context: EnvContextSettings,
+    #[serde(default)]
+    sandbox: SandboxSettings,
+    #[serde(default)]
+    network: NetworkSettings,
+    #[serde(default)]
+    index: IndexSettings,
+    #[serde(default)]
+    audit: AuditSettings,
+    #[serde(default)]
+    remote: RemoteSettings,
+    #[serde(default)]
+    collections: BTreeMap<String, CollectionSettings>,
+}
+
+/// Limits for each on-disk answer cache.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct CacheSettings {
+    pub command: CacheLimits,
+    pub explain: CacheLimits,
+    pub rag: CacheLimits,
+}
+
+/// When to announce that a long-running step finished, per mode, set under
+/// `[notify.<mode>]` in `config.toml`.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct NotifySettings {
+    /// A single suggested command.
+    pub command: NotifyRule,
+    /// Each step of an agent plan.
+    pub agent: NotifyRule,
+    /// A whole `watch` session.
+    pub watch: NotifyRule,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct NotifyRule {
+    /// Only runs at least this long are announced.
+    pub after_secs: u64,
+    pub desktop: bool,
+    pub bell: bool,
+}
+
+impl Default for NotifyRule {
+    fn default() -> Self {
+        Self {
+            after_secs: 30,
+            desktop: true,
+            bell: true,
         }
+    }
+}
+
+impl NotifyRule {
+    pub fn applies(&self, elapsed: Duration) -> bool {
+        (self.desktop || self.bell) && elapsed.as_secs() >= self.after_secs
+    }
+}
+
+/// Environment variables mentioned to the model, set under `[env_context]`
+/// in `config.toml`. Nothing is shared unless listed.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct EnvContextSettings {
+    /// Variables whose presence is shared, e.g. `KUBECONFIG`.
+    pub names: Vec<String>,
+    /// Variables whose value is shared too, e.g. `AWS_PROFILE`.
+    pub values: Vec<String>,
+}
 
-        if !current.pop() {
-            break;
+/// The environment commands run with, set under `[sandbox]` in
+/// `config.toml`.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SandboxSettings {
+    /// Run commands with only a few basic variables and those in `keep`
+    /// (`--scrub-env`, `VIBE_SCRUB_ENV`).
+    pub scrub_env: bool,
+    /// Further variables kept when scrubbing; a trailing `*` matches any
+    /// suffix, e.g. `AWS_*`.
+    pub keep: Vec<String>,
+    /// The directory commands run in, when it isn't vibe_cli's own: where
+    /// an earlier `cd` in the session or plan left it.
+    #[serde(skip)]
+    pub cwd: Option<PathBuf>,
+    /// How long the command is expected to take, when an agent step says;
+    /// running well past it asks the user whether to keep waiting.
+    #[serde(skip)]
+    pub expected: Option<Duration>,
+    /// The SSH host commands run on, over `ssh`, instead of here: a step of
+    /// a multi-host plan. The safety checks still see the command itself.
+    #[serde(skip)]
+    pub host: Option<String>,
+}
+
+/// How firewall and network changes are guarded, set under `[network]` in
+/// `config.toml`.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Minutes after which a firewall or network change is rolled back
+    /// unless the user keeps it; 0 turns the revert window off.
+    pub revert_minutes: u64,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self { revert_minutes: 5 }
+    }
+}
+
+/// What `audit export` includes, set under `[audit]` in `config.toml`.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct AuditSettings {
+    /// Include what the user asked for; off keeps prompt text out of
+    /// exports handed to other teams.
+    pub export_prompts: bool,
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        Self { export_prompts: true }
+    }
+}
+
+/// How agent plans run on hosts from the SSH inventory, set under
+/// `[remote]` in `config.toml`.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct RemoteSettings {
+    /// Hosts where the approved plan runs without confirming each step; a
+    /// trailing `*` matches any suffix, e.g. `staging-*`.
+    pub unattended: Vec<String>,
+    /// How many of those run at the same time (`--parallel`).
+    pub parallel: usize,
+    /// Hosts to run the plan on (`--hosts`), instead of those the task names.
+    #[serde(skip)]
+    pub hosts: Vec<String>,
+}
+
+impl Default for RemoteSettings {
+    fn default() -> Self {
+        Self {
+            unattended: Vec::new(),
+            parallel: 1,
+            hosts: Vec::new(),
         }
     }
-    None
 }
 
-fn project_cache_suffix() -> String {
-    if let Some(root) = find_project_root() {
-        let mut hasher = DefaultHasher::new();
-        root.hash(&mut hasher);
-        format!("{:x}", hasher.finish())
-    } else {
-        "global".to_string()
+/// How the project is walked to find the files to index, set under
+/// `[index]` in `config.toml` and, for one project, in its `.vibe.toml`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct IndexSettings {
+    /// Follow symbolic links; loops are detected and skipped. Off, links
+    /// are left out.
+    pub follow_symlinks: bool,
+    /// Stay on the file system of the project root, skipping mounts below
+    /// it.
+    pub same_file_system: bool,
+    /// Deepest directory level walked below the root; 0 for no limit.
+    pub max_depth: usize,
+    /// The walk stops after this many files; 0 for no limit.
+    pub max_files: usize,
+    /// Files larger than this are left out (`RAG_MAX_FILE_BYTES`).
+    pub max_file_bytes: u64,
+    /// Directory names skipped besides the built-in ones (`target`,
+    /// `node_modules`, `venv`, ...), e.g. `.venv-py311` or `vendor`.
+    pub ignored_dirs: Vec<String>,
+    /// Built-in ignored directory names to walk after all, e.g. `build`
+    /// when it holds sources.
+    pub include_dirs: Vec<String>,
+    /// Globs, relative to the project root, of files to index even when
+    /// they look binary or minified, or are lockfiles.
+    pub force_include: Vec<String>,
+}
+
+impl Default for IndexSettings {
+    fn default() -> Self {
+        Self {
+            follow_symlinks: false,
+            same_file_system: true,
+            max_depth: 32,
+            max_files: 100_000,
+            max_file_bytes: 32 * 1024 * 1024,
+            ignored_dirs: Vec::new(),
+            include_dirs: Vec::new(),
+            force_include: Vec::new(),
+        }
     }
 }
 
+/// Per-project settings, kept in the project root. Only its `[index]`
+/// table is read: a checked-out repository shouldn't be able to loosen
+/// confirmations or the sandbox.
+pub const PROJECT_CONFIG_FILE: &str = ".vibe.toml";
+
+/// `base` with the keys of the `[index]` table in `root`'s `.vibe.toml`
+/// applied over it; `base` itself when there is no such file, or it is
+/// invalid (which is reported).
+pub fn project_index_settings(root: &Path, base: &IndexSettings) -> IndexSettings {
+    let path = root.join(PROJECT_CONFIG_FILE);
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return base.clone();
+    };
+    let merged = (|| -> Result<IndexSettings> {
+        let project: toml::Table = toml::from_str(&content)?;
+        let Some(overrides) = project.get("index") else {
+            return Ok(base.clone());
+        };
+        let toml::Value::Table(overrides) = overrides else {
+            return Err(anyhow::anyhow!("`index` must be a table"));
+        };
+        let mut settings = toml::Table::try_from(base)?;
+        settings.extend(overrides.clone());
+        Ok(toml::Value::Table(settings).try_into()?)
+    })();
+    merged.unwrap_or_else(|e| {
+        eprintln!("Ignoring invalid {}: {}", path.display(), e);
+        base.clone()
+    })
+}
+
+/// Embedding and retrieval settings of one collection of the embeddings
+/// store, set under `[collections.<name>]` in `config.toml`. Unset keys
+/// keep the global RAG settings.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct CollectionSettings {
+    /// Model that embeds this collection and questions asked of it.
+    pub embedding_model: Option<String>,
+    pub top_k: Option<usize>,
+    pub min_score: Option<f32>,
+    pub max_chunks_per_file: Option<usize>,
+}
+
+/// How commands are confirmed, per risk tier, set under `[confirm.<tier>]`
+/// in `config.toml`. Unset keys keep the tier's default.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(from = "RawConfirmSettings")]
+pub struct ConfirmSettings {
+    /// Read-only commands.
+    pub safe: ConfirmRule,
+    /// Commands that change files or system state.
+    pub mutating: ConfirmRule,
+    /// Commands the safety check warns about.
+    pub dangerous: ConfirmRule,
+    /// Commands that change SSH keys or which hosts are trusted.
+    pub ssh: ConfirmRule,
+    /// Commands that change the firewall or network.
+    pub network: ConfirmRule,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfirmRule {
+    /// The answer Enter gives.
+    pub default_yes: bool,
+    /// Ask a second time before going ahead.
+    pub double: bool,
+    /// Text that must be typed before the yes/no question.
+    pub phrase: Option<String>,
+}
+
+impl Default for ConfirmSettings {
+    fn default() -> Self {
+        Self {
+            safe: ConfirmRule {
+                default_yes: true,
+                double: false,
+                phrase: None,
+            },
+            mutating: ConfirmRule {
+                default_yes: false,
+                double: false,
+                phrase: None,
+            },
+            dangerous: ConfirmRule {
+                default_yes: false,
+                double: false,
+                phrase: Some("yes".to_string()),
+            },
+            ssh: ConfirmRule {
+                default_yes: false,
+                double: true,
+                phrase: Some("yes".to_string()),
+            },
+            network: ConfirmRule {
+                default_yes: false,
+                double: true,
+                phrase: Some("yes".to_string()),
+            },
+        }
+    }
+}
+
+impl ConfirmSettings {
+    /// These settings with Enter declining at every tier.
+    pub fn declining(mut self) -> Self {
+        for rule in [&mut self.safe, &mut self.mutating, &mut self.dangerous, &mut self.ssh, &mut self.network] {
+            rule.default_yes = false;
+        }
+        self
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawConfirmSettings {
+    safe: RawConfirmRule,
+    mutating: RawConfirmRule,
+    dangerous: RawConfirmRule,
+    ssh: RawConfirmRule,
+    network: RawConfirmRule,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawConfirmRule {
+    default_yes: Option<bool>,
+    double: Option<bool>,
+    /// An empty phrase turns the requirement off.
+    phrase: Option<String>,
+}
+
+impl RawConfirmRule {
+    fn over(self, rule: ConfirmRule) -> ConfirmRule {
+        ConfirmRule {
+            default_yes: self.default_yes.unwrap_or(rule.default_yes),
+            double: self.double.unwrap_or(rule.double),
+            phrase: match self.phrase {
+                Some(phrase) if phrase.trim().is_empty() => None,
+                Some(phrase) => Some(phrase),
+                None => rule.phrase,
+            },
+        }
+    }
+}
+
+impl From<RawConfirmSettings> for ConfirmSettings {
+    fn from(raw: RawConfirmSettings) -> Self {
+        let defaults = ConfirmSettings::default();
+        Self {
+            safe: raw.safe.over(defaults.safe),
+            mutating: raw.mutating.over(defaults.mutating),
+            dangerous: raw.dangerous.over(defaults.dangerous),
+            ssh: raw.ssh.over(defaults.ssh),
+            network: raw.network.over(defaults.network),
+        }
+    }
+}
+
+/// `$VIBE_CONFIG`, or `config.toml` in the user config directory.
+pub fn user_config_path() -> PathBuf {
+    if let Ok(path) = env::var("VIBE_CONFIG") {
+        return PathBuf::from(path);
+    }
+    paths::config_dir().join("config.toml")
+}
+
+fn load_file_config() -> FileConfig {
+    let path = user_config_path();
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return FileConfig::default();
+    };
+    toml::from_str(&content).unwrap_or_else(|e| {
+        eprintln!("Ignoring invalid config file {}: {}", path.display(), e);
+        FileConfig::default()
+    })
+}
+
+/// Comma-separated collection names, e.g. `code,docs`, without blanks or
+/// repeats.
+pub fn parse_collections(list: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Safe mode for a `SAFE_MODE` value (`None` when unset): on unless it is
+/// `0`, `false` or `off`.
+pub fn parse_safe_mode(value: Option<&str>) -> bool {
+    value.is_none_or(|v| !matches!(v.trim(), "0" | "false" | "off"))
+}
+
+const DEFAULT_RAG_TOP_K: usize = 50;
+const DEFAULT_RAG_MMR_LAMBDA: f32 = 0.7;
+const DEFAULT_RAG_MAX_CHUNKS_PER_FILE: usize = 8;
+
 #[derive(Clone)]
 pub struct Config {
     pub ollama_base_url: String,
     pub ollama_model: String,
+    pub api_style: ApiStyle,
+    /// Bearer token for servers that require one (`VIBE_API_KEY`).
+    pub api_key: Option<String>,
     pub db_path: String,
     pub rag_include_patterns: Vec<String>,
     pub rag_exclude_patterns: Vec<String>,
+    pub vector_quantization: Quantization,
+    pub vector_cache: bool,
+    /// Widen the best retrieved chunks with the chunks around them.
+    pub rag_expand_neighbors: bool,
+    /// Files kept by the summary-based first retrieval stage; 0 disables
+    /// per-file summaries altogether.
+    pub rag_summary_files: usize,
+    /// Chunks retrieved for a question (`RAG_TOP_K`, `--top-k`).
+    pub rag_top_k: usize,
+    /// Retrieved chunks less similar to the question than this are dropped
+    /// (`RAG_MIN_SCORE`, `--min-score`).
+    pub rag_min_score: f32,
+    /// Collections a question searches, e.g. `code` and `docs`
+    /// (`RAG_COLLECTIONS`, `--collections`).
+    pub rag_collections: Vec<String>,
+    /// Per-collection overrides of the RAG settings.
+    pub collections: BTreeMap<String, CollectionSettings>,
+    /// Cap on the characters of context sent with a question; 0 for none.
+    pub rag_max_context_chars: usize,
+    /// Trade-off between relevance (1.0) and variety among the retrieved
+    /// chunks (`RAG_MMR_LAMBDA`).
+    pub rag_mmr_lambda: f32,
+    /// Most chunks retrieved from one file; 0 for no cap.
+    pub rag_max_chunks_per_file: usize,
+    /// Ask the model which mode should handle input the heuristics can't
+    /// place (`VIBE_ROUTE_WITH_MODEL`).
+    pub route_with_model: bool,
+    pub safe_mode: bool,
+    pub telemetry: bool,
+    /// Encrypt caches and indexed code at rest (`VIBE_ENCRYPT`).
+    pub encrypt_at_rest: bool,
+    pub caches: CacheSettings,
+    pub notify: NotifySettings,
+    /// Plain, screen-reader-friendly output (`--accessible`).
+    pub accessible: bool,
+    /// Print timing and token counts after each model call
+    /// (`VIBE_SHOW_STATS`).
+    pub show_stats: bool,
+    /// Embed accepted RAG answers, explanations and chat exchanges for
+    /// `recall` (on unless `remember = false` or `VIBE_REMEMBER=0`).
+    pub remember: bool,
+    /// Add the docs.rs docs of crates a RAG question names (on unless
+    /// `crate_docs = false` or `VIBE_CRATE_DOCS=0`).
+    pub crate_docs: bool,
+    pub confirm: ConfirmSettings,
+    pub env_context: EnvContextSettings,
+    pub sandbox: SandboxSettings,
+    pub network: NetworkSettings,
+    pub index: IndexSettings,
+    pub audit: AuditSettings,
+    pub remote: RemoteSettings,
+    /// Copy each suggested command to the clipboard (`--copy`).
+    pub copy_to_clipboard: bool,
+    /// Type confirmed commands into this tmux pane or screen window instead
+    /// of running them (`--send-to-pane`).
+    pub send_to_pane: Option<String>,
+    /// Start confirmed commands as detached jobs instead of waiting for
+    /// them (`--detach`).
+    pub detach: bool,
+    /// Where the command being confirmed came from; set per command.
+    pub provenance: Provenance,
+    /// Re-index the project before answering a RAG question instead of in
+    /// the background (`--fresh`).
+    pub rag_fresh: bool,
+    pub postprocess_rules: Vec<RewriteRule>,
 }
 
 impl Config {
     pub fn load() -> Self {
         dotenv().ok();
+        let file_config = load_file_config();
         let db_path = env::var("DB_PATH").unwrap_or_else(|_| {
-            let home = env::var("HOME").unwrap_or_else(|_| ".".to_string());
-            let mut path = PathBuf::from(home);
-            path.push(".local");
-            path.push("share");
-            path.push("vibe_cli");
-            let suffix = project_cache_suffix();
-            path.push(format!("{}_embeddings.db", suffix));
-            path.to_string_lossy().to_string()
+            paths::project_data_file("embeddings.db")
+                .to_string_lossy()
+                .to_string()
         });
 
         // Default include patterns for common code files
@@ -84,14 +533,161 @@ impl Config {
             .map(|s| s.trim().to_string())
             .collect();
 
-        Self {
+        let mut config = Self {
             ollama_base_url: env::var("OLLAMA_BASE_URL")
                 .unwrap_or_else(|_| "http://localhost:11434".to_string()),
             ollama_model: env::var("BASE_MODEL")
                 .unwrap_or_else(|_| "qwen2.5:1.5b-instruct".to_string()),
+            api_style: env::var("API_STYLE")
+                .ok()
+                .and_then(|v| ApiStyle::parse(&v))
+                .or(file_config.api_style)
+                .unwrap_or_default(),
+            api_key: env::var("VIBE_API_KEY")
+                .or_else(|_| env::var("OPENAI_API_KEY"))
+                .ok()
+                .or(file_config.api_key),
             db_path,
             rag_include_patterns,
             rag_exclude_patterns,
+            vector_quantization: env::var("VECTOR_QUANTIZATION")
+                .map(|v| Quantization::parse(&v))
+                .unwrap_or_default(),
+            vector_cache: env::var("VECTOR_CACHE")
+                .map(|v| !matches!(v.trim(), "0" | "false" | "off"))
+                .unwrap_or(true),
+            rag_expand_neighbors: env::var("RAG_EXPAND_NEIGHBORS")
+                .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+                .unwrap_or(false),
+            rag_summary_files: env::var("RAG_SUMMARY_FILES")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(0),
+            rag_top_k: env::var("RAG_TOP_K")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|&k| k > 0)
+                .unwrap_or(DEFAULT_RAG_TOP_K),
+            rag_min_score: env::var("RAG_MIN_SCORE")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(0.0),
+            rag_collections: env::var("RAG_COLLECTIONS")
+                .ok()
+                .map(|v| parse_collections(&v))
+                .filter(|names| !names.is_empty())
+                .unwrap_or_else(|| vec![DEFAULT_COLLECTION.to_string()]),
+            collections: file_config.collections,
+            rag_max_context_chars: env::var("RAG_MAX_CONTEXT_CHARS")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(0),
+            rag_mmr_lambda: env::var("RAG_MMR_LAMBDA")
+                .ok()
+                .and_then(|v| v.trim().parse::<f32>().ok())
+                .map(|v| v.clamp(0.0, 1.0))
+                .unwrap_or(DEFAULT_RAG_MMR_LAMBDA),
+            rag_max_chunks_per_file: env::var("RAG_MAX_CHUNKS_PER_FILE")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(DEFAULT_RAG_MAX_CHUNKS_PER_FILE),
+            route_with_model: env::var("VIBE_ROUTE_WITH_MODEL")
+                .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+                .unwrap_or(false),
+            safe_mode: parse_safe_mode(env::var("SAFE_MODE").ok().as_deref()),
+            telemetry: env::var("TELEMETRY")
+                .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+                .unwrap_or(false),
+            encrypt_at_rest: env::var("VIBE_ENCRYPT")
+                .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+                .unwrap_or(false),
+            caches: file_config.cache,
+            notify: file_config.notify,
+            confirm: file_config.confirm,
+            env_context: file_config.env_context,
+            sandbox: SandboxSettings {
+                scrub_env: file_config.sandbox.scrub_env
+                    || env::var("VIBE_SCRUB_ENV")
+                        .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+                        .unwrap_or(false),
+                ..file_config.sandbox
+            },
+            network: file_config.network,
+            index: IndexSettings {
+                max_file_bytes: env::var("RAG_MAX_FILE_BYTES")
+                    .ok()
+                    .and_then(|v| v.trim().parse().ok())
+                    .unwrap_or(file_config.index.max_file_bytes),
+                ..file_config.index
+            },
+            audit: file_config.audit,
+            remote: file_config.remote,
+            accessible: file_config.accessible
+                || env::var("VIBE_ACCESSIBLE")
+                    .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+                    .unwrap_or(false),
+            show_stats: file_config.show_stats
+                || env::var("VIBE_SHOW_STATS")
+                    .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+                    .unwrap_or(false),
+            remember: file_config.remember.unwrap_or(true)
+                && !env::var("VIBE_REMEMBER")
+                    .map(|v| matches!(v.trim(), "0" | "false" | "off"))
+                    .unwrap_or(false),
+            crate_docs: file_config.crate_docs.unwrap_or(true)
+                && !env::var("VIBE_CRATE_DOCS")
+                    .map(|v| matches!(v.trim(), "0" | "false" | "off"))
+                    .unwrap_or(false),
+            copy_to_clipboard: false,
+            send_to_pane: None,
+            detach: false,
+            provenance: Provenance::default(),
+            rag_fresh: false,
+            postprocess_rules: file_config.postprocess,
+        };
+        config.apply_policy(policy::system());
+        config
+    }
+
+    /// Settings for searching or filling the collection `name`: its own
+    /// database, with its `[collections.<name>]` overrides applied.
+    pub fn for_collection(&self, name: &str) -> Result<Config> {
+        let mut config = self.clone();
+        config.db_path = collection_db_path(&self.db_path, name)?
+            .to_string_lossy()
+            .to_string();
+        if let Some(settings) = self.collections.get(name) {
+            if let Some(top_k) = settings.top_k.filter(|&k| k > 0) {
+                config.rag_top_k = top_k;
+            }
+            if let Some(min_score) = settings.min_score {
+                config.rag_min_score = min_score;
+            }
+            if let Some(max) = settings.max_chunks_per_file {
+                config.rag_max_chunks_per_file = max;
+            }
+        }
+        config.rag_collections = vec![name.to_string()];
+        Ok(config)
+    }
+
+    /// Model that embeds the collection `name`; `None` for the chat model.
+    pub fn embedding_model(&self, name: &str) -> Option<String> {
+        self.collections
+            .get(name)
+            .and_then(|settings| settings.embedding_model.clone())
+    }
+
+    /// Overlay administrator-enforced settings on top of the user's.
+    pub fn apply_policy(&mut self, policy: &Policy) {
+        if let Some(safe_mode) = policy.safe_mode {
+            self.safe_mode = safe_mode;
+        }
+        if let Some(telemetry) = policy.telemetry {
+            self.telemetry = telemetry;
+        }
+        if let Some(encrypt) = policy.encrypt_at_rest {
+            self.encrypt_at_rest = encrypt;
         }
     }
 }
@@ -0,0 +1,260 @@
+//! docs.rs pages (or crates.io READMEs) of the Rust crates a project
+//! depends on, at the version its Cargo.lock resolves, cached on disk.
+
+use crate::doc_source::html_to_markdown;
+use crate::offline;
+use crate::policy;
+use shared::paths;
+use shared::types::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const DOCS_RS: &str = "https://docs.rs";
+const CRATES_IO: &str = "https://crates.io";
+/// crates.io refuses requests without a user agent naming the client.
+const USER_AGENT: &str = concat!("vibe_cli/", env!("CARGO_PKG_VERSION"));
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+/// Dependency tables of a manifest, besides those under `target.*`.
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// A crate the project depends on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+    /// The version Cargo.lock resolves, or else the manifest's requirement,
+    /// e.g. `4.5`; `latest` when neither says.
+    pub version: String,
+    /// `version` is an exact version from Cargo.lock.
+    pub locked: bool,
+}
+
+/// Dependencies declared by the manifests at `root`: its Cargo.toml (with
+/// `[workspace.dependencies]`) and those of the directories right below it,
+/// which covers the usual workspace layouts. Empty when `root` isn't a Rust
+/// project.
+pub fn rust_dependencies(root: &Path) -> Vec<Dependency> {
+    let mut manifests = vec![root.join("Cargo.toml")];
+    if let Ok(entries) = std::fs::read_dir(root) {
+        let mut members: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path().join("Cargo.toml"))
+            .filter(|manifest| manifest.is_file())
+            .collect();
+        members.sort();
+        manifests.extend(members);
+    }
+    let mut requirements: BTreeMap<String, String> = BTreeMap::new();
+    for manifest in manifests {
+        let Ok(text) = std::fs::read_to_string(&manifest) else {
+            continue;
+        };
+        let Ok(value) = text.parse::<toml::Table>() else {
+            continue;
+        };
+        let mut tables: Vec<&toml::Table> = DEPENDENCY_TABLES
+            .iter()
+            .filter_map(|name| value.get(*name)?.as_table())
+            .collect();
+        if let Some(deps) = value
+            .get("workspace")
+            .and_then(|w| w.get("dependencies"))
+            .and_then(|d| d.as_table())
+        {
+            tables.push(deps);
+        }
+        if let Some(targets) = value.get("target").and_then(|t| t.as_table()) {
+            for target in targets.values() {
+                tables.extend(
+                    DEPENDENCY_TABLES
+                        .iter()
+                        .filter_map(|name| target.get(*name)?.as_table()),
+                );
+            }
+        }
+        for (key, spec) in tables.into_iter().flatten() {
+            // `foo = { path = "../foo" }` is part of the project itself.
+            if spec.get("path").is_some() {
+                continue;
+            }
+            let name = spec
+                .get("package")
+                .and_then(|p| p.as_str())
+                .unwrap_or(key)
+                .to_string();
+            let requirement = match spec {
+                toml::Value::String(version) => Some(version.clone()),
+                _ => spec.get("version").and_then(|v| v.as_str()).map(str::to_string),
+            };
+            let entry = requirements.entry(name).or_default();
+            if entry.is_empty() {
+                *entry = requirement.unwrap_or_default();
+            }
+        }
+    }
+    let locked = locked_versions(&root.join("Cargo.lock"));
+    requirements
+        .into_iter()
+        .map(|(name, requirement)| match locked.get(&name) {
+            Some(version) => Dependency {
+                name,
+                version: version.clone(),
+                locked: true,
+            },
+            None => Dependency {
+                name,
+                version: match requirement.trim_start_matches(['^', '=', ' ']) {
+                    "" | "*" => "latest".to_string(),
+                    requirement => requirement.to_string(),
+                },
+                locked: false,
+            },
+        })
+        .collect()
+}
+
+/// The highest version Cargo.lock resolves for each package.
+fn locked_versions(lockfile: &Path) -> BTreeMap<String, String> {
+    let mut versions: BTreeMap<String, String> = BTreeMap::new();
+    let Ok(text) = std::fs::read_to_string(lockfile) else {
+        return versions;
+    };
+    let Ok(lock) = text.parse::<toml::Table>() else {
+        return versions;
+    };
+    let packages = lock.get("package").and_then(|p| p.as_array());
+    for package in packages.into_iter().flatten() {
+        let (Some(name), Some(version)) = (
+            package.get("name").and_then(|n| n.as_str()),
+            package.get("version").and_then(|v| v.as_str()),
+        ) else {
+            continue;
+        };
+        let newer = versions
+            .get(name)
+            .is_none_or(|known| version_key(version) > version_key(known));
+        if newer {
+            versions.insert(name.to_string(), version.to_string());
+        }
+    }
+    versions
+}
+
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(['.', '-', '+'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+/// `name` as it is written in code: `tree-sitter` is `tree_sitter`.
+fn ident(name: &str) -> String {
+    name.replace('-', "_").to_lowercase()
+}
+
+/// The dependencies `question` names, in the order it names them; `-` and
+/// `_` are interchangeable, as in `serde_json` and `serde-json`.
+pub fn mentioned_crates<'a>(question: &str, dependencies: &'a [Dependency]) -> Vec<&'a Dependency> {
+    let mut found: Vec<&Dependency> = Vec::new();
+    for word in question.split(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_')) {
+        let word = ident(word.trim_matches(['-', '_']));
+        // `tokio::spawn` and `serde::Deserialize` split on `:` already.
+        if let Some(dependency) = dependencies.iter().find(|d| ident(&d.name) == word) {
+            if !found.contains(&dependency) {
+                found.push(dependency);
+            }
+        }
+    }
+    found
+}
+
+/// Fetches crate documentation, keeping pages of exact versions on disk
+/// since they never change.
+#[derive(Debug, Clone)]
+pub struct CrateDocs {
+    docs_rs: String,
+    crates_io: String,
+    cache_dir: PathBuf,
+}
+
+impl Default for CrateDocs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CrateDocs {
+    pub fn new() -> Self {
+        Self {
+            docs_rs: DOCS_RS.to_string(),
+            crates_io: CRATES_IO.to_string(),
+            cache_dir: paths::data_dir().join("crate_docs"),
+        }
+    }
+
+    /// Fetch from these servers instead of docs.rs and crates.io.
+    pub fn with_base_urls(mut self, docs_rs: impl Into<String>, crates_io: impl Into<String>) -> Self {
+        self.docs_rs = docs_rs.into();
+        self.crates_io = crates_io.into();
+        self
+    }
+
+    pub fn with_cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = cache_dir.into();
+        self
+    }
+
+    /// The crate's docs.rs front page as Markdown, or its README when
+    /// docs.rs has none (e.g. the build failed).
+    pub async fn fetch(&self, dependency: &Dependency) -> Result<String> {
+        let cached = self
+            .cache_dir
+            .join(format!("{}-{}.md", dependency.name, dependency.version));
+        if dependency.locked {
+            if let Ok(text) = std::fs::read_to_string(&cached) {
+                return Ok(text);
+            }
+        }
+        offline::ensure_online("fetching crate documentation")?;
+        let client = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(FETCH_TIMEOUT)
+            .build()?;
+        let docs_url = format!(
+            "{}/{}/{}/{}/",
+            self.docs_rs.trim_end_matches('/'),
+            dependency.name,
+            dependency.version,
+            ident(&dependency.name)
+        );
+        let readme_url = format!(
+            "{}/api/v1/crates/{}/{}/readme",
+            self.crates_io.trim_end_matches('/'),
+            dependency.name,
+            dependency.version
+        );
+        let mut text = String::new();
+        for url in [docs_url, readme_url] {
+            policy::system().check_endpoint(&url)?;
+            let response = client.get(&url).send().await?;
+            if response.status().is_success() {
+                text = html_to_markdown(&response.text().await?);
+                if !text.trim().is_empty() {
+                    break;
+                }
+            }
+        }
+        if text.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "no documentation found for {} {}",
+                dependency.name,
+                dependency.version
+            ));
+        }
+        if dependency.locked {
+            std::fs::create_dir_all(&self.cache_dir)?;
+            std::fs::write(&cached, &text)?;
+        }
+        Ok(text)
+    }
+}
@@ -0,0 +1,392 @@
+//! Pages for `docs add`: fetched from a documentation site or read from a
+//! local doc tree, with HTML turned into Markdown-like text.
+
+use crate::offline;
+use crate::policy;
+pub use reqwest::Url;
+use shared::types::Result;
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+use std::time::Duration;
+
+/// Extensions of local files that are read as documentation.
+const DOC_EXTENSIONS: [&str; 7] = ["md", "markdown", "mdx", "txt", "rst", "html", "htm"];
+/// Linked files that are never fetched as pages.
+const SKIPPED_EXTENSIONS: [&str; 16] = [
+    "png", "jpg", "jpeg", "gif", "svg", "ico", "webp", "css", "js", "json", "xml", "zip",
+    "gz", "pdf", "woff", "woff2",
+];
+/// Elements whose content is navigation, scripts or styles, not
+/// documentation.
+const SKIPPED_ELEMENTS: [&str; 10] = [
+    "head", "script", "style", "noscript", "template", "svg", "nav", "header", "footer", "aside",
+];
+const BLOCK_ELEMENTS: [&str; 17] = [
+    "p", "div", "section", "article", "main", "table", "tr", "ul", "ol", "dl", "dt", "dd",
+    "blockquote", "figure", "figcaption", "details", "summary",
+];
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One page of documentation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocPage {
+    /// URL or path of the page.
+    pub location: String,
+    pub text: String,
+}
+
+pub fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// The pages of the site at `url`: the page itself and those it links to,
+/// breadth first, staying under the URL's directory, up to `max_pages`.
+pub async fn fetch_site(url: &str, max_pages: usize) -> Result<Vec<DocPage>> {
+    offline::ensure_online("fetching documentation")?;
+    let start = Url::parse(url)?;
+    let scope = match start.path().rfind('/') {
+        Some(end) => format!("{}{}", start.origin().ascii_serialization(), &start.path()[..=end]),
+        None => start.origin().ascii_serialization(),
+    };
+    let client = reqwest::Client::builder().timeout(FETCH_TIMEOUT).build()?;
+    let mut queue = VecDeque::from([start.clone()]);
+    let mut seen = HashSet::from([start.to_string()]);
+    let mut pages = Vec::new();
+    while let Some(page) = queue.pop_front() {
+        if pages.len() >= max_pages {
+            break;
+        }
+        policy::system().check_endpoint(page.as_str())?;
+        let response = client.get(page.clone()).send().await?;
+        if !response.status().is_success() {
+            // A broken link elsewhere doesn't stop the crawl; the page asked
+            // for does.
+            if page == start {
+                return Err(anyhow::anyhow!("{} answered {}", page, response.status()));
+            }
+            continue;
+        }
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        let body = response.text().await?;
+        // Some servers send pages as text/plain.
+        let html = content_type.contains("html") || body.trim_start().starts_with('<');
+        let text = if html {
+            for link in links(&body, &page) {
+                if link.as_str().starts_with(&scope) && seen.insert(link.to_string()) {
+                    queue.push_back(link);
+                }
+            }
+            html_to_markdown(&body)
+        } else {
+            body
+        };
+        if !text.trim().is_empty() {
+            pages.push(DocPage {
+                location: page.to_string(),
+                text,
+            });
+        }
+    }
+    Ok(pages)
+}
+
+/// The documentation files at `path` (a file, or a directory searched
+/// recursively, skipping hidden entries), HTML converted.
+pub fn read_tree(path: &Path) -> Result<Vec<DocPage>> {
+    let mut files = Vec::new();
+    collect_docs(path, &mut files)?;
+    files.sort();
+    let mut pages = Vec::new();
+    for file in files {
+        let text = String::from_utf8_lossy(&std::fs::read(&file)?).into_owned();
+        let text = match file.extension().and_then(|e| e.to_str()) {
+            Some("html" | "htm") => html_to_markdown(&text),
+            _ => text,
+        };
+        if !text.trim().is_empty() {
+            pages.push(DocPage {
+                location: file.to_string_lossy().to_string(),
+                text,
+            });
+        }
+    }
+    Ok(pages)
+}
+
+fn collect_docs(path: &Path, files: &mut Vec<std::path::PathBuf>) -> Result<()> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("{} does not exist", path.display()));
+    }
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?.path();
+        if entry
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        if entry.is_dir() {
+            collect_docs(&entry, files)?;
+        } else if entry
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| DOC_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Pages `html` links to, resolved against `base`, without fragments or
+/// links to images, scripts and other non-page files.
+pub fn links(html: &str, base: &Url) -> Vec<Url> {
+    let mut found = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = rest.find('>').unwrap_or(rest.len());
+        let (name, attributes) = split_tag(&rest[..end]);
+        rest = &rest[end..];
+        if name != "a" {
+            continue;
+        }
+        let Some(href) = attribute(attributes, "href") else {
+            continue;
+        };
+        let Ok(mut url) = base.join(&decode_entities(&href)) else {
+            continue;
+        };
+        url.set_fragment(None);
+        let skipped = url
+            .path()
+            .rsplit('/')
+            .next()
+            .and_then(|file| file.rsplit_once('.'))
+            .is_some_and(|(_, ext)| SKIPPED_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        if matches!(url.scheme(), "http" | "https") && !skipped && !found.contains(&url) {
+            found.push(url);
+        }
+    }
+    found
+}
+
+/// Lowercased tag name and the raw attributes of a tag's inside, e.g.
+/// `a href="x"`.
+fn split_tag(tag: &str) -> (String, &str) {
+    let tag = tag.trim_start_matches('/');
+    let end = tag
+        .find(|c: char| c.is_whitespace() || c == '/')
+        .unwrap_or(tag.len());
+    (tag[..end].to_lowercase(), &tag[end..])
+}
+
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    // ASCII lowercasing keeps byte offsets valid in `attributes`.
+    let lower = attributes.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(found) = lower[from..].find(name) {
+        let at = from + found;
+        from = at + name.len();
+        let before_ok = at == 0 || lower[..at].ends_with(char::is_whitespace);
+        let after = lower[from..].trim_start();
+        if !before_ok || !after.starts_with('=') {
+            continue;
+        }
+        let value = attributes[attributes.len() - after.len() + 1..].trim_start();
+        return Some(match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or("").to_string(),
+            _ => value
+                .split(|c: char| c.is_whitespace())
+                .next()
+                .unwrap_or("")
+                .to_string(),
+        });
+    }
+    None
+}
+
+/// `&amp;`, `&lt;`, `&#39;`, `&#x27;` and the like as the characters they
+/// stand for; unknown entities are kept as written.
+pub fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..].find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..=end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                "hellip" => Some('…'),
+                "rsquo" | "lsquo" => Some('\''),
+                "rdquo" | "ldquo" => Some('"'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            }?;
+            Some((c, end + 2))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The readable text of an HTML page as Markdown: headings, lists, code
+/// blocks and inline code kept, navigation, scripts and styles dropped.
+pub fn html_to_markdown(html: &str) -> String {
+    let mut out = String::new();
+    let mut skipping: Vec<String> = Vec::new();
+    let mut in_pre = 0usize;
+    let mut rest = html;
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(start) = rest.find('<') else {
+            push_text(&mut out, rest, in_pre > 0, !skipping.is_empty());
+            break;
+        };
+        push_text(&mut out, &rest[..start], in_pre > 0, !skipping.is_empty());
+        rest = &rest[start + 1..];
+        let end = rest.find('>').unwrap_or(rest.len());
+        let tag = &rest[..end];
+        rest = rest.get(end + 1..).unwrap_or("");
+        let closing = tag.starts_with('/');
+        let (name, _) = split_tag(tag);
+
+        if SKIPPED_ELEMENTS.contains(&name.as_str()) {
+            if closing {
+                if let Some(at) = skipping.iter().rposition(|open| *open == name) {
+                    skipping.truncate(at);
+                }
+            } else if !tag.ends_with('/') {
+                skipping.push(name);
+            }
+            continue;
+        }
+        if !skipping.is_empty() {
+            continue;
+        }
+        match name.as_str() {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                block_break(&mut out);
+                if !closing {
+                    let level = name[1..].parse().unwrap_or(1);
+                    out.push_str(&"#".repeat(level));
+                    out.push(' ');
+                }
+            }
+            "pre" if closing => {
+                in_pre = in_pre.saturating_sub(1);
+                if !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str("```");
+                block_break(&mut out);
+            }
+            "pre" => {
+                block_break(&mut out);
+                out.push_str("```\n");
+                in_pre += 1;
+            }
+            "code" | "kbd" if in_pre == 0 => out.push('`'),
+            "li" if !closing => {
+                line_break(&mut out);
+                out.push_str("- ");
+            }
+            "br" => out.push('\n'),
+            "hr" => {
+                block_break(&mut out);
+                out.push_str("---");
+                block_break(&mut out);
+            }
+            "td" | "th" if !closing => out.push(' '),
+            name if BLOCK_ELEMENTS.contains(&name) => block_break(&mut out),
+            _ => {}
+        }
+    }
+    tidy(&out)
+}
+
+fn push_text(out: &mut String, text: &str, verbatim: bool, skipping: bool) {
+    if skipping || text.is_empty() {
+        return;
+    }
+    let text = decode_entities(text);
+    if verbatim {
+        out.push_str(&text);
+        return;
+    }
+    let starts_with_space = text.starts_with(char::is_whitespace);
+    let ends_with_space = text.ends_with(char::is_whitespace);
+    let words = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let at_line_start = out.is_empty() || out.ends_with('\n') || out.ends_with("- ");
+    if (starts_with_space || words.is_empty()) && !at_line_start && !out.ends_with(' ') {
+        out.push(' ');
+    }
+    out.push_str(&words);
+    if ends_with_space && !words.is_empty() {
+        out.push(' ');
+    }
+}
+
+fn line_break(out: &mut String) {
+    let trimmed = out.trim_end_matches(' ').len();
+    out.truncate(trimmed);
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+fn block_break(out: &mut String) {
+    line_break(out);
+    if !out.is_empty() && !out.ends_with("\n\n") {
+        out.push('\n');
+    }
+}
+
+/// Trailing spaces dropped and runs of blank lines cut to one.
+fn tidy(text: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
@@ -1,4 +1,5 @@
 use super::ollama_client::OllamaClient;
+use super::scheduler::Priority;
 use domain::models::Embedding;
 use futures::stream::{self, StreamExt};
 use shared::types::Result;
@@ -7,42 +8,36 @@ pub struct Embedder {
     client: OllamaClient,
 }
 
-#[derive(Clone)]
-pub struct EmbeddingInput {
-    pub id: String,
-    pub path: String,
-    pub text: String,
-}
-
 impl Embedder {
+    /// Embedding batches are background work and yield to interactive requests.
     pub fn new(client: OllamaClient) -> Self {
-        Self { client }
+        Self {
+            client: client.with_priority(Priority::Background),
+        }
     }
 
-    pub async fn generate_embeddings(&self, inputs: &[EmbeddingInput]) -> Result<Vec<Embedding>> {
+    /// Copies of `pending` with their vectors filled in from their text.
+    pub async fn generate_embeddings(&self, pending: &[Embedding]) -> Result<Vec<Embedding>> {
         const BATCH_SIZE: usize = 32;
-        let mut embeddings = Vec::with_capacity(inputs.len());
+        let mut embeddings = Vec::with_capacity(pending.len());
 
-        for chunk in inputs.chunks(BATCH_SIZE) {
-            eprintln!("Generating embeddings for {} chunks...", chunk.len());
+        for chunk in pending.chunks(BATCH_SIZE) {
             let batch_embeddings = self.generate_batch_embeddings(chunk).await?;
             embeddings.extend(batch_embeddings);
         }
         Ok(embeddings)
     }
 
-    async fn generate_batch_embeddings(&self, inputs: &[EmbeddingInput]) -> Result<Vec<Embedding>> {
-        let futures: Vec<_> = inputs
+    async fn generate_batch_embeddings(&self, pending: &[Embedding]) -> Result<Vec<Embedding>> {
+        let futures: Vec<_> = pending
             .iter()
-            .map(|input| {
+            .map(|embedding| {
                 let client = &self.client;
                 async move {
-                    let vector = client.generate_embedding(&input.text).await?;
+                    let vector = client.generate_embedding(&embedding.text).await?;
                     Ok(Embedding {
-                        id: input.id.clone(),
                         vector,
-                        text: input.text.clone(),
-                        path: input.path.clone(),
+                        ..embedding.clone()
                     }) as Result<Embedding>
                 }
             })
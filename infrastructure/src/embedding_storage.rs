@@ -1,19 +1,122 @@
-use domain::models::Embedding;
+use super::quantization::{Quantization, QuantizedVector};
+use domain::models::{DocSource, Embedding, Memory};
+use rusqlite::types::ValueRef;
 use rusqlite::{params, Connection, Result as SqlResult};
+use shared::at_rest;
 use shared::types::Result;
-use std::fs;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::task;
 
+// Fast level; chunk text is small and indexing speed matters more than ratio.
+const TEXT_COMPRESSION_LEVEL: i32 = 3;
+
+/// Columns read into an [`Embedding`] by [`EmbeddingStorage::read_embedding`].
+const EMBEDDING_COLUMNS: &str = "id, vector, text, path, start_offset, language, ordinal, chunk_hash, cell";
+
+/// The project's own code, stored at the configured database path itself.
+pub const DEFAULT_COLLECTION: &str = "code";
+
+/// Database of the collection `name`: the default collection lives at
+/// `db_path`, the others beside it, e.g. `embeddings.docs.db`. Names are
+/// lowercase letters, digits, `-` and `_`.
+pub fn collection_db_path(db_path: impl AsRef<Path>, name: &str) -> Result<PathBuf> {
+    let db_path = db_path.as_ref();
+    if name == DEFAULT_COLLECTION {
+        return Ok(db_path.to_path_buf());
+    }
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    {
+        return Err(anyhow::anyhow!(
+            "invalid collection name '{}': use lowercase letters, digits, '-' and '_'",
+            name
+        ));
+    }
+    let stem = db_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "embeddings".to_string());
+    Ok(db_path.with_file_name(format!("{}.{}.db", stem, name)))
+}
+
+/// Names of the collections stored beside `db_path`, the default one first
+/// when it exists.
+pub fn collection_names(db_path: impl AsRef<Path>) -> Vec<String> {
+    let db_path = db_path.as_ref();
+    let mut names = Vec::new();
+    if db_path.exists() {
+        names.push(DEFAULT_COLLECTION.to_string());
+    }
+    let Some(stem) = db_path.file_stem() else {
+        return names;
+    };
+    let dir = match db_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}.", stem.to_string_lossy());
+    let mut others: Vec<String> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let file = entry.file_name().to_string_lossy().to_string();
+            let name = file.strip_prefix(&prefix)?.strip_suffix(".db")?.to_string();
+            collection_db_path(db_path, &name).ok().map(|_| name)
+        })
+        .filter(|name| name != DEFAULT_COLLECTION)
+        .collect();
+    others.sort();
+    names.extend(others);
+    names
+}
+
 pub struct EmbeddingStorage {
     conn: Arc<Mutex<Connection>>,
+    db_path: std::path::PathBuf,
+    quantization: Quantization,
+}
+
+/// Lightweight row used by the coarse search stage: no text, no f32 vector.
+#[derive(Debug, Clone)]
+pub struct QuantizedEntry {
+    pub id: String,
+    pub vector: QuantizedVector,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StorageStats {
+    pub chunks: usize,
+    pub files: usize,
+    /// Bytes of chunk text as stored on disk (compressed where applicable).
+    pub stored_text_bytes: u64,
+    /// Bytes the same chunk text occupies uncompressed.
+    pub raw_text_bytes: u64,
+    pub db_file_bytes: u64,
+}
+
+impl StorageStats {
+    pub fn saved_bytes(&self) -> u64 {
+        self.raw_text_bytes.saturating_sub(self.stored_text_bytes)
+    }
+
+    pub fn compression_ratio(&self) -> f64 {
+        if self.stored_text_bytes == 0 {
+            return 1.0;
+        }
+        self.raw_text_bytes as f64 / self.stored_text_bytes as f64
+    }
 }
 
 impl EmbeddingStorage {
     pub async fn new(db_path: impl AsRef<Path>) -> Result<Self> {
         let db_path = db_path.as_ref().to_path_buf();
+        let db_file = db_path.clone();
         let conn = task::spawn_blocking(move || -> Result<Connection> {
             if let Some(parent) = db_path.parent() {
                 std::fs::create_dir_all(parent)?;
@@ -22,7 +125,21 @@ impl EmbeddingStorage {
             Self::setup_db(&conn)?;
             Ok(conn)
         }).await??;
-        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            db_path: db_file,
+            quantization: Quantization::None,
+        })
+    }
+
+    /// Also store quantized copies of vectors for two-stage search.
+    pub fn with_quantization(mut self, quantization: Quantization) -> Self {
+        self.quantization = quantization;
+        self
+    }
+
+    pub fn quantization(&self) -> Quantization {
+        self.quantization
     }
 
     fn setup_db(conn: &Connection) -> SqlResult<()> {
@@ -43,55 +160,163 @@ impl EmbeddingStorage {
                 path TEXT PRIMARY KEY,
                 hash TEXT NOT NULL
             );
+            CREATE TABLE IF NOT EXISTS file_summaries (
+                path TEXT PRIMARY KEY,
+                summary TEXT NOT NULL,
+                vector BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS memories (
+                id TEXT PRIMARY KEY,
+                kind TEXT NOT NULL,
+                text BLOB NOT NULL,
+                vector BLOB NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS doc_sources (
+                source TEXT PRIMARY KEY,
+                pages TEXT NOT NULL,
+                fetched_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS index_meta (
+                key TEXT PRIMARY KEY,
+                value INTEGER NOT NULL
+            );
+            INSERT OR IGNORE INTO index_meta (key, value) VALUES ('generation', 0);
         ",
         )?;
-        // Backfill missing path column for existing DBs.
-        let mut stmt = conn.prepare("PRAGMA table_info(embeddings)")?;
+        // Backfill columns missing from DBs created by older versions.
+        Self::ensure_column(conn, "embeddings", "path", "TEXT NOT NULL DEFAULT ''")?;
+        Self::ensure_column(conn, "embeddings", "text_len", "INTEGER NOT NULL DEFAULT 0")?;
+        Self::ensure_column(conn, "embeddings", "qvector", "BLOB")?;
+        // NULL on rows from older versions: their neighbors are unknown.
+        Self::ensure_column(conn, "embeddings", "start_offset", "INTEGER")?;
+        Self::ensure_column(conn, "embeddings", "language", "TEXT NOT NULL DEFAULT ''")?;
+        Self::ensure_column(conn, "embeddings", "ordinal", "INTEGER")?;
+        Self::ensure_column(conn, "embeddings", "chunk_hash", "TEXT NOT NULL DEFAULT ''")?;
+        Self::ensure_column(conn, "embeddings", "cell", "INTEGER")?;
+        // Ensure the indexes exist once the columns are known to be present.
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_embeddings_path ON embeddings(path)",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_embeddings_path_offset ON embeddings(path, start_offset)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Every write to the embeddings table bumps the generation so derived
+    /// caches (e.g. the mmapped vector matrix) know when to rebuild.
+    fn bump_generation(conn: &Connection) -> SqlResult<()> {
+        conn.execute(
+            "UPDATE index_meta SET value = value + 1 WHERE key = 'generation'",
+            [],
+        )?;
+        Ok(())
+    }
+
+    pub fn db_path(&self) -> &Path {
+        &self.db_path
+    }
+
+    pub async fn generation(&self) -> Result<u64> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let value: i64 = conn.query_row(
+                "SELECT value FROM index_meta WHERE key = 'generation'",
+                [],
+                |row| row.get(0),
+            )?;
+            Ok(value as u64)
+        }).await?
+    }
+
+    fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> SqlResult<()> {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
         let mut rows = stmt.query([])?;
-        let mut has_path = false;
         while let Some(row) = rows.next()? {
             let col_name: String = row.get(1)?;
-            if col_name == "path" {
-                has_path = true;
-                break;
+            if col_name == column {
+                return Ok(());
             }
         }
-        if !has_path {
-            conn.execute(
-                "ALTER TABLE embeddings ADD COLUMN path TEXT NOT NULL DEFAULT ''",
-                [],
-            )?;
-        }
-        // Ensure the path index exists once the column is known to be present.
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_embeddings_path ON embeddings(path)",
-            [],
-        )?;
+        conn.execute(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"), [])?;
         Ok(())
     }
 
+    /// Chunk text is stored zstd-compressed as a BLOB, and sealed when
+    /// encryption at rest is on. Rows written by older versions hold plain
+    /// TEXT and are read back unchanged.
+    fn compress_text(text: &str) -> Result<Vec<u8>> {
+        at_rest::seal(zstd::encode_all(text.as_bytes(), TEXT_COMPRESSION_LEVEL)?)
+    }
+
+    fn decode_text(value: ValueRef<'_>) -> Result<String> {
+        match value {
+            ValueRef::Blob(bytes) => {
+                let raw = zstd::decode_all(at_rest::open(bytes.to_vec())?.as_slice())?;
+                Ok(String::from_utf8_lossy(&raw).into_owned())
+            }
+            ValueRef::Text(bytes) => Ok(String::from_utf8_lossy(bytes).into_owned()),
+            _ => Ok(String::new()),
+        }
+    }
+
+    /// A row selected as [`EMBEDDING_COLUMNS`]. Offsets and ordinals missing
+    /// from rows written by older versions read as 0, and their chunk
+    /// hashes as empty.
+    fn read_embedding(row: &rusqlite::Row<'_>) -> Result<Embedding> {
+        let vector_bytes: Vec<u8> = row.get(1)?;
+        Ok(Embedding {
+            id: row.get(0)?,
+            vector: bincode::deserialize(&vector_bytes)?,
+            text: Self::decode_text(row.get_ref(2)?)?,
+            path: row.get(3)?,
+            start_offset: row.get::<_, Option<i64>>(4)?.unwrap_or(0) as usize,
+            language: row.get(5)?,
+            ordinal: row.get::<_, Option<i64>>(6)?.unwrap_or(0) as usize,
+            chunk_hash: row.get(7)?,
+            cell: row.get::<_, Option<i64>>(8)?.map(|cell| cell as usize),
+        })
+    }
+
     pub async fn insert_embeddings(&self, embeddings: Vec<Embedding>) -> Result<()> {
         let conn = Arc::clone(&self.conn);
+        let quantization = self.quantization;
         task::spawn_blocking(move || -> Result<()> {
             let conn = conn.blocking_lock();
             let tx = conn.unchecked_transaction()?;
             {
                 let mut stmt = tx.prepare(
-                    "INSERT OR REPLACE INTO embeddings (id, vector, text, path) VALUES (?, ?, ?, ?)",
+                    "INSERT OR REPLACE INTO embeddings (id, vector, text, path, text_len, qvector, start_offset, language, ordinal, chunk_hash, cell) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
                 )?;
                 for embedding in &embeddings {
                     let vector_bytes = bincode::serialize(&embedding.vector)?;
+                    let text_bytes = Self::compress_text(&embedding.text)?;
+                    let qvector_bytes = QuantizedVector::encode(&embedding.vector, quantization)
+                        .map(|q| bincode::serialize(&q))
+                        .transpose()?;
                     stmt.execute(params![
                         &embedding.id,
                         vector_bytes,
-                        &embedding.text,
-                        &embedding.path
+                        text_bytes,
+                        &embedding.path,
+                        embedding.text.len() as i64,
+                        qvector_bytes,
+                        embedding.start_offset as i64,
+                        &embedding.language,
+                        embedding.ordinal as i64,
+                        &embedding.chunk_hash,
+                        embedding.cell.map(|cell| cell as i64)
                     ])?;
                 }
             }
+            Self::bump_generation(&tx)?;
             tx.commit()?;
             Ok(())
-        }).await?;
+        }).await??;
         eprintln!("Embeddings stored successfully");
         Ok(())
     }
@@ -100,22 +325,254 @@ impl EmbeddingStorage {
         let conn = Arc::clone(&self.conn);
         task::spawn_blocking(move || {
             let conn = conn.blocking_lock();
-            let mut stmt = conn
-                .prepare("SELECT id, vector, text, path FROM embeddings")?;
+            let mut stmt = conn.prepare(&format!("SELECT {EMBEDDING_COLUMNS} FROM embeddings"))?;
             let mut rows = stmt.query([])?;
             let mut embeddings = Vec::new();
+            while let Some(row) = rows.next()? {
+                embeddings.push(Self::read_embedding(row)?);
+            }
+            Ok(embeddings)
+        }).await?
+    }
+
+    /// Chunk text only, for keyword search when the query can't be embedded.
+    pub async fn get_all_texts(&self) -> Result<Vec<String>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut stmt = conn.prepare("SELECT text FROM embeddings")?;
+            let mut rows = stmt.query([])?;
+            let mut texts = Vec::new();
+            while let Some(row) = rows.next()? {
+                texts.push(Self::decode_text(row.get_ref(0)?)?);
+            }
+            Ok(texts)
+        }).await?
+    }
+
+    /// Ids and vectors only; skips decompressing chunk text.
+    pub async fn get_all_vectors(&self) -> Result<Vec<(String, Vec<f32>)>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut stmt = conn.prepare("SELECT id, vector FROM embeddings ORDER BY rowid")?;
+            let mut rows = stmt.query([])?;
+            let mut vectors = Vec::new();
             while let Some(row) = rows.next()? {
                 let id: String = row.get(0)?;
-                let vector_bytes: Vec<u8> = row.get(1)?;
-                let text: String = row.get(2)?;
-                let path: String = row.get(3)?;
-                let vector: Vec<f32> = bincode::deserialize(&vector_bytes)?;
-                embeddings.push(Embedding {
-                    id,
-                    vector,
-                    text,
-                    path,
-                });
+                let bytes: Vec<u8> = row.get(1)?;
+                vectors.push((id, bincode::deserialize(&bytes)?));
+            }
+            Ok(vectors)
+        }).await?
+    }
+
+    /// Load only the quantized vectors. Rows stored without one (older
+    /// indexes, or a different quantization setting) are quantized on the fly
+    /// and written back so the next scan is cheap.
+    pub async fn get_quantized_embeddings(&self) -> Result<Vec<QuantizedEntry>> {
+        let conn = Arc::clone(&self.conn);
+        let quantization = self.quantization;
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut entries = Vec::new();
+            let mut backfill = Vec::new();
+            {
+                let mut stmt = conn.prepare("SELECT id, qvector FROM embeddings")?;
+                let mut rows = stmt.query([])?;
+                while let Some(row) = rows.next()? {
+                    let id: String = row.get(0)?;
+                    let stored: Option<Vec<u8>> = row.get(1)?;
+                    let decoded = stored
+                        .and_then(|bytes| bincode::deserialize::<QuantizedVector>(&bytes).ok())
+                        .filter(|q| Self::matches_quantization(q, quantization));
+                    match decoded {
+                        Some(vector) => entries.push(QuantizedEntry { id, vector }),
+                        None => backfill.push(id),
+                    }
+                }
+            }
+
+            if !backfill.is_empty() {
+                let tx = conn.unchecked_transaction()?;
+                {
+                    let mut select = tx.prepare("SELECT vector FROM embeddings WHERE id = ?1")?;
+                    let mut update =
+                        tx.prepare("UPDATE embeddings SET qvector = ?1 WHERE id = ?2")?;
+                    for id in backfill {
+                        let bytes: Vec<u8> = select.query_row([&id], |row| row.get(0))?;
+                        let vector: Vec<f32> = bincode::deserialize(&bytes)?;
+                        if let Some(q) = QuantizedVector::encode(&vector, quantization) {
+                            update.execute(params![bincode::serialize(&q)?, &id])?;
+                            entries.push(QuantizedEntry { id, vector: q });
+                        }
+                    }
+                }
+                tx.commit()?;
+            }
+            Ok(entries)
+        }).await?
+    }
+
+    fn matches_quantization(vector: &QuantizedVector, quantization: Quantization) -> bool {
+        matches!(
+            (vector, quantization),
+            (QuantizedVector::Int8 { .. }, Quantization::Int8)
+                | (QuantizedVector::Binary { .. }, Quantization::Binary)
+        )
+    }
+
+    pub async fn get_embeddings_by_ids(&self, ids: Vec<String>) -> Result<Vec<Embedding>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {EMBEDDING_COLUMNS} FROM embeddings WHERE id = ?1"
+            ))?;
+            let mut embeddings = Vec::with_capacity(ids.len());
+            for id in ids {
+                let mut rows = stmt.query([&id])?;
+                if let Some(row) = rows.next()? {
+                    embeddings.push(Self::read_embedding(row)?);
+                }
+            }
+            Ok(embeddings)
+        }).await?
+    }
+
+    /// Text of the chunks just before and after each `(path, start_offset)`
+    /// in the same file, for widening retrieved chunks to their surroundings.
+    pub async fn get_neighbor_chunks(
+        &self,
+        anchors: Vec<(String, usize)>,
+    ) -> Result<Vec<(Option<String>, Option<String>)>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut prev = conn.prepare(
+                "SELECT text FROM embeddings WHERE path = ?1 AND start_offset < ?2
+                 ORDER BY start_offset DESC LIMIT 1",
+            )?;
+            let mut next = conn.prepare(
+                "SELECT text FROM embeddings WHERE path = ?1 AND start_offset > ?2
+                 ORDER BY start_offset ASC LIMIT 1",
+            )?;
+            let mut neighbors = Vec::with_capacity(anchors.len());
+            for (path, offset) in anchors {
+                let lookup = |stmt: &mut rusqlite::Statement<'_>| -> Result<Option<String>> {
+                    let mut rows = stmt.query(params![&path, offset as i64])?;
+                    match rows.next()? {
+                        Some(row) => Ok(Some(Self::decode_text(row.get_ref(0)?)?)),
+                        None => Ok(None),
+                    }
+                };
+                let before = lookup(&mut prev)?;
+                let after = lookup(&mut next)?;
+                neighbors.push((before, after));
+            }
+            Ok(neighbors)
+        }).await?
+    }
+
+    pub async fn upsert_file_summary(
+        &self,
+        path: String,
+        summary: String,
+        vector: Vec<f32>,
+    ) -> Result<()> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            conn.execute(
+                "INSERT OR REPLACE INTO file_summaries (path, summary, vector) VALUES (?1, ?2, ?3)",
+                params![path, Self::compress_text(&summary)?, bincode::serialize(&vector)?],
+            )?;
+            Ok(())
+        }).await?
+    }
+
+    pub async fn has_file_summary(&self, path: String) -> Result<bool> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM file_summaries WHERE path = ?1",
+                [path],
+                |row| row.get(0),
+            )?;
+            Ok(count > 0)
+        }).await?
+    }
+
+    /// Vectors of the stored chunks of `path` by chunk hash, so the chunks
+    /// that didn't change when the file did aren't embedded again.
+    pub async fn get_chunk_vectors(&self, path: String) -> Result<HashMap<String, Vec<f32>>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut stmt = conn.prepare(
+                "SELECT chunk_hash, vector FROM embeddings WHERE path = ?1 AND chunk_hash != ''",
+            )?;
+            let mut rows = stmt.query([path])?;
+            let mut vectors = HashMap::new();
+            while let Some(row) = rows.next()? {
+                let hash: String = row.get(0)?;
+                let bytes: Vec<u8> = row.get(1)?;
+                vectors.insert(hash, bincode::deserialize(&bytes)?);
+            }
+            Ok(vectors)
+        }).await?
+    }
+
+    /// Paths and summaries of the summarized files, by path.
+    pub async fn get_file_summaries(&self) -> Result<Vec<(String, String)>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut stmt = conn.prepare("SELECT path, summary FROM file_summaries ORDER BY path")?;
+            let mut rows = stmt.query([])?;
+            let mut summaries = Vec::new();
+            while let Some(row) = rows.next()? {
+                let path: String = row.get(0)?;
+                summaries.push((path, Self::decode_text(row.get_ref(1)?)?));
+            }
+            Ok(summaries)
+        }).await?
+    }
+
+    /// Paths and summary vectors for the file-level retrieval stage.
+    pub async fn get_file_summary_vectors(&self) -> Result<Vec<(String, Vec<f32>)>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut stmt = conn.prepare("SELECT path, vector FROM file_summaries")?;
+            let mut rows = stmt.query([])?;
+            let mut vectors = Vec::new();
+            while let Some(row) = rows.next()? {
+                let path: String = row.get(0)?;
+                let bytes: Vec<u8> = row.get(1)?;
+                vectors.push((path, bincode::deserialize(&bytes)?));
+            }
+            Ok(vectors)
+        }).await?
+    }
+
+    /// Chunks of the given files, plus chunks of any file that has no
+    /// summary yet so a partially summarized index still finds everything.
+    pub async fn get_embeddings_in_files(&self, paths: Vec<String>) -> Result<Vec<Embedding>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let placeholders = vec!["?"; paths.len()].join(", ");
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {EMBEDDING_COLUMNS} FROM embeddings
+                 WHERE path IN ({placeholders})
+                    OR path NOT IN (SELECT path FROM file_summaries)"
+            ))?;
+            let mut rows = stmt.query(rusqlite::params_from_iter(paths.iter()))?;
+            let mut embeddings = Vec::new();
+            while let Some(row) = rows.next()? {
+                embeddings.push(Self::read_embedding(row)?);
             }
             Ok(embeddings)
         }).await?
@@ -152,8 +609,261 @@ impl EmbeddingStorage {
         let conn = Arc::clone(&self.conn);
         task::spawn_blocking(move || {
             let conn = conn.blocking_lock();
-            conn.execute("DELETE FROM embeddings WHERE path = ?1", params![path])?;
+            let deleted = conn.execute("DELETE FROM embeddings WHERE path = ?1", params![&path])?;
+            conn.execute("DELETE FROM file_summaries WHERE path = ?1", params![&path])?;
+            if deleted > 0 {
+                Self::bump_generation(&conn)?;
+            }
+            Ok(())
+        }).await?
+    }
+
+    /// Forget `path` altogether: its chunks, summary and recorded hash.
+    pub async fn delete_file(&self, path: String) -> Result<()> {
+        self.delete_embeddings_for_path(path.clone()).await?;
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            conn.execute("DELETE FROM file_meta WHERE path = ?1", params![&path])?;
+            Ok(())
+        }).await?
+    }
+
+    /// Record a documentation source, replacing an earlier record of it.
+    pub async fn upsert_doc_source(&self, source: DocSource) -> Result<()> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            conn.execute(
+                "INSERT OR REPLACE INTO doc_sources (source, pages, fetched_at) VALUES (?1, ?2, ?3)",
+                params![&source.source, source.pages.join("\n"), source.fetched_at as i64],
+            )?;
+            Ok(())
+        }).await?
+    }
+
+    /// Every recorded documentation source, sorted by source.
+    pub async fn get_doc_sources(&self) -> Result<Vec<DocSource>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut stmt =
+                conn.prepare("SELECT source, pages, fetched_at FROM doc_sources ORDER BY source")?;
+            let mut rows = stmt.query([])?;
+            let mut sources = Vec::new();
+            while let Some(row) = rows.next()? {
+                let pages: String = row.get(1)?;
+                sources.push(DocSource {
+                    source: row.get(0)?,
+                    pages: pages.lines().map(str::to_string).collect(),
+                    fetched_at: row.get::<_, i64>(2)? as u64,
+                });
+            }
+            Ok(sources)
+        }).await?
+    }
+
+    pub async fn delete_doc_source(&self, source: String) -> Result<()> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            conn.execute("DELETE FROM doc_sources WHERE source = ?1", params![&source])?;
+            Ok(())
+        }).await?
+    }
+
+    /// Write a consistent copy of the whole database (including any
+    /// uncommitted WAL pages) to `dest`.
+    pub async fn snapshot_to(&self, dest: PathBuf) -> Result<()> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            if dest.exists() {
+                std::fs::remove_file(&dest)?;
+            }
+            conn.execute("VACUUM INTO ?1", [dest.to_string_lossy()])?;
+            Ok(())
+        }).await?
+    }
+
+    /// Store a memory, replacing one with the same id. Memories live beside
+    /// the code index but are never part of its search.
+    pub async fn insert_memory(&self, memory: Memory) -> Result<()> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || -> Result<()> {
+            let conn = conn.blocking_lock();
+            conn.execute(
+                "INSERT OR REPLACE INTO memories (id, kind, text, vector, created_at) VALUES (?, ?, ?, ?, ?)",
+                params![
+                    &memory.id,
+                    &memory.kind,
+                    Self::compress_text(&memory.text)?,
+                    bincode::serialize(&memory.vector)?,
+                    memory.created_at as i64
+                ],
+            )?;
+            Ok(())
+        }).await?
+    }
+
+    /// Every stored memory, oldest first.
+    pub async fn get_memories(&self) -> Result<Vec<Memory>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut stmt = conn.prepare(
+                "SELECT id, kind, text, vector, created_at FROM memories ORDER BY created_at, rowid",
+            )?;
+            let mut rows = stmt.query([])?;
+            let mut memories = Vec::new();
+            while let Some(row) = rows.next()? {
+                let vector_bytes: Vec<u8> = row.get(3)?;
+                memories.push(Memory {
+                    id: row.get(0)?,
+                    kind: row.get(1)?,
+                    text: Self::decode_text(row.get_ref(2)?)?,
+                    vector: bincode::deserialize(&vector_bytes)?,
+                    created_at: row.get::<_, i64>(4)? as u64,
+                });
+            }
+            Ok(memories)
+        }).await?
+    }
+
+    /// Delete every memory, vacuuming so none is left in free pages.
+    pub async fn clear_memories(&self) -> Result<()> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            conn.blocking_lock()
+                .execute_batch("DELETE FROM memories; VACUUM;")?;
             Ok(())
         }).await?
     }
+
+    /// Add the memories of the database at `other`, keeping this one's on
+    /// id clashes.
+    pub async fn import_memories_from(&self, other: PathBuf) -> Result<()> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            conn.execute("ATTACH DATABASE ?1 AS other", [other.to_string_lossy()])?;
+            let copied = conn.execute(
+                "INSERT OR IGNORE INTO memories SELECT id, kind, text, vector, created_at FROM other.memories",
+                [],
+            );
+            conn.execute("DETACH DATABASE other", [])?;
+            copied?;
+            Ok(())
+        }).await?
+    }
+
+    /// Dimensions of the stored vectors, if any are stored.
+    pub async fn vector_dims(&self) -> Result<Option<usize>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut stmt = conn.prepare("SELECT vector FROM embeddings LIMIT 1")?;
+            let mut rows = stmt.query([])?;
+            match rows.next()? {
+                Some(row) => {
+                    let bytes: Vec<u8> = row.get(0)?;
+                    let vector: Vec<f32> = bincode::deserialize(&bytes)?;
+                    Ok(Some(vector.len()))
+                }
+                None => Ok(None),
+            }
+        }).await?
+    }
+
+    pub async fn stats(&self) -> Result<StorageStats> {
+        let conn = Arc::clone(&self.conn);
+        let db_path = self.db_path.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let (chunks, stored, raw): (i64, i64, i64) = conn.query_row(
+                "SELECT COUNT(*),
+                        COALESCE(SUM(length(CAST(text AS BLOB))), 0),
+                        COALESCE(SUM(CASE WHEN typeof(text) = 'blob' THEN text_len
+                                          ELSE length(CAST(text AS BLOB)) END), 0)
+                 FROM embeddings",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?;
+            let files: i64 =
+                conn.query_row("SELECT COUNT(*) FROM file_meta", [], |row| row.get(0))?;
+            let db_file_bytes = std::fs::metadata(&db_path).map(|m| m.len()).unwrap_or(0);
+            Ok(StorageStats {
+                chunks: chunks as usize,
+                files: files as usize,
+                stored_text_bytes: stored as u64,
+                raw_text_bytes: raw as u64,
+                db_file_bytes,
+            })
+        }).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedding(id: &str, text: &str) -> Embedding {
+        Embedding {
+            id: id.to_string(),
+            vector: vec![0.1, 0.2],
+            text: text.to_string(),
+            path: "src/main.rs".to_string(),
+            start_offset: 0,
+            language: "rust".to_string(),
+            ordinal: 0,
+            chunk_hash: String::new(),
+            cell: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn chunk_text_round_trips_through_compressed_and_legacy_rows() {
+        let dir = std::env::temp_dir().join(format!("vibe_cli_embedding_storage_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let storage = EmbeddingStorage::new(dir.join("embeddings.db")).await.unwrap();
+        let long = "fn main() {\n    println!(\"hello\");\n}\n".repeat(40);
+        storage
+            .insert_embeddings(vec![embedding("a", &long), embedding("b", "short")])
+            .await
+            .unwrap();
+        // Written before chunk text was compressed: plain TEXT, no `text_len`.
+        storage
+            .conn
+            .lock()
+            .await
+            .execute(
+                "INSERT INTO embeddings (id, vector, text, path) VALUES (?, ?, ?, ?)",
+                params!["legacy", bincode::serialize(&vec![0.3f32, 0.4]).unwrap(), "plain old text", "README.md"],
+            )
+            .unwrap();
+
+        let mut texts: Vec<(String, String)> =
+            storage.get_all_embeddings().await.unwrap().into_iter().map(|e| (e.id, e.text)).collect();
+        texts.sort();
+        assert_eq!(
+            texts,
+            [
+                ("a".to_string(), long.clone()),
+                ("b".to_string(), "short".to_string()),
+                ("legacy".to_string(), "plain old text".to_string()),
+            ]
+        );
+
+        let compressed: u64 = [long.as_str(), "short"]
+            .iter()
+            .map(|text| EmbeddingStorage::compress_text(text).unwrap().len() as u64)
+            .sum();
+        let stats = storage.stats().await.unwrap();
+        assert_eq!(stats.chunks, 3);
+        assert_eq!(stats.raw_text_bytes, (long.len() + "short".len() + "plain old text".len()) as u64);
+        assert_eq!(stats.stored_text_bytes, compressed + "plain old text".len() as u64);
+        assert!(stats.saved_bytes() > 0 && stats.compression_ratio() > 1.0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -1,16 +1,111 @@
+use crate::config::IndexSettings;
+use crate::prompt_report;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::{WalkBuilder, WalkState};
 use md5;
 use memmap2::Mmap;
 use rayon::prelude::*;
 use shared::types::Result;
-use shared::utils::is_supported_file;
+use shared::utils::{detect_language, is_supported_file, shebang_language};
 use std::collections::HashSet;
 use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
+/// Files up to this size are chunked from a decoded copy; larger ones are
+/// chunked straight from the mapped file, a window at a time.
+const STREAM_THRESHOLD: u64 = 2 * 1024 * 1024;
+/// Largest chunk the streaming chunker cuts.
+const STREAM_CHUNK_SIZE: usize = 2000;
+/// The streaming chunker looks for a paragraph or line break after this
+/// many bytes of a window.
+const STREAM_MIN_CHUNK_SIZE: usize = 500;
+/// How much of a file is looked at to tell whether it is worth indexing.
+const SNIFF_BYTES: usize = 64 * 1024;
+/// A line this long only comes out of a minifier or a generator.
+const MAX_LINE_BYTES: usize = 4096;
+/// Bits per byte above which content is compressed, encrypted or otherwise
+/// binary; source code sits around 4.5 to 5, base64 at 6.
+const MAX_ENTROPY: f64 = 7.0;
+/// Lockfiles are long and say little about the code; the versions they pin
+/// are indexed separately as a summary.
+const LOCKFILE_NAMES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "Pipfile.lock",
+    "composer.lock",
+    "Gemfile.lock",
+    "go.sum",
+];
+
+#[derive(Clone)]
 pub struct FileScanner {
     root_path: PathBuf,
-    ignored_dirs: HashSet<String>,
+    ignored_dirs: Arc<HashSet<String>>,
     max_file_bytes: u64,
+    walk: IndexSettings,
+    force_include: GlobSet,
+}
+
+/// Why a file with a supported extension still isn't worth indexing:
+/// a lockfile, binary content (NUL bytes or near-random bytes), or a
+/// minified bundle or sourcemap (very long lines). Judged from the start of
+/// the file.
+fn unindexable(path: &Path, bytes: &[u8]) -> Option<&'static str> {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    if LOCKFILE_NAMES.contains(&name) {
+        return Some("lockfile");
+    }
+    let sample = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    if sample.contains(&0) {
+        return Some("binary (NUL bytes)");
+    }
+    // A line cut off by the end of the sample counts too.
+    if sample.split(|&b| b == b'\n').any(|line| line.len() > MAX_LINE_BYTES) {
+        return Some("minified (very long lines)");
+    }
+    if sample.len() >= 1024 && entropy(sample) > MAX_ENTROPY {
+        return Some("binary (high entropy)");
+    }
+    None
+}
+
+/// Whether `path` is an extensionless script with a `#!` line naming an
+/// interpreter we know, such as `bin/deploy` starting `#!/bin/bash`.
+fn is_script(path: &Path) -> bool {
+    use std::io::Read;
+
+    if path.extension().is_some() {
+        return false;
+    }
+    let mut head = [0u8; 256];
+    let Ok(read) = File::open(path).and_then(|mut file| file.read(&mut head)) else {
+        return false;
+    };
+    let head = String::from_utf8_lossy(&head[..read]);
+    head.lines().next().and_then(shebang_language).is_some()
+}
+
+/// Shannon entropy of `bytes`, in bits per byte.
+fn entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &b in bytes {
+        counts[b as usize] += 1;
+    }
+    let len = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
 }
 
 impl FileScanner {
@@ -38,12 +133,49 @@ impl FileScanner {
             ]
             .into_iter()
             .map(String::from)
-            .collect(),
-            // Cap per-file scanning to keep indexing responsive; adjust if needed.
-            max_file_bytes: 2 * 1024 * 1024,
+            .collect::<HashSet<_>>()
+            .into(),
+            // Cap per-file scanning to keep indexing responsive.
+            max_file_bytes: 32 * 1024 * 1024,
+            walk: IndexSettings::default(),
+            force_include: GlobSet::empty(),
         }
     }
 
+    /// Walk the project with `walk`'s symlink policy, limits, file size
+    /// cap, changes to the ignored directories and forced includes.
+    pub fn with_walk(mut self, walk: IndexSettings) -> Self {
+        let mut force_include = GlobSetBuilder::new();
+        for pattern in &walk.force_include {
+            match Glob::new(pattern) {
+                Ok(glob) => {
+                    force_include.add(glob);
+                }
+                Err(e) => eprintln!("Ignoring force_include pattern `{}`: {}", pattern, e),
+            }
+        }
+        self.force_include = force_include.build().unwrap_or_else(|_| GlobSet::empty());
+        let mut ignored = (*self.ignored_dirs).clone();
+        ignored.extend(walk.ignored_dirs.iter().cloned());
+        for name in &walk.include_dirs {
+            ignored.remove(name);
+        }
+        self.ignored_dirs = Arc::new(ignored);
+        self.max_file_bytes = walk.max_file_bytes;
+        self.walk = walk;
+        self
+    }
+
+    /// Leave files larger than `bytes` out of scans.
+    pub fn with_max_file_bytes(mut self, bytes: u64) -> Self {
+        self.max_file_bytes = bytes;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root_path
+    }
+
     pub fn scan_files(&self) -> Result<Vec<FileScanResult>> {
         let files = self.collect_files()?;
         self.scan_paths(&files)
@@ -62,9 +194,70 @@ impl FileScanner {
         Ok(all_results)
     }
 
+    /// Scan `paths` in parallel, handing each result to `sink` as soon as
+    /// it is ready instead of collecting them all; stops early once `sink`
+    /// returns `false`.
+    pub fn scan_paths_with(&self, paths: &[PathBuf], sink: impl Fn(Result<FileScanResult>) -> bool + Sync) {
+        eprintln!("Scanning files with parallel processing...");
+        let _ = paths
+            .par_iter()
+            .try_for_each(|path| sink(self.load_and_chunk_file(path)).then_some(()));
+    }
+
+    /// The supported files under the root, found by a parallel walk that
+    /// skips the ignored directories, follows symlinks only when told to
+    /// (never around a loop), and stops at the configured depth and file
+    /// count. Sorted, so the order doesn't depend on the walk.
     pub fn collect_files(&self) -> Result<Vec<PathBuf>> {
-        let mut files = Vec::new();
-        self.collect_files_recursive(&self.root_path, &mut files)?;
+        // A missing root is an error rather than an empty project.
+        std::fs::read_dir(&self.root_path)?;
+        let ignored = Arc::clone(&self.ignored_dirs);
+        let mut walker = WalkBuilder::new(&self.root_path);
+        walker
+            .standard_filters(false)
+            .follow_links(self.walk.follow_symlinks)
+            .same_file_system(self.walk.same_file_system)
+            .max_depth((self.walk.max_depth > 0).then_some(self.walk.max_depth))
+            .filter_entry(move |entry| {
+                let dir = entry.file_type().is_some_and(|t| t.is_dir());
+                !(dir && entry.file_name().to_str().is_some_and(|name| ignored.contains(name)))
+            });
+
+        let max_files = match self.walk.max_files {
+            0 => usize::MAX,
+            max => max,
+        };
+        let files = Mutex::new(Vec::new());
+        let found = AtomicUsize::new(0);
+        walker.build_parallel().run(|| {
+            Box::new(|entry| {
+                // Unreadable entries and symlink loops are skipped.
+                let Ok(entry) = entry else {
+                    return WalkState::Continue;
+                };
+                let file = entry.file_type().is_some_and(|t| t.is_file());
+                if !file || !(is_supported_file(entry.path()) || is_script(entry.path())) {
+                    return WalkState::Continue;
+                }
+                if found.fetch_add(1, Ordering::Relaxed) >= max_files {
+                    return WalkState::Quit;
+                }
+                if let Ok(mut files) = files.lock() {
+                    files.push(entry.into_path());
+                }
+                WalkState::Continue
+            })
+        });
+
+        let mut files = files.into_inner().unwrap_or_default();
+        if found.load(Ordering::Relaxed) > max_files {
+            eprintln!(
+                "Stopped after {} files (index.max_files); the rest of {} isn't indexed",
+                max_files,
+                self.root_path.display()
+            );
+        }
+        files.sort();
         Ok(files)
     }
 
@@ -121,7 +314,8 @@ impl FileScanner {
 
         for entry in entries {
             let path = entry.path();
-            if path.is_dir() {
+            let link = entry.file_type().is_ok_and(|t| t.is_symlink());
+            if (self.walk.follow_symlinks || !link) && path.is_dir() {
                 if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
                     if self.ignored_dirs.contains(name) {
                         continue;
@@ -148,22 +342,16 @@ impl FileScanner {
         }
     }
 
-    fn collect_files_recursive(&self, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
-        for entry in std::fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                    if self.ignored_dirs.contains(name) {
-                        continue;
-                    }
-                }
-                self.collect_files_recursive(&path, files)?;
-            } else if is_supported_file(&path) {
-                files.push(path);
-            }
+    /// The hash indexing records for `path`, to tell whether the file
+    /// changed since; `None` when it can't be read or is over the size
+    /// limit (such files aren't indexed).
+    pub fn file_hash(&self, path: &Path) -> Option<String> {
+        if path.metadata().ok()?.len() > self.max_file_bytes {
+            return None;
         }
-        Ok(())
+        let file = File::open(path).ok()?;
+        let mmap = unsafe { Mmap::map(&file).ok()? };
+        Some(format!("{:x}", md5::compute(&mmap[..])))
     }
 
     fn load_and_chunk_file(&self, path: &Path) -> Result<FileScanResult> {
@@ -178,10 +366,31 @@ impl FileScanner {
         }
         let file = File::open(path)?;
         let mmap = unsafe { Mmap::map(&file)? };
-        // Lossy conversion ensures non-UTF8 bytes don't crash scanning.
-        let content = String::from_utf8_lossy(&mmap).into_owned();
-        let hash = format!("{:x}", md5::compute(content.as_bytes()));
-        let chunks = self.chunk_text(&content, path);
+        let relative = path.strip_prefix(&self.root_path).unwrap_or(path);
+        let notebook = path.extension().is_some_and(|ext| ext == "ipynb");
+        // Notebook outputs (embedded images) look binary; the cells are
+        // judged once extracted instead.
+        if !notebook && !self.force_include.is_match(relative) {
+            if let Some(reason) = unindexable(path, &mmap) {
+                if prompt_report::verbose() {
+                    eprintln!("Skipping {}: {}", path.display(), reason);
+                }
+                return Ok(FileScanResult {
+                    path: path.to_string_lossy().to_string(),
+                    hash: String::new(),
+                    chunks: Vec::new(),
+                });
+            }
+        }
+        let hash = format!("{:x}", md5::compute(&mmap[..]));
+        let chunks = if notebook {
+            self.chunk_notebook(&mmap, path)
+        } else if mmap.len() as u64 > STREAM_THRESHOLD {
+            self.chunk_bytes(&mmap, path)
+        } else {
+            // Lossy conversion ensures non-UTF8 bytes don't crash scanning.
+            self.chunk_text(&String::from_utf8_lossy(&mmap), path)
+        };
         Ok(FileScanResult {
             path: path.to_string_lossy().to_string(),
             hash,
@@ -189,6 +398,15 @@ impl FileScanner {
         })
     }
 
+    /// Chunks of a document that isn't a file on disk, such as a fetched
+    /// web page, labelled with its `location`.
+    pub fn chunk_document(&self, text: &str, location: &str) -> Vec<FileChunk> {
+        self.chunk_text(text, Path::new(location))
+    }
+
+    /// Paragraph-based chunks of `text`: consecutive paragraphs are
+    /// gathered until a chunk reaches the minimum size, without passing the
+    /// maximum. Each chunk is the exact slice of `text` at its offset.
     fn chunk_text(&self, text: &str, path: &Path) -> Vec<FileChunk> {
         const MAX_CHUNK_SIZE: usize = 2000;
         const MIN_CHUNK_SIZE: usize = 500;
@@ -196,64 +414,145 @@ impl FileScanner {
         let mut chunks = Vec::new();
         let mut seen_hashes = HashSet::new();
         let path_str = path.to_string_lossy().to_string();
+        let mut push = |start: usize, end: usize, chunks: &mut Vec<FileChunk>| {
+            let chunk = &text[start..end];
+            // Deduplicate identical chunks within the file
+            if seen_hashes.insert(md5::compute(chunk.as_bytes())) {
+                chunks.push(FileChunk {
+                    path: path_str.clone(),
+                    text: chunk.to_string(),
+                    start_offset: start,
+                    ..FileChunk::default()
+                });
+            }
+        };
 
-        // Split text into paragraphs (double newlines)
-        let paragraphs: Vec<&str> = text.split("\n\n").collect();
-        let mut current_chunk = String::new();
-        let mut start_offset = 0;
-
-        for paragraph in paragraphs {
-            if current_chunk.len() + paragraph.len() > MAX_CHUNK_SIZE && !current_chunk.is_empty() {
-                // Check deduplication
-                let hash = format!("{:x}", md5::compute(current_chunk.as_bytes()));
-                if seen_hashes.insert(hash) {
-                    chunks.push(FileChunk {
-                        path: path_str.clone(),
-                        text: current_chunk.clone(),
-                        start_offset,
-                    });
+        // The open chunk, as a byte range of `text`.
+        let mut current: Option<(usize, usize)> = None;
+        let mut paragraph_start = 0;
+        for paragraph in text.split("\n\n") {
+            let start = paragraph_start;
+            let end = start + paragraph.len();
+            // Past the paragraph and the "\n\n" that ended it.
+            paragraph_start = end + 2;
+            if let Some((chunk_start, chunk_end)) = current {
+                if chunk_end - chunk_start + paragraph.len() > MAX_CHUNK_SIZE {
+                    push(chunk_start, chunk_end, &mut chunks);
+                    current = None;
                 }
-                current_chunk.clear();
-                start_offset += paragraph.as_ptr() as usize - text.as_ptr() as usize;
             }
+            let chunk_start = match current {
+                Some((chunk_start, _)) => chunk_start,
+                // A chunk never starts with blank paragraphs.
+                None if paragraph.is_empty() => continue,
+                None => start,
+            };
+            if end - chunk_start >= MIN_CHUNK_SIZE {
+                push(chunk_start, end, &mut chunks);
+                current = None;
+            } else {
+                current = Some((chunk_start, end));
+            }
+        }
+        if let Some((chunk_start, chunk_end)) = current {
+            push(chunk_start, chunk_end, &mut chunks);
+        }
 
-            if !current_chunk.is_empty() {
-                current_chunk.push_str("\n\n");
+        // If no chunks, fallback to fixed size
+        let mut chunks = if chunks.is_empty() {
+            self.chunk_fixed_size_dedup(text, path)
+        } else {
+            chunks
+        };
+        let language = detect_language(path, text);
+        for (ordinal, chunk) in chunks.iter_mut().enumerate() {
+            chunk.language = language.to_string();
+            chunk.ordinal = ordinal;
+        }
+        number_lines(text.as_bytes(), &mut chunks);
+        chunks
+    }
+
+    /// Chunks of a Jupyter notebook's code and markdown cells, each cell
+    /// chunked on its own, labelled with its index and language. Outputs
+    /// are left out. Offsets are into the cells' sources joined by blank
+    /// lines, which keeps the chunks in order; an unreadable notebook has
+    /// none.
+    fn chunk_notebook(&self, bytes: &[u8], path: &Path) -> Vec<FileChunk> {
+        let Ok(notebook) = serde_json::from_slice::<serde_json::Value>(bytes) else {
+            if prompt_report::verbose() {
+                eprintln!("Skipping {}: not a valid notebook", path.display());
             }
-            current_chunk.push_str(paragraph);
-
-            if current_chunk.len() >= MIN_CHUNK_SIZE {
-                let hash = format!("{:x}", md5::compute(current_chunk.as_bytes()));
-                if seen_hashes.insert(hash) {
-                    chunks.push(FileChunk {
-                        path: path_str.clone(),
-                        text: current_chunk.clone(),
-                        start_offset,
-                    });
-                }
-                current_chunk.clear();
-                start_offset += paragraph.as_ptr() as usize - text.as_ptr() as usize + paragraph.len();
+            return Vec::new();
+        };
+        let metadata = &notebook["metadata"];
+        let code_language = metadata["kernelspec"]["language"]
+            .as_str()
+            .or(metadata["language_info"]["name"].as_str())
+            .unwrap_or("python")
+            .to_lowercase();
+        let cells = notebook["cells"].as_array().map(Vec::as_slice).unwrap_or_default();
+
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        for (index, cell) in cells.iter().enumerate() {
+            let language = match cell["cell_type"].as_str() {
+                Some("code") => code_language.as_str(),
+                Some("markdown") => "markdown",
+                _ => continue,
+            };
+            // The source is one string or a list of lines.
+            let source = match &cell["source"] {
+                serde_json::Value::String(text) => text.clone(),
+                serde_json::Value::Array(lines) => lines.iter().filter_map(|l| l.as_str()).collect(),
+                _ => continue,
+            };
+            if source.trim().is_empty() {
+                continue;
             }
+            for mut chunk in self.chunk_text(&source, path) {
+                chunk.start_offset += offset;
+                chunk.language = language.to_string();
+                chunk.cell = Some(index);
+                chunks.push(chunk);
+            }
+            offset += source.len() + 2;
+        }
+        for (ordinal, chunk) in chunks.iter_mut().enumerate() {
+            chunk.ordinal = ordinal;
         }
+        chunks
+    }
 
-        // Add remaining chunk
-        if !current_chunk.is_empty() {
-            let hash = format!("{:x}", md5::compute(current_chunk.as_bytes()));
-            if seen_hashes.insert(hash) {
+    /// Chunks of a large file read straight from `bytes` (the mapped file),
+    /// so only one window at a time is decoded. Windows end at a paragraph
+    /// break, else a line break, else a character boundary, and offsets are
+    /// byte offsets in the file.
+    pub fn chunk_bytes(&self, bytes: &[u8], path: &Path) -> Vec<FileChunk> {
+        let mut chunks = Vec::new();
+        let mut seen_hashes = HashSet::new();
+        let path_str = path.to_string_lossy().to_string();
+        let head = String::from_utf8_lossy(&bytes[..bytes.len().min(256)]);
+        let language = detect_language(path, &head);
+        let mut start = 0;
+        while start < bytes.len() {
+            let end = window_end(bytes, start);
+            let text = String::from_utf8_lossy(&bytes[start..end]);
+            let trimmed = text.trim();
+            if !trimmed.is_empty() && seen_hashes.insert(md5::compute(trimmed.as_bytes())) {
                 chunks.push(FileChunk {
                     path: path_str.clone(),
-                    text: current_chunk,
-                    start_offset,
+                    text: text.trim_end().to_string(),
+                    start_offset: start,
+                    language: language.to_string(),
+                    ordinal: chunks.len(),
+                    ..FileChunk::default()
                 });
             }
+            start = end;
         }
-
-        // If no chunks, fallback to fixed size
-        if chunks.is_empty() {
-            self.chunk_fixed_size_dedup(text, path)
-        } else {
-            chunks
-        }
+        number_lines(bytes, &mut chunks);
+        chunks
     }
 
     fn chunk_fixed_size_dedup(&self, text: &str, path: &Path) -> Vec<FileChunk> {
@@ -280,6 +579,7 @@ impl FileScanner {
                     path: path_str.clone(),
                     text: chunk_text,
                     start_offset: start,
+                    ..FileChunk::default()
                 });
             }
 
@@ -296,11 +596,57 @@ impl FileScanner {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Set the line numbers of `chunks`, cut from `bytes` in offset order.
+fn number_lines(bytes: &[u8], chunks: &mut [FileChunk]) {
+    let mut line = 1;
+    let mut counted_to = 0;
+    for chunk in chunks {
+        line += bytes[counted_to..chunk.start_offset].iter().filter(|b| **b == b'\n').count();
+        counted_to = chunk.start_offset;
+        chunk.start_line = line;
+        chunk.end_line = line + chunk.text.trim_end_matches('\n').matches('\n').count();
+    }
+}
+
+/// Where the streaming window starting at `start` ends: after the last
+/// paragraph break, else line break, past [`STREAM_MIN_CHUNK_SIZE`], else at
+/// the last character boundary within [`STREAM_CHUNK_SIZE`].
+fn window_end(bytes: &[u8], start: usize) -> usize {
+    let limit = (start + STREAM_CHUNK_SIZE).min(bytes.len());
+    if limit == bytes.len() {
+        return limit;
+    }
+    let window = &bytes[start..limit];
+    let floor = STREAM_MIN_CHUNK_SIZE.min(window.len());
+    let paragraph = window.windows(2).rposition(|pair| pair == b"\n\n").map(|i| i + 2);
+    let line = window.iter().rposition(|b| *b == b'\n').map(|i| i + 1);
+    if let Some(cut) = paragraph.filter(|i| *i > floor).or(line.filter(|i| *i > floor)) {
+        return start + cut;
+    }
+    // No break in reach: cut before a UTF-8 continuation byte.
+    let mut end = limit;
+    while end > start + 1 && bytes[end] & 0xC0 == 0x80 {
+        end -= 1;
+    }
+    end
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct FileChunk {
     pub path: String,
     pub text: String,
     pub start_offset: usize,
+    /// From the file's extension; empty when unknown.
+    pub language: String,
+    /// Position of the chunk among its file's chunks, from 0.
+    pub ordinal: usize,
+    /// Lines of the file the chunk spans, from 1, inclusive; for a
+    /// notebook, lines of its cell.
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Index of the notebook cell the chunk comes from, from 0; `None`
+    /// outside notebooks.
+    pub cell: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
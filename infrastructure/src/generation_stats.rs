@@ -0,0 +1,118 @@
+//! Timing and token counts of each model call, from the metadata Ollama
+//! (`prompt_eval_count`, `eval_count`, `eval_duration`) or an
+//! OpenAI-compatible server (`usage`) sends with every response. Printed
+//! after each call when `show_stats` is on, and summed for `/stats` in chat.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+static SHOW_STATS: AtomicBool = AtomicBool::new(false);
+static TOTALS: Mutex<StatsTotals> = Mutex::new(StatsTotals {
+    calls: 0,
+    wall: Duration::ZERO,
+    prompt_tokens: 0,
+    eval_tokens: 0,
+    eval_time: Duration::ZERO,
+});
+
+/// What one model call cost. Token counts are `None` when the server
+/// didn't report them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GenerationStats {
+    pub wall: Duration,
+    pub prompt_tokens: Option<u64>,
+    pub eval_tokens: Option<u64>,
+    /// Time spent generating, as reported by the server.
+    pub eval_time: Option<Duration>,
+}
+
+impl GenerationStats {
+    /// Generated tokens per second, over the server's generation time when
+    /// known and the wall time otherwise.
+    pub fn tokens_per_sec(&self) -> Option<f64> {
+        let tokens = self.eval_tokens?;
+        let secs = self.eval_time.unwrap_or(self.wall).as_secs_f64();
+        (secs > 0.0).then(|| tokens as f64 / secs)
+    }
+
+    /// e.g. `2.31s, 412 prompt + 87 generated tokens, 38.1 tokens/s`.
+    pub fn format(&self) -> String {
+        let mut parts = vec![format!("{:.2}s", self.wall.as_secs_f64())];
+        match (self.prompt_tokens, self.eval_tokens) {
+            (Some(prompt), Some(eval)) => {
+                parts.push(format!("{} prompt + {} generated tokens", prompt, eval))
+            }
+            (None, Some(eval)) => parts.push(format!("{} generated tokens", eval)),
+            _ => parts.push("no token counts reported".to_string()),
+        }
+        if let Some(rate) = self.tokens_per_sec() {
+            parts.push(format!("{:.1} tokens/s", rate));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Sums over the model calls made so far in this process.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StatsTotals {
+    pub calls: u64,
+    pub wall: Duration,
+    pub prompt_tokens: u64,
+    pub eval_tokens: u64,
+    pub eval_time: Duration,
+}
+
+impl StatsTotals {
+    pub fn add(&mut self, stats: &GenerationStats) {
+        self.calls += 1;
+        self.wall += stats.wall;
+        self.prompt_tokens += stats.prompt_tokens.unwrap_or(0);
+        self.eval_tokens += stats.eval_tokens.unwrap_or(0);
+        self.eval_time += stats.eval_time.unwrap_or(stats.wall);
+    }
+
+    pub fn format(&self) -> String {
+        if self.calls == 0 {
+            return "No model calls yet.".to_string();
+        }
+        let mut text = format!(
+            "{} model call{}, {:.2}s in total ({:.2}s on average)\n{} prompt + {} generated tokens",
+            self.calls,
+            if self.calls == 1 { "" } else { "s" },
+            self.wall.as_secs_f64(),
+            self.wall.as_secs_f64() / self.calls as f64,
+            self.prompt_tokens,
+            self.eval_tokens
+        );
+        let secs = self.eval_time.as_secs_f64();
+        if self.eval_tokens > 0 && secs > 0.0 {
+            text.push_str(&format!(", {:.1} tokens/s", self.eval_tokens as f64 / secs));
+        }
+        text
+    }
+}
+
+/// Print the stats of every model call to stderr as it finishes.
+pub fn set_show_stats(enabled: bool) {
+    SHOW_STATS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn show_stats() -> bool {
+    SHOW_STATS.load(Ordering::Relaxed)
+}
+
+/// Add a finished call to the totals, and print it if enabled.
+pub fn record(stats: GenerationStats) {
+    TOTALS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .add(&stats);
+    if show_stats() {
+        eprintln!("[model: {}]", stats.format());
+    }
+}
+
+pub fn totals() -> StatsTotals {
+    *TOTALS.lock().unwrap_or_else(|e| e.into_inner())
+}
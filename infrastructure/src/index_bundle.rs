@@ -0,0 +1,211 @@
+//! Portable RAG index bundles (`vibe_cli rag export` / `rag import`), so a
+//! prebuilt index can be shared instead of re-embedding a large repository
+//! on every machine.
+//!
+//! A bundle is a zstd-compressed tar holding `manifest.json` and a snapshot
+//! of the embeddings database (chunks, vectors and file hashes).
+
+use super::embedding_storage::EmbeddingStorage;
+use super::vector_cache::VectorMatrixCache;
+use serde::{Deserialize, Serialize};
+use shared::cache::now_secs;
+use shared::types::Result;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "embeddings.db";
+/// Bumped when the bundle layout or database schema changes incompatibly.
+const BUNDLE_FORMAT: u32 = 1;
+const COMPRESSION_LEVEL: i32 = 9;
+
+/// What the vectors in an index are only comparable under: an index built
+/// with one embedding model is useless with another.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexFingerprint {
+    pub model: String,
+    pub dims: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format: u32,
+    pub fingerprint: IndexFingerprint,
+    pub vibe_version: String,
+    /// Seconds since the Unix epoch.
+    pub created_at: u64,
+    pub files: usize,
+    pub chunks: usize,
+}
+
+/// Snapshot the index at `db_path` into a bundle at `out`.
+pub async fn export(db_path: &Path, model: &str, out: &Path) -> Result<BundleManifest> {
+    let storage = EmbeddingStorage::new(db_path).await?;
+    let stats = storage.stats().await?;
+    if stats.chunks == 0 {
+        return Err(anyhow::anyhow!(
+            "nothing to export: the index at {} is empty",
+            db_path.display()
+        ));
+    }
+    let manifest = BundleManifest {
+        format: BUNDLE_FORMAT,
+        fingerprint: IndexFingerprint {
+            model: model.to_string(),
+            dims: storage.vector_dims().await?,
+        },
+        vibe_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: now_secs(),
+        files: stats.files,
+        chunks: stats.chunks,
+    };
+
+    let snapshot = staging_path(out, "snapshot");
+    storage.snapshot_to(snapshot.clone()).await?;
+    // Memories of earlier sessions are personal; bundles only share the index.
+    EmbeddingStorage::new(&snapshot).await?.clear_memories().await?;
+    let out = out.to_path_buf();
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    let database = snapshot.clone();
+    let result = tokio::task::spawn_blocking(move || -> Result<()> {
+        let encoder = zstd::Encoder::new(File::create(&out)?, COMPRESSION_LEVEL)?;
+        let mut archive = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest_json.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive.append_data(&mut header, MANIFEST_ENTRY, manifest_json.as_slice())?;
+        archive.append_path_with_name(&database, DATABASE_ENTRY)?;
+        archive.into_inner()?.finish()?;
+        Ok(())
+    })
+    .await?;
+    let _ = std::fs::remove_file(&snapshot);
+    result.map(|_| manifest)
+}
+
+/// Replace the index at `db_path` with the one in `bundle`. Refuses a bundle
+/// built with a different model unless `force` is set.
+pub async fn import(
+    bundle: &Path,
+    db_path: &Path,
+    model: &str,
+    force: bool,
+) -> Result<BundleManifest> {
+    let staging = staging_path(db_path, "import");
+    let bundle = bundle.to_path_buf();
+    let staged = staging.clone();
+    let manifest = tokio::task::spawn_blocking(move || unpack(&bundle, &staged)).await?;
+    let manifest = match manifest {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            let _ = std::fs::remove_file(&staging);
+            return Err(e);
+        }
+    };
+
+    if let Err(e) = check_compatible(&manifest, model, force) {
+        let _ = std::fs::remove_file(&staging);
+        return Err(e);
+    }
+
+    // Opening the staged copy validates it and migrates older schemas.
+    let staged = match EmbeddingStorage::new(&staging).await {
+        Ok(staged) => staged,
+        Err(e) => {
+            let _ = std::fs::remove_file(&staging);
+            return Err(anyhow::anyhow!("bundle database is unreadable: {}", e));
+        }
+    };
+    // The index is replaced, but memories of earlier sessions are kept.
+    if db_path.exists() {
+        EmbeddingStorage::new(db_path).await?;
+        if let Err(e) = staged.import_memories_from(db_path.to_path_buf()).await {
+            let _ = std::fs::remove_file(&staging);
+            return Err(e);
+        }
+    }
+    drop(staged);
+
+    for stale in [
+        sidecar(db_path, "-wal"),
+        sidecar(db_path, "-shm"),
+        VectorMatrixCache::sidecar_path(db_path),
+    ] {
+        let _ = std::fs::remove_file(stale);
+    }
+    for leftover in [sidecar(&staging, "-wal"), sidecar(&staging, "-shm")] {
+        let _ = std::fs::remove_file(leftover);
+    }
+    if let Some(parent) = db_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(&staging, db_path)?;
+    Ok(manifest)
+}
+
+fn check_compatible(manifest: &BundleManifest, model: &str, force: bool) -> Result<()> {
+    if manifest.format != BUNDLE_FORMAT {
+        return Err(anyhow::anyhow!(
+            "bundle format {} is not supported (expected {})",
+            manifest.format,
+            BUNDLE_FORMAT
+        ));
+    }
+    if manifest.fingerprint.model != model && !force {
+        return Err(anyhow::anyhow!(
+            "bundle was embedded with model '{}' but the current model is '{}'; its vectors would not match your queries (use --force to import anyway)",
+            manifest.fingerprint.model,
+            model
+        ));
+    }
+    Ok(())
+}
+
+/// Read the manifest and write the database entry to `staging`. Entries are
+/// matched by name and copied to a fixed path, never unpacked by their own
+/// path, so a crafted archive cannot write elsewhere.
+fn unpack(bundle: &Path, staging: &Path) -> Result<BundleManifest> {
+    let decoder = zstd::Decoder::new(File::open(bundle)?)?;
+    let mut archive = tar::Archive::new(decoder);
+    let mut manifest = None;
+    let mut has_database = false;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        match name.as_str() {
+            MANIFEST_ENTRY => {
+                let mut json = String::new();
+                entry.read_to_string(&mut json)?;
+                manifest = Some(serde_json::from_str::<BundleManifest>(&json)?);
+            }
+            DATABASE_ENTRY => {
+                let mut file = File::create(staging)?;
+                std::io::copy(&mut entry, &mut file)?;
+                has_database = true;
+            }
+            _ => {}
+        }
+    }
+    let manifest =
+        manifest.ok_or_else(|| anyhow::anyhow!("not an index bundle: no {}", MANIFEST_ENTRY))?;
+    if !has_database {
+        return Err(anyhow::anyhow!("not an index bundle: no {}", DATABASE_ENTRY));
+    }
+    Ok(manifest)
+}
+
+/// `<path>.<suffix>`, next to `path` so the final rename stays on one filesystem.
+fn staging_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{suffix}"));
+    PathBuf::from(name)
+}
+
+/// SQLite's `<db>-wal` / `<db>-shm` companions.
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
@@ -0,0 +1,165 @@
+//! Long commands run detached from the terminal (`--detach`): each job has
+//! a record, `<id>.json`, a log of its output, `<id>.log`, and once it ends
+//! its exit status, `<id>.exit`, in `<data_dir>/jobs`. The command keeps
+//! running after vibe_cli exits; `vibe_cli jobs` lists and follows them.
+
+use serde::{Deserialize, Serialize};
+use shared::paths;
+use shared::types::Result;
+use shared::utils::shell_quote;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub command: String,
+    /// The directory it runs in.
+    pub dir: PathBuf,
+    /// Process id of the shell running it.
+    pub pid: u32,
+    /// Seconds since the Unix epoch.
+    pub started: u64,
+    /// The model's summary of its output, once it ended and was reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Running,
+    /// Ended with this exit status.
+    Exited(i32),
+    /// Gone without an exit status, e.g. killed or lost in a reboot.
+    Lost,
+}
+
+impl JobStatus {
+    pub fn label(self) -> String {
+        match self {
+            Self::Running => "running".to_string(),
+            Self::Exited(0) => "done".to_string(),
+            Self::Exited(code) => format!("failed ({})", code),
+            Self::Lost => "lost".to_string(),
+        }
+    }
+}
+
+pub struct JobStore {
+    dir: PathBuf,
+}
+
+impl Default for JobStore {
+    fn default() -> Self {
+        Self::new(paths::data_dir().join("jobs"))
+    }
+}
+
+impl JobStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn record_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    pub fn log_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}.log", id))
+    }
+
+    /// Where the job's shell writes its exit status when the command ends.
+    pub fn exit_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}.exit", id))
+    }
+
+    /// One more than the highest id in use.
+    pub fn next_id(&self) -> Result<u64> {
+        Ok(self.list()?.iter().map(|job| job.id).max().unwrap_or(0) + 1)
+    }
+
+    pub fn save(&self, job: &Job) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.record_path(job.id), serde_json::to_string_pretty(job)?)?;
+        Ok(())
+    }
+
+    pub fn load(&self, id: u64) -> Result<Job> {
+        let text = std::fs::read_to_string(self.record_path(id))
+            .map_err(|_| anyhow::anyhow!("no job {}; see `vibe_cli jobs`", id))?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Every job, oldest first; records that can't be read are skipped.
+    pub fn list(&self) -> Result<Vec<Job>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut jobs: Vec<Job> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+            .filter_map(|text| serde_json::from_str(&text).ok())
+            .collect();
+        jobs.sort_by_key(|job| job.id);
+        Ok(jobs)
+    }
+
+    /// Remove a job's record, log and exit status.
+    pub fn remove(&self, id: u64) -> Result<()> {
+        for path in [self.record_path(id), self.log_path(id), self.exit_path(id)] {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn status(&self, job: &Job) -> JobStatus {
+        if let Ok(text) = std::fs::read_to_string(self.exit_path(job.id)) {
+            if let Ok(code) = text.trim().parse() {
+                return JobStatus::Exited(code);
+            }
+        }
+        if process_alive(job.pid) {
+            JobStatus::Running
+        } else {
+            JobStatus::Lost
+        }
+    }
+
+    /// The last `lines` lines of the job's log.
+    pub fn tail(&self, id: u64, lines: usize) -> Result<Vec<String>> {
+        let text = std::fs::read_to_string(self.log_path(id)).unwrap_or_default();
+        let all: Vec<&str> = text.lines().collect();
+        let start = all.len().saturating_sub(lines);
+        Ok(all[start..].iter().map(|line| line.to_string()).collect())
+    }
+}
+
+/// The script a job's shell runs: `cmd` in a shell of its own, so even an
+/// `exit` in it is recorded, then its exit status written to `exit_path`.
+pub fn job_script(cmd: &str, exit_path: &Path) -> String {
+    format!(
+        "bash -c {}\nprintf '%s\\n' \"$?\" > {}",
+        shell_quote(cmd),
+        shell_quote(&exit_path.to_string_lossy())
+    )
+}
+
+fn process_alive(pid: u32) -> bool {
+    Command::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
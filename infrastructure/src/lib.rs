@@ -1,6 +1,26 @@
+pub mod allowlist;
+pub mod audit_log;
+pub mod backoff;
+pub mod cassette;
 pub mod config;
+pub mod crate_docs;
+pub mod doc_source;
 pub mod embedder;
 pub mod embedding_storage;
 pub mod file_scanner;
+pub mod generation_stats;
+pub mod index_bundle;
+pub mod jobs;
+pub mod lockfiles;
+pub mod offline;
 pub mod ollama_client;
+pub mod policy;
+pub mod postprocess;
+pub mod prompt_report;
+pub mod quantization;
+pub mod scheduler;
 pub mod search;
+pub mod session_store;
+pub mod ssh_inventory;
+pub mod updater;
+pub mod vector_cache;
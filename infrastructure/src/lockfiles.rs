@@ -0,0 +1,211 @@
+//! Versions of a project's direct dependencies as its lockfiles pin them
+//! (Cargo.lock, package-lock.json, poetry.lock), so answers and commands
+//! use the versions actually installed rather than whatever the model
+//! remembers.
+
+use crate::crate_docs::rust_dependencies;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Characters of the versions summary; a project with hundreds of direct
+/// dependencies is cut.
+const MAX_SUMMARY_CHARS: usize = 4_000;
+/// Words that make a question about dependencies even without naming one.
+const VERSION_WORDS: [&str; 7] = [
+    "version", "versions", "dependency", "dependencies", "upgrade", "lockfile", "outdated",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedDependency {
+    pub name: String,
+    pub version: String,
+    /// File name of the lockfile, e.g. `Cargo.lock`.
+    pub lockfile: &'static str,
+}
+
+/// The direct dependencies of the project at `root` with the versions its
+/// lockfiles pin, grouped by lockfile.
+pub fn locked_dependencies(root: &Path) -> Vec<LockedDependency> {
+    let mut dependencies: Vec<LockedDependency> = rust_dependencies(root)
+        .into_iter()
+        .filter(|d| d.locked)
+        .map(|d| LockedDependency {
+            name: d.name,
+            version: d.version,
+            lockfile: "Cargo.lock",
+        })
+        .collect();
+    dependencies.extend(npm_dependencies(root));
+    dependencies.extend(poetry_dependencies(root));
+    dependencies
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+    serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()
+}
+
+fn read_toml(path: &Path) -> Option<toml::Table> {
+    std::fs::read_to_string(path).ok()?.parse().ok()
+}
+
+fn object_keys(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_object)
+        .map(|object| object.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// package-lock.json, lockfile version 1 to 3. Direct dependencies are
+/// those the lock's root package (or package.json) declares.
+fn npm_dependencies(root: &Path) -> Vec<LockedDependency> {
+    let Some(lock) = read_json(&root.join("package-lock.json")) else {
+        return Vec::new();
+    };
+    let manifest = lock
+        .get("packages")
+        .and_then(|packages| packages.get(""))
+        .cloned()
+        .or_else(|| read_json(&root.join("package.json")));
+    let mut names: Vec<String> = Vec::new();
+    for table in ["dependencies", "devDependencies", "optionalDependencies"] {
+        names.extend(object_keys(manifest.as_ref().and_then(|m| m.get(table))));
+    }
+    if names.is_empty() {
+        names = object_keys(lock.get("dependencies"));
+    }
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let version = lock
+                .get("packages")
+                .and_then(|p| p.get(format!("node_modules/{}", name)))
+                .or_else(|| lock.get("dependencies").and_then(|d| d.get(&name)))?
+                .get("version")?
+                .as_str()?
+                .to_string();
+            Some(LockedDependency {
+                name,
+                version,
+                lockfile: "package-lock.json",
+            })
+        })
+        .collect()
+}
+
+/// PEP 503 normalized name: `Typing_Extensions` is `typing-extensions`.
+fn python_name(name: &str) -> String {
+    name.trim().to_lowercase().replace(['_', '.'], "-")
+}
+
+/// Names declared in pyproject.toml, Poetry style or PEP 621; `None` when
+/// there is no pyproject.toml to tell direct dependencies apart.
+fn pyproject_names(root: &Path) -> Option<Vec<String>> {
+    let pyproject = read_toml(&root.join("pyproject.toml"))?;
+    let mut names = Vec::new();
+    if let Some(poetry) = pyproject.get("tool").and_then(|t| t.get("poetry")) {
+        let mut tables: Vec<&toml::Value> = poetry.get("dependencies").into_iter().collect();
+        tables.extend(poetry.get("dev-dependencies"));
+        if let Some(groups) = poetry.get("group").and_then(|g| g.as_table()) {
+            tables.extend(groups.values().filter_map(|group| group.get("dependencies")));
+        }
+        for table in tables.iter().filter_map(|t| t.as_table()) {
+            names.extend(table.keys().filter(|k| *k != "python").map(|k| python_name(k)));
+        }
+    }
+    let requirements = pyproject
+        .get("project")
+        .and_then(|p| p.get("dependencies"))
+        .and_then(|d| d.as_array());
+    for requirement in requirements.into_iter().flatten().filter_map(|r| r.as_str()) {
+        // PEP 508: the name comes first, e.g. `requests[socks]>=2.31`.
+        let end = requirement
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+            .unwrap_or(requirement.len());
+        names.push(python_name(&requirement[..end]));
+    }
+    Some(names)
+}
+
+fn poetry_dependencies(root: &Path) -> Vec<LockedDependency> {
+    let Some(lock) = read_toml(&root.join("poetry.lock")) else {
+        return Vec::new();
+    };
+    let direct = pyproject_names(root);
+    let mut versions: BTreeMap<String, String> = BTreeMap::new();
+    let packages = lock.get("package").and_then(|p| p.as_array());
+    for package in packages.into_iter().flatten() {
+        let (Some(name), Some(version)) = (
+            package.get("name").and_then(|n| n.as_str()),
+            package.get("version").and_then(|v| v.as_str()),
+        ) else {
+            continue;
+        };
+        let name = python_name(name);
+        if direct.as_ref().is_none_or(|direct| direct.contains(&name)) {
+            versions.insert(name, version.to_string());
+        }
+    }
+    versions
+        .into_iter()
+        .map(|(name, version)| LockedDependency {
+            name,
+            version,
+            lockfile: "poetry.lock",
+        })
+        .collect()
+}
+
+/// A context chunk listing `dependencies` by lockfile; empty when there
+/// are none.
+pub fn versions_summary(dependencies: &[LockedDependency]) -> String {
+    let mut by_lockfile: Vec<(&str, Vec<String>)> = Vec::new();
+    for dependency in dependencies {
+        let entry = format!("{} {}", dependency.name, dependency.version);
+        match by_lockfile.iter_mut().find(|(lockfile, _)| *lockfile == dependency.lockfile) {
+            Some((_, entries)) => entries.push(entry),
+            None => by_lockfile.push((dependency.lockfile, vec![entry])),
+        }
+    }
+    if by_lockfile.is_empty() {
+        return String::new();
+    }
+    let mut summary =
+        "DEPENDENCY VERSIONS (pinned by the project's lockfiles; use these versions):".to_string();
+    for (lockfile, entries) in by_lockfile {
+        summary.push_str(&format!("\n{}: {}", lockfile, entries.join(", ")));
+    }
+    match summary.char_indices().nth(MAX_SUMMARY_CHARS) {
+        Some((end, _)) => format!("{} ...", &summary[..end]),
+        None => summary,
+    }
+}
+
+fn normalize(word: &str) -> String {
+    word.to_lowercase().replace('_', "-")
+}
+
+/// The dependencies `text` names; `-` and `_` are interchangeable, and
+/// npm scopes are part of the name, as in `@tanstack/react-query`.
+pub fn mentioned<'a>(text: &str, dependencies: &'a [LockedDependency]) -> Vec<&'a LockedDependency> {
+    let words: Vec<String> = text
+        .split(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '@' | '/')))
+        .map(|word| normalize(word.trim_matches(['.', '-', '_', '/'])))
+        .collect();
+    dependencies
+        .iter()
+        .filter(|dependency| words.contains(&normalize(&dependency.name)))
+        .collect()
+}
+
+/// Whether `question` is about dependencies: it names one, or asks about
+/// versions, upgrades and the like.
+pub fn is_about_dependencies(question: &str, dependencies: &[LockedDependency]) -> bool {
+    let lower = question.to_lowercase();
+    lower
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| VERSION_WORDS.contains(&word))
+        || !mentioned(question, dependencies).is_empty()
+}
@@ -0,0 +1,31 @@
+//! `--offline`: never contact the model server or the network. Callers
+//! degrade to cached answers and the local index; anything that truly needs
+//! the model fails with a clear error instead of a connection timeout.
+
+use shared::types::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Set by `--offline` or `VIBE_OFFLINE=1` (for airgapped machines).
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+        || std::env::var("VIBE_OFFLINE")
+            .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+            .unwrap_or(false)
+}
+
+/// Error out if offline; `what` names the operation that needs the network.
+pub fn ensure_online(what: &str) -> Result<()> {
+    if is_offline() {
+        return Err(anyhow::anyhow!(
+            "offline mode: {} needs the model server or network, which is disabled",
+            what
+        ));
+    }
+    Ok(())
+}
@@ -1,8 +1,34 @@
-use reqwest::Client;
+use super::backoff::{self, Backoff};
+use super::config::Config;
+use super::cassette::{self, Cassette, Interaction};
+use super::generation_stats::{self, GenerationStats};
+use super::offline;
+use super::policy;
+use super::prompt_report;
+use super::scheduler::{Priority, RequestScheduler};
+use reqwest::{Client, RequestBuilder, Response};
 use serde::{Deserialize, Serialize};
+use shared::accessibility;
 use shared::types::Result;
 use std::env;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+const DEFAULT_MAX_IN_FLIGHT: usize = 4;
+
+/// One scheduler per process so every client instance shares the same limit.
+fn shared_scheduler() -> Arc<RequestScheduler> {
+    static SCHEDULER: OnceLock<Arc<RequestScheduler>> = OnceLock::new();
+    SCHEDULER
+        .get_or_init(|| {
+            let max_in_flight = env::var("OLLAMA_MAX_IN_FLIGHT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAX_IN_FLIGHT);
+            Arc::new(RequestScheduler::new(max_in_flight))
+        })
+        .clone()
+}
 
 #[derive(Serialize)]
 struct EmbeddingRequest {
@@ -32,6 +58,75 @@ struct ChatRequest {
 struct ChatResponse {
     message: Message,
     done: bool,
+    /// Metadata sent with the final chunk.
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    eval_count: Option<u64>,
+    /// Nanoseconds.
+    #[serde(default)]
+    eval_duration: Option<u64>,
+}
+
+/// `/v1/embeddings` request; the OpenAI API calls the text `input`.
+#[derive(Serialize)]
+struct OpenAiEmbeddingRequest {
+    model: String,
+    input: String,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingResponse {
+    data: Vec<EmbeddingResponse>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiChatResponse {
+    choices: Vec<OpenAiChoice>,
+    #[serde(default)]
+    usage: Option<OpenAiUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiUsage {
+    #[serde(default)]
+    prompt_tokens: Option<u64>,
+    #[serde(default)]
+    completion_tokens: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiChoice {
+    message: OpenAiMessage,
+}
+
+#[derive(Deserialize)]
+struct OpenAiMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// Wire protocol spoken by the model server (`api_style` in `config.toml`,
+/// or `API_STYLE`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiStyle {
+    /// Ollama's `/api/chat` and `/api/embeddings`.
+    #[default]
+    Ollama,
+    /// `/v1/chat/completions` and `/v1/embeddings`, as served by LM Studio,
+    /// vLLM, llamafile and other OpenAI-compatible servers.
+    OpenAi,
+}
+
+impl ApiStyle {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "ollama" => Some(ApiStyle::Ollama),
+            "openai" => Some(ApiStyle::OpenAi),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -39,29 +134,193 @@ pub struct OllamaClient {
     client: Arc<Client>,
     base_url: String,
     model: String,
+    scheduler: Arc<RequestScheduler>,
+    priority: Priority,
+    cassette: Option<Arc<Cassette>>,
+    backoff: Backoff,
+    api_style: ApiStyle,
+    /// Sent as a bearer token when set.
+    api_key: Option<String>,
+    /// Model that embeds text, when it isn't `model`.
+    embedding_model: Option<String>,
 }
 
 impl OllamaClient {
     pub fn new() -> Result<Self> {
-        let base_url =
-            env::var("OLLAMA_BASE_URL").unwrap_or_else(|_| "http://localhost:11434".to_string());
-        let model = env::var("BASE_MODEL").unwrap_or_else(|_| "qwen2.5:1.5b-instruct".to_string());
-        Ok(Self {
+        Ok(Self::from_config(&Config::load()))
+    }
+
+    /// Client for the server, model, API style and key in `config`.
+    pub fn from_config(config: &Config) -> Self {
+        Self::with_base_url(&config.ollama_base_url, &config.ollama_model)
+            .with_api_style(config.api_style)
+            .with_api_key(config.api_key.clone())
+    }
+
+    /// Client for an explicit endpoint, ignoring the environment.
+    pub fn with_base_url(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
             client: Arc::new(Client::new()),
-            base_url,
-            model,
-        })
+            base_url: base_url.into(),
+            model: model.into(),
+            scheduler: shared_scheduler(),
+            priority: Priority::Interactive,
+            cassette: cassette::active(),
+            backoff: Backoff::from_env(),
+            api_style: ApiStyle::Ollama,
+            api_key: None,
+            embedding_model: None,
+        }
+    }
+
+    /// Clone of this client that speaks `api_style` to the server.
+    pub fn with_api_style(&self, api_style: ApiStyle) -> Self {
+        Self {
+            api_style,
+            ..self.clone()
+        }
+    }
+
+    /// Clone of this client that authenticates with `api_key`.
+    pub fn with_api_key(&self, api_key: Option<String>) -> Self {
+        Self {
+            api_key: api_key.filter(|k| !k.trim().is_empty()),
+            ..self.clone()
+        }
+    }
+
+    /// Clone of this client that embeds text with `model` instead of the
+    /// chat model.
+    pub fn with_embedding_model(&self, model: Option<String>) -> Self {
+        Self {
+            embedding_model: model.filter(|m| !m.trim().is_empty()),
+            ..self.clone()
+        }
+    }
+
+    /// Model that [`generate_embedding`](Self::generate_embedding) uses.
+    pub fn embedding_model(&self) -> &str {
+        self.embedding_model.as_deref().unwrap_or(&self.model)
+    }
+
+    /// `path` on the server; a base URL given with a trailing `/v1` (as
+    /// OpenAI-compatible servers document it) is accepted either way.
+    fn endpoint(&self, path: &str) -> String {
+        let base = self.base_url.trim_end_matches('/');
+        match self.api_style {
+            ApiStyle::OpenAi => format!("{}/v1/{}", base.trim_end_matches("/v1"), path),
+            ApiStyle::Ollama => format!("{}/api/{}", base, path),
+        }
+    }
+
+    fn post(&self, url: &str) -> RequestBuilder {
+        let request = self.client.post(url);
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    /// Clone of this client whose requests are queued at the given priority.
+    pub fn with_priority(&self, priority: Priority) -> Self {
+        Self {
+            priority,
+            ..self.clone()
+        }
+    }
+
+    /// Clone of this client that records to or replays from `cassette`.
+    pub fn with_cassette(&self, cassette: Arc<Cassette>) -> Self {
+        Self {
+            cassette: Some(cassette),
+            ..self.clone()
+        }
+    }
+
+    /// Clone of this client that waits out busy responses per `backoff`.
+    pub fn with_backoff(&self, backoff: Backoff) -> Self {
+        Self {
+            backoff,
+            ..self.clone()
+        }
+    }
+
+    /// Send the request built by `request`, rebuilding and resending it while
+    /// the server answers 429/503. The scheduler permit is held throughout,
+    /// so other prompts stay queued instead of piling onto a busy server.
+    async fn send(&self, request: impl Fn() -> RequestBuilder) -> Result<Response> {
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let response = request().send().await?;
+            let status = response.status();
+            if !backoff::is_busy(status) {
+                return Ok(response);
+            }
+            let delay = backoff::retry_after(&response)
+                .unwrap_or_else(|| self.backoff.delay(attempt, backoff::jitter()));
+            if started.elapsed() + delay > self.backoff.max_wait {
+                return Err(anyhow::anyhow!(
+                    "model server still busy ({}) after waiting {}s; try again later or raise OLLAMA_MAX_WAIT_SECS",
+                    status,
+                    started.elapsed().as_secs()
+                ));
+            }
+            eprintln!(
+                "Model busy, retrying in {}s{}",
+                delay.as_secs_f64().ceil() as u64,
+                accessibility::glyph("…", "...")
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
     }
 
     pub async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
-        let url = format!("{}/api/embeddings", self.base_url);
-        let request = EmbeddingRequest {
-            model: self.model.clone(),
-            prompt: text.to_string(),
+        if let Some(cassette) = self.cassette.as_ref().filter(|c| c.is_replay()) {
+            return cassette.replay_embedding(text);
+        }
+        offline::ensure_online("embedding text")?;
+        policy::system().check_endpoint(&self.base_url)?;
+        let _permit = self.scheduler.acquire(self.priority).await;
+        let embedding = match self.api_style {
+            ApiStyle::Ollama => {
+                let url = self.endpoint("embeddings");
+                let request = EmbeddingRequest {
+                    model: self.embedding_model().to_string(),
+                    prompt: text.to_string(),
+                };
+                let response = self.send(|| self.post(&url).json(&request)).await?;
+                response.json::<EmbeddingResponse>().await?.embedding
+            }
+            ApiStyle::OpenAi => {
+                let url = self.endpoint("embeddings");
+                let request = OpenAiEmbeddingRequest {
+                    model: self.embedding_model().to_string(),
+                    input: text.to_string(),
+                };
+                let response = self.send(|| self.post(&url).json(&request)).await?;
+                let status = response.status();
+                let body = response.text().await?;
+                if !status.is_success() {
+                    return Err(api_error(self.api_style, &body));
+                }
+                serde_json::from_str::<OpenAiEmbeddingResponse>(&body)?
+                    .data
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("embeddings response has no data"))?
+                    .embedding
+            }
         };
-        let response = self.client.post(&url).json(&request).send().await?;
-        let embedding_response: EmbeddingResponse = response.json().await?;
-        Ok(embedding_response.embedding)
+        if let Some(cassette) = &self.cassette {
+            cassette.save(Interaction::Embedding {
+                model: self.embedding_model().to_string(),
+                prompt: text.to_string(),
+                embedding: embedding.clone(),
+            })?;
+        }
+        Ok(embedding)
     }
 
     pub async fn generate_response(&self, prompt: &str) -> Result<String> {
@@ -69,7 +328,21 @@ impl OllamaClient {
     }
 
     pub async fn generate_response_with_system(&self, prompt: &str, system: &str) -> Result<String> {
-        let url = format!("{}/api/chat", self.base_url);
+        if prompt_report::show_prompt() {
+            eprint!(
+                "{}",
+                prompt_report::format_prompt_report(&self.model, system, prompt)
+            );
+        }
+        if let Some(cassette) = self.cassette.as_ref().filter(|c| c.is_replay()) {
+            return cassette.replay_chat(system, prompt);
+        }
+        offline::ensure_online("generating a response")?;
+        policy::system().check_endpoint(&self.base_url)?;
+        let url = self.endpoint(match self.api_style {
+            ApiStyle::Ollama => "chat",
+            ApiStyle::OpenAi => "chat/completions",
+        });
         let mut messages = Vec::new();
         if !system.is_empty() {
             messages.push(Message {
@@ -86,24 +359,78 @@ impl OllamaClient {
             messages,
             stream: false,
         };
-        let response = self.client.post(&url).json(&request).send().await?;
+        let _permit = self.scheduler.acquire(self.priority).await;
+        let started = Instant::now();
+        let response = self.send(|| self.post(&url).json(&request)).await?;
         let status = response.status();
         let text = response.text().await?;
         if !status.is_success() {
-            return Err(anyhow::anyhow!("Ollama API error: {}", text));
+            return Err(api_error(self.api_style, &text));
         }
-        let mut full_content = String::new();
-        for line in text.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-            if let Ok(chat_resp) = serde_json::from_str::<ChatResponse>(line) {
-                full_content.push_str(&chat_resp.message.content);
-                if chat_resp.done {
-                    break;
-                }
+        let (full_content, mut stats) = match self.api_style {
+            ApiStyle::Ollama => ollama_chat_content(&text),
+            ApiStyle::OpenAi => {
+                let response = serde_json::from_str::<OpenAiChatResponse>(&text)?;
+                let stats = GenerationStats {
+                    prompt_tokens: response.usage.as_ref().and_then(|u| u.prompt_tokens),
+                    eval_tokens: response.usage.as_ref().and_then(|u| u.completion_tokens),
+                    ..GenerationStats::default()
+                };
+                let content = response
+                    .choices
+                    .into_iter()
+                    .next()
+                    .and_then(|choice| choice.message.content)
+                    .unwrap_or_default();
+                (content, stats)
             }
+        };
+        stats.wall = started.elapsed();
+        generation_stats::record(stats);
+        if let Some(cassette) = &self.cassette {
+            cassette.save(Interaction::Chat {
+                model: self.model.clone(),
+                system: system.to_string(),
+                prompt: prompt.to_string(),
+                response: full_content.clone(),
+            })?;
         }
         Ok(full_content)
     }
 }
+
+/// Content and token counts of an Ollama chat response, which may be
+/// newline-delimited stream chunks.
+fn ollama_chat_content(text: &str) -> (String, GenerationStats) {
+    let mut full_content = String::new();
+    let mut stats = GenerationStats::default();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Ok(chat_resp) = serde_json::from_str::<ChatResponse>(line) {
+            full_content.push_str(&chat_resp.message.content);
+            if chat_resp.done {
+                stats.prompt_tokens = chat_resp.prompt_eval_count;
+                stats.eval_tokens = chat_resp.eval_count;
+                stats.eval_time = chat_resp.eval_duration.map(Duration::from_nanos);
+                break;
+            }
+        }
+    }
+    (full_content, stats)
+}
+
+/// The server's error message: OpenAI-style `{"error": {"message": ...}}`
+/// or Ollama's `{"error": "..."}`, else the raw body.
+fn api_error(api_style: ApiStyle, body: &str) -> anyhow::Error {
+    let value: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+    let message = value["error"]["message"]
+        .as_str()
+        .or_else(|| value["error"].as_str())
+        .unwrap_or(body);
+    match api_style {
+        ApiStyle::Ollama => anyhow::anyhow!("Ollama API error: {}", message),
+        ApiStyle::OpenAi => anyhow::anyhow!("Model API error: {}", message),
+    }
+}
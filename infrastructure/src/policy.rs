@@ -0,0 +1,140 @@
+use regex::Regex;
+use serde::Deserialize;
+use shared::types::Result;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Administrator-installed policy. Deliberately not relocatable through the
+/// environment, otherwise users could point it at an empty file.
+pub const SYSTEM_POLICY_PATH: &str = "/etc/vibe_cli/policy.toml";
+
+/// Settings enforced on every user of the machine. Anything set here wins
+/// over the user config file and environment variables.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Force safe mode on (or off) regardless of `SAFE_MODE`.
+    #[serde(default)]
+    pub safe_mode: Option<bool>,
+    /// Force telemetry on or off regardless of `TELEMETRY`.
+    #[serde(default)]
+    pub telemetry: Option<bool>,
+    /// Require (or forbid) encrypting caches and the index at rest.
+    #[serde(default)]
+    pub encrypt_at_rest: Option<bool>,
+    /// Regexes; a command matching any of them is never run.
+    #[serde(default)]
+    pub blocked_commands: Vec<String>,
+    /// Hosts (`api.example.com`) or URL prefixes the client may not contact.
+    #[serde(default)]
+    pub forbidden_endpoints: Vec<String>,
+    #[serde(skip)]
+    blocked: Vec<Regex>,
+}
+
+impl Policy {
+    pub fn load_from(path: &Path) -> Result<Option<Self>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut policy: Policy = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("invalid policy file {}: {}", path.display(), e))?;
+        policy.blocked = policy
+            .blocked_commands
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    anyhow::anyhow!("invalid blocked command pattern '{}': {}", pattern, e)
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Some(policy))
+    }
+
+    /// Policy used when the system file exists but cannot be read or parsed:
+    /// fail closed rather than silently dropping the administrator's rules.
+    fn locked_down() -> Self {
+        Self {
+            safe_mode: Some(true),
+            telemetry: Some(false),
+            encrypt_at_rest: None,
+            blocked_commands: vec![".*".to_string()],
+            forbidden_endpoints: Vec::new(),
+            blocked: Regex::new(".*").into_iter().collect(),
+        }
+    }
+
+    /// Reason the command is forbidden, if it is.
+    pub fn blocked_reason(&self, command: &str) -> Option<String> {
+        self.blocked
+            .iter()
+            .find(|re| re.is_match(command))
+            .map(|re| format!("Blocked by administrator policy (matches '{}').", re.as_str()))
+    }
+
+    pub fn check_endpoint(&self, url: &str) -> Result<()> {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_lowercase));
+        for entry in &self.forbidden_endpoints {
+            let entry_lower = entry.trim().to_lowercase();
+            let forbidden = if entry_lower.contains("://") {
+                url.to_lowercase().starts_with(&entry_lower)
+            } else {
+                host.as_deref() == Some(entry_lower.as_str())
+            };
+            if forbidden {
+                return Err(anyhow::anyhow!(
+                    "endpoint {} is forbidden by administrator policy",
+                    url
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The system policy, loaded once per process.
+pub fn system() -> &'static Policy {
+    static POLICY: OnceLock<Policy> = OnceLock::new();
+    POLICY.get_or_init(|| {
+        let path = Path::new(SYSTEM_POLICY_PATH);
+        match Policy::load_from(path) {
+            Ok(policy) => policy.unwrap_or_default(),
+            Err(e) => {
+                eprintln!("{}; refusing to run commands until it is fixed", e);
+                Policy::locked_down()
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_blocks_commands_and_endpoints() {
+        let dir = std::env::temp_dir().join(format!("vibe_policy_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("policy.toml");
+        std::fs::write(
+            &path,
+            "safe_mode = true\nblocked_commands = ['\\bshutdown\\b']\nforbidden_endpoints = ['api.openai.com', 'http://10.0.0.5:11434']\n",
+        )
+        .unwrap();
+
+        let policy = Policy::load_from(&path).unwrap().unwrap();
+        assert_eq!(policy.safe_mode, Some(true));
+        assert!(policy.blocked_reason("sudo shutdown -h now").is_some());
+        assert!(policy.blocked_reason("ls -la").is_none());
+        assert!(policy.check_endpoint("https://api.openai.com/v1").is_err());
+        assert!(policy.check_endpoint("http://10.0.0.5:11434").is_err());
+        assert!(policy.check_endpoint("http://localhost:11434").is_ok());
+
+        assert!(Policy::load_from(&dir.join("missing.toml")).unwrap().is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
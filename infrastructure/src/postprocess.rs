@@ -0,0 +1,110 @@
+use regex::Regex;
+use serde::Deserialize;
+use shared::types::Result;
+
+/// A house-convention rewrite applied to every generated command, e.g.
+/// forcing `--dry-run` on rsync or replacing `apt` with `apt-get`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RewriteRule {
+    /// Shown to the user when the rule changes a command.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Regex matched against the command.
+    pub pattern: String,
+    /// Replacement for every match; `$1`-style group references work.
+    pub replace: String,
+    /// Skip the rule when this regex matches (e.g. the flag is already there).
+    #[serde(default)]
+    pub unless: Option<String>,
+}
+
+struct CompiledRule {
+    label: String,
+    pattern: Regex,
+    replace: String,
+    unless: Option<Regex>,
+}
+
+/// Ordered chain of rewrite rules; each rule sees the output of the previous one.
+pub struct PostProcessor {
+    rules: Vec<CompiledRule>,
+}
+
+impl PostProcessor {
+    pub fn new(rules: &[RewriteRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let pattern = Regex::new(&rule.pattern).map_err(|e| {
+                    anyhow::anyhow!("invalid postprocess pattern '{}': {}", rule.pattern, e)
+                })?;
+                let unless = match &rule.unless {
+                    Some(unless) => Some(Regex::new(unless).map_err(|e| {
+                        anyhow::anyhow!("invalid postprocess 'unless' pattern '{}': {}", unless, e)
+                    })?),
+                    None => None,
+                };
+                Ok(CompiledRule {
+                    label: rule.name.clone().unwrap_or_else(|| rule.pattern.clone()),
+                    pattern,
+                    replace: rule.replace.clone(),
+                    unless,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Rewritten command plus the labels of the rules that changed it.
+    pub fn apply(&self, command: &str) -> (String, Vec<String>) {
+        let mut current = command.to_string();
+        let mut applied = Vec::new();
+        for rule in &self.rules {
+            if rule.unless.as_ref().is_some_and(|u| u.is_match(&current)) {
+                continue;
+            }
+            let rewritten = rule.pattern.replace_all(&current, rule.replace.as_str());
+            if rewritten != current {
+                current = rewritten.into_owned();
+                applied.push(rule.label.clone());
+            }
+        }
+        (current, applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, replace: &str, unless: Option<&str>) -> RewriteRule {
+        RewriteRule {
+            name: None,
+            pattern: pattern.to_string(),
+            replace: replace.to_string(),
+            unless: unless.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn rules_chain_in_order_and_respect_unless() {
+        let processor = PostProcessor::new(&[
+            rule(r"\brsync ", "rsync --dry-run ", Some("--dry-run")),
+            rule(r"(^|[;&|]\s*)rm ", "${1}rm -i ", Some(r"\brm -i\b")),
+            rule(r"\bapt ", "apt-get ", None),
+        ])
+        .unwrap();
+
+        let (out, applied) = processor.apply("rm old.log && sudo apt install rsync");
+        assert_eq!(out, "rm -i old.log && sudo apt-get install rsync");
+        assert_eq!(applied.len(), 2);
+
+        let (out, applied) = processor.apply("rsync --dry-run -a src/ dst/");
+        assert_eq!(out, "rsync --dry-run -a src/ dst/");
+        assert!(applied.is_empty());
+    }
+}
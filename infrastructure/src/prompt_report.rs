@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SHOW_PROMPT: AtomicBool = AtomicBool::new(false);
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Print every final prompt to stderr before it is sent to the model.
+pub fn set_show_prompt(enabled: bool) {
+    SHOW_PROMPT.store(enabled, Ordering::Relaxed);
+}
+
+pub fn show_prompt() -> bool {
+    SHOW_PROMPT.load(Ordering::Relaxed)
+}
+
+/// Report to stderr which chunks made it into each RAG prompt, with their
+/// scores, and what was left out; also which files indexing skipped.
+pub fn set_verbose(enabled: bool) {
+    VERBOSE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Rough token count (about four bytes per token for English and code).
+/// Good enough to spot which part of a prompt is eating the context window.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Human-readable dump of a prompt: the system and user messages in full,
+/// followed by token estimates per message and per retrieved `FILE:` chunk.
+pub fn format_prompt_report(model: &str, system: &str, prompt: &str) -> String {
+    let mut out = format!("===== prompt for {} =====\n", model);
+    if !system.is_empty() {
+        out.push_str("--- system ---\n");
+        out.push_str(system);
+        out.push('\n');
+    }
+    out.push_str("--- user ---\n");
+    out.push_str(prompt);
+    out.push('\n');
+
+    out.push_str("--- tokens (estimated) ---\n");
+    if !system.is_empty() {
+        out.push_str(&format!("system: {}\n", estimate_tokens(system)));
+    }
+    out.push_str(&format!("user: {}\n", estimate_tokens(prompt)));
+    for (path, tokens) in chunk_tokens(prompt) {
+        out.push_str(&format!("  chunk {}: {}\n", path, tokens));
+    }
+    out.push_str(&format!(
+        "total: {}\n",
+        estimate_tokens(system) + estimate_tokens(prompt)
+    ));
+    out.push_str("==========================\n");
+    out
+}
+
+/// Split retrieved context on the `FILE: <path>` headers the RAG indexer
+/// prefixes every chunk with.
+fn chunk_tokens(prompt: &str) -> Vec<(String, usize)> {
+    let mut chunks: Vec<(String, usize)> = Vec::new();
+    for line in prompt.lines() {
+        if let Some(path) = line.strip_prefix("FILE: ") {
+            chunks.push((path.trim().to_string(), 0));
+        }
+        if let Some((_, bytes)) = chunks.last_mut() {
+            *bytes += line.len() + 1;
+        }
+    }
+    chunks
+        .into_iter()
+        .map(|(path, bytes)| (path, bytes.div_ceil(4)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_breaks_down_retrieved_chunks() {
+        let prompt = "Question\n\nContext:\nFILE: src/a.rs\nOFFSET: 0\nfn a() {}\nFILE: src/b.rs\nOFFSET: 0\nfn b() {}";
+        let report = format_prompt_report("m", "be brief", prompt);
+
+        assert!(report.contains("--- system ---\nbe brief\n"));
+        assert!(report.contains("  chunk src/a.rs: "));
+        assert!(report.contains("  chunk src/b.rs: "));
+        assert_eq!(chunk_tokens(prompt).len(), 2);
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+/// How stored vectors are compressed for the coarse search stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quantization {
+    #[default]
+    None,
+    /// One signed byte per dimension with a per-vector scale (4x smaller).
+    Int8,
+    /// One sign bit per dimension (32x smaller), compared by Hamming distance.
+    Binary,
+}
+
+impl Quantization {
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "int8" | "i8" => Self::Int8,
+            "binary" | "bit" | "bits" => Self::Binary,
+            _ => Self::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum QuantizedVector {
+    Int8 { scale: f32, norm: f32, values: Vec<i8> },
+    Binary { dims: usize, bits: Vec<u64> },
+}
+
+impl QuantizedVector {
+    pub fn encode(vector: &[f32], quantization: Quantization) -> Option<Self> {
+        match quantization {
+            Quantization::None => None,
+            Quantization::Int8 => {
+                let max_abs = vector.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+                let scale = if max_abs > 0.0 { max_abs / 127.0 } else { 1.0 };
+                let values: Vec<i8> = vector
+                    .iter()
+                    .map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8)
+                    .collect();
+                let norm = values
+                    .iter()
+                    .map(|&v| (v as f32 * scale).powi(2))
+                    .sum::<f32>()
+                    .sqrt();
+                Some(Self::Int8 {
+                    scale,
+                    norm,
+                    values,
+                })
+            }
+            Quantization::Binary => Some(Self::Binary {
+                dims: vector.len(),
+                bits: pack_sign_bits(vector),
+            }),
+        }
+    }
+}
+
+/// Query-side representation prepared once per search.
+pub struct QuantizedQuery<'a> {
+    vector: &'a [f32],
+    norm: f32,
+    bits: Vec<u64>,
+}
+
+impl<'a> QuantizedQuery<'a> {
+    pub fn new(vector: &'a [f32]) -> Self {
+        Self {
+            vector,
+            norm: vector.iter().map(|x| x * x).sum::<f32>().sqrt(),
+            bits: pack_sign_bits(vector),
+        }
+    }
+
+    /// Approximate similarity; only the relative order between candidates
+    /// matters since the top candidates are rescored exactly.
+    pub fn approx_score(&self, candidate: &QuantizedVector) -> f32 {
+        match candidate {
+            QuantizedVector::Int8 {
+                scale,
+                norm,
+                values,
+            } => {
+                // Asymmetric: full-precision query against the int8 codes.
+                let dot: f32 = self
+                    .vector
+                    .iter()
+                    .zip(values.iter())
+                    .map(|(q, &v)| q * v as f32)
+                    .sum();
+                let denom = self.norm * norm;
+                if denom == 0.0 {
+                    0.0
+                } else {
+                    dot * scale / denom
+                }
+            }
+            QuantizedVector::Binary { dims, bits } => {
+                let hamming: u32 = self
+                    .bits
+                    .iter()
+                    .zip(bits.iter())
+                    .map(|(a, b)| (a ^ b).count_ones())
+                    .sum();
+                if *dims == 0 {
+                    0.0
+                } else {
+                    1.0 - 2.0 * hamming as f32 / *dims as f32
+                }
+            }
+        }
+    }
+}
+
+fn pack_sign_bits(vector: &[f32]) -> Vec<u64> {
+    let mut bits = vec![0u64; vector.len().div_ceil(64)];
+    for (i, x) in vector.iter().enumerate() {
+        if *x > 0.0 {
+            bits[i / 64] |= 1 << (i % 64);
+        }
+    }
+    bits
+}
@@ -0,0 +1,158 @@
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Priority of a model request. Interactive requests (the user is waiting on
+/// the answer) always go ahead of queued background work such as indexing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Interactive,
+    Background,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    in_flight: usize,
+    waiting_interactive: usize,
+}
+
+/// Client-side concurrency guard in front of the single Ollama instance.
+/// Caps the number of in-flight requests and lets interactive requests jump
+/// the queue ahead of background embedding batches.
+pub struct RequestScheduler {
+    state: Mutex<SchedulerState>,
+    notify: Notify,
+    max_in_flight: usize,
+}
+
+/// Slot held for the duration of one request; releasing it wakes up waiters.
+pub struct SchedulerPermit {
+    scheduler: Arc<RequestScheduler>,
+}
+
+impl RequestScheduler {
+    pub fn new(max_in_flight: usize) -> Self {
+        Self {
+            state: Mutex::new(SchedulerState::default()),
+            notify: Notify::new(),
+            max_in_flight: max_in_flight.max(1),
+        }
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.lock_state().in_flight
+    }
+
+    pub async fn acquire(self: &Arc<Self>, priority: Priority) -> SchedulerPermit {
+        let _waiting = (priority == Priority::Interactive).then(|| {
+            self.lock_state().waiting_interactive += 1;
+            WaitingInteractive { scheduler: self }
+        });
+
+        loop {
+            // Register for wakeups before checking state so a release between
+            // the check and the await is not lost.
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            {
+                let mut state = self.lock_state();
+                let may_run = priority == Priority::Interactive || state.waiting_interactive == 0;
+                if state.in_flight < self.max_in_flight && may_run {
+                    state.in_flight += 1;
+                    return SchedulerPermit {
+                        scheduler: Arc::clone(self),
+                    };
+                }
+            }
+
+            notified.await;
+        }
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, SchedulerState> {
+        // The state is plain counters, so a poisoned lock is still usable.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// An interactive request counted as waiting until it is granted or its
+/// future is dropped, whichever comes first, so a cancelled request stops
+/// holding back background work.
+struct WaitingInteractive<'a> {
+    scheduler: &'a RequestScheduler,
+}
+
+impl Drop for WaitingInteractive<'_> {
+    fn drop(&mut self) {
+        {
+            let mut state = self.scheduler.lock_state();
+            state.waiting_interactive = state.waiting_interactive.saturating_sub(1);
+        }
+        self.scheduler.notify.notify_waiters();
+    }
+}
+
+impl Drop for SchedulerPermit {
+    fn drop(&mut self) {
+        {
+            let mut state = self.scheduler.lock_state();
+            state.in_flight = state.in_flight.saturating_sub(1);
+        }
+        self.scheduler.notify.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn interactive_requests_bypass_queued_background_work() {
+        let scheduler = Arc::new(RequestScheduler::new(1));
+        let running = scheduler.acquire(Priority::Background).await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let bg = {
+            let (scheduler, order) = (Arc::clone(&scheduler), Arc::clone(&order));
+            tokio::spawn(async move {
+                let _permit = scheduler.acquire(Priority::Background).await;
+                order.lock().unwrap().push("background");
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let fg = {
+            let (scheduler, order) = (Arc::clone(&scheduler), Arc::clone(&order));
+            tokio::spawn(async move {
+                let _permit = scheduler.acquire(Priority::Interactive).await;
+                order.lock().unwrap().push("interactive");
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        drop(running);
+        fg.await.unwrap();
+        bg.await.unwrap();
+        assert_eq!(*order.lock().unwrap(), vec!["interactive", "background"]);
+        assert_eq!(scheduler.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn a_cancelled_interactive_request_stops_holding_back_background_work() {
+        let scheduler = Arc::new(RequestScheduler::new(1));
+        let running = scheduler.acquire(Priority::Background).await;
+
+        let pending = tokio::time::timeout(Duration::from_millis(10), scheduler.acquire(Priority::Interactive)).await;
+        assert!(pending.is_err(), "the only slot is taken");
+        drop(running);
+
+        let background = tokio::time::timeout(Duration::from_secs(1), scheduler.acquire(Priority::Background)).await;
+        assert!(background.is_ok(), "the dropped interactive request no longer counts as waiting");
+        assert_eq!(scheduler.in_flight(), 1);
+    }
+}
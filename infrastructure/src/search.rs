@@ -1,5 +1,9 @@
+use super::embedding_storage::QuantizedEntry;
+use super::quantization::QuantizedQuery;
 use domain::models::Embedding;
-use std::cmp::Ordering;
+use shared::keywords;
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
 
 pub struct SearchEngine;
 
@@ -16,12 +20,24 @@ impl SearchEngine {
         embeddings: &[Embedding],
         top_k: usize,
     ) -> Vec<String> {
+        Self::find_scored_chunks(query_embedding, embeddings, top_k)
+            .into_iter()
+            .map(|(_, embedding)| embedding.text.clone())
+            .collect()
+    }
+
+    /// Best `top_k` chunks with their cosine similarity, highest first.
+    pub fn find_scored_chunks<'a>(
+        query_embedding: &[f32],
+        embeddings: &'a [Embedding],
+        top_k: usize,
+    ) -> Vec<(f32, &'a Embedding)> {
         use std::collections::BinaryHeap;
 
         #[derive(Debug)]
         struct Scored<'a> {
             score: f32,
-            text: &'a str,
+            embedding: &'a Embedding,
         }
 
         impl<'a> PartialEq for Scored<'a> {
@@ -32,34 +48,152 @@ impl SearchEngine {
         impl<'a> Eq for Scored<'a> {}
         impl<'a> PartialOrd for Scored<'a> {
             fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                self.score.partial_cmp(&other.score)
+                Some(self.cmp(other))
             }
         }
         impl<'a> Ord for Scored<'a> {
             fn cmp(&self, other: &Self) -> Ordering {
-                self.partial_cmp(other).unwrap_or(Ordering::Equal)
+                self.score.total_cmp(&other.score)
             }
         }
 
-        let mut heap: BinaryHeap<Scored> =
+        // Min-heap so popping discards the weakest match, not the best one.
+        let mut heap: BinaryHeap<Reverse<Scored>> =
             BinaryHeap::with_capacity(top_k.saturating_mul(2).max(8));
         for emb in embeddings {
             let score = Self::cosine_similarity(query_embedding, &emb.vector);
-            heap.push(Scored {
+            heap.push(Reverse(Scored {
                 score,
-                text: emb.text.as_str(),
-            });
+                embedding: emb,
+            }));
             if heap.len() > top_k * 3 {
                 heap.pop();
             }
         }
 
-        let mut results: Vec<Scored> = heap.into_iter().collect();
+        let mut results: Vec<Scored> = heap.into_iter().map(|r| r.0).collect();
         results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
         results
             .into_iter()
             .take(top_k)
-            .map(|s| s.text.to_string())
+            .map(|s| (s.score, s.embedding))
             .collect()
     }
-}
\ No newline at end of file
+
+    /// Maximal marginal relevance over `scored` (best first): up to `top_k`
+    /// chunks, each pick maximizing `lambda * score - (1 - lambda) * s`, where
+    /// `s` is its highest similarity to the chunks already picked. Chunks
+    /// nearly identical to a picked one are dropped, as are chunks past
+    /// `max_per_file` from one file (0 for no cap). A `lambda` of 1 keeps
+    /// the ranking as it is.
+    pub fn diversify(
+        scored: Vec<(f32, &Embedding)>,
+        top_k: usize,
+        lambda: f32,
+        max_per_file: usize,
+    ) -> Vec<(f32, &Embedding)> {
+        // Cosine similarity above which two chunks count as the same text.
+        const NEAR_DUPLICATE: f32 = 0.97;
+
+        let mut remaining = scored;
+        // Highest similarity of each remaining chunk to the picked ones.
+        let mut redundancy = vec![0.0f32; remaining.len()];
+        let mut per_file: HashMap<&str, usize> = HashMap::new();
+        let mut picked = Vec::with_capacity(top_k.min(remaining.len()));
+        while picked.len() < top_k && !remaining.is_empty() {
+            let best = (0..remaining.len())
+                .map(|i| (lambda * remaining[i].0 - (1.0 - lambda) * redundancy[i], i))
+                .max_by(|a, b| a.0.total_cmp(&b.0).then(b.1.cmp(&a.1)))
+                .map(|(_, i)| i)
+                .unwrap_or(0);
+            let (score, chunk) = remaining.swap_remove(best);
+            redundancy.swap_remove(best);
+            let taken = per_file.entry(chunk.path.as_str()).or_insert(0);
+            if max_per_file > 0 && *taken >= max_per_file {
+                continue;
+            }
+            *taken += 1;
+            picked.push((score, chunk));
+
+            let mut i = 0;
+            while i < remaining.len() {
+                let similarity = Self::cosine_similarity(&chunk.vector, &remaining[i].1.vector);
+                if similarity >= NEAR_DUPLICATE {
+                    remaining.swap_remove(i);
+                    redundancy.swap_remove(i);
+                    continue;
+                }
+                redundancy[i] = redundancy[i].max(similarity);
+                i += 1;
+            }
+        }
+        picked
+    }
+
+    /// Rank texts by keyword overlap with `query_terms` (TF-IDF over the
+    /// stemmed terms), for searching the index without embedding the query.
+    /// Returns `(score, index)` pairs, best first, for texts that match at all.
+    pub fn rank_by_keywords(
+        query_terms: &[String],
+        texts: &[String],
+        top_k: usize,
+    ) -> Vec<(f32, usize)> {
+        let documents: Vec<HashMap<String, usize>> = texts
+            .iter()
+            .map(|text| {
+                let mut counts = HashMap::new();
+                for term in keywords::terms(text) {
+                    *counts.entry(term).or_insert(0) += 1;
+                }
+                counts
+            })
+            .collect();
+        let n = documents.len() as f32;
+        let idf: HashMap<&str, f32> = query_terms
+            .iter()
+            .map(|term| {
+                let df = documents.iter().filter(|d| d.contains_key(term)).count() as f32;
+                (term.as_str(), (1.0 + n / (1.0 + df)).ln())
+            })
+            .collect();
+
+        let mut scored: Vec<(f32, usize)> = documents
+            .iter()
+            .enumerate()
+            .map(|(i, counts)| {
+                let score = query_terms
+                    .iter()
+                    .filter_map(|term| {
+                        counts
+                            .get(term)
+                            .map(|&tf| (1.0 + (tf as f32).ln()) * idf[term.as_str()])
+                    })
+                    .sum();
+                (score, i)
+            })
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.truncate(top_k);
+        scored
+    }
+
+    /// Coarse stage of two-stage search: rank quantized vectors and return the
+    /// ids of the best `limit` candidates for exact rescoring.
+    pub fn find_candidate_ids(
+        query_embedding: &[f32],
+        entries: &[QuantizedEntry],
+        limit: usize,
+    ) -> Vec<String> {
+        let query = QuantizedQuery::new(query_embedding);
+        let mut scored: Vec<(f32, &str)> = entries
+            .iter()
+            .map(|e| (query.approx_score(&e.vector), e.id.as_str()))
+            .collect();
+        if scored.len() > limit {
+            scored.select_nth_unstable_by(limit, |a, b| b.0.total_cmp(&a.0));
+            scored.truncate(limit);
+        }
+        scored.into_iter().map(|(_, id)| id.to_string()).collect()
+    }
+}
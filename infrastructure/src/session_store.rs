@@ -0,0 +1,132 @@
+//! Sessions as JSON files under `<data_dir>/sessions`, one per session,
+//! sealed like the caches when encryption at rest is on. A file named
+//! `active` holds the id of the session in use.
+
+use anyhow::anyhow;
+use domain::session::{Session, SessionRepository};
+use shared::at_rest;
+use shared::paths;
+use shared::types::Result;
+use std::path::PathBuf;
+
+const ACTIVE_FILE: &str = "active";
+
+pub struct JsonSessionRepository {
+    dir: PathBuf,
+}
+
+impl Default for JsonSessionRepository {
+    fn default() -> Self {
+        Self::new(paths::data_dir().join("sessions"))
+    }
+}
+
+impl JsonSessionRepository {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        if !is_valid_id(id) {
+            return Err(anyhow!(
+                "invalid session id '{}' (use letters, digits, '-' and '_')",
+                id
+            ));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    fn read(&self, path: &PathBuf) -> Result<Session> {
+        let data = at_rest::open(std::fs::read(path)?)?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+}
+
+/// Ids become file names, so they are kept to a safe alphabet.
+pub fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl SessionRepository for JsonSessionRepository {
+    fn create(&self, id: &str) -> Result<Session> {
+        if self.path(id)?.exists() {
+            return Err(anyhow!("session '{}' already exists", id));
+        }
+        let session = Session::new(id.to_string());
+        self.save(&session)?;
+        Ok(session)
+    }
+
+    fn list(&self) -> Result<Vec<Session>> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Ok(Vec::new());
+        };
+        let mut sessions: Vec<Session> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            // Unreadable files (sealed under another key) are left out
+            .filter_map(|path| self.read(&path).ok())
+            .collect();
+        sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(sessions)
+    }
+
+    fn load(&self, id: &str) -> Result<Option<Session>> {
+        let path = self.path(id)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        self.read(&path).map(Some)
+    }
+
+    fn save(&self, session: &Session) -> Result<()> {
+        let path = self.path(&session.id)?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(path, at_rest::seal(serde_json::to_vec_pretty(session)?)?)?;
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<bool> {
+        let path = self.path(id)?;
+        if !path.exists() {
+            return Ok(false);
+        }
+        std::fs::remove_file(path)?;
+        if self.active()?.as_deref() == Some(id) {
+            self.set_active(None)?;
+        }
+        Ok(true)
+    }
+
+    fn active(&self) -> Result<Option<String>> {
+        match std::fs::read_to_string(self.dir.join(ACTIVE_FILE)) {
+            Ok(id) => {
+                let id = id.trim();
+                Ok((is_valid_id(id) && self.path(id)?.exists()).then(|| id.to_string()))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn set_active(&self, id: Option<&str>) -> Result<()> {
+        let marker = self.dir.join(ACTIVE_FILE);
+        match id {
+            Some(id) => {
+                if !self.path(id)?.exists() {
+                    return Err(anyhow!("no session '{}'", id));
+                }
+                std::fs::create_dir_all(&self.dir)?;
+                std::fs::write(marker, id)?;
+            }
+            None => {
+                let _ = std::fs::remove_file(marker);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+//! The hosts the user can reach over SSH, read from the `Host` entries of
+//! `~/.ssh/config`, so an agent plan can be run on hosts named in the task.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshHost {
+    /// The name `ssh` is given, e.g. `web1`.
+    pub alias: String,
+    /// `HostName`, when the alias isn't the address itself.
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+}
+
+/// `~/.ssh/config`.
+pub fn config_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| Path::new(&home).join(".ssh").join("config"))
+}
+
+/// The hosts in the user's SSH config; none if it can't be read.
+pub fn load() -> Vec<SshHost> {
+    config_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|text| parse(&text))
+        .unwrap_or_default()
+}
+
+/// The concrete hosts of an SSH config: patterns (`*`, `?`, `!`) are left
+/// out, and `Match` blocks are skipped.
+pub fn parse(text: &str) -> Vec<SshHost> {
+    let mut hosts: Vec<SshHost> = Vec::new();
+    // Indexes into `hosts` of the aliases the current block applies to.
+    let mut current: Vec<usize> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or((line, ""));
+        let key = key.to_lowercase();
+        let value = value.trim_matches(|c: char| c.is_whitespace() || c == '=');
+        match key.as_str() {
+            "host" => {
+                current.clear();
+                for alias in value.split_whitespace() {
+                    if alias.contains(['*', '?', '!']) {
+                        continue;
+                    }
+                    let index = match hosts.iter().position(|h| h.alias == alias) {
+                        Some(index) => index,
+                        None => {
+                            hosts.push(SshHost {
+                                alias: alias.to_string(),
+                                hostname: None,
+                                user: None,
+                            });
+                            hosts.len() - 1
+                        }
+                    };
+                    current.push(index);
+                }
+            }
+            "match" => current.clear(),
+            // The first value given for a host wins, as in ssh.
+            "hostname" => {
+                for &i in &current {
+                    hosts[i].hostname.get_or_insert_with(|| value.to_string());
+                }
+            }
+            "user" => {
+                for &i in &current {
+                    hosts[i].user.get_or_insert_with(|| value.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    hosts
+}
@@ -0,0 +1,215 @@
+use super::{offline, policy};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_RELEASES_URL: &str =
+    "https://api.github.com/repos/rendivs925/vibe_cli/releases/latest";
+
+/// Name of the release asset listing `<sha256>  <asset>` lines.
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+#[derive(Debug, Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    assets: Vec<GithubAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GithubAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// The newest published release and the download for this platform.
+#[derive(Debug, Clone)]
+pub struct ReleaseInfo {
+    pub version: String,
+    binary: Option<GithubAsset>,
+    checksums: Option<GithubAsset>,
+}
+
+impl ReleaseInfo {
+    pub fn is_newer_than(&self, current: &str) -> bool {
+        parse_version(&self.version) > parse_version(current)
+    }
+}
+
+/// Asset name for the running platform, e.g. `vibe_cli-x86_64-linux`.
+pub fn platform_asset_name() -> String {
+    format!(
+        "vibe_cli-{}-{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    )
+}
+
+/// `v1.2.3` / `1.2.3-rc1` → `[1, 2, 3]`; non-numeric parts are ignored.
+pub fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .trim()
+        .trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+pub struct Updater {
+    client: reqwest::Client,
+    releases_url: String,
+}
+
+impl Default for Updater {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Updater {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            releases_url: std::env::var("VIBE_RELEASES_URL")
+                .unwrap_or_else(|_| DEFAULT_RELEASES_URL.to_string()),
+        }
+    }
+
+    /// The release feed in use when it isn't the project's own, i.e.
+    /// `VIBE_RELEASES_URL` points elsewhere.
+    pub fn mirror(&self) -> Option<&str> {
+        (self.releases_url != DEFAULT_RELEASES_URL).then_some(self.releases_url.as_str())
+    }
+
+    pub async fn latest_release(&self) -> Result<ReleaseInfo> {
+        offline::ensure_online("checking for releases")?;
+        policy::system().check_endpoint(&self.releases_url)?;
+        let response = self
+            .client
+            .get(&self.releases_url)
+            .header("User-Agent", "vibe_cli")
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "release check failed: HTTP {}",
+                response.status()
+            ));
+        }
+        let release: GithubRelease = response.json().await?;
+        let asset_name = platform_asset_name();
+        let find = |name: &str| release.assets.iter().find(|a| a.name == name).cloned();
+        Ok(ReleaseInfo {
+            version: release.tag_name.clone(),
+            binary: find(&asset_name),
+            checksums: find(CHECKSUMS_ASSET),
+        })
+    }
+
+    /// Download the release binary, check it against the SHA-256 checksum
+    /// published with it, and atomically replace `target` with it. The
+    /// checksum comes from the same release, so it catches a corrupted
+    /// download but not a tampered release: the binary is not signed.
+    pub async fn install(&self, release: &ReleaseInfo, target: &Path) -> Result<()> {
+        let asset_name = platform_asset_name();
+        let binary = release.binary.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("release {} has no build named {}", release.version, asset_name)
+        })?;
+        let checksums = release.checksums.as_ref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "release {} publishes no {}; refusing to install an unverified binary",
+                release.version,
+                CHECKSUMS_ASSET
+            )
+        })?;
+
+        let sums = self.download(&checksums.browser_download_url).await?;
+        let expected = expected_checksum(&String::from_utf8_lossy(&sums), &asset_name)
+            .ok_or_else(|| anyhow::anyhow!("{} has no entry for {}", CHECKSUMS_ASSET, asset_name))?;
+        let bytes = self.download(&binary.browser_download_url).await?;
+        let actual = sha256_hex(&bytes);
+        if actual != expected {
+            return Err(anyhow::anyhow!(
+                "checksum mismatch for {}: expected {}, got {}",
+                asset_name,
+                expected,
+                actual
+            ));
+        }
+
+        let target = target.to_path_buf();
+        tokio::task::spawn_blocking(move || replace_binary(&target, &bytes)).await?
+    }
+
+    async fn download(&self, url: &str) -> Result<Vec<u8>> {
+        offline::ensure_online("downloading a release")?;
+        policy::system().check_endpoint(url)?;
+        let response = self
+            .client
+            .get(url)
+            .header("User-Agent", "vibe_cli")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "download of {} failed: HTTP {}",
+                url,
+                response.status()
+            ));
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Look up `asset` in `sha256sum`-style output (`<hex>  <name>` or `<hex> *<name>`).
+fn expected_checksum(sums: &str, asset: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let hash = parts.next()?;
+        let name = parts.next()?.trim_start_matches('*');
+        (name == asset).then(|| hash.to_lowercase())
+    })
+}
+
+/// Write next to the target and rename over it, so an interrupted update
+/// never leaves a half-written executable behind.
+fn replace_binary(target: &Path, bytes: &[u8]) -> Result<()> {
+    let staging: PathBuf = target.with_extension("update");
+    std::fs::write(&staging, bytes)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staging, std::fs::Permissions::from_mode(0o755))?;
+    }
+    if let Err(e) = std::fs::rename(&staging, target) {
+        let _ = std::fs::remove_file(&staging);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_compare_numerically_and_checksums_are_found() {
+        assert!(parse_version("v0.10.0") > parse_version("0.9.3"));
+        assert_eq!(parse_version("v1.2.3-rc1"), vec![1, 2, 3]);
+
+        let sums = "abc123  vibe_cli-x86_64-linux\nDEF456 *vibe_cli-aarch64-macos\n";
+        assert_eq!(
+            expected_checksum(sums, "vibe_cli-aarch64-macos").as_deref(),
+            Some("def456")
+        );
+        assert_eq!(expected_checksum(sums, "vibe_cli-x86_64-windows"), None);
+    }
+}
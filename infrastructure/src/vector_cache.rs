@@ -0,0 +1,166 @@
+use super::embedding_storage::EmbeddingStorage;
+use memmap2::Mmap;
+use shared::types::Result;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"VIBEVEC1";
+const HEADER_LEN: usize = 32;
+
+/// Contiguous f32 matrix of every stored vector, persisted next to the DB as
+/// `<db>.vectors` and memory-mapped on load, so repeated queries skip
+/// per-row bincode deserialization entirely.
+///
+/// Layout: magic, generation, rows, dims (u64 LE each) | rows*dims f32 LE |
+/// rows f32 norms | bincode `Vec<String>` of ids.
+pub struct VectorMatrixCache {
+    mmap: Mmap,
+    generation: u64,
+    rows: usize,
+    dims: usize,
+    ids: Vec<String>,
+}
+
+impl VectorMatrixCache {
+    pub fn sidecar_path(db_path: &Path) -> PathBuf {
+        let mut name = db_path.as_os_str().to_owned();
+        name.push(".vectors");
+        PathBuf::from(name)
+    }
+
+    /// Open the sidecar if it matches the DB generation, otherwise rebuild it.
+    pub async fn load(storage: &EmbeddingStorage) -> Result<Self> {
+        let generation = storage.generation().await?;
+        let path = Self::sidecar_path(storage.db_path());
+        if let Ok(cache) = Self::open(&path) {
+            if cache.generation == generation {
+                return Ok(cache);
+            }
+        }
+
+        let vectors = storage.get_all_vectors().await?;
+        let write_path = path.clone();
+        tokio::task::spawn_blocking(move || Self::write(&write_path, generation, &vectors))
+            .await??;
+        Self::open(&path)
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    /// Ids of the `limit` rows most similar to the query, best first.
+    pub fn top_ids(&self, query: &[f32], limit: usize) -> Vec<(f32, String)> {
+        if query.len() != self.dims || self.rows == 0 {
+            return Vec::new();
+        }
+        let query_norm = query.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let matrix = &self.mmap[HEADER_LEN..HEADER_LEN + self.rows * self.dims * 4];
+        let norms_start = HEADER_LEN + self.rows * self.dims * 4;
+        let norms = &self.mmap[norms_start..norms_start + self.rows * 4];
+
+        let mut scored: Vec<(f32, usize)> = matrix
+            .chunks_exact(self.dims * 4)
+            .zip(norms.chunks_exact(4))
+            .enumerate()
+            .map(|(row, (bytes, norm))| {
+                let dot: f32 = bytes
+                    .chunks_exact(4)
+                    .zip(query.iter())
+                    .map(|(b, q)| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) * q)
+                    .sum();
+                let denom = f32::from_le_bytes([norm[0], norm[1], norm[2], norm[3]]) * query_norm;
+                let score = if denom == 0.0 { 0.0 } else { dot / denom };
+                (score, row)
+            })
+            .collect();
+
+        if scored.len() > limit {
+            scored.select_nth_unstable_by(limit, |a, b| b.0.total_cmp(&a.0));
+            scored.truncate(limit);
+        }
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+            .into_iter()
+            .map(|(score, row)| (score, self.ids[row].clone()))
+            .collect()
+    }
+
+    /// Open the sidecar at `path`. Anything but a complete file in the
+    /// layout above, e.g. one whose header claims more rows than it holds
+    /// or with bytes after the ids, is an error, so [`Self::load`] rebuilds
+    /// it.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() < HEADER_LEN || &mmap[..8] != MAGIC {
+            return Err(anyhow::anyhow!("invalid vector cache header"));
+        }
+        let read_u64 = |at: usize| {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(&mmap[at..at + 8]);
+            u64::from_le_bytes(buf)
+        };
+        let generation = read_u64(8);
+        let rows = usize::try_from(read_u64(16))?;
+        let dims = usize::try_from(read_u64(24))?;
+        let ids_start = rows
+            .checked_mul(dims)
+            .and_then(|cells| cells.checked_add(rows))
+            .and_then(|floats| floats.checked_mul(4))
+            .and_then(|bytes| bytes.checked_add(HEADER_LEN))
+            .filter(|&ids_start| ids_start <= mmap.len())
+            .ok_or_else(|| anyhow::anyhow!("truncated vector cache"))?;
+        let ids: Vec<String> = bincode::deserialize(&mmap[ids_start..])?;
+        if ids.len() != rows {
+            return Err(anyhow::anyhow!("vector cache id count mismatch"));
+        }
+        if ids_start as u64 + bincode::serialized_size(&ids)? != mmap.len() as u64 {
+            return Err(anyhow::anyhow!("vector cache has trailing bytes"));
+        }
+        Ok(Self {
+            mmap,
+            generation,
+            rows,
+            dims,
+            ids,
+        })
+    }
+
+    /// Write `vectors` as the sidecar at `path`, tagged with `generation`.
+    pub fn write(path: &Path, generation: u64, vectors: &[(String, Vec<f32>)]) -> Result<()> {
+        let dims = vectors.first().map(|(_, v)| v.len()).unwrap_or(0);
+        // Vectors from a different model would corrupt the matrix; skip them.
+        let rows: Vec<&(String, Vec<f32>)> =
+            vectors.iter().filter(|(_, v)| v.len() == dims).collect();
+
+        // Write to a temp file and rename so readers never see a partial file.
+        let tmp = path.with_extension("vectors.tmp");
+        {
+            let mut out = BufWriter::new(File::create(&tmp)?);
+            out.write_all(MAGIC)?;
+            out.write_all(&generation.to_le_bytes())?;
+            out.write_all(&(rows.len() as u64).to_le_bytes())?;
+            out.write_all(&(dims as u64).to_le_bytes())?;
+            for (_, vector) in &rows {
+                for x in vector {
+                    out.write_all(&x.to_le_bytes())?;
+                }
+            }
+            for (_, vector) in &rows {
+                let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+                out.write_all(&norm.to_le_bytes())?;
+            }
+            let ids: Vec<&String> = rows.iter().map(|(id, _)| id).collect();
+            out.write_all(&bincode::serialize(&ids)?)?;
+            out.flush()?;
+        }
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
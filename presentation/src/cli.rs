@@ -1,57 +1,85 @@
-use application::rag_service::RagService;
-use clap::Parser;
+use application::rag_service::{IndexStatus, RagConversation, RagService};
+use application::recall_service::{self, MemoryKind, RecallService, Recollection};
+use application::redaction;
+use application::agent_service::{self, StepEvent, StepExecutor, StepOutcome};
+use application::alias_service::{self, AliasProposal, AliasService, DEFAULT_MIN_COUNT};
+use application::ask_service::AskService;
+use application::cache_check::{self, Problem, Reuse};
+use application::command_planner::LlmCommandPlanner;
+use application::command_rules::{self, CommandRule};
+use application::diff_explain_service::{self, DiffExplainService};
+use application::doc_gen_service::{self, DocGenService, DocLanguage};
+use application::env_snapshot::{self, EnvSnapshot};
+use application::docs_service::{DocsService, DEFAULT_MAX_PAGES, DOCS_COLLECTION};
+use application::explain_service::{load_document, ExplainService};
+use application::multi_host::{self, CellStatus, HostConfirm, StepMatrix};
+use application::prompt_guard;
+use application::intent_router::{Intent, IntentRouter};
+use application::plan_review_service::{self, PlanReviewService, ReviewedPlan};
+use application::review_service::{self, ReviewComment, ReviewService, Severity};
+use application::safety_service::SafetyService;
+use application::summary_service::SummaryService;
+use application::test_gen_service::{self, TestGenService, TestTarget};
+use application::watch_service::WatchService;
+use application::working_dir::{Located, WorkingDir};
+use clap::{Parser, Subcommand};
 use colored::Colorize;
-use docx_rs::*;
-use infrastructure::{config::Config, ollama_client::OllamaClient};
-use serde::{Deserialize, Serialize};
+use crate::clipboard;
+use crate::env_context;
+use crate::highlight::Highlighter;
+use crate::notify;
+use crate::pane::PaneTarget;
+use crate::plan_diff;
+use crate::parsing::{extract_command_from_response, parse_interval};
+use crate::pty_shell::PtyShell;
+use crate::policy_test;
+use crate::runner::{self, CommandRun, RunOutcome};
+use crate::safety::{assess_command, confirm, read_only_violation, risk_tier, RiskTier};
+use crate::script_output::{edit_file, write_script, ScriptWritten, WriteMode};
+use crate::script_params::{self, ScriptParam};
+use crate::script_preflight;
+use crate::step_budget;
+use dialoguer::console::Term;
+use domain::command_plan::{CommandPlan, CommandPlanner, PlanStep};
+use domain::session::{Session, SessionRepository};
+use infrastructure::{
+    allowlist::{AllowScope, Allowlist},
+    audit_log::{self, AuditEntry, AuditLog, ExportFormat, Provenance},
+    cassette::{self, Cassette},
+    generation_stats,
+    config::{parse_collections, Config, NotifyRule},
+    crate_docs::CrateDocs,
+    lockfiles,
+    embedding_storage::{collection_db_path, collection_names, EmbeddingStorage, DEFAULT_COLLECTION},
+    index_bundle,
+    jobs::{Job, JobStatus, JobStore},
+    offline,
+    ollama_client::OllamaClient,
+    policy::{self, Policy},
+    ssh_inventory,
+    prompt_report,
+    session_store::JsonSessionRepository,
+    updater::Updater,
+};
+use shared::accessibility;
+use shared::at_rest;
+use shared::cache::{CacheEntry, CacheStore};
 use shared::confirmation::ask_confirmation;
+use shared::paths;
 use shared::types::Result;
+use shared::utils::format_bytes;
 use std::collections::HashSet;
-use std::hash::{Hash, Hasher};
-use std::io::{self, Write};
-use std::path::PathBuf;
-
-fn find_project_root() -> Option<String> {
-    let mut current = std::env::current_dir().ok()?;
-    loop {
-        // Check for various project indicators
-        let project_files = [
-            "Cargo.toml",      // Rust
-            "package.json",    // Node.js
-            "requirements.txt", // Python
-            "Pipfile",         // Python
-            "pyproject.toml",  // Python
-            "setup.py",        // Python
-            "Makefile",        // C/C++
-            "CMakeLists.txt",  // C/C++
-            "configure.ac",    // C/C++
-            "go.mod",          // Go
-            "Gemfile",         // Ruby
-            "composer.json",   // PHP
-            ".git",            // Git repo as fallback
-        ];
-
-        for file in &project_files {
-            if current.join(file).exists() {
-                return Some(current.display().to_string());
-            }
-        }
-
-        if !current.pop() {
-            break;
-        }
-    }
-    None
-}
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-fn project_cache_suffix() -> String {
-    if let Some(root) = find_project_root() {
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        root.hash(&mut hasher);
-        format!("{:x}", hasher.finish())
-    } else {
-        "global".to_string()
-    }
+
+fn running_as_root() -> bool {
+    std::process::Command::new("id")
+        .arg("-u")
+        .output()
+        .is_ok_and(|out| String::from_utf8_lossy(&out.stdout).trim() == "0")
 }
 
 fn detect_system_info() -> String {
@@ -124,209 +152,23 @@ fn detect_system_info() -> String {
     info.join(", ")
 }
 
-// Cache entries expire after 7 days (604800 seconds)
-const CACHE_TTL_SECONDS: u64 = 604800;
-
 // Semantic similarity threshold (0.0 to 1.0)
 const SEMANTIC_SIMILARITY_THRESHOLD: f64 = 0.7;
 
-#[derive(Serialize, Deserialize, Default)]
-struct CacheFile {
-    entries: Vec<CacheEntry>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct CacheEntry {
-    prompt: String,
-    command: String,
-    timestamp: u64,
-}
-
-#[derive(Serialize, Deserialize, Default)]
-struct ExplainCacheFile {
-    entries: Vec<ExplainCacheEntry>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct ExplainCacheEntry {
-    prompt: String,
-    response: String,
-    timestamp: u64,
-}
-
-#[derive(Serialize, Deserialize, Default)]
-struct RagCacheFile {
-    entries: Vec<RagCacheEntry>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct RagCacheEntry {
-    question: String,
-    response: String,
-    timestamp: u64,
-}
-
-/// Remove markdown code fences/backticks and surrounding quotes
-fn clean_command_output(raw: &str) -> String {
-    let trimmed = raw.trim();
-    if trimmed.starts_with("```") && trimmed.ends_with("```") {
-        let lines: Vec<&str> = trimmed.lines().collect();
-        if lines.len() >= 3 && lines.last().unwrap().trim() == "```" {
-            return lines[1..lines.len() - 1].join("\n").trim().to_string();
-        }
-    }
-    trimmed
-        .trim_matches('`')
-        .trim_matches('"')
-        .trim_matches('\'')
-        .trim()
-        .to_string()
-}
-
-/// Extract last JSON object/array from text
-fn extract_last_json(raw: &str) -> Option<&str> {
-    let trimmed = raw.trim();
-    if trimmed.starts_with('{') && trimmed.ends_with('}')
-        || trimmed.starts_with('[') && trimmed.ends_with(']')
-    {
-        return Some(trimmed);
-    }
-    let bytes = trimmed.as_bytes();
-    let mut depth = 0;
-    let mut start = None;
-    for (i, &b) in bytes.iter().enumerate() {
-        if b == b'{' || b == b'[' {
-            if depth == 0 {
-                start = Some(i);
-            }
-            depth += 1;
-        } else if b == b'}' || b == b']' {
-            depth -= 1;
-            if depth == 0 {
-                if let Some(s) = start {
-                    return Some(&trimmed[s..=i]);
-                }
-            }
-        }
-    }
-    None
-}
-
-/// Extract JSON array from possibly noisy text
-fn extract_json_array(text: &str) -> Option<&str> {
-    let bytes = text.as_bytes();
-    let mut depth = 0;
-    let mut start = None;
-    let mut in_string = false;
-    let mut escape_next = false;
-
-    for (i, &b) in bytes.iter().enumerate() {
-        if escape_next {
-            escape_next = false;
-            continue;
-        }
-
-        match b {
-            b'"' => in_string = !in_string,
-            b'\\' => {
-                if in_string {
-                    escape_next = true;
-                }
-            }
-            b'[' => {
-                if !in_string && depth == 0 {
-                    start = Some(i);
-                }
-                if !in_string {
-                    depth += 1;
-                }
-            }
-            b']' => {
-                if !in_string {
-                    depth -= 1;
-                    if depth == 0 {
-                        if let Some(s) = start {
-                            return Some(&text[s..=i]);
-                        }
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-    None
-}
-
-/// Parse agent response into a list of commands
-fn parse_agent_plan(raw: &str) -> Vec<String> {
-    // Try plain parse
-    if let Ok(cmds) = serde_json::from_str::<Vec<String>>(raw) {
-        return cmds;
-    }
-    // Clean and try again
-    let cleaned = clean_command_output(raw);
-    if let Ok(cmds) = serde_json::from_str::<Vec<String>>(&cleaned) {
-        return cmds;
-    }
-    // Try to pull array from noisy text
-    if let Some(arr) = extract_json_array(raw) {
-        if let Ok(cmds) = serde_json::from_str::<Vec<String>>(arr) {
-            return cmds;
-        }
-    }
-    if let Some(json) = extract_last_json(raw) {
-        if let Ok(cmds) = serde_json::from_str::<Vec<String>>(json) {
-            return cmds;
-        }
-    }
-    // Fallback: split non-empty lines, stripping common list markers and code fences
-    raw.lines()
-        .map(|l| l.trim())
-        .filter(|l| {
-            !l.is_empty() && !l.starts_with("```") && !l.ends_with("```") && *l != "[" && *l != "]"
-        })
-        .map(|l| {
-            let mut line = l
-                .trim_start_matches(|c| c == '-' || c == '*' || c == '•')
-                .trim();
-            if let Some(pos) = line.find(|c: char| c == ')' || c == '.' || c == ':') {
-                // Only strip early numbering markers
-                if pos < 4 {
-                    line = line[pos + 1..].trim();
-                }
-            }
-            line.trim_matches(',').trim().trim_matches('"').to_string()
-        })
-        .filter(|l| !l.is_empty())
-        .collect()
-}
+/// Session context key holding the RAG conversation.
+const RAG_SESSION_KEY: &str = "rag";
 
-fn extract_command_from_response(response: &str) -> String {
-    let response = response.trim();
-    let cleaned = if response.starts_with("```bash") && response.ends_with("```") {
-        let start = response.find('\n').unwrap_or(0) + 1;
-        let end = response.len() - 3;
-        response[start..end].trim().to_string()
-    } else if response.starts_with("```") && response.ends_with("```") {
-        let start = response.find('\n').unwrap_or(0) + 1;
-        let end = response.len() - 3;
-        response[start..end].trim().to_string()
-    } else {
-        response.to_string()
-    };
-    // Remove surrounding backticks, quotes, and extra whitespace
-    cleaned
-        .trim_matches('`')
-        .trim_matches('"')
-        .trim_matches('\'')
-        .trim()
-        .to_string()
-}
+/// Earlier successful agent runs described to the planner.
+const REMEMBERED_AGENT_RUNS: usize = 5;
 
 #[derive(Parser)]
 #[command(name = "vibe_cli")]
 #[command(about = "Vibe CLI assistant with RAG capabilities")]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Enter interactive chat mode
     #[arg(long)]
     pub chat: bool,
@@ -339,24 +181,526 @@ pub struct Cli {
     #[arg(long)]
     pub explain: bool,
 
+    /// Only explain these PDF pages, e.g. 3, 2-5, 7- or 1,4-6
+    #[arg(long, visible_alias = "range", value_name = "PAGES", requires = "explain")]
+    pub pages: Option<String>,
+
+    /// Explain what changed between two files
+    #[arg(long, num_args = 2, value_names = ["OLD", "NEW"], requires = "explain", conflicts_with_all = ["rev", "pages"])]
+    pub diff: Option<Vec<PathBuf>>,
+
+    /// Explain what changed in a git revision range, e.g. HEAD~1..HEAD
+    /// (only in the paths given, if any)
+    #[arg(long, value_name = "RANGE", requires = "explain", conflicts_with = "pages")]
+    pub rev: Option<String>,
+
+    /// With --diff or --rev, add related definitions from the RAG index
+    #[arg(long, requires = "explain")]
+    pub with_context: bool,
+
     /// Query with RAG context
     #[arg(long)]
     pub rag: bool,
 
+    /// Always generate a command instead of routing the input to ask, RAG
+    /// or agent mode
+    #[arg(long, alias = "no-ask")]
+    pub no_route: bool,
+
+    /// Chunks retrieved for a --rag question (default 50, or RAG_TOP_K)
+    #[arg(long, value_name = "N", requires = "rag")]
+    pub top_k: Option<usize>,
+
+    /// Leave out retrieved chunks less similar to the question than this
+    /// cosine similarity, e.g. 0.3 (default RAG_MIN_SCORE, or none)
+    #[arg(long, value_name = "SCORE", requires = "rag")]
+    pub min_score: Option<f32>,
+
+    /// Re-index the project before answering a --rag question, instead of
+    /// answering while it is indexed in the background
+    #[arg(long, requires = "rag")]
+    pub fresh: bool,
+
+    /// Cap on the characters of code context sent with a --rag question
+    #[arg(long, value_name = "CHARS", requires = "rag")]
+    pub max_context_chars: Option<usize>,
+
+    /// Collections a --rag question searches, e.g. code,docs (default code,
+    /// or RAG_COLLECTIONS)
+    #[arg(long, value_name = "NAMES", requires = "rag")]
+    pub collections: Option<String>,
+
+    /// Generate a bash script instead of running commands
+    #[arg(long)]
+    pub script: bool,
+
+    /// Output file for --script mode
+    #[arg(short = 'o', long, value_name = "FILE", requires = "script")]
+    pub output: Option<PathBuf>,
+
+    /// Add the script to the end of an existing output file
+    #[arg(long, requires = "script")]
+    pub append: bool,
+
+    /// Keep a timestamped copy of the output file before changing it
+    #[arg(long, requires = "script")]
+    pub backup: bool,
+
+    /// Give the script getopts options for the <placeholders> in the request,
+    /// or for the names given (--with-args=src,dest)
+    #[arg(
+        long,
+        value_name = "NAMES",
+        num_args = 0..=1,
+        require_equals = true,
+        value_delimiter = ',',
+        requires = "script"
+    )]
+    pub with_args: Option<Vec<String>>,
+
+    /// Leave `set -euo pipefail` out of scripts generated with --with-args
+    #[arg(long, requires = "with_args")]
+    pub no_strict: bool,
+
+    /// Leave out the checks for the OS, commands and tool versions the
+    /// script needs
+    #[arg(long, requires = "script")]
+    pub no_preflight: bool,
+
     /// Load context from path
     #[arg(long)]
     pub context: bool,
 
+    /// Record every model request and response to a cassette file
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Answer model requests from a recorded cassette instead of the server
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Keep indexes and caches in this directory (portable installs)
+    #[arg(long, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Print the final prompt sent to the model, with token estimates
+    #[arg(long)]
+    pub show_prompt: bool,
+
+    /// Show command output and audit log entries without masking values
+    /// that look like secrets
+    #[arg(long)]
+    pub show_secrets: bool,
+
+    /// Show which files and chunks a RAG answer was given, their similarity
+    /// scores, and what was left out for the context budget
+    #[arg(long)]
+    pub verbose: bool,
+
+    /// Never contact the model or network; serve cached answers and raw
+    /// index matches instead
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Copy suggested commands to the clipboard (works over SSH and in tmux
+    /// through OSC 52)
+    #[arg(long)]
+    pub copy: bool,
+
+    /// Type the confirmed command into a tmux pane (e.g. `work:1.0`) or
+    /// `screen:<session>[:<window>]` without pressing Enter, instead of
+    /// running it
+    #[arg(long, value_name = "TARGET")]
+    pub send_to_pane: Option<String>,
+
+    /// Start the confirmed command as a background job that outlives this
+    /// terminal; see `jobs`
+    #[arg(long)]
+    pub detach: bool,
+
+    /// Run commands with only basic variables like PATH and HOME plus those
+    /// kept under `[sandbox]` in config.toml
+    #[arg(long)]
+    pub scrub_env: bool,
+
+    /// Run an agent plan on these SSH hosts, e.g. web1,web2, instead of
+    /// the ones the task names
+    #[arg(long, value_name = "LIST")]
+    pub hosts: Option<String>,
+
+    /// Hosts running an agent plan unattended at the same time
+    #[arg(long, value_name = "N")]
+    pub parallel: Option<usize>,
+
+    /// Run as if started in DIR: commands, previews and the project index
+    /// all start there
+    #[arg(long, value_name = "DIR")]
+    pub cwd: Option<PathBuf>,
+
+    /// Disable colored output (also set by `NO_COLOR`)
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// Screen-reader-friendly output: no color, ASCII only, progress on
+    /// separate lines and typed yes/no answers
+    #[arg(long)]
+    pub accessible: bool,
+
     /// The query or file path to process
     #[arg(trailing_var_arg = true)]
     pub args: Vec<String>,
 }
 
+#[derive(Subcommand)]
+pub enum Command {
+    /// Answer a question in prose instead of generating a command
+    Ask {
+        #[arg(trailing_var_arg = true, required = true)]
+        question: Vec<String>,
+    },
+    /// Answer from earlier RAG answers, explanations and chat exchanges
+    Recall {
+        #[arg(trailing_var_arg = true, required = true)]
+        question: Vec<String>,
+    },
+    /// Manage the RAG index
+    Rag {
+        #[command(subcommand)]
+        command: RagCommand,
+    },
+    /// Add external documentation (a site or a local doc tree) to the docs
+    /// collection
+    Docs {
+        #[command(subcommand)]
+        command: DocsCommand,
+    },
+    /// Print the version
+    Version {
+        /// Also report whether a newer release is available
+        #[arg(long)]
+        check: bool,
+    },
+    /// Download the latest release, check it against the release's checksum and replace this binary
+    SelfUpdate,
+    /// Re-run a read-only command until the model says a condition holds
+    Watch {
+        /// The command to run, e.g. "kubectl rollout status deploy/api"
+        command: String,
+        /// Interval between runs (e.g. 30s, 5m, 1h)
+        #[arg(long, default_value = "30s")]
+        every: String,
+        /// Condition in plain English, e.g. "the deployment finished"
+        #[arg(long)]
+        until: String,
+        /// Give up after this many checks
+        #[arg(long)]
+        max_checks: Option<usize>,
+    },
+    /// Review uncommitted changes, the staged ones, or a branch
+    Review {
+        /// Review the changes staged for commit
+        #[arg(long, conflicts_with = "branch")]
+        staged: bool,
+        /// Review what HEAD adds on top of this branch, as a pull request would
+        #[arg(long)]
+        branch: Option<String>,
+        /// Print the comments as JSON
+        #[arg(long)]
+        json: bool,
+        /// Don't look up related code in the RAG index
+        #[arg(long)]
+        no_context: bool,
+    },
+    /// Write unit tests for a function, based on its callers and existing tests
+    GenTests {
+        /// The function, e.g. "checksum::archive_checksum" or "src/checksum.rs::archive_checksum"
+        target: String,
+        /// Run the tests once they are written
+        #[arg(long)]
+        run: bool,
+        /// Command that runs the tests, instead of the usual one for the language
+        #[arg(long, requires = "run")]
+        test_command: Option<String>,
+    },
+    /// Write doc comments for the public items of a Rust or Python file that have none
+    GenDocs {
+        file: PathBuf,
+        /// Don't look up call sites in the RAG index
+        #[arg(long)]
+        no_context: bool,
+        /// Keep a timestamped copy of the file before changing it
+        #[arg(long)]
+        backup: bool,
+    },
+    /// Keep chat, agent and RAG history across runs in named sessions
+    Session {
+        #[command(subcommand)]
+        command: SessionCommand,
+    },
+    /// Manage commands that run without confirmation
+    Allowlist {
+        #[command(subcommand)]
+        command: AllowlistCommand,
+    },
+    /// Propose shell aliases and functions for frequently accepted commands
+    Aliasify {
+        /// Times a command must have succeeded to be considered
+        #[arg(long, default_value_t = DEFAULT_MIN_COUNT)]
+        min_count: usize,
+    },
+    /// Work with the log of proposed and run commands
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+    /// Check commands against the safety rules
+    Policy {
+        #[command(subcommand)]
+        command: PolicyCommand,
+    },
+    /// Manage the requests answered from the local rule table
+    Rules {
+        #[command(subcommand)]
+        command: RulesCommand,
+    },
+    /// List and follow commands started with --detach
+    Jobs {
+        #[command(subcommand)]
+        command: Option<JobsCommand>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum JobsCommand {
+    /// List jobs with their status, summarizing those that just ended
+    List,
+    /// Show the end of a job's log
+    Tail {
+        id: u64,
+        /// Lines to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+        /// Keep showing new output until the job ends, then summarize it
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Stop a running job
+    Kill { id: u64 },
+    /// Forget jobs that have ended, with their logs
+    Clean,
+}
+
+#[derive(Subcommand)]
+pub enum RulesCommand {
+    /// List your rules and the built-in ones
+    List,
+    /// Open your rules file in $EDITOR
+    Edit,
+    /// Remove one of your rules
+    Remove { name: String },
+}
+
+#[derive(Subcommand)]
+pub enum PolicyCommand {
+    /// Show which safety rules a command matches and what would happen to
+    /// it, without running it or asking the model
+    Test {
+        /// The command, e.g. "sudo ufw enable"
+        command: String,
+        /// A draft policy file to use instead of the installed one
+        #[arg(long, value_name = "FILE")]
+        policy: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AuditCommand {
+    /// Print audit entries for ingestion elsewhere (e.g. a SIEM)
+    Export {
+        /// Only entries from this far back (e.g. 12h, 7d)
+        #[arg(long)]
+        since: Option<String>,
+        /// jsonl or csv
+        #[arg(long, default_value = "jsonl")]
+        format: String,
+        /// Every project's log instead of this project's
+        #[arg(long)]
+        all_projects: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DocsCommand {
+    /// Fetch a documentation site, or read a local file or directory, and
+    /// embed its pages
+    Add {
+        /// URL, e.g. https://book.leptos.dev/, or path
+        source: String,
+        /// Most pages fetched from a site
+        #[arg(long, default_value_t = DEFAULT_MAX_PAGES)]
+        max_pages: usize,
+    },
+    /// Fetch added documentation again, re-embedding changed pages
+    Refresh {
+        /// Only this source (default: all of them)
+        source: Option<String>,
+        #[arg(long, default_value_t = DEFAULT_MAX_PAGES)]
+        max_pages: usize,
+    },
+    /// Remove added documentation and its pages
+    Remove { source: String },
+    /// List added documentation
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum AllowlistCommand {
+    /// List allowlisted commands of this project
+    List,
+    /// Ask for confirmation again for an entry
+    Revoke {
+        /// The entry id shown by `allowlist list` (a prefix is enough)
+        #[arg(required_unless_present = "all")]
+        id: Option<String>,
+        /// Revoke every entry of this project
+        #[arg(long, conflicts_with = "id")]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SessionCommand {
+    /// Start a new session and make it active
+    New {
+        /// Letters, digits, '-' and '_'; generated if left out
+        id: Option<String>,
+    },
+    /// List sessions, most recent first
+    List,
+    /// Record into this session from now on
+    Use { id: String },
+    /// Print a session's history (the active one by default)
+    Show { id: Option<String> },
+    /// Delete a session
+    Delete {
+        id: String,
+        /// Don't ask first
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RagCommand {
+    /// Show index size and storage statistics
+    Status,
+    /// Embed the files under a directory into a collection, e.g. docs or
+    /// notes
+    Index {
+        dir: PathBuf,
+        /// Collection to fill
+        #[arg(long, default_value = DEFAULT_COLLECTION)]
+        collection: String,
+    },
+    /// List the collections in the embeddings store and their settings
+    Collections,
+    /// Bundle the index into a file another machine can import
+    Export {
+        /// Output file, e.g. index.tar.zst
+        path: PathBuf,
+    },
+    /// Replace this project's index with an exported bundle
+    Import {
+        path: PathBuf,
+        /// Import even if the bundle was embedded with a different model
+        #[arg(long)]
+        force: bool,
+        /// Don't ask before replacing an existing index (for CI)
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+}
+
+/// Warn that answers come from a partial index while a build is running.
+fn print_index_notice(status: &IndexStatus) {
+    if let Some(error) = &status.error {
+        eprintln!("{}", format!("Indexing failed: {}; answers may be partial", error).red());
+    } else if !status.done {
+        let progress = match status.percent() {
+            Some(percent) => format!("index {}% complete", percent),
+            None => "index is still scanning files".to_string(),
+        };
+        eprintln!("{}", format!("{}, answers may be partial", progress).yellow());
+    }
+}
+
+/// A unified diff with added lines in green and removed ones in red.
+fn colored_diff(diff: &str) -> String {
+    diff.lines()
+        .map(|line| {
+            let colored = if line.starts_with("+++") || line.starts_with("---") {
+                line.bold()
+            } else if line.starts_with('+') {
+                line.green()
+            } else if line.starts_with('-') {
+                line.red()
+            } else if line.starts_with("@@") {
+                line.cyan()
+            } else {
+                line.normal()
+            };
+            format!("{}\n", colored)
+        })
+        .collect()
+}
+
+/// `src/lib.rs:12 [major] message`, with the suggestion indented below.
+fn format_review_comment(comment: &ReviewComment) -> String {
+    let label = format!("[{}]", comment.severity);
+    let label = match comment.severity {
+        Severity::Critical => label.red().bold(),
+        Severity::Major => label.red(),
+        Severity::Minor => label.yellow(),
+        Severity::Nit => label.dimmed(),
+    };
+    let mut text = format!("{} {} {}", comment.location().bold(), label, comment.message);
+    if let Some(suggestion) = &comment.suggestion {
+        text.push_str(&format!("\n    {}", "Suggestion:".green()));
+        for line in suggestion.lines() {
+            text.push_str(&format!("\n        {}", line));
+        }
+    }
+    text
+}
+
+/// How `--script` generates its result.
+struct ScriptOptions {
+    params: Option<Vec<ScriptParam>>,
+    strict: bool,
+    preflight: bool,
+}
+
+/// Where and how `--script` writes its result.
+struct ScriptOutput<'a> {
+    path: Option<&'a std::path::Path>,
+    mode: WriteMode,
+    backup: bool,
+}
+
 pub struct CliApp {
-    rag_service: Option<RagService>,
+    rag_service: Option<Arc<RagService>>,
     cache_path: PathBuf,
     system_info: String,
     config: Config,
+    client: Option<OllamaClient>,
+    /// Where the session's commands run, moved by the `cd`s leading them.
+    work_dir: Mutex<WorkingDir>,
+}
+
+impl Default for CliApp {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CliApp {
@@ -370,27 +714,288 @@ impl CliApp {
             cache_path,
             system_info,
             config,
+            client: None,
+            work_dir: Mutex::new(WorkingDir::new(Self::process_dir())),
+        }
+    }
+
+    fn process_dir() -> PathBuf {
+        std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+    }
+
+    /// Use a fixed client instead of one built from the environment.
+    pub fn with_client(mut self, client: OllamaClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn with_cache_path(mut self, cache_path: PathBuf) -> Self {
+        self.cache_path = cache_path;
+        self
+    }
+
+    /// Use `config` instead of the one loaded from the environment.
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn client(&self) -> Result<OllamaClient> {
+        match &self.client {
+            Some(client) => Ok(client.clone()),
+            None => Ok(OllamaClient::from_config(&self.config)),
+        }
+    }
+
+    /// System details for prompts, plus any allowlisted environment
+    /// variables that are set.
+    fn environment(&self) -> String {
+        match env_context::from_process(&self.config.env_context) {
+            Some(variables) => format!("{}; {}", self.system_info, variables),
+            None => self.system_info.clone(),
+        }
+    }
+
+    /// [`Self::environment`], plus the versions the project's lockfiles pin
+    /// for the dependencies `request` names, so a command that installs or
+    /// upgrades one starts from the version in use.
+    fn environment_for(&self, request: &str) -> String {
+        let dependencies = lockfiles::locked_dependencies(Path::new("."));
+        let named: Vec<String> = lockfiles::mentioned(request, &dependencies)
+            .iter()
+            .map(|d| format!("{} {} ({})", d.name, d.version, d.lockfile))
+            .collect();
+        match named.is_empty() {
+            true => self.environment(),
+            false => format!("{}; locked dependency versions: {}", self.environment(), named.join(", ")),
+        }
+    }
+
+    /// [`Self::environment_for`] `request`, saying the plan for `task` runs on
+    /// remote hosts when it does.
+    fn plan_environment(&self, task: &str, request: &str) -> String {
+        let hosts = self.target_hosts(task);
+        let environment = self.environment_for(request);
+        match hosts.is_empty() {
+            true => environment,
+            false => format!("{}; {}", environment, multi_host::planning_note(&hosts)),
+        }
+    }
+
+    /// The SSH hosts an agent plan for `task` runs on: those given with
+    /// `--hosts`, or else the inventory hosts the task names.
+    pub fn target_hosts(&self, task: &str) -> Vec<String> {
+        if !self.config.remote.hosts.is_empty() {
+            return self.config.remote.hosts.clone();
+        }
+        multi_host::mentioned_hosts(task, &ssh_inventory::load())
+    }
+
+    /// Ask the model for a single shell command for `query`.
+    pub async fn generate_command(&self, query: &str) -> Result<String> {
+        let prompt = format!("You are on a system with: {}. Generate a bash command to: {}. Respond with only the exact command to run, without any formatting, backticks, quotes, or explanation. Ensure the command is complete, syntactically correct, and uses standard Unix tools. For size comparisons, use appropriate units like -BG for gigabytes in df.", self.environment_for(query), query);
+        let response = self.client()?.generate_response(&prompt).await?;
+        Ok(extract_command_from_response(&response))
+    }
+
+    fn rules_path() -> PathBuf {
+        paths::config_dir().join(command_rules::RULES_FILE)
+    }
+
+    /// The user's rules, then the built-in ones; an unreadable rules file
+    /// is reported and skipped.
+    fn command_rules(&self) -> Vec<CommandRule> {
+        let mut rules = command_rules::load_rules(&Self::rules_path()).unwrap_or_else(|e| {
+            eprintln!("{} {}", "Rules file ignored:".yellow(), e);
+            Vec::new()
+        });
+        rules.extend(command_rules::builtin_rules());
+        rules
+    }
+
+    /// Once `command` has been accepted for requests like `query` often
+    /// enough, offer to answer them from the rule table.
+    fn offer_rule(&self, query: &str, command: &str) -> Result<()> {
+        if !std::io::stdin().is_terminal()
+            || command_rules::match_rule(&self.command_rules(), query).is_some()
+        {
+            return Ok(());
+        }
+        let mut entries = Vec::new();
+        for log in AuditLog::all_projects() {
+            entries.extend(log.entries().unwrap_or_default());
+        }
+        let similar = |a: &str, b: &str| Self::semantic_similarity(a, b) >= SEMANTIC_SIMILARITY_THRESHOLD;
+        let Some(mut rule) =
+            command_rules::promotion(&entries, query, command, command_rules::PROMOTE_AFTER, similar)
+        else {
+            return Ok(());
+        };
+        let question = format!(
+            "`{}` was accepted for requests like this {}+ times. Answer them from the local rule table without the model?",
+            rule.command,
+            command_rules::PROMOTE_AFTER
+        );
+        if !ask_confirmation(&question, false)? {
+            return Ok(());
+        }
+        let path = Self::rules_path();
+        let mut rules = command_rules::load_rules(&path)?;
+        let base = rule.name.clone();
+        let mut n = 1;
+        while rules.iter().any(|r| r.name == rule.name) {
+            n += 1;
+            rule.name = format!("{}-{}", base, n);
+        }
+        println!("{} {} ({})", "Added rule".green(), rule.name, path.display());
+        rules.push(rule);
+        command_rules::save_rules(&path, &rules)
+    }
+
+    /// The rule-table command for `query`, if a rule covers it; announced
+    /// so it is clear the model wasn't asked.
+    pub fn rule_for(&self, query: &str) -> Option<CommandRule> {
+        let rules = self.command_rules();
+        let rule = command_rules::match_rule(&rules, query)?.clone();
+        println!("{}", format!("From the local rule table ({}).", rule.name).dimmed());
+        Some(rule)
+    }
+
+    /// Ask the model for a standalone bash script, without markdown fences.
+    pub async fn generate_script(&self, task: &str) -> Result<String> {
+        let prompt = format!(
+            "Generate a POSIX-compatible bash script for a system with: {}.\n\
+Return only the script text, starting with a shebang, no markdown and no explanation.\n\n\
+Task: {}",
+            self.environment_for(task), task
+        );
+        let response = self.client()?.generate_response(&prompt).await?;
+        let mut script = Self::clean_command_output(&response);
+        script.push('\n');
+        Ok(script)
+    }
+
+    /// Like [`Self::generate_script`], but the script starts with an argument
+    /// parsing header for `params` and the model writes only the body, using
+    /// their variables instead of hard-coded values.
+    pub async fn generate_parameterized_script(
+        &self,
+        task: &str,
+        params: &[ScriptParam],
+        strict: bool,
+    ) -> Result<String> {
+        let variables = params
+            .iter()
+            .map(|p| format!("${} ({})", p.var, p.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let prompt = format!(
+            "Write the body of a POSIX-compatible bash script for a system with: {}.\n\
+A header already parses the command line{}. Do not include a shebang, `set` options or argument parsing; refer to the variables, quoted, wherever the task needs those values.\n\
+Return only the script text, no markdown and no explanation.\n\n\
+Task: {}",
+            self.environment_for(task),
+            if variables.is_empty() {
+                String::new()
+            } else {
+                format!(" and sets {}", variables)
+            },
+            script_params::substitute(task, params)
+        );
+        let response = self.client()?.generate_response(&prompt).await?;
+        let body = Self::clean_command_output(&response);
+        Ok(format!(
+            "{}{}\n",
+            script_params::argument_header(params, strict),
+            script_params::strip_preamble(&body)
+        ))
+    }
+
+    fn command_planner(&self) -> Result<LlmCommandPlanner> {
+        Ok(LlmCommandPlanner::new(self.client()?)
+            .with_environment(self.environment())
+            .with_history(Self::agent_history())
+            .with_safety(SafetyService::new().with_ultra_safe(self.config.safe_mode)))
+    }
+
+    /// Summaries of the last successful agent runs in this project, from
+    /// the audit log; empty if it can't be read.
+    fn agent_history() -> Vec<String> {
+        AuditLog::for_project()
+            .recent_agent_runs(REMEMBERED_AGENT_RUNS)
+            .map(|runs| runs.iter().map(|run| run.summary()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Record how a proposed command ended in the project's audit log.
+    /// Failing to write it is reported but doesn't stop the caller.
+    fn audit(mode: &str, prompt: &str, run: &CommandRun, agent_run: Option<&str>) {
+        let entry = AuditEntry {
+            verdict: run
+                .tier
+                .map(|tier| format!("{:?}", tier).to_lowercase())
+                .unwrap_or_default(),
+            outcome: format!("{:?}", run.outcome).to_lowercase(),
+            exit_code: run.exit_code,
+            run: agent_run.map(str::to_string),
+            provenance: run.provenance.label().to_string(),
+            ..AuditEntry::new(
+                mode,
+                &redaction::for_display(prompt),
+                &redaction::for_display(&run.command),
+            )
+        };
+        if let Err(e) = AuditLog::for_project().append(&entry) {
+            eprintln!("{} {}", "Audit log not written:".yellow(), e);
+        }
+    }
+
+    /// Ask the model for a multi-step plan; without steps if no command list could be parsed.
+    pub async fn generate_agent_plan(&self, task: &str) -> Result<CommandPlan> {
+        self.command_planner()?
+            .with_environment(self.plan_environment(task, task))
+            .plan_command(task)
+            .await
+    }
+
+    /// Ask for a new plan after the user rejected `previous` with `feedback`.
+    pub async fn revise_agent_plan(
+        &self,
+        previous: &CommandPlan,
+        feedback: &str,
+    ) -> Result<CommandPlan> {
+        let request = format!("{} {}", previous.description, feedback);
+        self.command_planner()?
+            .with_environment(self.plan_environment(&previous.description, &request))
+            .revise(previous, feedback)
+            .await
+    }
+
+    /// Check a plan before it is shown. The model pass is best-effort: when
+    /// it is unavailable only the local rules are applied.
+    pub async fn review_agent_plan(&self, task: &str, steps: Vec<PlanStep>) -> ReviewedPlan {
+        let as_root = running_as_root();
+        if !offline::is_offline() {
+            if let Ok(client) = self.client() {
+                let review = PlanReviewService::new(client)
+                    .review(task, &self.environment(), &steps, as_root)
+                    .await;
+                match review {
+                    Ok(plan) => return plan,
+                    Err(e) => eprintln!("{} {}", "Plan review incomplete:".yellow(), e),
+                }
+            }
         }
+        let issues = plan_review_service::local_issues(&steps, as_root);
+        ReviewedPlan::new(steps, issues)
     }
 
     fn default_cache_path() -> PathBuf {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let mut path = PathBuf::from(home);
-        path.push(".local");
-        path.push("share");
-        path.push("vibe_cli");
-        let suffix = project_cache_suffix();
-        path.push(format!("{}_cli_cache.json", suffix));
-        path
+        paths::project_data_file("command_cache.bin")
     }
 
     fn default_system_info_path() -> PathBuf {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let mut path = PathBuf::from(home);
-        path.push(".config");
-        path.push("vibe_cli");
-        path.push("system_info.txt");
-        path
+        paths::config_dir().join("system_info.txt")
     }
 
     fn load_or_collect_system_info(path: &PathBuf) -> String {
@@ -449,93 +1054,193 @@ impl CliApp {
         if trimmed.starts_with("```") && trimmed.ends_with("```") {
             // Remove the first and last lines if they are ``` or ```sh
             let lines: Vec<&str> = trimmed.lines().collect();
-            if lines.len() >= 3 {
-                if lines[0].trim().starts_with("```") && lines.last().unwrap().trim() == "```" {
+            if lines.len() >= 3
+                && lines[0].trim().starts_with("```") && lines.last().is_some_and(|line| line.trim() == "```") {
                     return lines[1..lines.len() - 1].join("\n").trim().to_string();
                 }
-            }
         }
         trimmed.to_string()
     }
 
-    fn load_cached(&self, prompt: &str) -> Result<Option<String>> {
-        if !self.cache_path.exists() {
-            return Ok(None);
-        }
+    fn command_cache(&self) -> CacheStore {
+        CacheStore::new(&self.cache_path, self.config.caches.command)
+            .with_legacy_path(paths::project_data_file("cli_cache.json"))
+    }
+
+    pub fn load_cached(&self, prompt: &str) -> Result<Option<String>> {
+        Ok(self.cached_command(prompt)?.map(|entry| entry.value))
+    }
 
-        let data = std::fs::read_to_string(&self.cache_path)?;
-        let mut cache: CacheFile = serde_json::from_str(&data).unwrap_or_default();
+    /// The cache entry for `prompt`, its command cleaned: an exact match
+    /// first, then the most similar prompt above the threshold.
+    fn cached_command(&self, prompt: &str) -> Result<Option<CacheEntry>> {
+        let entry = self.command_cache().lookup_entry(|cached| {
+            if cached == prompt {
+                return Some(f64::INFINITY);
+            }
+            let similarity = Self::semantic_similarity(prompt, cached);
+            (similarity >= SEMANTIC_SIMILARITY_THRESHOLD).then_some(similarity)
+        })?;
+        Ok(entry.map(|entry| CacheEntry {
+            value: Self::clean_command_output(&entry.value),
+            ..entry
+        }))
+    }
 
-        // Remove expired entries
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        cache
-            .entries
-            .retain(|entry| now - entry.timestamp < CACHE_TTL_SECONDS);
+    pub fn save_cached(&self, prompt: &str, command: &str) -> Result<()> {
+        self.command_cache().put_scoped(
+            prompt,
+            &Self::clean_command_output(command),
+            Some(&cache_check::scope(&self.session_dir())),
+        )
+    }
 
-        // Save cleaned cache back to disk
-        if let Some(parent) = self.cache_path.parent() {
-            std::fs::create_dir_all(parent)?;
+    /// What speaks against reusing `cached` in the session's directory.
+    pub fn cache_problems(&self, cached: &CacheEntry) -> Vec<Problem> {
+        let missing_programs = script_preflight::required_binaries(&cached.value)
+            .into_iter()
+            .filter(|program| !clipboard::on_path(program))
+            .collect();
+        cache_check::problems(&cached.value, cached.scope.as_deref(), &self.session_dir(), missing_programs)
+    }
+
+    /// Whether to reuse the cached command for `query`, after checking it
+    /// still fits: one that can't run here is passed over for a new one,
+    /// and one that may be stale is shown with its problems. The run's own
+    /// confirmation is the only question asked.
+    fn offer_cached(&self, query: &str, cached: &CacheEntry) -> Reuse {
+        let problems = self.cache_problems(cached);
+        let reasons: Vec<String> = problems.iter().map(Problem::describe).collect();
+        let reuse = cache_check::reuse(&problems);
+        if reuse == Reuse::Regenerate {
+            println!(
+                "{}",
+                format!(
+                    "Cached command `{}` can't run here ({}); generating a new one.",
+                    cached.value,
+                    reasons.join("; ")
+                )
+                .yellow()
+            );
+            return reuse;
         }
-        let serialized = serde_json::to_string_pretty(&cache)?;
-        std::fs::write(&self.cache_path, serialized)?;
+        println!("{}", format!("Found cached command: {}", cached.value).green());
+        if cached.key != query {
+            println!("{}", format!("Cached for a similar request: {}", cached.key).dimmed());
+        }
+        if reuse == Reuse::Warn {
+            println!("{} {}", "It may be stale:".yellow().bold(), reasons.join("; "));
+        }
+        reuse
+    }
 
-        // First try exact match
-        for entry in &cache.entries {
-            if entry.prompt == prompt {
-                return Ok(Some(Self::clean_command_output(&entry.command)));
+    pub async fn run(&mut self, cli: Cli) -> Result<()> {
+        if let Some(path) = &cli.record {
+            cassette::install(Cassette::record(path)?)?;
+        } else if let Some(path) = &cli.replay {
+            cassette::install(Cassette::replay(path)?)?;
+        }
+        if let Some(dir) = &cli.cwd {
+            if !dir.is_dir() {
+                return Err(anyhow::anyhow!("--cwd {}: not a directory", dir.display()));
             }
+            std::env::set_current_dir(dir)?;
+            self.work_dir = Mutex::new(WorkingDir::new(Self::process_dir()));
         }
-
-        // Then try semantic similarity
-        let mut best_match: Option<&CacheEntry> = None;
-        let mut best_similarity = 0.0;
-
-        for entry in &cache.entries {
-            let similarity = Self::semantic_similarity(prompt, &entry.prompt);
-            if similarity > best_similarity && similarity >= SEMANTIC_SIMILARITY_THRESHOLD {
-                best_similarity = similarity;
-                best_match = Some(entry);
+        prompt_report::set_show_prompt(cli.show_prompt);
+        redaction::set_show_secrets(cli.show_secrets);
+        prompt_report::set_verbose(cli.verbose);
+        generation_stats::set_show_stats(self.config.show_stats);
+        offline::set_offline(cli.offline);
+        self.config.copy_to_clipboard = cli.copy;
+        self.config.sandbox.scrub_env |= cli.scrub_env;
+        if let Some(hosts) = &cli.hosts {
+            self.config.remote.hosts = multi_host::parse_hosts(hosts);
+        }
+        if let Some(parallel) = cli.parallel {
+            self.config.remote.parallel = parallel.max(1);
+        }
+        if cli.no_color {
+            accessibility::disable_color();
+        }
+        self.config.accessible |= cli.accessible;
+        accessibility::set_accessible(self.config.accessible);
+        if let Some(top_k) = cli.top_k {
+            self.config.rag_top_k = top_k.max(1);
+        }
+        if let Some(min_score) = cli.min_score {
+            self.config.rag_min_score = min_score;
+        }
+        self.config.rag_fresh = cli.fresh;
+        if let Some(max_chars) = cli.max_context_chars {
+            self.config.rag_max_context_chars = max_chars;
+        }
+        if let Some(names) = cli.collections.as_deref().map(parse_collections) {
+            if !names.is_empty() {
+                self.config.rag_collections = names;
             }
         }
-
-        if let Some(entry) = best_match {
-            Ok(Some(Self::clean_command_output(&entry.command)))
-        } else {
-            Ok(None)
+        if let Some(target) = &cli.send_to_pane {
+            PaneTarget::parse(target)?;
+            self.config.send_to_pane = Some(target.clone());
         }
-    }
-
-    fn save_cached(&self, prompt: &str, command: &str) -> Result<()> {
-        let mut cache = if self.cache_path.exists() {
-            let data = std::fs::read_to_string(&self.cache_path).unwrap_or_default();
-            serde_json::from_str::<CacheFile>(&data).unwrap_or_default()
-        } else {
-            CacheFile::default()
-        };
-
-        cache.entries.push(CacheEntry {
-            prompt: prompt.to_string(),
-            command: Self::clean_command_output(command),
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-        });
-
-        if let Some(parent) = self.cache_path.parent() {
-            std::fs::create_dir_all(parent)?;
+        self.config.detach = cli.detach;
+        if self.config.encrypt_at_rest {
+            at_rest::enable()?;
+        }
+        if let (Some(client), Some(active)) = (&self.client, cassette::active()) {
+            self.client = Some(client.with_cassette(active));
         }
 
-        let serialized = serde_json::to_string_pretty(&cache)?;
-        std::fs::write(&self.cache_path, serialized)?;
-
-        Ok(())
-    }
+        if let Some(command) = cli.command {
+            return match command {
+                Command::Ask { question } => self.handle_ask(&question.join(" ")).await,
+                Command::Recall { question } => self.handle_recall(&question.join(" ")).await,
+                Command::Rag { command } => self.handle_rag_command(command).await,
+                Command::Docs { command } => self.handle_docs_command(command).await,
+                Command::Version { check } => self.handle_version(check).await,
+                Command::SelfUpdate => self.handle_self_update().await,
+                Command::Watch {
+                    command,
+                    every,
+                    until,
+                    max_checks,
+                } => self.handle_watch(&command, &every, &until, max_checks).await,
+                Command::Review {
+                    staged,
+                    branch,
+                    json,
+                    no_context,
+                } => {
+                    let diff = match (&branch, staged) {
+                        (Some(branch), _) => review_service::diff_branch(branch),
+                        (None, true) => review_service::diff_staged(),
+                        (None, false) => review_service::diff_working_tree(),
+                    };
+                    self.handle_review(diff, json, !no_context).await
+                }
+                Command::GenTests {
+                    target,
+                    run,
+                    test_command,
+                } => self.handle_gen_tests(&target, run, test_command).await,
+                Command::GenDocs {
+                    file,
+                    no_context,
+                    backup,
+                } => self.handle_gen_docs(&file, !no_context, backup).await,
+                Command::Session { command } => self.handle_session_command(command),
+                Command::Allowlist { command } => self.handle_allowlist_command(command),
+                Command::Aliasify { min_count } => self.handle_aliasify(min_count).await,
+                Command::Audit { command } => self.handle_audit_command(command),
+                Command::Policy { command } => self.handle_policy_command(command),
+                Command::Rules { command } => self.handle_rules_command(command),
+                Command::Jobs { command } => {
+                    self.handle_jobs_command(command.unwrap_or(JobsCommand::List)).await
+                }
+            };
+        }
 
-    pub async fn run(&mut self, cli: Cli) -> Result<()> {
         let args_str = cli.args.join(" ");
         if cli.chat {
             if args_str.trim().is_empty() {
@@ -547,74 +1252,119 @@ impl CliApp {
         } else if cli.agent {
             self.handle_agent(&args_str).await
         } else if cli.explain {
-            self.handle_explain(&args_str).await
+            if let Some(files) = &cli.diff {
+                let diff = diff_explain_service::diff_files(&files[0], &files[1]);
+                self.handle_explain_diff(diff, cli.with_context).await
+            } else if let Some(range) = &cli.rev {
+                let diff = diff_explain_service::diff_revisions(range, &cli.args);
+                self.handle_explain_diff(diff, cli.with_context).await
+            } else {
+                self.handle_explain(&args_str, cli.pages.as_deref()).await
+            }
         } else if cli.rag {
             self.handle_rag(&args_str).await
+        } else if cli.script {
+            let mode = if cli.append {
+                WriteMode::Append
+            } else {
+                WriteMode::Overwrite
+            };
+            let options = ScriptOptions {
+                params: cli.with_args.as_ref().map(|names| {
+                    let names = if names.is_empty() {
+                        script_params::placeholders(&args_str)
+                    } else {
+                        names.clone()
+                    };
+                    script_params::params(&names)
+                }),
+                strict: !cli.no_strict,
+                preflight: !cli.no_preflight,
+            };
+            let output = ScriptOutput {
+                path: cli.output.as_deref(),
+                mode,
+                backup: cli.backup,
+            };
+            self.handle_script(&args_str, options, output).await
         } else if cli.context {
             self.handle_context(&args_str).await
-        } else {
-            // Default: general query
+        } else if cli.no_route || command_rules::match_rule(&self.command_rules(), &args_str).is_some() {
+            // A request the rule table answers is a command, even when
+            // phrased as a question.
             self.handle_query(&args_str).await
+        } else {
+            // Default: whichever mode the input calls for
+            let mut router = IntentRouter::new();
+            if self.config.route_with_model && !offline::is_offline() {
+                router = router.with_model(self.client()?);
+            }
+            let intent = router.route(&args_str).await?;
+            let mode = match intent {
+                Intent::Command => return self.handle_query(&args_str).await,
+                Intent::Question => "a question",
+                Intent::Code => "a question about this project (RAG)",
+                Intent::Plan => "a multi-step task (agent)",
+            };
+            eprintln!(
+                "{}",
+                format!("Handling this as {}; pass --no-route for a single command.", mode)
+                    .dimmed()
+            );
+            match intent {
+                Intent::Question => self.handle_ask(&args_str).await,
+                Intent::Code => self.handle_rag(&args_str).await,
+                _ => self.handle_agent(&args_str).await,
+            }
         }
     }
 
     async fn handle_chat(&self) -> Result<()> {
-        use dialoguer::{theme::ColorfulTheme, Input};
-        println!("Command execution mode. Type 'exit' to quit.");
+        use dialoguer::theme::{ColorfulTheme, SimpleTheme, Theme};
+        use dialoguer::Input;
+        println!("Command execution mode. Type 'exit' to quit, '/stats' for model usage.");
+        let colorful = ColorfulTheme::default();
+        let theme: &dyn Theme = if accessibility::is_accessible() {
+            &SimpleTheme
+        } else {
+            &colorful
+        };
+        if let Some(session) = self.active_session() {
+            let earlier = session.exchanges("chat");
+            if !earlier.is_empty() {
+                println!("Session '{}', continuing after:", session.id);
+                for (query, command) in earlier.iter().rev().take(3).rev() {
+                    println!("  {} {}", query.dimmed(), command);
+                }
+            }
+        }
         loop {
-            let input: String = Input::with_theme(&ColorfulTheme::default())
+            let input: String = Input::with_theme(theme)
                 .with_prompt("Query")
                 .interact_text()?;
             if input.to_lowercase() == "exit" {
                 break;
             }
-            // Use the same logic as handle_query
-            let client = infrastructure::ollama_client::OllamaClient::new()?;
-            let prompt = format!("You are on a system with: {}. Generate a bash command to: {}. Respond with only the exact command to run, without any formatting, backticks, quotes, or explanation. Ensure the command is complete, syntactically correct, and uses standard Unix tools. For size comparisons, use appropriate units like -BG for gigabytes in df.", self.system_info, input);
-            let response = client.generate_response(&prompt).await?;
-            let command = extract_command_from_response(&response);
-            println!("{}", format!("Command: {}", command).green());
-            if ask_confirmation("Run this command?", false)? {
-                let output = std::process::Command::new("bash")
-                    .arg("-c")
-                    .arg(&command)
-                    .output()?;
-                println!("{}", String::from_utf8_lossy(&output.stdout));
-                if !output.status.success() {
-                    println!(
-                        "{}",
-                        format!(
-                            "Command failed: {}",
-                            String::from_utf8_lossy(&output.stderr)
-                        )
-                        .red()
-                    );
-                }
-            } else {
-                println!("{}", "Command execution cancelled.".yellow());
+            if input.trim() == "/stats" {
+                println!("{}", generation_stats::totals().format());
+                continue;
             }
+            let (command, provenance) = match self.rule_for(&input) {
+                Some(rule) => (rule.command, Provenance::Rule(rule.name)),
+                None => (self.generate_command(&input).await?, Provenance::Model),
+            };
+            let (command, _) = self.run_refinable("chat", &input, command, provenance, false).await?;
+            self.update_session(|session| session.add_exchange("chat", &input, &command));
+            self.remember(MemoryKind::Chat, &input, &format!("Suggested command: {}", command))
+                .await;
         }
         Ok(())
     }
 
     async fn handle_agent(&self, task: &str) -> Result<()> {
-        let client = infrastructure::ollama_client::OllamaClient::new()?;
-        let prompt = format!(
-            "You are an assistant that turns a user's goal into a sequence of POSIX shell commands that can be run one-by-one with confirmation in between.\n\
-Environment: {}.\n\
-Constraints:\n\
-- Respond ONLY with a JSON array of strings. Each element must be a complete shell command ready to run.\n\
-- No prose, no markdown, no comments. If you cannot produce a valid JSON array, respond with [].\n\
-- Prefer Debian/Ubuntu defaults (apt/apt-get, systemctl) unless otherwise implied.\n\
-- Use real paths; avoid placeholders like /path/to.\n\
-- Keep commands minimal and idempotent (check state before changing it).\n\n\
-User request: {}",
-            self.system_info, task
-        );
-        let response = client.generate_response(&prompt).await?;
-        let commands = parse_agent_plan(&response);
+        let proposed = self.generate_agent_plan(task).await?;
 
-        if commands.is_empty() {
+        if proposed.steps.is_empty() {
             println!(
                 "{}",
                 "Model did not return a runnable command list (expected JSON array).".red()
@@ -622,103 +1372,253 @@ User request: {}",
             return Ok(());
         }
 
+        let (mut plan, review) = self.reviewed_agent_plan(proposed).await?;
         println!("\n{}", "Proposed plan:".green());
-        for (i, cmd) in commands.iter().enumerate() {
-            println!("  {} {}", format!("[{}]", i + 1).blue(), cmd);
+        Self::print_plan(&plan, &review);
+
+        while !ask_confirmation("Go ahead with this plan?", true)? {
+            let mut feedback = String::new();
+            eprint!("What should change? (Enter to cancel): ");
+            io::stdout().flush()?;
+            io::stdin().read_line(&mut feedback)?;
+            let feedback = feedback.trim();
+            if feedback.is_empty() {
+                println!("{}", "Plan cancelled.".yellow());
+                return Ok(());
+            }
+            eprintln!("Regenerating with feedback...");
+            let revised = self.revise_agent_plan(&plan, feedback).await?;
+            if revised.steps.is_empty() {
+                println!("{}", "Model did not return a revised plan; keeping the current one.".red());
+                continue;
+            }
+            let (revised, review) = self.reviewed_agent_plan(revised).await?;
+            let lines = |steps: &[PlanStep]| steps.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            println!("\n{}", "Changes to the plan:".green());
+            print!("{}", plan_diff::render(&lines(&plan.steps), &lines(&revised.steps)));
+            println!("\n{}", "Revised plan:".green());
+            Self::print_plan(&revised, &review);
+            plan = revised;
+        }
+
+        let steps = plan.steps.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        self.update_session(|session| session.add_exchange("agent", task, &steps.join("\n")));
+
+        let run_id = format!(
+            "{}-{}",
+            plan.id,
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or_default()
+        );
+        let hosts = self.target_hosts(task);
+        if !hosts.is_empty() {
+            return self.run_on_hosts(task, &plan, &hosts, &run_id).await;
+        }
+        let commands: Vec<&str> = plan
+            .steps
+            .iter()
+            .flat_map(|step| {
+                [
+                    Some(&step.command),
+                    step.check.as_ref(),
+                    step.when.as_ref(),
+                    step.on_failure.as_ref(),
+                ]
+            })
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        let tracked = env_snapshot::mentioned_paths(&commands);
+        eprintln!("{}", "Taking a snapshot of packages, services and the files the plan names...".dimmed());
+        let before = EnvSnapshot::capture(&tracked);
+
+        let executed = agent_service::execute(
+            &plan.steps,
+            &mut AgentSteps {
+                app: self,
+                task,
+                run_id: run_id.clone(),
+                last_run: None,
+                shell: None,
+                shell_unavailable: false,
+                expected: plan.steps.iter().map(|step| step.expected_secs).collect(),
+                step: 0,
+            },
+        )
+        .await;
+        step_budget::wait_for_background();
+
+        // Reported even when a step errored: earlier steps may have run.
+        let changes = before.changes(&EnvSnapshot::capture(&tracked));
+        Self::report_changes(task, &run_id, changes);
+        executed?;
+        Ok(())
+    }
+
+    /// Run `plan` on each of `hosts` over `ssh`. Hosts confirmed step by step
+    /// go one after another, then the unattended ones run the plan
+    /// `parallel` at a time; the step × host matrix is printed as each
+    /// host or batch finishes.
+    async fn run_on_hosts(&self, task: &str, plan: &CommandPlan, hosts: &[String], run_id: &str) -> Result<()> {
+        let matrix = Mutex::new(StepMatrix::new(plan.steps.len(), hosts));
+        let (attended, unattended): (Vec<&String>, Vec<&String>) = hosts
+            .iter()
+            .partition(|host| multi_host::confirm_policy(host, &self.config.remote.unattended) == HostConfirm::Step);
+        let print_matrix = || {
+            println!();
+            for line in matrix.lock().unwrap_or_else(|e| e.into_inner()).render() {
+                println!("  {}", line);
+            }
+        };
+
+        for host in attended {
+            println!("\n{} {}", "Host".cyan().bold(), host.cyan().bold());
+            let mut config = self.config.clone();
+            config.sandbox.host = Some(host.clone());
+            let mut steps = HostSteps {
+                app: self,
+                config,
+                task,
+                run_id,
+                host,
+                matrix: &matrix,
+                last_run: None,
+            };
+            agent_service::execute(&plan.steps, &mut steps).await?;
+            print_matrix();
+        }
+
+        let parallel = self.config.remote.parallel.max(1);
+        for batch in unattended.chunks(parallel) {
+            let names: Vec<&str> = batch.iter().map(|host| host.as_str()).collect();
+            println!("\n{} {}", "Running the approved plan unattended on".cyan(), names.join(", ").cyan());
+            std::thread::scope(|scope| {
+                for host in batch {
+                    let mut steps = UnattendedHostSteps {
+                        config: self.config.clone(),
+                        task,
+                        run_id,
+                        host,
+                        matrix: &matrix,
+                    };
+                    let steps_ref = &plan.steps;
+                    scope.spawn(move || {
+                        let ran = tokio::runtime::Builder::new_current_thread()
+                            .build()
+                            .map_err(anyhow::Error::from)
+                            .and_then(|runtime| runtime.block_on(agent_service::execute(steps_ref, &mut steps)));
+                        if let Err(e) = ran {
+                            eprintln!("{} {}", format!("[{}]", steps.host).red(), e);
+                        }
+                    });
+                }
+            });
+            print_matrix();
+        }
+
+        let matrix = matrix.into_inner().unwrap_or_else(|e| e.into_inner());
+        let failed = matrix.failed_hosts();
+        if failed.is_empty() {
+            println!("\n{}", "No step failed on any host.".green());
+        } else {
+            println!("\n{} {}", "Steps failed on:".red().bold(), failed.join(", ").red());
         }
+        Ok(())
+    }
 
-        for (i, cmd) in commands.iter().enumerate() {
+    /// Print what an agent run changed and record it in the audit log with
+    /// the run's steps.
+    fn report_changes(task: &str, run_id: &str, changes: Vec<String>) {
+        if changes.is_empty() {
             println!(
-                "\n{} {}",
-                "Step".green().bold(),
-                format!("{}:", i + 1).green().bold()
+                "\n{}",
+                "No changes to packages, enabled services or the files the plan names.".dimmed()
             );
-            println!("{} {}", "Suggested command:".green(), cmd.yellow());
-            let accept = ask_confirmation("Run this command?", false)?;
-            if !accept {
-                println!("{}", "Skipping this step.".yellow());
-                continue;
+        } else {
+            println!("\n{}", "What changed:".green().bold());
+            for change in &changes {
+                println!("  - {}", change);
             }
-            let status = std::process::Command::new("bash")
-                .arg("-c")
-                .arg(cmd)
-                .status()?;
-            if status.success() {
-                println!("{}", "Command completed successfully.".green());
-            } else {
+        }
+        let entry = AuditEntry {
+            outcome: "report".to_string(),
+            run: Some(run_id.to_string()),
+            changes,
+            ..AuditEntry::new("agent", task, "")
+        };
+        if let Err(e) = AuditLog::for_project().append(&entry) {
+            eprintln!("{} {}", "Audit log not written:".yellow(), e);
+        }
+    }
+
+    /// `plan` with its reviewed steps (and safety checks to match), and the
+    /// review itself for display.
+    async fn reviewed_agent_plan(
+        &self,
+        plan: CommandPlan,
+    ) -> Result<(CommandPlan, ReviewedPlan)> {
+        let review = self
+            .review_agent_plan(&plan.description, plan.steps.clone())
+            .await;
+        let plan = self.command_planner()?.with_steps(plan, review.steps.clone());
+        Ok((plan, review))
+    }
+
+    fn print_plan(plan: &CommandPlan, review: &ReviewedPlan) {
+        for (i, step) in plan.steps.iter().enumerate() {
+            println!("  {} {}", format!("[{}]", i + 1).blue(), step.command);
+            if let Some(check) = &step.check {
+                println!("      {} {}", "done if:".cyan(), check);
+            }
+            if let Some(condition) = &step.when {
+                println!("      {} {}", "only if:".cyan(), condition);
+            }
+            if let Some(fallback) = &step.on_failure {
+                println!("      {} {}", "on failure:".cyan(), fallback);
+            }
+            if let Some(secs) = step.expected_secs {
+                println!(
+                    "      {} {}",
+                    "expected:".cyan(),
+                    notify::format_elapsed(Duration::from_secs(secs))
+                );
+            }
+            if step.command != review.original[i].command {
                 println!(
-                    "{} (exit status: {:?})",
-                    "Command failed.".red(),
-                    status.code()
+                    "      {} {}",
+                    "revised from:".yellow(),
+                    review.original[i].command.dimmed()
                 );
             }
+            for issue in review.issues_for(i) {
+                println!("      {} {}", "note:".yellow(), issue.problem);
+            }
+        }
+        let failed: Vec<_> = plan.safety_checks.iter().filter(|c| !c.passed).collect();
+        if !failed.is_empty() {
+            println!("\n{}", "Steps that will be blocked:".red().bold());
+            for check in failed {
+                println!("  - {}", check.check_type.red());
+            }
         }
-        Ok(())
     }
 
-    async fn handle_explain(&self, file: &str) -> Result<()> {
+    async fn handle_explain(&self, file: &str, pages: Option<&str>) -> Result<()> {
         let path = std::path::Path::new(file);
-        let content = if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-            match ext.to_lowercase().as_str() {
-                "pdf" => match pdf_extract::extract_text(file) {
-                    Ok(text) => text,
-                    Err(e) => {
-                        println!("Error extracting text from PDF '{}': {}", file, e);
-                        return Ok(());
-                    }
-                },
-                "docx" => {
-                    match std::fs::read(file) {
-                        Ok(bytes) => {
-                            match read_docx(&bytes) {
-                                Ok(docx) => {
-                                    let mut text = String::new();
-                                    for child in &docx.document.children {
-                                        match child {
-                                            DocumentChild::Paragraph(p) => {
-                                                text.push_str(&p.raw_text());
-                                                text.push('\n');
-                                            }
-                                            DocumentChild::Table(_t) => {
-                                                // For tables, we could extract text from cells
-                                                // For now, just add a placeholder
-                                                text.push_str("[Table content not extracted]\n");
-                                            }
-                                            _ => {
-                                                // Skip other elements for now
-                                            }
-                                        }
-                                    }
-                                    text
-                                }
-                                Err(e) => {
-                                    println!("Error parsing DOCX '{}': {}", file, e);
-                                    return Ok(());
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            println!("Error reading DOCX file '{}': {}", file, e);
-                            return Ok(());
-                        }
-                    }
-                }
-
-                _ => match std::fs::read_to_string(file) {
-                    Ok(text) => text,
-                    Err(_) => {
-                        println!("Error: Cannot read file '{}' as text. Supported formats: text files, PDF, DOCX.", file);
-                        return Ok(());
-                    }
-                },
-            }
-        } else {
-            match std::fs::read_to_string(file) {
-                Ok(text) => text,
-                Err(_) => {
-                    println!("Error: Cannot read file '{}' as text. Supported formats: text files, PDF, DOCX.", file);
-                    return Ok(());
-                }
+        let is_pdf = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"));
+        if pages.is_some() && !is_pdf {
+            println!("Error: --pages only applies to PDF files.");
+            return Ok(());
+        }
+        let content = match load_document(path, pages) {
+            Ok(text) => text,
+            Err(e) => {
+                println!("Error: {}", e);
+                return Ok(());
             }
         };
 
@@ -730,282 +1630,1871 @@ User request: {}",
         let prompt = format!("Explain this content in detail:\n\n{}", content);
 
         // Check cache first
-        if let Some(cached_response) = self.load_cached_explain(&prompt)? {
+        if let Some(cached_response) = self.explain_cache().get(&prompt)? {
             println!("{}", cached_response);
             return Ok(());
         }
 
         eprintln!("Analyzing file content...");
-        let client = infrastructure::ollama_client::OllamaClient::new()?;
-        let response = client.generate_response(&prompt).await?;
+        let explanation = ExplainService::new(self.client()?)
+            .explain(
+                file,
+                &content,
+                |done, total| {
+                    // Rewriting one line in place is unreadable to screen readers
+                    if accessibility::is_accessible() {
+                        eprintln!("Explained part {} of {}", done, total);
+                    } else {
+                        eprint!("\rExplained part {}/{} (Ctrl-C to stop early)", done, total);
+                    }
+                },
+                async {
+                    let _ = tokio::signal::ctrl_c().await;
+                },
+            )
+            .await?;
+        if explanation.parts_total > 1 && !accessibility::is_accessible() {
+            eprintln!();
+        }
+
+        if !explanation.complete {
+            eprintln!(
+                "{}",
+                format!(
+                    "Stopped after {} of {} parts; showing notes on the parts read so far.",
+                    explanation.parts_done, explanation.parts_total
+                )
+                .yellow()
+            );
+            println!("{}", explanation.text);
+            return Ok(());
+        }
 
         // Cache the response
-        self.save_cached_explain(&prompt, &response)?;
+        self.explain_cache().put(&prompt, &explanation.text)?;
+        self.remember(MemoryKind::Explain, &format!("Explanation of {}", file), &explanation.text)
+            .await;
 
-        println!("{}", response);
+        println!("{}", explanation.text);
         Ok(())
     }
 
-    async fn handle_rag(&mut self, question: &str) -> Result<()> {
-        if let Some(cached_response) = self.load_cached_rag(question)? {
-            if ask_confirmation("Cached answer found. Use it?", true)? {
-                println!("{}", cached_response);
+    async fn handle_explain_diff(&self, diff: Result<String>, with_context: bool) -> Result<()> {
+        let diff = match diff {
+            Ok(diff) => diff,
+            Err(e) => {
+                println!("Error: {}", e);
                 return Ok(());
             }
+        };
+        if diff.trim().is_empty() {
+            println!("No differences.");
+            return Ok(());
         }
-
-        if self.rag_service.is_none() {
-            eprintln!("Analyzing query and scanning codebase...");
-            let client = OllamaClient::new()?;
-            self.rag_service = Some(RagService::new(".", &self.config.db_path, client, self.config.clone()).await?);
-            let keywords = Self::keywords_from_text(question);
-            self.rag_service
-                .as_ref()
-                .unwrap()
-                .build_index_for_keywords(&keywords)
-                .await?;
+        let context = if with_context {
+            self.diff_context(&diff).await
+        } else {
+            Vec::new()
+        };
+        let prompt = diff_explain_service::diff_prompt(&diff, &context);
+        if let Some(cached_response) = self.explain_cache().get(&prompt)? {
+            print!("{}", Highlighter::default().render_answer(&cached_response));
+            return Ok(());
         }
+        eprintln!("Explaining the change...");
+        let explanation = DiffExplainService::new(self.client()?)
+            .explain(&diff, &context)
+            .await?;
+        self.explain_cache().put(&prompt, &explanation)?;
+        print!("{}", Highlighter::default().render_answer(&explanation));
+        let files: Vec<String> = review_service::split_diff(&diff)
+            .into_iter()
+            .map(|file| file.path)
+            .collect();
+        let title = format!("Explanation of a change to {}", files.join(", "));
+        self.remember(MemoryKind::Explain, &title, &explanation).await;
+        Ok(())
+    }
 
-        let mut feedback = String::new();
-        loop {
-            eprintln!("Thinking...");
-            let response = self
-                .rag_service
-                .as_ref()
-                .unwrap()
-                .query_with_feedback(question, &feedback)
-                .await?;
+    /// Indexed chunks defining what `diff` changes; empty, with a warning,
+    /// if the index can't be used.
+    async fn diff_context(&self, diff: &str) -> Vec<String> {
+        // Chunks of surrounding code sent with a diff.
+        const DIFF_CONTEXT_CHUNKS: usize = 8;
+        let identifiers = diff_explain_service::changed_identifiers(diff);
+        if identifiers.is_empty() {
+            return Vec::new();
+        }
+        let context = async {
+            let service = self.keyword_index(&identifiers).await?;
+            service
+                .related_context(&identifiers.join(" "), DIFF_CONTEXT_CHUNKS)
+                .await
+        };
+        match context.await {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                eprintln!("{} {}", "No related definitions:".yellow(), e);
+                Vec::new()
+            }
+        }
+    }
 
-            println!("{}", response);
+    /// The RAG index, brought up to date for files named like `identifiers`.
+    async fn keyword_index(&self, identifiers: &[String]) -> Result<RagService> {
+        let service =
+            RagService::collection(".", DEFAULT_COLLECTION, self.client()?, &self.config).await?;
+        eprintln!("Looking up related definitions...");
+        service.build_index_for_keywords(identifiers).await?;
+        Ok(service)
+    }
 
-            if ask_confirmation("Satisfied with this response?", true)? {
-                self.save_cached_rag(question, &response)?;
-                break;
-            } else {
-                feedback.clear();
-                eprint!("Provide feedback for improvement: ");
-                io::stdout().flush()?;
-                io::stdin().read_line(&mut feedback)?;
-                feedback = feedback.trim().to_string();
-                eprintln!("Regenerating with feedback...");
+    async fn handle_review(&self, diff: Result<String>, json: bool, with_context: bool) -> Result<()> {
+        let diff = match diff {
+            Ok(diff) => diff,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return Ok(());
             }
+        };
+        let comments = self.review_diff(&diff, with_context).await?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&comments)?);
+            return Ok(());
+        }
+        if comments.is_empty() {
+            println!("{}", "No review comments.".green());
+            return Ok(());
+        }
+        for comment in &comments {
+            println!("{}", format_review_comment(comment));
         }
-
         Ok(())
     }
 
-    async fn handle_context(&mut self, path: &str) -> Result<()> {
-        eprintln!("Loading context from {}...", path);
-        let client = OllamaClient::new()?;
-        self.rag_service = Some(RagService::new(path, &self.config.db_path, client, self.config.clone()).await?);
-        self.rag_service.as_ref().unwrap().build_index().await?;
-        eprintln!("Context loaded from {}", path);
-        self.handle_chat().await
+    /// Review comments on `diff`, most severe first. With `with_context`,
+    /// code related to each file's changes is looked up in the RAG index;
+    /// if the index can't be used, files are reviewed without it.
+    pub async fn review_diff(&self, diff: &str, with_context: bool) -> Result<Vec<ReviewComment>> {
+        // Chunks of related code sent with each file.
+        const REVIEW_CONTEXT_CHUNKS: usize = 6;
+        let files = review_service::split_diff(diff);
+        let mut index = None;
+        if with_context && !files.is_empty() {
+            let identifiers = diff_explain_service::changed_identifiers(diff);
+            if !identifiers.is_empty() {
+                match self.keyword_index(&identifiers).await {
+                    Ok(service) => index = Some(service),
+                    Err(e) => eprintln!("{} {}", "No related code:".yellow(), e),
+                }
+            }
+        }
+        let reviewer = ReviewService::new(self.client()?);
+        let mut comments = Vec::new();
+        for (i, file) in files.iter().enumerate() {
+            eprintln!("Reviewing {} ({}/{})...", file.path, i + 1, files.len());
+            let identifiers = diff_explain_service::changed_identifiers(&file.text());
+            let context = match &index {
+                Some(service) if !identifiers.is_empty() => service
+                    .related_context(&identifiers.join(" "), REVIEW_CONTEXT_CHUNKS)
+                    .await
+                    .unwrap_or_default(),
+                _ => Vec::new(),
+            };
+            comments.extend(reviewer.review_file(file, &context).await?);
+        }
+        review_service::sort_comments(&mut comments);
+        Ok(comments)
     }
 
-    async fn handle_query(&mut self, query: &str) -> Result<()> {
-        if let Ok(Some(cached_command)) = self.load_cached(query) {
-            println!(
-                "{}",
-                format!("Found cached command: {}", cached_command).green()
-            );
-            if ask_confirmation("Use cached command?", true)? {
-                let output = std::process::Command::new("bash")
-                    .arg("-c")
-                    .arg(&cached_command)
-                    .output()?;
-                println!("{}", String::from_utf8_lossy(&output.stdout));
-                if !output.status.success() {
-                    println!(
-                        "{}",
-                        format!(
-                            "Command failed: {}",
-                            String::from_utf8_lossy(&output.stderr)
-                        )
-                        .red()
-                    );
-                }
+    async fn handle_gen_tests(
+        &self,
+        spec: &str,
+        run: bool,
+        test_command: Option<String>,
+    ) -> Result<()> {
+        let target = TestTarget::parse(spec)?;
+        let index = self.keyword_index(&target.keywords()).await?;
+        let context = match test_gen_service::gather(&index, &target).await {
+            Ok(context) => context,
+            Err(e) => {
+                println!("Error: {}", e);
                 return Ok(());
             }
+        };
+        let file = test_gen_service::test_file(&context.source);
+        let existing = std::fs::read_to_string(&file.path).ok();
+        eprintln!("Writing tests for {}...", target.function);
+        let tests = TestGenService::new(self.client()?)
+            .generate(&target, &context, &file, existing.as_deref())
+            .await?;
+        let contents = test_gen_service::with_tests(existing.as_deref(), &tests);
+
+        let name = file.path.display().to_string();
+        let diff = diff_explain_service::unified_diff(
+            &name,
+            existing.as_deref().unwrap_or(""),
+            &name,
+            &contents,
+        );
+        print!("{}", colored_diff(&diff));
+        if !ask_confirmation(&format!("Write these tests to {}?", name), true)? {
+            println!("{}", "Tests not written.".yellow());
+            return Ok(());
         }
-
-        let client = infrastructure::ollama_client::OllamaClient::new()?;
-        let system_info = detect_system_info();
-        let prompt = format!("You are on a system with: {}. Generate a bash command to: {}. Respond with only the exact command to run, without any formatting, backticks, quotes, or explanation. Ensure the command is complete, syntactically correct, and uses standard Unix tools. For size comparisons, use appropriate units like -BG for gigabytes in df.", system_info, query);
-        let response = client.generate_response(&prompt).await?;
-        let command = extract_command_from_response(&response);
-        println!("{}", format!("Command: {}", command).green());
-        if ask_confirmation("Run this command?", false)? {
-            let output = std::process::Command::new("bash")
-                .arg("-c")
-                .arg(&command)
-                .output()?;
-            println!("{}", String::from_utf8_lossy(&output.stdout));
-            if !output.status.success() {
-                println!(
+        if let Some(parent) = file.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&file.path, &contents)?;
+        println!("{} {}", "Wrote tests to".green(), name);
+
+        if run {
+            let command = match test_command {
+                Some(command) => Some((command, Provenance::UserEdited)),
+                None => file
+                    .command(&target.function)
+                    .map(|command| (command, Provenance::Rule(format!("usual test command for {}", name)))),
+            };
+            match command {
+                Some((command, provenance)) => {
+                    let config = Config {
+                        provenance,
+                        ..self.config.clone()
+                    };
+                    let run = runner::confirm_and_run(&command, &config)?;
+                    Self::audit("gen-tests", spec, &run, None);
+                }
+                None => println!(
                     "{}",
-                    format!(
-                        "Command failed: {}",
-                        String::from_utf8_lossy(&output.stderr)
-                    )
-                    .red()
-                );
-            } else {
-                let _ = self.save_cached(query, &command);
+                    format!("No usual test command for {}; pass --test-command.", name).yellow()
+                ),
             }
-        } else {
-            println!("{}", "Command execution cancelled.".yellow());
         }
         Ok(())
     }
 
-    fn keywords_from_text(text: &str) -> Vec<String> {
-        text.split_whitespace()
-            .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
-            .filter(|w| w.len() > 2)
-            .map(|w| w.to_lowercase())
-            .collect()
-    }
-
-    fn explain_cache_path() -> PathBuf {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let mut path = PathBuf::from(home);
-        path.push(".local");
-        path.push("share");
-        path.push("vibe_cli");
-        let suffix = project_cache_suffix();
-        path.push(format!("{}_explain_cache.bin", suffix));
-        path
+    async fn handle_gen_docs(&self, path: &std::path::Path, with_context: bool, backup: bool) -> Result<()> {
+        let Some(documented) = self.generate_docs(path, with_context).await? else {
+            println!(
+                "{}",
+                format!("Every public item in {} is documented.", path.display()).green()
+            );
+            return Ok(());
+        };
+        let width = Term::stdout().size().1 as usize;
+        let written = edit_file(path, &documented, backup, width, |diff| {
+            println!("{}", "Doc comments to add (left: current, right: documented):".yellow());
+            print!("{}", diff);
+            confirm(RiskTier::Mutating, &self.config.confirm, "Apply them?")
+        })?;
+        match written {
+            ScriptWritten::Kept | ScriptWritten::Unchanged => {
+                println!("{} {}", "File unchanged:".yellow(), path.display())
+            }
+            ScriptWritten::Overwritten { backup } | ScriptWritten::Appended { backup } => {
+                println!("{} {}", "Doc comments added to".green(), path.display());
+                if let Some(backup) = backup {
+                    println!("Previous version saved to {}", backup.display());
+                }
+            }
+            ScriptWritten::Created => {}
+        }
+        Ok(())
     }
 
-    fn load_cached_explain(&self, prompt: &str) -> Result<Option<String>> {
-        let cache_path = Self::explain_cache_path();
-        if !cache_path.exists() {
+    /// The contents of the Rust or Python file at `path` with doc comments
+    /// added to its undocumented public items; `None` if there are none.
+    /// With `with_context`, call sites are looked up in the RAG index.
+    pub async fn generate_docs(&self, path: &std::path::Path, with_context: bool) -> Result<Option<String>> {
+        let language = DocLanguage::from_path(path).ok_or_else(|| {
+            anyhow::anyhow!("gen-docs supports Rust and Python files, not {}", path.display())
+        })?;
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("cannot read {}: {}", path.display(), e))?;
+        let items = doc_gen_service::undocumented_items(&source, language)?;
+        if items.is_empty() {
             return Ok(None);
         }
-
-        let data = std::fs::read(&cache_path)?;
-        let mut cache: ExplainCacheFile = bincode::deserialize(&data).unwrap_or_default();
-
-        // Remove expired entries (7 days)
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        cache.entries.retain(|entry| now - entry.timestamp < 604800);
-
-        // Save cleaned cache
-        if let Some(parent) = cache_path.parent() {
-            std::fs::create_dir_all(parent)?;
+        let mut index = None;
+        if with_context {
+            let names: Vec<String> = items.iter().map(|item| item.name.clone()).collect();
+            match self.keyword_index(&names).await {
+                Ok(service) => index = Some(service),
+                Err(e) => eprintln!("{} {}", "No call sites:".yellow(), e),
+            }
         }
-        let serialized = bincode::serialize(&cache)?;
-        std::fs::write(&cache_path, serialized)?;
-
-        // Find exact match
-        for entry in &cache.entries {
-            if entry.prompt == prompt {
-                return Ok(Some(entry.response.clone()));
+        let writer = DocGenService::new(self.client()?);
+        let mut docs = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            eprintln!("Documenting {} {} ({}/{})...", item.kind, item.name, i + 1, items.len());
+            let call_sites = match &index {
+                Some(service) => doc_gen_service::call_sites(service, &item.name)
+                    .await
+                    .unwrap_or_default(),
+                None => Vec::new(),
+            };
+            let doc = writer.document(item, language, &call_sites).await?;
+            if !doc.is_empty() {
+                let comment = doc_gen_service::format_doc(&doc, item, language);
+                docs.push((item.clone(), comment));
             }
         }
-        Ok(None)
+        Ok(Some(doc_gen_service::apply_docs(&source, &docs)))
     }
 
-    fn save_cached_explain(&self, prompt: &str, response: &str) -> Result<()> {
-        let cache_path = Self::explain_cache_path();
-        let mut cache = if cache_path.exists() {
-            let data = std::fs::read(&cache_path).unwrap_or_default();
-            bincode::deserialize::<ExplainCacheFile>(&data).unwrap_or_default()
-        } else {
-            ExplainCacheFile::default()
+    /// Text of a PDF, or of just the `pages` selected from it.
+    async fn handle_rag(&mut self, question: &str) -> Result<()> {
+        // A cached answer may predate the changes --fresh is asked for.
+        let cached = match self.config.rag_fresh {
+            true => None,
+            false => self.rag_cache().get(&self.rag_cache_key(question))?,
         };
+        if let Some(cached_response) = cached {
+            if offline::is_offline() || ask_confirmation("Cached answer found. Use it?", true)? {
+                print!("{}", Highlighter::default().render_answer(&cached_response));
+                self.flag_answer_commands(&cached_response);
+                return Ok(());
+            }
+        }
+        if offline::is_offline() {
+            return self.print_offline_matches(question).await;
+        }
 
-        cache.entries.push(ExplainCacheEntry {
-            prompt: prompt.to_string(),
-            response: response.to_string(),
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-        });
+        let service = match &self.rag_service {
+            Some(service) => Arc::clone(service),
+            None => {
+                let with_code = self
+                    .config
+                    .rag_collections
+                    .iter()
+                    .any(|name| name == DEFAULT_COLLECTION);
+                let service = Arc::new(self.question_service(".", with_code).await?);
+                if with_code && self.config.rag_fresh {
+                    eprintln!("Re-indexing before answering...");
+                    service.build_index().await?;
+                } else if with_code {
+                    // Index in the background; answer from what is already stored.
+                    service.spawn_index_for_keywords(Self::keywords_from_text(question));
+                }
+                self.rag_service = Some(Arc::clone(&service));
+                service
+            }
+        };
+        eprintln!("Scanning codebase...");
+        service.wait_for_searchable_index().await?;
+
+        // An active session carries the conversation across runs
+        let mut conversation: RagConversation = self
+            .active_session()
+            .and_then(|session| session.context.get(RAG_SESSION_KEY).cloned())
+            .and_then(|saved| serde_json::from_str(&saved).ok())
+            .unwrap_or_default();
+        let mut question = question.to_string();
+        loop {
+            let mut feedback = String::new();
+            loop {
+                print_index_notice(&service.index_status());
+                eprintln!("Thinking...");
+                let answer = service.answer(&conversation, &question, &feedback).await?;
+
+                print!("{}", Highlighter::default().render_answer(&answer.text));
+                self.flag_answer_commands(&answer.text);
+
+                if ask_confirmation("Satisfied with this response?", true)? {
+                    // Follow-up answers depend on the conversation; only the
+                    // opening question is worth caching.
+                    if conversation.is_empty() {
+                        self.rag_cache().put(&self.rag_cache_key(&question), &answer.text)?;
+                    }
+                    let text = answer.text.clone();
+                    self.remember(MemoryKind::Rag, &question, &text).await;
+                    conversation.record(&question, answer);
+                    let referenced = service.referenced_files(&conversation);
+                    if !referenced.is_empty()
+                        && ask_confirmation(
+                            &format!(
+                                "The answer mentions {}. Add to the context for follow-ups?",
+                                referenced.join(", ")
+                            ),
+                            true,
+                        )?
+                    {
+                        let added = service.include_files(&mut conversation, &referenced)?;
+                        eprintln!("Added {} chunks from {} file(s).", added, referenced.len());
+                    }
+                    let saved = serde_json::to_string(&conversation)?;
+                    self.update_session(|session| {
+                        session.add_exchange("rag", &question, &text);
+                        session.set_context(RAG_SESSION_KEY, saved);
+                    });
+                    break;
+                } else {
+                    feedback.clear();
+                    eprint!("Provide feedback for improvement: ");
+                    io::stdout().flush()?;
+                    io::stdin().read_line(&mut feedback)?;
+                    feedback = feedback.trim().to_string();
+                    eprintln!("Regenerating with feedback...");
+                }
+            }
 
-        if let Some(parent) = cache_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            let mut follow_up = String::new();
+            eprint!("Follow-up question (Enter to finish): ");
+            io::stdout().flush()?;
+            io::stdin().read_line(&mut follow_up)?;
+            if follow_up.trim().is_empty() {
+                break;
+            }
+            question = follow_up.trim().to_string();
         }
 
-        let serialized = serde_json::to_string_pretty(&cache)?;
-        std::fs::write(&cache_path, serialized)?;
-
         Ok(())
     }
 
-    fn rag_cache_path() -> PathBuf {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let mut path = PathBuf::from(home);
-        path.push(".local");
-        path.push("share");
-        path.push("vibe_cli");
-        let suffix = project_cache_suffix();
-        path.push(format!("{}_rag_cache.bin", suffix));
-        path
-    }
-
-    fn load_cached_rag(&self, question: &str) -> Result<Option<String>> {
-        let cache_path = Self::rag_cache_path();
-        if !cache_path.exists() {
-            return Ok(None);
+    /// Warn about the shell commands in a `--rag` answer that the safety
+    /// checks block or rate dangerous or worse: the model may have picked
+    /// them up from instructions planted in the indexed files.
+    fn flag_answer_commands(&self, answer: &str) {
+        let policy = policy::system();
+        let flagged: Vec<(String, String)> = prompt_guard::answer_commands(answer)
+            .into_iter()
+            .filter_map(|cmd| {
+                let simulation = policy_test::simulate(&cmd, &self.config, policy).ok()?;
+                match simulation.tier {
+                    Some(tier) if tier < RiskTier::Dangerous => None,
+                    _ => Some((simulation.command, simulation.verdict)),
+                }
+            })
+            .collect();
+        if flagged.is_empty() {
+            return;
         }
-
-        let data = std::fs::read(&cache_path)?;
-        let mut cache: RagCacheFile = bincode::deserialize(&data).unwrap_or_default();
-
-        // Remove expired entries (7 days)
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        cache.entries.retain(|entry| now - entry.timestamp < 604800);
-
-        // Save cleaned cache
-        if let Some(parent) = cache_path.parent() {
-            std::fs::create_dir_all(parent)?;
+        println!(
+            "\n{} {}",
+            accessibility::glyph("⚠", "Warning:").yellow(),
+            "The safety checks flag commands in this answer. They may come from instructions in the indexed files; review them before running any:"
+                .yellow()
+        );
+        for (command, verdict) in flagged {
+            println!("  {} {}", command.red(), format!("({})", verdict).dimmed());
         }
+    }
 
-        let serialized = bincode::serialize(&cache)?;
-        std::fs::write(&cache_path, serialized)?;
-        // Find exact match
-        for entry in &cache.entries {
-            if entry.question == question {
-                return Ok(Some(entry.response.clone()));
+    /// The service `--rag` questions are answered from: the code collection
+    /// indexed from `root` when `with_code`, searched together with the other
+    /// collections in `rag_collections`, which must have been filled already.
+    async fn question_service(&self, root: &str, with_code: bool) -> Result<RagService> {
+        let client = self.client()?;
+        let mut services = Vec::new();
+        if with_code {
+            services.push(RagService::collection(root, DEFAULT_COLLECTION, client.clone(), &self.config).await?);
+        }
+        for name in self.config.rag_collections.iter().filter(|n| *n != DEFAULT_COLLECTION) {
+            if !collection_db_path(&self.config.db_path, name)?.exists() {
+                return Err(anyhow::anyhow!(
+                    "collection '{}' is empty; fill it with `vibe_cli rag index <dir> --collection {}`",
+                    name,
+                    name
+                ));
             }
+            services.push(RagService::collection(root, name, client.clone(), &self.config).await?);
+        }
+        if services.is_empty() {
+            return Err(anyhow::anyhow!("no collections to search"));
         }
-        Ok(None)
+        let mut first = services.remove(0);
+        if self.config.crate_docs {
+            first = first.with_crate_docs(CrateDocs::new());
+        }
+        Ok(first.with_collections(services))
     }
 
-    fn save_cached_rag(&self, question: &str, response: &str) -> Result<()> {
-        let cache_path = Self::rag_cache_path();
-        let mut cache = if cache_path.exists() {
-            let data = std::fs::read(&cache_path).unwrap_or_default();
-            bincode::deserialize::<RagCacheFile>(&data).unwrap_or_default()
-        } else {
-            RagCacheFile::default()
-        };
+    /// `--rag` without a model: show the indexed chunks that best match the
+    /// question by keyword.
+    async fn print_offline_matches(&self, question: &str) -> Result<()> {
+        const OFFLINE_MATCHES: usize = 5;
+        let service =
+            RagService::collection(".", DEFAULT_COLLECTION, self.client()?, &self.config).await?;
+        let matches = service.search_offline(question, OFFLINE_MATCHES).await?;
+        if matches.is_empty() {
+            println!(
+                "{}",
+                "Offline: no indexed code matches this question, and no cached answer exists."
+                    .yellow()
+            );
+            return Ok(());
+        }
+        println!(
+            "{}",
+            "Offline: no model answer; these indexed chunks match best.".yellow()
+        );
+        let highlighter = Highlighter::default();
+        for chunk in matches {
+            println!("{}", accessibility::glyph("─", "-").repeat(60).dimmed());
+            print!("{}", highlighter.render_chunk(&chunk));
+        }
+        Ok(())
+    }
 
-        cache.entries.push(RagCacheEntry {
-            question: question.to_string(),
-            response: response.to_string(),
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+    async fn handle_aliasify(&self, min_count: usize) -> Result<()> {
+        let path = paths::config_dir().join(alias_service::ALIASES_FILE);
+        let proposals = self.propose_aliases(&path, min_count).await?;
+        if proposals.is_empty() {
+            println!(
+                "No aliases to propose: no command succeeded {} times or more without one.",
+                min_count
+            );
+            return Ok(());
+        }
+        let mut accepted = Vec::new();
+        for proposal in proposals {
+            println!();
+            print!("{}", proposal.render().cyan());
+            if confirm(RiskTier::Mutating, &self.config.confirm, &format!("Add `{}`?", proposal.name))? {
+                accepted.push(proposal);
+            }
+        }
+        if accepted.is_empty() {
+            println!("{}", "No aliases added.".yellow());
+            return Ok(());
+        }
+        alias_service::append_aliases(&path, &accepted)?;
+        println!("{} {} to {}", "Added".green(), accepted.len(), path.display());
+
+        let shell = std::env::var("SHELL").unwrap_or_default();
+        let rc = std::env::var_os("HOME")
+            .and_then(|home| alias_service::rc_file(&shell, Path::new(&home)));
+        let sourced = rc.as_ref().is_some_and(|rc| {
+            std::fs::read_to_string(rc).is_ok_and(|text| text.contains(&*path.to_string_lossy()))
         });
+        let line = alias_service::source_line(&path);
+        match rc {
+            Some(rc) if !sourced => {
+                if ask_confirmation(&format!("Source it from {}?", rc.display()), true)? {
+                    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&rc)?;
+                    writeln!(file, "\n{}", line)?;
+                    println!("Open a new shell, or run: . {}", rc.display());
+                }
+            }
+            Some(_) => println!("Open a new shell to use them."),
+            None => println!("Add this to your shell's startup file: {}", line),
+        }
+        Ok(())
+    }
 
-        if let Some(parent) = cache_path.parent() {
-            std::fs::create_dir_all(parent)?;
+    /// Aliases for the commands that succeeded at least `min_count` times in
+    /// any project, minus names the aliases file at `path` already defines
+    /// and names of installed commands.
+    pub async fn propose_aliases(&self, path: &Path, min_count: usize) -> Result<Vec<AliasProposal>> {
+        let mut entries = Vec::new();
+        for log in AuditLog::all_projects() {
+            entries.extend(log.entries()?);
         }
+        let commands = alias_service::frequent_commands(&entries, min_count);
+        let taken = alias_service::defined_names(&std::fs::read_to_string(path).unwrap_or_default());
+        let proposals = AliasService::new(self.client()?).propose(&commands, &taken).await?;
+        Ok(proposals
+            .into_iter()
+            .filter(|proposal| !alias_service::shadows_command(&proposal.name))
+            .collect())
+    }
 
-        let serialized = bincode::serialize(&cache)?;
-        std::fs::write(&cache_path, serialized)?;
+    fn handle_session_command(&self, command: SessionCommand) -> Result<()> {
+        let sessions = JsonSessionRepository::default();
+        match command {
+            SessionCommand::New { id } => {
+                let id = match id {
+                    Some(id) => id,
+                    None => {
+                        let stamp = std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .map(|d| d.as_secs())
+                            .unwrap_or_default();
+                        let mut id = format!("s{}", stamp);
+                        let mut n = 1;
+                        while sessions.load(&id)?.is_some() {
+                            n += 1;
+                            id = format!("s{}-{}", stamp, n);
+                        }
+                        id
+                    }
+                };
+                sessions.create(&id)?;
+                sessions.set_active(Some(&id))?;
+                println!("{}", format!("Created session '{}'; it is now active.", id).green());
+            }
+            SessionCommand::List => {
+                let active = sessions.active()?;
+                let list = sessions.list()?;
+                if list.is_empty() {
+                    println!("No sessions yet; start one with `session new`.");
+                }
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default();
+                for session in list {
+                    let marker = if active.as_deref() == Some(session.id.as_str()) { "*" } else { " " };
+                    println!(
+                        "{} {}  {} messages, updated {} ago",
+                        marker,
+                        session.id,
+                        session.history.len(),
+                        notify::format_elapsed(std::time::Duration::from_secs(
+                            now.saturating_sub(session.updated_at)
+                        ))
+                    );
+                }
+            }
+            SessionCommand::Use { id } => {
+                sessions.set_active(Some(&id))?;
+                println!("{}", format!("Now recording into session '{}'.", id).green());
+            }
+            SessionCommand::Show { id } => {
+                let id = match id.or(sessions.active()?) {
+                    Some(id) => id,
+                    None => {
+                        println!("No active session; name one or run `session use <id>`.");
+                        return Ok(());
+                    }
+                };
+                let session = sessions
+                    .load(&id)?
+                    .ok_or_else(|| anyhow::anyhow!("no session '{}'", id))?;
+                println!("{}", format!("Session '{}'", session.id).green().bold());
+                for message in &session.history {
+                    let who = format!("[{}] {}:", message.mode, message.role);
+                    println!("{} {}", who.blue(), message.content);
+                }
+            }
+            SessionCommand::Delete { id, yes } => {
+                if !yes && !ask_confirmation(&format!("Delete session '{}'?", id), false)? {
+                    return Ok(());
+                }
+                if sessions.delete(&id)? {
+                    println!("Deleted session '{}'.", id);
+                } else {
+                    println!("No session '{}'.", id);
+                }
+            }
+        }
+        Ok(())
+    }
 
+    fn handle_allowlist_command(&self, command: AllowlistCommand) -> Result<()> {
+        let allowlist = Allowlist::for_project()?;
+        match command {
+            AllowlistCommand::List => {
+                let entries = allowlist.entries()?;
+                if entries.is_empty() {
+                    println!("No allowlisted commands; approve one twice to be offered.");
+                }
+                for entry in entries {
+                    let scope = match entry.scope() {
+                        AllowScope::Session(id) => format!("session '{}'", id),
+                        AllowScope::Project => "project".to_string(),
+                    };
+                    println!("{}  {:<20} {}", entry.id().yellow(), scope, entry.command);
+                }
+            }
+            AllowlistCommand::Revoke { id, all } => {
+                let removed = if all {
+                    allowlist.clear()?
+                } else {
+                    allowlist.revoke(id.as_deref().unwrap_or_default())?
+                };
+                match removed {
+                    0 => println!("No matching allowlist entry."),
+                    1 => println!("Revoked 1 entry."),
+                    n => println!("Revoked {} entries.", n),
+                }
+            }
+        }
         Ok(())
     }
+
+    fn handle_audit_command(&self, command: AuditCommand) -> Result<()> {
+        match command {
+            AuditCommand::Export { since, format, all_projects } => {
+                let format = ExportFormat::parse(&format)
+                    .ok_or_else(|| anyhow::anyhow!("unknown format '{}' (use jsonl or csv)", format))?;
+                let cutoff = match since {
+                    Some(since) => {
+                        let age = parse_interval(&since)
+                            .ok_or_else(|| anyhow::anyhow!("invalid --since '{}' (use e.g. 12h, 7d)", since))?;
+                        let now = std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .map(|d| d.as_secs())
+                            .unwrap_or(0);
+                        now.saturating_sub(age.as_secs())
+                    }
+                    None => 0,
+                };
+                let logs = if all_projects {
+                    AuditLog::all_projects()
+                } else {
+                    vec![AuditLog::for_project()]
+                };
+                let mut entries = Vec::new();
+                for log in logs {
+                    let project = log.project();
+                    entries.extend(
+                        log.entries()?
+                            .into_iter()
+                            .filter(|entry| entry.timestamp >= cutoff)
+                            .map(|entry| (project.clone(), entry)),
+                    );
+                }
+                entries.sort_by_key(|(_, entry)| entry.timestamp);
+                print!("{}", audit_log::export(&entries, format, self.config.audit.export_prompts)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_policy_command(&self, command: PolicyCommand) -> Result<()> {
+        match command {
+            PolicyCommand::Test { command, policy: draft_path } => {
+                let mut config = self.config.clone();
+                let draft = match &draft_path {
+                    Some(path) => Some(
+                        Policy::load_from(path)?
+                            .ok_or_else(|| anyhow::anyhow!("{}: no such file", path.display()))?,
+                    ),
+                    None => None,
+                };
+                if let Some(draft) = &draft {
+                    config.apply_policy(draft);
+                }
+                let policy = draft.as_ref().unwrap_or_else(|| policy::system());
+                let simulation = policy_test::simulate(&command, &config, policy)?;
+                println!("{} {}", "Command:".cyan(), simulation.command);
+                if simulation.matches.is_empty() {
+                    println!("{}", "No rules matched.".dimmed());
+                }
+                for rule in &simulation.matches {
+                    println!("  {:<10} {}", format!("[{}]", rule.check).yellow(), rule.detail);
+                }
+                if let Some(tier) = simulation.tier {
+                    println!("{} {}", "Tier:".cyan(), format!("{:?}", tier).to_lowercase());
+                }
+                println!("{} {}", "Verdict:".cyan().bold(), simulation.verdict);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_rules_command(&self, command: RulesCommand) -> Result<()> {
+        let path = Self::rules_path();
+        match command {
+            RulesCommand::List => {
+                let own = command_rules::load_rules(&path)?;
+                if own.is_empty() {
+                    println!("{}", format!("No rules of your own in {}.", path.display()).dimmed());
+                }
+                let builtin = command_rules::builtin_rules();
+                let listed = own.iter().map(|rule| (rule, "yours"));
+                for (rule, source) in listed.chain(builtin.iter().map(|rule| (rule, "built-in"))) {
+                    println!("{} {} {}", rule.name.yellow(), format!("({})", source).dimmed(), rule.command);
+                    println!("    {}", rule.phrases.join(" | ").dimmed());
+                }
+            }
+            RulesCommand::Edit => {
+                command_rules::ensure_rules_file(&path)?;
+                let editor = std::env::var("VISUAL")
+                    .or_else(|_| std::env::var("EDITOR"))
+                    .unwrap_or_else(|_| "vi".to_string());
+                let status = std::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(format!("{} \"$1\"", editor))
+                    .arg("sh")
+                    .arg(&path)
+                    .status()?;
+                if !status.success() {
+                    return Err(anyhow::anyhow!("{} exited with {}", editor, status));
+                }
+                let rules = command_rules::load_rules(&path)?;
+                println!("{} rules in {}.", rules.len(), path.display());
+            }
+            RulesCommand::Remove { name } => {
+                let mut rules = command_rules::load_rules(&path)?;
+                let before = rules.len();
+                rules.retain(|rule| rule.name != name);
+                if rules.len() == before {
+                    if command_rules::builtin_rules().iter().any(|rule| rule.name == name) {
+                        println!("'{}' is built in and can't be removed.", name);
+                    } else {
+                        println!("No rule named '{}'.", name);
+                    }
+                    return Ok(());
+                }
+                command_rules::save_rules(&path, &rules)?;
+                println!("Removed '{}'.", name);
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_jobs_command(&self, command: JobsCommand) -> Result<()> {
+        let store = JobStore::default();
+        match command {
+            JobsCommand::List => {
+                let jobs = store.list()?;
+                if jobs.is_empty() {
+                    println!("{}", "No jobs; start one with --detach.".dimmed());
+                }
+                for job in jobs {
+                    let status = store.status(&job);
+                    let label = match status {
+                        JobStatus::Running => status.label().cyan(),
+                        JobStatus::Exited(0) => status.label().green(),
+                        _ => status.label().red(),
+                    };
+                    let age = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH + Duration::from_secs(job.started))
+                        .unwrap_or_default();
+                    let started = format!("started {} ago", notify::format_elapsed(age));
+                    println!("{} {} {} {}", format!("[{}]", job.id).blue(), label, started.dimmed(), job.command);
+                    if status != JobStatus::Running {
+                        self.report_job(&store, job).await;
+                    }
+                }
+            }
+            JobsCommand::Tail { id, lines, follow } => {
+                let job = store.load(id)?;
+                let mut shown = store.tail(id, usize::MAX)?.len();
+                for line in store.tail(id, lines)? {
+                    println!("{}", redaction::for_display(&line));
+                }
+                if follow {
+                    while store.status(&job) == JobStatus::Running {
+                        std::thread::sleep(Duration::from_millis(500));
+                        let all = store.tail(id, usize::MAX)?;
+                        for line in all.iter().skip(shown) {
+                            println!("{}", redaction::for_display(line));
+                        }
+                        shown = all.len();
+                    }
+                    for line in store.tail(id, usize::MAX)?.iter().skip(shown) {
+                        println!("{}", redaction::for_display(line));
+                    }
+                    println!("\n{} {}", format!("Job {}:", id).bold(), store.status(&job).label());
+                    self.report_job(&store, job).await;
+                }
+            }
+            JobsCommand::Kill { id } => {
+                let job = store.load(id)?;
+                if store.status(&job) != JobStatus::Running {
+                    println!("Job {} is not running.", id);
+                    return Ok(());
+                }
+                // The job's shell leads its own process group under setsid.
+                let killed = std::process::Command::new("kill")
+                    .args(["-TERM", "--", &format!("-{}", job.pid)])
+                    .status()
+                    .is_ok_and(|s| s.success())
+                    || std::process::Command::new("kill")
+                        .args(["-TERM", &job.pid.to_string()])
+                        .status()
+                        .is_ok_and(|s| s.success());
+                if !killed {
+                    return Err(anyhow::anyhow!("could not stop job {} (pid {})", id, job.pid));
+                }
+                println!("Stopped job {}.", id);
+            }
+            JobsCommand::Clean => {
+                let mut removed = 0;
+                for job in store.list()? {
+                    if store.status(&job) != JobStatus::Running {
+                        store.remove(job.id)?;
+                        removed += 1;
+                    }
+                }
+                println!("Removed {} ended job(s).", removed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Print the model's summary of an ended job's log, asking for it the
+    /// first time and keeping it with the job. Without the model, nothing
+    /// is printed and it is asked again next time.
+    async fn report_job(&self, store: &JobStore, mut job: Job) {
+        if job.summary.is_none() && !offline::is_offline() {
+            let log = std::fs::read_to_string(store.log_path(job.id)).unwrap_or_default();
+            let summary = match self.client() {
+                Ok(client) if !log.trim().is_empty() => SummaryService::new(client)
+                    .summarize_output(&job.command, &redaction::redact(&log))
+                    .await
+                    .ok(),
+                _ => None,
+            };
+            if let Some(summary) = summary {
+                job.summary = Some(summary);
+                if let Err(e) = store.save(&job) {
+                    eprintln!("{} {}", "Job summary not kept:".yellow(), e);
+                }
+            }
+        }
+        if let Some(summary) = &job.summary {
+            for line in summary.lines() {
+                println!("    {}", line.dimmed());
+            }
+        }
+    }
+
+    /// The session modes record into, if one is active and readable.
+    fn active_session(&self) -> Option<Session> {
+        let sessions = JsonSessionRepository::default();
+        let loaded = sessions
+            .active()
+            .and_then(|id| id.map(|id| sessions.load(&id)).transpose())
+            .map(Option::flatten);
+        match loaded {
+            Ok(session) => session,
+            Err(e) => {
+                eprintln!("{} {}", "Session unavailable:".yellow(), e);
+                None
+            }
+        }
+    }
+
+    /// Apply `change` to the active session and save it; nothing happens
+    /// without one.
+    /// Keep `body` for `recall`. Failing to is reported but doesn't stop
+    /// the caller; nothing is kept offline or with `remember = false`.
+    pub async fn remember(&self, kind: MemoryKind, title: &str, body: &str) {
+        if !self.config.remember || offline::is_offline() {
+            return;
+        }
+        let remembered = async {
+            RecallService::new(&self.config.db_path, self.client()?)
+                .await?
+                .remember(kind, title, body)
+                .await
+        };
+        if let Err(e) = remembered.await {
+            eprintln!("{} {}", "Not remembered for recall:".yellow(), e);
+        }
+    }
+
+    /// Answer `question` from what was remembered in earlier sessions.
+    pub async fn recall(&self, question: &str) -> Result<Recollection> {
+        RecallService::new(&self.config.db_path, self.client()?)
+            .await?
+            .recall(question, self.config.rag_top_k, self.config.rag_min_score)
+            .await
+    }
+
+    async fn handle_recall(&self, question: &str) -> Result<()> {
+        eprintln!("Searching earlier sessions...");
+        let recollection = self.recall(question).await?;
+        print!("{}", Highlighter::default().render_answer(&recollection.answer));
+        if !recollection.sources.is_empty() {
+            let now = shared::cache::now_secs();
+            println!("\n{}", "From:".dimmed());
+            for (i, memory) in recollection.sources.iter().enumerate() {
+                let title = memory.text.lines().next().unwrap_or("");
+                println!(
+                    "  {} {}, {}: {}",
+                    format!("[{}]", i + 1).blue(),
+                    memory.kind,
+                    recall_service::age(memory.created_at, now),
+                    title
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn update_session(&self, change: impl FnOnce(&mut Session)) {
+        let Some(mut session) = self.active_session() else {
+            return;
+        };
+        change(&mut session);
+        if let Err(e) = JsonSessionRepository::default().save(&session) {
+            eprintln!("{} {}", "Could not save the session:".yellow(), e);
+        }
+    }
+
+    async fn handle_rag_command(&self, command: RagCommand) -> Result<()> {
+        match command {
+            RagCommand::Status => {
+                let storage = EmbeddingStorage::new(&self.config.db_path).await?;
+                let stats = storage.stats().await?;
+                println!("{}", "RAG index status".green().bold());
+                println!("  Database:      {}", self.config.db_path);
+                println!("  Files indexed: {}", stats.files);
+                println!("  Chunks:        {}", stats.chunks);
+                println!("  DB file size:  {}", format_bytes(stats.db_file_bytes));
+                println!(
+                    "  Chunk text:    {} stored / {} raw ({:.1}x, {} saved)",
+                    format_bytes(stats.stored_text_bytes),
+                    format_bytes(stats.raw_text_bytes),
+                    stats.compression_ratio(),
+                    format_bytes(stats.saved_bytes())
+                );
+                Ok(())
+            }
+            RagCommand::Index { dir, collection } => {
+                offline::ensure_online("indexing")?;
+                let service = RagService::collection(
+                    &dir.to_string_lossy(),
+                    &collection,
+                    self.client()?,
+                    &self.config,
+                )
+                .await?;
+                eprintln!("Indexing {} into '{}'...", dir.display(), collection);
+                service.build_index().await?;
+                let db_path = collection_db_path(&self.config.db_path, &collection)?;
+                let stats = EmbeddingStorage::new(&db_path).await?.stats().await?;
+                println!(
+                    "{} '{}' now holds {} files, {} chunks",
+                    "Indexed".green().bold(),
+                    collection,
+                    stats.files,
+                    stats.chunks
+                );
+                Ok(())
+            }
+            RagCommand::Collections => {
+                let names = collection_names(&self.config.db_path);
+                if names.is_empty() {
+                    println!("No collections yet; ask a --rag question or run `vibe_cli rag index <dir> --collection <name>`.");
+                    return Ok(());
+                }
+                println!("{}", "Collections".green().bold());
+                for name in names {
+                    let config = self.config.for_collection(&name)?;
+                    let stats = EmbeddingStorage::new(&config.db_path).await?.stats().await?;
+                    let model = self
+                        .config
+                        .embedding_model(&name)
+                        .unwrap_or_else(|| self.config.ollama_model.clone());
+                    println!(
+                        "  {:<14} {} files, {} chunks, embedded with {}, top {} (min score {})",
+                        name,
+                        stats.files,
+                        stats.chunks,
+                        model,
+                        config.rag_top_k,
+                        config.rag_min_score
+                    );
+                }
+                Ok(())
+            }
+            RagCommand::Export { path } => {
+                let db_path = PathBuf::from(&self.config.db_path);
+                let manifest =
+                    index_bundle::export(&db_path, &self.config.ollama_model, &path).await?;
+                let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                println!(
+                    "{} {} files, {} chunks ({}) to {}",
+                    "Exported".green().bold(),
+                    manifest.files,
+                    manifest.chunks,
+                    format_bytes(size),
+                    path.display()
+                );
+                Ok(())
+            }
+            RagCommand::Import { path, force, yes } => {
+                let db_path = PathBuf::from(&self.config.db_path);
+                if db_path.exists()
+                    && !yes
+                    && !ask_confirmation("Replace this project's existing index?", true)?
+                {
+                    return Ok(());
+                }
+                let manifest =
+                    index_bundle::import(&path, &db_path, &self.config.ollama_model, force)
+                        .await?;
+                println!(
+                    "{} {} files, {} chunks embedded with {} (vibe_cli {})",
+                    "Imported".green().bold(),
+                    manifest.files,
+                    manifest.chunks,
+                    manifest.fingerprint.model,
+                    manifest.vibe_version
+                );
+                println!("Files changed since the export are re-embedded on the next query.");
+                Ok(())
+            }
+        }
+    }
+
+    async fn handle_docs_command(&self, command: DocsCommand) -> Result<()> {
+        let docs = DocsService::new(self.client()?, &self.config).await?;
+        match command {
+            DocsCommand::Add { source, max_pages } => {
+                eprintln!("Fetching {}...", source);
+                let added = docs.add(&source, max_pages).await?;
+                println!(
+                    "{} {} ({} pages) to the '{}' collection",
+                    "Added".green().bold(),
+                    added.source,
+                    added.pages.len(),
+                    DOCS_COLLECTION
+                );
+                println!(
+                    "Ask with: vibe_cli --rag --collections {},{} \"...\"",
+                    DEFAULT_COLLECTION, DOCS_COLLECTION
+                );
+            }
+            DocsCommand::Refresh { source, max_pages } => {
+                for refreshed in docs.refresh(source.as_deref(), max_pages).await? {
+                    println!(
+                        "{} {} ({} pages)",
+                        "Refreshed".green().bold(),
+                        refreshed.source,
+                        refreshed.pages.len()
+                    );
+                }
+            }
+            DocsCommand::Remove { source } => {
+                let removed = docs.remove(&source).await?;
+                println!(
+                    "{} {} ({} pages)",
+                    "Removed".green().bold(),
+                    removed.source,
+                    removed.pages.len()
+                );
+            }
+            DocsCommand::List => {
+                let sources = docs.sources().await?;
+                if sources.is_empty() {
+                    println!("No documentation added yet; see `vibe_cli docs add --help`.");
+                }
+                let now = shared::cache::now_secs();
+                for source in sources {
+                    println!(
+                        "  {}  {} pages, fetched {}",
+                        source.source,
+                        source.pages.len(),
+                        recall_service::age(source.fetched_at, now)
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Confirm and run a command; if it printed more than fits on screen,
+    /// offer a model summary of the captured output.
+    /// In an agent plan, the command runs in the plan's persistent `shell`,
+    /// whose directory afterwards is the session's.
+    async fn run_command(
+        &self,
+        cmd: &str,
+        shell: Option<&mut PtyShell>,
+        expected: Option<Duration>,
+    ) -> Result<CommandRun> {
+        let located = self.locate(cmd);
+        if located.command.is_empty() {
+            return Ok(self.change_dir(cmd, located.dir));
+        }
+        let mut config = self.config_in(&located.dir);
+        config.sandbox.expected = expected;
+        let (run, dir) = match shell {
+            Some(shell) => {
+                let run = runner::confirm_and_run_in(&located.command, &config, shell)?;
+                (run, shell.take_dir().unwrap_or(located.dir))
+            }
+            None => (runner::confirm_and_run(&located.command, &config)?, located.dir),
+        };
+        self.settle(&run, dir);
+        self.offer_summary(&run).await?;
+        Ok(run)
+    }
+
+    /// Where `cmd` runs in this session and what is left of it there.
+    fn locate(&self, cmd: &str) -> Located {
+        self.work_dir.lock().unwrap_or_else(|e| e.into_inner()).locate(cmd)
+    }
+
+    fn session_dir(&self) -> PathBuf {
+        self.work_dir.lock().unwrap_or_else(|e| e.into_inner()).current().to_path_buf()
+    }
+
+    /// The session's config with commands running in `dir`.
+    fn config_in(&self, dir: &Path) -> Config {
+        let mut config = self.config.clone();
+        config.sandbox.cwd = (dir != Self::process_dir()).then(|| dir.to_path_buf());
+        config
+    }
+
+    /// Move the session to `dir` for a command that was only `cd`s.
+    fn change_dir(&self, cmd: &str, dir: PathBuf) -> CommandRun {
+        let run = runner::change_dir(cmd, &dir);
+        self.settle(&run, dir);
+        run
+    }
+
+    /// Keep the session in `dir` if `run` got that far: its leading `cd`s
+    /// stand whether or not the rest of the command succeeded.
+    fn settle(&self, run: &CommandRun, dir: PathBuf) {
+        if matches!(run.outcome, RunOutcome::Succeeded | RunOutcome::Failed) {
+            self.work_dir.lock().unwrap_or_else(|e| e.into_inner()).enter(dir);
+        }
+    }
+
+    /// Confirm and run `command`, which came from `provenance`,
+    /// regenerating it from the user's corrections for as long as they
+    /// answer "refine" instead; a correction of the form `$ <command>` is
+    /// run as typed. Every proposal is audited; returns the last command
+    /// and its run. A `stale` cached command is confirmed with Enter
+    /// declining.
+    async fn run_refinable(
+        &self,
+        mode: &str,
+        query: &str,
+        mut command: String,
+        mut provenance: Provenance,
+        stale: bool,
+    ) -> Result<(String, CommandRun)> {
+        let mut attempts: Vec<(String, String)> = Vec::new();
+        loop {
+            let located = self.locate(&command);
+            let run = if located.command.is_empty() {
+                self.change_dir(&command, located.dir)
+            } else {
+                let mut config = Config {
+                    provenance: provenance.clone(),
+                    ..self.config_in(&located.dir)
+                };
+                if stale && matches!(provenance, Provenance::Cache { .. }) {
+                    config.confirm = config.confirm.declining();
+                }
+                let run = runner::confirm_and_run_or_refine(&located.command, &config)?;
+                self.settle(&run, located.dir);
+                run
+            };
+            let run = CommandRun { provenance, ..run };
+            self.offer_summary(&run).await?;
+            Self::audit(mode, query, &run, None);
+            let Some(correction) = run.refinement.clone() else {
+                return Ok((command, run));
+            };
+            if let Some(own) = runner::own_command(&correction) {
+                command = own.to_string();
+                provenance = Provenance::UserEdited;
+                continue;
+            }
+            attempts.push((command, correction));
+            eprintln!("Regenerating with your correction...");
+            command = self.refine_command(query, &attempts).await?;
+            provenance = Provenance::Model;
+        }
+    }
+
+    /// A new command for `query` after the user corrected the earlier
+    /// suggestions: `attempts` pairs each suggestion with its correction.
+    pub async fn refine_command(&self, query: &str, attempts: &[(String, String)]) -> Result<String> {
+        let conversation = attempts
+            .iter()
+            .map(|(command, correction)| {
+                format!("You suggested: {}\nThe user replied: {}", command, correction)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = format!(
+            "You are on a system with: {}. The user asked for a bash command to: {}.\n{}\n\
+Generate the command again, applying all of the user's corrections. Respond with only the exact command to run, without any formatting, backticks, quotes, or explanation.",
+            self.environment(),
+            query,
+            conversation
+        );
+        let response = self.client()?.generate_response(&prompt).await?;
+        Ok(extract_command_from_response(&response))
+    }
+
+    /// Offer to summarize output too long to have been shown in full.
+    async fn offer_summary(&self, run: &CommandRun) -> Result<()> {
+        if let Some(output) = run.output.as_ref().filter(|o| o.is_truncated()) {
+            let prompt = format!(
+                "Output was {} lines ({} shown). Summarize it with the model?",
+                output.total_lines, output.shown_lines
+            );
+            if ask_confirmation(&prompt, false)? {
+                let summary = SummaryService::new(self.client()?)
+                    .summarize_output(&run.command, &redaction::redact(&output.text()))
+                    .await?;
+                println!("\n{}\n{}", "Summary:".green().bold(), summary);
+            }
+        }
+        Ok(())
+    }
+
+    /// Announce `run` per `rule` if it ran long enough.
+    fn notify_finished(rule: &NotifyRule, what: &str, run: &CommandRun) {
+        if let Some(elapsed) = run.elapsed {
+            notify::finished(
+                rule,
+                what,
+                &run.command,
+                run.outcome == RunOutcome::Succeeded,
+                elapsed,
+            );
+        }
+    }
+
+    async fn handle_watch(
+        &self,
+        command: &str,
+        every: &str,
+        until: &str,
+        max_checks: Option<usize>,
+    ) -> Result<()> {
+        let interval = parse_interval(every)
+            .filter(|d| !d.is_zero())
+            .ok_or_else(|| anyhow::anyhow!("invalid interval '{}' (use e.g. 30s, 5m, 1h)", every))?;
+        let command = runner::postprocess(command, &self.config)?;
+        if let Some(reason) = read_only_violation(&command)
+            .or_else(|| infrastructure::policy::system().blocked_reason(&command))
+        {
+            println!(
+                "{} {}",
+                "Watch mode only runs read-only commands:".red().bold(),
+                reason.red()
+            );
+            return Ok(());
+        }
+        let prompt = format!(
+            "Run `{}` every {}s until \"{}\"?",
+            command,
+            interval.as_secs(),
+            until
+        );
+        let tier = risk_tier(&command, &assess_command(&command, self.config.safe_mode));
+        if !confirm(tier, &self.config.confirm, &prompt)? {
+            println!("{}", "Watch cancelled.".yellow());
+            return Ok(());
+        }
+
+        let watcher = WatchService::new(self.client()?);
+        let started = std::time::Instant::now();
+        let mut check = 0;
+        loop {
+            check += 1;
+            let (status, output) = runner::run_captured(&command, 0, &self.config.sandbox)?;
+            let verdict = watcher
+                .evaluate(&command, until, &redaction::redact(&output.text()), status.code())
+                .await?;
+            let elapsed = started.elapsed().as_secs();
+            let stamp = format!("[+{}m{:02}s] check {}", elapsed / 60, elapsed % 60, check);
+            if let Some(anomaly) = verdict.anomaly.as_deref().filter(|a| !a.trim().is_empty()) {
+                println!("{} {} {}\x07", stamp.dimmed(), "Anomaly:".red().bold(), anomaly.red());
+            }
+            if verdict.satisfied {
+                println!(
+                    "{} {} {}",
+                    stamp.dimmed(),
+                    "Condition met:".green().bold(),
+                    verdict.reason
+                );
+                let watch = format!("watch of `{}`", command);
+                notify::finished(
+                    &self.config.notify.watch,
+                    &watch,
+                    &verdict.reason,
+                    true,
+                    started.elapsed(),
+                );
+                return Ok(());
+            }
+            println!("{} not yet: {}", stamp.dimmed(), verdict.reason);
+            if max_checks.is_some_and(|max| check >= max) {
+                println!("{}", format!("Stopped after {} checks.", check).yellow());
+                let watch = format!("watch of `{}`", command);
+                notify::finished(
+                    &self.config.notify.watch,
+                    &watch,
+                    &verdict.reason,
+                    false,
+                    started.elapsed(),
+                );
+                return Ok(());
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn handle_version(&self, check: bool) -> Result<()> {
+        println!("vibe_cli {}", env!("CARGO_PKG_VERSION"));
+        if !check {
+            return Ok(());
+        }
+        let release = Updater::new().latest_release().await?;
+        if release.is_newer_than(env!("CARGO_PKG_VERSION")) {
+            println!(
+                "{}",
+                format!(
+                    "A newer release is available: {} (run `vibe_cli self-update`)",
+                    release.version
+                )
+                .yellow()
+            );
+        } else {
+            println!("{}", "You are on the latest release.".green());
+        }
+        Ok(())
+    }
+
+    async fn handle_self_update(&self) -> Result<()> {
+        let updater = Updater::new();
+        let release = updater.latest_release().await?;
+        if !release.is_newer_than(env!("CARGO_PKG_VERSION")) {
+            println!(
+                "{}",
+                format!("Already up to date ({}).", env!("CARGO_PKG_VERSION")).green()
+            );
+            return Ok(());
+        }
+        if let Some(mirror) = updater.mirror() {
+            println!(
+                "{}",
+                format!(
+                    "Releases come from {}. Its checksums come from there too, so only update from a mirror you trust.",
+                    mirror
+                )
+                .yellow()
+            );
+        }
+        let prompt = format!(
+            "Update vibe_cli {} -> {}?",
+            env!("CARGO_PKG_VERSION"),
+            release.version
+        );
+        if !ask_confirmation(&prompt, true)? {
+            println!("{}", "Update cancelled.".yellow());
+            return Ok(());
+        }
+        let target = std::env::current_exe()?;
+        eprintln!("Downloading {}...", release.version);
+        updater.install(&release, &target).await?;
+        println!(
+            "{}",
+            format!("Updated to {} (checksum matches).", release.version).green()
+        );
+        Ok(())
+    }
+
+    async fn handle_context(&mut self, path: &str) -> Result<()> {
+        offline::ensure_online("indexing context")?;
+        eprintln!("Loading context from {}...", path);
+        let service = Arc::new(self.question_service(path, true).await?);
+        service.spawn_index();
+        self.rag_service = Some(service);
+        eprintln!("Indexing {} in the background", path);
+        self.handle_chat().await
+    }
+
+    async fn handle_script(
+        &self,
+        task: &str,
+        options: ScriptOptions,
+        output: ScriptOutput<'_>,
+    ) -> Result<()> {
+        let ScriptOutput { path: output, mode, backup } = output;
+        if task.trim().is_empty() {
+            println!(
+                "{}",
+                "Script mode requires a prompt (e.g. vibe_cli --script -o clean.sh \"describe the script\")"
+                    .red()
+            );
+            return Ok(());
+        }
+
+        let script = match &options.params {
+            Some(params) => {
+                if params.is_empty() {
+                    eprintln!(
+                        "{}",
+                        "No <placeholders> found in the request; the script takes no options."
+                            .yellow()
+                    );
+                }
+                self.generate_parameterized_script(task, params, options.strict)
+                    .await?
+            }
+            None => self.generate_script(task).await?,
+        };
+        let script = if options.preflight {
+            script_preflight::with_preflight(&script, &self.system_info)
+        } else {
+            script
+        };
+        let path = output.unwrap_or(std::path::Path::new("generated_script.sh"));
+        let width = Term::stdout().size().1 as usize;
+        let written = write_script(path, &script, mode, backup, width, |diff| {
+            println!(
+                "{}",
+                format!("{} already exists (left: current, right: generated):", path.display())
+                    .yellow()
+            );
+            print!("{}", diff);
+            confirm(RiskTier::Mutating, &self.config.confirm, "Overwrite it?")
+        })?;
+
+        match written {
+            ScriptWritten::Unchanged => {
+                println!("{} {}", "Script unchanged:".green(), path.display());
+                return Ok(());
+            }
+            ScriptWritten::Kept => {
+                println!("Kept the existing {}.", path.display());
+                return Ok(());
+            }
+            ScriptWritten::Created => {
+                println!("{} {}", "Script written to".green().bold(), path.display())
+            }
+            ScriptWritten::Overwritten { backup } | ScriptWritten::Appended { backup } => {
+                if let Some(backup) = backup {
+                    println!("Previous version saved to {}", backup.display());
+                }
+                let verb = if mode == WriteMode::Append {
+                    "Script appended to"
+                } else {
+                    "Script written to"
+                };
+                println!("{} {}", verb.green().bold(), path.display());
+            }
+        }
+        println!("{}", "Review it carefully before running:".yellow());
+        println!("  {}", format!("bash {}", path.display()).yellow());
+        Ok(())
+    }
+
+    async fn handle_ask(&self, question: &str) -> Result<()> {
+        let answer = AskService::new(self.client()?)
+            .answer(question, &self.environment())
+            .await?;
+        print!("{}", Highlighter::default().render_answer(&answer));
+        self.update_session(|session| session.add_exchange("ask", question, &answer));
+        Ok(())
+    }
+
+    async fn handle_query(&mut self, query: &str) -> Result<()> {
+        if let Some(rule) = self.rule_for(query) {
+            let (_, run) = self
+                .run_refinable("command", query, rule.command, Provenance::Rule(rule.name), false)
+                .await?;
+            Self::notify_finished(&self.config.notify.command, "command", &run);
+            return Ok(());
+        }
+        if let Ok(Some(cached)) = self.cached_command(query) {
+            let reuse = self.offer_cached(query, &cached);
+            if reuse != Reuse::Regenerate {
+                let cached_command = cached.value;
+                let provenance = Provenance::Cache {
+                    saved_at: cached.created_at,
+                };
+                let (command, run) = self
+                    .run_refinable("command", query, cached_command.clone(), provenance, reuse == Reuse::Warn)
+                    .await?;
+                Self::notify_finished(&self.config.notify.command, "command", &run);
+                if run.outcome == RunOutcome::Succeeded {
+                    if command != cached_command {
+                        let _ = self.save_cached(query, &command);
+                    }
+                    self.offer_rule(query, &command)?;
+                }
+                return Ok(());
+            }
+        }
+
+        let command = self.generate_command(query).await?;
+        let (command, run) = self.run_refinable("command", query, command, Provenance::Model, false).await?;
+        Self::notify_finished(&self.config.notify.command, "command", &run);
+        if run.outcome == RunOutcome::Succeeded {
+            let _ = self.save_cached(query, &command);
+            self.offer_rule(query, &command)?;
+        }
+        Ok(())
+    }
+
+    /// Words as typed; the RAG service splits identifiers and stems them,
+    /// which needs the original casing.
+    fn keywords_from_text(text: &str) -> Vec<String> {
+        text.split_whitespace()
+            .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
+            .filter(|w| w.len() > 2)
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn explain_cache(&self) -> CacheStore {
+        CacheStore::new(
+            paths::project_data_file("explain_cache.bin"),
+            self.config.caches.explain,
+        )
+    }
+
+    fn rag_cache(&self) -> CacheStore {
+        CacheStore::new(
+            paths::project_data_file("rag_cache.bin"),
+            self.config.caches.rag,
+        )
+    }
+
+    /// Answers from other collections than the code alone are cached apart.
+    fn rag_cache_key(&self, question: &str) -> String {
+        if self.config.rag_collections == [DEFAULT_COLLECTION] {
+            question.to_string()
+        } else {
+            format!("[{}] {}", self.config.rag_collections.join(","), question)
+        }
+    }
+}
+
+/// Runs a multi-host plan's steps on one host over `ssh`, each confirmed
+/// through the usual flow. `config` names the host in its sandbox, so each
+/// step is rated by its own command and only wrapped in `ssh` to run.
+struct HostSteps<'a> {
+    app: &'a CliApp,
+    config: Config,
+    task: &'a str,
+    run_id: &'a str,
+    host: &'a str,
+    matrix: &'a Mutex<StepMatrix>,
+    last_run: Option<CommandRun>,
+}
+
+impl StepExecutor for HostSteps<'_> {
+    async fn check(&mut self, condition: &str) -> Result<Option<bool>> {
+        runner::check_condition(condition, &self.config)
+    }
+
+    async fn run(&mut self, command: &str) -> Result<StepOutcome> {
+        let run = runner::confirm_and_run(command, &self.config)?;
+        self.app.offer_summary(&run).await?;
+        CliApp::audit("agent", self.task, &run, Some(self.run_id));
+        let outcome = step_outcome(&run);
+        self.last_run = Some(run);
+        Ok(outcome)
+    }
+
+    fn event(&mut self, step: usize, event: StepEvent<'_>) {
+        record_host_step(self.matrix, self.host, step, &event);
+        match event {
+            StepEvent::Started => {
+                self.last_run = None;
+                println!(
+                    "\n{} {}",
+                    format!("Step {} on", step + 1).green().bold(),
+                    format!("{}:", self.host).green().bold()
+                );
+            }
+            StepEvent::AlreadyDone(check) => {
+                println!("{} {}", "Already done, skipped:".green(), check.dimmed());
+            }
+            StepEvent::CheckIgnored(check) => {
+                println!("{} {}", "Not a read-only check, so not run:".yellow(), check.dimmed());
+            }
+            StepEvent::ConditionMet(condition) => {
+                println!("{} {}", "Condition met:".green(), condition.dimmed());
+            }
+            StepEvent::Skipped(reason) => println!("{} {}", "Skipped:".yellow(), reason),
+            StepEvent::FallingBack(_) => {
+                println!("\n{}", "Step failed; running its fallback.".yellow());
+            }
+            StepEvent::Finished(_) => {
+                if let Some(run) = &self.last_run {
+                    CliApp::notify_finished(
+                        &self.app.config.notify.agent,
+                        &format!("step {} on {}", step + 1, self.host),
+                        run,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Runs a multi-host plan's steps on one host over `ssh` without asking;
+/// see [`runner::run_unattended`]. One runs per host, in parallel.
+struct UnattendedHostSteps<'a> {
+    config: Config,
+    task: &'a str,
+    run_id: &'a str,
+    host: &'a str,
+    matrix: &'a Mutex<StepMatrix>,
+}
+
+impl StepExecutor for UnattendedHostSteps<'_> {
+    async fn check(&mut self, condition: &str) -> Result<Option<bool>> {
+        let run = runner::run_unattended(condition, &self.config, |cmd| {
+            multi_host::remote_command(self.host, cmd, true)
+        })?;
+        Ok(match run.outcome {
+            RunOutcome::Succeeded => Some(true),
+            RunOutcome::Failed => Some(false),
+            _ => None,
+        })
+    }
+
+    async fn run(&mut self, command: &str) -> Result<StepOutcome> {
+        let run = runner::run_unattended(command, &self.config, |cmd| {
+            multi_host::remote_command(self.host, cmd, true)
+        })?;
+        CliApp::audit("agent", self.task, &run, Some(self.run_id));
+        if matches!(run.outcome, RunOutcome::Cancelled | RunOutcome::Blocked) {
+            println!(
+                "{} {}",
+                format!("[{}]", self.host).yellow(),
+                format!("not run without confirmation: {}", command).yellow()
+            );
+        }
+        Ok(step_outcome(&run))
+    }
+
+    fn event(&mut self, step: usize, event: StepEvent<'_>) {
+        record_host_step(self.matrix, self.host, step, &event);
+        let label = format!("[{}] step {}:", self.host, step + 1);
+        match event {
+            StepEvent::AlreadyDone(_) => println!("{} {}", label.dimmed(), "already done".green()),
+            StepEvent::CheckIgnored(_) => println!("{} {}", label.dimmed(), "check is not read-only; not run".yellow()),
+            StepEvent::Skipped(reason) => println!("{} {} {}", label.dimmed(), "skipped,".yellow(), reason),
+            StepEvent::FallingBack(fallback) => {
+                println!("{} {} {}", label.dimmed(), "failed; falling back to".yellow(), fallback)
+            }
+            StepEvent::Finished(StepOutcome::Succeeded { .. }) => println!("{} {}", label.dimmed(), "ok".green()),
+            StepEvent::Finished(StepOutcome::Failed) => println!("{} {}", label.dimmed(), "failed".red()),
+            _ => {}
+        }
+    }
+}
+
+fn step_outcome(run: &CommandRun) -> StepOutcome {
+    match (&run.outcome, &run.output) {
+        (RunOutcome::Succeeded, output) => StepOutcome::Succeeded {
+            stdout: output.as_ref().map(|o| o.stdout().to_string()),
+        },
+        (RunOutcome::Failed, _) => StepOutcome::Failed,
+        _ => StepOutcome::NotRun,
+    }
+}
+
+/// Keep the step × host matrix up to date with `event`.
+fn record_host_step(matrix: &Mutex<StepMatrix>, host: &str, step: usize, event: &StepEvent<'_>) {
+    let status = match event {
+        StepEvent::Started => CellStatus::Running,
+        StepEvent::Finished(StepOutcome::Succeeded { .. }) => CellStatus::Succeeded,
+        StepEvent::Finished(StepOutcome::Failed) => CellStatus::Failed,
+        StepEvent::Finished(StepOutcome::NotRun) => CellStatus::NotRun,
+        _ => return,
+    };
+    matrix.lock().unwrap_or_else(|e| e.into_inner()).set(step, host, status);
+}
+
+/// Runs agent steps through the usual confirmation flow and prints their
+/// progress.
+struct AgentSteps<'a> {
+    app: &'a CliApp,
+    task: &'a str,
+    /// Groups this run's steps in the audit log.
+    run_id: String,
+    /// The command run for the current step, for the finish notification.
+    last_run: Option<CommandRun>,
+    /// The shell every step runs in, started with the first step.
+    shell: Option<PtyShell>,
+    /// The shell couldn't be started; steps run in shells of their own.
+    shell_unavailable: bool,
+    /// Each step's expected duration in seconds, if the plan gave one.
+    expected: Vec<Option<u64>>,
+    /// The step being run.
+    step: usize,
+}
+
+impl AgentSteps<'_> {
+    /// The plan's shell, started again if a step ended it.
+    fn shell(&mut self) -> Option<&mut PtyShell> {
+        if self.shell_unavailable {
+            return None;
+        }
+        if !self.shell.as_mut().is_some_and(PtyShell::is_alive) {
+            if self.shell.take().is_some() {
+                println!("{}", "The plan's shell exited; later steps start in a new one.".yellow());
+            }
+            let sandbox = self.app.config_in(&self.app.session_dir()).sandbox;
+            match PtyShell::spawn(&sandbox) {
+                Ok(shell) => self.shell = Some(shell),
+                Err(e) => {
+                    eprintln!("{} {}; steps run in separate shells.", "No persistent shell:".yellow(), e);
+                    self.shell_unavailable = true;
+                }
+            }
+        }
+        self.shell.as_mut()
+    }
+}
+
+impl StepExecutor for AgentSteps<'_> {
+    async fn check(&mut self, condition: &str) -> Result<Option<bool>> {
+        let config = self.app.config_in(&self.app.session_dir());
+        let checked = runner::check_condition_in(condition, &config, self.shell());
+        // A condition doesn't move the session, even one that `cd`s.
+        if let Some(shell) = &mut self.shell {
+            shell.take_dir();
+        }
+        checked
+    }
+
+    async fn run(&mut self, command: &str) -> Result<StepOutcome> {
+        runner::preview_packages(command, &self.app.config);
+        runner::preview_service_impact(command);
+        let expected = self.expected.get(self.step).copied().flatten().map(Duration::from_secs);
+        let started = Instant::now();
+        let run = self.app.run_command(command, self.shell(), expected).await?;
+        if run.outcome == RunOutcome::Backgrounded {
+            // The shell is still running the step; later steps get a new one.
+            if self.shell.as_ref().is_some_and(|shell| shell.is_busy()) {
+                if let Some(shell) = self.shell.take() {
+                    println!("{}", "Later steps run in a new shell, without this one's variables.".dimmed());
+                    step_budget::background(command, started, move || shell.finish());
+                }
+            }
+        }
+        CliApp::audit("agent", self.task, &run, Some(&self.run_id));
+        let outcome = step_outcome(&run);
+        self.last_run = Some(run);
+        Ok(outcome)
+    }
+
+    fn event(&mut self, step: usize, event: StepEvent<'_>) {
+        match event {
+            StepEvent::Started => {
+                self.last_run = None;
+                self.step = step;
+                println!(
+                    "\n{} {}",
+                    "Step".green().bold(),
+                    format!("{}:", step + 1).green().bold()
+                );
+            }
+            StepEvent::AlreadyDone(check) => {
+                println!("{} {}", "Already done, skipped:".green(), check.dimmed());
+            }
+            StepEvent::CheckIgnored(check) => {
+                println!("{} {}", "Not a read-only check, so not run:".yellow(), check.dimmed());
+            }
+            StepEvent::ConditionMet(condition) => {
+                println!("{} {}", "Condition met:".green(), condition.dimmed());
+            }
+            StepEvent::Skipped(reason) => println!("{} {}", "Skipped:".yellow(), reason),
+            StepEvent::FallingBack(_) => {
+                println!("\n{}", "Step failed; running its fallback.".yellow());
+            }
+            StepEvent::Finished(_) => {
+                if let Some(run) = &self.last_run {
+                    CliApp::notify_finished(
+                        &self.app.config.notify.agent,
+                        &format!("step {}", step + 1),
+                        run,
+                    );
+                }
+            }
+        }
+    }
 }
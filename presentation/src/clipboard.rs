@@ -0,0 +1,230 @@
+//! Copying suggested commands to the clipboard (`--copy`).
+//!
+//! Native tools are tried in order of how likely they are to reach the
+//! user's clipboard (`wl-copy`, `xclip`/`xsel`, `pbcopy`, `clip.exe`, then
+//! the platform API), falling back to an OSC 52 escape sequence, which the
+//! terminal emulator itself handles, so copying works over SSH and inside
+//! tmux or screen. `VIBE_CLIPBOARD` forces one backend.
+
+use base64::Engine;
+use shared::types::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    WlCopy,
+    Xclip,
+    Xsel,
+    Pbcopy,
+    ClipExe,
+    /// The platform clipboard API, through `arboard`.
+    Native,
+    Osc52,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::WlCopy => "wl-copy",
+            Backend::Xclip => "xclip",
+            Backend::Xsel => "xsel",
+            Backend::Pbcopy => "pbcopy",
+            Backend::ClipExe => "clip.exe",
+            Backend::Native => "native",
+            Backend::Osc52 => "osc52",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        [
+            Backend::WlCopy,
+            Backend::Xclip,
+            Backend::Xsel,
+            Backend::Pbcopy,
+            Backend::ClipExe,
+            Backend::Native,
+            Backend::Osc52,
+        ]
+        .into_iter()
+        .find(|b| b.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Program and arguments that read the text on stdin.
+    fn command(self) -> Option<(&'static str, &'static [&'static str])> {
+        match self {
+            Backend::WlCopy => Some(("wl-copy", &[])),
+            Backend::Xclip => Some(("xclip", &["-selection", "clipboard"])),
+            Backend::Xsel => Some(("xsel", &["--clipboard", "--input"])),
+            Backend::Pbcopy => Some(("pbcopy", &[])),
+            Backend::ClipExe => Some(("clip.exe", &[])),
+            Backend::Native | Backend::Osc52 => None,
+        }
+    }
+}
+
+/// What backend detection looks at, captured once so it can be faked.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    pub ssh: bool,
+    pub wayland: bool,
+    pub x11: bool,
+    pub macos: bool,
+    pub windows_or_wsl: bool,
+    /// Programs found on `PATH`.
+    pub programs: Vec<&'static str>,
+}
+
+impl Environment {
+    pub fn detect() -> Self {
+        let set = |name: &str| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+        let wsl = set("WSL_DISTRO_NAME")
+            || std::fs::read_to_string("/proc/version")
+                .is_ok_and(|v| v.to_lowercase().contains("microsoft"));
+        let programs = ["wl-copy", "xclip", "xsel", "pbcopy", "clip.exe"]
+            .into_iter()
+            .filter(|p| on_path(p))
+            .collect();
+        Self {
+            ssh: set("SSH_CONNECTION") || set("SSH_TTY"),
+            wayland: set("WAYLAND_DISPLAY"),
+            x11: set("DISPLAY"),
+            macos: cfg!(target_os = "macos"),
+            windows_or_wsl: cfg!(windows) || wsl,
+            programs,
+        }
+    }
+
+    fn has(&self, program: &str) -> bool {
+        self.programs.contains(&program)
+    }
+}
+
+pub(crate) fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| dir.join(program).is_file())
+    })
+}
+
+/// Backends to try, best first. Over SSH the local tools would fill the
+/// remote machine's clipboard, so OSC 52 goes first there.
+pub fn candidates(env: &Environment) -> Vec<Backend> {
+    let mut backends = Vec::new();
+    if env.ssh {
+        backends.push(Backend::Osc52);
+    }
+    if env.wayland && env.has("wl-copy") {
+        backends.push(Backend::WlCopy);
+    }
+    if env.x11 {
+        if env.has("xclip") {
+            backends.push(Backend::Xclip);
+        }
+        if env.has("xsel") {
+            backends.push(Backend::Xsel);
+        }
+    }
+    if env.macos && env.has("pbcopy") {
+        backends.push(Backend::Pbcopy);
+    }
+    if env.windows_or_wsl && env.has("clip.exe") {
+        backends.push(Backend::ClipExe);
+    }
+    if !env.ssh {
+        backends.push(Backend::Native);
+        backends.push(Backend::Osc52);
+    }
+    backends
+}
+
+/// Copy `text` with the first backend that works, and report which one.
+pub fn copy(text: &str) -> Result<Backend> {
+    let backends = match std::env::var("VIBE_CLIPBOARD") {
+        Ok(name) => vec![Backend::parse(&name)
+            .ok_or_else(|| anyhow::anyhow!("unknown VIBE_CLIPBOARD backend '{}'", name))?],
+        Err(_) => candidates(&Environment::detect()),
+    };
+    let mut last_error = None;
+    for backend in backends {
+        match copy_with(backend, text) {
+            Ok(()) => return Ok(backend),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("no clipboard backend available")))
+}
+
+pub fn copy_with(backend: Backend, text: &str) -> Result<()> {
+    if let Some((program, args)) = backend.command() {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("{} has no stdin", program))?
+            .write_all(text.as_bytes())?;
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("{} exited with {}", program, status));
+        }
+        return Ok(());
+    }
+    match backend {
+        Backend::Native => {
+            arboard::Clipboard::new()?.set_text(text.to_string())?;
+            Ok(())
+        }
+        _ => write_osc52(text),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplexer {
+    None,
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    pub fn detect() -> Self {
+        if std::env::var_os("TMUX").is_some() {
+            Multiplexer::Tmux
+        } else if std::env::var("TERM").is_ok_and(|t| t.starts_with("screen"))
+            || std::env::var_os("STY").is_some()
+        {
+            Multiplexer::Screen
+        } else {
+            Multiplexer::None
+        }
+    }
+}
+
+/// The OSC 52 "set clipboard" sequence for `text`, wrapped in the
+/// passthrough escape a multiplexer needs to forward it to the terminal.
+pub fn osc52_sequence(text: &str, multiplexer: Multiplexer) -> String {
+    let payload = base64::engine::general_purpose::STANDARD.encode(text);
+    let osc = format!("\x1b]52;c;{}\x07", payload);
+    match multiplexer {
+        Multiplexer::None => osc,
+        // tmux needs `set -g allow-passthrough on` (3.3+) for this to pass.
+        Multiplexer::Tmux => format!("\x1bPtmux;{}\x1b\\", osc.replace('\x1b', "\x1b\x1b")),
+        Multiplexer::Screen => format!("\x1bP{}\x1b\\", osc),
+    }
+}
+
+/// Send the sequence to the controlling terminal, so it is not captured
+/// when stdout is redirected.
+fn write_osc52(text: &str) -> Result<()> {
+    let sequence = osc52_sequence(text, Multiplexer::detect());
+    let mut tty = std::fs::OpenOptions::new()
+        .write(true)
+        .open(if cfg!(windows) { "CONOUT$" } else { "/dev/tty" })
+        .map_err(|e| anyhow::anyhow!("no terminal for OSC 52: {}", e))?;
+    tty.write_all(sequence.as_bytes())?;
+    tty.flush()?;
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//! Telling the model about the user's active environment (virtualenvs,
+//! cloud profiles, kube configs) through an opt-in allowlist of variables,
+//! so suggestions target the right context without leaking secrets.
+
+use infrastructure::config::EnvContextSettings;
+
+/// Longest value shared; anything longer is cut.
+const MAX_VALUE_CHARS: usize = 200;
+
+/// One line for the prompt naming the allowlisted variables that are set,
+/// with values only for those in `values`; `None` if none are set.
+pub fn describe(
+    settings: &EnvContextSettings,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let mut seen = Vec::new();
+    let mut parts = Vec::new();
+    for name in settings.names.iter().chain(&settings.values) {
+        let name = name.trim();
+        if name.is_empty() || seen.contains(&name) {
+            continue;
+        }
+        seen.push(name);
+        let Some(value) = lookup(name) else {
+            continue;
+        };
+        if settings.values.iter().any(|v| v.trim() == name) {
+            parts.push(format!("{}={}", name, clean_value(&value)));
+        } else {
+            parts.push(format!("{} is set", name));
+        }
+    }
+    if parts.is_empty() {
+        None
+    } else {
+        Some(format!("Active environment variables: {}", parts.join(", ")))
+    }
+}
+
+/// `value` on one line and at most [`MAX_VALUE_CHARS`] long, so it cannot
+/// smuggle extra instructions into the prompt.
+fn clean_value(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let value = value.trim();
+    match value.char_indices().nth(MAX_VALUE_CHARS) {
+        Some((end, _)) => format!("{}...", &value[..end]),
+        None => value.to_string(),
+    }
+}
+
+/// [`describe`] for the current process environment.
+pub fn from_process(settings: &EnvContextSettings) -> Option<String> {
+    describe(settings, |name| std::env::var(name).ok())
+}
@@ -0,0 +1,213 @@
+//! Showing code from the index in the terminal: fenced blocks in RAG
+//! answers and the bodies of retrieved chunks are syntax highlighted by
+//! language, and every line is wrapped to the terminal width.
+
+use colored::Colorize;
+use shared::accessibility;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Style, Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::{as_24_bit_terminal_escaped, split_at, LinesWithEndings};
+
+/// Used when the terminal size is unknown, e.g. when output is piped.
+const DEFAULT_WIDTH: usize = 100;
+/// Narrower terminals still get this much per line.
+const MIN_WIDTH: usize = 20;
+const THEME: &str = "base16-ocean.dark";
+const RESET: &str = "\x1b[0m";
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEME_SET: OnceLock<ThemeSet> = OnceLock::new();
+    &THEME_SET.get_or_init(ThemeSet::load_defaults).themes[THEME]
+}
+
+/// The terminal's width in columns, from `COLUMNS` or the terminal itself.
+pub fn terminal_width() -> usize {
+    let columns = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .or_else(|| {
+            crossterm::terminal::size()
+                .ok()
+                .map(|(columns, _)| columns as usize)
+        })
+        .filter(|&columns| columns > 0)
+        .unwrap_or(DEFAULT_WIDTH);
+    columns.max(MIN_WIDTH)
+}
+
+pub struct Highlighter {
+    width: usize,
+    color: bool,
+}
+
+impl Default for Highlighter {
+    /// The terminal's width, colored unless color is turned off.
+    fn default() -> Self {
+        Self {
+            width: terminal_width(),
+            color: colored::control::SHOULD_COLORIZE.should_colorize(),
+        }
+    }
+}
+
+impl Highlighter {
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width.max(MIN_WIDTH);
+        self
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// A model answer: prose is word-wrapped, and fenced code blocks are
+    /// highlighted for the language named after the opening fence.
+    pub fn render_answer(&self, text: &str) -> String {
+        let mut out = String::new();
+        let mut lines = text.lines();
+        while let Some(line) = lines.next() {
+            let Some(language) = line.trim_start().strip_prefix("```") else {
+                self.wrap_prose(line, &mut out);
+                continue;
+            };
+            self.push_fence(line, &mut out);
+            let mut code = String::new();
+            let mut closing = None;
+            for line in lines.by_ref() {
+                if line.trim() == "```" {
+                    closing = Some(line);
+                    break;
+                }
+                code.push_str(line);
+                code.push('\n');
+            }
+            let syntax = syntaxes()
+                .find_syntax_by_token(language.trim())
+                .unwrap_or_else(|| syntaxes().find_syntax_plain_text());
+            self.highlight_code(&code, syntax, &mut out);
+            if let Some(closing) = closing {
+                self.push_fence(closing, &mut out);
+            }
+        }
+        out
+    }
+
+    /// A retrieved chunk as stored in the index: its `FILE:` and `OFFSET:`
+    /// header lines dimmed, and the rest highlighted by the file's extension.
+    pub fn render_chunk(&self, chunk: &str) -> String {
+        let mut out = String::new();
+        let mut body = chunk;
+        let mut path = "";
+        while let Some((line, rest)) = body.split_once('\n') {
+            if let Some(file) = line.strip_prefix("FILE: ") {
+                path = file.trim();
+            } else if !line.starts_with("OFFSET: ") {
+                break;
+            }
+            self.push_fence(line, &mut out);
+            body = rest;
+        }
+        let syntax = std::path::Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| syntaxes().find_syntax_by_extension(ext))
+            .unwrap_or_else(|| syntaxes().find_syntax_plain_text());
+        self.highlight_code(body, syntax, &mut out);
+        out
+    }
+
+    fn push_fence(&self, line: &str, out: &mut String) {
+        if self.color {
+            out.push_str(&line.dimmed().to_string());
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+
+    /// Code lines are cut at the width, never at words, so nothing shifts;
+    /// each continuation starts with a marker so it isn't taken for a line
+    /// of its own.
+    fn highlight_code(&self, code: &str, syntax: &SyntaxReference, out: &mut String) {
+        let marker = accessibility::glyph("↪ ", "> ");
+        let continuation = if self.color {
+            marker.dimmed().to_string()
+        } else {
+            marker.to_string()
+        };
+        let mut highlighter = HighlightLines::new(syntax, theme());
+        for line in LinesWithEndings::from(code) {
+            let ranges = match highlighter.highlight_line(line, syntaxes()) {
+                Ok(ranges) if self.color => ranges,
+                _ => vec![(Style::default(), line)],
+            };
+            let mut rest: Vec<(Style, &str)> = ranges
+                .into_iter()
+                .map(|(style, text)| (style, text.trim_end_matches(['\n', '\r'])))
+                .collect();
+            let mut width = self.width;
+            loop {
+                let text: String = rest.iter().map(|(_, text)| *text).collect();
+                let cut = text.char_indices().nth(width).map(|(at, _)| at);
+                let (head, tail) = match cut {
+                    Some(at) => split_at(&rest, at),
+                    None => (rest, Vec::new()),
+                };
+                self.push_ranges(&head, out);
+                if tail.is_empty() {
+                    break;
+                }
+                out.push_str(&continuation);
+                rest = tail;
+                width = self.width - marker.chars().count();
+            }
+        }
+    }
+
+    fn push_ranges(&self, ranges: &[(Style, &str)], out: &mut String) {
+        if self.color {
+            out.push_str(&as_24_bit_terminal_escaped(ranges, false));
+            out.push_str(RESET);
+        } else {
+            out.extend(ranges.iter().map(|(_, text)| *text));
+        }
+        out.push('\n');
+    }
+
+    /// Greedy word wrap; continuation lines keep the line's indentation.
+    fn wrap_prose(&self, line: &str, out: &mut String) {
+        if line.chars().count() <= self.width {
+            out.push_str(line);
+            out.push('\n');
+            return;
+        }
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let mut current = indent.to_string();
+        let mut current_len = indent.chars().count();
+        for word in line.split_whitespace() {
+            let len = word.chars().count();
+            if current_len > indent.chars().count() && current_len + 1 + len > self.width {
+                out.push_str(&current);
+                out.push('\n');
+                current = indent.to_string();
+                current_len = indent.chars().count();
+            }
+            if current_len > indent.chars().count() {
+                current.push(' ');
+                current_len += 1;
+            }
+            current.push_str(word);
+            current_len += len;
+        }
+        out.push_str(&current);
+        out.push('\n');
+    }
+}
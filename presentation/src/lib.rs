@@ -1,2 +1,19 @@
 pub mod adapters;
 pub mod cli;
+pub mod clipboard;
+pub mod env_context;
+pub mod highlight;
+pub mod notify;
+pub mod pane;
+pub mod parsing;
+pub mod plan_diff;
+pub mod policy_test;
+pub mod pty_attach;
+pub mod pty_shell;
+pub mod revert_window;
+pub mod runner;
+pub mod safety;
+pub mod script_output;
+pub mod script_params;
+pub mod script_preflight;
+pub mod step_budget;
@@ -0,0 +1,62 @@
+//! Announcing that a long-running step finished, with a terminal bell and a
+//! desktop notification, for users who switched away during a long install.
+
+use infrastructure::config::NotifyRule;
+use std::io::Write;
+use std::time::Duration;
+
+/// `1m05s`, `42s`.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Notification summary, e.g. `vibe: step 2 failed after 1m05s`.
+pub fn summary(what: &str, succeeded: bool, elapsed: Duration) -> String {
+    format!(
+        "vibe: {} {} after {}",
+        what,
+        if succeeded { "finished" } else { "failed" },
+        format_elapsed(elapsed)
+    )
+}
+
+/// Announce a finished run when `rule` says it took long enough; returns
+/// whether anything was emitted. Desktop notifications are best-effort, as
+/// there may be no notification daemon (e.g. over SSH).
+pub fn finished(
+    rule: &NotifyRule,
+    what: &str,
+    detail: &str,
+    succeeded: bool,
+    elapsed: Duration,
+) -> bool {
+    if !rule.applies(elapsed) {
+        return false;
+    }
+    if rule.bell {
+        print!("\x07");
+        let _ = std::io::stdout().flush();
+    }
+    if rule.desktop {
+        desktop(&summary(what, succeeded, elapsed), detail);
+    }
+    true
+}
+
+#[cfg(feature = "desktop-notifications")]
+fn desktop(summary: &str, body: &str) {
+    let _ = notify_rust::Notification::new()
+        .appname("vibe")
+        .summary(summary)
+        .body(body)
+        .show();
+}
+
+/// Built without the `desktop-notifications` feature: only the bell rings.
+#[cfg(not(feature = "desktop-notifications"))]
+fn desktop(_summary: &str, _body: &str) {}
@@ -0,0 +1,111 @@
+//! Typing a confirmed command into a tmux pane or screen window
+//! (`--send-to-pane`) instead of running it, so the user can review it and
+//! press Enter in their own, fully interactive shell.
+
+use shared::types::Result;
+use std::process::Command;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaneTarget {
+    /// Any tmux target, e.g. `2`, `work:1.0` or `%3`.
+    Tmux(String),
+    /// `screen:<session>[:<window>]`.
+    Screen {
+        session: String,
+        window: Option<String>,
+    },
+}
+
+impl PaneTarget {
+    pub fn parse(target: &str) -> Result<Self> {
+        let target = target.trim();
+        if let Some(rest) = target.strip_prefix("screen:") {
+            let (session, window) = match rest.split_once(':') {
+                Some((session, window)) => (session, Some(window.to_string())),
+                None => (rest, None),
+            };
+            if session.is_empty() || window.as_deref() == Some("") {
+                return Err(anyhow::anyhow!(
+                    "screen target must be screen:<session>[:<window>], got '{}'",
+                    target
+                ));
+            }
+            return Ok(PaneTarget::Screen {
+                session: session.to_string(),
+                window,
+            });
+        }
+        let target = target.strip_prefix("tmux:").unwrap_or(target);
+        if target.is_empty() {
+            return Err(anyhow::anyhow!("empty pane target"));
+        }
+        Ok(PaneTarget::Tmux(target.to_string()))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            PaneTarget::Tmux(target) => format!("tmux pane {}", target),
+            PaneTarget::Screen {
+                session,
+                window: Some(window),
+            } => format!("screen window {} of {}", window, session),
+            PaneTarget::Screen { session, .. } => format!("screen session {}", session),
+        }
+    }
+
+    /// The multiplexer invocation that types `text` without a trailing Enter.
+    pub fn send_command(&self, text: &str) -> Command {
+        match self {
+            PaneTarget::Tmux(target) => {
+                let mut command = Command::new("tmux");
+                // -l sends the text literally rather than as key names
+                command.args(["send-keys", "-t", target, "-l", "--", text]);
+                command
+            }
+            PaneTarget::Screen { session, window } => {
+                let mut command = Command::new("screen");
+                command.args(["-S", session]);
+                if let Some(window) = window {
+                    command.args(["-p", window]);
+                }
+                command.args(["-X", "stuff", &screen_escape(text)]);
+                command
+            }
+        }
+    }
+}
+
+/// screen's command parser expands `\`, `^` and `$` sequences in `stuff`.
+fn screen_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '^' | '$') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Type `command` into `target`. Multi-line commands are refused: each
+/// newline would press Enter and run part of it.
+pub fn send(target: &PaneTarget, command: &str) -> Result<()> {
+    let command = command.trim_end_matches(['\n', '\r']);
+    if command.contains('\n') {
+        return Err(anyhow::anyhow!(
+            "multi-line commands cannot be typed without running them"
+        ));
+    }
+    let output = target
+        .send_command(command)
+        .output()
+        .map_err(|e| anyhow::anyhow!("could not reach {}: {}", target.describe(), e))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "could not send to {}: {}",
+            target.describe(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
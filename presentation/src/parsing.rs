@@ -0,0 +1,36 @@
+pub fn extract_command_from_response(response: &str) -> String {
+    let response = response.trim();
+    // Fenced block (```bash, ```sh or bare ```): drop the fence lines.
+    let cleaned = if response.starts_with("```") && response.ends_with("```") {
+        let start = response.find('\n').unwrap_or(0) + 1;
+        let end = response.len() - 3;
+        response[start..end].trim().to_string()
+    } else {
+        response.to_string()
+    };
+    // Remove surrounding backticks, quotes, and extra whitespace
+    cleaned
+        .trim_matches('`')
+        .trim_matches('"')
+        .trim_matches('\'')
+        .trim()
+        .to_string()
+}
+
+/// Parse an interval like `30s`, `5m`, `1h`, `7d` or a bare number of
+/// seconds.
+pub fn parse_interval(value: &str) -> Option<std::time::Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let amount: u64 = value[..split].parse().ok()?;
+    let seconds = match value[split..].trim() {
+        "" | "s" | "sec" | "secs" => amount,
+        "m" | "min" | "mins" => amount * 60,
+        "h" | "hr" | "hrs" => amount * 3600,
+        "d" | "day" | "days" => amount * 86400,
+        _ => return None,
+    };
+    Some(std::time::Duration::from_secs(seconds))
+}
@@ -0,0 +1,85 @@
+//! What regenerating an agent plan with feedback changed: steps added,
+//! removed and changed relative to the plan the user rejected.
+
+use colored::Colorize;
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanChange {
+    /// Indices of the step in the old and new plans.
+    Kept { old: usize, new: usize },
+    Removed { old: usize },
+    Added { new: usize },
+    Changed { old: usize, new: usize },
+}
+
+/// Step-level diff of `old` and `new`; a removed step directly replaced by
+/// an added one counts as changed.
+pub fn diff_plans(old: &[String], new: &[String]) -> Vec<PlanChange> {
+    let mut changes = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, old, new) {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Equal => changes.extend(
+                old_range
+                    .zip(new_range)
+                    .map(|(old, new)| PlanChange::Kept { old, new }),
+            ),
+            DiffTag::Delete => changes.extend(old_range.map(|old| PlanChange::Removed { old })),
+            DiffTag::Insert => changes.extend(new_range.map(|new| PlanChange::Added { new })),
+            DiffTag::Replace => {
+                let paired = old_range.len().min(new_range.len());
+                changes.extend(
+                    old_range
+                        .clone()
+                        .zip(new_range.clone())
+                        .map(|(old, new)| PlanChange::Changed { old, new }),
+                );
+                changes.extend(old_range.skip(paired).map(|old| PlanChange::Removed { old }));
+                changes.extend(new_range.skip(paired).map(|new| PlanChange::Added { new }));
+            }
+        }
+    }
+    changes
+}
+
+/// `+`, `-` and `~` lines per step numbered as in the new plan (removed
+/// steps by their old number), then a one-line tally.
+pub fn render(old: &[String], new: &[String]) -> String {
+    let changes = diff_plans(old, new);
+    let mut out = String::new();
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for change in &changes {
+        let line = match *change {
+            PlanChange::Kept { new: n, .. } => {
+                format!("  [{}] {}", n + 1, new[n]).dimmed().to_string()
+            }
+            PlanChange::Removed { old: o } => {
+                removed += 1;
+                format!("- [{}] {}", o + 1, old[o]).red().to_string()
+            }
+            PlanChange::Added { new: n } => {
+                added += 1;
+                format!("+ [{}] {}", n + 1, new[n]).green().to_string()
+            }
+            PlanChange::Changed { old: o, new: n } => {
+                changed += 1;
+                format!(
+                    "{}\n{}",
+                    format!("~ [{}] {}", n + 1, new[n]).yellow(),
+                    format!("      was: {}", old[o]).dimmed()
+                )
+            }
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    let tally = if added + removed + changed == 0 {
+        "The plan is unchanged.".to_string()
+    } else {
+        format!("{} added, {} removed, {} changed", added, removed, changed)
+    };
+    out.push_str(&tally);
+    out.push('\n');
+    out
+}
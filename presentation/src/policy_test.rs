@@ -0,0 +1,107 @@
+//! `policy test`: a command put through the checks it would meet before
+//! running (rewrite rules, syntax, built-in blocks, administrator policy,
+//! warnings, SSH and network changes, the allowlist, the confirmation rule
+//! for its tier), reporting each rule that matched and the verdict. Nothing
+//! is run and the model isn't asked.
+
+use crate::runner::{allowlisted, validate_command_syntax};
+use crate::safety::{assess_command, confirm_rule, mutation_reason, not_read_only, risk_tier, RiskTier};
+use infrastructure::config::Config;
+use infrastructure::policy::Policy;
+use infrastructure::postprocess::PostProcessor;
+use shared::types::Result;
+
+/// A rule that matched, and which check it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleMatch {
+    /// `rewrite`, `syntax`, `block`, `policy`, `warning`, `ssh`, `network`,
+    /// `mutation` or `allowlist`.
+    pub check: &'static str,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simulation {
+    /// The command after rewrite rules, as it would be checked and run.
+    pub command: String,
+    pub matches: Vec<RuleMatch>,
+    /// `None` when the command fails the syntax check and is never tiered.
+    pub tier: Option<RiskTier>,
+    /// What would happen: blocked, rejected, run unconfirmed or how it
+    /// would be confirmed.
+    pub verdict: String,
+}
+
+/// What the safety checks make of `cmd` under `config` and `policy`.
+pub fn simulate(cmd: &str, config: &Config, policy: &Policy) -> Result<Simulation> {
+    let mut matches = Vec::new();
+    let mut found = |check: &'static str, detail: String| matches.push(RuleMatch { check, detail });
+
+    let (command, applied) = PostProcessor::new(&config.postprocess_rules)?.apply(cmd);
+    for label in applied {
+        found("rewrite", label);
+    }
+    if let Err(e) = validate_command_syntax(&command) {
+        found("syntax", e.to_string());
+        return Ok(Simulation {
+            command,
+            matches,
+            tier: None,
+            verdict: "rejected: fails the syntax check and is never run".to_string(),
+        });
+    }
+
+    let mut assessment = assess_command(&command, config.safe_mode);
+    for reason in &assessment.reasons {
+        found("block", reason.clone());
+    }
+    if let Some(reason) = policy.blocked_reason(&command) {
+        found("policy", reason.clone());
+        assessment.blocked = true;
+        assessment.reasons.push(reason);
+    }
+    for warning in &assessment.warnings {
+        found("warning", warning.clone());
+    }
+    for risk in &assessment.ssh {
+        found("ssh", risk.clone());
+    }
+    for change in &assessment.network {
+        found("network", change.clone());
+    }
+    if let Some(reason) = mutation_reason(&command) {
+        found("mutation", reason);
+    } else if command.to_lowercase().contains("sudo") {
+        found("mutation", "Uses 'sudo'.".to_string());
+    } else if let Some(reason) = not_read_only(&command) {
+        found("mutation", reason);
+    }
+
+    let tier = risk_tier(&command, &assessment);
+    let verdict = match (confirm_rule(tier, &config.confirm), allowlisted(&command, tier)) {
+        (None, _) => "blocked: never run".to_string(),
+        (Some(_), Some(entry)) => {
+            found("allowlist", format!("entry {}", entry.id()));
+            "runs without confirmation (allowlisted)".to_string()
+        }
+        (Some(rule), None) => {
+            let mut verdict = format!(
+                "asks for confirmation (Enter means {})",
+                if rule.default_yes { "yes" } else { "no" }
+            );
+            if let Some(phrase) = &rule.phrase {
+                verdict.push_str(&format!(", after typing '{}'", phrase));
+            }
+            if rule.double {
+                verdict.push_str(", then asks again");
+            }
+            verdict
+        }
+    };
+    Ok(Simulation {
+        command,
+        matches,
+        tier: Some(tier),
+        verdict,
+    })
+}
@@ -0,0 +1,238 @@
+//! Running a command that needs the user at the keyboard in a PTY attached
+//! to the terminal: keys go to the command as typed, its output is drawn
+//! as is, and a plain-text transcript of that output is recorded for the
+//! run.
+
+use crate::runner::CapturedOutput;
+use application::env_review::scrubbed_environment;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use infrastructure::config::SandboxSettings;
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use shared::types::Result;
+use std::io::{Read, Write};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long the transcript may lag once the command has exited, e.g.
+/// behind a background process still holding the PTY.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Run `cmd` attached to the terminal through a PTY with `secrets` in its
+/// environment. Returns its exit code (`None` if killed by a signal) and
+/// the transcript.
+pub fn run_attached(
+    cmd: &str,
+    sandbox: &SandboxSettings,
+    secrets: &[(String, String)],
+) -> Result<(Option<i32>, CapturedOutput)> {
+    let (cols, rows) = terminal::size()?;
+    let pair = native_pty_system().openpty(size(cols, rows))?;
+    let mut command = CommandBuilder::new("bash");
+    command.args(["-c", cmd]);
+    if sandbox.scrub_env {
+        command.env_clear();
+        for (var, value) in scrubbed_environment(std::env::vars(), &sandbox.keep) {
+            command.env(var, value);
+        }
+    }
+    for (var, value) in secrets {
+        command.env(var, value);
+    }
+    match &sandbox.cwd {
+        Some(dir) => command.cwd(dir),
+        None => command.cwd(std::env::current_dir()?),
+    }
+    let raw = RawMode::enable()?;
+    let mut child = pair.slave.spawn_command(command)?;
+    drop(pair.slave);
+
+    let mut reader = pair.master.try_clone_reader()?;
+    let mut writer = pair.master.take_writer()?;
+    let (done, transcript) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut recorded = Transcript::default();
+        let mut stdout = std::io::stdout();
+        let mut buffer = [0u8; 8192];
+        while let Ok(read) = reader.read(&mut buffer) {
+            if read == 0 {
+                break;
+            }
+            let _ = stdout.write_all(&buffer[..read]);
+            let _ = stdout.flush();
+            recorded.feed(&buffer[..read]);
+        }
+        let _ = done.send(recorded.finish());
+    });
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if !event::poll(Duration::from_millis(50))? {
+            continue;
+        }
+        let input = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key_bytes(&key),
+            Event::Paste(text) => Some(text.into_bytes()),
+            Event::Resize(cols, rows) => {
+                let _ = pair.master.resize(size(cols, rows));
+                None
+            }
+            _ => None,
+        };
+        if let Some(input) = input {
+            // The command may have stopped reading; its exit is seen on
+            // the next turn.
+            let _ = writer.write_all(&input).and_then(|_| writer.flush());
+        }
+    };
+    drop(raw);
+    let output = transcript.recv_timeout(DRAIN_TIMEOUT).unwrap_or_default();
+    let code = match status.signal() {
+        Some(_) => None,
+        None => i32::try_from(status.exit_code()).ok(),
+    };
+    Ok((code, output))
+}
+
+fn size(cols: u16, rows: u16) -> PtySize {
+    PtySize {
+        rows,
+        cols,
+        ..Default::default()
+    }
+}
+
+/// Raw mode on the user's terminal while the command has it, so every key
+/// reaches the command; restored when dropped.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// The bytes a terminal sends for `key`.
+fn key_bytes(key: &KeyEvent) -> Option<Vec<u8>> {
+    let control = key.modifiers.contains(KeyModifiers::CONTROL);
+    let mut bytes = match key.code {
+        KeyCode::Char(c) if control => match c.to_ascii_lowercase() {
+            c @ 'a'..='z' => vec![c as u8 - b'a' + 1],
+            ' ' | '@' | '2' => vec![0],
+            '[' | '3' => vec![0x1b],
+            '\\' | '4' => vec![0x1c],
+            ']' | '5' => vec![0x1d],
+            '^' | '6' => vec![0x1e],
+            '_' | '7' | '/' => vec![0x1f],
+            _ => return None,
+        },
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => b"\r".to_vec(),
+        KeyCode::Tab => b"\t".to_vec(),
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Backspace => b"\x7f".to_vec(),
+        KeyCode::Esc => b"\x1b".to_vec(),
+        KeyCode::Up => b"\x1b[A".to_vec(),
+        KeyCode::Down => b"\x1b[B".to_vec(),
+        KeyCode::Right => b"\x1b[C".to_vec(),
+        KeyCode::Left => b"\x1b[D".to_vec(),
+        KeyCode::Home => b"\x1b[H".to_vec(),
+        KeyCode::End => b"\x1b[F".to_vec(),
+        KeyCode::Insert => b"\x1b[2~".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        KeyCode::PageUp => b"\x1b[5~".to_vec(),
+        KeyCode::PageDown => b"\x1b[6~".to_vec(),
+        KeyCode::F(n) => match n {
+            1..=4 => vec![0x1b, b'O', b'P' + n - 1],
+            5 => b"\x1b[15~".to_vec(),
+            6..=10 => format!("\x1b[{}~", n + 11).into_bytes(),
+            11 | 12 => format!("\x1b[{}~", n + 12).into_bytes(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        bytes.insert(0, 0x1b);
+    }
+    Some(bytes)
+}
+
+/// The text of a terminal session, line by line: escape sequences and
+/// control characters are dropped, and a line overwritten after a carriage
+/// return (a progress bar) keeps only what was left on screen.
+#[derive(Default)]
+pub struct Transcript {
+    line: Vec<u8>,
+    output: CapturedOutput,
+}
+
+impl Transcript {
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.line);
+                self.output.push(plain_text(&line), false);
+            } else {
+                self.line.push(byte);
+            }
+        }
+    }
+
+    /// Everything recorded; it was all shown as it came.
+    pub fn finish(mut self) -> CapturedOutput {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.output.push(plain_text(&line), false);
+        }
+        self.output.shown_lines = self.output.total_lines;
+        self.output
+    }
+}
+
+fn plain_text(line: &[u8]) -> String {
+    let line = String::from_utf8_lossy(line);
+    let line = line.strip_suffix('\r').unwrap_or(&line);
+    let mut text = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters up to a final byte in @..~.
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: up to BEL or ESC \.
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                // Character set selection takes one more character.
+                Some('(' | ')') => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            '\r' => text.clear(),
+            '\t' => text.push(c),
+            c if c.is_control() => {}
+            c => text.push(c),
+        }
+    }
+    text
+}
@@ -0,0 +1,326 @@
+//! One bash process that runs every step of an agent plan, so what a step
+//! leaves behind (exported variables, `cd`, an activated virtualenv) is
+//! there for the next step, as it would be in a terminal.
+//!
+//! The shell runs in a PTY, so programs behave as they would for the user.
+//! Each command is sent as one base64 line, which keeps its quoting and
+//! newlines intact and out of the shell's input handling, and is followed
+//! by a sentinel line carrying its exit status and directory; that line is
+//! where one step's output ends. Standard error comes back through a FIFO
+//! of its own, so a step's standard output stays separate for
+//! `{{prev.stdout}}`.
+
+use crate::runner::CapturedOutput;
+use crate::step_budget::{Budget, Overrun};
+use application::env_review::scrubbed_environment;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use infrastructure::config::SandboxSettings;
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use shared::types::Result;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// How long a killed step has to end after Ctrl-C before the shell is
+/// killed instead.
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// What the shell runs: read a line holding the directory and the command,
+/// run the command there with stdin closed and stderr on the FIFO, then
+/// end both streams with the sentinel (`$2`). Echo is off so the input
+/// isn't mixed into the output.
+const SHELL_LOOP: &str = r#"stty -echo -icanon -onlcr 2>/dev/null
+exec 4>"$1"
+__vibe_end=$2
+while IFS=' ' read -r __vibe_dir __vibe_cmd; do
+  cd -- "$(printf %s "$__vibe_dir" | base64 -d)" 2>&4 &&
+    eval "$(printf %s "$__vibe_cmd" | base64 -d)" </dev/null 2>&4
+  __vibe_status=$?
+  printf '\n%s %s %s\n' "$__vibe_end" "$__vibe_status" "$PWD"
+  printf '\n%s\n' "$__vibe_end" >&4
+done"#;
+
+/// A line from the shell: standard output (from the PTY) or error.
+enum Line {
+    Out(String),
+    Err(String),
+}
+
+/// How a command in the shell ended.
+pub struct PtyRun {
+    /// Its exit status; `None` if the shell itself exited.
+    pub code: Option<i32>,
+    pub output: CapturedOutput,
+    /// The user left the command running in the background; the shell is
+    /// still busy with it.
+    pub backgrounded: bool,
+}
+
+/// A persistent bash session in a PTY.
+pub struct PtyShell {
+    child: Box<dyn Child + Send + Sync>,
+    writer: Box<dyn Write + Send>,
+    // Kept open for the life of the shell: closing it hangs the shell up.
+    _master: Box<dyn MasterPty + Send>,
+    lines: Receiver<Line>,
+    sentinel: String,
+    fifo_dir: PathBuf,
+    dir: Option<PathBuf>,
+    /// A command was sent whose end hasn't been read yet.
+    busy: bool,
+}
+
+impl PtyShell {
+    /// Start a shell in `sandbox`'s directory (vibe_cli's own if unset),
+    /// with its environment scrubbed in paranoid mode.
+    pub fn spawn(sandbox: &SandboxSettings) -> Result<Self> {
+        let nonce = format!(
+            "{}_{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or_default()
+        );
+        let fifo_dir = std::env::temp_dir().join(format!("vibe_cli_pty_{}", nonce));
+        std::fs::create_dir(&fifo_dir)?;
+        let fifo = fifo_dir.join("stderr");
+        let made = std::process::Command::new("mkfifo").arg("-m").arg("600").arg(&fifo).status()?;
+        if !made.success() {
+            let _ = std::fs::remove_dir_all(&fifo_dir);
+            return Err(anyhow::anyhow!("mkfifo {} failed", fifo.display()));
+        }
+        let sentinel = format!("__VIBE_STEP_END_{}__", nonce);
+
+        let (cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
+        let pair = native_pty_system().openpty(PtySize {
+            rows,
+            cols,
+            ..Default::default()
+        })?;
+        let mut command = CommandBuilder::new("bash");
+        command.args(["--noprofile", "--norc", "-c", SHELL_LOOP, "bash"]);
+        command.arg(&fifo);
+        command.arg(&sentinel);
+        if sandbox.scrub_env {
+            command.env_clear();
+            for (var, value) in scrubbed_environment(std::env::vars(), &sandbox.keep) {
+                command.env(var, value);
+            }
+        }
+        // Output is read as text, not drawn on a screen.
+        command.env("TERM", "dumb");
+        command.env("NO_COLOR", "1");
+        match &sandbox.cwd {
+            Some(dir) => command.cwd(dir),
+            None => command.cwd(std::env::current_dir()?),
+        }
+        let child = pair.slave.spawn_command(command)?;
+        drop(pair.slave);
+
+        let (sender, lines) = mpsc::channel();
+        let reader = pair.master.try_clone_reader()?;
+        let out = sender.clone();
+        std::thread::spawn(move || forward(reader, &out, Line::Out));
+        let fifo_path = fifo.clone();
+        std::thread::spawn(move || {
+            // Blocks until the shell opens its end.
+            if let Ok(file) = std::fs::File::open(&fifo_path) {
+                forward(file, &sender, Line::Err);
+            }
+        });
+
+        Ok(Self {
+            child,
+            writer: pair.master.take_writer()?,
+            _master: pair.master,
+            lines,
+            sentinel,
+            fifo_dir,
+            dir: None,
+            busy: false,
+        })
+    }
+
+    /// The shell hasn't exited.
+    pub fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// A command left running in the background hasn't ended yet.
+    pub fn is_busy(&self) -> bool {
+        self.busy
+    }
+
+    /// The directory the last command left the shell in, unless already
+    /// taken.
+    pub fn take_dir(&mut self) -> Option<PathBuf> {
+        self.dir.take()
+    }
+
+    /// Run `cmd` in `dir`, echoing its output live until `display_lines`
+    /// lines have been shown, as [`crate::runner::run_captured`] does.
+    pub fn run(&mut self, cmd: &str, dir: &Path, display_lines: usize) -> Result<PtyRun> {
+        self.run_within(cmd, dir, display_lines, None)
+    }
+
+    /// [`Self::run`], asking the user what to do once `cmd` runs well past
+    /// `expected` (see [`Budget`]). Killing it sends Ctrl-C, then kills the
+    /// shell if that isn't enough; a command left in the background keeps
+    /// the shell busy until [`Self::finish`].
+    pub fn run_within(
+        &mut self,
+        cmd: &str,
+        dir: &Path,
+        display_lines: usize,
+        expected: Option<Duration>,
+    ) -> Result<PtyRun> {
+        let line = format!(
+            "{} {}\n",
+            STANDARD.encode(dir.to_string_lossy().as_bytes()),
+            STANDARD.encode(cmd)
+        );
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.flush()?;
+        self.busy = true;
+        self.collect(cmd, display_lines, expected.map(Budget::new))
+    }
+
+    /// Wait for a command left in the background to end, quietly, and
+    /// return its exit status.
+    pub fn finish(mut self) -> Option<i32> {
+        self.collect("", 0, None).ok().and_then(|run| run.code)
+    }
+
+    /// Read the running command's output up to its sentinel.
+    fn collect(&mut self, cmd: &str, display_lines: usize, mut budget: Option<Budget>) -> Result<PtyRun> {
+        let started = Instant::now();
+        // When to give up on a command sent Ctrl-C.
+        let mut kill_by: Option<Instant> = None;
+        let mut output = CapturedOutput::default();
+        let mut code = None;
+        let (mut out_done, mut err_done) = (false, false);
+        // The sentinel starts on a line of its own, so a blank line before
+        // it is held back until it's known not to be that separator.
+        let mut held = [false, false];
+        while !(out_done && err_done) {
+            let wait = kill_by
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+                .or_else(|| budget.map(|b| b.remaining(started.elapsed())));
+            let received = match wait {
+                Some(wait) => self.lines.recv_timeout(wait),
+                None => self.lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let line = match received {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    if kill_by.is_some() {
+                        let _ = self.child.kill();
+                        break;
+                    }
+                    let Some(budget) = budget.as_mut() else {
+                        continue;
+                    };
+                    match budget.ask(cmd, started.elapsed()) {
+                        Overrun::Wait => {}
+                        Overrun::Background => {
+                            return Ok(PtyRun {
+                                code: None,
+                                output,
+                                backgrounded: true,
+                            })
+                        }
+                        Overrun::Kill => {
+                            self.writer.write_all(b"\x03")?;
+                            self.writer.flush()?;
+                            kill_by = Some(Instant::now() + KILL_GRACE);
+                        }
+                    }
+                    continue;
+                }
+            };
+            let (text, stream) = match line {
+                Line::Out(text) => (text, 0),
+                Line::Err(text) => (text, 1),
+            };
+            if let Some(end) = text.strip_prefix(&self.sentinel) {
+                if stream == 0 {
+                    let (status, pwd) = end.trim_start().split_once(' ').unwrap_or((end.trim(), ""));
+                    code = status.parse().ok();
+                    if !pwd.is_empty() {
+                        self.dir = Some(PathBuf::from(pwd));
+                    }
+                    out_done = true;
+                } else {
+                    err_done = true;
+                }
+                held[stream] = false;
+                continue;
+            }
+            if std::mem::replace(&mut held[stream], text.is_empty()) {
+                output.record(String::new(), display_lines, stream == 1);
+            }
+            if !text.is_empty() {
+                output.record(text, display_lines, stream == 1);
+            }
+        }
+        if !out_done {
+            // Both streams closed: the shell is exiting, so reap it.
+            let _ = self.child.wait();
+            code = None;
+        }
+        self.busy = false;
+        Ok(PtyRun {
+            code,
+            output,
+            backgrounded: false,
+        })
+    }
+
+    /// A file of `export` lines recreating the variables exported in the
+    /// shell, for a command that has to run in `dir` in a shell of its own
+    /// to source. It is readable only by the user, so the values stay off
+    /// the command line and out of other users' reach.
+    pub fn exports_file(&mut self, dir: &Path) -> Result<PathBuf> {
+        use std::os::unix::fs::OpenOptionsExt;
+        let run = self.run("export -p", dir, 0)?;
+        self.dir = None;
+        let path = self.fifo_dir.join("exports");
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&path)?;
+        file.write_all(run.output.stdout().as_bytes())?;
+        Ok(path)
+    }
+}
+
+impl Drop for PtyShell {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        // Opening the FIFO read-write never blocks on Linux, and frees the
+        // reader thread if the shell died before opening its end.
+        let fifo = self.fifo_dir.join("stderr");
+        drop(std::fs::OpenOptions::new().read(true).write(true).open(&fifo));
+        let _ = std::fs::remove_dir_all(&self.fifo_dir);
+    }
+}
+
+/// Send each line of `stream` as `wrap(line)` until it ends or nobody is
+/// listening. Only the text after a carriage return is kept, as a terminal
+/// would show it.
+fn forward(stream: impl std::io::Read, sender: &Sender<Line>, wrap: fn(String) -> Line) {
+    for line in BufReader::new(stream).split(b'\n').map_while(|l| l.ok()) {
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches('\r');
+        let shown = line.rsplit('\r').next().unwrap_or(line);
+        if sender.send(wrap(shown.to_string())).is_err() {
+            return;
+        }
+    }
+}
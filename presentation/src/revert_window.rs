@@ -0,0 +1,196 @@
+//! The revert window around a firewall or network change: the current
+//! state is saved and a rollback scheduled before the change runs, and the
+//! user cancels the rollback only after checking they can still connect.
+//! If the change locks them out, the rollback runs on its own. A change
+//! made on an SSH host gets its window on that host.
+
+use crate::clipboard::on_path;
+use application::multi_host::remote_command;
+use application::network_guard::{
+    cancel_script, network_changes, parse_at_job, revert_plan, RevertPlan, Scheduler,
+};
+use colored::*;
+use infrastructure::config::Config;
+use shared::cache::now_secs;
+use shared::confirmation::ask_confirmation;
+use shared::types::Result;
+use shared::utils::shell_quote;
+use std::process::{Command, Output, Stdio};
+
+/// A rollback scheduled for a change about to run.
+pub struct RevertWindow {
+    plan: RevertPlan,
+    scheduler: Scheduler,
+    /// The `at` job number or the systemd unit.
+    job: String,
+    /// The SSH host the change runs on, if not here.
+    host: Option<String>,
+}
+
+/// What came of opening a window.
+pub enum Opened {
+    Window(RevertWindow),
+    /// Nothing to schedule; the change runs unguarded.
+    Unguarded,
+    /// The user chose not to run the change without a window.
+    Declined,
+}
+
+/// `script` run by bash as root, through `sudo` unless already root, here
+/// or on `host`.
+fn as_root(script: &str, host: Option<&str>) -> Command {
+    if let Some(host) = host {
+        let quoted = shell_quote(script);
+        let remote = format!(
+            "if [ \"$(id -u)\" = 0 ]; then bash -c {}; else sudo bash -c {}; fi",
+            quoted, quoted
+        );
+        let mut command = Command::new("bash");
+        command.args(["-c", &remote_command(host, &remote, false)]);
+        return command;
+    }
+    let root = Command::new("id")
+        .arg("-u")
+        .output()
+        .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).trim() == "0");
+    let mut command = match root {
+        true => Command::new("bash"),
+        false => {
+            let mut sudo = Command::new("sudo");
+            sudo.arg("bash");
+            sudo
+        }
+    };
+    command.args(["-c", script]);
+    command
+}
+
+fn run_as_root(script: &str, host: Option<&str>) -> Result<Output> {
+    Ok(as_root(script, host).stdin(Stdio::inherit()).output()?)
+}
+
+/// Whether `program` is installed here or on `host`.
+fn installed(program: &str, host: Option<&str>) -> bool {
+    match host {
+        Some(host) => Command::new("bash")
+            .args(["-c", &remote_command(host, &format!("command -v {}", program), false)])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success()),
+        None => on_path(program),
+    }
+}
+
+/// Ask whether to go on without a rollback after `problem`.
+fn unguarded(problem: &str) -> Result<Opened> {
+    println!("{} {}", "No revert window:".yellow().bold(), problem.yellow());
+    match ask_confirmation("Run the change without a rollback?", false)? {
+        true => Ok(Opened::Unguarded),
+        false => Ok(Opened::Declined),
+    }
+}
+
+/// Save the state `cmd` changes and schedule its rollback in
+/// `[network] revert_minutes`, on the sandbox's host if it has one.
+pub fn open(cmd: &str, config: &Config) -> Result<Opened> {
+    let host = config.sandbox.host.as_deref();
+    let minutes = config.network.revert_minutes;
+    if minutes == 0 {
+        return Ok(Opened::Unguarded);
+    }
+    let id = format!("vibe-cli-revert-{}", now_secs());
+    let Some(plan) = revert_plan(&network_changes(cmd), &format!("/var/tmp/{}", id)) else {
+        println!(
+            "{}",
+            "This change can't be rolled back automatically; make sure you have console access."
+                .yellow()
+        );
+        return Ok(Opened::Unguarded);
+    };
+    let scheduler = if installed("at", host) {
+        Scheduler::At
+    } else if installed("systemd-run", host) {
+        Scheduler::SystemdRun
+    } else {
+        return unguarded("neither `at` nor `systemd-run` is available to schedule a rollback.");
+    };
+
+    let prepared = run_as_root(&plan.prepare_script(), host)?;
+    if !prepared.status.success() {
+        let error = String::from_utf8_lossy(&prepared.stderr).trim().to_string();
+        return unguarded(&format!("saving the current state failed: {}", error));
+    }
+    let scheduled = run_as_root(&plan.schedule_script(scheduler, minutes, &id), host)?;
+    let job = match scheduler {
+        Scheduler::At => parse_at_job(&String::from_utf8_lossy(&scheduled.stdout)),
+        Scheduler::SystemdRun => scheduled.status.success().then(|| id.clone()),
+    };
+    let Some(job) = job else {
+        let error = String::from_utf8_lossy(&scheduled.stdout).trim().to_string()
+            + String::from_utf8_lossy(&scheduled.stderr).trim();
+        return unguarded(&format!("scheduling the rollback failed: {}", error));
+    };
+
+    println!(
+        "{}",
+        format!(
+            "Rollback of {} scheduled in {} minute(s) ({}); it runs unless you keep the change.",
+            plan.covers.join(", "),
+            minutes,
+            match scheduler {
+                Scheduler::At => format!("at job {}", job),
+                Scheduler::SystemdRun => format!("timer {}", job),
+            }
+        )
+        .cyan()
+    );
+    for risk in &plan.uncovered {
+        println!("  {} {}", "Not rolled back:".yellow(), risk);
+    }
+    Ok(Opened::Window(RevertWindow {
+        plan,
+        scheduler,
+        job,
+        host: host.map(str::to_string),
+    }))
+}
+
+impl RevertWindow {
+    /// After the change ran: keep it and cancel the rollback, or roll back
+    /// now. If the session is gone by then, the scheduled rollback runs.
+    pub fn close(self) -> Result<()> {
+        println!(
+            "\n{}",
+            "Check that you can still connect (e.g. open a new SSH session) before answering."
+                .cyan()
+                .bold()
+        );
+        let cancel = cancel_script(self.scheduler, &self.job);
+        if ask_confirmation("Keep the change and cancel the rollback?", false)? {
+            match run_as_root(&cancel, self.host.as_deref())?.status.success() {
+                true => println!("{}", "Rollback cancelled; the change stays.".green()),
+                false => println!(
+                    "{} {}",
+                    "Cancelling the rollback failed; it will still run. Cancel it with:".red(),
+                    cancel
+                ),
+            }
+            return Ok(());
+        }
+        println!("{}", "Rolling back...".cyan());
+        let host = self.host.as_deref();
+        let rolled_back = as_root(&format!("sh {}", self.plan.rollback_path()), host).status()?;
+        if rolled_back.success() {
+            run_as_root(&cancel, host)?;
+            println!("{}", "Rolled back.".green());
+        } else {
+            println!(
+                "{}",
+                "The rollback failed; the scheduled one will try again.".red()
+            );
+        }
+        Ok(())
+    }
+}
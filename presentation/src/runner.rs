@@ -0,0 +1,1137 @@
+use crate::clipboard;
+use crate::pane::{self, PaneTarget};
+use crate::pty_attach;
+use crate::pty_shell::PtyShell;
+use crate::revert_window::{self, Opened};
+use crate::safety::{
+    assess_command, confirm, confirm_or_refine, is_read_only, print_assessment, risk_tier, RiskTier,
+};
+use crate::step_budget::{self, Budget, Overrun};
+use anyhow::anyhow;
+use colored::*;
+use application::credential_prompt::{self, SecretSlot};
+use application::recall_service;
+use application::redaction;
+use application::disk_guard::{self, Shortfall};
+use application::env_review::{env_assignments, scrubbed_environment};
+use application::glob_preview::{self, GlobPreview, SHOWN_PATHS};
+use application::interactive;
+use application::multi_host;
+use application::package_preview;
+use application::service_impact;
+use domain::session::SessionRepository;
+use infrastructure::allowlist::{AllowEntry, AllowScope, Allowlist};
+use infrastructure::audit_log::Provenance;
+use infrastructure::config::{Config, SandboxSettings};
+use infrastructure::jobs::{job_script, Job, JobStore};
+use infrastructure::policy;
+use infrastructure::postprocess::PostProcessor;
+use infrastructure::session_store::JsonSessionRepository;
+use shared::cache::now_secs;
+use shared::confirmation::{ask_confirmation, Answer};
+use shared::types::Result;
+use shared::utils::{format_bytes, shell_quote};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Approvals of the same exact command after which allowlisting it is offered.
+const ALLOWLIST_OFFER_AFTER: usize = 2;
+
+/// Approvals of each exact command in this process.
+static APPROVALS: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+
+/// Lines echoed to the terminal before further output is only captured.
+pub const DISPLAY_LINES: usize = 200;
+
+/// Bytes of output kept for summarization; beyond this the middle is dropped
+/// and only the first and last halves are retained.
+const CAPTURE_BYTES: usize = 512 * 1024;
+
+/// Output of a command run through [`run_captured`].
+#[derive(Debug, Default)]
+pub struct CapturedOutput {
+    head: Vec<String>,
+    head_bytes: usize,
+    tail: VecDeque<String>,
+    tail_bytes: usize,
+    /// Standard output alone, up to [`CAPTURE_BYTES`], for later agent steps.
+    stdout: String,
+    pub total_lines: usize,
+    pub shown_lines: usize,
+}
+
+impl CapturedOutput {
+    /// Show `line` on the terminal while fewer than `display_lines` have
+    /// been, then keep it.
+    pub(crate) fn record(&mut self, line: String, display_lines: usize, is_stderr: bool) {
+        if self.shown_lines < display_lines {
+            self.shown_lines += 1;
+            let shown = redaction::for_display(&line);
+            if is_stderr {
+                eprintln!("{}", shown);
+            } else {
+                println!("{}", shown);
+            }
+        } else if display_lines > 0 && self.shown_lines == display_lines && self.total_lines == display_lines {
+            println!(
+                "{}",
+                format!("... more output is being captured but not shown (limit {} lines)", display_lines)
+                    .dimmed()
+            );
+            let _ = std::io::stdout().flush();
+        }
+        self.push(line, is_stderr);
+    }
+
+    pub(crate) fn push(&mut self, line: String, is_stderr: bool) {
+        self.total_lines += 1;
+        if !is_stderr && self.stdout.len() + line.len() < CAPTURE_BYTES {
+            self.stdout.push_str(&line);
+            self.stdout.push('\n');
+        }
+        if self.head_bytes < CAPTURE_BYTES / 2 {
+            self.head_bytes += line.len() + 1;
+            self.head.push(line);
+            return;
+        }
+        self.tail_bytes += line.len() + 1;
+        self.tail.push_back(line);
+        while self.tail_bytes > CAPTURE_BYTES / 2 {
+            match self.tail.pop_front() {
+                Some(dropped) => self.tail_bytes -= dropped.len() + 1,
+                None => break,
+            }
+        }
+    }
+
+    /// More lines were printed than shown on the terminal.
+    pub fn is_truncated(&self) -> bool {
+        self.total_lines > self.shown_lines
+    }
+
+    /// What the command wrote to standard output, without stderr.
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    /// Retained text, with a marker where lines were dropped to fit the cap.
+    pub fn text(&self) -> String {
+        let kept = self.head.len() + self.tail.len();
+        let mut text = self.head.join("\n");
+        if kept < self.total_lines {
+            text.push_str(&format!(
+                "\n[... {} lines omitted ...]",
+                self.total_lines - kept
+            ));
+        }
+        for line in &self.tail {
+            text.push('\n');
+            text.push_str(line);
+        }
+        text
+    }
+}
+
+/// Result of [`confirm_and_run`]; `output` is set when the command ran with
+/// its output captured.
+pub struct CommandRun {
+    pub command: String,
+    pub outcome: RunOutcome,
+    pub output: Option<CapturedOutput>,
+    /// How long the command ran; `None` when it was not run.
+    pub elapsed: Option<Duration>,
+    /// How risky the safety check judged it; `None` if it wasn't checked.
+    pub tier: Option<RiskTier>,
+    pub exit_code: Option<i32>,
+    /// The user's correction when the outcome is [`RunOutcome::Refine`].
+    pub refinement: Option<String>,
+    /// Where the command came from, for the audit log.
+    pub provenance: Provenance,
+}
+
+impl CommandRun {
+    fn skipped(command: &str, outcome: RunOutcome, tier: Option<RiskTier>) -> Self {
+        Self {
+            command: command.to_string(),
+            outcome,
+            output: None,
+            elapsed: None,
+            tier,
+            exit_code: None,
+            refinement: None,
+            provenance: Provenance::default(),
+        }
+    }
+}
+
+/// How a proposed command ended up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    Succeeded,
+    Failed,
+    Cancelled,
+    Blocked,
+    Invalid,
+    /// Typed into another pane for the user to run (`--send-to-pane`).
+    Sent,
+    /// Not run; the user asked for a corrected command instead.
+    Refine,
+    /// Ran past its expected duration and was left running while the plan
+    /// goes on; see [`step_budget`].
+    Backgrounded,
+    /// Started as a detached job (`--detach`).
+    Detached,
+}
+
+/// Validate basic shell command syntax
+pub fn validate_command_syntax(cmd: &str) -> Result<()> {
+    let cmd = cmd.trim();
+
+    // Check for incomplete expressions (common patterns); an escaped `\;`
+    // (as in `find -exec ... \;`) is a literal argument, not a separator.
+    if cmd.ends_with("&&")
+        || cmd.ends_with("||")
+        || cmd.ends_with('|')
+        || (cmd.ends_with(';') && !cmd.ends_with("\\;"))
+    {
+        return Err(anyhow!("Command ends with incomplete expression"));
+    }
+
+    let mut parens = 0;
+    let mut brackets = 0;
+    let mut braces = 0;
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let mut escape_next = false;
+
+    for ch in cmd.chars() {
+        if escape_next {
+            escape_next = false;
+            continue;
+        }
+        let quoted = in_single_quote || in_double_quote;
+
+        match ch {
+            '\\' => {
+                escape_next = true;
+            }
+            '\'' if !in_double_quote => {
+                in_single_quote = !in_single_quote;
+            }
+            '"' if !in_single_quote => {
+                in_double_quote = !in_double_quote;
+            }
+            '(' if !quoted => parens += 1,
+            ')' if !quoted => {
+                parens -= 1;
+                if parens < 0 {
+                    return Err(anyhow!("Unmatched closing parenthesis"));
+                }
+            }
+            '[' if !quoted => brackets += 1,
+            ']' if !quoted => {
+                brackets -= 1;
+                if brackets < 0 {
+                    return Err(anyhow!("Unmatched closing bracket"));
+                }
+            }
+            '{' if !quoted => braces += 1,
+            '}' if !quoted => {
+                braces -= 1;
+                if braces < 0 {
+                    return Err(anyhow!("Unmatched closing brace"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Check for unclosed quotes
+    if in_single_quote {
+        return Err(anyhow!("Unclosed single quote"));
+    }
+    if in_double_quote {
+        return Err(anyhow!("Unclosed double quote"));
+    }
+
+    // Check for unmatched parentheses/brackets/braces
+    if parens != 0 {
+        return Err(anyhow!("Unmatched parentheses"));
+    }
+    if brackets != 0 {
+        return Err(anyhow!("Unmatched brackets"));
+    }
+    if braces != 0 {
+        return Err(anyhow!("Unmatched braces"));
+    }
+
+    // Check for incomplete awk expressions
+    if cmd.contains("awk") && (cmd.ends_with("$") || cmd.contains("$") && !cmd.contains("{print") && !cmd.contains("{print ")) {
+        return Err(anyhow!("Potentially incomplete awk expression"));
+    }
+
+    Ok(())
+}
+
+/// [`postprocess`] without telling the user, for commands nobody watches.
+fn postprocess_quietly(cmd: &str, config: &Config) -> Result<String> {
+    Ok(PostProcessor::new(&config.postprocess_rules)?.apply(cmd).0)
+}
+
+/// Apply the configured post-processing rules, telling the user what changed.
+pub fn postprocess(cmd: &str, config: &Config) -> Result<String> {
+    let processor = PostProcessor::new(&config.postprocess_rules)?;
+    let (rewritten, applied) = processor.apply(cmd);
+    if !applied.is_empty() {
+        println!(
+            "{} {}",
+            "Rewritten by rules:".cyan(),
+            applied.join(", ").cyan()
+        );
+    }
+    Ok(rewritten)
+}
+
+fn active_session() -> Option<String> {
+    JsonSessionRepository::default().active().ok().flatten()
+}
+
+/// The allowlist entry that lets `cmd` run unconfirmed. Commands with
+/// warnings always ask, and an unreadable allowlist allows nothing.
+pub(crate) fn allowlisted(cmd: &str, tier: RiskTier) -> Option<AllowEntry> {
+    if !matches!(tier, RiskTier::Safe | RiskTier::Mutating) {
+        return None;
+    }
+    Allowlist::for_project()
+        .and_then(|list| list.allowing(cmd, active_session().as_deref()))
+        .ok()
+        .flatten()
+}
+
+/// Once the same command has been approved repeatedly, offer to stop
+/// asking for it, for the active session if there is one or the project.
+fn offer_allowlist(cmd: &str, tier: RiskTier) -> Result<()> {
+    if !matches!(tier, RiskTier::Safe | RiskTier::Mutating) || !std::io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let approvals = {
+        let mut counts = APPROVALS.lock().unwrap_or_else(|e| e.into_inner());
+        let count = counts.get_or_insert_with(HashMap::new).entry(cmd.to_string()).or_insert(0);
+        *count += 1;
+        *count
+    };
+    if approvals != ALLOWLIST_OFFER_AFTER
+        || !ask_confirmation("Always allow this exact command without asking?", false)?
+    {
+        return Ok(());
+    }
+    let scope = match active_session() {
+        Some(id)
+            if ask_confirmation(
+                &format!("Only while session '{}' is active? (no: for this project)", id),
+                true,
+            )? =>
+        {
+            AllowScope::Session(id)
+        }
+        _ => AllowScope::Project,
+    };
+    let entry = Allowlist::for_project()?.add(cmd, scope)?;
+    println!(
+        "{}",
+        format!(
+            "Allowlisted as {}; `vibe_cli allowlist revoke {}` undoes it.",
+            entry.id(),
+            entry.id()
+        )
+        .green()
+    );
+    Ok(())
+}
+
+/// Show the variables `cmd` sets with their resolved values, and have the
+/// user agree to pass on any that look like secrets. Variables in `slots`
+/// are asked for later and left out.
+fn review_environment(cmd: &str, slots: &[SecretSlot]) -> Result<bool> {
+    let mut assignments = env_assignments(cmd, |name| std::env::var(name).ok());
+    assignments.retain(|a| !slots.iter().any(|slot| slot.var == a.name));
+    if assignments.is_empty() {
+        return Ok(true);
+    }
+    println!("{}", "Environment set by this command:".cyan());
+    for assignment in &assignments {
+        let line = format!("  {}={}", assignment.name, assignment.display_value());
+        if assignment.secret {
+            println!("{} {}", line.yellow(), "(looks like a secret)".yellow());
+        } else {
+            println!("{}", line);
+        }
+    }
+    let secrets: Vec<&str> = assignments
+        .iter()
+        .filter(|a| a.secret)
+        .map(|a| a.name.as_str())
+        .collect();
+    if secrets.is_empty() {
+        return Ok(true);
+    }
+    ask_confirmation(&format!("Pass {} to this command?", secrets.join(", ")), false)
+}
+
+/// Warn that a command needs more room or inodes than its file system has free.
+fn print_shortfalls(shortfalls: &[Shortfall]) {
+    for shortfall in shortfalls {
+        let (need, free) = (&shortfall.need, &shortfall.free);
+        if shortfall.out_of_space() {
+            println!(
+                "{} {} needs about {} on {}, but only {} is free.",
+                "Disk space:".yellow().bold(),
+                need.what,
+                format_bytes(need.bytes),
+                free.mount,
+                format_bytes(free.bytes)
+            );
+        }
+        if shortfall.out_of_inodes() {
+            println!(
+                "{} {} creates about {} files on {}, but only {} inodes are free.",
+                "Disk space:".yellow().bold(),
+                need.what,
+                need.files,
+                free.mount,
+                free.inodes.unwrap_or(0)
+            );
+        }
+    }
+}
+
+/// The first paths each glob of a destructive command matches, and how
+/// many paths it affects in all.
+fn print_glob_previews(previews: &[GlobPreview]) {
+    for preview in previews {
+        let target = &preview.target;
+        if preview.matches.is_empty() {
+            println!(
+                "{} `{}` matches nothing here.",
+                "Affected paths:".cyan(),
+                target.pattern
+            );
+            continue;
+        }
+        let contents = match preview.total > preview.matches.len() {
+            true => ", including directory contents",
+            false => "",
+        };
+        println!(
+            "{} {} {} affects {}{} path(s){}:",
+            "Affected paths:".cyan(),
+            target.program,
+            target.pattern,
+            preview.total,
+            if preview.capped { "+" } else { "" },
+            contents
+        );
+        for path in preview.matches.iter().take(SHOWN_PATHS) {
+            println!("  {}", path);
+        }
+        if preview.matches.len() > SHOWN_PATHS {
+            println!("  ... and {} more matches", preview.matches.len() - SHOWN_PATHS);
+        }
+    }
+}
+
+/// `bash -c cmd` in the sandbox's working directory, with a scrubbed
+/// environment in paranoid mode, plus the variables in `secrets`.
+fn shell(cmd: &str, sandbox: &SandboxSettings, secrets: &[(String, String)]) -> Command {
+    let mut command = Command::new("bash");
+    command.arg("-c").arg(cmd);
+    sandboxed(&mut command, sandbox);
+    command.envs(secrets.iter().map(|(var, value)| (var, value)));
+    command
+}
+
+/// Run `command` in the sandbox's directory and environment.
+fn sandboxed(command: &mut Command, sandbox: &SandboxSettings) {
+    if let Some(dir) = &sandbox.cwd {
+        command.current_dir(dir);
+    }
+    if sandbox.scrub_env {
+        command
+            .env_clear()
+            .envs(scrubbed_environment(std::env::vars(), &sandbox.keep));
+    }
+}
+
+/// Ask for each secret with hidden input (a plain line when stdin isn't a
+/// terminal); `None` if one is left empty.
+fn read_secrets(slots: &[SecretSlot]) -> Result<Option<Vec<(String, String)>>> {
+    let mut secrets = Vec::new();
+    for slot in slots {
+        let prompt = format!("{} (hidden)", slot.label);
+        let value = if std::io::stdin().is_terminal() {
+            dialoguer::Password::new()
+                .with_prompt(prompt)
+                .allow_empty_password(true)
+                .interact()?
+        } else {
+            eprint!("{}: ", prompt);
+            std::io::stderr().flush()?;
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        };
+        if value.is_empty() {
+            return Ok(None);
+        }
+        secrets.push((slot.var.clone(), value));
+    }
+    Ok(Some(secrets))
+}
+
+/// The run of a command that was only `cd`s: nothing is run, the session
+/// just moves to `dir`.
+pub fn change_dir(cmd: &str, dir: &Path) -> CommandRun {
+    println!("{} {}", "Working directory:".green().bold(), dir.display());
+    CommandRun {
+        exit_code: Some(0),
+        ..CommandRun::skipped(cmd, RunOutcome::Succeeded, Some(RiskTier::Safe))
+    }
+}
+
+pub fn confirm_and_run(cmd: &str, config: &Config) -> Result<CommandRun> {
+    run_confirmed(cmd, config, false, None)
+}
+
+/// Like [`confirm_and_run`], but run in `shell`, an agent plan's
+/// persistent shell.
+pub fn confirm_and_run_in(cmd: &str, config: &Config, shell: &mut PtyShell) -> Result<CommandRun> {
+    run_confirmed(cmd, config, false, Some(shell))
+}
+
+/// Like [`confirm_and_run`], but the user may answer "refine" and type a
+/// correction instead; the run then ends with [`RunOutcome::Refine`] and the
+/// correction for the caller to regenerate the command with.
+pub fn confirm_and_run_or_refine(cmd: &str, config: &Config) -> Result<CommandRun> {
+    run_confirmed(cmd, config, true, None)
+}
+
+/// Ask how to correct `cmd`; `None` when the user enters nothing. A reply
+/// starting with `$ ` is a command of the user's own, see [`own_command`].
+fn read_refinement() -> Result<Option<String>> {
+    eprint!("What should change? (`$ <command>` to run your own; Enter to cancel): ");
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    let line = line.trim();
+    Ok((!line.is_empty()).then(|| line.to_string()))
+}
+
+/// Confirm `cmd` with `question` as its tier requires: `None` if accepted,
+/// else the run to return.
+fn accept(
+    cmd: &str,
+    tier: RiskTier,
+    config: &Config,
+    question: &str,
+    refine: bool,
+) -> Result<Option<CommandRun>> {
+    let answer = if refine {
+        confirm_or_refine(tier, &config.confirm, question)?
+    } else if confirm(tier, &config.confirm, question)? {
+        Answer::Yes
+    } else {
+        Answer::No
+    };
+    let cancelled = CommandRun::skipped(cmd, RunOutcome::Cancelled, Some(tier));
+    Ok(match answer {
+        Answer::Yes => None,
+        Answer::Refine => Some(match read_refinement()? {
+            Some(correction) => CommandRun {
+                outcome: RunOutcome::Refine,
+                refinement: Some(correction),
+                ..cancelled
+            },
+            None => cancelled,
+        }),
+        Answer::No => Some(cancelled),
+    })
+}
+
+/// The command in a correction of the form `$ <command>`: the user's own
+/// command to run in place of the suggestion.
+pub fn own_command(correction: &str) -> Option<&str> {
+    correction
+        .trim_start()
+        .strip_prefix("$ ")
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+}
+
+/// The source line shown under a suggested command, e.g.
+/// `Source: cache (saved 94 days ago)`; `now` is in seconds since the Unix
+/// epoch.
+pub fn describe_source(provenance: &Provenance, now: u64) -> String {
+    let detail = match provenance {
+        Provenance::Model => "generated just now".to_string(),
+        Provenance::Cache { saved_at } => format!("saved {}", recall_service::age(*saved_at, now)),
+        Provenance::Rule(name) => name.clone(),
+        Provenance::UserEdited => "as you typed it".to_string(),
+    };
+    format!("Source: {} ({})", provenance.label(), detail)
+}
+
+/// The tier `cmd` is confirmed at. A command for another host is rated by
+/// its own words, not by the `ssh` it is wrapped in to run.
+pub fn rate(cmd: &str, config: &Config) -> RiskTier {
+    risk_tier(cmd, &assess_command(cmd, config.safe_mode))
+}
+
+/// `cmd` as it is run: over `ssh` on the sandbox's host, if it has one.
+pub fn on_host(cmd: &str, sandbox: &SandboxSettings) -> String {
+    match &sandbox.host {
+        Some(host) => multi_host::remote_command(host, cmd, false),
+        None => cmd.to_string(),
+    }
+}
+
+fn run_confirmed(cmd: &str, config: &Config, refine: bool, session: Option<&mut PtyShell>) -> Result<CommandRun> {
+    let run = confirm_then_run(cmd, config, refine, session)?;
+    Ok(CommandRun {
+        command: on_host(&run.command, &config.sandbox),
+        provenance: config.provenance.clone(),
+        ..run
+    })
+}
+
+fn confirm_then_run(cmd: &str, config: &Config, refine: bool, session: Option<&mut PtyShell>) -> Result<CommandRun> {
+    // House conventions are applied first so the safety checks and the user
+    // see the command that will actually run.
+    let cmd = postprocess(cmd, config)?;
+    // Credential placeholders become variable references, filled in when
+    // the command runs; a pane's shell wouldn't have the variables, so
+    // there the placeholders are left for the user to replace.
+    let rewrite = match &config.send_to_pane {
+        Some(_) => credential_prompt::SecretRewrite {
+            command: cmd,
+            slots: Vec::new(),
+        },
+        None => credential_prompt::secret_slots(&cmd),
+    };
+    let cmd = rewrite.command.as_str();
+    // What runs: the checks below rate `cmd` itself, not its `ssh` wrapper.
+    let target = on_host(cmd, &config.sandbox);
+
+    println!("{} {}", "Suggested command:".green().bold(), cmd.yellow());
+    println!("{}", describe_source(&config.provenance, now_secs()).dimmed());
+    if let Some(host) = &config.sandbox.host {
+        println!("{}", format!("Runs on {} over ssh", host).dimmed());
+    }
+    if let Some(dir) = &config.sandbox.cwd {
+        println!("{}", format!("Runs in {}", dir.display()).dimmed());
+    }
+    if !rewrite.slots.is_empty() {
+        let labels: Vec<&str> = rewrite.slots.iter().map(|s| s.label.as_str()).collect();
+        println!(
+            "{}",
+            format!(
+                "Asks for {} when it runs; passed in the environment, never stored.",
+                labels.join(", ")
+            )
+            .dimmed()
+        );
+    }
+
+    // Validate command syntax before proceeding
+    if let Err(validation_error) = validate_command_syntax(cmd) {
+        println!(
+            "{} {}",
+            "Command validation failed:".red().bold(),
+            validation_error.to_string().red()
+        );
+        println!("{}", "This command appears to have syntax errors and will not be executed.".red());
+        return Ok(CommandRun::skipped(cmd, RunOutcome::Invalid, None));
+    }
+
+    if config.copy_to_clipboard {
+        match clipboard::copy(cmd) {
+            Ok(backend) => println!(
+                "{}",
+                format!("Copied to clipboard ({}).", backend.name()).green()
+            ),
+            Err(err) => eprintln!("{} {}", "Clipboard copy failed:".red(), err),
+        }
+    }
+
+    let mut assessment = assess_command(cmd, config.safe_mode);
+    if let Some(reason) = policy::system().blocked_reason(cmd) {
+        assessment.blocked = true;
+        assessment.reasons.push(reason);
+    }
+
+    if assessment.blocked {
+        print_assessment(&assessment);
+        println!(
+            "\n{}",
+            "Command has been blocked. It will not be executed.".red()
+        );
+        return Ok(CommandRun::skipped(cmd, RunOutcome::Blocked, Some(RiskTier::Blocked)));
+    }
+
+    print_assessment(&assessment);
+    // The previews look at this machine's files.
+    if config.sandbox.host.is_none() {
+        let dir = config.sandbox.cwd.as_deref().unwrap_or(Path::new("."));
+        print_glob_previews(&glob_preview::preview(cmd, dir));
+        print_shortfalls(&disk_guard::shortfalls(&disk_guard::space_needs(cmd, dir)));
+    }
+
+    let tier = risk_tier(cmd, &assessment);
+
+    if !review_environment(cmd, &rewrite.slots)? {
+        println!("{}", "Command execution cancelled.".yellow());
+        return Ok(CommandRun::skipped(cmd, RunOutcome::Cancelled, Some(tier)));
+    }
+
+    if let Some(pane) = &config.send_to_pane {
+        let pane = PaneTarget::parse(pane)?;
+        let question = format!("Send this command to {}?", pane.describe());
+        if let Some(run) = accept(cmd, tier, config, &question, refine)? {
+            if run.outcome == RunOutcome::Cancelled {
+                println!("{}", "Command not sent.".yellow());
+            }
+            return Ok(run);
+        }
+        // The pane's shell has a directory of its own.
+        match &config.sandbox.cwd {
+            Some(dir) => pane::send(&pane, &format!("cd {} && {}", shell_quote(&dir.to_string_lossy()), target))?,
+            None => pane::send(&pane, &target)?,
+        }
+        println!(
+            "{}",
+            format!("Typed into {}; press Enter there to run it.", pane.describe()).green()
+        );
+        return Ok(CommandRun::skipped(cmd, RunOutcome::Sent, Some(tier)));
+    }
+
+    if let Some(entry) = allowlisted(&target, tier) {
+        println!(
+            "{}",
+            format!(
+                "Allowlisted (entry {}); running without confirmation.",
+                entry.id()
+            )
+            .dimmed()
+        );
+    } else {
+        if let Some(run) = accept(cmd, tier, config, "Run this command?", refine)? {
+            if run.outcome == RunOutcome::Cancelled {
+                println!("{}", "Command execution cancelled.".yellow());
+            }
+            return Ok(run);
+        }
+        offer_allowlist(&target, tier)?;
+    }
+
+    let Some(secrets) = read_secrets(&rewrite.slots)? else {
+        println!("{}", "No secret given; command execution cancelled.".yellow());
+        return Ok(CommandRun::skipped(cmd, RunOutcome::Cancelled, Some(tier)));
+    };
+
+    if config.detach {
+        match tier {
+            RiskTier::Network => println!(
+                "{}",
+                "Network changes run here, inside their revert window, not as a job.".yellow()
+            ),
+            _ => return start_job(&target, config, &secrets, tier),
+        }
+    }
+
+    // A firewall or network change gets a scheduled rollback first, in
+    // case it cuts off the session.
+    let window = match tier {
+        RiskTier::Network => match revert_window::open(cmd, config)? {
+            Opened::Window(window) => Some(window),
+            Opened::Unguarded => None,
+            Opened::Declined => {
+                println!("{}", "Command execution cancelled.".yellow());
+                return Ok(CommandRun::skipped(cmd, RunOutcome::Cancelled, Some(tier)));
+            }
+        },
+        _ => None,
+    };
+
+    if config.sandbox.scrub_env {
+        println!("{}", "Running with a scrubbed environment.".dimmed());
+    }
+    println!("{}", "Running command...\n".cyan());
+
+    let started = Instant::now();
+    let dir = config.sandbox.cwd.clone().map_or_else(std::env::current_dir, Ok)?;
+    let expected = config.sandbox.expected;
+    let (code, output, backgrounded) = match session {
+        Some(session) if secrets.is_empty() && !needs_terminal(cmd) && config.sandbox.host.is_none() => {
+            let run = session.run_within(cmd, &dir, DISPLAY_LINES, expected)?;
+            (run.code, Some(run.output), run.backgrounded)
+        }
+        session => {
+            let attached = interactive::interaction(cmd);
+            // A command that needs the terminal or a secret gets a shell of
+            // its own, which sources the session's exported variables.
+            let cmd = match session {
+                Some(session) => {
+                    let exports = session.exports_file(&dir)?;
+                    format!(". {}\n{}", shell_quote(&exports.to_string_lossy()), target)
+                }
+                None => target.clone(),
+            };
+            let terminal = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+            if let Some(reason) = attached.as_ref().filter(|_| terminal) {
+                println!("{}", format!("Attached to the terminal: {}.", reason).dimmed());
+                let (code, output) = pty_attach::run_attached(&cmd, &config.sandbox, &secrets)?;
+                (code, Some(output), false)
+            } else if attached.is_some() {
+                (shell(&cmd, &config.sandbox, &secrets).status()?.code(), None, false)
+            } else {
+                let (status, output) = run_captured_with(&cmd, DISPLAY_LINES, &config.sandbox, &secrets)?;
+                (status.and_then(|s| s.code()), Some(output), status.is_none())
+            }
+        }
+    };
+
+    let outcome = if backgrounded {
+        println!("{}", "Left running in the background; it is waited for when the plan ends.".cyan());
+        RunOutcome::Backgrounded
+    } else if code == Some(0) {
+        println!("{}", "Command completed successfully.".green());
+        RunOutcome::Succeeded
+    } else {
+        println!("{} (exit status: {:?})", "Command failed.".red(), code);
+        RunOutcome::Failed
+    };
+    let elapsed = started.elapsed();
+    if let Some(window) = window {
+        window.close()?;
+    }
+    Ok(CommandRun {
+        command: cmd.to_string(),
+        outcome,
+        output,
+        elapsed: Some(elapsed),
+        tier: Some(tier),
+        exit_code: code,
+        refinement: None,
+        provenance: config.provenance.clone(),
+    })
+}
+
+/// Start `cmd` as a job detached from the terminal, its output going to
+/// the job's log, so it outlives vibe_cli; see `vibe_cli jobs`.
+fn start_job(cmd: &str, config: &Config, secrets: &[(String, String)], tier: RiskTier) -> Result<CommandRun> {
+    let store = JobStore::default();
+    let id = store.next_id()?;
+    std::fs::create_dir_all(store.dir())?;
+    let log = std::fs::File::create(store.log_path(id))?;
+    let script = job_script(cmd, &store.exit_path(id));
+    // setsid leaves the terminal's session, so closing it doesn't hang the
+    // job up; where there's no setsid, nohup ignores the hangup instead.
+    let detach = if clipboard::on_path("setsid") { "setsid" } else { "nohup" };
+    let child = shell(&format!("exec {} bash -c {}", detach, shell_quote(&script)), &config.sandbox, secrets)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .spawn()?;
+    store.save(&Job {
+        id,
+        command: cmd.to_string(),
+        dir: config.sandbox.cwd.clone().map_or_else(std::env::current_dir, Ok)?,
+        pid: child.id(),
+        started: now_secs(),
+        summary: None,
+    })?;
+    println!(
+        "{} {}",
+        format!("Started job {}.", id).green(),
+        format!("Follow it with `vibe_cli jobs tail {} --follow`.", id).dimmed()
+    );
+    Ok(CommandRun::skipped(cmd, RunOutcome::Detached, Some(tier)))
+}
+
+/// Evaluate a plan step's `when` condition: `Some(true)` if it exits 0. A
+/// probe on the read-only allowlist ([`is_read_only`]) runs quietly without
+/// asking; anything else is confirmed and run like a step, and is `None` if
+/// it did not run.
+pub fn check_condition(condition: &str, config: &Config) -> Result<Option<bool>> {
+    check_condition_in(condition, config, None)
+}
+
+/// [`check_condition`] in an agent plan's persistent shell, if it has one.
+pub fn check_condition_in(condition: &str, config: &Config, session: Option<&mut PtyShell>) -> Result<Option<bool>> {
+    let allowed = policy::system().blocked_reason(condition).is_none();
+    if allowed && is_read_only(condition) && rate(condition, config) == RiskTier::Safe {
+        if let Some(session) = session.filter(|_| config.sandbox.host.is_none()) {
+            let dir = config.sandbox.cwd.clone().map_or_else(std::env::current_dir, Ok)?;
+            return Ok(Some(session.run(condition, &dir, 0)?.code == Some(0)));
+        }
+        let status = shell(&on_host(condition, &config.sandbox), &config.sandbox, &[])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        return Ok(Some(status.success()));
+    }
+    println!("{}", "This step's condition is not a known read-only check; confirm it first.".yellow());
+    let run = run_confirmed(condition, config, false, session)?;
+    Ok(match run.outcome {
+        RunOutcome::Succeeded => Some(true),
+        RunOutcome::Failed => Some(false),
+        _ => None,
+    })
+}
+
+/// Run `cmd` without asking, as `wrap` turns it into (e.g. over `ssh`), its
+/// output captured but not shown, if the safety check allows that:
+/// anything blocked is [`RunOutcome::Blocked`], and anything rated above
+/// mutating, invalid or needing a secret is left unrun as
+/// [`RunOutcome::Cancelled`].
+pub fn run_unattended(cmd: &str, config: &Config, wrap: impl Fn(&str) -> String) -> Result<CommandRun> {
+    let checked = postprocess_quietly(cmd, config)?;
+    let cmd = wrap(&checked);
+    let cmd = cmd.as_str();
+    let mut assessment = assess_command(&checked, config.safe_mode);
+    if let Some(reason) = policy::system().blocked_reason(&checked) {
+        assessment.blocked = true;
+        assessment.reasons.push(reason);
+    }
+    let tier = risk_tier(&checked, &assessment);
+    if tier == RiskTier::Blocked {
+        return Ok(CommandRun::skipped(cmd, RunOutcome::Blocked, Some(tier)));
+    }
+    if tier > RiskTier::Mutating
+        || validate_command_syntax(&checked).is_err()
+        || !credential_prompt::secret_slots(&checked).slots.is_empty()
+    {
+        return Ok(CommandRun::skipped(cmd, RunOutcome::Cancelled, Some(tier)));
+    }
+    let started = Instant::now();
+    let (status, output) = run_captured(cmd, 0, &config.sandbox)?;
+    Ok(CommandRun {
+        command: cmd.to_string(),
+        outcome: if status.success() {
+            RunOutcome::Succeeded
+        } else {
+            RunOutcome::Failed
+        },
+        output: Some(output),
+        elapsed: Some(started.elapsed()),
+        tier: Some(tier),
+        exit_code: status.code(),
+        refinement: None,
+        provenance: config.provenance.clone(),
+    })
+}
+
+/// For a plan step that installs or removes packages, run the package
+/// manager's dry run first and print the transaction it resolves, so the
+/// step is confirmed knowing its new dependencies, removals and sizes.
+/// The dry run is run directly, never through a shell.
+pub fn preview_packages(cmd: &str, config: &Config) {
+    let Some(dry_run) = package_preview::dry_run_command(cmd) else {
+        return;
+    };
+    let manager = dry_run.manager;
+    if policy::system().blocked_reason(cmd).is_some() || assess_command(cmd, config.safe_mode).blocked {
+        return;
+    }
+    println!("{} {}", "Package transaction (dry run):".cyan(), dry_run.to_string().dimmed());
+    let mut command = Command::new(&dry_run.program);
+    command.args(&dry_run.args);
+    sandboxed(&mut command, &config.sandbox);
+    let output = match command.stdin(Stdio::null()).output() {
+        Ok(output) => output,
+        Err(e) => {
+            println!("  {} {}", "The dry run couldn't start:".yellow(), e);
+            return;
+        }
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let transaction = package_preview::parse_transaction(
+        manager,
+        &stdout,
+        dry_run.removes(),
+    );
+    if !transaction.is_empty() {
+        for line in transaction.summary() {
+            println!("  {}", line);
+        }
+        print_shortfalls(&disk_guard::shortfalls(&disk_guard::package_needs(manager, &transaction)));
+        return;
+    }
+    // `dnf --assumeno` exits 1 after printing the transaction, so only an
+    // empty result is reported as a failure.
+    if output.status.success() {
+        println!("  {}", "Nothing to install, upgrade or remove.".dimmed());
+        return;
+    }
+    println!(
+        "  {} (exit status: {:?})",
+        "The dry run failed; the transaction is unknown.".yellow(),
+        output.status.code()
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().filter(|l| !l.trim().is_empty()).collect();
+    for line in &lines[lines.len().saturating_sub(5)..] {
+        println!("  {}", line.dimmed());
+    }
+}
+
+/// For a plan step that restarts or stops services, show what each one is
+/// doing now (ports, processes, connections), probed read-only, so the
+/// step is confirmed knowing what it interrupts.
+pub fn preview_service_impact(cmd: &str) {
+    for action in service_impact::interrupted_services(cmd) {
+        let Some(impact) = service_impact::probe(&action.unit) else {
+            continue;
+        };
+        let label = format!("Impact of {}:", action.verb);
+        println!("{} {}", label.cyan(), impact.summary());
+    }
+}
+
+/// Needs the user's terminal: an interactive command, or `sudo`, which
+/// asks for a password on it.
+fn needs_terminal(cmd: &str) -> bool {
+    interactive::interaction(cmd).is_some() || cmd.split_whitespace().any(|word| word == "sudo")
+}
+
+/// Run `cmd`, echoing stdout and stderr live until `display_lines` lines have
+/// been shown, then keep capturing quietly so a flood of output (e.g.
+/// `journalctl`) doesn't bury the terminal.
+pub fn run_captured(
+    cmd: &str,
+    display_lines: usize,
+    sandbox: &SandboxSettings,
+) -> Result<(std::process::ExitStatus, CapturedOutput)> {
+    let (status, output) = run_captured_with(cmd, display_lines, sandbox, &[])?;
+    let status = status.ok_or_else(|| anyhow!("{} was left running in the background", cmd))?;
+    Ok((status, output))
+}
+
+/// [`run_captured`] with `secrets` added to the environment. With an
+/// expected duration in `sandbox`, the command gets no input and the user
+/// is asked what to do once it runs well past it; the status is `None` if
+/// it was left running in the background.
+fn run_captured_with(
+    cmd: &str,
+    display_lines: usize,
+    sandbox: &SandboxSettings,
+    secrets: &[(String, String)],
+) -> Result<(Option<std::process::ExitStatus>, CapturedOutput)> {
+    let stdin = match sandbox.expected {
+        Some(_) => Stdio::null(),
+        None => Stdio::inherit(),
+    };
+    let mut child = shell(cmd, sandbox, secrets)
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let captured = Arc::new(Mutex::new(CapturedOutput::default()));
+    let stdout = child.stdout.take().map(|pipe| {
+        let captured = Arc::clone(&captured);
+        std::thread::spawn(move || pump(pipe, &captured, display_lines, false))
+    });
+    let stderr = child.stderr.take().map(|pipe| {
+        let captured = Arc::clone(&captured);
+        std::thread::spawn(move || pump(pipe, &captured, display_lines, true))
+    });
+    let readers: Vec<_> = [stdout, stderr].into_iter().flatten().collect();
+    if let Some(expected) = sandbox.expected {
+        let started = Instant::now();
+        let mut budget = Budget::new(expected);
+        while child.try_wait()?.is_none() {
+            let remaining = budget.remaining(started.elapsed());
+            if !remaining.is_zero() {
+                std::thread::sleep(remaining.min(Duration::from_millis(100)));
+                continue;
+            }
+            match budget.ask(cmd, started.elapsed()) {
+                Overrun::Wait => {}
+                Overrun::Background => {
+                    step_budget::background(cmd, started, move || {
+                        for reader in readers {
+                            let _ = reader.join();
+                        }
+                        child.wait().ok().and_then(|status| status.code())
+                    });
+                    return Ok((None, CapturedOutput::default()));
+                }
+                Overrun::Kill => {
+                    let _ = child.kill();
+                    break;
+                }
+            }
+        }
+    }
+    for reader in readers {
+        let _ = reader.join();
+    }
+    let status = child.wait()?;
+
+    let output = std::mem::take(&mut *captured.lock().unwrap_or_else(|e| e.into_inner()));
+    Ok((Some(status), output))
+}
+
+fn pump(
+    pipe: impl std::io::Read,
+    captured: &Mutex<CapturedOutput>,
+    display_lines: usize,
+    is_stderr: bool,
+) {
+    for line in BufReader::new(pipe).lines().map_while(|l| l.ok()) {
+        captured
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(line, display_lines, is_stderr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_command_syntax;
+
+    #[test]
+    fn test_valid_commands() {
+        assert!(validate_command_syntax("ls -la").is_ok());
+        assert!(validate_command_syntax("echo 'hello world'").is_ok());
+        assert!(validate_command_syntax("du -h --max-depth=1 | sort -hr").is_ok());
+        assert!(validate_command_syntax("find . -name '*.rs' -exec grep 'fn' {} \\;").is_ok());
+    }
+
+    #[test]
+    fn test_invalid_commands() {
+        assert!(validate_command_syntax("echo 'hello").is_err()); // unclosed quote
+        assert!(validate_command_syntax("du -h | awk '$1 >").is_err()); // incomplete awk
+        assert!(validate_command_syntax("ls &&").is_err()); // incomplete expression
+        assert!(validate_command_syntax("echo (hello").is_err()); // unmatched paren
+        assert!(validate_command_syntax("ls [ -f file").is_err()); // unmatched bracket
+    }
+
+    #[test]
+    fn long_output_is_captured_beyond_the_display_limit() {
+        let (status, output) = super::run_captured("seq 1 500", 10, &Default::default()).unwrap();
+
+        assert!(status.success());
+        assert_eq!(output.total_lines, 500);
+        assert_eq!(output.shown_lines, 10);
+        assert!(output.is_truncated());
+        assert!(output.text().ends_with("499\n500"));
+        assert!(super::needs_terminal("sudo journalctl -f | less"));
+        assert!(!super::needs_terminal("journalctl -u nginx"));
+    }
+}
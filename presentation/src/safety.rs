@@ -0,0 +1,131 @@
+pub use application::read_only::{is_read_only, not_read_only};
+pub use application::safety_service::{
+    assess_command, mutation_reason, read_only_violation, risk_tier, RiskTier, SafetyAssessment,
+};
+use colored::*;
+use infrastructure::config::{ConfirmRule, ConfirmSettings};
+use shared::confirmation::{ask_confirmation, ask_or_refine, Answer};
+use shared::types::Result;
+
+pub fn print_assessment(assessment: &SafetyAssessment) {
+    if !assessment.reasons.is_empty() {
+        println!("\n{}", "Blocked for safety:".red().bold());
+        for r in &assessment.reasons {
+            println!("  - {}", r.red());
+        }
+    }
+
+    if !assessment.warnings.is_empty() {
+        println!("\n{}", "Warnings:".yellow().bold());
+        for w in &assessment.warnings {
+            println!("  - {}", w.yellow());
+        }
+    }
+
+    if !assessment.ssh.is_empty() {
+        println!("\n{}", "Changes SSH access:".magenta().bold());
+        for s in &assessment.ssh {
+            println!("  - {}", s.magenta());
+        }
+    }
+
+    if !assessment.network.is_empty() {
+        println!("\n{}", "Changes the firewall or network:".magenta().bold());
+        for n in &assessment.network {
+            println!("  - {}", n.magenta());
+        }
+    }
+}
+
+/// The configured rule for `tier`; blocked commands are never confirmed.
+pub fn confirm_rule(tier: RiskTier, settings: &ConfirmSettings) -> Option<&ConfirmRule> {
+    match tier {
+        RiskTier::Safe => Some(&settings.safe),
+        RiskTier::Mutating => Some(&settings.mutating),
+        RiskTier::Dangerous => Some(&settings.dangerous),
+        RiskTier::Ssh => Some(&settings.ssh),
+        RiskTier::Network => Some(&settings.network),
+        RiskTier::Blocked => None,
+    }
+}
+
+/// Ask `question` the way `tier` requires: the typed phrase first if one is
+/// configured, then yes/no with the tier's default, then a second yes/no
+/// for double confirmation.
+pub fn confirm(tier: RiskTier, settings: &ConfirmSettings, question: &str) -> Result<bool> {
+    Ok(confirm_with(tier, settings, question, false)? == Answer::Yes)
+}
+
+/// Like [`confirm`], but the user may also ask to refine the command, at
+/// the typed phrase or the yes/no question.
+pub fn confirm_or_refine(
+    tier: RiskTier,
+    settings: &ConfirmSettings,
+    question: &str,
+) -> Result<Answer> {
+    confirm_with(tier, settings, question, true)
+}
+
+fn confirm_with(
+    tier: RiskTier,
+    settings: &ConfirmSettings,
+    question: &str,
+    refine: bool,
+) -> Result<Answer> {
+    let Some(rule) = confirm_rule(tier, settings) else {
+        return Ok(Answer::No);
+    };
+    if let Some(phrase) = &rule.phrase {
+        match tier {
+            RiskTier::Dangerous => println!("\n{}", "This command has warnings.".yellow().bold()),
+            RiskTier::Ssh => println!(
+                "\n{}",
+                "This command changes SSH access; make sure you understand why.".magenta().bold()
+            ),
+            RiskTier::Network => println!(
+                "\n{}",
+                "This command can cut off your connection; make sure you have another way in."
+                    .magenta()
+                    .bold()
+            ),
+            _ => {}
+        }
+        match require_phrase(phrase, refine)? {
+            Answer::Yes => {}
+            answer => return Ok(answer),
+        }
+    }
+    let answer = if refine {
+        ask_or_refine(question, rule.default_yes)?
+    } else if ask_confirmation(question, rule.default_yes)? {
+        Answer::Yes
+    } else {
+        Answer::No
+    };
+    if answer != Answer::Yes {
+        return Ok(answer);
+    }
+    if rule.double && !ask_confirmation("Are you sure?", false)? {
+        return Ok(Answer::No);
+    }
+    Ok(Answer::Yes)
+}
+
+fn require_phrase(phrase: &str, refine: bool) -> Result<Answer> {
+    let prompt = if refine {
+        format!("Type '{}' to continue, 'r' to refine, anything else to cancel:", phrase)
+    } else {
+        format!("Type '{}' to continue, anything else to cancel:", phrase)
+    };
+    println!("{}", prompt.yellow());
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    if input.trim() == phrase.trim() {
+        Ok(Answer::Yes)
+    } else if refine && matches!(input.trim(), "r" | "refine") {
+        Ok(Answer::Refine)
+    } else {
+        println!("{}", "Cancelled.".red());
+        Ok(Answer::No)
+    }
+}
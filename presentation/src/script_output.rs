@@ -0,0 +1,233 @@
+//! Writing generated scripts and edited files to disk without silently
+//! clobbering existing ones: a side-by-side diff preview, appending, and
+//! timestamped backups.
+
+use colored::Colorize;
+use shared::accessibility::glyph;
+use shared::types::Result;
+use similar::{DiffTag, TextDiff};
+use std::path::{Path, PathBuf};
+
+/// Unchanged lines shown around each change in the diff preview.
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Replace the file, after confirming a diff if it already exists.
+    Overwrite,
+    /// Add the script to the end of the existing file.
+    Append,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptWritten {
+    Created,
+    Overwritten { backup: Option<PathBuf> },
+    Appended { backup: Option<PathBuf> },
+    /// The file already held exactly this script.
+    Unchanged,
+    /// The user declined to overwrite the existing file.
+    Kept,
+}
+
+/// Write `script` to `path`. An existing file is only replaced once
+/// `confirm` approves the diff preview it is given; with `backup` the old
+/// contents are first copied to [`backup_path`].
+pub fn write_script(
+    path: &Path,
+    script: &str,
+    mode: WriteMode,
+    backup: bool,
+    width: usize,
+    confirm: impl FnOnce(&str) -> Result<bool>,
+) -> Result<ScriptWritten> {
+    write_with_preview(path, script, mode, backup, width, true, confirm)
+}
+
+/// Replace the contents of the file at `path` with `contents`, once
+/// `confirm` approves the diff preview; like [`write_script`] but without
+/// making the file executable.
+pub fn edit_file(
+    path: &Path,
+    contents: &str,
+    backup: bool,
+    width: usize,
+    confirm: impl FnOnce(&str) -> Result<bool>,
+) -> Result<ScriptWritten> {
+    write_with_preview(path, contents, WriteMode::Overwrite, backup, width, false, confirm)
+}
+
+fn write_with_preview(
+    path: &Path,
+    script: &str,
+    mode: WriteMode,
+    backup: bool,
+    width: usize,
+    executable: bool,
+    confirm: impl FnOnce(&str) -> Result<bool>,
+) -> Result<ScriptWritten> {
+    let write = |path: &Path, contents: &str| match executable {
+        true => write_executable(path, contents),
+        false => Ok(std::fs::write(path, contents)?),
+    };
+    let existing = match std::fs::read_to_string(path) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            write(path, script)?;
+            return Ok(ScriptWritten::Created);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let contents = match mode {
+        WriteMode::Append => appended_script(&existing, script),
+        WriteMode::Overwrite => {
+            if existing == script {
+                return Ok(ScriptWritten::Unchanged);
+            }
+            if !confirm(&side_by_side_diff(&existing, script, width))? {
+                return Ok(ScriptWritten::Kept);
+            }
+            script.to_string()
+        }
+    };
+
+    let backup = if backup {
+        let backup = backup_path(path, shared::cache::now_secs());
+        std::fs::copy(path, &backup)?;
+        Some(backup)
+    } else {
+        None
+    };
+    write(path, &contents)?;
+    Ok(match mode {
+        WriteMode::Append => ScriptWritten::Appended { backup },
+        WriteMode::Overwrite => ScriptWritten::Overwritten { backup },
+    })
+}
+
+fn write_executable(path: &Path, contents: &str) -> Result<()> {
+    std::fs::write(path, contents)?;
+    // Make it executable (best-effort)
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            let mut perms = metadata.permissions();
+            perms.set_mode(perms.mode() | 0o755);
+            let _ = std::fs::set_permissions(path, perms);
+        }
+    }
+    Ok(())
+}
+
+/// `sdiff`-style preview of replacing `old` with `new`: the existing script on
+/// the left, the generated one on the right, only changed regions shown.
+pub fn side_by_side_diff(old: &str, new: &str, width: usize) -> String {
+    let column = (width.saturating_sub(3) / 2).max(20);
+    let diff = TextDiff::from_lines(old, new);
+    let (old_lines, new_lines) = (diff.old_slices(), diff.new_slices());
+    let mut out = String::new();
+    for (i, group) in diff.grouped_ops(CONTEXT_LINES).iter().enumerate() {
+        if i > 0 {
+            out.push_str(&format!("{}\n", glyph("···", "...").dimmed()));
+        }
+        for op in group {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            let rows = old_range.len().max(new_range.len());
+            for row in 0..rows {
+                let left = old_range.clone().nth(row).map(|i| old_lines[i]);
+                let right = new_range.clone().nth(row).map(|i| new_lines[i]);
+                out.push_str(&render_row(tag, left, right, column));
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+fn render_row(tag: DiffTag, left: Option<&str>, right: Option<&str>, column: usize) -> String {
+    let left = fit(left.unwrap_or(""), column);
+    let right = fit(right.unwrap_or(""), column);
+    match tag {
+        DiffTag::Equal => format!(
+            "{} {} {}",
+            left.dimmed(),
+            glyph("│", "|").dimmed(),
+            right.trim_end().dimmed()
+        ),
+        DiffTag::Delete => format!("{} {}", left.red(), "<".red().bold()),
+        DiffTag::Insert => format!(
+            "{} {} {}",
+            " ".repeat(column),
+            ">".green().bold(),
+            right.trim_end().green()
+        ),
+        DiffTag::Replace => format!(
+            "{} {} {}",
+            left.red(),
+            "|".yellow().bold(),
+            right.trim_end().green()
+        ),
+    }
+}
+
+/// `line` without its newline, tabs expanded, cut or padded to `width` chars.
+fn fit(line: &str, width: usize) -> String {
+    let line = line.trim_end_matches(['\n', '\r']).replace('\t', "    ");
+    let mut fitted: String = line.chars().take(width).collect();
+    let len = fitted.chars().count();
+    if line.chars().count() > width && width > 0 {
+        fitted.pop();
+        fitted.push_str(glyph("…", "~"));
+    }
+    fitted.push_str(&" ".repeat(width - len));
+    fitted
+}
+
+/// `existing` followed by `addition`, minus the addition's shebang line
+/// since the file already starts with its own.
+pub fn appended_script(existing: &str, addition: &str) -> String {
+    let addition = match addition.strip_prefix("#!") {
+        Some(rest) => rest.split_once('\n').map(|(_, body)| body).unwrap_or(""),
+        None => addition,
+    };
+    let mut script = existing.trim_end().to_string();
+    script.push_str("\n\n");
+    script.push_str(addition.trim_start_matches('\n'));
+    if !script.ends_with('\n') {
+        script.push('\n');
+    }
+    script
+}
+
+/// `<path>.<UTC timestamp>.bak`, e.g. `deploy.sh.20261017T093000Z.bak`.
+pub fn backup_path(path: &Path, unix_secs: u64) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}.bak", utc_timestamp(unix_secs)));
+    PathBuf::from(name)
+}
+
+fn utc_timestamp(unix_secs: u64) -> String {
+    let days = (unix_secs / 86_400) as i64;
+    let secs = unix_secs % 86_400;
+    // Civil date from days since the epoch (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,156 @@
+//! Argument parsing for generated scripts (`--script --with-args`): values the
+//! user left as placeholders (`<dir>`, `{dest}`) become getopts options
+//! instead of being hard-coded by the model.
+
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptParam {
+    /// As written in the request, e.g. `backup-dir`.
+    pub name: String,
+    /// Shell variable the option sets, e.g. `BACKUP_DIR`.
+    pub var: String,
+    pub flag: char,
+}
+
+/// Distinct `<name>`, `{name}` and `{{name}}` placeholders in `request`, in
+/// order of appearance.
+pub fn placeholders(request: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut seen = HashSet::new();
+    let mut rest = request;
+    while let Some(start) = rest.find(['<', '{']) {
+        let close = if rest[start..].starts_with('<') { '>' } else { '}' };
+        let inner = &rest[start + 1..];
+        let name = inner
+            .find(close)
+            .map(|end| inner[..end].trim_matches(['{', '}']).trim())
+            .filter(|name| is_placeholder_name(name));
+        match name {
+            Some(name) => {
+                if seen.insert(name.to_lowercase()) {
+                    names.push(name.to_string());
+                }
+                rest = &inner[inner.find(close).unwrap_or(0) + 1..];
+            }
+            // A comparison like `x < 5`, not a placeholder
+            None => rest = inner,
+        }
+    }
+    names
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// One option per valid name, flagged by the first free letter of the name
+/// (lowercase, then uppercase); `-h` stays reserved for help.
+pub fn params(names: &[String]) -> Vec<ScriptParam> {
+    let mut used: HashSet<char> = HashSet::from(['h']);
+    names
+        .iter()
+        .map(|name| name.trim())
+        .filter(|name| is_placeholder_name(name))
+        .filter_map(|name| {
+            let letters: Vec<char> = name.chars().filter(char::is_ascii_alphabetic).collect();
+            let flag = letters
+                .iter()
+                .map(char::to_ascii_lowercase)
+                .chain(letters.iter().map(char::to_ascii_uppercase))
+                .chain('a'..='z')
+                .find(|c| !used.contains(c))?;
+            used.insert(flag);
+            Some(ScriptParam {
+                name: name.to_ascii_lowercase(),
+                var: name.to_uppercase().replace('-', "_"),
+                flag,
+            })
+        })
+        .collect()
+}
+
+/// `request` with each placeholder replaced by the variable that holds it,
+/// so the model writes `"$DEST"` rather than a made-up path.
+pub fn substitute(request: &str, params: &[ScriptParam]) -> String {
+    let mut request = request.to_string();
+    for param in params {
+        for form in ["{{%}}", "<%>", "{%}"] {
+            let placeholder = form.replace('%', &param.name);
+            request = replace_ignore_case(&request, &placeholder, &format!("${}", param.var));
+        }
+    }
+    request
+}
+
+fn replace_ignore_case(text: &str, needle: &str, replacement: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (i, _) in lower.match_indices(needle) {
+        out.push_str(&text[last..i]);
+        out.push_str(replacement);
+        last = i + needle.len();
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// Shebang, optional strict mode, `usage` and a getopts loop that sets each
+/// parameter's variable and requires all of them.
+pub fn argument_header(params: &[ScriptParam], strict: bool) -> String {
+    let mut header = String::from("#!/usr/bin/env bash\n");
+    if strict {
+        header.push_str("set -euo pipefail\n");
+    }
+    header.push('\n');
+
+    let synopsis: String = params
+        .iter()
+        .map(|p| format!(" -{} <{}>", p.flag, p.name))
+        .collect();
+    header.push_str(&format!(
+        "usage() {{\n    echo \"Usage: $(basename \"$0\"){}\" >&2\n    exit 2\n}}\n\n",
+        synopsis
+    ));
+    if params.is_empty() {
+        header.push_str("[ \"${1:-}\" = \"-h\" ] && usage\n\n");
+        return header;
+    }
+
+    for p in params {
+        header.push_str(&format!("{}=\"\"\n", p.var));
+    }
+    let spec: String = params.iter().map(|p| format!("{}:", p.flag)).collect();
+    header.push_str(&format!(
+        "while getopts \":{}h\" opt; do\n    case \"$opt\" in\n",
+        spec
+    ));
+    for p in params {
+        header.push_str(&format!("        {}) {}=\"$OPTARG\" ;;\n", p.flag, p.var));
+    }
+    header.push_str("        *) usage ;;\n    esac\ndone\nshift $((OPTIND - 1))\n\n");
+    for p in params {
+        header.push_str(&format!("[ -n \"${}\" ] || usage\n", p.var));
+    }
+    header.push('\n');
+    header
+}
+
+/// The model's script body with any shebang or strict-mode line it added
+/// anyway removed, since the header provides both.
+pub fn strip_preamble(body: &str) -> &str {
+    let mut body = body.trim_start();
+    while let Some(first) = body.lines().next() {
+        let first = first.trim();
+        if first.starts_with("#!") || first.starts_with("set -e") {
+            body = body[body.find('\n').map_or(body.len(), |i| i + 1)..].trim_start();
+        } else {
+            break;
+        }
+    }
+    body
+}
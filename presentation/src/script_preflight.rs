@@ -0,0 +1,370 @@
+//! Preflight checks embedded in generated scripts, so a script copied to
+//! another machine stops with a clear message instead of failing halfway:
+//! the OS it was generated for, the commands it calls, and the major version
+//! of each non-standard tool as found on the generating machine.
+
+use application::env_review::program_words;
+use std::collections::HashSet;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How long a tool gets to answer `--version` before it is left unversioned.
+const VERSION_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Shell keywords and builtins: never binaries to check for.
+const SHELL_WORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+    "in", "function", "select", "time", "!", "[[", "]]", "{", "}", "echo", "printf", "read",
+    "cd", "test", "[", "true", "false", "exit", "return", "set", "unset", "export", "local",
+    "shift", "getopts", "eval", "source", ".", "trap", "wait", "break", "continue", "declare",
+    "readonly", "type", "command", "alias", "pwd", "let", "exec", "umask", "ulimit", "hash",
+    "builtin", "mapfile", "pushd", "popd", "shopt", ":",
+];
+
+/// Keywords followed directly by a command (`if grep -q ...`). `time` is
+/// skipped with the other wrappers, see [`program_words`].
+const COMMAND_PREFIX_KEYWORDS: &[&str] = &["if", "then", "else", "elif", "while", "until", "do", "!"];
+
+/// Standard utilities present on any POSIX system, whose versions are not
+/// worth pinning.
+const CORE_UTILITIES: &[&str] = &[
+    "cat", "cp", "mv", "rm", "mkdir", "rmdir", "ls", "ln", "chmod", "chown", "touch", "head",
+    "tail", "sort", "uniq", "wc", "cut", "tr", "tee", "grep", "egrep", "sed", "awk", "find",
+    "xargs", "date", "sleep", "basename", "dirname", "env", "id", "uname", "du", "df", "ps",
+    "kill", "expr", "mktemp", "stat", "diff", "cmp", "which", "sh", "bash", "sudo", "nohup",
+    "nice", "timeout", "whoami", "hostname", "realpath", "readlink", "seq", "yes", "true",
+    "false", "tar", "gzip", "gunzip", "printf", "test",
+];
+
+/// Tools that only exist on some Linux distribution families; a script
+/// using one is pinned to the generating machine's family.
+const DISTRO_TOOLS: &[&str] = &[
+    "apt", "apt-get", "apt-cache", "dpkg", "dnf", "yum", "rpm", "pacman", "zypper", "apk",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub binary: String,
+    /// Major version found on the generating machine, if the tool reports one.
+    pub min_major: Option<u64>,
+}
+
+/// `uname -s` of the target, plus the distribution family (`debian`,
+/// `fedora`, ...) when the script depends on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OsTarget {
+    pub kernel: String,
+    pub family: Option<String>,
+}
+
+/// The target OS as recorded by the system info detection.
+pub fn os_target(system_info: &str) -> Option<OsTarget> {
+    let field = |key: &str| {
+        system_info
+            .split(", ")
+            .find_map(|part| part.strip_prefix(key))
+            .map(|value| value.trim().to_string())
+    };
+    if let Some(distro) = field("Distro: ") {
+        return Some(OsTarget {
+            kernel: "Linux".to_string(),
+            family: Some(distro_family(&distro)),
+        });
+    }
+    field("OS: ").map(|kernel| OsTarget {
+        kernel,
+        family: None,
+    })
+}
+
+/// The `/etc/os-release` ID that the distribution and its relatives all
+/// list in `ID` or `ID_LIKE`.
+fn distro_family(distro: &str) -> String {
+    let family = match distro {
+        "ubuntu" | "debian" | "linuxmint" | "pop" | "elementary" | "kali" | "raspbian" => "debian",
+        "fedora" | "rhel" | "centos" | "rocky" | "almalinux" | "ol" | "amzn" => "fedora",
+        "arch" | "manjaro" | "endeavouros" => "arch",
+        d if d.starts_with("opensuse") || d == "sles" => "suse",
+        other => other,
+    };
+    family.to_string()
+}
+
+/// Commands `script` runs, in order of first use: the first word of every
+/// simple command, skipping keywords, builtins, functions the script defines,
+/// variable assignments, paths, case patterns and heredoc bodies.
+pub fn required_binaries(script: &str) -> Vec<String> {
+    let functions = defined_functions(script);
+    let mut seen = HashSet::new();
+    let mut binaries = Vec::new();
+    let mut heredoc_end: Option<String> = None;
+    let mut case_depth = 0usize;
+    for line in script.lines() {
+        if let Some(end) = &heredoc_end {
+            if line.trim() == end {
+                heredoc_end = None;
+            }
+            continue;
+        }
+        let code = sanitize(line);
+        heredoc_end = heredoc_delimiter(&code);
+        let mut code = code.trim_start();
+        if case_depth > 0 {
+            // `pattern)` at the start of a case arm
+            if let Some(close) = code.find(')').filter(|&i| !code[..i].contains('(')) {
+                code = &code[close + 1..];
+            }
+        }
+        let words: Vec<&str> = code.split_whitespace().collect();
+        if words.first() == Some(&"case") && words.contains(&"in") {
+            case_depth += 1;
+        }
+        if words.contains(&"esac") {
+            case_depth = case_depth.saturating_sub(1);
+        }
+
+        for segment in code.split(['|', '&', ';', '(', ')', '`', '{', '}']) {
+            let words: Vec<&str> = segment
+                .split_whitespace()
+                .skip_while(|w| COMMAND_PREFIX_KEYWORDS.contains(w))
+                .collect();
+            let Some(&word) = program_words(&words).first() else { continue };
+            let is_command_name = word
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '+' | '-'));
+            if is_command_name
+                && !word.starts_with(['-', '.'])
+                && !word.chars().all(|c| c.is_ascii_digit())
+                && !SHELL_WORDS.contains(&word)
+                && !functions.contains(word)
+                && seen.insert(word.to_string())
+            {
+                binaries.push(word.to_string());
+            }
+        }
+    }
+    binaries
+}
+
+/// `line` without its comment, with quoted text, `${...}` expansions and
+/// `$((...))` arithmetic blanked out; command substitutions are kept, even
+/// inside double quotes, since they run commands too.
+fn sanitize(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::with_capacity(line.len());
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut substitution_depth = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if in_single {
+            in_single = c != '\'';
+            out.push(' ');
+        } else if c == '$'
+            && (next == Some('{') || (next == Some('(') && chars.get(i + 2) == Some(&'(')))
+        {
+            // Blank through the matching close
+            let (open, close) = if next == Some('{') { ('{', '}') } else { ('(', ')') };
+            let mut depth = 0;
+            while i < chars.len() {
+                if chars[i] == open {
+                    depth += 1;
+                } else if chars[i] == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                out.push(' ');
+                i += 1;
+            }
+            out.push(' ');
+        } else if c == '$' && next == Some('(') {
+            substitution_depth += 1;
+            out.push_str("$(");
+            i += 1;
+        } else if c == ')' && substitution_depth > 0 {
+            substitution_depth -= 1;
+            out.push(')');
+        } else if in_double && substitution_depth == 0 {
+            in_double = c != '"';
+            out.push(' ');
+        } else if c == '\'' {
+            in_single = true;
+            out.push(' ');
+        } else if c == '"' {
+            in_double = true;
+            out.push(' ');
+        } else if c == '#' && out.chars().last().is_none_or(char::is_whitespace) {
+            break;
+        } else {
+            out.push(c);
+        }
+        i += 1;
+    }
+    out
+}
+
+fn defined_functions(script: &str) -> HashSet<&str> {
+    script
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim().trim_start_matches("function ").trim_start();
+            let name = line.split(['(', ' ', '{']).next()?;
+            let rest = line[name.len()..].trim_start();
+            (!name.is_empty() && (rest.starts_with("()") || rest.starts_with('{'))).then_some(name)
+        })
+        .collect()
+}
+
+fn heredoc_delimiter(code: &str) -> Option<String> {
+    let rest = &code[code.find("<<")? + 2..];
+    let word = rest.trim_start_matches('-').split_whitespace().next()?;
+    let word = word.trim_matches(['\'', '"']);
+    (!word.is_empty() && !word.starts_with('<')).then(|| word.to_string())
+}
+
+/// Each binary with the major version this machine has of it, for tools
+/// outside the standard utilities that answer `--version`.
+pub fn probe_versions(binaries: &[String]) -> Vec<Requirement> {
+    binaries
+        .iter()
+        .map(|binary| Requirement {
+            binary: binary.clone(),
+            min_major: if CORE_UTILITIES.contains(&binary.as_str()) {
+                None
+            } else {
+                local_version(binary).and_then(|v| major(&v))
+            },
+        })
+        .collect()
+}
+
+fn local_version(binary: &str) -> Option<String> {
+    let mut child = Command::new(binary)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let started = Instant::now();
+    loop {
+        match child.try_wait().ok()? {
+            Some(_) => break,
+            None if started.elapsed() > VERSION_PROBE_TIMEOUT => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            None => std::thread::sleep(Duration::from_millis(20)),
+        }
+    }
+    let output = child.wait_with_output().ok()?;
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    find_version(&text)
+}
+
+/// First dotted number (`3.2.7`) in `text`.
+pub fn find_version(text: &str) -> Option<String> {
+    text.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map(|s| s.trim_matches('.'))
+        .find(|s| s.contains('.') && s.split('.').all(|p| !p.is_empty()))
+        .map(str::to_string)
+}
+
+fn major(version: &str) -> Option<u64> {
+    version.split('.').next()?.parse().ok()
+}
+
+/// The shell section that enforces `os` and `requirements`.
+pub fn render(os: Option<&OsTarget>, requirements: &[Requirement]) -> String {
+    let mut out = String::from(
+        "# --- preflight: environment this script was generated for ---\n\
+         preflight_fail() {\n    echo \"preflight: $*\" >&2\n    exit 1\n}\n",
+    );
+    if let Some(os) = os {
+        out.push_str(&format!(
+            "[ \"$(uname -s)\" = \"{0}\" ] || preflight_fail \"this script targets {0}, not $(uname -s)\"\n",
+            os.kernel
+        ));
+        if let Some(family) = &os.family {
+            out.push_str(&format!(
+                "os_ids=\" $( . /etc/os-release 2>/dev/null; echo \"${{ID:-}} ${{ID_LIKE:-}}\") \"\n\
+                 case \"$os_ids\" in\n    *\" {0} \"*) ;;\n    *) preflight_fail \"this script targets {0}-family Linux (found:${{os_ids% }})\" ;;\nesac\n",
+                family
+            ));
+        }
+    }
+    if !requirements.is_empty() {
+        let names: Vec<&str> = requirements.iter().map(|r| r.binary.as_str()).collect();
+        out.push_str(&format!(
+            "missing=\"\"\nfor bin in {}; do\n    command -v \"$bin\" >/dev/null 2>&1 || missing=\"$missing $bin\"\ndone\n\
+             [ -z \"$missing\" ] || preflight_fail \"missing required commands:$missing\"\n",
+            names.join(" ")
+        ));
+    }
+    let versioned: Vec<&Requirement> = requirements
+        .iter()
+        .filter(|r| r.min_major.is_some())
+        .collect();
+    if !versioned.is_empty() {
+        out.push_str(
+            "require_major() {\n    local have\n    have=$(\"$1\" --version 2>&1 | grep -Eo '[0-9]+(\\.[0-9]+)+' | head -n 1 || true)\n    \
+             if [ -n \"$have\" ] && [ \"${have%%.*}\" -lt \"$2\" ]; then\n        \
+             preflight_fail \"$1 $2.x or newer is required (found $have)\"\n    fi\n}\n",
+        );
+        for r in versioned {
+            out.push_str(&format!(
+                "require_major {} {}\n",
+                r.binary,
+                r.min_major.unwrap_or_default()
+            ));
+        }
+    }
+    out.push_str("# --- end preflight ---\n");
+    out
+}
+
+/// `script` with a preflight section for the machine described by
+/// `system_info`, placed after the shebang and `set` options.
+pub fn with_preflight(script: &str, system_info: &str) -> String {
+    let binaries = required_binaries(script);
+    let mut os = os_target(system_info);
+    if let Some(os) = &mut os {
+        if !binaries.iter().any(|b| DISTRO_TOOLS.contains(&b.as_str())) {
+            os.family = None;
+        }
+    }
+    let section = render(os.as_ref(), &probe_versions(&binaries));
+
+    let lines: Vec<&str> = script.lines().collect();
+    let preamble = lines
+        .iter()
+        .take_while(|l| {
+            let l = l.trim();
+            l.starts_with("#!") || l.starts_with("set -") || l.is_empty()
+        })
+        .count();
+    let mut out = String::new();
+    for line in &lines[..preamble] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    if preamble > 0 && !out.ends_with("\n\n") {
+        out.push('\n');
+    }
+    out.push_str(&section);
+    out.push('\n');
+    for line in &lines[preamble..] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,152 @@
+//! A running agent step checked against how long the plan expected it to
+//! take. Once it runs well past that, the user can keep waiting, leave it
+//! running in the background while the plan goes on, or kill it, so an
+//! agent run doesn't hang silently for hours.
+
+use crate::notify::format_elapsed;
+use colored::*;
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The least time past the expected duration before the user is asked.
+const MIN_GRACE: Duration = Duration::from_secs(10);
+
+/// What to do about a step running past its budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overrun {
+    Wait,
+    /// Stop waiting: the step keeps running while the plan goes on, and is
+    /// waited for when the plan ends.
+    Background,
+    Kill,
+}
+
+/// When to ask about a step expected to take `expected`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    expected: Duration,
+    /// Running time at which the user is next asked.
+    ask_at: Duration,
+}
+
+impl Budget {
+    /// The user is first asked once the step has run twice as long as
+    /// expected, and at least [`MIN_GRACE`] longer.
+    pub fn new(expected: Duration) -> Self {
+        Self {
+            expected,
+            ask_at: (expected * 2).max(expected + MIN_GRACE),
+        }
+    }
+
+    /// Time left at `elapsed` before the user is asked; zero once due.
+    pub fn remaining(&self, elapsed: Duration) -> Duration {
+        self.ask_at.saturating_sub(elapsed)
+    }
+
+    /// After waiting on at `elapsed`, ask again once the step has run as
+    /// long again.
+    pub fn extend(&mut self, elapsed: Duration) {
+        self.ask_at = (elapsed * 2).max(elapsed + MIN_GRACE);
+    }
+
+    /// Ask what to do about `cmd`, running for `elapsed`. Without a terminal
+    /// to ask on, the step is waited for.
+    pub fn ask(&mut self, cmd: &str, elapsed: Duration) -> Overrun {
+        println!(
+            "\n{} {}",
+            format!(
+                "Still running after {} (expected about {}):",
+                format_elapsed(elapsed),
+                format_elapsed(self.expected)
+            )
+            .yellow()
+            .bold(),
+            cmd
+        );
+        let answer = match std::io::stdin().is_terminal() {
+            true => read_answer(),
+            false => None,
+        };
+        let overrun = answer.unwrap_or(Overrun::Wait);
+        if overrun == Overrun::Wait {
+            self.extend(elapsed);
+        }
+        overrun
+    }
+}
+
+fn read_answer() -> Option<Overrun> {
+    loop {
+        print!("Keep waiting, run it in the background, or kill it? [W/b/k] ");
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        if let Some(overrun) = parse_answer(&line) {
+            return Some(overrun);
+        }
+    }
+}
+
+/// `w`/`wait` (or nothing), `b`/`background` or `k`/`kill`.
+pub fn parse_answer(line: &str) -> Option<Overrun> {
+    match line.trim().to_lowercase().as_str() {
+        "" | "w" | "wait" => Some(Overrun::Wait),
+        "b" | "bg" | "background" => Some(Overrun::Background),
+        "k" | "kill" => Some(Overrun::Kill),
+        _ => None,
+    }
+}
+
+/// A step left running in the background.
+struct Backgrounded {
+    command: String,
+    started: Instant,
+    /// Waits for the step and returns its exit status.
+    finish: Box<dyn FnOnce() -> Option<i32> + Send>,
+}
+
+static BACKGROUND: Mutex<Vec<Backgrounded>> = Mutex::new(Vec::new());
+
+/// Keep `command`, started at `started`, for [`wait_for_background`];
+/// `finish` waits for it to end.
+pub fn background(command: &str, started: Instant, finish: impl FnOnce() -> Option<i32> + Send + 'static) {
+    BACKGROUND
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Backgrounded {
+            command: command.to_string(),
+            started,
+            finish: Box::new(finish),
+        });
+}
+
+/// Wait for every step left running in the background, reporting how each
+/// ended.
+pub fn wait_for_background() {
+    let steps = std::mem::take(&mut *BACKGROUND.lock().unwrap_or_else(|e| e.into_inner()));
+    if steps.is_empty() {
+        return;
+    }
+    println!(
+        "\n{}",
+        format!("Waiting for {} step(s) left running in the background...", steps.len()).cyan()
+    );
+    for step in steps {
+        let code = (step.finish)();
+        let elapsed = format_elapsed(step.started.elapsed());
+        match code {
+            Some(0) => println!("{} {} ({})", "Background step finished:".green(), step.command, elapsed),
+            code => println!(
+                "{} {} (exit status: {:?}, {})",
+                "Background step failed:".red(),
+                step.command,
+                code,
+                elapsed
+            ),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Plain output for screen readers and dumb terminals (`--accessible`):
+//! no color, ASCII-only symbols, progress on separate lines, and prompts
+//! answered with a typed line instead of a single raw-mode keypress.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ACCESSIBLE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_accessible(accessible: bool) {
+    ACCESSIBLE.store(accessible, Ordering::Relaxed);
+    if accessible {
+        disable_color();
+    }
+}
+
+/// Set by `--accessible`, `VIBE_ACCESSIBLE=1` or `accessible = true` in
+/// `config.toml`.
+pub fn is_accessible() -> bool {
+    ACCESSIBLE.load(Ordering::Relaxed)
+}
+
+/// `--no-color`; `NO_COLOR` is honoured by `colored` itself.
+pub fn disable_color() {
+    colored::control::set_override(false);
+}
+
+/// `unicode`, or `ascii` in accessible mode.
+pub fn glyph<'a>(unicode: &'a str, ascii: &'a str) -> &'a str {
+    if is_accessible() {
+        ascii
+    } else {
+        unicode
+    }
+}
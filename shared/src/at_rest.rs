@@ -0,0 +1,167 @@
+//! Optional encryption of cached prompts and indexed code at rest
+//! (`VIBE_ENCRYPT=1`), for proprietary codebases on shared machines.
+//!
+//! Data is sealed with XChaCha20-Poly1305 under a per-user key taken from
+//! `VIBE_CACHE_KEY` (64 hex chars) or else the OS keyring, where one is
+//! generated on first use. Sealed blobs start with [`MAGIC`]; anything else
+//! is treated as plaintext written before encryption was turned on.
+
+use crate::types::Result;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use std::sync::OnceLock;
+
+const MAGIC: &[u8; 8] = b"VIBEENC1";
+const NONCE_LEN: usize = 24;
+const KEYRING_SERVICE: &str = "vibe_cli";
+const KEYRING_USER: &str = "cache-key";
+
+pub struct Cipher {
+    aead: XChaCha20Poly1305,
+}
+
+impl Cipher {
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let bytes = decode_hex(hex.trim())
+            .filter(|b| b.len() == 32)
+            .ok_or_else(|| anyhow::anyhow!("encryption key must be 64 hex characters"))?;
+        Ok(Self {
+            aead: XChaCha20Poly1305::new(Key::from_slice(&bytes)),
+        })
+    }
+
+    /// `VIBE_CACHE_KEY` if set, otherwise the key in the OS keyring
+    /// (created there on first use).
+    pub fn load() -> Result<Self> {
+        if let Ok(hex) = std::env::var("VIBE_CACHE_KEY") {
+            return Self::from_hex(&hex);
+        }
+        Self::from_keyring().map_err(|e| {
+            anyhow::anyhow!(
+                "encryption is enabled but the OS keyring is unavailable ({}); set VIBE_CACHE_KEY to a 64-character hex key instead",
+                e
+            )
+        })
+    }
+
+    fn from_keyring() -> Result<Self> {
+        let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?;
+        match entry.get_password() {
+            Ok(hex) => Self::from_hex(&hex),
+            Err(keyring::Error::NoEntry) => {
+                let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+                let hex = encode_hex(&key);
+                entry.set_password(&hex)?;
+                Self::from_hex(&hex)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .aead
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow::anyhow!("encryption failed"))?;
+        let mut sealed = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(MAGIC);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypt `data` if sealed; plaintext is returned unchanged.
+    pub fn open(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if !is_sealed(data) {
+            return Ok(data.to_vec());
+        }
+        let body = &data[MAGIC.len()..];
+        if body.len() < NONCE_LEN {
+            return Err(anyhow::anyhow!("encrypted data is truncated"));
+        }
+        let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+        self.aead
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("cannot decrypt data: wrong key or corrupted file"))
+    }
+}
+
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+static ACTIVE: OnceLock<Cipher> = OnceLock::new();
+
+/// Use `cipher` for all cache and index writes for the rest of the process.
+/// Later calls are ignored.
+pub fn install(cipher: Cipher) {
+    let _ = ACTIVE.set(cipher);
+}
+
+/// Load the key and turn encryption on, unless it already is.
+pub fn enable() -> Result<()> {
+    if ACTIVE.get().is_none() {
+        install(Cipher::load()?);
+    }
+    Ok(())
+}
+
+pub fn active() -> Option<&'static Cipher> {
+    ACTIVE.get()
+}
+
+/// Seal with the active cipher; unchanged when encryption is off.
+pub fn seal(data: Vec<u8>) -> Result<Vec<u8>> {
+    match active() {
+        Some(cipher) => cipher.seal(&data),
+        None => Ok(data),
+    }
+}
+
+/// Reverse of [`seal`]. Plaintext passes through, so files written before
+/// encryption was enabled stay readable.
+pub fn open(data: Vec<u8>) -> Result<Vec<u8>> {
+    if !is_sealed(&data) {
+        return Ok(data);
+    }
+    match active() {
+        Some(cipher) => cipher.open(&data),
+        None => Err(anyhow::anyhow!(
+            "data is encrypted; set VIBE_ENCRYPT=1 to read it"
+        )),
+    }
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_data_round_trips_and_plaintext_passes_through() {
+        let cipher = Cipher::from_hex(&"ab".repeat(32)).unwrap();
+        let sealed = cipher.seal(b"fn secret() {}").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.windows(6).any(|w| w == b"secret"));
+        assert_eq!(cipher.open(&sealed).unwrap(), b"fn secret() {}");
+        assert_eq!(cipher.open(b"plain").unwrap(), b"plain");
+
+        let other = Cipher::from_hex(&"cd".repeat(32)).unwrap();
+        assert!(other.open(&sealed).is_err());
+        assert!(Cipher::from_hex("abc").is_err());
+    }
+}
@@ -0,0 +1,375 @@
+//! On-disk answer caches (generated commands, explanations, RAG answers).
+//!
+//! Every cache is a [`CacheStore`]: one file holding a versioned header and
+//! the bincode-encoded entries, sealed by [`at_rest`](crate::at_rest) when
+//! encryption is on, and kept within its [`CacheLimits`]. Files written
+//! before the store existed are migrated on first read.
+
+use crate::at_rest;
+use crate::types::Result;
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+const MAGIC: &[u8; 8] = b"VIBECACH";
+/// Bumped when [`CacheEntry`] changes; files with another version are
+/// treated as empty and rewritten.
+const SCHEMA_VERSION: u32 = 2;
+
+/// Limits for one cache kind, set under `[cache.<kind>]` in `config.toml`.
+/// A limit of 0 disables it.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CacheLimits {
+    pub ttl_days: u64,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+}
+
+impl Default for CacheLimits {
+    fn default() -> Self {
+        Self {
+            ttl_days: 7,
+            max_entries: 500,
+            max_bytes: 4 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// The prompt or question the entry answers.
+    pub key: String,
+    pub value: String,
+    /// Seconds since the Unix epoch when the entry was stored.
+    pub created_at: u64,
+    /// Seconds since the Unix epoch when the entry was last served.
+    pub last_used: u64,
+    /// Where the value was made for, e.g. the project a command was
+    /// generated in; `None` when it holds anywhere.
+    pub scope: Option<String>,
+}
+
+impl CacheEntry {
+    fn recency(&self) -> u64 {
+        self.last_used.max(self.created_at)
+    }
+
+    fn size(&self) -> usize {
+        self.key.len() + self.value.len()
+    }
+}
+
+impl CacheLimits {
+    /// Drop expired entries and duplicate keys, then evict the least recently
+    /// used entries until the count and byte limits hold. Entries are expected
+    /// oldest first, which breaks ties between equal timestamps; survivors
+    /// keep that order.
+    pub fn prune(&self, entries: &mut Vec<CacheEntry>, now: u64) {
+        if self.ttl_days > 0 {
+            let ttl = self.ttl_days * DAY_SECS;
+            entries.retain(|e| now.saturating_sub(e.created_at) < ttl);
+        }
+
+        entries.reverse();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.recency()));
+        let mut seen = HashSet::new();
+        let mut bytes = 0;
+        let mut kept = 0;
+        entries.retain(|e| {
+            if !seen.insert(e.key.clone()) {
+                return false;
+            }
+            bytes += e.size();
+            kept += 1;
+            (self.max_entries == 0 || kept <= self.max_entries)
+                && (self.max_bytes == 0 || bytes <= self.max_bytes)
+        });
+        entries.reverse();
+    }
+}
+
+pub struct CacheStore {
+    path: PathBuf,
+    legacy_path: Option<PathBuf>,
+    limits: CacheLimits,
+}
+
+impl CacheStore {
+    pub fn new(path: impl Into<PathBuf>, limits: CacheLimits) -> Self {
+        Self {
+            path: path.into(),
+            legacy_path: None,
+            limits,
+        }
+    }
+
+    /// Read entries from `path` while the store's own file does not exist
+    /// yet; it is removed once the store has been written.
+    pub fn with_legacy_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.legacy_path = Some(path.into());
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The value stored under exactly `key`.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        self.lookup(|k| (k == key).then_some(1.0))
+    }
+
+    /// The value of the entry `score` rates highest; entries it returns
+    /// `None` for never match. A hit counts as a use for eviction.
+    pub fn lookup(&self, score: impl Fn(&str) -> Option<f64>) -> Result<Option<String>> {
+        Ok(self.lookup_entry(score)?.map(|entry| entry.value))
+    }
+
+    /// Like [`Self::lookup`], but the whole entry, with the key it was
+    /// stored under and when.
+    pub fn lookup_entry(&self, score: impl Fn(&str) -> Option<f64>) -> Result<Option<CacheEntry>> {
+        let Some(mut entries) = self.load() else {
+            return Ok(None);
+        };
+        let now = now_secs();
+        let before = entries.len();
+        self.limits.prune(&mut entries, now);
+
+        let hit = entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| score(&e.key).map(|s| (i, s)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i);
+        let value = hit.map(|i| {
+            let mut entry = entries.remove(i);
+            entry.last_used = now;
+            entries.push(entry.clone());
+            entry
+        });
+
+        if value.is_some() || entries.len() != before {
+            self.save(&entries)?;
+        }
+        Ok(value)
+    }
+
+    /// Store `value` under `key`, replacing any previous entry for it.
+    pub fn put(&self, key: &str, value: &str) -> Result<()> {
+        self.put_scoped(key, value, None)
+    }
+
+    /// Like [`Self::put`], recording the `scope` the value was made for.
+    pub fn put_scoped(&self, key: &str, value: &str, scope: Option<&str>) -> Result<()> {
+        let mut entries = self.load().unwrap_or_default();
+        let now = now_secs();
+        entries.retain(|e| e.key != key);
+        entries.push(CacheEntry {
+            key: key.to_string(),
+            value: value.to_string(),
+            created_at: now,
+            last_used: now,
+            scope: scope.map(str::to_string),
+        });
+        self.limits.prune(&mut entries, now);
+        self.save(&entries)
+    }
+
+    /// Entries on disk, or `None` if there is no cache yet. Unreadable files
+    /// (sealed under another key, written by a newer release) count as empty.
+    fn load(&self) -> Option<Vec<CacheEntry>> {
+        let path = if self.path.exists() {
+            &self.path
+        } else {
+            self.legacy_path.as_ref().filter(|p| p.exists())?
+        };
+        let entries = std::fs::read(path)
+            .map_err(anyhow::Error::from)
+            .and_then(at_rest::open)
+            .ok()
+            .and_then(|data| decode(&data))
+            .unwrap_or_default();
+        Some(entries)
+    }
+
+    fn save(&self, entries: &[CacheEntry]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+        data.extend(bincode::serialize(entries)?);
+        std::fs::write(&self.path, at_rest::seal(data)?)?;
+        if let Some(legacy) = &self.legacy_path {
+            let _ = std::fs::remove_file(legacy);
+        }
+        Ok(())
+    }
+}
+
+fn decode(data: &[u8]) -> Option<Vec<CacheEntry>> {
+    let Some(rest) = data.strip_prefix(MAGIC) else {
+        return migrate_legacy(data);
+    };
+    let version = rest.get(..4)?;
+    let body = &rest[4..];
+    match u32::from_le_bytes(version.try_into().ok()?) {
+        SCHEMA_VERSION => bincode::deserialize(body).ok(),
+        // Entries without a scope
+        1 => {
+            let rows: Vec<(String, String, u64, u64)> = bincode::deserialize(body).ok()?;
+            Some(rows.into_iter().map(unscoped).collect())
+        }
+        _ => None,
+    }
+}
+
+fn unscoped((key, value, created_at, last_used): (String, String, u64, u64)) -> CacheEntry {
+    CacheEntry {
+        key,
+        value,
+        created_at,
+        last_used,
+        scope: None,
+    }
+}
+
+#[derive(Deserialize)]
+struct LegacyFile<E> {
+    entries: Vec<E>,
+}
+
+#[derive(Deserialize)]
+struct LegacyJsonEntry {
+    #[serde(alias = "prompt", alias = "question")]
+    key: String,
+    #[serde(alias = "command", alias = "response")]
+    value: String,
+    timestamp: u64,
+    #[serde(default)]
+    last_used: u64,
+}
+
+/// Files from before the store: `{ entries: [...] }` of prompt, answer and
+/// timestamp, with or without a trailing `last_used`, as JSON (the command
+/// cache, and explain caches that were mistakenly saved as JSON) or bincode.
+fn migrate_legacy(data: &[u8]) -> Option<Vec<CacheEntry>> {
+    if let Ok(file) = serde_json::from_slice::<LegacyFile<LegacyJsonEntry>>(data) {
+        return Some(
+            file.entries
+                .into_iter()
+                .map(|e| unscoped((e.key, e.value, e.timestamp, e.last_used)))
+                .collect(),
+        );
+    }
+    let strict = bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes();
+    let rows = strict
+        .deserialize::<LegacyFile<(String, String, u64, u64)>>(data)
+        .map(|file| file.entries)
+        .or_else(|_| {
+            strict
+                .deserialize::<LegacyFile<(String, String, u64)>>(data)
+                .map(|file| file.entries.into_iter().map(|(k, v, t)| (k, v, t, 0)).collect())
+        })
+        .ok()?;
+    Some(rows.into_iter().map(unscoped).collect())
+}
+
+/// Seconds since the Unix epoch.
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, created_at: u64, last_used: u64) -> CacheEntry {
+        CacheEntry {
+            key: key.to_string(),
+            value: String::new(),
+            created_at,
+            last_used,
+            scope: None,
+        }
+    }
+
+    fn keys(entries: &[CacheEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.key.as_str()).collect()
+    }
+
+    #[test]
+    fn prune_expires_dedupes_and_evicts_least_recently_used() {
+        let now = 100 * DAY_SECS;
+        let limits = CacheLimits {
+            ttl_days: 7,
+            max_entries: 2,
+            max_bytes: 0,
+        };
+        let mut entries = vec![
+            entry("old", now - 8 * DAY_SECS, now),
+            entry("aa", now - 10, now - 5),
+            entry("bb", now - 20, now - 20),
+            entry("aa", now - 30, now - 30),
+            entry("cc", now - 40, now - 1),
+        ];
+        limits.prune(&mut entries, now);
+        assert_eq!(keys(&entries), ["aa", "cc"]);
+
+        let limits = CacheLimits {
+            ttl_days: 0,
+            max_entries: 0,
+            max_bytes: 4,
+        };
+        let mut entries = vec![entry("aa", 1, 1), entry("bb", 1, 1), entry("cc", 1, 1)];
+        limits.prune(&mut entries, now);
+        assert_eq!(keys(&entries), ["bb", "cc"]);
+    }
+
+    #[test]
+    fn legacy_json_and_bincode_files_are_migrated() {
+        let now = now_secs();
+        let json = format!(
+            r#"{{"entries":[{{"prompt":"list files","command":"ls","timestamp":{now}}}]}}"#
+        );
+        let migrated = decode(json.as_bytes()).unwrap();
+        assert_eq!(migrated[0].key, "list files");
+        assert_eq!(migrated[0].value, "ls");
+
+        #[derive(Serialize)]
+        struct OldFile {
+            entries: Vec<(String, String, u64)>,
+        }
+        let old = bincode::serialize(&OldFile {
+            entries: vec![("why?".into(), "because".into(), now)],
+        })
+        .unwrap();
+        assert_eq!(decode(&old).unwrap()[0].value, "because");
+
+        let mut unscoped = MAGIC.to_vec();
+        unscoped.extend_from_slice(&1u32.to_le_bytes());
+        unscoped.extend(bincode::serialize(&vec![("df".to_string(), "df -h".to_string(), now, now)]).unwrap());
+        let migrated = decode(&unscoped).unwrap();
+        assert_eq!((migrated[0].value.as_str(), migrated[0].scope.as_deref()), ("df -h", None));
+
+        let dir = std::env::temp_dir().join(format!("vibe_cache_test_{}", std::process::id()));
+        let legacy = dir.join("explain_cache.bin");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&legacy, json).unwrap();
+        let store = CacheStore::new(dir.join("explain.cache"), CacheLimits::default())
+            .with_legacy_path(&legacy);
+        assert_eq!(store.get("list files").unwrap().as_deref(), Some("ls"));
+        assert!(!legacy.exists());
+        assert!(decode(&std::fs::read(store.path()).unwrap()).is_some());
+        assert_eq!(store.get("list files").unwrap().as_deref(), Some("ls"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
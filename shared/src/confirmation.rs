@@ -1,14 +1,51 @@
+use crate::accessibility;
 use crate::types::Result;
 use colored::Colorize;
 use crossterm::event::{read, Event, KeyCode};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use dialoguer::console::Term;
+use std::io::{BufRead, IsTerminal, Write};
+
+/// An answer to [`ask_or_refine`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    Yes,
+    No,
+    /// The user wants to correct what they were asked about first.
+    Refine,
+}
 
 /// Standardized confirmation prompt used across binaries.
 /// Returns immediately on single keypress: y/Y, n/N, or Enter for default.
+/// In accessible mode, or when stdin is not a terminal, a typed line is read
+/// instead.
 pub fn ask_confirmation(prompt: &str, default_yes: bool) -> Result<bool> {
+    Ok(ask(prompt, default_yes, false)? == Answer::Yes)
+}
+
+/// Like [`ask_confirmation`], with `r` as a third answer.
+pub fn ask_or_refine(prompt: &str, default_yes: bool) -> Result<Answer> {
+    ask(prompt, default_yes, true)
+}
+
+fn ask(prompt: &str, default_yes: bool, refine: bool) -> Result<Answer> {
+    if accessibility::is_accessible() || !std::io::stdin().is_terminal() {
+        return ask_line(
+            &mut std::io::stdin().lock(),
+            &mut std::io::stdout(),
+            prompt,
+            default_yes,
+            refine,
+        );
+    }
+
     let term = Term::stdout();
-    let default_hint = if default_yes { "[Y/n]" } else { "[y/N]" };
+    let default_hint = match (default_yes, refine) {
+        (true, false) => "[Y/n]",
+        (false, false) => "[y/N]",
+        (true, true) => "[Y/n/r=refine]",
+        (false, true) => "[y/N/r=refine]",
+    };
     term.write_str(&format!("{prompt} {default_hint} "))?;
     term.flush()?;
 
@@ -16,9 +53,11 @@ pub fn ask_confirmation(prompt: &str, default_yes: bool) -> Result<bool> {
     let result = loop {
         match read()? {
             Event::Key(key) => match key.code {
-                KeyCode::Char('y') | KeyCode::Char('Y') => break true,
-                KeyCode::Char('n') | KeyCode::Char('N') => break false,
-                KeyCode::Enter => break default_yes,
+                KeyCode::Char('y') | KeyCode::Char('Y') => break Answer::Yes,
+                KeyCode::Char('n') | KeyCode::Char('N') => break Answer::No,
+                KeyCode::Char('r') | KeyCode::Char('R') if refine => break Answer::Refine,
+                KeyCode::Enter if default_yes => break Answer::Yes,
+                KeyCode::Enter => break Answer::No,
                 _ => continue,
             },
             _ => continue,
@@ -27,8 +66,74 @@ pub fn ask_confirmation(prompt: &str, default_yes: bool) -> Result<bool> {
     disable_raw_mode()?;
 
     // Echo selection with color for clarity.
-    let selection = if result { "y".green() } else { "n".red() };
+    let selection = match result {
+        Answer::Yes => "y".green(),
+        Answer::No => "n".red(),
+        Answer::Refine => "r".cyan(),
+    };
     term.write_line(&selection.to_string())?;
 
     Ok(result)
 }
+
+/// Line-based confirmation: `yes`/`no` (or `y`/`n`) followed by Enter, an
+/// empty line for the default. The question is repeated on anything else;
+/// end of input declines.
+pub fn ask_confirmation_line(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    prompt: &str,
+    default_yes: bool,
+) -> Result<bool> {
+    Ok(ask_line(input, output, prompt, default_yes, false)? == Answer::Yes)
+}
+
+/// Like [`ask_confirmation_line`], also accepting `r`/`refine`.
+pub fn ask_or_refine_line(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    prompt: &str,
+    default_yes: bool,
+) -> Result<Answer> {
+    ask_line(input, output, prompt, default_yes, true)
+}
+
+fn ask_line(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    prompt: &str,
+    default_yes: bool,
+    refine: bool,
+) -> Result<Answer> {
+    let default_hint = match (default_yes, refine) {
+        (true, false) => "(yes/no, default yes)",
+        (false, false) => "(yes/no, default no)",
+        (true, true) => "(yes/no/refine, default yes)",
+        (false, true) => "(yes/no/refine, default no)",
+    };
+    loop {
+        write!(output, "{prompt} {default_hint}: ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(output)?;
+            return Ok(Answer::No);
+        }
+        match parse_answer(&line, default_yes, refine) {
+            Some(answer) => return Ok(answer),
+            None if refine => writeln!(output, "Please answer yes, no or refine.")?,
+            None => writeln!(output, "Please answer yes or no.")?,
+        }
+    }
+}
+
+fn parse_answer(line: &str, default_yes: bool, refine: bool) -> Option<Answer> {
+    match line.trim().to_ascii_lowercase().as_str() {
+        "" if default_yes => Some(Answer::Yes),
+        "" => Some(Answer::No),
+        "y" | "yes" => Some(Answer::Yes),
+        "n" | "no" => Some(Answer::No),
+        "r" | "refine" if refine => Some(Answer::Refine),
+        _ => None,
+    }
+}
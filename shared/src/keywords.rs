@@ -0,0 +1,157 @@
+//! Turning free text into keywords for matching against file paths.
+//!
+//! Identifiers are split into their words (`EmbeddingStorage`,
+//! `embedding_storage`, `embedding-storage` all give `embedding`, `storage`)
+//! and each word is lightly stemmed, so a question about "embeddings" finds
+//! `embedding_storage.rs`.
+
+use std::collections::HashSet;
+
+/// English filler plus words that carry no signal in a question to a coding
+/// assistant.
+const STOP_WORDS: &[&str] = &[
+    "the", "a", "an", "and", "or", "but", "in", "on", "at", "to", "for", "of", "with", "by",
+    "is", "are", "was", "were", "be", "been", "being", "have", "has", "had", "do", "does",
+    "did", "will", "would", "could", "should", "may", "might", "must", "can", "shall",
+    "this", "that", "these", "those", "i", "you", "he", "she", "it", "we", "they", "me",
+    "him", "her", "us", "them", "my", "your", "his", "its", "our", "their", "what", "which",
+    "who", "when", "where", "why", "how", "all", "any", "both", "each", "few", "more",
+    "most", "other", "some", "such", "no", "nor", "not", "only", "own", "same", "so",
+    "than", "too", "very", "just", "now", "here", "there", "then", "once", "also",
+    "explain", "available", "list", "show", "get", "find", "search", "query", "select",
+];
+
+/// Programming-language keywords: they appear in nearly every source file,
+/// so matching on them would select everything.
+const CODE_STOP_WORDS: &[&str] = &[
+    "fn", "pub", "impl", "struct", "enum", "trait", "mod", "use", "let", "mut", "self",
+    "crate", "async", "await", "def", "class", "function", "func", "var", "const", "return",
+    "import", "from", "package", "public", "private", "static", "void", "new", "true",
+    "false", "null", "none", "nil", "else", "elif", "while", "loop", "match", "type",
+];
+
+pub fn is_stop_word(word: &str) -> bool {
+    let lower = word.to_lowercase();
+    STOP_WORDS.contains(&lower.as_str()) || CODE_STOP_WORDS.contains(&lower.as_str())
+}
+
+/// Lowercased words of an identifier, split on `_`, `-`, case changes and
+/// letter/digit boundaries; acronyms stay together (`HTTPServer` → `http`,
+/// `server`).
+pub fn identifier_parts(identifier: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    for piece in identifier.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = piece.chars().collect();
+        let mut current = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            let boundary = i > 0 && {
+                let prev = chars[i - 1];
+                let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+                (c.is_uppercase() && (prev.is_lowercase() || prev.is_ascii_digit()))
+                    || (c.is_uppercase() && prev.is_uppercase() && next_lower)
+                    || (c.is_ascii_digit() != prev.is_ascii_digit())
+            };
+            if boundary && !current.is_empty() {
+                parts.push(std::mem::take(&mut current));
+            }
+            current.extend(c.to_lowercase());
+        }
+        if !current.is_empty() {
+            parts.push(current);
+        }
+    }
+    parts
+}
+
+/// Strip common English inflections (`embeddings` → `embed`,
+/// `indexing` → `index`, `queries` → `query`). Deliberately conservative:
+/// a stem is only useful if the same word in a file name stems the same way.
+pub fn stem(word: &str) -> String {
+    let word = singular(&word.to_lowercase());
+    if word.len() <= 4 {
+        return word;
+    }
+    for suffix in ["ing", "ed"] {
+        if let Some(base) = word.strip_suffix(suffix) {
+            if base.len() >= 4 {
+                return undouble(base);
+            }
+        }
+    }
+    word
+}
+
+fn singular(word: &str) -> String {
+    if word.len() <= 4 {
+        return word.to_string();
+    }
+    if let Some(base) = word.strip_suffix("ies") {
+        return format!("{base}y");
+    }
+    if let Some(base) = word.strip_suffix("es") {
+        if ["s", "x", "z", "ch", "sh"].iter().any(|end| base.ends_with(end)) {
+            return base.to_string();
+        }
+    }
+    if word.ends_with('s') && !["ss", "us", "is"].iter().any(|end| word.ends_with(end)) {
+        return word[..word.len() - 1].to_string();
+    }
+    word.to_string()
+}
+
+/// `embedd` → `embed`, `runn` → `run`.
+fn undouble(base: &str) -> String {
+    let bytes = base.as_bytes();
+    let n = bytes.len();
+    if n >= 2 && bytes[n - 1] == bytes[n - 2] && !b"aeiouls".contains(&bytes[n - 1]) {
+        base[..n - 1].to_string()
+    } else {
+        base.to_string()
+    }
+}
+
+/// Stemmed terms of `text` in order, repeats included, with stop words and
+/// words shorter than three characters dropped.
+pub fn terms(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .flat_map(identifier_parts)
+        .filter(|part| part.len() >= 3 && !is_stop_word(part))
+        .map(|part| stem(&part))
+        .collect()
+}
+
+/// Distinct [`terms`] of `text`, in order of first appearance.
+pub fn keywords(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    terms(text)
+        .into_iter()
+        .filter(|k| seen.insert(k.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_split_and_stem_to_matching_keywords() {
+        assert_eq!(identifier_parts("EmbeddingStorage"), ["embedding", "storage"]);
+        assert_eq!(identifier_parts("embedding_storage.rs"), ["embedding", "storage", "rs"]);
+        assert_eq!(identifier_parts("HTTPServer2Config"), ["http", "server", "2", "config"]);
+        assert_eq!(identifier_parts("vector-cache"), ["vector", "cache"]);
+
+        assert_eq!(stem("embeddings"), "embed");
+        assert_eq!(stem("embedding"), "embed");
+        assert_eq!(stem("running"), "run");
+        assert_eq!(stem("string"), "string");
+        assert_eq!(stem("queries"), "query");
+        assert_eq!(stem("indexes"), "index");
+        assert_eq!(stem("status"), "status");
+        assert_eq!(stem("process"), "process");
+
+        assert_eq!(
+            keywords("Where does EmbeddingStorage keep the embeddings? fn impl"),
+            ["embed", "storage", "keep"]
+        );
+    }
+}
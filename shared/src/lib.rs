@@ -2,4 +2,9 @@ pub mod error;
 pub mod telemetry;
 pub mod types;
 pub mod utils;
+pub mod accessibility;
+pub mod at_rest;
+pub mod cache;
 pub mod confirmation;
+pub mod keywords;
+pub mod paths;
@@ -0,0 +1,100 @@
+//! Where vibe_cli keeps its files.
+//!
+//! Data (indexes, caches) and config each resolve through, in order: an
+//! explicit override, the platform convention from `directories` (which
+//! honors `XDG_DATA_HOME` / `XDG_CONFIG_HOME` on Linux and uses
+//! `~/Library/Application Support` on macOS), then `./.vibe_cli`.
+
+use directories::ProjectDirs;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static DATA_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use `dir` for all data files for the rest of the process (`--data-dir`).
+/// Must be called before any path is resolved; later calls are ignored.
+pub fn set_data_dir(dir: PathBuf) {
+    let _ = DATA_DIR_OVERRIDE.set(dir);
+}
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "vibe_cli")
+}
+
+/// Base directory for indexes and caches.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = DATA_DIR_OVERRIDE.get() {
+        return dir.clone();
+    }
+    if let Ok(dir) = std::env::var("VIBE_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+    project_dirs()
+        .map(|dirs| dirs.data_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from(".vibe_cli"))
+}
+
+/// Directory holding `config.toml` and other user settings.
+pub fn config_dir() -> PathBuf {
+    project_dirs()
+        .map(|dirs| dirs.config_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from(".vibe_cli"))
+}
+
+fn find_project_root() -> Option<String> {
+    project_root_of(&std::env::current_dir().ok()?)
+}
+
+/// The project `dir` is in: the nearest directory at or above it holding a
+/// project file such as `Cargo.toml` or `.git`.
+pub fn project_root_of(dir: &Path) -> Option<String> {
+    let mut current = dir.to_path_buf();
+    loop {
+        // Check for various project indicators
+        let project_files = [
+            "Cargo.toml",       // Rust
+            "package.json",     // Node.js
+            "requirements.txt", // Python
+            "Pipfile",          // Python
+            "pyproject.toml",   // Python
+            "setup.py",         // Python
+            "Makefile",         // C/C++
+            "CMakeLists.txt",   // C/C++
+            "configure.ac",     // C/C++
+            "go.mod",           // Go
+            "Gemfile",          // Ruby
+            "composer.json",    // PHP
+            ".git",             // Git repo as fallback
+        ];
+
+        for file in &project_files {
+            if current.join(file).exists() {
+                return Some(current.display().to_string());
+            }
+        }
+
+        if !current.pop() {
+            break;
+        }
+    }
+    None
+}
+
+/// Hash of the enclosing project root, so each project gets its own index
+/// and caches; `global` outside any project.
+pub fn project_suffix() -> String {
+    if let Some(root) = find_project_root() {
+        let mut hasher = DefaultHasher::new();
+        root.hash(&mut hasher);
+        format!("{:x}", hasher.finish())
+    } else {
+        "global".to_string()
+    }
+}
+
+/// Per-project data file, e.g. `<data_dir>/<suffix>_embeddings.db`.
+pub fn project_data_file(name: &str) -> PathBuf {
+    data_dir().join(format!("{}_{}", project_suffix(), name))
+}
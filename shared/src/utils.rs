@@ -2,5 +2,91 @@ use std::path::Path;
 
 pub fn is_supported_file(path: &Path) -> bool {
     let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-    matches!(ext, "rs" | "md" | "toml" | "json" | "graphql" | "c" | "h" | "cpp" | "hpp" | "cc" | "cxx" | "py" | "js" | "ts" | "java" | "go" | "rb" | "php" | "sh" | "bash" | "zsh" | "fish" | "html" | "css" | "scss" | "sass" | "xml" | "yaml" | "yml" | "ini" | "cfg" | "conf")
+    matches!(ext, "rs" | "md" | "toml" | "json" | "graphql" | "c" | "h" | "cpp" | "hpp" | "cc" | "cxx" | "py" | "js" | "ts" | "java" | "go" | "rb" | "php" | "sh" | "bash" | "zsh" | "fish" | "html" | "css" | "scss" | "sass" | "xml" | "yaml" | "yml" | "ini" | "cfg" | "conf" | "ipynb" | "org" | "rst")
+}
+
+/// `bytes` in the largest binary unit that keeps the value at least 1,
+/// e.g. `1.5 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Language of a file judged by its extension; empty when unknown.
+pub fn language_for_path(path: &Path) -> &'static str {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    match ext {
+        "rs" => "rust",
+        "md" => "markdown",
+        "toml" => "toml",
+        "json" => "json",
+        "graphql" => "graphql",
+        "c" | "h" => "c",
+        "cpp" | "hpp" | "cc" | "cxx" => "cpp",
+        "py" => "python",
+        "js" => "javascript",
+        "ts" => "typescript",
+        "java" => "java",
+        "go" => "go",
+        "rb" => "ruby",
+        "php" => "php",
+        "sh" | "bash" | "zsh" | "fish" => "shell",
+        "html" => "html",
+        "css" | "scss" | "sass" => "css",
+        "xml" => "xml",
+        "yaml" | "yml" => "yaml",
+        "ini" | "cfg" | "conf" => "ini",
+        "ipynb" => "jupyter",
+        "org" => "org",
+        "rst" => "rst",
+        _ => "",
+    }
+}
+
+/// Language of a file judged by its extension, else by a shebang on the
+/// first line of `head` (the start of the file); empty when unknown.
+pub fn detect_language(path: &Path, head: &str) -> &'static str {
+    match language_for_path(path) {
+        "" => head.lines().next().and_then(shebang_language).unwrap_or(""),
+        language => language,
+    }
+}
+
+/// Language a script's `#!` line runs it with, e.g. `python` for
+/// `#!/usr/bin/env python3`.
+pub fn shebang_language(first_line: &str) -> Option<&'static str> {
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+    // python3.11 and the like.
+    let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    Some(match name {
+        "python" | "pypy" => "python",
+        "sh" | "bash" | "zsh" | "fish" | "dash" | "ksh" => "shell",
+        "node" | "nodejs" | "deno" | "bun" => "javascript",
+        "ts-node" | "tsx" => "typescript",
+        "ruby" => "ruby",
+        "perl" => "perl",
+        "php" => "php",
+        "lua" => "lua",
+        "rust-script" => "rust",
+        _ => return None,
+    })
+}
+
+/// `value` in single quotes, safe to paste into a `bash -c` command.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
 }
@@ -0,0 +1,9 @@
+/// Adler-style rolling checksum used to verify uploaded archives.
+pub fn archive_checksum(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
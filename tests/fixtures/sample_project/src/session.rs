@@ -0,0 +1,15 @@
+/// Conversation state kept between turns of a chat.
+pub struct ChatHistory {
+    turns: Vec<String>,
+}
+
+impl ChatHistory {
+    pub fn new() -> Self {
+        Self { turns: Vec::new() }
+    }
+
+    /// Remember a turn so the next prompt can include it.
+    pub fn remember_turn(&mut self, turn: &str) {
+        self.turns.push(turn.to_string());
+    }
+}
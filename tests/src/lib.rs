@@ -0,0 +1,47 @@
+//! Test-only harness shared by the end-to-end tests in `tests/tests`.
+
+pub mod mock_ollama;
+
+use std::path::PathBuf;
+use std::sync::Once;
+
+/// Absolute path to a file or directory under `tests/fixtures`.
+pub fn fixture_path(relative: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(relative)
+}
+
+pub fn fixture(relative: &str) -> String {
+    std::fs::read_to_string(fixture_path(relative)).unwrap_or_default()
+}
+
+/// Fresh scratch directory under the system temp dir, unique per call.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "vibe_cli_tests_{}_{}_{}",
+        std::process::id(),
+        name,
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+/// Point HOME (and therefore every cache and config path) at a scratch
+/// directory once per test binary, so tests never touch the real user dirs.
+pub fn isolate_home() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let home = scratch_dir("home");
+        std::env::set_var("HOME", &home);
+        std::env::remove_var("DB_PATH");
+        std::env::remove_var("VIBE_DATA_DIR");
+        std::env::remove_var("VIBE_CONFIG");
+        std::env::remove_var("XDG_DATA_HOME");
+        std::env::remove_var("XDG_CONFIG_HOME");
+    });
+}
@@ -0,0 +1,162 @@
+use infrastructure::ollama_client::OllamaClient;
+use serde_json::{json, Value};
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+/// Dimensions of the deterministic fake embeddings.
+pub const EMBEDDING_DIMS: usize = 64;
+
+/// In-process stand-in for an Ollama server speaking `/api/chat` and
+/// `/api/embeddings`, so flows can be driven without a real model.
+pub struct MockOllama {
+    server: MockServer,
+}
+
+impl MockOllama {
+    /// Start a server that embeds any text deterministically and answers
+    /// every chat request with `default_reply` until more specific replies
+    /// are mounted.
+    pub async fn start(default_reply: &str) -> Self {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/embeddings"))
+            .respond_with(HashingEmbedder)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .respond_with(chat_reply(default_reply))
+            .with_priority(u8::MAX)
+            .mount(&server)
+            .await;
+        Self { server }
+    }
+
+    /// Reply with `content` to chat requests whose body contains `needle`.
+    pub async fn reply_when(&self, needle: &str, content: &str) {
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_string_contains(needle))
+            .respond_with(chat_reply(content))
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Reply to every chat request with a raw HTTP status and body.
+    pub async fn fail_chat(&self, status: u16, body: &str) {
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .respond_with(ResponseTemplate::new(status).set_body_string(body))
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Turn away the next `times` chat requests with `status` (429 or 503),
+    /// as a busy server does.
+    pub async fn busy_chat(&self, status: u16, times: u64) {
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .respond_with(ResponseTemplate::new(status).set_body_string("server busy"))
+            .up_to_n_times(times)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    pub fn base_url(&self) -> String {
+        self.server.uri()
+    }
+
+    pub fn client(&self) -> OllamaClient {
+        OllamaClient::with_base_url(self.base_url(), "mock-model")
+    }
+
+    /// The user-visible prompt (last message) of every chat request received.
+    pub async fn chat_prompts(&self) -> Vec<String> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter(|r| r.url.path() == "/api/chat")
+            .filter_map(|r| serde_json::from_slice::<Value>(&r.body).ok())
+            .filter_map(|body| {
+                body["messages"]
+                    .as_array()
+                    .and_then(|m| m.last())
+                    .and_then(|m| m["content"].as_str())
+                    .map(str::to_string)
+            })
+            .collect()
+    }
+
+    pub async fn embedding_requests(&self) -> usize {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter(|r| r.url.path() == "/api/embeddings")
+            .count()
+    }
+
+    /// The model named in every embedding request received.
+    pub async fn embedding_models(&self) -> Vec<String> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter(|r| r.url.path() == "/api/embeddings")
+            .filter_map(|r| serde_json::from_slice::<Value>(&r.body).ok())
+            .filter_map(|body| body["model"].as_str().map(str::to_string))
+            .collect()
+    }
+}
+
+/// Prompt tokens the mock reports for every chat request.
+pub const MOCK_PROMPT_TOKENS: u64 = 100;
+
+/// A final chat chunk with Ollama's metadata: one generated token per
+/// word of `content`, generated in half a second.
+fn chat_reply(content: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "model": "mock-model",
+        "message": { "role": "assistant", "content": content },
+        "done": true,
+        "prompt_eval_count": MOCK_PROMPT_TOKENS,
+        "eval_count": content.split_whitespace().count(),
+        "eval_duration": 500_000_000u64,
+    }))
+}
+
+/// Hashed bag-of-words embedding: texts sharing words get similar vectors,
+/// which is enough for retrieval tests to be meaningful and repeatable.
+pub fn embed_text(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0f32; EMBEDDING_DIMS];
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.len() > 2)
+    {
+        let hash = word
+            .to_lowercase()
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |h, b| {
+                (h ^ b as u64).wrapping_mul(0x100000001b3)
+            });
+        vector[(hash % EMBEDDING_DIMS as u64) as usize] += 1.0;
+    }
+    vector
+}
+
+struct HashingEmbedder;
+
+impl Respond for HashingEmbedder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let body: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+        let prompt = body["prompt"].as_str().unwrap_or_default();
+        ResponseTemplate::new(200).set_body_json(json!({ "embedding": embed_text(prompt) }))
+    }
+}
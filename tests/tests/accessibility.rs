@@ -0,0 +1,37 @@
+use shared::accessibility::{self, glyph};
+use shared::confirmation::ask_confirmation_line;
+use std::io::Cursor;
+
+fn answer(input: &str, default_yes: bool) -> (bool, String) {
+    let mut output = Vec::new();
+    let answer =
+        ask_confirmation_line(&mut Cursor::new(input), &mut output, "Run this command?", default_yes)
+            .unwrap();
+    (answer, String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn typed_answers_replace_single_keypresses() {
+    assert!(answer("yes\n", false).0);
+    assert!(!answer("N\n", true).0);
+    assert!(answer("\n", true).0);
+    assert!(!answer("", true).0, "end of input declines");
+
+    let (accepted, output) = answer("maybe\ny\n", false);
+    assert!(accepted);
+    assert_eq!(
+        output,
+        "Run this command? (yes/no, default no): Please answer yes or no.\n\
+         Run this command? (yes/no, default no): "
+    );
+}
+
+#[test]
+fn accessible_mode_uses_ascii_and_no_color() {
+    use colored::Colorize;
+    assert_eq!(glyph("│", "|"), "│");
+    accessibility::set_accessible(true);
+    assert_eq!(glyph("│", "|"), "|");
+    assert_eq!("plain".red().to_string(), "plain");
+    accessibility::set_accessible(false);
+}
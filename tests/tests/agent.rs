@@ -0,0 +1,492 @@
+use application::agent_service::{
+    execute, substitute, uses_previous, StepEvent, StepExecutor, StepOutcome,
+};
+use application::command_planner::LlmCommandPlanner;
+use application::plan_parsing::{parse_agent_plan, parse_plan_steps};
+use application::plan_review_service::local_issues;
+use application::safety_service::SafetyService;
+use application::working_dir::WorkingDir;
+use domain::command_plan::{CommandPlan, CommandPlanner, PlanStep};
+use infrastructure::config::{Config, SandboxSettings};
+use presentation::cli::CliApp;
+use presentation::plan_diff::{diff_plans, render, PlanChange};
+use presentation::pty_shell::PtyShell;
+use presentation::runner::{check_condition, run_captured};
+use tests::mock_ollama::MockOllama;
+use tests::{fixture, isolate_home, scratch_dir};
+
+#[tokio::test]
+async fn agent_plan_is_extracted_from_noisy_reply() {
+    isolate_home();
+    let mock = MockOllama::start(&fixture("responses/agent_plan_noisy.txt")).await;
+    let app = CliApp::new().with_client(mock.client());
+
+    let plan = app.generate_agent_plan("find what fills the disk").await.unwrap();
+
+    assert_eq!(commands(&plan.steps), ["df -h", "du -sh /var/log", "journalctl --disk-usage"]);
+    assert_eq!(plan.description, "find what fills the disk");
+    assert_eq!(plan.safety_checks.len(), 3);
+    assert!(plan.safety_checks.iter().all(|c| c.passed));
+    let prompts = mock.chat_prompts().await;
+    assert!(prompts[0].contains("User request: find what fills the disk"));
+}
+
+#[tokio::test]
+async fn agent_plan_falls_back_to_list_lines() {
+    isolate_home();
+    let mock = MockOllama::start(&fixture("responses/agent_plan_list.txt")).await;
+    let app = CliApp::new().with_client(mock.client());
+
+    let plan = app.generate_agent_plan("memory report").await.unwrap();
+
+    assert_eq!(commands(&plan.steps), ["free -h", "ps aux --sort=-%mem | head", "uptime"]);
+}
+
+#[test]
+fn agent_plan_parsing_handles_fenced_and_empty_replies() {
+    assert_eq!(
+        parse_agent_plan("```json\n[\"uptime\", \"who\"]\n```"),
+        vec!["uptime", "who"]
+    );
+    assert!(parse_agent_plan("[]").is_empty());
+}
+
+fn steps(commands: &[&str]) -> Vec<String> {
+    commands.iter().map(|c| c.to_string()).collect()
+}
+
+fn plan(commands: &[&str]) -> Vec<PlanStep> {
+    commands.iter().map(|c| PlanStep::from(*c)).collect()
+}
+
+fn commands(plan: &[PlanStep]) -> Vec<&str> {
+    plan.iter().map(|s| s.command.as_str()).collect()
+}
+
+#[test]
+fn local_review_flags_placeholders_missing_sudo_and_ordering() {
+    let plan = plan(&[
+        "systemctl enable --now nginx",
+        "apt-get install -y nginx",
+        "cp site.conf /path/to/sites-enabled/",
+        "sudo apt-get update && apt list --installed | grep nginx",
+    ]);
+    let issues = local_issues(&plan, false);
+
+    let problems: Vec<(usize, &str)> = issues.iter().map(|i| (i.step, i.problem.as_str())).collect();
+    assert_eq!(
+        problems,
+        [
+            (0, "`systemctl` needs root but is not run with sudo."),
+            (0, "Uses the nginx service before step 2 installs it."),
+            (1, "`apt-get` needs root but is not run with sudo."),
+            (2, "Contains the placeholder `/path/to/sites-enabled/`; fill in a real value."),
+        ]
+    );
+    assert_eq!(issues[2].revised.as_deref(), Some("sudo apt-get install -y nginx"));
+    assert!(local_issues(&plan[1..2], true).is_empty(), "root needs no sudo");
+}
+
+#[tokio::test]
+async fn review_pass_revises_steps_that_contradict_the_system() {
+    isolate_home();
+    let mock = MockOllama::start("[]").await;
+    mock.reply_when(
+        "Review this shell plan",
+        r#"Here you go: [{"step": 1, "problem": "This system uses apt, not yum.", "fix": "`apt-get install -y htop`"}, {"step": 7, "problem": "out of range", "fix": null}]"#,
+    )
+    .await;
+    let app = CliApp::new().with_client(mock.client());
+
+    let plan = app
+        .review_agent_plan("install htop", plan(&["yum install -y htop", "htop --version"]))
+        .await;
+
+    // Prefixed with sudo too unless the tests run as root
+    assert!(plan.steps[0].command.ends_with("apt-get install -y htop"), "{:?}", plan.steps);
+    assert_eq!(plan.steps[1].command, "htop --version");
+    assert_eq!(plan.original[0].command, "yum install -y htop");
+    let notes: Vec<&str> = plan.issues_for(0).map(|i| i.problem.as_str()).collect();
+    assert!(notes.contains(&"This system uses apt, not yum."), "{:?}", notes);
+    assert_eq!(plan.issues_for(1).count(), 0);
+    assert!(mock.chat_prompts().await[0].contains("1. yum install -y htop\n2. htop --version"));
+}
+
+#[test]
+fn plan_diff_reports_added_removed_and_changed_steps() {
+    let old = steps(&["df -h", "du -sh /var/log", "journalctl --disk-usage", "uptime"]);
+    let new = steps(&["df -h", "du -sh /var/*", "journalctl --disk-usage", "docker system df"]);
+
+    assert_eq!(
+        diff_plans(&old, &new),
+        [
+            PlanChange::Kept { old: 0, new: 0 },
+            PlanChange::Changed { old: 1, new: 1 },
+            PlanChange::Kept { old: 2, new: 2 },
+            PlanChange::Changed { old: 3, new: 3 },
+        ]
+    );
+    assert_eq!(
+        diff_plans(&old[..2], &old),
+        [
+            PlanChange::Kept { old: 0, new: 0 },
+            PlanChange::Kept { old: 1, new: 1 },
+            PlanChange::Added { new: 2 },
+            PlanChange::Added { new: 3 },
+        ]
+    );
+
+    colored::control::set_override(false);
+    assert_eq!(
+        render(&old, &old[1..]),
+        "- [1] df -h\n  [1] du -sh /var/log\n  [2] journalctl --disk-usage\n  [3] uptime\n\
+         0 added, 1 removed, 0 changed\n"
+    );
+    assert!(render(&new, &new).ends_with("The plan is unchanged.\n"));
+}
+
+#[tokio::test]
+async fn revised_plans_carry_the_rejected_plan_and_feedback() {
+    isolate_home();
+    let mock = MockOllama::start(r#"["df -h", "docker system df"]"#).await;
+    let app = CliApp::new().with_client(mock.client());
+
+    let rejected = CommandPlan {
+        id: "disk".into(),
+        description: "find what fills the disk".into(),
+        steps: plan(&["df -h", "du -sh /"]),
+        safety_checks: Vec::new(),
+    };
+
+    let plan = app.revise_agent_plan(&rejected, "check docker too").await.unwrap();
+
+    assert_eq!(commands(&plan.steps), ["df -h", "docker system df"]);
+    assert_eq!(plan.description, "find what fills the disk");
+    assert_eq!(plan.safety_checks.len(), 2);
+    let prompt = &mock.chat_prompts().await[0];
+    assert!(prompt.contains("User request: find what fills the disk"));
+    assert!(prompt.contains("The user rejected this plan:\n1. df -h\n2. du -sh /\nTheir feedback: check docker too"));
+}
+
+#[test]
+fn previous_step_output_is_substituted_as_one_quoted_word() {
+    let (status, output) = run_captured("echo /var/log/big file.log; echo noise >&2", 0, &Default::default()).unwrap();
+    assert!(status.success());
+    assert_eq!(output.stdout(), "/var/log/big file.log\n");
+
+    let step = "gzip -9 {{prev.stdout}}";
+    assert!(uses_previous(step));
+    assert_eq!(
+        substitute(step, Some(output.stdout())).unwrap(),
+        "gzip -9 '/var/log/big file.log'"
+    );
+    // Quotes the model already put around the token are not doubled
+    assert_eq!(
+        substitute("ls -l \"{{prev.stdout}}\"", Some("it's here\n")).unwrap(),
+        "ls -l 'it'\\''s here'"
+    );
+    let (status, output) = run_captured(
+        &substitute("printf '%s' {{prev.stdout}}", Some("it's $HOME")).unwrap(),
+        0,
+        &Default::default(),
+    )
+    .unwrap();
+    assert!(status.success());
+    assert_eq!(output.stdout(), "it's $HOME\n");
+
+    assert_eq!(substitute("df -h", None).unwrap(), "df -h");
+    assert!(substitute(step, None).is_err());
+    assert!(substitute(step, Some("  \n")).is_err());
+}
+
+#[test]
+fn review_accepts_prev_stdout_but_not_in_the_first_step() {
+    assert!(local_issues(&plan(&["ls -S /var/log | head -n1", "gzip {{prev.stdout}}"]), true).is_empty());
+
+    let issues = local_issues(&plan(&["gzip {{prev.stdout}} {{file}}"]), true);
+    let problems: Vec<_> = issues.iter().map(|i| i.problem.as_str()).collect();
+    assert_eq!(
+        problems,
+        vec![
+            "Contains the placeholder `{{file}}`; fill in a real value.",
+            "Uses {{prev.stdout}} but there is no previous step."
+        ]
+    );
+}
+
+#[test]
+fn plan_steps_may_carry_conditions_and_fallbacks() {
+    let raw = r#"Plan: [
+        "nginx -v",
+        {"command": "sudo apt-get install -y nginx", "when": "! command -v nginx"},
+        {"cmd": "sudo systemctl start nginx", "on_failure": "sudo journalctl -u nginx -n 20", "when": " "}
+    ]"#;
+    let steps = parse_plan_steps(raw);
+
+    assert_eq!(
+        steps,
+        [
+            PlanStep::new("nginx -v"),
+            PlanStep::new("sudo apt-get install -y nginx").with_when("! command -v nginx"),
+            PlanStep::new("sudo systemctl start nginx").with_on_failure("sudo journalctl -u nginx -n 20"),
+        ]
+    );
+    assert_eq!(
+        steps[1].to_string(),
+        "sudo apt-get install -y nginx (only if: ! command -v nginx)"
+    );
+    assert_eq!(commands(&parse_plan_steps("1. uptime\n2. who")), ["uptime", "who"]);
+
+    let issues = local_issues(&[PlanStep::new("ls").with_on_failure("cat /path/to/log")], true);
+    assert_eq!(issues[0].problem, "Contains the placeholder `/path/to/log`; fill in a real value.");
+}
+
+#[test]
+fn read_only_conditions_are_checked_without_asking() {
+    isolate_home();
+    let config = Config::load();
+
+    assert_eq!(check_condition("test -d /", &config).unwrap(), Some(true));
+    assert_eq!(
+        check_condition("command -v surely-not-installed-vibe", &config).unwrap(),
+        Some(false)
+    );
+    assert_eq!(check_condition("! command -v surely-not-installed-vibe", &config).unwrap(), Some(true));
+}
+
+#[test]
+fn leading_cds_move_the_working_directory_of_later_steps() {
+    let root = scratch_dir("working_dir");
+    std::fs::create_dir_all(root.join("app/src")).unwrap();
+    std::fs::create_dir_all(root.join("my docs")).unwrap();
+    let mut dir = WorkingDir::new(root.clone());
+
+    let step = dir.locate("cd app && npm test");
+    assert_eq!((step.dir.as_path(), step.command.as_str()), (root.join("app").as_path(), "npm test"));
+    assert_eq!(dir.current(), root, "nothing moves until the step has run");
+    dir.enter(step.dir);
+
+    let only = dir.locate("cd src");
+    assert_eq!(only.dir, root.join("app/src"));
+    assert!(only.command.is_empty());
+    assert_eq!(dir.locate("cd ..; cd app/src ; ls").dir, root.join("app/src"));
+    assert_eq!(dir.locate("cd app && ls").dir, root.join("app"), "a repeated cd from the start");
+    assert_eq!(dir.locate("cd '../my docs' && ls").dir, root.join("my docs"));
+    dir.enter(root.join("app/src"));
+    assert_eq!(dir.locate("cd -").dir, root.join("app"));
+
+    for kept in ["cd missing && make", "cd $APP && make", "cd app || exit 1", "echo hi && cd app"] {
+        let step = dir.locate(kept);
+        assert_eq!((step.dir.as_path(), step.command.as_str()), (dir.current(), kept), "{kept}");
+    }
+
+    let sandbox = SandboxSettings {
+        cwd: Some(root.join("app")),
+        ..Default::default()
+    };
+    let (_, output) = run_captured("pwd", 0, &sandbox).unwrap();
+    assert_eq!(output.stdout().trim(), root.join("app").to_string_lossy());
+}
+
+#[test]
+fn persistent_shell_keeps_variables_directories_and_sourced_scripts() {
+    let root = scratch_dir("pty_shell");
+    std::fs::create_dir_all(root.join("venv/bin")).unwrap();
+    std::fs::write(root.join("venv/bin/activate"), "export VIRTUAL_ENV=\"$PWD/venv\"\n").unwrap();
+    let mut shell = PtyShell::spawn(&SandboxSettings::default()).unwrap();
+
+    let first = shell.run("export GREETING='hi there'; source venv/bin/activate; cd venv", &root, 0).unwrap();
+    assert_eq!(first.code, Some(0));
+    assert_eq!(shell.take_dir(), Some(root.join("venv")));
+
+    let here = root.join("venv");
+    let second = shell.run("echo \"$GREETING\"; echo \"$VIRTUAL_ENV\"; pwd; echo oops >&2", &here, 0).unwrap();
+    let expected = format!("hi there\n{}\n{}\n", here.display(), here.display());
+    assert_eq!(second.output.stdout(), expected, "stderr is kept apart from stdout");
+    assert!(second.output.text().contains("oops"));
+
+    let printf = shell.run("printf 'no newline'; false", &here, 0).unwrap();
+    assert_eq!((printf.code, printf.output.stdout()), (Some(1), "no newline\n"));
+    let blank = shell.run("echo; echo end; echo", &here, 0).unwrap();
+    assert_eq!(blank.output.stdout(), "\nend\n\n", "blank lines of the output itself are kept");
+
+    let exports = shell.exports_file(&here).unwrap();
+    let mode = std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(&exports).unwrap().permissions());
+    assert_eq!(mode & 0o777, 0o600);
+    let sourced = std::process::Command::new("bash")
+        .arg("-c")
+        .arg(format!(". '{}'; echo \"$GREETING\"", exports.display()))
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&sourced.stdout), "hi there\n");
+
+    let exited = shell.run("exit 3", &here, 0).unwrap();
+    assert_eq!(exited.code, None);
+    assert!(!shell.is_alive());
+}
+
+/// Answers conditions and runs from scripted results, recording what ran.
+#[derive(Default)]
+struct ScriptedSteps {
+    conditions: Vec<(&'static str, Option<bool>)>,
+    results: Vec<(&'static str, StepOutcome)>,
+    ran: Vec<String>,
+    events: Vec<String>,
+}
+
+impl StepExecutor for ScriptedSteps {
+    async fn check(&mut self, condition: &str) -> shared::types::Result<Option<bool>> {
+        self.ran.push(format!("check {}", condition));
+        Ok(self.conditions.iter().find(|(c, _)| *c == condition).and_then(|(_, r)| *r))
+    }
+
+    async fn run(&mut self, command: &str) -> shared::types::Result<StepOutcome> {
+        self.ran.push(command.to_string());
+        let outcome = self.results.iter().find(|(c, _)| *c == command).map(|(_, o)| o.clone());
+        Ok(outcome.unwrap_or(StepOutcome::Succeeded { stdout: None }))
+    }
+
+    fn event(&mut self, step: usize, event: StepEvent<'_>) {
+        match event {
+            StepEvent::AlreadyDone(check) => self.events.push(format!("{} already done: {}", step + 1, check)),
+            StepEvent::CheckIgnored(check) => self.events.push(format!("{} check ignored: {}", step + 1, check)),
+            StepEvent::Skipped(reason) => self.events.push(format!("{} skipped: {}", step + 1, reason)),
+            StepEvent::FallingBack(fallback) => self.events.push(format!("{} fallback: {}", step + 1, fallback)),
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn executor_follows_conditions_fallbacks_and_piped_output() {
+    let steps = [
+        PlanStep::new("apt-get install -y nginx").with_when("! command -v nginx"),
+        PlanStep::new("systemctl start nginx").with_on_failure("journalctl -u nginx"),
+        PlanStep::new("ls -S /var/log | head -n1"),
+        PlanStep::new("gzip {{prev.stdout}}"),
+        PlanStep::new("false"),
+        PlanStep::new("cat {{prev.stdout}}"),
+    ];
+    let mut executor = ScriptedSteps {
+        conditions: vec![("! command -v nginx", Some(false))],
+        results: vec![
+            ("systemctl start nginx", StepOutcome::Failed),
+            ("ls -S /var/log | head -n1", StepOutcome::Succeeded { stdout: Some("/var/log/syslog\n".into()) }),
+            ("false", StepOutcome::Failed),
+        ],
+        ..Default::default()
+    };
+
+    let outcomes = execute(&steps, &mut executor).await.unwrap();
+
+    assert_eq!(
+        executor.ran,
+        [
+            "check ! command -v nginx",
+            "systemctl start nginx",
+            "journalctl -u nginx",
+            "ls -S /var/log | head -n1",
+            "gzip '/var/log/syslog'",
+            "false",
+        ]
+    );
+    assert_eq!(
+        executor.events,
+        [
+            "1 skipped: condition not met: ! command -v nginx",
+            "2 fallback: journalctl -u nginx",
+            "6 skipped: This step uses {{prev.stdout}} but the previous step did not succeed with captured output.",
+        ]
+    );
+    assert_eq!(outcomes[0], StepOutcome::NotRun);
+    assert_eq!(outcomes[1], StepOutcome::Succeeded { stdout: None }, "the fallback stands in");
+    assert_eq!(outcomes[4], StepOutcome::Failed);
+}
+
+#[tokio::test]
+async fn steps_already_done_are_skipped_by_their_check() {
+    let steps = [
+        PlanStep::new("apt-get install -y nginx").with_check("dpkg -s nginx"),
+        PlanStep::new("mkdir -p /srv/app").with_check("test -d /srv/app").with_when("true"),
+        PlanStep::new("useradd app").with_check("id app"),
+        PlanStep::new("touch /srv/app/.ready").with_check("rm -f /srv/app/.ready; false"),
+    ];
+    let mut executor = ScriptedSteps {
+        conditions: vec![("dpkg -s nginx", Some(true)), ("test -d /srv/app", Some(false)), ("true", Some(true))],
+        ..Default::default()
+    };
+
+    let outcomes = execute(&steps, &mut executor).await.unwrap();
+
+    assert_eq!(
+        executor.ran,
+        [
+            "check dpkg -s nginx",
+            "check test -d /srv/app",
+            "check true",
+            "mkdir -p /srv/app",
+            "check id app",
+            "useradd app",
+            "touch /srv/app/.ready",
+        ],
+        "an unchecked, failing or ignored check runs the step"
+    );
+    assert_eq!(
+        executor.events,
+        ["1 already done: dpkg -s nginx", "4 check ignored: rm -f /srv/app/.ready; false"]
+    );
+    assert_eq!(outcomes[0], StepOutcome::Succeeded { stdout: None });
+    assert_eq!(PlanStep::new("a").with_check("b").to_string(), "a (done if: b)");
+}
+
+#[test]
+fn safety_service_checks_every_step_and_branch() {
+    let steps = [
+        PlanStep::new("df -h"),
+        PlanStep::new("ls /dev").with_on_failure("mkfs.ext4 /dev/sdb1"),
+    ];
+    let safety = SafetyService::new();
+    let checks = safety.checks(&steps);
+
+    assert!(checks[0].passed);
+    assert!(!checks[1].passed);
+    assert_eq!(checks[1].check_type, "step 2: Contains 'mkfs' which can format disks.");
+    let plan = CommandPlan {
+        id: "disk".into(),
+        description: "check disks".into(),
+        steps: steps.to_vec(),
+        safety_checks: checks,
+    };
+    assert!(safety.validate(&plan).is_err());
+    assert_eq!(
+        SafetyService::new().with_ultra_safe(true).tier("sudo ls"),
+        application::safety_service::RiskTier::Blocked
+    );
+}
+
+#[tokio::test]
+async fn llm_planner_builds_plans_with_safety_checks() {
+    isolate_home();
+    let mock = MockOllama::start(
+        r#"["lsblk", {"command": "sudo mkfs.ext4 /dev/sdb1", "when": "lsblk /dev/sdb1", "done_if": "blkid /dev/sdb1"}]"#,
+    )
+    .await;
+    let planner = LlmCommandPlanner::new(mock.client()).with_environment("OS: debian");
+
+    let plan = planner.plan_command("  format the new disk ").await.unwrap();
+
+    assert_eq!(plan.description, "format the new disk");
+    assert_eq!(plan.steps[1].when.as_deref(), Some("lsblk /dev/sdb1"));
+    assert_eq!(plan.steps[1].check.as_deref(), Some("blkid /dev/sdb1"));
+    assert_eq!(
+        plan.safety_checks.iter().map(|c| c.passed).collect::<Vec<_>>(),
+        [true, false]
+    );
+    assert!(SafetyService::new().validate(&plan).is_err());
+    assert!(mock.chat_prompts().await[0].contains("Environment: OS: debian."));
+
+    let id = plan.id.clone();
+    let fixed = planner.with_steps(plan, vec![PlanStep::new("lsblk")]);
+    assert_eq!(fixed.id, id, "the same task keeps its id");
+    assert!(fixed.safety_checks.iter().all(|c| c.passed));
+}
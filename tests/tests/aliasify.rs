@@ -0,0 +1,120 @@
+use application::alias_service::{
+    append_aliases, defined_names, frequent_commands, parse_proposals, rc_file, source_line,
+    AliasKind, AliasProposal,
+};
+use infrastructure::audit_log::{AuditEntry, AuditLog};
+use presentation::cli::CliApp;
+use shared::paths;
+use std::path::Path;
+use tests::mock_ollama::MockOllama;
+use tests::{isolate_home, scratch_dir};
+
+fn entry(command: &str, outcome: &str) -> AuditEntry {
+    AuditEntry {
+        outcome: outcome.to_string(),
+        ..AuditEntry::new("command", "", command)
+    }
+}
+
+#[test]
+fn frequent_successful_commands_are_counted() {
+    let mut entries = Vec::new();
+    for _ in 0..3 {
+        entries.push(entry("git status -sb", "succeeded"));
+        entries.push(entry("  docker compose up -d ", "succeeded"));
+        entries.push(entry("ls", "succeeded"));
+    }
+    entries.push(entry("git status -sb", "succeeded"));
+    entries.push(entry("docker compose up -d", "failed"));
+    entries.push(entry("cargo test -p tests", "succeeded"));
+
+    let found: Vec<(String, usize)> = frequent_commands(&entries, 3)
+        .into_iter()
+        .map(|c| (c.command, c.count))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("git status -sb".to_string(), 4),
+            ("docker compose up -d".to_string(), 3),
+        ],
+        "failed runs and single words don't count"
+    );
+}
+
+#[test]
+fn proposals_are_checked_and_rendered() {
+    let reply = r#"Here you go:
+[
+  {"name": "gs", "kind": "alias", "doc": "Short git status", "body": "git status -sb"},
+  {"name": "dlogs", "kind": "function", "doc": "Follow a service's logs\nsecond line", "body": "docker compose logs -f \"$1\""},
+  {"name": "bad name", "body": "true"},
+  {"name": "gs", "body": "git status"},
+  {"name": "up", "body": "docker compose up -d"},
+  {"name": "empty", "body": "  "}
+]"#;
+    let proposals = parse_proposals(reply, &["up".to_string()]);
+    let names: Vec<&str> = proposals.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["gs", "dlogs"]);
+    assert_eq!(proposals[1].kind, AliasKind::Function);
+
+    let quoted = AliasProposal {
+        name: "hello".to_string(),
+        kind: AliasKind::Alias,
+        doc: String::new(),
+        body: "echo 'hi there'".to_string(),
+    };
+    assert_eq!(quoted.render(), "alias hello='echo '\\''hi there'\\'''\n");
+    assert_eq!(
+        proposals[1].render(),
+        "# Follow a service's logs\ndlogs() {\n    docker compose logs -f \"$1\"\n}\n"
+    );
+
+    let dir = scratch_dir("aliasify_file");
+    let path = dir.join("config").join("aliases.sh");
+    append_aliases(&path, &proposals[..1]).unwrap();
+    append_aliases(&path, &proposals[1..]).unwrap();
+    let script = std::fs::read_to_string(&path).unwrap();
+    assert!(script.starts_with("# Aliases and functions written by `vibe_cli aliasify`."));
+    assert_eq!(script.matches("vibe_cli aliasify").count(), 1, "one header");
+    assert_eq!(defined_names(&script), ["gs", "dlogs"]);
+    assert_eq!(defined_names("function deploy {\n  make deploy\n}\n"), ["deploy"]);
+
+    assert_eq!(rc_file("/usr/bin/zsh", Path::new("/home/u")), Some(Path::new("/home/u/.zshrc").to_path_buf()));
+    assert_eq!(rc_file("/usr/bin/fish", Path::new("/home/u")), None);
+    assert_eq!(
+        source_line(Path::new("/home/u/.config/vibe_cli/aliases.sh")),
+        "[ -f '/home/u/.config/vibe_cli/aliases.sh' ] && . '/home/u/.config/vibe_cli/aliases.sh'"
+    );
+}
+
+#[tokio::test]
+async fn aliases_are_proposed_from_every_projects_audit_log() {
+    isolate_home();
+    let mock = MockOllama::start(
+        r#"[{"name": "kpods", "kind": "alias", "doc": "List pods", "body": "kubectl get pods -A"},
+            {"name": "ls", "kind": "alias", "doc": "Shadows ls", "body": "ls -la"},
+            {"name": "gs", "kind": "alias", "doc": "Taken", "body": "git status"}]"#,
+    )
+    .await;
+    for (project, command) in [("one", "kubectl get pods -A"), ("two", "kubectl get pods -A")] {
+        let log = AuditLog::new(paths::data_dir().join(format!("{}_audit.jsonl", project)));
+        for _ in 0..2 {
+            log.append(&entry(command, "succeeded")).unwrap();
+        }
+    }
+    let dir = scratch_dir("aliasify_propose");
+    let aliases = dir.join("aliases.sh");
+    std::fs::write(&aliases, "alias gs='git status'\n").unwrap();
+
+    let app = CliApp::new().with_client(mock.client());
+    let proposals = app.propose_aliases(&aliases, 4).await.unwrap();
+    let names: Vec<&str> = proposals.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["kpods"], "installed commands and existing aliases are never shadowed");
+    let prompt = mock.chat_prompts().await.pop().unwrap();
+    assert!(prompt.contains("4x  kubectl get pods -A"));
+    assert!(prompt.contains("Do not use these names: gs."));
+
+    assert!(app.propose_aliases(&aliases, 5).await.unwrap().is_empty());
+    assert_eq!(mock.chat_prompts().await.len(), 1, "nothing frequent enough, no model call");
+}
@@ -0,0 +1,56 @@
+use infrastructure::allowlist::{AllowScope, Allowlist};
+use tests::scratch_dir;
+
+fn allowlist(name: &str, key: &[u8], project: &str) -> Allowlist {
+    Allowlist::new(scratch_dir(name).join("allowlist.json"), key.to_vec(), project)
+}
+
+#[test]
+fn entries_allow_exact_commands_for_their_scope_until_revoked() {
+    let list = allowlist("allowlist_scope", b"0123456789abcdef", "proj");
+    assert!(list.entries().unwrap().is_empty());
+
+    let project = list.add("cargo test", AllowScope::Project).unwrap();
+    let scoped = list
+        .add("git pull", AllowScope::Session("work".to_string()))
+        .unwrap();
+    assert_eq!(project.scope(), AllowScope::Project);
+    assert_eq!(scoped.scope(), AllowScope::Session("work".to_string()));
+
+    assert!(list.allowing("cargo test", None).unwrap().is_some());
+    assert!(list.allowing("cargo test", Some("home")).unwrap().is_some());
+    assert!(list.allowing("cargo test --release", None).unwrap().is_none(), "exact match only");
+    assert!(list.allowing("git pull", Some("work")).unwrap().is_some());
+    assert!(list.allowing("git pull", Some("home")).unwrap().is_none());
+    assert!(list.allowing("git pull", None).unwrap().is_none());
+
+    assert_eq!(list.revoke(project.id()).unwrap(), 1);
+    assert!(list.allowing("cargo test", None).unwrap().is_none());
+    assert_eq!(list.revoke("zzzz").unwrap(), 0);
+    assert!(list.revoke(" ").is_err());
+    assert_eq!(list.clear().unwrap(), 1);
+    assert!(list.entries().unwrap().is_empty());
+}
+
+#[test]
+fn edited_or_foreign_entries_are_ignored() {
+    let dir = scratch_dir("allowlist_tamper");
+    let path = dir.join("allowlist.json");
+    let key = b"0123456789abcdef".to_vec();
+    let list = Allowlist::new(&path, key.clone(), "proj");
+    list.add("ls -la", AllowScope::Project).unwrap();
+
+    let edited = std::fs::read_to_string(&path)
+        .unwrap()
+        .replace("ls -la", "rm -rf ~");
+    std::fs::write(&path, edited).unwrap();
+    assert!(list.allowing("rm -rf ~", None).unwrap().is_none());
+    assert!(list.entries().unwrap().is_empty());
+
+    list.add("ls -la", AllowScope::Project).unwrap();
+    let other_project = Allowlist::new(&path, key, "other");
+    assert!(other_project.allowing("ls -la", None).unwrap().is_none());
+    let other_key = Allowlist::new(&path, b"fedcba9876543210".to_vec(), "proj");
+    assert!(other_key.allowing("ls -la", None).unwrap().is_none());
+    assert!(list.allowing("ls -la", None).unwrap().is_some());
+}
@@ -0,0 +1,41 @@
+use application::ask_service::{looks_like_question, AskService};
+use tests::isolate_home;
+use tests::mock_ollama::MockOllama;
+
+#[test]
+fn questions_are_told_apart_from_requests_for_commands() {
+    for question in [
+        "what does errno 111 mean",
+        "Why is my swap full?",
+        "what's the difference between hard and soft links",
+        "how does DNS resolution work",
+        "is ext4 journaled?",
+    ] {
+        assert!(looks_like_question(question), "{question}");
+    }
+    for request in [
+        "show the five largest folders",
+        "how do I free up disk space?",
+        "list open ports",
+        "compress logs older than a week",
+        "",
+    ] {
+        assert!(!looks_like_question(request), "{request}");
+    }
+}
+
+#[tokio::test]
+async fn ask_returns_the_prose_answer() {
+    isolate_home();
+    let mock = MockOllama::start("  ECONNREFUSED: nothing is listening on that port.\n").await;
+
+    let answer = AskService::new(mock.client())
+        .answer("what does errno 111 mean", "Linux")
+        .await
+        .unwrap();
+
+    assert_eq!(answer, "ECONNREFUSED: nothing is listening on that port.");
+    let prompts = mock.chat_prompts().await;
+    assert!(prompts[0].contains("what does errno 111 mean"));
+    assert!(prompts[0].contains("Linux"));
+}
@@ -0,0 +1,143 @@
+use infrastructure::audit_log::{export, AuditEntry, AuditLog, ExportFormat, Provenance};
+use presentation::cli::CliApp;
+use presentation::runner::{describe_source, own_command};
+use tests::mock_ollama::MockOllama;
+use tests::{isolate_home, scratch_dir};
+
+fn step(run: &str, task: &str, command: &str, outcome: &str, timestamp: u64) -> AuditEntry {
+    AuditEntry {
+        timestamp,
+        verdict: "mutating".to_string(),
+        outcome: outcome.to_string(),
+        exit_code: Some(if outcome == "succeeded" { 0 } else { 1 }),
+        run: Some(run.to_string()),
+        ..AuditEntry::new("agent", task, command)
+    }
+}
+
+#[test]
+fn agent_runs_are_summarised_from_the_log() {
+    let log = AuditLog::new(scratch_dir("audit_runs").join("audit.jsonl"));
+    assert!(log.entries().unwrap().is_empty());
+
+    let entries = [
+        step("a", "set up nginx", "apt-get install -y nginx", "succeeded", 10),
+        AuditEntry::new("command", "disk usage", "df -h"),
+        step("b", "break things", "false", "failed", 20),
+        step("a", "set up nginx", "ufw allow 'Nginx Full'", "cancelled", 11),
+        step("a", "set up nginx", "ufw enable", "succeeded", 12),
+        step("c", "add swap", "fallocate -l 2G /swapfile", "succeeded", 30),
+    ];
+    for entry in &entries {
+        log.append(entry).unwrap();
+    }
+    assert_eq!(log.entries().unwrap(), entries);
+
+    let runs = log.recent_agent_runs(5).unwrap();
+    let summaries: Vec<String> = runs.iter().map(|run| run.summary()).collect();
+    assert_eq!(
+        summaries,
+        [
+            "\"set up nginx\": apt-get install -y nginx; ufw enable",
+            "\"add swap\": fallocate -l 2G /swapfile",
+        ],
+        "runs with nothing done are left out"
+    );
+    assert_eq!(log.recent_agent_runs(1).unwrap()[0].task, "add swap");
+}
+
+#[tokio::test]
+async fn planning_is_told_about_earlier_runs_in_the_project() {
+    isolate_home();
+    AuditLog::for_project()
+        .append(&step("a", "set up nginx", "apt-get install -y nginx", "succeeded", 10))
+        .unwrap();
+    let mock = MockOllama::start("[\"systemctl reload nginx\"]").await;
+    let app = CliApp::new().with_client(mock.client());
+
+    app.generate_agent_plan("serve my site over https").await.unwrap();
+
+    let prompts = mock.chat_prompts().await;
+    assert!(prompts[0].contains("Earlier agent runs in this project"));
+    assert!(prompts[0].contains("- \"set up nginx\": apt-get install -y nginx"));
+}
+
+#[test]
+fn entries_export_as_jsonl_and_csv() {
+    let entries = vec![
+        ("web".to_string(), step("a", "set up nginx", "apt-get install -y nginx", "succeeded", 10)),
+        ("web".to_string(), AuditEntry {
+            outcome: "cancelled".to_string(),
+            provenance: "cache".to_string(),
+            ..AuditEntry::new("command", "say \"hi\", twice", "echo hi, hi")
+        }),
+    ];
+
+    let jsonl = export(&entries, ExportFormat::Jsonl, true).unwrap();
+    let first: serde_json::Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+    assert_eq!(first["project"], "web");
+    assert_eq!(first["prompt"], "set up nginx");
+    assert_eq!(first["approval"], "approved");
+    assert_eq!(first["exit_code"], 0);
+
+    let csv = export(&entries, ExportFormat::Csv, true).unwrap();
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        rows[0],
+        "project,timestamp,mode,prompt,command,verdict,approval,outcome,exit_code,run,provenance"
+    );
+    assert!(rows[2].ends_with(",command,\"say \"\"hi\"\", twice\",\"echo hi, hi\",,declined,cancelled,,,cache"));
+
+    let private = export(&entries, ExportFormat::Jsonl, false).unwrap();
+    assert!(!private.contains("set up nginx"));
+    assert_eq!(ExportFormat::parse("CSV"), Some(ExportFormat::Csv));
+    assert_eq!(ExportFormat::parse("xml"), None);
+}
+
+#[test]
+fn entries_record_where_their_command_came_from() {
+    let path = scratch_dir("audit_provenance").join("audit.jsonl");
+    let log = AuditLog::new(&path);
+    log.append(&AuditEntry {
+        provenance: Provenance::Cache { saved_at: 0 }.label().to_string(),
+        ..AuditEntry::new("command", "check disk space", "df -h")
+    })
+    .unwrap();
+    let mut old = std::fs::read_to_string(&path).unwrap();
+    old.push_str("{\"timestamp\":1,\"mode\":\"command\",\"prompt\":\"\",\"command\":\"ls\",\"verdict\":\"\",\"outcome\":\"\"}\n");
+    std::fs::write(&path, old).unwrap();
+
+    let entries = log.entries().unwrap();
+    assert_eq!(entries[0].provenance, "cache");
+    assert_eq!(entries[1].provenance, "", "entries from before provenance still read");
+
+    let labels: Vec<&str> = [
+        Provenance::Model,
+        Provenance::Cache { saved_at: 0 },
+        Provenance::Rule("disk-usage".to_string()),
+        Provenance::UserEdited,
+    ]
+    .iter()
+    .map(|p| p.label())
+    .collect();
+    assert_eq!(labels, ["model", "cache", "rule", "user-edited"]);
+}
+
+#[test]
+fn the_source_of_a_command_is_described_at_confirmation() {
+    let day = 86_400;
+    assert_eq!(describe_source(&Provenance::Model, 0), "Source: model (generated just now)");
+    assert_eq!(
+        describe_source(&Provenance::Cache { saved_at: day }, 95 * day),
+        "Source: cache (saved 94 days ago)"
+    );
+    assert_eq!(
+        describe_source(&Provenance::Rule("disk-usage".to_string()), 0),
+        "Source: rule (disk-usage)"
+    );
+    assert_eq!(describe_source(&Provenance::UserEdited, 0), "Source: user-edited (as you typed it)");
+
+    assert_eq!(own_command("$ du -sh ."), Some("du -sh ."));
+    assert_eq!(own_command("use du instead"), None);
+    assert_eq!(own_command("$ "), None);
+}
@@ -0,0 +1,55 @@
+use infrastructure::backoff::Backoff;
+use std::time::Duration;
+use tests::mock_ollama::MockOllama;
+
+fn quick(max_wait_ms: u64) -> Backoff {
+    Backoff {
+        initial: Duration::from_millis(10),
+        max_delay: Duration::from_millis(40),
+        max_wait: Duration::from_millis(max_wait_ms),
+    }
+}
+
+#[test]
+fn delays_double_up_to_the_cap_with_jitter() {
+    let backoff = Backoff {
+        initial: Duration::from_secs(1),
+        max_delay: Duration::from_secs(15),
+        max_wait: Duration::from_secs(60),
+    };
+    assert_eq!(backoff.delay(0, 0.0), Duration::from_millis(500));
+    assert_eq!(backoff.delay(3, 0.999_999).as_secs(), 7);
+    assert_eq!(backoff.delay(10, 1.0), Duration::from_secs(15));
+    assert_eq!(backoff.delay(u32::MAX, 0.0), Duration::from_millis(7500));
+}
+
+#[tokio::test]
+async fn busy_responses_are_retried_until_the_model_answers() {
+    let mock = MockOllama::start("ls -la").await;
+    mock.busy_chat(503, 1).await;
+    mock.busy_chat(429, 1).await;
+
+    let reply = mock
+        .client()
+        .with_backoff(quick(5_000))
+        .generate_response("list files")
+        .await
+        .unwrap();
+    assert_eq!(reply, "ls -la");
+    assert_eq!(mock.chat_prompts().await.len(), 3);
+}
+
+#[tokio::test]
+async fn waiting_stops_at_the_configured_maximum() {
+    let mock = MockOllama::start("ls -la").await;
+    mock.busy_chat(429, 1_000).await;
+
+    let err = mock
+        .client()
+        .with_backoff(quick(100))
+        .generate_response("list files")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("still busy"), "{}", err);
+    assert!(mock.chat_prompts().await.len() > 1);
+}
@@ -0,0 +1,63 @@
+use application::cache_check::{missing_paths, problems, reuse, scope, Problem, Reuse};
+use presentation::cli::CliApp;
+use shared::cache::{now_secs, CacheEntry};
+use tests::{isolate_home, scratch_dir};
+
+#[test]
+fn paths_a_command_reads_are_checked_but_not_those_it_writes() {
+    let dir = scratch_dir("cache_check_paths");
+    std::fs::create_dir_all(dir.join("logs")).unwrap();
+    std::fs::write(dir.join("logs/app.log"), "").unwrap();
+
+    assert_eq!(missing_paths("tail -n 50 logs/app.log ./notes.txt", &dir), ["./notes.txt"]);
+    assert_eq!(missing_paths("grep -c ERROR logs/old.log > logs/count.txt", &dir), ["logs/old.log"]);
+    assert!(missing_paths("mkdir -p ./build/out && cp logs/app.log ./backup/app.log", &dir).is_empty());
+    assert!(missing_paths("tar -czf ./archive.tgz logs && curl -o ./page.html https://example.com/x", &dir).is_empty());
+    assert!(missing_paths("sed -i 's/a/b/g' logs/app.log", &dir).is_empty(), "a sed script isn't a path");
+    assert_eq!(missing_paths("cat /nonexistent/vibe/file", &dir), ["/nonexistent/vibe/file"]);
+    assert!(missing_paths("cd /nonexistent && cat ./file", &dir).is_empty(), "after a cd the directory is unknown");
+}
+
+#[test]
+fn missing_programs_regenerate_other_problems_warn() {
+    let dir = scratch_dir("cache_check_problems");
+    let here = scope(&dir);
+
+    let found = problems("ls ./gone", Some(&here), &dir, Vec::new());
+    assert_eq!(found, [Problem::MissingPath("./gone".to_string())]);
+    assert_eq!(reuse(&found), Reuse::Warn);
+
+    let found = problems("ls", Some("/elsewhere/project"), &dir, Vec::new());
+    assert_eq!(found, [Problem::OtherProject { cached: "/elsewhere/project".to_string() }]);
+    assert_eq!(found[0].describe(), "it was generated in /elsewhere/project");
+    assert_eq!(reuse(&found), Reuse::Warn);
+
+    let found = problems("exa -l", None, &dir, vec!["exa".to_string()]);
+    assert_eq!(found, [Problem::MissingProgram("exa".to_string())]);
+    assert_eq!(reuse(&found), Reuse::Regenerate);
+
+    assert_eq!(reuse(&problems("ls", None, &dir, Vec::new())), Reuse::Offer, "old entries have no scope");
+}
+
+#[test]
+fn cached_commands_are_checked_against_this_machine() {
+    isolate_home();
+    let app = CliApp::new().with_cache_path(scratch_dir("cache_check_app").join("command_cache.bin"));
+    let entry = |command: &str, scope: Option<&str>| CacheEntry {
+        key: "list things".to_string(),
+        value: command.to_string(),
+        created_at: now_secs(),
+        last_used: now_secs(),
+        scope: scope.map(str::to_string),
+    };
+
+    assert!(app.cache_problems(&entry("ls -la", None)).is_empty());
+    assert_eq!(
+        app.cache_problems(&entry("vibe-no-such-tool --all", None)),
+        [Problem::MissingProgram("vibe-no-such-tool".to_string())]
+    );
+    assert!(matches!(
+        app.cache_problems(&entry("ls", Some("/some/other/project")))[..],
+        [Problem::OtherProject { .. }]
+    ));
+}
@@ -0,0 +1,30 @@
+use infrastructure::cassette::Cassette;
+use infrastructure::ollama_client::OllamaClient;
+use presentation::cli::CliApp;
+use std::sync::Arc;
+use tests::mock_ollama::MockOllama;
+use tests::{isolate_home, scratch_dir};
+
+#[tokio::test]
+async fn recorded_session_replays_without_a_server() {
+    isolate_home();
+    let path = scratch_dir("cassette").join("session.jsonl");
+
+    let mock = MockOllama::start("ls -la").await;
+    let recording = Arc::new(Cassette::record(&path).unwrap());
+    let client = mock.client().with_cassette(recording);
+    let app = CliApp::new().with_client(client.clone());
+    let recorded = app.generate_command("list all files").await.unwrap();
+    let embedding = client.generate_embedding("disk usage").await.unwrap();
+    drop(mock);
+
+    // Nothing listens here; every answer must come from the cassette.
+    let offline = OllamaClient::with_base_url("http://127.0.0.1:9", "mock-model")
+        .with_cassette(Arc::new(Cassette::replay(&path).unwrap()));
+    let app = CliApp::new().with_client(offline.clone());
+
+    assert_eq!(app.generate_command("list all files").await.unwrap(), recorded);
+    assert_eq!(offline.generate_embedding("disk usage").await.unwrap(), embedding);
+    let err = app.generate_command("something never recorded").await.unwrap_err();
+    assert!(err.to_string().contains("no recorded chat interaction"));
+}
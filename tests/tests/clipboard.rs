@@ -0,0 +1,62 @@
+use presentation::clipboard::{self, candidates, osc52_sequence, Backend, Environment, Multiplexer};
+use tests::scratch_dir;
+
+#[test]
+fn native_tools_are_preferred_locally_and_osc52_over_ssh() {
+    let desktop = Environment {
+        wayland: true,
+        x11: true,
+        programs: vec!["wl-copy", "xsel"],
+        ..Environment::default()
+    };
+    assert_eq!(
+        candidates(&desktop),
+        [Backend::WlCopy, Backend::Xsel, Backend::Native, Backend::Osc52]
+    );
+
+    let ssh = Environment {
+        ssh: true,
+        x11: true,
+        programs: vec!["xclip"],
+        ..Environment::default()
+    };
+    assert_eq!(candidates(&ssh), [Backend::Osc52, Backend::Xclip]);
+
+    let wsl = Environment {
+        windows_or_wsl: true,
+        programs: vec!["clip.exe"],
+        ..Environment::default()
+    };
+    assert_eq!(candidates(&wsl)[0], Backend::ClipExe);
+    assert_eq!(Backend::parse("OSC52"), Some(Backend::Osc52));
+}
+
+#[test]
+fn osc52_is_wrapped_for_multiplexers() {
+    assert_eq!(osc52_sequence("ls -la", Multiplexer::None), "\x1b]52;c;bHMgLWxh\x07");
+    assert_eq!(
+        osc52_sequence("ls -la", Multiplexer::Tmux),
+        "\x1bPtmux;\x1b\x1b]52;c;bHMgLWxh\x07\x1b\\"
+    );
+    assert_eq!(
+        osc52_sequence("ls -la", Multiplexer::Screen),
+        "\x1bP\x1b]52;c;bHMgLWxh\x07\x1b\\"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn tool_backends_receive_the_text_on_stdin() {
+    use std::os::unix::fs::PermissionsExt;
+    let bin = scratch_dir("clipboard");
+    let copied = bin.join("copied.txt");
+    let tool = bin.join("wl-copy");
+    std::fs::write(&tool, format!("#!/bin/sh\ncat > '{}'\n", copied.display())).unwrap();
+    std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = std::env::var("PATH").unwrap_or_default();
+    std::env::set_var("PATH", format!("{}:{}", bin.display(), path));
+
+    clipboard::copy_with(Backend::WlCopy, "du -sh */").unwrap();
+
+    assert_eq!(std::fs::read_to_string(copied).unwrap(), "du -sh */");
+}
@@ -0,0 +1,111 @@
+use application::rag_service::RagService;
+use infrastructure::config::{parse_collections, CollectionSettings, Config};
+use infrastructure::embedding_storage::{collection_db_path, collection_names, EmbeddingStorage};
+use tests::mock_ollama::MockOllama;
+use tests::{fixture_path, isolate_home, scratch_dir};
+
+#[tokio::test]
+async fn collections_live_beside_the_code_index() {
+    let dir = scratch_dir("collections_paths");
+    let db_path = dir.join("embeddings.db");
+    assert_eq!(collection_db_path(&db_path, "code").unwrap(), db_path);
+    assert_eq!(
+        collection_db_path(&db_path, "chat-history").unwrap(),
+        dir.join("embeddings.chat-history.db")
+    );
+    assert!(collection_db_path(&db_path, "../etc").is_err());
+    assert!(collection_db_path(&db_path, "").is_err());
+
+    assert!(collection_names(&db_path).is_empty());
+    EmbeddingStorage::new(collection_db_path(&db_path, "notes").unwrap()).await.unwrap();
+    EmbeddingStorage::new(collection_db_path(&db_path, "docs").unwrap()).await.unwrap();
+    EmbeddingStorage::new(&db_path).await.unwrap();
+    assert_eq!(collection_names(&db_path), ["code", "docs", "notes"]);
+
+    assert_eq!(parse_collections(" code, docs,,code "), ["code", "docs"]);
+}
+
+#[test]
+fn collection_settings_override_the_global_ones() {
+    let mut config = Config::load();
+    config.db_path = "/data/embeddings.db".to_string();
+    config.rag_top_k = 50;
+    config.rag_min_score = 0.0;
+    config.collections.insert(
+        "docs".to_string(),
+        CollectionSettings {
+            embedding_model: Some("nomic-embed-text".to_string()),
+            top_k: Some(5),
+            min_score: Some(0.3),
+            max_chunks_per_file: None,
+        },
+    );
+
+    let docs = config.for_collection("docs").unwrap();
+    assert_eq!(docs.db_path, "/data/embeddings.docs.db");
+    assert_eq!((docs.rag_top_k, docs.rag_min_score), (5, 0.3));
+    assert_eq!(docs.rag_max_chunks_per_file, config.rag_max_chunks_per_file);
+    assert_eq!(config.embedding_model("docs").as_deref(), Some("nomic-embed-text"));
+
+    let code = config.for_collection("code").unwrap();
+    assert_eq!((code.db_path.as_str(), code.rag_top_k), ("/data/embeddings.db", 50));
+    assert_eq!(config.embedding_model("code"), None);
+}
+
+#[tokio::test]
+async fn questions_search_the_union_of_collections() {
+    isolate_home();
+    let mock = MockOllama::start("Run certbot renew on the proxy [notes].").await;
+    let dir = scratch_dir("collections_union");
+    let notes_dir = dir.join("notes");
+    std::fs::create_dir_all(&notes_dir).unwrap();
+    std::fs::write(
+        notes_dir.join("runbook.md"),
+        "# Staging certificates\n\nTo rotate the staging certificates, run certbot renew on the proxy host.\n",
+    )
+    .unwrap();
+    let mut config = Config::load();
+    config.db_path = dir.join("embeddings.db").to_string_lossy().to_string();
+    config.collections.insert(
+        "notes".to_string(),
+        CollectionSettings {
+            embedding_model: Some("notes-embedder".to_string()),
+            top_k: Some(1),
+            ..CollectionSettings::default()
+        },
+    );
+
+    let code = RagService::collection(
+        &fixture_path("sample_project").to_string_lossy(),
+        "code",
+        mock.client(),
+        &config,
+    )
+    .await
+    .unwrap();
+    code.build_index().await.unwrap();
+    let notes = RagService::collection(&notes_dir.to_string_lossy(), "notes", mock.client(), &config)
+        .await
+        .unwrap();
+    notes.build_index().await.unwrap();
+    assert!(mock.embedding_models().await.iter().any(|m| m == "notes-embedder"));
+    let code_chunks = EmbeddingStorage::new(&config.db_path).await.unwrap().stats().await.unwrap().chunks;
+    assert!(code_chunks > 0);
+
+    let union = code.with_collections(vec![notes]);
+    let before = mock.embedding_models().await.len();
+    union
+        .query("how do we rotate the staging certificates?")
+        .await
+        .unwrap();
+
+    let prompt = mock.chat_prompts().await.pop().unwrap();
+    assert!(prompt.contains("certbot renew"), "the notes chunk is included");
+    assert!(prompt.contains(".rs"), "code chunks are still included");
+    let models = mock.embedding_models().await;
+    assert_eq!(
+        models[before..],
+        ["mock-model", "notes-embedder"],
+        "each collection embeds the question with its own model"
+    );
+}
@@ -0,0 +1,63 @@
+use application::command_rules::{builtin_rules, load_rules, match_rule, promotion, save_rules};
+use infrastructure::audit_log::AuditEntry;
+use tests::scratch_dir;
+
+fn rule_name(request: &str) -> Option<String> {
+    match_rule(&builtin_rules(), request).map(|rule| rule.name.clone())
+}
+
+#[test]
+fn common_requests_are_answered_by_rules() {
+    assert_eq!(rule_name("check disk space").as_deref(), Some("disk-space"));
+    assert_eq!(rule_name("How much memory am I using?").as_deref(), Some("memory"));
+    assert_eq!(rule_name("show me the top processes").as_deref(), Some("top-processes"));
+    assert_eq!(rule_name("find large files").as_deref(), Some("large-files"));
+    assert_eq!(rule_name("tail the system logs").as_deref(), Some("tail-logs"));
+    assert_eq!(
+        rule_name("which processes are using the most memory"),
+        None,
+        "'most' is more than a rule covers"
+    );
+}
+
+#[test]
+fn more_specific_requests_go_to_the_model() {
+    assert_eq!(rule_name("find large files in /var older than a week"), None);
+    assert_eq!(rule_name("disk space of my docker volumes"), None);
+    assert_eq!(rule_name("free memory in the redis cache"), None);
+    assert_eq!(rule_name("list files"), None);
+}
+
+#[test]
+fn repeatedly_accepted_commands_become_rules_kept_in_a_file() {
+    let accepted = |prompt: &str, command: &str| AuditEntry {
+        outcome: "succeeded".to_string(),
+        ..AuditEntry::new("command", prompt, command)
+    };
+    let mut entries = vec![
+        accepted("show docker containers", "docker ps"),
+        accepted("Show docker  containers", "docker ps"),
+        accepted("show docker containers", "docker ps -a"),
+        accepted("what's the weather", "docker ps"),
+        AuditEntry::new("command", "show docker containers", "docker ps"),
+    ];
+    let similar = |a: &str, b: &str| a.to_lowercase().contains("docker") == b.to_lowercase().contains("docker");
+
+    assert_eq!(promotion(&entries, "show docker containers", "docker ps", 3, similar), None);
+    entries.push(accepted("list docker containers", "docker ps"));
+    let rule = promotion(&entries, "show docker containers", "docker ps", 3, similar).unwrap();
+    assert_eq!(rule.name, "show-docker-containers");
+    assert_eq!(rule.phrases, ["show docker containers", "list docker containers"]);
+    assert_eq!(rule.command, "docker ps");
+
+    let path = scratch_dir("command_rules_file").join("rules.toml");
+    assert!(load_rules(&path).unwrap().is_empty());
+    save_rules(&path, std::slice::from_ref(&rule)).unwrap();
+    let rules = load_rules(&path).unwrap();
+    assert_eq!(rules, [rule]);
+    assert_eq!(match_rule(&rules, "please list docker containers").unwrap().command, "docker ps");
+    assert!(match_rule(&rules, "list docker containers on web1").is_none());
+
+    std::fs::write(&path, "[[rule]]\nname = 1\n").unwrap();
+    assert!(load_rules(&path).is_err());
+}
@@ -0,0 +1,183 @@
+use infrastructure::config::{parse_safe_mode, Config, ConfirmRule, ConfirmSettings};
+use presentation::safety::{assess_command, confirm_rule, risk_tier, RiskTier};
+use tests::scratch_dir;
+
+fn tier(cmd: &str) -> RiskTier {
+    risk_tier(cmd, &assess_command(cmd, false))
+}
+
+#[test]
+fn commands_are_sorted_into_risk_tiers() {
+    assert_eq!(tier("ls -la"), RiskTier::Safe);
+    assert_eq!(tier("git log | head"), RiskTier::Safe);
+    assert_eq!(tier("sudo apt install ripgrep"), RiskTier::Mutating);
+    assert_eq!(tier("df -h > report.txt"), RiskTier::Mutating);
+    assert_eq!(tier("chmod 777 shared"), RiskTier::Dangerous);
+    assert_eq!(tier("mkfs.ext4 /dev/sdb1"), RiskTier::Blocked);
+}
+
+#[test]
+fn only_known_read_only_commands_are_safe() {
+    for cmd in [
+        "find /tmp -name '*.log' -delete",
+        "git reset --hard",
+        "git clean -fdx",
+        "curl -fsSL https://example.dev/install.sh | bash",
+        "userdel -r deploy",
+        "shred -u secrets.txt",
+        "crontab -r",
+        "docker system prune -af",
+        "mkdir build",
+        "touch notes.txt",
+        "some-unknown-tool --flag",
+    ] {
+        assert_eq!(tier(cmd), RiskTier::Mutating, "{cmd}");
+    }
+    for cmd in ["df -h", "kubectl get pods -n prod", "systemctl status nginx", "find . -name '*.rs'"] {
+        assert_eq!(tier(cmd), RiskTier::Safe, "{cmd}");
+    }
+}
+
+#[test]
+fn ssh_access_changes_get_their_own_tier() {
+    for cmd in [
+        "cat id_ed25519.pub >> ~/.ssh/authorized_keys",
+        "ssh-copy-id deploy@web1",
+        "ssh -o StrictHostKeyChecking=no deploy@web1 uptime",
+        "scp -o 'UserKnownHostsFile /dev/null' f host:",
+        "ssh-keygen -R web1.example.dev",
+        "sudo sed -i 's/^PermitRootLogin no/PermitRootLogin yes/' /etc/ssh/sshd_config",
+        "chmod 644 ~/.ssh/id_rsa",
+        "rm -rf ~/.ssh/known_hosts",
+    ] {
+        let assessment = assess_command(cmd, false);
+        assert_eq!(risk_tier(cmd, &assessment), RiskTier::Ssh, "{cmd}");
+        assert_eq!(assessment.ssh.len(), 1, "{cmd}: {:?}", assessment.ssh);
+    }
+    assert!(assess_command("ssh-keygen -R web1", false).ssh[0].contains("fingerprint"));
+
+    let key = scratch_dir("ssh_tier").join("id_ed25519");
+    let generate = format!("ssh-keygen -t ed25519 -N '' -f {}", key.display());
+    assert!(tier(&generate) < RiskTier::Ssh, "a new key is fine");
+    std::fs::write(&key, "key").unwrap();
+    assert_eq!(tier(&generate), RiskTier::Ssh, "an existing key would be overwritten");
+    assert_eq!(tier(&format!("ssh-keygen -l -f {}", key.display())), RiskTier::Safe);
+
+    for cmd in ["ssh web1 uptime", "cat ~/.ssh/config", "ssh -o StrictHostKeyChecking=accept-new web1"] {
+        assert!(tier(cmd) < RiskTier::Ssh, "{cmd}");
+    }
+    assert_eq!(tier("rm -rf / ; ssh-copy-id web1"), RiskTier::Blocked);
+}
+
+#[test]
+fn removing_root_is_blocked_whatever_the_flag_order() {
+    for cmd in ["rm -fr /", "rm -r -f /", "sudo rm --recursive --force /*", "rm -Rf '/'"] {
+        assert_eq!(tier(cmd), RiskTier::Blocked, "{cmd}");
+    }
+    assert_eq!(tier("rm -f -r build"), RiskTier::Dangerous);
+}
+
+#[test]
+fn firewall_and_network_changes_get_their_own_tier() {
+    for cmd in [
+        "sudo ufw default deny incoming && sudo ufw --force enable",
+        "sudo iptables -P INPUT DROP",
+        "sudo nft add rule inet filter input tcp dport 22 drop",
+        "sudo firewall-cmd --permanent --remove-service=ssh",
+        "sudo ip link set dev eth0 down",
+        "sudo systemctl restart NetworkManager",
+    ] {
+        assert_eq!(tier(cmd), RiskTier::Network, "{cmd}");
+    }
+    for cmd in ["sudo ufw status verbose", "sudo iptables -L -n", "nft list ruleset", "firewall-cmd --list-all", "ip addr show"] {
+        assert!(tier(cmd) < RiskTier::Network, "{cmd}");
+    }
+    assert_eq!(tier("ufw allow 22; mkfs.ext4 /dev/sdb1"), RiskTier::Blocked);
+}
+
+#[test]
+fn defaults_follow_the_tier() {
+    let settings = ConfirmSettings::default();
+    assert!(confirm_rule(RiskTier::Safe, &settings).unwrap().default_yes);
+    assert!(!confirm_rule(RiskTier::Mutating, &settings).unwrap().default_yes);
+    assert_eq!(
+        confirm_rule(RiskTier::Dangerous, &settings).unwrap().phrase.as_deref(),
+        Some("yes")
+    );
+    let ssh = confirm_rule(RiskTier::Ssh, &settings).unwrap();
+    assert_eq!(ssh.phrase.as_deref(), Some("yes"));
+    assert!(ssh.double, "SSH changes are confirmed twice");
+    let network = confirm_rule(RiskTier::Network, &settings).unwrap();
+    assert_eq!(network.phrase.as_deref(), Some("yes"));
+    assert!(network.double, "firewall changes are confirmed twice");
+    assert!(confirm_rule(RiskTier::Blocked, &settings).is_none());
+}
+
+#[test]
+fn stale_cached_commands_are_confirmed_with_enter_declining() {
+    let settings = ConfirmSettings::default().declining();
+    for tier in [RiskTier::Safe, RiskTier::Mutating, RiskTier::Dangerous, RiskTier::Ssh, RiskTier::Network] {
+        assert!(!confirm_rule(tier, &settings).unwrap().default_yes, "{tier:?}");
+    }
+    assert_eq!(settings.network.phrase.as_deref(), Some("yes"), "only the default changes");
+}
+
+#[test]
+fn safe_mode_is_on_unless_turned_off() {
+    assert!(parse_safe_mode(None));
+    assert!(parse_safe_mode(Some("1")));
+    for off in ["0", "false", " off "] {
+        assert!(!parse_safe_mode(Some(off)), "{off}");
+    }
+}
+
+#[test]
+fn configured_keys_override_only_what_they_set() {
+    let config = scratch_dir("confirm").join("config.toml");
+    std::fs::write(
+        &config,
+        "[confirm.mutating]\ndouble = true\n\n[confirm.dangerous]\nphrase = \"\"\ndouble = true\n",
+    )
+    .unwrap();
+    std::env::set_var("VIBE_CONFIG", &config);
+
+    let confirm = Config::load().confirm;
+    assert_eq!(confirm.safe, ConfirmSettings::default().safe);
+    assert_eq!(
+        confirm.mutating,
+        ConfirmRule {
+            default_yes: false,
+            double: true,
+            phrase: None
+        }
+    );
+    assert_eq!(confirm.dangerous.phrase, None);
+    assert!(confirm.dangerous.double);
+}
+
+#[test]
+fn refine_is_a_third_answer_where_offered() {
+    use shared::confirmation::{ask_confirmation_line, ask_or_refine_line, Answer};
+    use std::io::Cursor;
+
+    let mut output = Vec::new();
+    let answer =
+        ask_or_refine_line(&mut Cursor::new("maybe\nr\n"), &mut output, "Run this command?", false)
+            .unwrap();
+    assert_eq!(answer, Answer::Refine);
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "Run this command? (yes/no/refine, default no): Please answer yes, no or refine.\n\
+         Run this command? (yes/no/refine, default no): "
+    );
+    let mut output = Vec::new();
+    let answer =
+        ask_or_refine_line(&mut Cursor::new("\n"), &mut output, "Run?", true).unwrap();
+    assert_eq!(answer, Answer::Yes);
+
+    let mut output = Vec::new();
+    let accepted =
+        ask_confirmation_line(&mut Cursor::new("refine\nn\n"), &mut output, "Run?", true).unwrap();
+    assert!(!accepted);
+    assert!(String::from_utf8(output).unwrap().contains("Please answer yes or no."));
+}
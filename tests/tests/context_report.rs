@@ -0,0 +1,70 @@
+use application::context_report::{chunk_label, ChunkScore, ContextReport, RetrievalStats};
+
+fn scored(label: &str, score: f32) -> ChunkScore {
+    ChunkScore {
+        label: label.to_string(),
+        score,
+    }
+}
+
+fn file_chunk(path: &str, offset: usize) -> String {
+    format!("FILE: {}\nOFFSET: {}\nfn body() {{}}\n", path, offset)
+}
+
+#[test]
+fn chunks_are_labelled_by_where_they_come_from() {
+    assert_eq!(chunk_label(&file_chunk("src/auth/mod.rs", 1200)), "src/auth/mod.rs @1200");
+    assert_eq!(chunk_label("FILE: README.md\n# App"), "README.md");
+    assert_eq!(chunk_label("CRATE DOCS: serde 1.0.210\nSerialize..."), "crate docs: serde 1.0.210");
+    assert_eq!(
+        chunk_label("DEPENDENCY VERSIONS (pinned by the project's lockfiles; use these versions):\nCargo.lock: tokio 1.40.0"),
+        "dependency versions (lockfiles)"
+    );
+    assert_eq!(chunk_label("DIRECTORY TREE:\nsrc/"), "directory tree");
+}
+
+#[test]
+fn reports_show_scores_budget_drops_and_misses() {
+    let mut stats = RetrievalStats {
+        candidates: 40,
+        min_score: 0.3,
+        picked: vec![scored("src/auth/mod.rs @0", 0.812), scored("src/lib.rs @0", 0.511)],
+        below_min_score: vec![scored("src/auth/session.rs @800", 0.291)],
+        not_picked: vec![scored("src/auth/mod.rs @2400", 0.7)],
+    };
+    stats.merge(RetrievalStats {
+        candidates: 10,
+        below_min_score: vec![scored("docs/auth.md @0", 0.295)],
+        ..RetrievalStats::default()
+    });
+    assert_eq!(stats.candidates, 50);
+    assert_eq!(stats.below_min_score[0].label, "docs/auth.md @0", "best misses first");
+
+    let assembled = vec![
+        "DIRECTORY TREE:\nsrc/".to_string(),
+        file_chunk("src/auth/mod.rs", 0),
+        file_chunk("src/lib.rs", 0),
+    ];
+    let report = ContextReport::new(stats, &assembled, &assembled[..2], 60);
+    assert_eq!(
+        report.included,
+        [
+            ("directory tree".to_string(), None),
+            ("src/auth/mod.rs @0".to_string(), Some(0.812)),
+        ]
+    );
+    assert_eq!(report.over_budget, [("src/lib.rs @0".to_string(), Some(0.511))]);
+
+    let text = report.render();
+    for expected in [
+        "retrieval: 50 candidate chunk(s) compared; 2 picked, 2 under the minimum score 0.30, 1 passed over",
+        "in the prompt: 2 chunk(s), ",
+        "  0.812  src/auth/mod.rs @0\n",
+        "  -    directory tree\n",
+        "dropped for the context budget: 1\n  0.511  src/lib.rs @0\n",
+        "passed over:\n  0.700  src/auth/mod.rs @2400\n",
+        "closest under the minimum score:\n  0.295  docs/auth.md @0\n  0.291  src/auth/session.rs @800\n",
+    ] {
+        assert!(text.contains(expected), "{expected:?} in\n{text}");
+    }
+}
@@ -0,0 +1,129 @@
+use application::rag_service::RagService;
+use infrastructure::config::Config;
+use infrastructure::crate_docs::{mentioned_crates, rust_dependencies, CrateDocs, Dependency};
+use std::path::Path;
+use tests::mock_ollama::MockOllama;
+use tests::{isolate_home, scratch_dir};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn write_workspace(root: &Path) {
+    std::fs::create_dir_all(root.join("app")).unwrap();
+    std::fs::write(
+        root.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"app\"]\n\n[workspace.dependencies]\nclap = { version = \"4.5\", features = [\"derive\"] }\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("app").join("Cargo.toml"),
+        "[package]\nname = \"app\"\n\n[dependencies]\nclap.workspace = true\nserde = \"1\"\ntree-sitter = \"^0.24\"\nlocal = { path = \"../local\" }\njson = { package = \"serde_json\", version = \"1\" }\n\n[target.'cfg(unix)'.dependencies]\nlibc = \"*\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("Cargo.lock"),
+        "version = 3\n\n[[package]]\nname = \"clap\"\nversion = \"4.5.20\"\n\n[[package]]\nname = \"clap\"\nversion = \"3.2.25\"\n\n[[package]]\nname = \"serde\"\nversion = \"1.0.210\"\n",
+    )
+    .unwrap();
+}
+
+fn dependency(name: &str, version: &str, locked: bool) -> Dependency {
+    Dependency {
+        name: name.to_string(),
+        version: version.to_string(),
+        locked,
+    }
+}
+
+#[test]
+fn dependencies_take_the_locked_version() {
+    let root = scratch_dir("crate_docs_deps");
+    write_workspace(&root);
+
+    assert_eq!(
+        rust_dependencies(&root),
+        [
+            dependency("clap", "4.5.20", true),
+            dependency("libc", "latest", false),
+            dependency("serde", "1.0.210", true),
+            dependency("serde_json", "1", false),
+            dependency("tree-sitter", "0.24", false),
+        ],
+        "path dependencies are the project's own; the highest locked version wins"
+    );
+    assert!(rust_dependencies(&root.join("app").join("src")).is_empty());
+
+    let dependencies = rust_dependencies(&root);
+    let named: Vec<&str> = mentioned_crates(
+        "how do I parse with tree_sitter and use clap::Parser derive (clap subcommands)?",
+        &dependencies,
+    )
+    .iter()
+    .map(|d| d.name.as_str())
+    .collect();
+    assert_eq!(named, ["tree-sitter", "clap"]);
+}
+
+#[tokio::test]
+async fn answers_include_the_docs_of_the_crates_they_name() {
+    isolate_home();
+    let mock = MockOllama::start("Derive Subcommand on an enum [clap docs].").await;
+    let docs = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/clap/4.5.20/clap/"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            "<nav>All items</nav><main><h1>Crate clap</h1><p>Derive <code>Subcommand</code> on an enum to declare subcommands.</p></main>",
+        ))
+        .expect(1)
+        .mount(&docs)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/crates/serde_json/1/readme"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            "<h1>Serde JSON</h1><p>Use serde_json::from_str to parse JSON text.</p>",
+        ))
+        .mount(&docs)
+        .await;
+
+    let root = scratch_dir("crate_docs_rag");
+    write_workspace(&root);
+    let mut config = Config::load();
+    config.db_path = root.join("embeddings.db").to_string_lossy().to_string();
+    let crate_docs = CrateDocs::new()
+        .with_base_urls(docs.uri(), docs.uri())
+        .with_cache_dir(root.join("cache"));
+    let service = RagService::new(
+        &root.to_string_lossy(),
+        &config.db_path.clone(),
+        mock.client(),
+        config,
+    )
+    .await
+    .unwrap()
+    .with_crate_docs(crate_docs);
+
+    for _ in 0..2 {
+        service
+            .query("how do I use clap derive subcommands here?")
+            .await
+            .unwrap();
+        let prompt = mock.chat_prompts().await.pop().unwrap();
+        assert!(prompt.contains(
+            "CRATE DOCS: clap 4.5.20\n# Crate clap\n\nDerive `Subcommand` on an enum to declare subcommands."
+        ));
+        assert!(!prompt.contains("All items"));
+    }
+    assert!(root.join("cache").join("clap-4.5.20.md").exists());
+
+    service.query("how does serde_json parse text?").await.unwrap();
+    let prompt = mock.chat_prompts().await.pop().unwrap();
+    assert!(
+        prompt.contains("CRATE DOCS: serde_json 1\n# Serde JSON"),
+        "the README stands in when docs.rs has no page"
+    );
+    assert!(!root.join("cache").join("serde_json-1.md").exists(), "only exact versions are cached");
+
+    let answer = service.query("what does tree-sitter offer?").await.unwrap();
+    assert!(answer.contains("Low-context answer"), "{answer}");
+    let prompt = mock.chat_prompts().await.pop().unwrap();
+    assert!(!prompt.contains("CRATE DOCS: tree-sitter"), "docs that can't be found are left out");
+}
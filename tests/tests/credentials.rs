@@ -0,0 +1,89 @@
+use application::credential_prompt::{is_placeholder, secret_slots, SecretSlot};
+
+fn rewritten(cmd: &str) -> (String, Vec<(String, String)>) {
+    let rewrite = secret_slots(cmd);
+    let slots = rewrite
+        .slots
+        .into_iter()
+        .map(|SecretSlot { var, label }| (var, label))
+        .collect();
+    (rewrite.command, slots)
+}
+
+fn slot(var: &str, label: &str) -> (String, String) {
+    (var.to_string(), label.to_string())
+}
+
+#[test]
+fn placeholders_are_told_from_real_values() {
+    for value in ["<password>", "'YOUR_TOKEN'", "***", "changeme", "your_password", "", "[secret]", "{{ api_key }}"] {
+        assert!(is_placeholder(value), "{value:?}");
+    }
+    for value in ["hunter2", "$DB_PASSWORD", "s3cr3t-Value", "admin"] {
+        assert!(!is_placeholder(value), "{value:?}");
+    }
+}
+
+#[test]
+fn secret_variables_and_flags_become_references() {
+    assert_eq!(
+        rewritten("PGPASSWORD=<password> psql -h db -U app -c 'select 1'"),
+        (
+            "PGPASSWORD=\"$PGPASSWORD\" psql -h db -U app -c 'select 1'".to_string(),
+            vec![slot("PGPASSWORD", "PGPASSWORD")]
+        )
+    );
+    assert_eq!(
+        rewritten("export API_TOKEN=YOUR_TOKEN && deploy --token YOUR_TOKEN"),
+        (
+            "export API_TOKEN=\"$API_TOKEN\" && deploy --token \"$VIBE_SECRET_2\"".to_string(),
+            vec![slot("API_TOKEN", "API_TOKEN"), slot("VIBE_SECRET_2", "token for deploy")]
+        )
+    );
+    assert_eq!(
+        rewritten("mysqldump -u root -p shop > shop.sql && mysql -u root --password=<pw> -e 'show databases'"),
+        (
+            "mysqldump -u root shop > shop.sql && mysql -u root -e 'show databases'".to_string(),
+            vec![slot("MYSQL_PWD", "password for mysqldump")]
+        ),
+        "mysql clients read MYSQL_PWD; one secret serves both"
+    );
+    assert_eq!(
+        rewritten("curl -u admin:<password> https://example.dev/api"),
+        (
+            "curl -u \"admin:$VIBE_SECRET_1\" https://example.dev/api".to_string(),
+            vec![slot("VIBE_SECRET_1", "password of admin for curl")]
+        )
+    );
+    assert_eq!(
+        rewritten("sshpass -p '<password>' ssh deploy@host uptime"),
+        (
+            "sshpass -e ssh deploy@host uptime".to_string(),
+            vec![slot("SSHPASS", "password for sshpass")]
+        )
+    );
+}
+
+#[test]
+fn registry_logins_read_the_secret_from_stdin() {
+    assert_eq!(
+        rewritten("sudo docker login -u ci -p <token> ghcr.io"),
+        (
+            "printf '%s\\n' \"$VIBE_SECRET_1\" | sudo docker login -u ci --password-stdin ghcr.io".to_string(),
+            vec![slot("VIBE_SECRET_1", "password for docker login")]
+        )
+    );
+}
+
+#[test]
+fn commands_without_placeholders_are_untouched() {
+    for cmd in [
+        "mkdir -p build/out && cp -p a b",
+        "mysql -u root -phunter2 shop",
+        "PGPASSWORD=$DB_PASSWORD psql -h db",
+        "docker login -u ci --password-stdin ghcr.io < token.txt",
+        "echo 'PGPASSWORD=<password>'",
+    ] {
+        assert_eq!(rewritten(cmd), (cmd.to_string(), Vec::new()), "{cmd}");
+    }
+}
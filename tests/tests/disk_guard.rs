@@ -0,0 +1,109 @@
+use application::disk_guard::{
+    free_space, layer_bytes, listed_sizes, package_needs, parse_dd_size, parse_size, shortfalls, space_needs,
+};
+use application::package_preview::{PackageManager, PackageTransaction};
+use std::path::{Path, PathBuf};
+use tests::scratch_dir;
+
+#[test]
+fn copies_archives_and_dd_are_measured_from_their_sources() {
+    let dir = scratch_dir("disk_guard");
+    std::fs::create_dir_all(dir.join("data/nested")).unwrap();
+    std::fs::write(dir.join("data/a.bin"), vec![0u8; 3000]).unwrap();
+    std::fs::write(dir.join("data/nested/b.bin"), vec![0u8; 2000]).unwrap();
+    std::fs::create_dir_all(dir.join("backup")).unwrap();
+    let root = dir.to_string_lossy().to_string();
+
+    let copy = space_needs(&format!("sudo cp -a {root}/data {root}/backup/"), Path::new("."));
+    assert_eq!(copy.len(), 1);
+    assert_eq!((copy[0].bytes, copy[0].files), (5000, 4), "two files and two directories");
+    assert_eq!(copy[0].target, PathBuf::from(format!("{root}/backup/")));
+    assert!(
+        space_needs(&format!("cp {root}/data {root}/backup/"), Path::new(".")).is_empty(),
+        "cp without -r skips directories"
+    );
+    let relative = space_needs("cp -r data backup/", &dir);
+    assert_eq!((relative[0].bytes, relative[0].target.clone()), (5000, dir.join("backup/")));
+    let into = space_needs(&format!("cp -t {root}/backup {root}/data/a.bin"), Path::new("."));
+    assert_eq!(into[0].bytes, 3000, "-t names the destination");
+    let synced = space_needs(&format!("rsync -avt {root}/data {root}/backup"), Path::new("."));
+    assert_eq!(synced[0].bytes, 5000, "rsync's -t keeps times");
+    assert!(space_needs(&format!("rsync -a {root}/data host:/srv/"), Path::new(".")).is_empty());
+
+    let archive = dir.join("data.tar");
+    let created = space_needs(&format!("tar cf {} -C {root} data", archive.display()), Path::new("."));
+    assert_eq!((created[0].what.as_str(), created[0].bytes), ("tar -c", 5000));
+    let status = std::process::Command::new("tar")
+        .args(["-cf", &archive.to_string_lossy(), "-C", &root, "data"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let extracted = space_needs(&format!("tar -xf {} -C {root}/backup", archive.display()), Path::new("."));
+    assert_eq!((extracted[0].bytes, extracted[0].files), (5000, 4));
+    assert_eq!(extracted[0].target, PathBuf::from(format!("{root}/backup")));
+
+    let image = space_needs(&format!("dd if=/dev/zero of={root}/disk.img bs=1M count=512"), Path::new("."));
+    assert_eq!(image[0].bytes, 512 << 20);
+    let cloned = space_needs(&format!("dd if={root}/data/a.bin of={root}/copy.bin"), Path::new("."));
+    assert_eq!(cloned[0].bytes, 3000);
+    assert!(space_needs("dd if=disk.img of=/dev/sdb bs=4M", Path::new(".")).is_empty(), "devices aren't file systems");
+    assert!(space_needs("cd /srv && cp -r data backup", Path::new(".")).is_empty());
+}
+
+#[test]
+fn sizes_are_parsed_as_each_tool_prints_them() {
+    assert_eq!(parse_dd_size("4M"), Some(4 << 20));
+    assert_eq!(parse_dd_size("2kB"), Some(2000));
+    assert_eq!(parse_dd_size("3b"), Some(1536));
+    assert_eq!(parse_dd_size("1X"), None);
+    assert_eq!(parse_size("1,234 kB"), Some(1_234_000));
+    assert_eq!(parse_size("40.5 MB"), Some(40_500_000));
+    assert_eq!(parse_size("12 M"), Some(12 << 20));
+    assert_eq!(parse_size("lots"), None);
+
+    let gnu = "-rw-r--r-- me/me 3000 2024-05-01 10:00 data/a.bin\ndrwxr-xr-x me/me 0 2024-05-01 10:00 data/\n";
+    assert_eq!(listed_sizes(gnu), (3000, 2));
+    let bsd = "-rw-r--r--  0 me staff 2000 May  1 10:00 data/b.bin\n";
+    assert_eq!(listed_sizes(bsd), (2000, 1));
+
+    let manifest = serde_json::json!({
+        "SchemaV2Manifest": {"layers": [{"size": 1000}, {"size": 500}]}
+    });
+    assert_eq!(layer_bytes(&manifest), Some(1500));
+    let list = serde_json::json!([
+        {"Descriptor": {"platform": {"architecture": "riscv"}}, "OCIManifest": {"layers": [{"size": 7}]}},
+    ]);
+    assert_eq!(layer_bytes(&list), Some(7), "another architecture's manifest is the fallback");
+}
+
+#[test]
+fn needs_beyond_the_free_space_are_shortfalls() {
+    let dir = scratch_dir("disk_guard_free");
+    let free = free_space(&dir.join("not/yet/created")).expect("df works on the nearest existing directory");
+    assert!(free.bytes > 0);
+
+    let root = dir.to_string_lossy();
+    let fits = space_needs(&format!("dd if=/dev/zero of={root}/small.img bs=1K count=1"), Path::new("."));
+    assert!(shortfalls(&fits).is_empty());
+    let huge = space_needs(&format!("dd if=/dev/zero of={root}/huge.img bs=1T count=100000"), Path::new("."));
+    let short = shortfalls(&huge);
+    assert_eq!(short.len(), 1);
+    assert!(short[0].out_of_space());
+    assert_eq!(short[0].free.mount, free.mount);
+
+    let transaction = PackageTransaction {
+        install: vec!["texlive-full".into()],
+        download: Some("1,234 kB".into()),
+        disk: Some("+5,000,000 GB".into()),
+        ..Default::default()
+    };
+    let needs = package_needs(PackageManager::Apt, &transaction);
+    assert_eq!(needs.len(), 2);
+    assert_eq!(needs[1].bytes, 5_000_000_000_000_000);
+    assert!(!shortfalls(&needs).is_empty());
+    let removal = PackageTransaction {
+        disk: Some("-3 MB".into()),
+        ..Default::default()
+    };
+    assert!(package_needs(PackageManager::Apt, &removal).is_empty());
+}
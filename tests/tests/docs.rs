@@ -0,0 +1,143 @@
+use application::docs_service::{DocsService, DOCS_COLLECTION};
+use application::rag_service::RagService;
+use infrastructure::config::Config;
+use infrastructure::doc_source::{decode_entities, fetch_site, html_to_markdown, links, Url};
+use infrastructure::embedding_storage::{collection_db_path, EmbeddingStorage};
+use tests::mock_ollama::MockOllama;
+use tests::{isolate_home, scratch_dir};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html><head><title>Signals</title><style>body { color: red }</style></head>
+<body>
+<nav class="sidebar"><a href="intro.html">Intro</a> <a href="signals.html">Signals</a></nav>
+<main>
+<h1 id="signals">Signals &amp; effects</h1>
+<p>Create a signal with   <code>create_signal</code>, then
+read it in a <em>closure</em>.</p>
+<!-- a comment -->
+<ul><li>getter</li><li>setter &lt;T&gt;</li></ul>
+<pre><code class="language-rust">let (count, set_count) = create_signal(0);
+set_count.update(|n| *n += 1);
+</code></pre>
+<script>window.search = true;</script>
+<p>See <a href="effects.html#top">effects</a>.</p>
+</main>
+</body></html>"#;
+
+#[test]
+fn html_pages_become_markdown() {
+    assert_eq!(
+        html_to_markdown(PAGE),
+        "# Signals & effects\n\n\
+Create a signal with `create_signal`, then read it in a closure.\n\n\
+- getter\n\
+- setter <T>\n\n\
+```\nlet (count, set_count) = create_signal(0);\nset_count.update(|n| *n += 1);\n```\n\n\
+See effects."
+    );
+    assert_eq!(decode_entities("&#39;a&#x27; &unknown; &"), "'a' &unknown; &");
+}
+
+#[test]
+fn links_are_resolved_without_fragments_or_assets() {
+    let base = Url::parse("https://book.example.dev/reactivity/signals.html").unwrap();
+    let html = r#"<a href="effects.html#top">x</a> <A HREF='../intro.html'>y</A>
+        <a href="/logo.png">z</a> <a href="mailto:team@example.dev">m</a> <a href="effects.html">again</a>"#;
+    let found: Vec<String> = links(html, &base).iter().map(Url::to_string).collect();
+    assert_eq!(
+        found,
+        [
+            "https://book.example.dev/reactivity/effects.html",
+            "https://book.example.dev/intro.html",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn sites_are_crawled_within_their_directory() {
+    let server = MockServer::start().await;
+    let html = |body: &str| {
+        ResponseTemplate::new(200)
+            .set_body_string(body.to_string())
+            .insert_header("content-type", "text/html; charset=utf-8")
+    };
+    Mock::given(method("GET"))
+        .and(path("/book/index.html"))
+        .respond_with(html(
+            r#"<h1>Book</h1><a href="ch1.html">1</a> <a href="missing.html">gone</a> <a href="/blog/post.html">blog</a>"#,
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/book/ch1.html"))
+        .respond_with(html(r#"<h2>Chapter one</h2><a href="index.html">home</a>"#))
+        .mount(&server)
+        .await;
+
+    let pages = fetch_site(&format!("{}/book/index.html", server.uri()), 10)
+        .await
+        .unwrap();
+    let found: Vec<(String, &str)> = pages
+        .iter()
+        .map(|p| (p.location.replace(&server.uri(), ""), p.text.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("/book/index.html".to_string(), "# Book\n\n1 gone blog"),
+            ("/book/ch1.html".to_string(), "## Chapter one\n\nhome"),
+        ],
+        "broken links are skipped and nothing outside /book/ is fetched"
+    );
+
+    let first = fetch_site(&format!("{}/book/index.html", server.uri()), 1)
+        .await
+        .unwrap();
+    assert_eq!(first.len(), 1);
+    assert!(fetch_site(&format!("{}/book/none.html", server.uri()), 10).await.is_err());
+}
+
+#[tokio::test]
+async fn added_docs_are_searchable_refreshed_and_removed() {
+    isolate_home();
+    let mock = MockOllama::start("Use create_signal [docs].").await;
+    let dir = scratch_dir("docs_add");
+    let tree = dir.join("wiki");
+    std::fs::create_dir_all(tree.join(".git")).unwrap();
+    std::fs::write(tree.join(".git").join("HEAD"), "ref: main").unwrap();
+    std::fs::write(tree.join("signals.html"), PAGE).unwrap();
+    std::fs::write(tree.join("deploy.md"), "# Deploying\n\nRun cargo leptos build --release.\n").unwrap();
+    std::fs::write(tree.join("logo.png"), [0u8, 1, 2]).unwrap();
+    let mut config = Config::load();
+    config.db_path = dir.join("embeddings.db").to_string_lossy().to_string();
+
+    let docs = DocsService::new(mock.client(), &config).await.unwrap();
+    let added = docs.add(&tree.to_string_lossy(), 50).await.unwrap();
+    assert_eq!(added.pages.len(), 2, "hidden directories and images are skipped");
+    let docs_db = collection_db_path(&config.db_path, DOCS_COLLECTION).unwrap();
+    let storage = EmbeddingStorage::new(&docs_db).await.unwrap();
+    assert_eq!(storage.stats().await.unwrap().files, 2);
+
+    let service = RagService::collection(".", DOCS_COLLECTION, mock.client(), &config)
+        .await
+        .unwrap();
+    service.query("how do I create a signal?").await.unwrap();
+    let prompt = mock.chat_prompts().await.pop().unwrap();
+    assert!(prompt.contains("let (count, set_count) = create_signal(0);"));
+    assert!(!prompt.contains("window.search"));
+
+    std::fs::remove_file(tree.join("deploy.md")).unwrap();
+    let refreshed = docs.refresh(None, 50).await.unwrap();
+    assert_eq!(refreshed.len(), 1);
+    assert_eq!(refreshed[0].pages.len(), 1);
+    assert_eq!(storage.stats().await.unwrap().files, 1, "pages gone from the source are dropped");
+    assert_eq!(docs.sources().await.unwrap().len(), 1, "re-adding keeps one record");
+
+    let removed = docs.remove(&tree.to_string_lossy()).await.unwrap();
+    assert_eq!(removed.pages.len(), 1);
+    assert_eq!(storage.stats().await.unwrap().chunks, 0);
+    assert!(docs.sources().await.unwrap().is_empty());
+    assert!(docs.remove(&tree.to_string_lossy()).await.is_err());
+}
@@ -0,0 +1,43 @@
+use application::rag_service::RagService;
+use infrastructure::config::Config;
+use shared::at_rest::{self, Cipher};
+use tests::mock_ollama::MockOllama;
+use tests::{fixture_path, isolate_home, scratch_dir};
+
+#[tokio::test]
+async fn indexed_code_is_encrypted_on_disk_but_still_retrievable() {
+    isolate_home();
+    at_rest::install(Cipher::from_hex(&"ab".repeat(32)).unwrap());
+    let mock = MockOllama::start("ok").await;
+    let mut config = Config::load();
+    config.db_path = scratch_dir("encryption")
+        .join("embeddings.db")
+        .to_string_lossy()
+        .to_string();
+    let root = fixture_path("sample_project");
+    let service = RagService::new(
+        &root.to_string_lossy(),
+        &config.db_path.clone(),
+        mock.client(),
+        config.clone(),
+    )
+    .await
+    .unwrap();
+    service.build_index().await.unwrap();
+
+    service
+        .query("how is the archive checksum computed?")
+        .await
+        .unwrap();
+    let prompts = mock.chat_prompts().await;
+    assert!(prompts.last().unwrap().contains("archive_checksum"));
+
+    drop(service);
+    let mut on_disk = std::fs::read(&config.db_path).unwrap();
+    let mut wal = config.db_path.clone();
+    wal.push_str("-wal");
+    on_disk.extend(std::fs::read(wal).unwrap_or_default());
+    assert!(!on_disk
+        .windows(b"fn archive_checksum".len())
+        .any(|w| w == b"fn archive_checksum"));
+}
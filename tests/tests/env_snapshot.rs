@@ -0,0 +1,82 @@
+use application::env_snapshot::{mentioned_paths, parse_enabled_units, parse_packages, EnvSnapshot};
+use infrastructure::audit_log::{AuditEntry, AuditLog};
+use tests::scratch_dir;
+
+#[test]
+fn plans_name_the_files_to_track() {
+    let home = std::env::var("HOME").unwrap();
+    assert_eq!(
+        mentioned_paths(&[
+            "sudo tee /etc/nginx/sites-available/app.conf > /dev/null",
+            "echo 'vm.swappiness=10' >>/etc/sysctl.conf && cp ~/.bashrc /tmp/x",
+            "mkswap --label=swap /swapfile; ls /var/log/*.log",
+        ]),
+        [
+            "/etc/nginx/sites-available/app.conf".to_string(),
+            "/etc/sysctl.conf".to_string(),
+            format!("{}/.bashrc", home),
+            "/swapfile".to_string(),
+        ],
+        "device, temp and glob paths aren't tracked"
+    );
+}
+
+#[test]
+fn snapshots_report_what_changed() {
+    let dir = scratch_dir("env_snapshot");
+    let conf = dir.join("conf.d");
+    std::fs::create_dir_all(&conf).unwrap();
+    std::fs::write(conf.join("a.conf"), "a = 1").unwrap();
+    std::fs::write(conf.join("b.conf"), "b = 1").unwrap();
+    let paths = vec![conf.to_string_lossy().to_string(), dir.join("new.txt").to_string_lossy().to_string()];
+
+    let mut before = EnvSnapshot::capture(&paths);
+    before.packages = Some(parse_packages("nginx 1.22.1-9\nopenssl 3.0.11-1\napache2 2.4.57-2\n"));
+    before.services = Some(parse_enabled_units("apache2.service enabled enabled\nssh.service enabled enabled\n"));
+
+    std::fs::write(conf.join("a.conf"), "a = 2").unwrap();
+    std::fs::remove_file(conf.join("b.conf")).unwrap();
+    std::fs::write(conf.join("c.conf"), "c = 1").unwrap();
+    std::fs::write(dir.join("new.txt"), "hello").unwrap();
+    let mut after = EnvSnapshot::capture(&paths);
+    after.packages = Some(parse_packages("nginx 1.22.1-9\nopenssl 3.0.13-1\ncertbot 2.1.0-4\n"));
+    after.services = Some(parse_enabled_units("ssh.service enabled enabled\nnginx.service enabled enabled\n"));
+
+    let conf = conf.to_string_lossy();
+    assert_eq!(
+        before.changes(&after),
+        [
+            "installed certbot 2.1.0-4".to_string(),
+            "upgraded openssl 3.0.11-1 -> 3.0.13-1".to_string(),
+            "removed apache2 2.4.57-2".to_string(),
+            "enabled nginx.service".to_string(),
+            "disabled apache2.service".to_string(),
+            format!("changed {}/a.conf", conf),
+            format!("deleted {}/b.conf", conf),
+            format!("created {}/c.conf", conf),
+            format!("created {}/new.txt", dir.display()),
+        ]
+    );
+    assert!(after.changes(&after).is_empty());
+    assert!(
+        EnvSnapshot::default().changes(&after).iter().all(|c| c.starts_with("created")),
+        "packages and services unknown before aren't diffed"
+    );
+}
+
+#[test]
+fn run_reports_are_kept_with_the_audit_log() {
+    let path = scratch_dir("env_snapshot_audit").join("audit.jsonl");
+    let log = AuditLog::new(&path);
+    let report = AuditEntry {
+        outcome: "report".to_string(),
+        run: Some("plan-1".to_string()),
+        changes: vec!["installed nginx 1.22.1-9".to_string()],
+        ..AuditEntry::new("agent", "set up nginx", "")
+    };
+    log.append(&AuditEntry::new("command", "disk usage", "df -h")).unwrap();
+    log.append(&report).unwrap();
+    assert_eq!(log.entries().unwrap()[1], report);
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(!text.lines().next().unwrap().contains("changes"), "entries without changes stay as they were");
+}
@@ -0,0 +1,120 @@
+use application::diff_explain_service::{
+    changed_identifiers, diff_files, diff_revisions, unified_diff, DiffExplainService,
+};
+use application::explain_service::{select_pages, ExplainService};
+use tests::mock_ollama::MockOllama;
+use tests::{isolate_home, scratch_dir};
+
+fn long_document() -> String {
+    (0..1500)
+        .map(|i| format!("Section {}: the retention policy keeps backups for {} days.\n", i, i % 30))
+        .collect()
+}
+
+#[tokio::test]
+async fn large_documents_are_explained_part_by_part_then_merged() {
+    isolate_home();
+    let mock = MockOllama::start("- retention rules").await;
+    mock.reply_when("explain the document in detail", "A backup retention policy.")
+        .await;
+    let mut progress = Vec::new();
+
+    let explanation = ExplainService::new(mock.client())
+        .explain(
+            "policy.pdf",
+            &long_document(),
+            |done, total| progress.push((done, total)),
+            std::future::pending(),
+        )
+        .await
+        .unwrap();
+
+    assert!(explanation.complete);
+    assert_eq!(explanation.text, "A backup retention policy.");
+    let total = explanation.parts_total;
+    assert!(total > 1);
+    assert_eq!(progress.first(), Some(&(0, total)));
+    assert_eq!(progress.last(), Some(&(total, total)));
+    let prompts = mock.chat_prompts().await;
+    assert_eq!(prompts.len(), total + 1);
+    assert!(prompts[0].contains(&format!("part 1 of {}", total)));
+    assert!(prompts[total].contains("Part 1:\n- retention rules"));
+}
+
+#[tokio::test]
+async fn cancelling_keeps_the_notes_gathered_so_far() {
+    isolate_home();
+    let mock = MockOllama::start("- retention rules").await;
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let mut stop = Some(stop);
+
+    let explanation = ExplainService::new(mock.client())
+        .explain(
+            "policy.pdf",
+            &long_document(),
+            |done, _| {
+                if done == 1 {
+                    let _ = stop.take().map(|s| s.send(()));
+                }
+            },
+            async {
+                let _ = stopped.await;
+            },
+        )
+        .await
+        .unwrap();
+
+    assert!(!explanation.complete);
+    assert_eq!(explanation.parts_done, 1);
+    assert_eq!(explanation.text, "Part 1:\n- retention rules");
+    assert_eq!(mock.chat_prompts().await.len(), 1);
+}
+
+#[test]
+fn page_selections_resolve_to_page_indexes() {
+    assert_eq!(select_pages("3", 10), Some(vec![2]));
+    assert_eq!(select_pages("2-4", 10), Some(vec![1, 2, 3]));
+    assert_eq!(select_pages("8-", 10), Some(vec![7, 8, 9]));
+    assert_eq!(select_pages("1, 3-4, 4", 10), Some(vec![0, 2, 3]));
+    assert_eq!(select_pages("0", 10), None);
+    assert_eq!(select_pages("5-3", 10), None);
+    assert_eq!(select_pages("11", 10), None);
+    assert_eq!(select_pages("two", 10), None);
+}
+
+#[test]
+fn diffs_are_unified_and_name_the_changed_identifiers() {
+    let dir = scratch_dir("explain_diff");
+    let (old, new) = (dir.join("old.rs"), dir.join("new.rs"));
+    std::fs::write(&old, "fn keep() {}\nfn retention_days() -> u32 { 30 }\n").unwrap();
+    std::fs::write(&new, "fn keep() {}\nfn retention_days() -> u32 { 90 }\n").unwrap();
+
+    let diff = diff_files(&old, &new).unwrap();
+    assert!(diff.contains(&format!("--- {}", old.display())));
+    assert!(diff.contains("-fn retention_days() -> u32 { 30 }"));
+    assert!(diff.contains("+fn retention_days() -> u32 { 90 }"));
+    assert_eq!(changed_identifiers(&diff), ["retention_days"]);
+
+    assert!(unified_diff("a", "same\n", "b", "same\n").is_empty());
+    assert!(diff_files(&dir.join("missing.rs"), &new).is_err());
+    assert!(diff_revisions("--output=/tmp/x", &[]).is_err(), "options are not ranges");
+}
+
+#[tokio::test]
+async fn diff_explanations_ask_for_behavioral_impact_with_context() {
+    isolate_home();
+    let mock = MockOllama::start("Backups are now kept three times longer.").await;
+    let diff = unified_diff("a.rs", "KEEP = 30\n", "b.rs", "KEEP = 90\n");
+    let context = vec!["FILE: src/backup.rs\nfn prune() { uses KEEP }".to_string()];
+
+    let text = DiffExplainService::new(mock.client())
+        .explain(&diff, &context)
+        .await
+        .unwrap();
+
+    assert_eq!(text, "Backups are now kept three times longer.");
+    let prompt = &mock.chat_prompts().await[0];
+    assert!(prompt.contains("behavioral impact"));
+    assert!(prompt.contains("Definitions from the project that the change touches:\nFILE: src/backup.rs"));
+    assert!(prompt.contains("-KEEP = 30\n+KEEP = 90"));
+}
@@ -0,0 +1,332 @@
+use infrastructure::file_scanner::{FileChunk, FileScanner};
+use proptest::prelude::*;
+use std::path::Path;
+use tests::scratch_dir;
+
+/// Distinct paragraphs (each starts with its index), so no chunk is
+/// dropped as a duplicate, joined by blank lines or runs of them.
+fn document() -> impl Strategy<Value = String> {
+    prop::collection::vec(("[a-zA-Z0-9ü€ .\n]{0,400}", "\n\n(\n){0,3}"), 0..30).prop_map(|parts| {
+        parts
+            .iter()
+            .enumerate()
+            .map(|(i, (text, gap))| format!("p{i} {text}{gap}"))
+            .collect()
+    })
+}
+
+fn lines_before(text: &str, offset: usize) -> usize {
+    1 + text[..offset].matches('\n').count()
+}
+
+/// Every chunk is the slice of `text` at its offset, with its line span,
+/// chunks follow each other, and only line breaks are left between them.
+fn assert_reconstructs(text: &str, chunks: &[FileChunk]) -> Result<(), TestCaseError> {
+    let mut covered_to = 0;
+    for chunk in chunks {
+        let end = chunk.start_offset + chunk.text.len();
+        prop_assert_eq!(&text[chunk.start_offset..end], chunk.text.as_str());
+        prop_assert!(chunk.start_offset >= covered_to, "chunks don't overlap");
+        prop_assert!(text[covered_to..chunk.start_offset].chars().all(|c| c == '\n'));
+        prop_assert_eq!(chunk.start_line, lines_before(text, chunk.start_offset));
+        prop_assert_eq!(
+            chunk.end_line,
+            chunk.start_line + chunk.text.trim_end_matches('\n').matches('\n').count()
+        );
+        covered_to = end;
+    }
+    prop_assert!(text[covered_to..].chars().all(|c| c == '\n'));
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn paragraph_chunks_reconstruct_the_text(text in document()) {
+        let chunks = FileScanner::new(".").chunk_document(&text, "notes.md");
+        assert_reconstructs(&text, &chunks)?;
+        for chunk in &chunks {
+            prop_assert!(chunk.text.len() <= 2000 || !chunk.text.contains("\n\n"), "only a single paragraph exceeds the maximum");
+        }
+    }
+
+    #[test]
+    fn streamed_chunks_sit_at_their_offsets(text in document()) {
+        let chunks = FileScanner::new(".").chunk_bytes(text.as_bytes(), Path::new("notes.md"));
+        for chunk in &chunks {
+            prop_assert!(text[chunk.start_offset..].starts_with(&chunk.text));
+            prop_assert_eq!(chunk.start_line, lines_before(&text, chunk.start_offset));
+        }
+    }
+}
+
+#[test]
+fn chunk_offsets_and_lines_follow_the_paragraphs() {
+    let first = format!("fn first() {{\n{}}}", "    step();\n".repeat(50));
+    let second = format!("fn second() {{\n{}}}", "    other();\n".repeat(50));
+    let text = format!("{first}\n\n\n\n{second}\n");
+
+    let chunks = FileScanner::new(".").chunk_document(&text, "lib.rs");
+
+    assert_eq!(chunks.len(), 2);
+    assert_eq!((chunks[0].start_offset, chunks[0].start_line, chunks[0].end_line), (0, 1, 52));
+    assert_eq!(chunks[1].start_offset, first.len() + 4);
+    assert_eq!(chunks[1].text, format!("{second}\n"));
+    assert_eq!((chunks[1].start_line, chunks[1].end_line), (56, 107));
+}
+
+#[test]
+fn streaming_chunks_cut_on_breaks_and_character_boundaries() {
+    let scanner = FileScanner::new(".");
+    let mut text = "ü".repeat(1500);
+    text.push_str("\n\n");
+    for i in 0..80 {
+        text.push_str(&format!("## Endpoint {i}\nGET /users/{i} returns user {i}.\n\n"));
+    }
+    let bytes = text.as_bytes();
+
+    let chunks = scanner.chunk_bytes(bytes, Path::new("spec.md"));
+
+    assert!(chunks.len() > 2);
+    for chunk in &chunks {
+        assert!(!chunk.text.contains('\u{FFFD}'), "no character is split");
+        assert!(text[chunk.start_offset..].starts_with(&chunk.text), "offsets are byte offsets");
+        assert_eq!(chunk.language, "markdown");
+    }
+    assert_eq!(chunks[0].text.len(), 2000, "a window without breaks is cut at the size limit");
+    assert!(chunks[1].text.starts_with('ü') && chunks[1].text.ends_with('.'), "the next one ends at a paragraph break");
+    assert!(chunks[2..].iter().all(|c| c.text.starts_with("## Endpoint")), "later windows end at paragraph breaks");
+    let ordinals: Vec<usize> = chunks.iter().map(|c| c.ordinal).collect();
+    assert_eq!(ordinals, (0..chunks.len()).collect::<Vec<_>>());
+}
+
+#[test]
+fn large_files_are_streamed_up_to_the_configured_cap() {
+    let dir = scratch_dir("large_file");
+    let client = dir.join("client.rs");
+    let body: String = (0..40_000)
+        .map(|i| format!("pub fn endpoint_{i}() -> &'static str {{ \"/v1/{i}\" }}\n"))
+        .collect();
+    assert!(body.len() > 2 * 1024 * 1024);
+    std::fs::write(&client, &body).unwrap();
+
+    let scans = FileScanner::new(&dir).scan_paths(std::slice::from_ref(&client)).unwrap();
+    assert!(!scans[0].hash.is_empty());
+    assert!(scans[0].chunks.iter().any(|c| c.text.contains("endpoint_39999")));
+    assert_eq!(
+        FileScanner::new(&dir).file_hash(&client).as_deref(),
+        Some(scans[0].hash.as_str()),
+        "query-time checks hash files the same way"
+    );
+
+    let capped = FileScanner::new(&dir)
+        .with_max_file_bytes(1024 * 1024)
+        .scan_paths(&[client])
+        .unwrap();
+    assert!(capped[0].hash.is_empty() && capped[0].chunks.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn the_walk_follows_symlinks_only_when_asked_and_never_loops() {
+    use infrastructure::config::IndexSettings;
+    use std::os::unix::fs::symlink;
+
+    let root = scratch_dir("walk");
+    std::fs::create_dir_all(root.join("src/nested/deeper")).unwrap();
+    std::fs::create_dir_all(root.join("target")).unwrap();
+    std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+    std::fs::write(root.join("src/nested/deeper/deep.rs"), "fn deep() {}").unwrap();
+    std::fs::write(root.join("target/build.rs"), "fn built() {}").unwrap();
+    let outside = scratch_dir("walk_outside");
+    std::fs::write(outside.join("shared.rs"), "fn shared() {}").unwrap();
+    symlink(&outside, root.join("src/linked")).unwrap();
+    symlink(&root, root.join("src/nested/back_to_root")).unwrap();
+    let names = |files: Vec<std::path::PathBuf>| -> Vec<String> {
+        files
+            .iter()
+            .map(|f| f.strip_prefix(&root).unwrap().to_string_lossy().to_string())
+            .collect()
+    };
+
+    let plain = FileScanner::new(&root).collect_files().unwrap();
+    assert_eq!(names(plain), ["src/main.rs", "src/nested/deeper/deep.rs"], "links and ignored dirs are left out");
+
+    let following = FileScanner::new(&root)
+        .with_walk(IndexSettings {
+            follow_symlinks: true,
+            ..IndexSettings::default()
+        })
+        .collect_files()
+        .unwrap();
+    assert_eq!(
+        names(following),
+        ["src/linked/shared.rs", "src/main.rs", "src/nested/deeper/deep.rs"],
+        "the link back to the root is a loop and isn't walked again"
+    );
+
+    let shallow = FileScanner::new(&root)
+        .with_walk(IndexSettings {
+            max_depth: 2,
+            ..IndexSettings::default()
+        })
+        .collect_files()
+        .unwrap();
+    assert_eq!(names(shallow), ["src/main.rs"]);
+
+    let capped = FileScanner::new(&root)
+        .with_walk(IndexSettings {
+            max_files: 1,
+            ..IndexSettings::default()
+        })
+        .collect_files()
+        .unwrap();
+    assert_eq!(capped.len(), 1);
+}
+
+#[test]
+fn ignored_dirs_and_the_size_cap_come_from_the_config_and_vibe_toml() {
+    use infrastructure::config::{project_index_settings, IndexSettings};
+
+    let root = scratch_dir("walk_config");
+    for dir in ["src", "build", ".venv-py311/lib", "vendor"] {
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+    std::fs::write(root.join("src/big.rs"), "// padding\n".repeat(200)).unwrap();
+    std::fs::write(root.join("build/gen.rs"), "fn generated() {}").unwrap();
+    std::fs::write(root.join(".venv-py311/lib/site.py"), "import os").unwrap();
+    std::fs::write(root.join("vendor/dep.rs"), "fn dep() {}").unwrap();
+    let names = |settings: IndexSettings| -> Vec<String> {
+        FileScanner::new(&root)
+            .with_walk(settings)
+            .collect_files()
+            .unwrap()
+            .iter()
+            .map(|f| f.strip_prefix(&root).unwrap().to_string_lossy().to_string())
+            .collect()
+    };
+
+    assert_eq!(
+        names(IndexSettings::default()),
+        [".venv-py311/lib/site.py", "src/big.rs", "src/main.rs", "vendor/dep.rs"]
+    );
+    let user = IndexSettings {
+        ignored_dirs: vec![".venv-py311".into(), "vendor".into()],
+        ..IndexSettings::default()
+    };
+    assert_eq!(names(user.clone()), ["src/big.rs", "src/main.rs"]);
+
+    std::fs::write(
+        root.join(".vibe.toml"),
+        "[index]\ninclude_dirs = [\"build\"]\nmax_file_bytes = 1000\n\n[safety]\nconfirm = false\n",
+    )
+    .unwrap();
+    let project = project_index_settings(&root, &user);
+    assert_eq!(project.ignored_dirs, user.ignored_dirs, "keys the project doesn't set are kept");
+    let scans = FileScanner::new(&root)
+        .with_walk(project.clone())
+        .scan_paths(&[root.join("src/big.rs"), root.join("src/main.rs")])
+        .unwrap();
+    assert!(scans[0].chunks.is_empty(), "big.rs is over the project's cap");
+    assert!(!scans[1].chunks.is_empty());
+    assert_eq!(names(project), [".vibe.toml", "build/gen.rs", "src/big.rs", "src/main.rs"]);
+
+    std::fs::write(root.join(".vibe.toml"), "[index]\nmax_depth = \"deep\"\n").unwrap();
+    assert_eq!(project_index_settings(&root, &user), user, "an invalid file is ignored");
+}
+
+#[test]
+fn binary_minified_and_lock_files_are_skipped_unless_forced() {
+    use infrastructure::config::IndexSettings;
+
+    let root = scratch_dir("sniff");
+    std::fs::create_dir_all(root.join("web")).unwrap();
+    let source = "fn main() {\n    println!(\"hi\");\n}\n";
+    std::fs::write(root.join("main.rs"), source).unwrap();
+    std::fs::write(root.join("web/app.min.js"), "var a=1;".repeat(1000)).unwrap();
+    std::fs::write(root.join("package-lock.json"), "{\n  \"lockfileVersion\": 3\n}\n").unwrap();
+    std::fs::write(root.join("asset.json"), b"{\"png\": \"\x89PNG\x00\x00\"}").unwrap();
+    // Noise without NULs or newlines to trip the other checks.
+    let mut state = 7u32;
+    let noise: Vec<u8> = (0..8192)
+        .map(|i| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            if i % 100 == 99 { b'\n' } else { ((state >> 16) % 255) as u8 + 1 }
+        })
+        .collect();
+    std::fs::write(root.join("blob.conf"), &noise).unwrap();
+    let paths: Vec<_> = ["main.rs", "web/app.min.js", "package-lock.json", "asset.json", "blob.conf"]
+        .iter()
+        .map(|p| root.join(p))
+        .collect();
+    let indexed = |scanner: FileScanner| -> Vec<bool> {
+        scanner.scan_paths(&paths).unwrap().iter().map(|s| !s.chunks.is_empty()).collect()
+    };
+
+    assert_eq!(indexed(FileScanner::new(&root)), [true, false, false, false, false]);
+
+    let forced = FileScanner::new(&root).with_walk(IndexSettings {
+        force_include: vec!["**/*.min.js".into(), "package-lock.json".into()],
+        ..IndexSettings::default()
+    });
+    assert_eq!(indexed(forced), [true, true, true, false, false]);
+}
+
+#[test]
+fn notebook_cells_are_chunked_separately_and_literate_formats_are_indexed() {
+    let root = scratch_dir("notebook");
+    let notebook = serde_json::json!({
+        "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+        "cells": [
+            {"cell_type": "markdown", "source": ["# Churn model\n", "\n", "Fits a logistic regression."]},
+            {"cell_type": "code", "source": "import pandas as pd\ndf = pd.read_csv('churn.csv')",
+             "outputs": [{"data": {"image/png": "iVBORw0KGgo".repeat(2000)}}]},
+            {"cell_type": "raw", "source": "not indexed"},
+            {"cell_type": "code", "source": []},
+            {"cell_type": "code", "source": ["model.fit(df)"]},
+        ]
+    });
+    std::fs::write(root.join("churn.ipynb"), notebook.to_string()).unwrap();
+    std::fs::write(root.join("notes.org"), "* Setup\nRun make.\n").unwrap();
+    std::fs::write(root.join("guide.rst"), "Guide\n=====\n\nRead this.\n").unwrap();
+
+    let scanner = FileScanner::new(&root);
+    let files = scanner.collect_files().unwrap();
+    assert_eq!(files.len(), 3, "{files:?}");
+    let scans = scanner.scan_paths(&files).unwrap();
+    let chunks = &scans.iter().find(|s| s.path.ends_with("churn.ipynb")).unwrap().chunks;
+    let cells: Vec<_> = chunks
+        .iter()
+        .map(|c| (c.cell, c.language.as_str(), c.text.as_str(), c.ordinal))
+        .collect();
+    assert_eq!(
+        cells,
+        [
+            (Some(0), "markdown", "# Churn model\n\nFits a logistic regression.", 0),
+            (Some(1), "python", "import pandas as pd\ndf = pd.read_csv('churn.csv')", 1),
+            (Some(4), "python", "model.fit(df)", 2),
+        ]
+    );
+    assert!(chunks.windows(2).all(|w| w[0].start_offset < w[1].start_offset));
+    for literate in ["notes.org", "guide.rst"] {
+        let scan = scans.iter().find(|s| s.path.ends_with(literate)).unwrap();
+        assert!(!scan.chunks.is_empty() && scan.chunks[0].cell.is_none(), "{literate}");
+    }
+}
+
+#[test]
+fn chunk_language_comes_from_the_extension_else_the_shebang() {
+    use shared::utils::detect_language;
+
+    let cases = [
+        ("lib.rs", "#!/usr/bin/env python3", "rust"),
+        ("train", "#!/usr/bin/env python3.11\nimport torch", "python"),
+        ("serve", "#!/usr/bin/env -S node --no-warnings", "javascript"),
+        ("setup", "#!/bin/sh -e", "shell"),
+        ("README", "A plain text file", ""),
+        ("run", "#!/opt/bin/unknown-tool", ""),
+    ];
+    for (name, head, language) in cases {
+        assert_eq!(detect_language(Path::new(name), head), language, "{name}");
+    }
+}
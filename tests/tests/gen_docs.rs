@@ -0,0 +1,163 @@
+use application::doc_gen_service::{
+    apply_docs, clean_doc, format_doc, undocumented_items, DocLanguage,
+};
+use presentation::cli::CliApp;
+use tests::mock_ollama::MockOllama;
+use tests::{isolate_home, scratch_dir};
+
+const RUST: &str = "\
+//! Archive helpers.
+
+/// Documented already.
+pub fn documented() {}
+
+pub fn archive_checksum(bytes: &[u8]) -> u32 {
+    bytes.len() as u32
+}
+
+fn private() {}
+pub(crate) fn internal() {}
+
+#[derive(Debug, Clone)]
+pub struct Archive {
+    pub name: String,
+}
+
+#[doc = \"Documented by attribute.\"]
+pub const LIMIT: usize = 10;
+
+impl Archive {
+    pub fn open(name: &str) -> Self {
+        Self { name: name.to_string() }
+    }
+}
+
+impl Default for Archive {
+    fn default() -> Self {
+        Self::open(\"\")
+    }
+}
+
+pub mod formats {
+    pub fn tar() {}
+}
+";
+
+const PYTHON: &str = "\
+\"\"\"Archive helpers.\"\"\"
+
+
+def archive_checksum(data):
+    return len(data)
+
+
+def _private():
+    return 1
+
+
+class Archive:
+    \"\"\"Documented already.\"\"\"
+
+    @staticmethod
+    def open(name):
+        return Archive()
+
+    def __repr__(self):
+        return 'Archive'
+
+
+def one_liner(): pass
+";
+
+#[test]
+fn undocumented_public_rust_items_are_found() {
+    let items = undocumented_items(RUST, DocLanguage::Rust).unwrap();
+    let found: Vec<(&str, &str, usize)> = items
+        .iter()
+        .map(|i| (i.kind.as_str(), i.name.as_str(), i.line))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("function", "archive_checksum", 5),
+            ("struct", "Archive", 12),
+            ("method", "open", 21),
+            ("module", "formats", 32),
+            ("function", "tar", 33),
+        ],
+        "comments go above attributes; trait impls and non-pub items are skipped"
+    );
+    assert_eq!(items[2].indent, "    ");
+    assert!(items[0].text.ends_with("bytes.len() as u32\n}"));
+}
+
+#[test]
+fn undocumented_python_definitions_are_found() {
+    let items = undocumented_items(PYTHON, DocLanguage::Python).unwrap();
+    let found: Vec<(&str, &str, usize, &str)> = items
+        .iter()
+        .map(|i| (i.kind.as_str(), i.name.as_str(), i.line, i.indent.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("function", "archive_checksum", 4, "    "),
+            ("method", "open", 16, "        "),
+        ],
+        "docstrings go first in the body; private, dunder and one-line definitions are skipped"
+    );
+}
+
+#[test]
+fn replies_become_comments_in_the_language_style() {
+    assert_eq!(
+        clean_doc("```rust\n/// Sums the bytes.\n///\n///\n/// # Errors\n/// - none\n```"),
+        "Sums the bytes.\n\n# Errors\n- none"
+    );
+    assert_eq!(clean_doc("\"\"\"Open an archive.\"\"\""), "Open an archive.");
+
+    let items = undocumented_items(PYTHON, DocLanguage::Python).unwrap();
+    let docstring = format_doc("Open an archive.\n\nArgs:\n    name: its name.", &items[1], DocLanguage::Python);
+    assert_eq!(
+        docstring,
+        "        \"\"\"Open an archive.\n\n        Args:\n            name: its name.\n        \"\"\"\n"
+    );
+
+    let items = undocumented_items(RUST, DocLanguage::Rust).unwrap();
+    let method = format_doc("Opens it.\n\nNever fails.", &items[2], DocLanguage::Rust);
+    assert_eq!(method, "    /// Opens it.\n    ///\n    /// Never fails.\n");
+    let docs = vec![
+        (items[2].clone(), method),
+        (items[0].clone(), format_doc("Sums.", &items[0], DocLanguage::Rust)),
+    ];
+    let documented = apply_docs(RUST, &docs);
+    assert!(documented.contains("/// Sums.\npub fn archive_checksum"));
+    assert!(documented.contains("impl Archive {\n    /// Opens it.\n    ///\n    /// Never fails.\n    pub fn open"));
+    assert_eq!(documented.lines().count(), RUST.lines().count() + 4);
+}
+
+#[tokio::test]
+async fn every_undocumented_item_gets_a_generated_comment() {
+    isolate_home();
+    let mock = MockOllama::start("Does something.").await;
+    mock.reply_when("`archive_checksum`", "/// Checksum of `data`, its length for now.").await;
+    let path = scratch_dir("gen_docs").join("archive.py");
+    std::fs::write(&path, PYTHON).unwrap();
+    let app = CliApp::new().with_client(mock.client());
+
+    let documented = app.generate_docs(&path, false).await.unwrap().unwrap();
+
+    assert!(documented.contains(
+        "def archive_checksum(data):\n    \"\"\"Checksum of `data`, its length for now.\"\"\"\n    return len(data)"
+    ));
+    assert!(documented.contains("def open(name):\n        \"\"\"Does something.\"\"\"\n"));
+    let prompts = mock.chat_prompts().await;
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[1].contains("PEP 257 docstring"));
+    assert!(prompts[1].contains("for the method `open`"));
+    assert!(prompts[1].contains("@staticmethod"));
+
+    std::fs::write(&path, documented).unwrap();
+    assert_eq!(app.generate_docs(&path, false).await.unwrap(), None);
+    assert!(app.generate_docs(&path.with_extension("txt"), false).await.is_err());
+}
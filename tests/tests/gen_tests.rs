@@ -0,0 +1,106 @@
+use application::rag_service::RagService;
+use application::test_gen_service::{
+    extract_code, gather, test_file, with_tests, TestGenService, TestTarget,
+};
+use infrastructure::config::Config;
+use tests::mock_ollama::MockOllama;
+use tests::{fixture_path, isolate_home, scratch_dir};
+
+#[test]
+fn targets_name_a_function_and_where_to_find_it() {
+    let target = TestTarget::parse("crate::storage::checksum::archive_checksum").unwrap();
+    assert_eq!(target.module, ["storage", "checksum"]);
+    assert_eq!(target.function, "archive_checksum");
+    assert_eq!(
+        TestTarget::parse("src/checksum.rs::archive_checksum").unwrap().module,
+        ["checksum"]
+    );
+    assert!(TestTarget::parse("checksum::").is_err());
+    assert!(TestTarget::parse("rm -rf").is_err());
+
+    let target = TestTarget::parse("checksum::archive_checksum").unwrap();
+    assert!(target.defined_in("FILE: src/checksum.rs\nOFFSET: 0\npub fn archive_checksum(b: &[u8])"));
+    assert!(!target.defined_in("FILE: src/other.rs\nOFFSET: 0\npub fn archive_checksum(b: &[u8])"));
+    assert!(!target.defined_in("FILE: src/checksum.rs\nOFFSET: 0\nfn archive_checksum_v2()"));
+    assert!(target.called_in("let sum = checksum::archive_checksum(&bytes);"));
+    assert!(!target.called_in("let sum = my_archive_checksum(&bytes);"));
+}
+
+#[test]
+fn tests_go_where_the_language_usually_keeps_them() {
+    let root = scratch_dir("gen_tests_layout");
+    let krate = root.join("crates/store");
+    std::fs::create_dir_all(krate.join("src")).unwrap();
+    std::fs::write(krate.join("Cargo.toml"), "[package]\n").unwrap();
+
+    let inline = test_file(&krate.join("src/checksum.rs"));
+    assert_eq!(inline.path, krate.join("src/checksum.rs"));
+    assert!(inline.inline);
+    let command = inline.command("archive_checksum").unwrap();
+    assert!(command.starts_with("cargo test --manifest-path "));
+    assert!(command.ends_with("store/Cargo.toml archive_checksum_tests"));
+
+    std::fs::create_dir_all(krate.join("tests")).unwrap();
+    let separate = test_file(&krate.join("src/checksum.rs"));
+    assert_eq!(separate.path, krate.join("tests/checksum.rs"));
+    assert!(separate.command("archive_checksum").unwrap().ends_with("--test checksum"));
+
+    std::fs::create_dir_all(root.join("tests")).unwrap();
+    assert_eq!(
+        test_file(&root.join("pkg/util.py")).path,
+        root.join("tests/test_util.py")
+    );
+    assert_eq!(test_file(&root.join("web/api.ts")).path, root.join("web/api.test.ts"));
+    let go = test_file(&root.join("cmd/main.go"));
+    assert_eq!(go.path, root.join("cmd/main_test.go"));
+    assert!(go.command("Run").unwrap().starts_with("go test ./"));
+    assert_eq!(test_file(&root.join("x.zig")).command("f"), None);
+}
+
+#[test]
+fn generated_code_is_taken_from_the_fence_and_appended() {
+    let reply = "Here are the tests:\n```rust\n#[test]\nfn empty() {}\n```\nThey cover the edges.";
+    assert_eq!(extract_code(reply), "#[test]\nfn empty() {}");
+    assert_eq!(extract_code("  #[test] fn a() {}\n"), "#[test] fn a() {}");
+
+    assert_eq!(with_tests(None, "\nfn a() {}"), "fn a() {}\n");
+    assert_eq!(
+        with_tests(Some("use x;\n\n\n"), "fn a() {}\n"),
+        "use x;\n\nfn a() {}\n"
+    );
+}
+
+#[tokio::test]
+async fn tests_are_written_from_the_indexed_definition() {
+    isolate_home();
+    let mock = MockOllama::start("```rust\n#[test]\nfn empty_input() {\n    assert_eq!(archive_checksum(&[]), 1);\n}\n```").await;
+    let mut config = Config::load();
+    config.db_path = scratch_dir("gen_tests_index")
+        .join("embeddings.db")
+        .to_string_lossy()
+        .to_string();
+    let root = fixture_path("sample_project");
+    let index = RagService::new(&root.to_string_lossy(), &config.db_path.clone(), mock.client(), config)
+        .await
+        .unwrap();
+    index.build_index().await.unwrap();
+
+    let target = TestTarget::parse("checksum::archive_checksum").unwrap();
+    let context = gather(&index, &target).await.unwrap();
+    assert!(context.source.ends_with("src/checksum.rs"));
+    assert!(context.definition.contains("(b << 16) | a"));
+
+    let file = test_file(&context.source);
+    let tests = TestGenService::new(mock.client())
+        .generate(&target, &context, &file, None)
+        .await
+        .unwrap();
+    assert!(tests.starts_with("#[test]\nfn empty_input()"));
+    let prompt = mock.chat_prompts().await.pop().unwrap();
+    assert!(prompt.contains("Write unit tests for `archive_checksum`"));
+    assert!(prompt.contains("pub fn archive_checksum(bytes: &[u8]) -> u32"));
+    assert!(!prompt.contains("OFFSET:"), "the definition is sent without index headers");
+
+    let missing = TestTarget::parse("checksum::no_such_function").unwrap();
+    assert!(gather(&index, &missing).await.is_err());
+}
@@ -0,0 +1,43 @@
+use infrastructure::generation_stats::{self, GenerationStats, StatsTotals};
+use std::time::Duration;
+use tests::mock_ollama::{MockOllama, MOCK_PROMPT_TOKENS};
+
+#[test]
+fn stats_are_formatted_with_rates() {
+    let stats = GenerationStats {
+        wall: Duration::from_millis(2500),
+        prompt_tokens: Some(412),
+        eval_tokens: Some(80),
+        eval_time: Some(Duration::from_secs(2)),
+    };
+    assert_eq!(stats.tokens_per_sec(), Some(40.0));
+    assert_eq!(stats.format(), "2.50s, 412 prompt + 80 generated tokens, 40.0 tokens/s");
+
+    let unreported = GenerationStats {
+        wall: Duration::from_secs(1),
+        ..GenerationStats::default()
+    };
+    assert_eq!(unreported.format(), "1.00s, no token counts reported");
+
+    let mut totals = StatsTotals::default();
+    assert_eq!(totals.format(), "No model calls yet.");
+    totals.add(&stats);
+    totals.add(&unreported);
+    assert_eq!(totals.calls, 2);
+    assert_eq!(totals.eval_tokens, 80);
+    assert!(totals.format().starts_with("2 model calls, 3.50s in total (1.75s on average)"));
+}
+
+#[tokio::test]
+async fn each_chat_call_adds_the_reported_counts_to_the_totals() {
+    let mock = MockOllama::start("one two three four").await;
+    let before = generation_stats::totals();
+
+    mock.client().generate_response("count to four").await.unwrap();
+
+    let after = generation_stats::totals();
+    assert_eq!(after.calls, before.calls + 1);
+    assert_eq!(after.prompt_tokens, before.prompt_tokens + MOCK_PROMPT_TOKENS);
+    assert_eq!(after.eval_tokens, before.eval_tokens + 4);
+    assert_eq!(after.eval_time, before.eval_time + Duration::from_millis(500));
+}
@@ -0,0 +1,62 @@
+use application::glob_preview::{destructive_globs, expand_glob, preview, GlobTarget};
+use std::path::Path;
+use tests::scratch_dir;
+
+fn target(program: &str, pattern: &str, recursive: bool) -> GlobTarget {
+    GlobTarget {
+        program: program.to_string(),
+        pattern: pattern.to_string(),
+        recursive,
+    }
+}
+
+#[test]
+fn globs_of_destructive_commands_are_found() {
+    assert_eq!(
+        destructive_globs("sudo rm -rf build/* 'keep*' && chmod -R 644 conf/*.conf; ls *.txt"),
+        [target("rm", "build/*", true), target("chmod", "conf/*.conf", true)],
+        "quoted globs aren't expanded by the shell, and ls changes nothing"
+    );
+    assert_eq!(
+        destructive_globs("chown www-data:www-data -- -weird* && mv *.bak /tmp/"),
+        [target("chown", "-weird*", false), target("mv", "*.bak", false)],
+    );
+    assert_eq!(destructive_globs("chmod --reference=a.txt b*.txt"), [target("chmod", "b*.txt", false)]);
+    assert!(destructive_globs("rm -f $(ls *.tmp) logs/{a,b}*").is_empty());
+    assert!(
+        destructive_globs("cd /var/log && rm *.gz").is_empty(),
+        "after cd the globs would be expanded in the wrong directory"
+    );
+}
+
+#[test]
+fn previews_expand_globs_without_running_them() {
+    let dir = scratch_dir("glob_preview");
+    let logs = dir.join("logs");
+    std::fs::create_dir_all(logs.join("old").join("nested")).unwrap();
+    for name in ["a.log", "b.log", "c.txt"] {
+        std::fs::write(logs.join(name), "x").unwrap();
+    }
+    std::fs::write(logs.join("old").join("d.log"), "x").unwrap();
+    let root = logs.to_string_lossy().to_string();
+
+    assert_eq!(
+        expand_glob(&format!("{}/*.log", root), Path::new(".")),
+        [format!("{}/a.log", root), format!("{}/b.log", root)]
+    );
+    assert!(expand_glob(&format!("{}/*.none", root), Path::new(".")).is_empty());
+    let marker = dir.join("ran");
+    assert!(expand_glob(&format!("{}/*;touch {}", root, marker.display()), Path::new(".")).is_empty());
+    assert!(!marker.exists(), "patterns are data, not code");
+    assert_eq!(expand_glob("*.log", &logs), ["a.log", "b.log"], "relative to the command's directory");
+
+    let previews = preview(&format!("rm -r {}/o* {}/*.gz", root, root), Path::new("."));
+    assert_eq!(previews.len(), 2);
+    assert_eq!(previews[0].matches, [format!("{}/old", root)]);
+    assert_eq!(previews[0].total, 3, "the directory, nested/ and d.log");
+    assert!(!previews[0].capped);
+    assert!(previews[1].matches.is_empty());
+
+    let moved = preview(&format!("mv {}/o* /tmp/", root), Path::new("."));
+    assert_eq!(moved[0].total, 1, "moving a directory doesn't touch its contents");
+}
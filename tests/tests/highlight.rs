@@ -0,0 +1,36 @@
+use presentation::highlight::Highlighter;
+
+#[test]
+fn answers_wrap_prose_and_cut_long_code_lines_with_a_marker() {
+    let highlighter = Highlighter::default().with_width(24).with_color(false);
+    let answer = "The session store keeps every exchange on disk.\n\
+```rust\n\
+let sessions = JsonSessionRepository::default();\n\
+```\n";
+
+    let rendered = highlighter.render_answer(answer);
+
+    assert_eq!(
+        rendered,
+        "The session store keeps\nevery exchange on disk.\n\
+```rust\n\
+let sessions = JsonSessi\n\
+↪ onRepository::default(\n\
+↪ );\n\
+```\n"
+    );
+}
+
+#[test]
+fn chunks_are_highlighted_by_their_file_extension() {
+    let highlighter = Highlighter::default().with_width(80).with_color(true);
+    let chunk = "FILE: src/session.rs\nOFFSET: 0\npub fn load() -> Session {}\n";
+
+    let rendered = highlighter.render_chunk(chunk);
+
+    assert!(rendered.contains("src/session.rs"));
+    // Keywords and names are colored separately.
+    assert!(rendered.matches("\x1b[38;2;").count() > 2);
+    let plain = Highlighter::default().with_width(80).with_color(false);
+    assert_eq!(plain.render_chunk(chunk), chunk);
+}
@@ -0,0 +1,39 @@
+use application::intent_router::{classify, Intent, IntentRouter};
+use tests::isolate_home;
+use tests::mock_ollama::MockOllama;
+
+#[test]
+fn clear_inputs_are_routed_by_heuristics() {
+    let cases = [
+        ("where is retry logic implemented in this project", Some(Intent::Code)),
+        ("what does src/session.rs do?", Some(Intent::Code)),
+        ("explain RagService::answer", Some(Intent::Code)),
+        ("install nginx and configure it as a reverse proxy", Some(Intent::Plan)),
+        ("back up /etc, then upgrade all packages", Some(Intent::Plan)),
+        ("what does errno 111 mean", Some(Intent::Question)),
+        ("show the five largest folders", None),
+        ("", None),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(classify(input), expected, "{input}");
+    }
+}
+
+#[tokio::test]
+async fn unclear_inputs_go_to_the_model_when_enabled() {
+    isolate_home();
+    let mock = MockOllama::start("Plan.").await;
+
+    let heuristic = IntentRouter::new().route("rotate the nginx logs").await.unwrap();
+    assert_eq!(heuristic, Intent::Command);
+    assert!(mock.chat_prompts().await.is_empty());
+
+    let routed = IntentRouter::new()
+        .with_model(mock.client())
+        .route("rotate the nginx logs")
+        .await
+        .unwrap();
+    assert_eq!(routed, Intent::Plan);
+    let prompts = mock.chat_prompts().await;
+    assert!(prompts[0].contains("rotate the nginx logs"));
+}
@@ -0,0 +1,48 @@
+use application::interactive::interaction;
+use presentation::pty_attach::Transcript;
+
+#[test]
+fn commands_that_wait_for_the_user_are_recognized() {
+    for (cmd, reason) in [
+        ("sudo apt install nginx", "apt asks for confirmation"),
+        ("pacman -Syu", "pacman asks for confirmation"),
+        ("nice -n 10 apt install nginx", "apt asks for confirmation"),
+        ("rm -ri build", "rm asks for confirmation"),
+        ("ssh deploy@web1", "ssh asks for input"),
+        ("journalctl -u nginx | less", "less takes over the terminal"),
+        ("psql mydb", "psql starts an interactive session"),
+        ("python3", "python3 starts an interactive session"),
+        ("make || bash", "bash starts an interactive session"),
+        ("git commit", "git opens an editor"),
+        ("crontab -e", "crontab opens an editor"),
+        ("docker exec -it web sh", "docker attaches to a container's terminal"),
+    ] {
+        assert_eq!(interaction(cmd).as_deref(), Some(reason), "{cmd}");
+    }
+    for cmd in [
+        "sudo apt-get install -y nginx",
+        "apt install --yes nginx",
+        "dnf -y upgrade",
+        "pacman -S --noconfirm git",
+        "psql mydb -c 'select 1'",
+        "psql mydb < dump.sql",
+        "cat schema.sql | sqlite3 app.db",
+        "sqlite3 app.db 'select 1'",
+        "python3 manage.py migrate",
+        "curl -fsSL https://get.example.sh | bash",
+        "git commit -am 'fix'",
+        "docker run --rm alpine echo hi",
+    ] {
+        assert_eq!(interaction(cmd), None, "{cmd}");
+    }
+}
+
+#[test]
+fn transcripts_keep_what_was_left_on_screen() {
+    let mut transcript = Transcript::default();
+    transcript.feed(b"\x1b[1;32mDo you want to continue?\x1b[0m [Y/n] y\r\n");
+    transcript.feed(b"Progress: 10%\rProgress: 100%\r\n\x1b]0;title\x07done");
+    let output = transcript.finish();
+    assert_eq!(output.stdout(), "Do you want to continue? [Y/n] y\nProgress: 100%\ndone\n");
+    assert!(!output.is_truncated(), "everything was shown as it came");
+}
@@ -0,0 +1,61 @@
+use infrastructure::jobs::{job_script, Job, JobStatus, JobStore};
+use std::process::Command;
+use tests::scratch_dir;
+
+fn job(id: u64, pid: u32) -> Job {
+    Job {
+        id,
+        command: "echo 'backing up'; exit 3".to_string(),
+        dir: std::env::temp_dir(),
+        pid,
+        started: 0,
+        summary: None,
+    }
+}
+
+#[test]
+fn jobs_are_recorded_and_listed_in_order() {
+    let store = JobStore::new(scratch_dir("jobs"));
+    assert_eq!(store.next_id().unwrap(), 1);
+    store.save(&job(2, 1)).unwrap();
+    store.save(&job(1, 1)).unwrap();
+    std::fs::write(store.dir().join("notes.txt"), "not a job").unwrap();
+
+    let ids: Vec<u64> = store.list().unwrap().iter().map(|job| job.id).collect();
+    assert_eq!(ids, [1, 2]);
+    assert_eq!(store.next_id().unwrap(), 3);
+    assert_eq!(store.load(2).unwrap(), job(2, 1));
+    assert!(store.load(9).is_err());
+
+    store.remove(1).unwrap();
+    assert_eq!(store.list().unwrap().len(), 1);
+}
+
+#[test]
+fn a_job_script_records_the_exit_status() {
+    let store = JobStore::new(scratch_dir("jobs"));
+    let finished = job(1, std::process::id());
+    store.save(&finished).unwrap();
+    assert_eq!(store.status(&finished), JobStatus::Running, "its shell is still alive");
+
+    let output = Command::new("bash")
+        .arg("-c")
+        .arg(job_script(&finished.command, &store.exit_path(1)))
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "backing up\n");
+    assert_eq!(store.status(&finished), JobStatus::Exited(3), "even after `exit`");
+    assert_eq!(store.status(&finished).label(), "failed (3)");
+
+    std::fs::write(store.log_path(1), "one\ntwo\nthree\n").unwrap();
+    assert_eq!(store.tail(1, 2).unwrap(), ["two", "three"]);
+}
+
+#[test]
+fn a_job_gone_without_an_exit_status_is_lost() {
+    let store = JobStore::new(scratch_dir("jobs"));
+    let mut child = Command::new("true").spawn().unwrap();
+    let pid = child.id();
+    child.wait().unwrap();
+    assert_eq!(store.status(&job(1, pid)), JobStatus::Lost);
+}
@@ -0,0 +1,108 @@
+use application::rag_service::RagService;
+use infrastructure::config::Config;
+use infrastructure::embedding_storage::EmbeddingStorage;
+use infrastructure::lockfiles::{is_about_dependencies, locked_dependencies, mentioned, versions_summary};
+use std::path::Path;
+use tests::mock_ollama::MockOllama;
+use tests::{isolate_home, scratch_dir};
+
+const PACKAGE_LOCK: &str = r#"{
+  "name": "web",
+  "lockfileVersion": 3,
+  "packages": {
+    "": {
+      "name": "web",
+      "dependencies": { "react": "^18.2.0", "@tanstack/react-query": "^5.0.0" },
+      "devDependencies": { "vite": "^5.0.0" }
+    },
+    "node_modules/react": { "version": "18.2.0" },
+    "node_modules/@tanstack/react-query": { "version": "5.17.9" },
+    "node_modules/vite": { "version": "5.0.12" },
+    "node_modules/loose-envify": { "version": "1.4.0" }
+  }
+}"#;
+
+fn write_project(root: &Path) {
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(root.join("src").join("main.js"), "import React from 'react';\nexport const App = () => null;\n").unwrap();
+    std::fs::write(root.join("package-lock.json"), PACKAGE_LOCK).unwrap();
+    std::fs::write(
+        root.join("pyproject.toml"),
+        "[tool.poetry.dependencies]\npython = \"^3.11\"\nRequests = \"^2.31\"\n\n[tool.poetry.group.dev.dependencies]\npytest = \"*\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("poetry.lock"),
+        "[[package]]\nname = \"requests\"\nversion = \"2.31.0\"\n\n[[package]]\nname = \"urllib3\"\nversion = \"2.1.0\"\n\n[[package]]\nname = \"pytest\"\nversion = \"7.4.4\"\n",
+    )
+    .unwrap();
+}
+
+#[test]
+fn lockfiles_pin_the_direct_dependencies() {
+    let root = scratch_dir("lockfiles_parse");
+    write_project(&root);
+
+    let dependencies = locked_dependencies(&root);
+    assert_eq!(
+        versions_summary(&dependencies),
+        "DEPENDENCY VERSIONS (pinned by the project's lockfiles; use these versions):\n\
+package-lock.json: @tanstack/react-query 5.17.9, react 18.2.0, vite 5.0.12\n\
+poetry.lock: pytest 7.4.4, requests 2.31.0",
+        "transitive packages are left out"
+    );
+
+    let named: Vec<&str> = mentioned("upgrade @tanstack/react-query and React.", &dependencies)
+        .iter()
+        .map(|d| d.name.as_str())
+        .collect();
+    assert_eq!(named, ["@tanstack/react-query", "react"]);
+    assert!(is_about_dependencies("which version of node do we need?", &dependencies));
+    assert!(!is_about_dependencies("where is the app component?", &dependencies));
+    assert!(locked_dependencies(&root.join("src")).is_empty());
+}
+
+#[tokio::test]
+async fn indexed_projects_answer_with_the_locked_versions() {
+    isolate_home();
+    let mock = MockOllama::start("React 18.2.0 [package-lock.json].").await;
+    let root = scratch_dir("lockfiles_rag");
+    write_project(&root);
+    let mut config = Config::load();
+    config.db_path = root.join("embeddings.db").to_string_lossy().to_string();
+    let service = RagService::new(
+        &root.to_string_lossy(),
+        &config.db_path.clone(),
+        mock.client(),
+        config.clone(),
+    )
+    .await
+    .unwrap();
+    service.build_index().await.unwrap();
+
+    let storage = EmbeddingStorage::new(&config.db_path).await.unwrap();
+    let versions_chunk = |embeddings: Vec<domain::models::Embedding>| {
+        embeddings
+            .into_iter()
+            .find(|e| e.path == "__dependency_versions__")
+            .map(|e| e.text)
+    };
+    let stored = versions_chunk(storage.get_all_embeddings().await.unwrap()).unwrap();
+    assert!(stored.contains("react 18.2.0"));
+
+    service.query("how do I upgrade react?").await.unwrap();
+    let prompt = mock.chat_prompts().await.pop().unwrap();
+    let context = prompt.split("Context:\n").nth(1).unwrap();
+    assert!(context.starts_with("BEGIN DATA "));
+    assert!(context.lines().nth(1).unwrap().starts_with("DEPENDENCY VERSIONS"), "the versions come first");
+    assert_eq!(prompt.matches("package-lock.json: ").count(), 1);
+
+    std::fs::remove_file(root.join("package-lock.json")).unwrap();
+    std::fs::remove_file(root.join("poetry.lock")).unwrap();
+    service.build_index().await.unwrap();
+    assert_eq!(
+        versions_chunk(storage.get_all_embeddings().await.unwrap()),
+        None,
+        "versions of removed lockfiles are dropped"
+    );
+}
@@ -0,0 +1,99 @@
+use application::multi_host::{
+    confirm_policy, mentioned_hosts, parse_hosts, remote_command, CellStatus, HostConfirm, StepMatrix,
+};
+use presentation::safety::RiskTier;
+use infrastructure::config::Config;
+use infrastructure::ssh_inventory::parse;
+use presentation::runner::{on_host, rate};
+use tests::isolate_home;
+
+const SSH_CONFIG: &str = "\
+Host *
+    ServerAliveInterval 30
+
+Host web1 web2
+    User deploy
+Host web1
+    HostName 10.0.0.11
+    User root
+
+Match host db*
+    User postgres
+
+Host db-primary
+    HostName = db.internal
+";
+
+#[test]
+fn ssh_config_hosts_are_read_without_patterns() {
+    let hosts = parse(SSH_CONFIG);
+    let aliases: Vec<&str> = hosts.iter().map(|h| h.alias.as_str()).collect();
+    assert_eq!(aliases, ["web1", "web2", "db-primary"]);
+    assert_eq!(hosts[0].user.as_deref(), Some("deploy"), "the first value wins");
+    assert_eq!(hosts[0].hostname.as_deref(), Some("10.0.0.11"));
+    assert_eq!(hosts[1].hostname, None);
+    assert_eq!(hosts[2].hostname.as_deref(), Some("db.internal"));
+    assert_eq!(hosts[2].user, None, "Match blocks are skipped");
+}
+
+#[test]
+fn hosts_named_in_a_task_are_found() {
+    let inventory = parse(SSH_CONFIG);
+    assert_eq!(mentioned_hosts("update nginx on web1 and web2.", &inventory), ["web1", "web2"]);
+    assert_eq!(mentioned_hosts("restart postgres on DB-PRIMARY", &inventory), ["db-primary"]);
+    assert!(mentioned_hosts("update nginx on web10", &inventory).is_empty());
+    assert_eq!(parse_hosts("web1, web2,,web1"), ["web1", "web2"]);
+}
+
+#[test]
+fn steps_reach_a_host_over_ssh() {
+    assert_eq!(remote_command("web1", "df -h", false), "ssh 'web1' -- 'df -h'");
+    assert_eq!(
+        remote_command("web1", "echo 'hi'", true),
+        "ssh -o BatchMode=yes 'web1' -- 'echo '\\''hi'\\'''"
+    );
+}
+
+#[test]
+fn remote_steps_are_rated_by_their_own_command() {
+    isolate_home();
+    let mut config = Config::load();
+    config.sandbox.host = Some("web1".to_string());
+
+    assert_eq!(rate("ufw deny 22", &config), RiskTier::Network);
+    assert_eq!(rate("df -h", &config), RiskTier::Safe);
+    assert_eq!(on_host("ufw deny 22", &config.sandbox), "ssh 'web1' -- 'ufw deny 22'");
+    config.sandbox.host = None;
+    assert_eq!(on_host("ufw deny 22", &config.sandbox), "ufw deny 22");
+}
+
+#[test]
+fn unattended_patterns_choose_the_confirmation_policy() {
+    let unattended = vec!["staging-*".to_string(), "build1".to_string()];
+    assert_eq!(confirm_policy("staging-web", &unattended), HostConfirm::Plan);
+    assert_eq!(confirm_policy("build1", &unattended), HostConfirm::Plan);
+    assert_eq!(confirm_policy("build10", &unattended), HostConfirm::Step);
+    assert_eq!(confirm_policy("prod-web", &[]), HostConfirm::Step);
+}
+
+#[test]
+fn the_matrix_shows_each_step_on_each_host() {
+    let hosts = vec!["web1".to_string(), "web2".to_string()];
+    let mut matrix = StepMatrix::new(2, &hosts);
+    matrix.set(0, "web1", CellStatus::Succeeded);
+    matrix.set(0, "web2", CellStatus::Failed);
+    matrix.set(1, "web1", CellStatus::Running);
+    matrix.set(5, "web1", CellStatus::Failed);
+    matrix.set(0, "web3", CellStatus::Failed);
+
+    assert_eq!(matrix.get(1, "web2"), Some(CellStatus::Pending));
+    assert_eq!(matrix.failed_hosts(), ["web2"]);
+    assert_eq!(
+        matrix.render(),
+        [
+            "        web1     web2",
+            "step 1  ok       failed",
+            "step 2  running  -",
+        ]
+    );
+}
@@ -0,0 +1,59 @@
+use application::network_guard::{
+    cancel_script, network_changes, parse_at_job, revert_plan, Firewall, NetworkChange, Scheduler,
+};
+use std::process::Command;
+
+#[test]
+fn firewall_and_interface_changes_are_found() {
+    assert_eq!(
+        network_changes("sudo ufw allow 22/tcp && sudo ufw --force enable && sudo iptables-restore < rules.v4"),
+        [NetworkChange::Firewall(Firewall::Ufw), NetworkChange::Firewall(Firewall::Iptables)]
+    );
+    assert_eq!(
+        network_changes("sudo nmcli connection down 'Office Wifi'; ifdown eth1"),
+        [
+            NetworkChange::Down {
+                undo: "nmcli connection up 'Office Wifi'".to_string()
+            },
+            NetworkChange::Down {
+                undo: "ifup eth1".to_string()
+            },
+        ]
+    );
+    assert!(matches!(network_changes("ip route del default")[..], [NetworkChange::Other(_)]));
+    for cmd in ["ufw status", "iptables-save > rules", "nft list tables", "firewall-cmd --state", "ip link show", "echo ufw enable"] {
+        assert!(network_changes(cmd).is_empty(), "{cmd}");
+    }
+}
+
+#[test]
+fn revert_plans_save_state_and_undo_in_reverse() {
+    let changes = network_changes("sudo iptables -P INPUT DROP; sudo ip link set eth0 down; sudo ip addr flush dev eth1");
+    let plan = revert_plan(&changes, "/var/tmp/vibe-cli-revert-1").unwrap();
+    assert_eq!(plan.covers, ["iptables rules", "`ip link set dev eth0 up`"]);
+    assert_eq!(plan.uncovered.len(), 1, "address removals can't be undone");
+    assert_eq!(plan.rollback_path(), "/var/tmp/vibe-cli-revert-1/rollback.sh");
+
+    let script = plan.prepare_script();
+    assert!(script.contains("iptables-save > \"$D/iptables.rules\""));
+    let rollback = script.split("<<'VIBE_REVERT_END'\n").nth(1).unwrap();
+    let link_up = rollback.find("ip link set dev eth0 up").unwrap();
+    assert!(link_up < rollback.find("iptables-restore").unwrap(), "the last change is undone first");
+    let syntax = Command::new("bash").args(["-n", "-c", &script]).status().unwrap();
+    assert!(syntax.success());
+
+    assert_eq!(
+        plan.schedule_script(Scheduler::At, 5, "unit"),
+        "echo 'sh /var/tmp/vibe-cli-revert-1/rollback.sh' | at now + 5 minutes 2>&1"
+    );
+    assert_eq!(
+        plan.schedule_script(Scheduler::SystemdRun, 3, "vibe-cli-revert-1"),
+        "systemd-run --quiet --unit=vibe-cli-revert-1 --on-active=3min sh /var/tmp/vibe-cli-revert-1/rollback.sh"
+    );
+    assert_eq!(cancel_script(Scheduler::SystemdRun, "vibe-cli-revert-1"), "systemctl stop vibe-cli-revert-1.timer");
+    assert_eq!(
+        parse_at_job("warning: commands will be executed using /bin/sh\njob 17 at Fri Oct 16 12:05:00 2026\n").as_deref(),
+        Some("17")
+    );
+    assert!(revert_plan(&network_changes("sudo systemctl restart networking"), "/tmp/x").is_none());
+}
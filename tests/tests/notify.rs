@@ -0,0 +1,50 @@
+use infrastructure::config::{Config, NotifyRule};
+use presentation::notify::{self, format_elapsed, summary};
+use std::time::Duration;
+use tests::scratch_dir;
+
+#[test]
+fn notify_rules_are_read_per_mode_with_defaults() {
+    let config = scratch_dir("notify").join("config.toml");
+    std::fs::write(&config, "[notify.agent]\nafter_secs = 120\ndesktop = false\n").unwrap();
+    std::env::set_var("VIBE_CONFIG", &config);
+
+    let notify = Config::load().notify;
+    assert_eq!(
+        notify.agent,
+        NotifyRule {
+            after_secs: 120,
+            desktop: false,
+            bell: true
+        }
+    );
+    assert_eq!(notify.watch, NotifyRule::default());
+    assert_eq!(notify.command.after_secs, 30);
+}
+
+#[test]
+fn only_long_runs_are_announced() {
+    let rule = NotifyRule {
+        after_secs: 60,
+        desktop: false,
+        bell: false,
+    };
+    assert!(!notify::finished(&rule, "step 1", "make", true, Duration::from_secs(600)));
+
+    let bell = NotifyRule { bell: true, ..rule };
+    assert!(!bell.applies(Duration::from_secs(59)));
+    assert!(notify::finished(&bell, "step 1", "make", true, Duration::from_secs(60)));
+}
+
+#[test]
+fn summaries_say_how_the_run_ended() {
+    assert_eq!(format_elapsed(Duration::from_secs(42)), "42s");
+    assert_eq!(
+        summary("step 2", false, Duration::from_secs(65)),
+        "vibe: step 2 failed after 1m05s"
+    );
+    assert_eq!(
+        summary("command", true, Duration::from_secs(3600)),
+        "vibe: command finished after 60m00s"
+    );
+}
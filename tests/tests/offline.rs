@@ -0,0 +1,43 @@
+use application::rag_service::RagService;
+use infrastructure::config::Config;
+use infrastructure::offline;
+use tests::mock_ollama::MockOllama;
+use tests::{fixture_path, isolate_home, scratch_dir};
+
+#[tokio::test]
+async fn offline_mode_searches_the_index_without_calling_the_model() {
+    isolate_home();
+    let mock = MockOllama::start("ok").await;
+    let mut config = Config::load();
+    config.db_path = scratch_dir("offline")
+        .join("embeddings.db")
+        .to_string_lossy()
+        .to_string();
+    let root = fixture_path("sample_project");
+    let service = RagService::new(
+        &root.to_string_lossy(),
+        &config.db_path.clone(),
+        mock.client(),
+        config,
+    )
+    .await
+    .unwrap();
+    service.build_index().await.unwrap();
+    let requests_before = mock.embedding_requests().await + mock.chat_prompts().await.len();
+
+    offline::set_offline(true);
+    let matches = service
+        .search_offline("how is the ArchiveChecksum computed?", 3)
+        .await
+        .unwrap();
+    let answer = service.query("how is the archive checksum computed?").await;
+    offline::set_offline(false);
+
+    assert!(matches[0].contains("archive_checksum"));
+    let error = answer.unwrap_err().to_string();
+    assert!(error.contains("offline mode"), "{error}");
+    assert_eq!(
+        mock.embedding_requests().await + mock.chat_prompts().await.len(),
+        requests_before
+    );
+}
@@ -0,0 +1,91 @@
+use infrastructure::config::Config;
+use presentation::cli::CliApp;
+use shared::cache::CacheLimits;
+use tests::mock_ollama::MockOllama;
+use tests::{fixture, isolate_home, scratch_dir};
+
+#[tokio::test]
+async fn one_shot_strips_code_fences_from_model_reply() {
+    isolate_home();
+    let mock = MockOllama::start(&fixture("responses/one_shot_fenced.txt")).await;
+    let app = CliApp::new().with_client(mock.client());
+
+    let command = app.generate_command("show the five largest folders").await.unwrap();
+
+    assert_eq!(command, "du -sh */ | sort -hr | head -n 5");
+    let prompts = mock.chat_prompts().await;
+    assert_eq!(prompts.len(), 1);
+    assert!(prompts[0].contains("show the five largest folders"));
+}
+
+#[tokio::test]
+async fn refining_sends_every_earlier_suggestion_and_correction() {
+    isolate_home();
+    let mock = MockOllama::start("rg --no-heading -t rust TODO").await;
+    let app = CliApp::new().with_client(mock.client());
+
+    let attempts = [
+        ("grep -rn TODO .".to_string(), "use ripgrep instead".to_string()),
+        ("rg TODO".to_string(), "only rust files".to_string()),
+    ];
+    let command = app.refine_command("find TODO comments", &attempts).await.unwrap();
+
+    assert_eq!(command, "rg --no-heading -t rust TODO");
+    let prompts = mock.chat_prompts().await;
+    assert!(prompts[0].contains("a bash command to: find TODO comments"));
+    assert!(prompts[0].contains(
+        "You suggested: grep -rn TODO .\nThe user replied: use ripgrep instead\n\
+         You suggested: rg TODO\nThe user replied: only rust files"
+    ));
+}
+
+#[tokio::test]
+async fn one_shot_surfaces_server_errors() {
+    isolate_home();
+    let mock = MockOllama::start("unused").await;
+    mock.fail_chat(500, "model not found").await;
+    let app = CliApp::new().with_client(mock.client());
+
+    let err = app.generate_command("list files").await.unwrap_err();
+
+    assert!(err.to_string().contains("model not found"));
+}
+
+#[tokio::test]
+async fn command_cache_matches_exact_and_similar_prompts() {
+    isolate_home();
+    let cache = scratch_dir("cache").join("command_cache.bin");
+    let app = CliApp::new().with_cache_path(cache);
+
+    assert_eq!(app.load_cached("check disk space").unwrap(), None);
+    app.save_cached("check disk space", "```sh\ndf -h\n```").unwrap();
+
+    assert_eq!(app.load_cached("check disk space").unwrap().as_deref(), Some("df -h"));
+    assert_eq!(
+        app.load_cached("check the disk space").unwrap().as_deref(),
+        Some("df -h")
+    );
+    assert_eq!(app.load_cached("restart nginx").unwrap(), None);
+}
+
+#[tokio::test]
+async fn command_cache_replaces_duplicates_and_evicts_least_recently_used() {
+    isolate_home();
+    let cache = scratch_dir("cache").join("command_cache.bin");
+    let mut config = Config::load();
+    config.caches.command = CacheLimits {
+        max_entries: 2,
+        ..CacheLimits::default()
+    };
+    let app = CliApp::new().with_config(config).with_cache_path(cache);
+
+    app.save_cached("check disk space", "df").unwrap();
+    app.save_cached("check disk space", "df -h").unwrap();
+    app.save_cached("restart nginx", "systemctl restart nginx").unwrap();
+    assert_eq!(app.load_cached("check disk space").unwrap().as_deref(), Some("df -h"));
+    app.save_cached("list open ports", "ss -tlnp").unwrap();
+
+    assert_eq!(app.load_cached("restart nginx").unwrap(), None);
+    assert_eq!(app.load_cached("list open ports").unwrap().as_deref(), Some("ss -tlnp"));
+    assert_eq!(app.load_cached("check disk space").unwrap().as_deref(), Some("df -h"));
+}
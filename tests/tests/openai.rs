@@ -0,0 +1,85 @@
+use infrastructure::ollama_client::{ApiStyle, OllamaClient};
+use serde_json::json;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+async fn server() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(header("authorization", "Bearer sk-local"))
+        .and(body_partial_json(json!({ "model": "qwen2.5-7b", "stream": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": "du -sh ." },
+                "finish_reason": "stop"
+            }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/embeddings"))
+        .and(body_partial_json(json!({ "input": "disk usage" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "object": "list",
+            "data": [{ "object": "embedding", "index": 0, "embedding": [0.5, -0.25] }]
+        })))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn client(base_url: String) -> OllamaClient {
+    OllamaClient::with_base_url(base_url, "qwen2.5-7b")
+        .with_api_style(ApiStyle::OpenAi)
+        .with_api_key(Some("sk-local".to_string()))
+}
+
+#[tokio::test]
+async fn chat_and_embeddings_use_the_openai_endpoints() {
+    let server = server().await;
+    let client = client(server.uri());
+
+    assert_eq!(
+        client
+            .generate_response_with_system("show disk usage", "Answer with a command")
+            .await
+            .unwrap(),
+        "du -sh ."
+    );
+    assert_eq!(client.generate_embedding("disk usage").await.unwrap(), [0.5, -0.25]);
+
+    let requests = server.received_requests().await.unwrap();
+    let chat: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(chat["messages"][0]["role"], "system");
+    assert_eq!(chat["messages"][1]["content"], "show disk usage");
+}
+
+#[tokio::test]
+async fn a_base_url_ending_in_v1_is_not_doubled() {
+    let server = server().await;
+    let reply = client(format!("{}/v1/", server.uri()))
+        .generate_response("show disk usage")
+        .await
+        .unwrap();
+    assert_eq!(reply, "du -sh .");
+}
+
+#[tokio::test]
+async fn server_errors_surface_their_message() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "error": { "message": "Invalid API key", "type": "invalid_request_error" }
+        })))
+        .mount(&server)
+        .await;
+
+    let err = client(server.uri()).generate_response("hi").await.unwrap_err();
+    assert!(err.to_string().ends_with("Invalid API key"), "{}", err);
+    assert_eq!(ApiStyle::parse("OpenAI"), Some(ApiStyle::OpenAi));
+}
@@ -0,0 +1,129 @@
+use application::package_preview::{dry_run_command, parse_transaction, PackageManager};
+use infrastructure::config::Config;
+use presentation::runner::preview_packages;
+use tests::{isolate_home, scratch_dir};
+
+fn dry_run(cmd: &str) -> Option<(PackageManager, String)> {
+    dry_run_command(cmd).map(|dry_run| (dry_run.manager, dry_run.to_string()))
+}
+
+#[test]
+fn package_steps_get_their_dry_run_form() {
+    assert_eq!(
+        dry_run("sudo apt update && sudo DEBIAN_FRONTEND=noninteractive apt install -y nginx"),
+        Some((PackageManager::Apt, "apt-get -s install nginx".to_string())),
+        "apt update changes no packages; the dry run needs no root"
+    );
+    assert_eq!(
+        dry_run("sudo dnf remove -y httpd"),
+        Some((PackageManager::Dnf, "sudo -n dnf --assumeno remove httpd".to_string()))
+    );
+    assert_eq!(
+        dry_run("sudo pacman -Syu --noconfirm nginx"),
+        Some((PackageManager::Pacman, "pacman -Sup nginx --print-format '%n %v'".to_string())),
+        "refreshing the databases is left to the real step"
+    );
+    assert_eq!(
+        dry_run("pacman -Rns vim"),
+        Some((PackageManager::Pacman, "pacman -Rnsp vim --print-format '%n %v'".to_string()))
+    );
+    for cmd in ["apt list --installed", "dnf info httpd", "pacman -Ss nginx", "pacman -Qi vim", "echo apt install x"] {
+        assert_eq!(dry_run(cmd), None, "{cmd}");
+    }
+}
+
+#[test]
+fn transactions_are_read_from_dry_run_output() {
+    let apt = "\
+NOTE: This is only a simulation!
+The following NEW packages will be installed:
+  nginx nginx-common
+The following packages will be REMOVED:
+  apache2
+1 upgraded, 2 newly installed, 1 to remove and 0 not upgraded.
+Need to get 0 B/1,234 kB of archives.
+After this operation, 3,210 kB of additional disk space will be used.
+Remv apache2 [2.4.57-2]
+Inst libssl3 [3.0.11-1] (3.0.13-1 Debian:12.5/stable [amd64])
+Inst nginx-common (1.22.1-9 Debian:12.5/stable [all])
+Inst nginx (1.22.1-9 Debian:12.5/stable [amd64])
+Conf nginx (1.22.1-9 Debian:12.5/stable [amd64])
+";
+    let transaction = parse_transaction(PackageManager::Apt, apt, false);
+    assert_eq!(transaction.install, ["nginx-common", "nginx"]);
+    assert_eq!(transaction.upgrade, ["libssl3"]);
+    assert_eq!(transaction.remove, ["apache2"]);
+    assert_eq!(transaction.download.as_deref(), Some("1,234 kB"));
+    assert_eq!(transaction.disk.as_deref(), Some("+3,210 kB"));
+    assert_eq!(
+        transaction.summary(),
+        [
+            "install (2): nginx-common, nginx",
+            "upgrade (1): libssl3",
+            "remove (1): apache2",
+            "download 1,234 kB; disk +3,210 kB",
+        ]
+    );
+
+    let dnf = "\
+Dependencies resolved.
+================================================================================
+ Package             Arch        Version              Repository          Size
+================================================================================
+Installing:
+ nginx               x86_64      1:1.24.0-1.fc39      updates             34 k
+Installing dependencies:
+ nginx-core          x86_64      1:1.24.0-1.fc39      updates            583 k
+Upgrading:
+ openssl-libs        x86_64      1:3.1.1-4.fc39       updates            2.2 M
+
+Transaction Summary
+================================================================================
+Install  2 Packages
+Upgrade  1 Package
+
+Total download size: 2.8 M
+Installed size: 6.1 M
+Operation aborted.
+";
+    let transaction = parse_transaction(PackageManager::Dnf, dnf, false);
+    assert_eq!(transaction.install, ["nginx", "nginx-core"]);
+    assert_eq!(transaction.upgrade, ["openssl-libs"]);
+    assert!(transaction.remove.is_empty());
+    assert_eq!(transaction.download.as_deref(), Some("2.8 M"));
+    assert_eq!(transaction.disk.as_deref(), Some("+6.1 M"));
+
+    let removes = dry_run_command("pacman -Rns vim").unwrap().removes();
+    let transaction = parse_transaction(PackageManager::Pacman, "vim 9.1.0-1\nvim-runtime 9.1.0-1\n", removes);
+    assert_eq!(transaction.remove, ["vim 9.1.0-1", "vim-runtime 9.1.0-1"]);
+    assert!(parse_transaction(PackageManager::Apt, "0 upgraded, 0 newly installed", false).is_empty());
+}
+
+#[test]
+fn dry_runs_are_plain_arguments_without_shell_syntax() {
+    let pacman = dry_run_command("sudo pacman -S --noconfirm 'nginx'").unwrap();
+    assert_eq!(pacman.program, "pacman");
+    assert_eq!(pacman.args, ["-Sp", "nginx", "--print-format", "%n %v"], "quotes are removed, not passed on");
+    assert!(!pacman.removes());
+
+    for cmd in [
+        "sudo apt-get install -y nginx $(touch /tmp/pwned_by_preview)",
+        "sudo dnf install -y `id > /tmp/x` httpd",
+        "apt install nginx > /tmp/out",
+        "apt install \"$PKG\"",
+        "pacman -S <(cat list)",
+    ] {
+        assert_eq!(dry_run(cmd), None, "{cmd}");
+    }
+}
+
+#[test]
+fn previewing_a_step_never_runs_its_substitutions() {
+    isolate_home();
+    let marker = scratch_dir("package_preview_marker").join("pwned");
+    let step = format!("sudo apt-get install -y nginx $(touch {})", marker.display());
+    preview_packages(&step, &Config::load());
+    let quoted = format!("sudo apt-get install -y 'nginx;touch {}'", marker.display());
+    preview_packages(&quoted, &Config::load());
+    assert!(!marker.exists());
+}
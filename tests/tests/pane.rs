@@ -0,0 +1,62 @@
+use presentation::pane::{self, PaneTarget};
+use tests::scratch_dir;
+
+fn args(target: &PaneTarget, text: &str) -> Vec<String> {
+    let command = target.send_command(text);
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn targets_parse_for_tmux_and_screen() {
+    assert_eq!(PaneTarget::parse("work:1.0").unwrap(), PaneTarget::Tmux("work:1.0".into()));
+    assert_eq!(PaneTarget::parse("tmux:%3").unwrap(), PaneTarget::Tmux("%3".into()));
+    assert_eq!(
+        PaneTarget::parse("screen:dev:2").unwrap(),
+        PaneTarget::Screen {
+            session: "dev".into(),
+            window: Some("2".into())
+        }
+    );
+    assert!(PaneTarget::parse("screen:").is_err());
+    assert!(PaneTarget::parse(" ").is_err());
+}
+
+#[test]
+fn commands_are_typed_literally_without_enter() {
+    assert_eq!(
+        args(&PaneTarget::Tmux("2".into()), "-n ls"),
+        ["tmux", "send-keys", "-t", "2", "-l", "--", "-n ls"]
+    );
+    assert_eq!(
+        args(&PaneTarget::parse("screen:dev").unwrap(), "echo $HOME ^C"),
+        ["screen", "-S", "dev", "-X", "stuff", "echo \\$HOME \\^C"]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn send_runs_tmux_and_refuses_multi_line_commands() {
+    use std::os::unix::fs::PermissionsExt;
+    let bin = scratch_dir("pane");
+    let log = bin.join("args.txt");
+    let tool = bin.join("tmux");
+    std::fs::write(
+        &tool,
+        format!("#!/bin/sh\nprintf '%s\\n' \"$@\" > '{}'\n", log.display()),
+    )
+    .unwrap();
+    std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = std::env::var("PATH").unwrap_or_default();
+    std::env::set_var("PATH", format!("{}:{}", bin.display(), path));
+
+    let target = PaneTarget::parse("work:1").unwrap();
+    pane::send(&target, "git status\n").unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&log).unwrap(),
+        "send-keys\n-t\nwork:1\n-l\n--\ngit status\n"
+    );
+    assert!(pane::send(&target, "cd /tmp\nls").is_err());
+}
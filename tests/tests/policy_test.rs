@@ -0,0 +1,41 @@
+use infrastructure::config::Config;
+use infrastructure::policy::Policy;
+use presentation::policy_test::simulate;
+use presentation::safety::RiskTier;
+use tests::{isolate_home, scratch_dir};
+
+fn draft_policy(name: &str, toml: &str) -> Policy {
+    let path = scratch_dir(name).join("policy.toml");
+    std::fs::write(&path, toml).unwrap();
+    Policy::load_from(&path).unwrap().unwrap()
+}
+
+#[test]
+fn every_matching_rule_and_the_verdict_are_reported() {
+    isolate_home();
+    let config = Config::load();
+    let policy = draft_policy("policy_test_draft", "blocked_commands = ['\\bshutdown\\b']\n");
+
+    let blocked = simulate("sudo shutdown -h now", &config, &policy).unwrap();
+    assert_eq!(blocked.tier, Some(RiskTier::Blocked));
+    assert!(blocked
+        .matches
+        .iter()
+        .any(|m| m.check == "policy" && m.detail.contains("shutdown")));
+    assert_eq!(blocked.verdict, "blocked: never run");
+
+    let risky = simulate("chmod 777 shared && ufw enable", &config, &policy).unwrap();
+    let checks: Vec<&str> = risky.matches.iter().map(|m| m.check).collect();
+    assert_eq!(checks, ["warning", "network", "mutation"]);
+    assert_eq!(risky.tier, Some(RiskTier::Network));
+    assert!(risky.verdict.starts_with("asks for confirmation (Enter means no)"));
+
+    let safe = simulate("ls -la", &config, &Policy::default()).unwrap();
+    assert!(safe.matches.is_empty());
+    assert_eq!(safe.tier, Some(RiskTier::Safe));
+    assert_eq!(safe.verdict, "asks for confirmation (Enter means yes)");
+
+    let broken = simulate("echo 'oops", &config, &policy).unwrap();
+    assert_eq!(broken.tier, None);
+    assert_eq!(broken.matches[0].check, "syntax");
+}
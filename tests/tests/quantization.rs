@@ -0,0 +1,93 @@
+use infrastructure::embedding_storage::QuantizedEntry;
+use infrastructure::quantization::{Quantization, QuantizedVector};
+use infrastructure::search::SearchEngine;
+use std::collections::HashSet;
+
+const DIMS: usize = 64;
+
+/// Deterministic vectors in [-1, 1), so the test needs no random seed.
+fn vectors(count: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    };
+    (0..count).map(|_| (0..DIMS).map(|_| next()).collect()).collect()
+}
+
+fn entries(stored: &[Vec<f32>], quantization: Quantization) -> Vec<QuantizedEntry> {
+    stored
+        .iter()
+        .enumerate()
+        .map(|(i, vector)| QuantizedEntry {
+            id: i.to_string(),
+            vector: QuantizedVector::encode(vector, quantization).unwrap(),
+        })
+        .collect()
+}
+
+/// Ids of the `k` stored vectors most similar to `query`.
+fn exact_top(query: &[f32], stored: &[Vec<f32>], k: usize) -> Vec<String> {
+    let mut scored: Vec<(f32, usize)> = stored
+        .iter()
+        .enumerate()
+        .map(|(i, vector)| (SearchEngine::cosine_similarity(query, vector), i))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().take(k).map(|(_, i)| i.to_string()).collect()
+}
+
+/// The share of the exact top 10 among the coarse stage's 40 candidates,
+/// the rescore factor searches use.
+fn recall(quantization: Quantization) -> f32 {
+    let stored = vectors(500, 1);
+    let entries = entries(&stored, quantization);
+    let queries = vectors(20, 2);
+    let found: usize = queries
+        .iter()
+        .map(|query| {
+            let candidates: HashSet<String> =
+                SearchEngine::find_candidate_ids(query, &entries, 40).into_iter().collect();
+            exact_top(query, &stored, 10).iter().filter(|id| candidates.contains(*id)).count()
+        })
+        .sum();
+    found as f32 / (queries.len() * 10) as f32
+}
+
+#[test]
+fn int8_codes_stay_within_half_a_step_of_the_vector() {
+    for vector in vectors(50, 3).into_iter().chain([vec![0.0; DIMS]]) {
+        let Some(QuantizedVector::Int8 { scale, norm, values }) = QuantizedVector::encode(&vector, Quantization::Int8)
+        else {
+            panic!("int8 quantization gives int8 codes");
+        };
+        assert_eq!(values.len(), vector.len());
+        for (&code, &x) in values.iter().zip(&vector) {
+            let restored = code as f32 * scale;
+            assert!((restored - x).abs() <= scale / 2.0 + 1e-6, "{x} came back as {restored}");
+        }
+        let exact_norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - exact_norm).abs() <= scale * (DIMS as f32).sqrt());
+    }
+}
+
+#[test]
+fn binary_codes_keep_each_sign() {
+    let vector = vectors(1, 4).remove(0);
+    let Some(QuantizedVector::Binary { dims, bits }) = QuantizedVector::encode(&vector, Quantization::Binary) else {
+        panic!("binary quantization gives sign bits");
+    };
+    assert_eq!(dims, DIMS);
+    for (i, x) in vector.iter().enumerate() {
+        assert_eq!(bits[i / 64] >> (i % 64) & 1 == 1, *x > 0.0, "dimension {i}");
+    }
+    assert!(QuantizedVector::encode(&vector, Quantization::None).is_none());
+}
+
+#[test]
+fn candidates_keep_the_exact_top_results() {
+    let int8 = recall(Quantization::Int8);
+    let binary = recall(Quantization::Binary);
+    assert!(int8 >= 0.98, "int8 recall {int8}");
+    assert!(binary >= 0.6, "binary recall {binary}");
+}
@@ -0,0 +1,142 @@
+use application::rag_service::RagService;
+use application::recall_service::{age, MemoryKind, RecallService};
+use domain::models::Memory;
+use infrastructure::config::Config;
+use infrastructure::embedding_storage::EmbeddingStorage;
+use infrastructure::index_bundle;
+use presentation::cli::CliApp;
+use tests::mock_ollama::MockOllama;
+use tests::{fixture_path, isolate_home, scratch_dir};
+
+fn app_with_db(mock: &MockOllama, name: &str) -> (CliApp, String) {
+    let mut config = Config::load();
+    config.db_path = scratch_dir(name)
+        .join("embeddings.db")
+        .to_string_lossy()
+        .to_string();
+    config.remember = true;
+    let db_path = config.db_path.clone();
+    (CliApp::new().with_client(mock.client()).with_config(config), db_path)
+}
+
+#[tokio::test]
+async fn earlier_answers_are_recalled_most_similar_first() {
+    isolate_home();
+    let mock = MockOllama::start("You put nginx in front of the app on port 8080 [1].").await;
+    let (app, db_path) = app_with_db(&mock, "recall");
+
+    app.remember(
+        MemoryKind::Chat,
+        "set up a reverse proxy for the app",
+        "Suggested command: sudo apt install nginx && sudo nginx -t",
+    )
+    .await;
+    app.remember(
+        MemoryKind::Rag,
+        "how does the reverse proxy reach the app?",
+        "The nginx reverse proxy forwards to the app on port 8080.",
+    )
+    .await;
+    app.remember(MemoryKind::Explain, "Explanation of backup.sh", "Copies the database to S3 nightly.")
+        .await;
+    app.remember(
+        MemoryKind::Rag,
+        "how does the reverse proxy reach the app?",
+        "The nginx reverse proxy forwards to the app on port 8080.",
+    )
+    .await;
+
+    let storage = EmbeddingStorage::new(&db_path).await.unwrap();
+    assert_eq!(storage.get_memories().await.unwrap().len(), 3, "repeats are kept once");
+    assert_eq!(storage.stats().await.unwrap().chunks, 0, "memories are not code chunks");
+
+    let recollection = app.recall("how did I set up the reverse proxy").await.unwrap();
+    assert_eq!(recollection.answer, "You put nginx in front of the app on port 8080 [1].");
+    let kinds: Vec<&str> = recollection.sources.iter().map(|m| m.kind.as_str()).collect();
+    assert_eq!(kinds[..2], ["rag", "chat"]);
+    let prompt = mock.chat_prompts().await.pop().unwrap();
+    assert!(prompt.contains("[1] rag (today):\nhow does the reverse proxy reach the app?"));
+    assert!(prompt.contains("Question: how did I set up the reverse proxy"));
+}
+
+#[tokio::test]
+async fn nothing_is_asked_of_the_model_without_memories() {
+    isolate_home();
+    let mock = MockOllama::start("unused").await;
+    let (app, db_path) = app_with_db(&mock, "recall_empty");
+
+    let recollection = app.recall("what did we decide about backups").await.unwrap();
+    assert!(recollection.sources.is_empty());
+    assert!(recollection.answer.starts_with("Nothing from earlier sessions"));
+
+    let mut config = Config::load();
+    config.db_path = db_path.clone();
+    config.remember = false;
+    let quiet = CliApp::new().with_client(mock.client()).with_config(config);
+    quiet.remember(MemoryKind::Chat, "list files", "Suggested command: ls").await;
+    let storage = EmbeddingStorage::new(&db_path).await.unwrap();
+    assert!(storage.get_memories().await.unwrap().is_empty());
+    assert!(mock.chat_prompts().await.is_empty());
+    assert_eq!(mock.embedding_requests().await, 0);
+}
+
+#[tokio::test]
+async fn bundles_share_the_index_but_not_memories() {
+    isolate_home();
+    let mock = MockOllama::start("unused").await;
+    let dir = scratch_dir("recall_bundle");
+    let mut config = Config::load();
+    config.db_path = dir.join("embeddings.db").to_string_lossy().to_string();
+    RagService::new(
+        &fixture_path("sample_project").to_string_lossy(),
+        &config.db_path.clone(),
+        mock.client(),
+        config.clone(),
+    )
+    .await
+    .unwrap()
+    .build_index()
+    .await
+    .unwrap();
+    RecallService::new(&config.db_path, mock.client())
+        .await
+        .unwrap()
+        .remember(MemoryKind::Rag, "where is the staging password?", "In the team vault.")
+        .await
+        .unwrap();
+
+    let bundle = dir.join("index.tar.zst");
+    index_bundle::export(std::path::Path::new(&config.db_path), "mock-model", &bundle)
+        .await
+        .unwrap();
+
+    let target = dir.join("teammate.db");
+    let own = EmbeddingStorage::new(&target).await.unwrap();
+    own.insert_memory(Memory {
+        id: "own".to_string(),
+        kind: "chat".to_string(),
+        text: "list files\n\nSuggested command: ls".to_string(),
+        vector: vec![1.0, 0.0],
+        created_at: 1,
+    })
+    .await
+    .unwrap();
+    drop(own);
+    index_bundle::import(&bundle, &target, "mock-model", false)
+        .await
+        .unwrap();
+
+    let imported = EmbeddingStorage::new(&target).await.unwrap();
+    assert!(imported.stats().await.unwrap().chunks > 0);
+    let memories = imported.get_memories().await.unwrap();
+    let ids: Vec<&str> = memories.iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(ids, ["own"], "the exporter's memories stay behind, the importer's are kept");
+}
+
+#[test]
+fn ages_are_counted_in_days() {
+    let now = 100 * 86_400;
+    assert_eq!(age(now - 60, now), "today");
+    assert_eq!(age(now - 86_400, now), "yesterday");
+    assert_eq!(age(now - 10 * 86_400, now), "10 days ago");
+}
@@ -0,0 +1,101 @@
+use application::review_service::{parse_comments, split_diff, Severity};
+use presentation::cli::CliApp;
+use tests::mock_ollama::MockOllama;
+
+const DIFF: &str = "\
+diff --git a/src/backup.rs b/src/backup.rs
+index 1111111..2222222 100644
+--- a/src/backup.rs
++++ b/src/backup.rs
+@@ -10,4 +10,4 @@ fn prune(dir: &Path) {
+     let keep = retention_days();
+-    let cutoff = now() - keep;
++    let cutoff = now() - keep * 86_400;
+     remove_older_than(dir, cutoff);
+ }
+@@ -40,2 +40,3 @@ fn retention_days() -> u64 {
+-    30
++    std::env::var(\"KEEP\").unwrap().parse().unwrap()
++    // fallback
+ }
+diff --git a/schema.sql b/schema.sql
+--- a/schema.sql
++++ b/schema.sql
+@@ -1,2 +1,1 @@
+--- legacy table
+ CREATE TABLE backups (id INTEGER);
+diff --git a/logo.png b/logo.png
+Binary files a/logo.png and b/logo.png differ
+diff --git a/old.txt b/old.txt
+deleted file mode 100644
+--- a/old.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+";
+
+#[test]
+fn diffs_split_into_files_and_hunks_with_new_line_numbers() {
+    let files = split_diff(DIFF);
+    let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, ["src/backup.rs", "schema.sql", "old.txt"], "binary files have no hunks");
+
+    let backup = &files[0];
+    assert_eq!(backup.hunks.len(), 2);
+    assert!(backup.covers(11) && backup.covers(42) && !backup.covers(20));
+    let numbered = backup.hunks[1].numbered();
+    assert!(numbered.contains("    40 +    std::env::var"));
+    assert!(numbered.contains("    41 +    // fallback"));
+
+    assert_eq!(files[1].hunks[0].text.lines().count(), 3, "`--- legacy table` is a removed line");
+}
+
+#[test]
+fn model_comments_are_checked_against_the_hunks() {
+    let files = split_diff(DIFF);
+    let reply = "Here you go:\n[\
+        {\"severity\": \"critical\", \"line\": 40, \"comment\": \"Panics when KEEP is unset.\", \"suggestion\": \"std::env::var(\\\"KEEP\\\").ok().and_then(|v| v.parse().ok()).unwrap_or(30)\"},\
+        {\"severity\": \"whatever\", \"line\": 99, \"message\": \"Line outside the diff.\"},\
+        {\"severity\": \"nit\", \"line\": 41, \"comment\": \"  \"}]";
+    let comments = parse_comments(reply, &files[0]);
+    assert_eq!(comments.len(), 2, "empty comments are dropped");
+    assert_eq!(comments[0].severity, Severity::Critical);
+    assert_eq!(comments[0].location(), "src/backup.rs:40");
+    assert!(comments[0].suggestion.as_deref().unwrap().contains("unwrap_or(30)"));
+    assert_eq!(comments[1].severity, Severity::Minor);
+    assert_eq!(comments[1].location(), "src/backup.rs");
+
+    assert!(parse_comments("Looks good to me.", &files[0]).is_empty());
+}
+
+#[tokio::test]
+async fn each_file_is_reviewed_and_comments_sorted_by_severity() {
+    let mock = MockOllama::start("[]").await;
+    mock.reply_when(
+        "change to schema.sql",
+        "[{\"severity\": \"nit\", \"line\": 1, \"comment\": \"Name the table in the singular.\"}]",
+    )
+    .await;
+    mock.reply_when(
+        "change to src/backup.rs",
+        "[{\"severity\": \"major\", \"line\": 11, \"comment\": \"Unit mismatch.\", \"suggestion\": null}]",
+    )
+    .await;
+    let app = CliApp::new().with_client(mock.client());
+
+    let comments = app.review_diff(DIFF, false).await.unwrap();
+
+    let prompts = mock.chat_prompts().await;
+    assert_eq!(prompts.len(), 3, "one call per file");
+    assert!(prompts[0].contains("    11 +    let cutoff = now() - keep * 86_400;"));
+    assert!(!prompts[0].contains("schema.sql"), "files are reviewed separately");
+
+    let json = serde_json::to_value(&comments).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {"severity": "major", "file": "src/backup.rs", "line": 11, "message": "Unit mismatch."},
+            {"severity": "nit", "file": "schema.sql", "line": 1, "message": "Name the table in the singular."},
+        ])
+    );
+}
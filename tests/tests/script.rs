@@ -0,0 +1,155 @@
+use presentation::cli::CliApp;
+use presentation::script_output::{backup_path, write_script, ScriptWritten, WriteMode};
+use presentation::script_params;
+use presentation::script_preflight;
+use std::path::Path;
+use tests::mock_ollama::MockOllama;
+use tests::{isolate_home, scratch_dir};
+
+const OLD: &str = "#!/bin/bash\necho old\n";
+const NEW: &str = "#!/bin/bash\necho new\n";
+
+#[tokio::test]
+async fn generated_scripts_come_back_without_fences() {
+    isolate_home();
+    let mock = MockOllama::start("```bash\n#!/bin/bash\ndf -h\n```").await;
+    let app = CliApp::new().with_client(mock.client());
+
+    let script = app.generate_script("report disk usage").await.unwrap();
+
+    assert_eq!(script, "#!/bin/bash\ndf -h\n");
+    assert!(mock.chat_prompts().await[0].contains("report disk usage"));
+}
+
+#[test]
+fn existing_scripts_are_only_overwritten_after_confirming_the_diff() {
+    let path = scratch_dir("script").join("clean.sh");
+    let never = |_: &str| -> shared::types::Result<bool> { panic!("nothing to confirm") };
+
+    let written = write_script(&path, OLD, WriteMode::Overwrite, false, 80, never).unwrap();
+    assert_eq!(written, ScriptWritten::Created);
+    let written = write_script(&path, OLD, WriteMode::Overwrite, false, 80, never).unwrap();
+    assert_eq!(written, ScriptWritten::Unchanged);
+
+    let mut preview = String::new();
+    let written = write_script(&path, NEW, WriteMode::Overwrite, false, 80, |diff| {
+        preview = diff.to_string();
+        Ok(false)
+    })
+    .unwrap();
+    assert_eq!(written, ScriptWritten::Kept);
+    assert!(preview.contains("echo old") && preview.contains("echo new"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), OLD);
+
+    let written = write_script(&path, NEW, WriteMode::Overwrite, true, 80, |_| Ok(true)).unwrap();
+    let ScriptWritten::Overwritten { backup: Some(backup) } = written else {
+        panic!("expected a backup, got {written:?}");
+    };
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), NEW);
+    assert_eq!(std::fs::read_to_string(backup).unwrap(), OLD);
+}
+
+#[test]
+fn appending_keeps_the_existing_script_and_one_shebang() {
+    let path = scratch_dir("script").join("clean.sh");
+    std::fs::write(&path, OLD).unwrap();
+
+    let written = write_script(&path, NEW, WriteMode::Append, false, 80, |_| {
+        panic!("appending needs no confirmation")
+    })
+    .unwrap();
+
+    assert_eq!(written, ScriptWritten::Appended { backup: None });
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "#!/bin/bash\necho old\n\necho new\n"
+    );
+}
+
+#[test]
+fn backups_are_named_with_a_utc_timestamp() {
+    assert_eq!(
+        backup_path(Path::new("deploy.sh"), 1_792_229_400),
+        Path::new("deploy.sh.20261017T093000Z.bak")
+    );
+}
+
+#[tokio::test]
+async fn with_args_turns_placeholders_into_getopts_options() {
+    isolate_home();
+    let mock = MockOllama::start("#!/bin/bash\nset -e\ncp -r \"$SRC\" \"$BACKUP_DIR\"").await;
+    let app = CliApp::new().with_client(mock.client());
+    let request = "copy <src> into {backup-dir}, skipping files over 5 < 6 MB";
+    let names = script_params::placeholders(request);
+    assert_eq!(names, ["src", "backup-dir"]);
+    let params = script_params::params(&names);
+
+    let script = app
+        .generate_parameterized_script(request, &params, true)
+        .await
+        .unwrap();
+
+    assert!(script.starts_with("#!/usr/bin/env bash\nset -euo pipefail\n"));
+    assert!(script.contains("while getopts \":s:b:h\" opt; do"));
+    assert!(script.contains("        b) BACKUP_DIR=\"$OPTARG\" ;;"));
+    assert!(script.contains("[ -n \"$SRC\" ] || usage"));
+    assert!(script.ends_with("[ -n \"$BACKUP_DIR\" ] || usage\n\ncp -r \"$SRC\" \"$BACKUP_DIR\"\n"));
+    let prompt = &mock.chat_prompts().await[0];
+    assert!(prompt.contains("copy $SRC into $BACKUP_DIR"));
+}
+
+#[test]
+fn preflight_finds_the_commands_a_script_runs() {
+    let script = r#"#!/bin/bash
+set -euo pipefail
+usage() { echo "usage"; }
+if grep -q foo "$FILE"; then
+    rsync -a "${SRC}/" dest | tee log.txt  # copy; then report
+fi
+count=$(find . -name '*.rs; rm -rf /' | wc -l)
+echo "total: $((count + 1)) | not a command"
+case "$opt" in
+    s) jq . file ;;
+esac
+cat <<END
+apt install nothing
+END
+sudo apt-get install -y curl
+"#;
+
+    assert_eq!(
+        script_preflight::required_binaries(script),
+        ["grep", "rsync", "tee", "find", "wc", "jq", "cat", "apt-get"]
+    );
+    assert_eq!(
+        script_preflight::find_version("rsync  version 3.2.7  protocol version 31"),
+        Some("3.2.7".to_string())
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn preflight_stops_scripts_missing_a_command() {
+    let system_info = "Distro: ubuntu, Version: 24.04, Kernel: 6.8.0";
+    let run = |body: &str| {
+        let script = script_preflight::with_preflight(
+            &format!("#!/bin/bash\nset -euo pipefail\n{body}\n"),
+            system_info,
+        );
+        std::process::Command::new("bash")
+            .arg("-c")
+            .arg(&script)
+            .output()
+            .unwrap()
+    };
+
+    let ok = run("cargo --version >/dev/null && echo ran");
+    assert!(ok.status.success(), "{}", String::from_utf8_lossy(&ok.stderr));
+    assert_eq!(String::from_utf8_lossy(&ok.stdout), "ran\n");
+
+    let missing = run("vibe-no-such-tool --sync && echo ran");
+    assert_eq!(missing.status.code(), Some(1));
+    assert!(missing.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&missing.stderr)
+        .contains("missing required commands: vibe-no-such-tool"));
+}
@@ -0,0 +1,66 @@
+use application::service_impact::{
+    connection_count, interrupted_services, listening_ports, ServiceAction, ServiceImpact,
+};
+use std::collections::BTreeSet;
+
+fn action(unit: &str, verb: &str) -> ServiceAction {
+    ServiceAction {
+        unit: unit.to_string(),
+        verb: verb.to_string(),
+    }
+}
+
+#[test]
+fn restarted_and_stopped_services_are_found() {
+    assert_eq!(
+        interrupted_services("sudo nginx -t && sudo systemctl restart nginx php8.2-fpm.service; sudo service redis-server stop"),
+        [
+            action("nginx.service", "restart"),
+            action("php8.2-fpm.service", "restart"),
+            action("redis-server.service", "stop"),
+        ]
+    );
+    assert_eq!(
+        interrupted_services("sudo -u root systemctl stop nginx"),
+        [action("nginx.service", "stop")]
+    );
+    for cmd in ["systemctl status nginx", "systemctl reload nginx", "systemctl restart 'getty@*'", "echo systemctl restart nginx"] {
+        assert!(interrupted_services(cmd).is_empty(), "{cmd}");
+    }
+}
+
+#[test]
+fn ports_and_connections_are_matched_to_the_service() {
+    let pids: BTreeSet<u32> = [1234, 1235, 1236].into();
+    let listening = "\
+tcp   LISTEN 0      511          0.0.0.0:443       0.0.0.0:*    users:((\"nginx\",pid=1235,fd=8),(\"nginx\",pid=1234,fd=8))
+tcp   LISTEN 0      511          0.0.0.0:80        0.0.0.0:*    users:((\"nginx\",pid=1235,fd=6),(\"nginx\",pid=1234,fd=6))
+tcp   LISTEN 0      511             [::]:80           [::]:*    users:((\"nginx\",pid=1234,fd=7))
+udp   UNCONN 0      0          127.0.0.1:8125      0.0.0.0:*    users:((\"nginx\",pid=1236,fd=3))
+tcp   LISTEN 0      128          0.0.0.0:22        0.0.0.0:*    users:((\"sshd\",pid=812,fd=3))
+";
+    assert_eq!(listening_ports(listening, &pids), [":80", ":443", ":8125/udp"]);
+    let established = "\
+tcp   0      0      10.0.0.5:443     203.0.113.9:51234  users:((\"nginx\",pid=1235,fd=12))
+tcp   0      0      10.0.0.5:443     203.0.113.7:40112  users:((\"nginx\",pid=1235,fd=13))
+tcp   0      0      10.0.0.5:22      203.0.113.9:60022  users:((\"sshd\",pid=9001,fd=4))
+";
+    assert_eq!(connection_count(established, &pids), 2);
+
+    let impact = ServiceImpact {
+        unit: "nginx.service".to_string(),
+        state: "active (running)".to_string(),
+        running: true,
+        processes: 3,
+        ports: vec![":80".to_string(), ":443".to_string()],
+        connections: 2,
+        sockets_hidden: false,
+    };
+    assert_eq!(impact.summary(), "nginx currently serving on :80/:443, 3 processes, 2 open connection(s)");
+    let stopped = ServiceImpact {
+        running: false,
+        state: "inactive (dead)".to_string(),
+        ..impact
+    };
+    assert_eq!(stopped.summary(), "nginx is not running (inactive (dead))");
+}
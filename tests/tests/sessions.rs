@@ -0,0 +1,59 @@
+use application::rag_service::RagConversation;
+use domain::session::{Session, SessionRepository};
+use infrastructure::session_store::JsonSessionRepository;
+use tests::scratch_dir;
+
+#[test]
+fn sessions_are_created_listed_switched_and_deleted() {
+    let sessions = JsonSessionRepository::new(scratch_dir("sessions_crud"));
+    assert!(sessions.list().unwrap().is_empty());
+    assert_eq!(sessions.active().unwrap(), None);
+
+    sessions.create("work").unwrap();
+    let mut home = sessions.create("home").unwrap();
+    assert!(sessions.create("work").is_err(), "ids are unique");
+    assert!(sessions.create("../escape").is_err());
+    assert!(sessions.set_active(Some("missing")).is_err());
+
+    home.add_exchange("chat", "list big files", "du -ah . | sort -rh | head");
+    home.updated_at += 10;
+    sessions.save(&home).unwrap();
+    sessions.set_active(Some("home")).unwrap();
+
+    let ids: Vec<String> = sessions.list().unwrap().into_iter().map(|s| s.id).collect();
+    assert_eq!(ids, ["home", "work"]);
+    assert_eq!(sessions.active().unwrap().as_deref(), Some("home"));
+    let loaded = sessions.load("home").unwrap().unwrap();
+    assert_eq!(
+        loaded.exchanges("chat"),
+        [("list big files".to_string(), "du -ah . | sort -rh | head".to_string())]
+    );
+
+    assert!(sessions.delete("home").unwrap());
+    assert!(!sessions.delete("home").unwrap());
+    assert_eq!(sessions.active().unwrap(), None, "deleting the active session clears it");
+    assert!(sessions.load("home").unwrap().is_none());
+}
+
+#[test]
+fn history_keeps_each_modes_exchanges_apart() {
+    let mut session = Session::new("s1".into());
+    session.add_exchange("rag", "what does the indexer do?", "It embeds chunks.");
+    session.add_exchange("agent", "clean the logs", "journalctl --vacuum-time=7d");
+    session.add_exchange("rag", "and the storage?", "SQLite.");
+    session.add_message("note", "unrelated");
+
+    assert_eq!(
+        session.exchanges("rag"),
+        [
+            ("what does the indexer do?".to_string(), "It embeds chunks.".to_string()),
+            ("and the storage?".to_string(), "SQLite.".to_string()),
+        ]
+    );
+    assert_eq!(session.exchanges("agent").len(), 1);
+
+    let saved = serde_json::to_string(&RagConversation::default()).unwrap();
+    session.set_context("rag", saved);
+    let restored: RagConversation = serde_json::from_str(&session.context["rag"]).unwrap();
+    assert!(restored.is_empty());
+}
@@ -0,0 +1,36 @@
+use application::plan_parsing::parse_plan_steps;
+use presentation::step_budget::{parse_answer, Budget, Overrun};
+use std::time::Duration;
+
+#[test]
+fn plan_steps_carry_their_expected_duration() {
+    let steps = parse_plan_steps(
+        r#"[{"command": "rsync -a /srv/ backup:/srv/", "expected_seconds": 1800}, {"command": "df -h", "expected_seconds": 0}]"#,
+    );
+    assert_eq!(steps[0].expected_secs, Some(1800));
+    assert_eq!(steps[0].to_string(), "rsync -a /srv/ backup:/srv/ (expected: 1800s)");
+    assert_eq!(steps[1].expected_secs, None, "a zero budget is no budget");
+}
+
+#[test]
+fn the_user_is_asked_well_past_the_expected_duration() {
+    let secs = Duration::from_secs;
+    let mut budget = Budget::new(secs(60));
+    assert_eq!(budget.remaining(secs(0)), secs(120), "twice as long as expected");
+    assert_eq!(budget.remaining(secs(150)), Duration::ZERO);
+
+    budget.extend(secs(150));
+    assert_eq!(budget.remaining(secs(150)), secs(150), "asked again after as long again");
+
+    assert_eq!(Budget::new(secs(2)).remaining(secs(0)), secs(12), "short steps get some grace");
+}
+
+#[test]
+fn overrun_answers_are_parsed() {
+    assert_eq!(parse_answer("\n"), Some(Overrun::Wait));
+    assert_eq!(parse_answer("W"), Some(Overrun::Wait));
+    assert_eq!(parse_answer("b"), Some(Overrun::Background));
+    assert_eq!(parse_answer("background"), Some(Overrun::Background));
+    assert_eq!(parse_answer("kill"), Some(Overrun::Kill));
+    assert_eq!(parse_answer("maybe"), None);
+}
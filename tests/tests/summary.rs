@@ -0,0 +1,25 @@
+use application::summary_service::SummaryService;
+use tests::isolate_home;
+use tests::mock_ollama::MockOllama;
+
+#[tokio::test]
+async fn long_output_is_summarized_per_chunk_then_merged() {
+    isolate_home();
+    let mock = MockOllama::start("nothing notable").await;
+    mock.reply_when("Merge them into one short summary", "disk full on /var at 03:12")
+        .await;
+    let output: String = (0..2000)
+        .map(|i| format!("Oct 17 03:{:02} host app[42]: heartbeat ok seq={}\n", i % 60, i))
+        .collect();
+
+    let summary = SummaryService::new(mock.client())
+        .summarize_output("journalctl -u app", &output)
+        .await
+        .unwrap();
+
+    assert_eq!(summary, "disk full on /var at 03:12");
+    let prompts = mock.chat_prompts().await;
+    assert!(prompts.len() > 2);
+    assert!(prompts[0].contains("part 1 of"));
+    assert!(prompts.last().unwrap().contains("Part 1:\nnothing notable"));
+}
@@ -0,0 +1,86 @@
+use domain::models::Embedding;
+use infrastructure::embedding_storage::EmbeddingStorage;
+use infrastructure::vector_cache::VectorMatrixCache;
+use tests::scratch_dir;
+
+fn vectors() -> Vec<(String, Vec<f32>)> {
+    vec![
+        ("north".to_string(), vec![0.0, 1.0, 0.0]),
+        ("east".to_string(), vec![1.0, 0.0, 0.0]),
+        ("north-east".to_string(), vec![0.7, 0.7, 0.0]),
+    ]
+}
+
+fn ids(top: Vec<(f32, String)>) -> Vec<String> {
+    top.into_iter().map(|(_, id)| id).collect()
+}
+
+#[test]
+fn written_matrices_open_with_their_rows() {
+    let path = scratch_dir("vector_cache").join("db.vectors");
+    VectorMatrixCache::write(&path, 7, &vectors()).unwrap();
+
+    let cache = VectorMatrixCache::open(&path).unwrap();
+
+    assert_eq!(cache.generation(), 7);
+    assert_eq!(ids(cache.top_ids(&[1.0, 0.1, 0.0], 2)), ["east", "north-east"]);
+    assert_eq!(ids(cache.top_ids(&[0.0, 2.0, 0.0], 1)), ["north"]);
+    assert!(cache.top_ids(&[1.0, 0.0], 3).is_empty(), "a query of other dimensions matches nothing");
+}
+
+#[test]
+fn corrupt_matrices_do_not_open() {
+    let dir = scratch_dir("vector_cache_corrupt");
+    let path = dir.join("db.vectors");
+    VectorMatrixCache::write(&path, 1, &vectors()).unwrap();
+    let written = std::fs::read(&path).unwrap();
+
+    let mut huge_rows = written.clone();
+    huge_rows[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+    let mut huge_dims = written.clone();
+    huge_dims[24..32].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+    let mut trailing = written.clone();
+    trailing.extend_from_slice(b"junk");
+    for (name, bytes) in [
+        ("huge rows", huge_rows),
+        ("huge dims", huge_dims),
+        ("truncated", written[..written.len() - 4].to_vec()),
+        ("trailing bytes", trailing),
+        ("header only", written[..32].to_vec()),
+    ] {
+        std::fs::write(&path, bytes).unwrap();
+        assert!(VectorMatrixCache::open(&path).is_err(), "{name}");
+    }
+}
+
+#[tokio::test]
+async fn a_corrupt_sidecar_is_rebuilt_from_the_database() {
+    let dir = scratch_dir("vector_cache_rebuild");
+    let storage = EmbeddingStorage::new(dir.join("embeddings.db")).await.unwrap();
+    let embeddings = vectors()
+        .into_iter()
+        .map(|(id, vector)| Embedding {
+            text: id.clone(),
+            path: "compass.txt".to_string(),
+            id,
+            vector,
+            start_offset: 0,
+            language: String::new(),
+            ordinal: 0,
+            chunk_hash: String::new(),
+            cell: None,
+        })
+        .collect();
+    storage.insert_embeddings(embeddings).await.unwrap();
+    let sidecar = VectorMatrixCache::sidecar_path(storage.db_path());
+
+    let built = VectorMatrixCache::load(&storage).await.unwrap();
+    assert_eq!(ids(built.top_ids(&[0.0, 1.0, 0.0], 1)), ["north"]);
+    let mut bytes = std::fs::read(&sidecar).unwrap();
+    bytes[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+    std::fs::write(&sidecar, bytes).unwrap();
+
+    let rebuilt = VectorMatrixCache::load(&storage).await.unwrap();
+    assert_eq!(ids(rebuilt.top_ids(&[0.0, 1.0, 0.0], 1)), ["north"]);
+    assert!(VectorMatrixCache::open(&sidecar).is_ok(), "the sidecar was written again");
+}